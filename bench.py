@@ -0,0 +1,92 @@
+#!/usr/bin/env python3
+
+# Builds `crates/wasm_split_bench`, splits it, and runs its benchmarks under
+# Node, writing the results to `bench-results.json`, or the given file. The
+# benchmarks and the format of the results are described in `bench.mjs`.
+#
+#     ./bench.py                      # writes bench-results.json
+#     ./bench.py --out results.json
+
+import argparse
+import json
+import os
+import shutil
+import subprocess
+
+ap = argparse.ArgumentParser()
+ap.add_argument("--out", default="bench-results.json")
+args = ap.parse_args()
+
+root_dir = os.path.dirname(os.path.abspath(__file__))
+bench_dir = os.path.join(root_dir, "crates", "wasm_split_bench")
+
+json_results = subprocess.run(
+    [
+        "cargo",
+        "build",
+        "--package",
+        "wasm_split_bench",
+        "--target",
+        "wasm32-unknown-unknown",
+        "--release",
+        "--message-format=json-render-diagnostics",
+    ],
+    stdout=subprocess.PIPE,
+    cwd=root_dir,
+    env={**os.environ, "RUSTFLAGS": "-Clink-args=--emit-relocs"},
+    check=True,
+).stdout.splitlines()
+
+target_path = None
+for json_result in json_results:
+    msg = json.loads(json_result)
+    if msg.get("target", {}).get("name") == "wasm_split_bench":
+        target_path = next(
+            (name for name in msg.get("filenames", []) if name.endswith(".wasm")),
+            target_path,
+        )
+assert target_path is not None
+
+split_dir = os.path.join(root_dir, "target", "bench_split")
+pkg_dir = os.path.join(root_dir, "target", "bench_pkg")
+shutil.rmtree(split_dir, ignore_errors=True)
+shutil.rmtree(pkg_dir, ignore_errors=True)
+
+subprocess.run(
+    [
+        "cargo",
+        "run",
+        "--release",
+        "--package",
+        "wasm_split_cli",
+        "--",
+        target_path,
+        split_dir,
+        # The benchmarks measure chunks of any size, including tiny ones.
+        "--fold-threshold",
+        "0",
+    ],
+    cwd=root_dir,
+    check=True,
+)
+subprocess.run(
+    [
+        "wasm-bindgen",
+        os.path.join(split_dir, "main.wasm"),
+        "--out-dir",
+        pkg_dir,
+        "--no-demangle",
+        "--target",
+        "web",
+        "--keep-lld-exports",
+    ],
+    check=True,
+)
+for name in os.listdir(split_dir):
+    if name != "main.wasm":
+        shutil.copyfile(os.path.join(split_dir, name), os.path.join(pkg_dir, name))
+
+subprocess.run(
+    ["node", os.path.join(bench_dir, "bench.mjs"), pkg_dir, os.path.abspath(args.out)],
+    check=True,
+)
@@ -0,0 +1,289 @@
+#!/usr/bin/env python3
+
+# Builds `crates/example` with and without splitting, serves both builds, and
+# runs Lighthouse on every route of each over each network profile, to measure
+# what splitting wins on a realistic app. The medians of each metric over
+# --runs runs are printed side by side and written to example-bench.json, or
+# the given file, along with the size of the main bundle of each build.
+#
+# Besides Lighthouse's timings, `wasm_ms` is when the last WebAssembly module
+# of the route finished downloading: the main module without splitting, and
+# the main module and the chunks of the route with it. The network profiles
+# are throttled by Chrome itself, so that this is measured under them.
+#
+# With --baseline, the split build's medians are also compared to those of an
+# earlier run, and the script fails if any got worse by more than
+# --tolerance percent, so that the example doubles as a regression test of
+# the splitting pipeline.
+#
+# Requires what `build.py` does (wasm-bindgen, and the wasm32 target), and Lighthouse with Chrome: `lighthouse` on PATH, or
+# else `npx lighthouse`.
+#
+#     ./bench_example.py
+#     ./bench_example.py --runs 5 --routes /,/editor
+#     ./bench_example.py --networks none,4g,3g
+#     ./bench_example.py --baseline example-bench.json --out new.json
+
+import argparse
+import gzip
+import http.server
+import json
+import os
+import shutil
+import socket
+import statistics
+import subprocess
+import sys
+import threading
+
+ROUTES = ["/", "/b", "/c", "/charts", "/editor", "/data"]
+
+# Lighthouse audits by name in the results, all of which are better lower.
+METRICS = {
+    "fcp_ms": "first-contentful-paint",
+    "lcp_ms": "largest-contentful-paint",
+    "tti_ms": "interactive",
+    "tbt_ms": "total-blocking-time",
+    "bytes": "total-byte-weight",
+}
+
+# Profiles of `--networks`, as the `--throttling` settings of Lighthouse's
+# devtools throttling, after its presets of a desktop on cable, and of a
+# mobile device on slow 4G and on 3G, whose CPU is slowed down as well.
+NETWORKS = {
+    "none": {},
+    "cable": {
+        "requestLatencyMs": 150,
+        "downloadThroughputKbps": 9216,
+        "uploadThroughputKbps": 9216,
+        "cpuSlowdownMultiplier": 1,
+    },
+    "4g": {
+        "requestLatencyMs": 562.5,
+        "downloadThroughputKbps": 1474.56,
+        "uploadThroughputKbps": 675,
+        "cpuSlowdownMultiplier": 4,
+    },
+    "3g": {
+        "requestLatencyMs": 1125,
+        "downloadThroughputKbps": 630,
+        "uploadThroughputKbps": 630,
+        "cpuSlowdownMultiplier": 4,
+    },
+}
+
+VARIANTS = {"split": [], "unsplit": ["--no-split"]}
+
+ap = argparse.ArgumentParser()
+ap.add_argument("--runs", type=int, default=3)
+ap.add_argument(
+    "--routes",
+    type=lambda value: [route for route in value.split(",") if route],
+    default=ROUTES,
+    help="comma-separated routes to measure",
+)
+ap.add_argument(
+    "--networks",
+    type=lambda value: [network for network in value.split(",") if network],
+    default=["4g"],
+    help=f"comma-separated network profiles, of {', '.join(NETWORKS)}",
+)
+ap.add_argument("--out", default="example-bench.json")
+ap.add_argument("--optimize", action="store_true", help="passed on to build.py")
+ap.add_argument(
+    "--skip-build", action="store_true", help="measure the builds of a previous run"
+)
+ap.add_argument("--baseline", help="results of an earlier run to compare against")
+ap.add_argument("--tolerance", type=float, default=10)
+args = ap.parse_args()
+
+unknown = [network for network in args.networks if network not in NETWORKS]
+if unknown:
+    ap.error(f"unknown network profiles {unknown}, expected some of {list(NETWORKS)}")
+
+root_dir = os.path.dirname(os.path.abspath(__file__))
+bench_dir = os.path.join(root_dir, "example-bench")
+
+if not args.skip_build:
+    shutil.rmtree(bench_dir, ignore_errors=True)
+    for variant, options in VARIANTS.items():
+        out_dir = os.path.join("example-bench", variant, "pkg")
+        subprocess.run(
+            [sys.executable, os.path.join(root_dir, "build.py"), "--out-dir", out_dir]
+            + options
+            + (["--optimize"] if args.optimize else []),
+            cwd=root_dir,
+            check=True,
+        )
+        shutil.copy(
+            os.path.join(root_dir, "index.html"),
+            os.path.join(bench_dir, variant, "index.html"),
+        )
+
+
+def free_port():
+    with socket.socket() as s:
+        s.bind(("127.0.0.1", 0))
+        return s.getsockname()[1]
+
+
+def serve(directory):
+    class Handler(http.server.SimpleHTTPRequestHandler):
+        extensions_map = {
+            **http.server.SimpleHTTPRequestHandler.extensions_map,
+            ".js": "text/javascript",
+            ".json": "application/json",
+            ".wasm": "application/wasm",
+        }
+
+        def __init__(self, *handler_args, **kwargs):
+            super().__init__(*handler_args, directory=directory, **kwargs)
+
+        def send_head(self):
+            # Routes of the app are not files, and get the page as any
+            # server of a single-page app would.
+            if not os.path.exists(self.translate_path(self.path)):
+                self.path = "/index.html"
+            return super().send_head()
+
+        def log_message(self, format, *log_args):
+            pass
+
+    server = http.server.ThreadingHTTPServer(("127.0.0.1", free_port()), Handler)
+    threading.Thread(target=server.serve_forever, daemon=True).start()
+    return f"http://127.0.0.1:{server.server_address[1]}"
+
+
+lighthouse = ["lighthouse"] if shutil.which("lighthouse") else ["npx", "--yes", "lighthouse"]
+
+
+def throttling_flags(network):
+    settings = NETWORKS[network]
+    if not settings:
+        return ["--throttling-method=provided"]
+    return ["--throttling-method=devtools"] + [
+        f"--throttling.{key}={value}" for key, value in settings.items()
+    ]
+
+
+def run_lighthouse(url, network):
+    report = subprocess.run(
+        lighthouse
+        + [
+            url,
+            "--output=json",
+            "--output-path=stdout",
+            "--only-categories=performance",
+            "--quiet",
+            "--chrome-flags=--headless=new --no-sandbox",
+        ]
+        + throttling_flags(network),
+        stdout=subprocess.PIPE,
+        check=True,
+    ).stdout
+    audits = json.loads(report)["audits"]
+    result = {key: audits[audit]["numericValue"] for key, audit in METRICS.items()}
+    # Times of the requests are relative to the first of the page.
+    result["wasm_ms"] = max(
+        (
+            request["networkEndTime"]
+            for request in audits["network-requests"]["details"]["items"]
+            if request["url"].split("?")[0].endswith(".wasm")
+        ),
+        default=0,
+    )
+    return result
+
+
+def main_bundle_size(variant):
+    """Size of the main module of a build, as served and with gzip, which
+    every route of the app waits for."""
+    with open(os.path.join(bench_dir, variant, "pkg", "main_bg.wasm"), "rb") as f:
+        data = f.read()
+    return {"bytes": len(data), "gzip_bytes": len(gzip.compress(data))}
+
+
+main_bundle = {variant: main_bundle_size(variant) for variant in VARIANTS}
+
+results = {}
+for variant in VARIANTS:
+    base_url = serve(os.path.join(bench_dir, variant))
+    for network in args.networks:
+        for route in args.routes:
+            runs = [run_lighthouse(base_url + route, network) for _ in range(args.runs)]
+            results.setdefault(network, {}).setdefault(route, {})[variant] = {
+                key: statistics.median(run[key] for run in runs) for key in runs[0]
+            }
+            print(
+                f"{variant} {network} {route}: {results[network][route][variant]}",
+                file=sys.stderr,
+            )
+
+with open(args.out, "w") as f:
+    json.dump(
+        {"runs": args.runs, "main_bundle": main_bundle, "networks": results},
+        f,
+        indent=2,
+    )
+    f.write("\n")
+
+
+def format_value(key, value):
+    if key.endswith("bytes"):
+        return f"{value / 1000:.1f} KB"
+    return f"{value:.0f} ms"
+
+
+def format_change(before, after):
+    return f"{100 * (after - before) / before:+.1f}%" if before else ""
+
+
+print(f"{'main bundle':<21} {'unsplit':>12} {'split':>12} {'change':>8}")
+for key in ["bytes", "gzip_bytes"]:
+    unsplit, split = main_bundle["unsplit"][key], main_bundle["split"][key]
+    print(
+        f"{key:<21} {format_value(key, unsplit):>12} {format_value(key, split):>12} "
+        f"{format_change(unsplit, split):>8}"
+    )
+
+for network, routes in results.items():
+    print()
+    print(
+        f"{'network':<7} {'route':<10} {'metric':<8} {'unsplit':>12} {'split':>12} "
+        f"{'change':>8}"
+    )
+    for route, variants in routes.items():
+        for key in variants["split"]:
+            unsplit, split = variants["unsplit"][key], variants["split"][key]
+            print(
+                f"{network:<7} {route:<10} {key:<8} {format_value(key, unsplit):>12} "
+                f"{format_value(key, split):>12} {format_change(unsplit, split):>8}"
+            )
+
+if args.baseline:
+    with open(args.baseline) as f:
+        baseline = json.load(f)
+    # Results of earlier versions of this script were all with Lighthouse's
+    # default throttling, which the 4g profile resembles.
+    baseline = baseline.get("networks") or {"4g": baseline["routes"]}
+    regressions = []
+    for network, routes in results.items():
+        for route, variants in routes.items():
+            before = baseline.get(network, {}).get(route, {}).get("split")
+            if before is None:
+                continue
+            for key, new in variants["split"].items():
+                old = before.get(key)
+                if old and (new - old) / old * 100 > args.tolerance:
+                    regressions.append(
+                        f"{network} {route} {key}: "
+                        f"{format_value(key, old)} -> {format_value(key, new)}"
+                    )
+    if regressions:
+        print(
+            f"Split build got worse by more than {args.tolerance}% than the baseline:",
+            file=sys.stderr,
+        )
+        for regression in regressions:
+            print(f"  {regression}", file=sys.stderr)
+        sys.exit(1)
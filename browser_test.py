@@ -0,0 +1,226 @@
+#!/usr/bin/env python3
+
+# Builds the split example with `build.py`, runs it in real browsers through
+# WebDriver, and checks that its routes render and load their chunks, whether
+# navigated to from the app or loaded directly.
+#
+# Requires what `build.py` does (wasm-bindgen, and the wasm32 target), and the
+# WebDriver of each browser on PATH: chromedriver, geckodriver, and on macOS
+# safaridriver (enabled once with `safaridriver --enable`). Without
+# --browsers, every browser whose driver is found runs, and the others are
+# skipped. The browsers listed by --browsers must all run, as in CI, where
+# each runner lists those it has. `/b` and `/c` fetch their data from
+# jsonplaceholder.typicode.com.
+#
+#     ./browser_test.py                          # all available browsers
+#     ./browser_test.py --browsers chrome,firefox
+
+import argparse
+import http.server
+import os
+import shutil
+import subprocess
+import sys
+import tempfile
+import threading
+import time
+
+from webdriver import WebDriver, free_port, wait_for_driver
+
+BROWSERS = {
+    "chrome": {
+        "driver": ["chromedriver", "--port={port}"],
+        "capabilities": {
+            "browserName": "chrome",
+            "goog:chromeOptions": {"args": ["--headless=new", "--no-sandbox"]},
+        },
+    },
+    "firefox": {
+        "driver": ["geckodriver", "--port", "{port}"],
+        "capabilities": {
+            "browserName": "firefox",
+            "moz:firefoxOptions": {"args": ["-headless"]},
+        },
+    },
+    # Safari has no headless mode.
+    "safari": {
+        "driver": ["safaridriver", "--port", "{port}"],
+        "capabilities": {"browserName": "safari"},
+    },
+}
+
+# Each step navigates to a route, either by loading the page or by clicking a
+# link of the already loaded app, and waits for the text to render and the
+# chunks to be loaded.
+STEPS = [
+    {"load": "/", "text": ["View A"], "chunks": []},
+    {
+        "click": "/b",
+        "text": ["View B", "Nested Child"],
+        "chunks": ["view_b", "view_b_child"],
+    },
+    {"click": "/c", "text": ["Nested Child"], "chunks": ["view_c"]},
+    # A deep link, which loads the chunks of the route while starting up.
+    {"load": "/c", "text": ["Nested Child"], "chunks": ["view_c"]},
+]
+
+
+def browser_list(value):
+    names = [name.strip() for name in value.split(",") if name.strip()]
+    unknown = [name for name in names if name not in BROWSERS]
+    if unknown:
+        raise argparse.ArgumentTypeError(
+            f"unknown browsers {unknown}, expected some of {sorted(BROWSERS)}"
+        )
+    return names
+
+
+ap = argparse.ArgumentParser()
+ap.add_argument(
+    "--browsers",
+    type=browser_list,
+    help="comma-separated browsers that must run, instead of all available ones",
+)
+ap.add_argument("--timeout", type=float, default=30)
+args = ap.parse_args()
+
+repo_dir = os.path.dirname(os.path.abspath(__file__))
+root_dir = tempfile.mkdtemp(prefix="wasm-split-browser-test-")
+subprocess.run(
+    [
+        sys.executable,
+        os.path.join(repo_dir, "build.py"),
+        "--out-dir",
+        os.path.join(root_dir, "pkg"),
+    ],
+    cwd=repo_dir,
+    check=True,
+)
+shutil.copy(os.path.join(repo_dir, "index.html"), os.path.join(root_dir, "index.html"))
+
+
+# Content types of the served files, since browsers only compile responses
+# served as `application/wasm` with `WebAssembly.compileStreaming`.
+served_types = {}
+
+
+class Handler(http.server.SimpleHTTPRequestHandler):
+    extensions_map = {
+        **http.server.SimpleHTTPRequestHandler.extensions_map,
+        ".js": "text/javascript",
+        ".json": "application/json",
+        ".wasm": "application/wasm",
+    }
+
+    def __init__(self, *args, **kwargs):
+        super().__init__(*args, directory=root_dir, **kwargs)
+
+    def send_head(self):
+        # Routes of the app are not files, and get the page as any server of
+        # a single-page app would.
+        if not os.path.exists(self.translate_path(self.path)):
+            self.path = "/index.html"
+        return super().send_head()
+
+    def guess_type(self, path):
+        content_type = super().guess_type(path)
+        served_types[os.path.basename(path)] = content_type
+        return content_type
+
+    def log_message(self, format, *args):
+        pass
+
+
+server = http.server.ThreadingHTTPServer(("127.0.0.1", free_port()), Handler)
+threading.Thread(target=server.serve_forever, daemon=True).start()
+base_url = f"http://127.0.0.1:{server.server_address[1]}"
+
+
+# Rendered text and the chunks reported by the loader's
+# `wasm-split:chunk-loaded` performance marks.
+STATE_SCRIPT = """
+return {
+  text: document.body ? document.body.innerText : "",
+  chunks: performance
+    .getEntriesByName("wasm-split:chunk-loaded", "mark")
+    .map((mark) => mark.detail.chunk),
+};
+"""
+
+
+def run_step(driver, step):
+    if "load" in step:
+        driver.navigate(base_url + step["load"])
+    else:
+        driver.execute(
+            "document.querySelector(`a[href='${arguments[0]}']`).click();",
+            step["click"],
+        )
+    deadline = time.monotonic() + args.timeout
+    while True:
+        state = driver.execute(STATE_SCRIPT)
+        missing_text = [text for text in step["text"] if text not in state["text"]]
+        missing_chunks = [
+            chunk for chunk in step["chunks"] if chunk not in state["chunks"]
+        ]
+        if not missing_text and not missing_chunks:
+            return
+        if time.monotonic() > deadline:
+            raise RuntimeError(
+                f"{step.get('load') or step.get('click')}: "
+                f"missing text {missing_text}, chunks not loaded {missing_chunks}, "
+                f"page text {state['text']!r}"
+            )
+        time.sleep(0.2)
+
+
+def run_browser(name):
+    config = BROWSERS[name]
+    if shutil.which(config["driver"][0]) is None:
+        if args.browsers:
+            return f"FAILED: {config['driver'][0]} not found"
+        return f"skipped ({config['driver'][0]} not found)"
+    port = free_port()
+    process = subprocess.Popen(
+        [arg.format(port=port) for arg in config["driver"]],
+        stdout=subprocess.DEVNULL,
+        stderr=subprocess.DEVNULL,
+    )
+    try:
+        driver_url = f"http://127.0.0.1:{port}"
+        wait_for_driver(driver_url, process, args.timeout)
+        driver = WebDriver(driver_url, config["capabilities"])
+        try:
+            for step in STEPS:
+                run_step(driver, step)
+        finally:
+            driver.quit()
+    finally:
+        process.terminate()
+        process.wait()
+    return "ok"
+
+
+results = {}
+for name in args.browsers or sorted(BROWSERS):
+    print(f"{name}: running", flush=True)
+    try:
+        results[name] = run_browser(name)
+    except Exception as e:
+        results[name] = f"FAILED: {e}"
+    print(f"{name}: {results[name]}", flush=True)
+
+wrong_types = {
+    name: content_type
+    for name, content_type in served_types.items()
+    if name.endswith(".wasm") and content_type != "application/wasm"
+}
+if wrong_types:
+    print(f"served with unexpected content types: {wrong_types}")
+
+server.shutdown()
+shutil.rmtree(root_dir)
+
+failed = [name for name, result in results.items() if result.startswith("FAILED")]
+if failed or wrong_types:
+    sys.exit(1)
@@ -1,5 +1,9 @@
 #!/usr/bin/env python3
 
+# Builds the example once. To rebuild it on every change while serving it, run
+# `cargo run -p wasm_split_cli -- serve --package-dir crates/example --features split`
+# from the repository root instead.
+
 import argparse
 import os
 import json
@@ -9,6 +13,16 @@ import shutil
 ap = argparse.ArgumentParser()
 ap.add_argument("--no-split", action="store_true")
 ap.add_argument("--optimize", action="store_true")
+ap.add_argument(
+    "--out-dir",
+    default="pkg",
+    help="directory to write the app to, relative to the repository root",
+)
+ap.add_argument(
+    "--fold-threshold",
+    help="size below which split modules are folded into the main module, "
+    "instead of the default of wasm_split_cli",
+)
 args = ap.parse_args()
 
 root_dir = os.path.dirname(__file__)
@@ -56,7 +70,7 @@ for json_result in json_results:
 print(target_path)
 assert target_path is not None
 
-pkg_dir = os.path.join(root_dir, "pkg")
+pkg_dir = os.path.join(root_dir, args.out_dir)
 
 shutil.rmtree(pkg_dir, ignore_errors=True)
 
@@ -80,6 +94,10 @@ else:
     split_temp_dir = os.path.join(root_dir, "split_tmp")
     shutil.rmtree(split_temp_dir, ignore_errors=True)
 
+    split_options = []
+    if args.fold_threshold is not None:
+        split_options.extend(["--fold-threshold", args.fold_threshold])
+
     subprocess.run(
         [
             "cargo",
@@ -89,7 +107,8 @@ else:
             "--",
             target_path,
             split_temp_dir,
-        ],
+        ]
+        + split_options,
         cwd=root_dir,
         check=True,
     )
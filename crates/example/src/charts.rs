@@ -0,0 +1,198 @@
+// The `/charts` route: a week of (generated) request counts drawn as SVG charts, whose layout
+// code is the kind of view-specific computation that only the users of a route should download.
+
+use leptos::{
+    prelude::*,
+    tachys::view::any_view::{AnyView, IntoAny},
+};
+use leptos_router::LazyRoute;
+
+use crate::i18n::t;
+
+const WIDTH: f64 = 640.0;
+const HEIGHT: f64 = 240.0;
+const MARGIN: f64 = 40.0;
+const DAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Requests per hour over a week, with a daily cycle, a quieter weekend and some noise from a
+/// fixed seed, so that every run of the benchmark renders the same charts.
+fn requests_per_hour() -> Vec<f64> {
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let mut noise = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state % 1000) as f64 / 1000.0
+    };
+    (0..DAYS.len() * 24)
+        .map(|hour| {
+            let time_of_day = (hour % 24) as f64 / 24.0 * std::f64::consts::TAU;
+            let weekend = if hour / 24 >= 5 { 0.6 } else { 1.0 };
+            weekend * (400.0 - 300.0 * time_of_day.cos()) + 120.0 * noise()
+        })
+        .collect()
+}
+
+/// Centered moving average over `window` values, shorter at the edges.
+fn smooth(values: &[f64], window: usize) -> Vec<f64> {
+    let half = window / 2;
+    (0..values.len())
+        .map(|index| {
+            let range = &values[index.saturating_sub(half)..(index + half + 1).min(values.len())];
+            range.iter().sum::<f64>() / range.len() as f64
+        })
+        .collect()
+}
+
+/// Round tick values from 0 to at least `max`, about `count` of them, at steps of 1, 2 or 5
+/// times a power of ten.
+fn ticks(max: f64, count: usize) -> Vec<f64> {
+    let rough = max / count as f64;
+    let magnitude = 10f64.powf(rough.log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0]
+        .into_iter()
+        .map(|factor| factor * magnitude)
+        .find(|step| *step >= rough)
+        .unwrap_or(10.0 * magnitude);
+    let ticks = (max / step).ceil() as usize;
+    (0..=ticks).map(|tick| tick as f64 * step).collect()
+}
+
+struct Scale {
+    max: f64,
+    ticks: Vec<f64>,
+}
+
+impl Scale {
+    fn new(values: impl Iterator<Item = f64>) -> Self {
+        let ticks = ticks(values.fold(1.0, f64::max), 5);
+        Self {
+            max: *ticks.last().unwrap(),
+            ticks,
+        }
+    }
+
+    fn y(&self, value: f64) -> f64 {
+        HEIGHT - MARGIN - value / self.max * (HEIGHT - 2.0 * MARGIN)
+    }
+
+    fn axis(&self) -> impl IntoView {
+        self.ticks
+            .iter()
+            .map(|&tick| {
+                let y = self.y(tick);
+                view! {
+                    <line x1=MARGIN x2=WIDTH - MARGIN y1=y y2=y stroke="#ddd"/>
+                    <text x=MARGIN - 6.0 y=y + 4.0 text-anchor="end" font-size="11">
+                        {format!("{tick}")}
+                    </text>
+                }
+            })
+            .collect_view()
+    }
+}
+
+fn line_path(values: &[f64], scale: &Scale) -> String {
+    let step = (WIDTH - 2.0 * MARGIN) / (values.len() - 1) as f64;
+    values
+        .iter()
+        .enumerate()
+        .map(|(index, &value)| {
+            let command = if index == 0 { 'M' } else { 'L' };
+            format!(
+                "{command}{:.1},{:.1}",
+                MARGIN + index as f64 * step,
+                scale.y(value)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[component]
+fn LineChart(values: Vec<f64>, smoothing: ReadSignal<usize>) -> impl IntoView {
+    let scale = Scale::new(values.iter().copied());
+    let axis = scale.axis();
+    let path = move || line_path(&smooth(&values, smoothing.get()), &scale);
+    view! {
+        <svg width=WIDTH height=HEIGHT role="img">
+            {axis}
+            <path d=path fill="none" stroke="#3573c6" stroke-width="2"/>
+        </svg>
+    }
+}
+
+#[component]
+fn BarChart(totals: Vec<f64>) -> impl IntoView {
+    let scale = Scale::new(totals.iter().copied());
+    let slot = (WIDTH - 2.0 * MARGIN) / totals.len() as f64;
+    let bars = totals
+        .iter()
+        .zip(DAYS)
+        .enumerate()
+        .map(|(index, (&total, day))| {
+            let x = MARGIN + index as f64 * slot;
+            let y = scale.y(total);
+            view! {
+                <rect x=x + slot * 0.15 y=y width=slot * 0.7 height=HEIGHT - MARGIN - y fill="#e08a2c">
+                    <title>{format!("{day}: {total:.0}")}</title>
+                </rect>
+                <text x=x + slot / 2.0 y=HEIGHT - MARGIN + 16.0 text-anchor="middle" font-size="11">
+                    {day}
+                </text>
+            }
+        })
+        .collect_view();
+    view! {
+        <svg width=WIDTH height=HEIGHT role="img">
+            {scale.axis()}
+            {bars}
+        </svg>
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ViewCharts {
+    requests: Vec<f64>,
+}
+
+#[cfg_attr(
+    feature = "split",
+    wasm_split::lazy_route(view_charts, route = "/charts")
+)]
+impl LazyRoute for ViewCharts {
+    fn data() -> Self {
+        Self {
+            requests: requests_per_hour(),
+        }
+    }
+
+    async fn view(this: Self) -> AnyView {
+        let (smoothing, set_smoothing) = signal(1);
+        let totals = this
+            .requests
+            .chunks(24)
+            .map(|day| day.iter().sum())
+            .collect::<Vec<f64>>();
+        view! {
+            <h2>{t("charts-title")}</h2>
+            <label>
+                {t("charts-smoothing")} " "
+                <input
+                    type="range"
+                    min="1"
+                    max="25"
+                    step="2"
+                    prop:value=move || smoothing.get().to_string()
+                    on:input=move |ev| {
+                        set_smoothing.set(event_target_value(&ev).parse().unwrap_or(1))
+                    }
+                />
+            </label>
+            <LineChart values=this.requests smoothing/>
+            <h2>{t("charts-total")}</h2>
+            <BarChart totals/>
+        }
+        .into_any()
+    }
+}
@@ -0,0 +1,221 @@
+// The `/checks` route: calls into split functions with the kinds of arguments and results that
+// the splitter has to pass between chunks, and renders what they return, so that
+// `example_test.py` can check that the split build renders the same as the unsplit one. Its
+// "Panic" button panics in a split function, and the page records the panic message in the
+// `data-panic` attribute of the body.
+
+use std::sync::Once;
+
+use leptos::{
+    prelude::*,
+    tachys::view::any_view::{AnyView, IntoAny},
+};
+use leptos_router::LazyRoute;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Point {
+    x: f64,
+    y: f64,
+}
+
+#[cfg_attr(feature = "split", wasm_split::wasm_split(check_scalars))]
+fn scalars(a: u8, b: i64, c: f64, d: bool, e: char) -> String {
+    format!("{a} {b} {c:.3} {d} {e}")
+}
+
+#[cfg_attr(feature = "split", wasm_split::wasm_split(check_strings))]
+fn strings(borrowed: &str, owned: String) -> String {
+    format!(
+        "{}|{}",
+        borrowed.to_uppercase(),
+        owned.chars().rev().collect::<String>()
+    )
+}
+
+#[cfg_attr(feature = "split", wasm_split::wasm_split(check_collections))]
+fn collections(slice: &[u32], owned: Vec<u32>, out: &mut Vec<u32>) -> u64 {
+    out.extend(slice.iter().zip(&owned).map(|(a, b)| a * b));
+    out.iter().map(|&value| u64::from(value)).sum()
+}
+
+#[cfg_attr(feature = "split", wasm_split::wasm_split(check_structs))]
+fn midpoint(a: Point, b: &Point) -> Option<Point> {
+    (a != *b).then(|| Point {
+        x: (a.x + b.x) / 2.0,
+        y: (a.y + b.y) / 2.0,
+    })
+}
+
+#[cfg_attr(feature = "split", wasm_split::wasm_split(check_results))]
+fn parse_count(text: &str) -> Result<u32, String> {
+    text.trim()
+        .parse()
+        .map_err(|error| format!("{text:?}: {error}"))
+}
+
+#[cfg_attr(feature = "split", wasm_split::wasm_split(check_closures))]
+fn apply_twice(f: impl Fn(u32) -> u32, x: u32) -> u32 {
+    f(f(x))
+}
+
+#[cfg_attr(feature = "split", wasm_split::wasm_split(check_closures))]
+fn counter(start: u32) -> Box<dyn FnMut() -> u32> {
+    let mut next = start;
+    Box::new(move || {
+        next += 1;
+        next - 1
+    })
+}
+
+#[cfg_attr(
+    feature = "split",
+    wasm_split::wasm_split(check_generics, types(u32, f64))
+)]
+fn largest<T: PartialOrd + Copy>(values: &[T]) -> Option<T> {
+    values.iter().copied().fold(None, |max, value| match max {
+        Some(max) if max >= value => Some(max),
+        _ => Some(value),
+    })
+}
+
+#[cfg_attr(feature = "split", wasm_split::wasm_split(check_concurrent_a))]
+fn fibonacci(n: u32) -> u64 {
+    (0..n).fold((0u64, 1u64), |(a, b), _| (b, a + b)).0
+}
+
+#[cfg_attr(feature = "split", wasm_split::wasm_split(check_concurrent_b))]
+fn collatz_steps(mut n: u64) -> u32 {
+    let mut steps = 0;
+    while n != 1 {
+        n = if n.is_multiple_of(2) {
+            n / 2
+        } else {
+            3 * n + 1
+        };
+        steps += 1;
+    }
+    steps
+}
+
+#[cfg_attr(feature = "split", wasm_split::wasm_split(check_panic))]
+fn fail(n: u32) -> u32 {
+    panic!("split function panicked with {n}")
+}
+
+/// Records the message of the next panic in the `data-panic` attribute of the body, where the
+/// test reads it from, before the panic hooks of the app report it.
+fn record_panics() {
+    static RECORD: Once = Once::new();
+    RECORD.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            let payload = info.payload();
+            let message = payload
+                .downcast_ref::<String>()
+                .map(String::as_str)
+                .or_else(|| payload.downcast_ref::<&str>().copied())
+                .unwrap_or_default();
+            if let Some(body) = document().body() {
+                let _ = body.set_attribute("data-panic", message);
+            }
+            previous(info);
+        }));
+    });
+}
+
+async fn run_checks() -> Vec<(&'static str, String)> {
+    let mut out = vec![1];
+    let collected = split_call!(collections(&[1, 2, 3], vec![4, 5, 6], &mut out));
+    let mut next = split_call!(counter(7));
+    // The chunks of both functions load at the same time, and the second call of `fibonacci`
+    // waits for the load that the first one started.
+    #[cfg(feature = "split")]
+    let concurrent = futures::join!(fibonacci(50), collatz_steps(27), fibonacci(20));
+    #[cfg(not(feature = "split"))]
+    let concurrent = (fibonacci(50), collatz_steps(27), fibonacci(20));
+    vec![
+        ("scalars", split_call!(scalars(200, -3, 2.5, true, 'λ'))),
+        (
+            "strings",
+            split_call!(strings("split", "chunk".to_string())),
+        ),
+        ("collections", format!("{collected} {out:?}")),
+        (
+            "structs",
+            format!(
+                "{:?} {:?}",
+                split_call!(midpoint(
+                    Point { x: 0.0, y: 1.0 },
+                    &Point { x: 4.0, y: 3.0 }
+                )),
+                split_call!(midpoint(
+                    Point { x: 1.0, y: 1.0 },
+                    &Point { x: 1.0, y: 1.0 }
+                )),
+            ),
+        ),
+        (
+            "results",
+            format!(
+                "{:?} {:?}",
+                split_call!(parse_count(" 42 ")),
+                split_call!(parse_count("many")),
+            ),
+        ),
+        (
+            "closures",
+            format!(
+                "{} {} {}",
+                split_call!(apply_twice(|x| x * 3, 5)),
+                next(),
+                next()
+            ),
+        ),
+        (
+            "generics",
+            format!(
+                "{:?} {:?} {:?}",
+                split_call!(largest(&[3u32, 9, 4])),
+                split_call!(largest(&[0.5f64, -1.0])),
+                split_call!(largest(&[] as &[u32])),
+            ),
+        ),
+        ("concurrent", format!("{concurrent:?}")),
+    ]
+}
+
+#[derive(Debug, Clone)]
+pub struct ViewChecks;
+
+#[cfg_attr(
+    feature = "split",
+    wasm_split::lazy_route(view_checks, route = "/checks")
+)]
+impl LazyRoute for ViewChecks {
+    fn data() -> Self {
+        Self
+    }
+
+    async fn view(_this: Self) -> AnyView {
+        let checks = run_checks().await;
+        let on_panic = move |_| {
+            record_panics();
+            leptos::task::spawn_local(async {
+                split_call!(fail(7));
+            });
+        };
+        view! {
+            <dl id="checks">
+                {checks
+                    .into_iter()
+                    .map(|(name, result)| view! {
+                        <dt>{name}</dt>
+                        <dd>{result}</dd>
+                    })
+                    .collect_view()}
+            </dl>
+            <button id="panic" on:click=on_panic>"Panic"</button>
+        }
+        .into_any()
+    }
+}
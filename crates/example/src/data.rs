@@ -0,0 +1,178 @@
+// The `/data` route: a JSON document of 5000 photos, about 1 MB, deserialized and grouped by
+// album on a worker, so that neither `serde_json`'s code for it nor the parsing itself weighs on
+// the main thread.
+
+use leptos::{
+    prelude::*,
+    tachys::view::any_view::{AnyView, IntoAny},
+};
+use leptos_router::LazyRoute;
+use serde::Deserialize;
+#[cfg(feature = "split")]
+use wasm_split::Transfer;
+
+use crate::{i18n::t, DATA_URL};
+
+const PAGE_SIZE: usize = 20;
+
+#[derive(Debug, Deserialize)]
+struct Photo {
+    #[serde(rename = "albumId")]
+    album_id: usize,
+    title: String,
+    url: String,
+}
+
+/// The photos of an album, as shown in the table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Album {
+    id: usize,
+    photos: usize,
+    first_title: String,
+    /// Mean length of the titles of its photos, as a stand-in for real per-album statistics.
+    mean_title_length: f64,
+    cover_url: String,
+}
+
+#[cfg(feature = "split")]
+impl Transfer<'_> for Album {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.id.encode(out);
+        self.photos.encode(out);
+        self.first_title.encode(out);
+        self.mean_title_length.encode(out);
+        self.cover_url.encode(out);
+    }
+
+    fn decode(input: &mut &[u8]) -> Self {
+        Self {
+            id: Transfer::decode(input),
+            photos: Transfer::decode(input),
+            first_title: Transfer::decode(input),
+            mean_title_length: Transfer::decode(input),
+            cover_url: Transfer::decode(input),
+        }
+    }
+}
+
+#[cfg_attr(
+    feature = "split",
+    wasm_split::wasm_split(summarize_photos, worker, route = "/data")
+)]
+fn summarize_photos(data: &str) -> Vec<Album> {
+    let photos: Vec<Photo> = serde_json::from_str(data).unwrap();
+    let mut albums = Vec::<Album>::new();
+    for photo in photos {
+        match albums.iter_mut().find(|album| album.id == photo.album_id) {
+            Some(album) => {
+                album.mean_title_length += photo.title.chars().count() as f64;
+                album.photos += 1;
+            }
+            None => albums.push(Album {
+                id: photo.album_id,
+                photos: 1,
+                mean_title_length: photo.title.chars().count() as f64,
+                first_title: photo.title,
+                cover_url: photo.url,
+            }),
+        }
+    }
+    for album in albums.iter_mut() {
+        album.mean_title_length /= album.photos as f64;
+    }
+    albums.sort_by_key(|album| album.id);
+    albums
+}
+
+#[derive(Clone, Debug)]
+pub struct ViewData {
+    albums: AsyncDerived<Vec<Album>, LocalStorage>,
+}
+
+#[cfg_attr(feature = "split", wasm_split::lazy_route(view_data, route = "/data"))]
+impl LazyRoute for ViewData {
+    fn data() -> Self {
+        Self {
+            albums: AsyncDerived::new_unsync(|| async move {
+                let data = gloo_net::http::Request::get(&format!("{DATA_URL}/photos"))
+                    .send()
+                    .await
+                    .unwrap()
+                    .text()
+                    .await
+                    .unwrap();
+                split_call!(summarize_photos(&data))
+            }),
+        }
+    }
+
+    async fn view(this: Self) -> AnyView {
+        let (filter, set_filter) = signal(String::new());
+        let (page, set_page) = signal(0usize);
+        let albums = this.albums;
+        let table = move || {
+            Suspend::new(async move {
+                let albums = albums.await;
+                let filter = filter.get().to_lowercase();
+                let matching = albums
+                    .iter()
+                    .filter(|album| album.first_title.to_lowercase().contains(&filter))
+                    .collect::<Vec<_>>();
+                let photos = matching.iter().map(|album| album.photos).sum::<usize>();
+                let pages = matching.len().div_ceil(PAGE_SIZE).max(1);
+                let page = page.get().min(pages - 1);
+                let rows = matching
+                    .iter()
+                    .skip(page * PAGE_SIZE)
+                    .take(PAGE_SIZE)
+                    .map(|album| {
+                        view! {
+                            <tr>
+                                <td>{album.id}</td>
+                                <td><img src=album.cover_url.clone() width="32" height="32"/></td>
+                                <td>{album.first_title.clone()}</td>
+                                <td>{album.photos}</td>
+                                <td>{format!("{:.1}", album.mean_title_length)}</td>
+                            </tr>
+                        }
+                    })
+                    .collect_view();
+                view! {
+                    <p>
+                        {matching.len()} " " {t("data-albums")} ", " {photos} " "
+                        {t("data-photos")}
+                    </p>
+                    <table>
+                        <tbody>{rows}</tbody>
+                    </table>
+                    <button
+                        disabled=page == 0
+                        on:click=move |_| set_page.update(|page| *page = page.saturating_sub(1))
+                    >
+                        {t("data-previous")}
+                    </button>
+                    {format!(" {} / {pages} ", page + 1)}
+                    <button
+                        disabled=page + 1 >= pages
+                        on:click=move |_| set_page.update(|page| *page += 1)
+                    >
+                        {t("data-next")}
+                    </button>
+                }
+            })
+        };
+        view! {
+            <h2>{t("data-title")}</h2>
+            <input
+                type="search"
+                placeholder=t("data-filter")
+                on:input=move |ev| {
+                    set_filter.set(event_target_value(&ev));
+                    set_page.set(0);
+                }
+            />
+            <Suspense fallback=t("data-loading")>{table}</Suspense>
+        }
+        .into_any()
+    }
+}
@@ -0,0 +1,69 @@
+// The `/editor` route: a markdown editor with a live preview, which brings in the whole of
+// `pulldown-cmark` for users who open it and nobody else.
+
+use leptos::{
+    prelude::*,
+    tachys::view::any_view::{AnyView, IntoAny},
+};
+use leptos_router::LazyRoute;
+use pulldown_cmark::{html, Options, Parser};
+
+use crate::i18n::t;
+
+const SAMPLE: &str = "# Release notes
+
+Chunks are now **preloaded** while the browser is idle, and:
+
+- compiled off the main thread,
+- retried with exponential backoff,
+- checked against their `integrity` hash.
+
+| Chunk | Size |
+|-------|-----:|
+| main  | 120 KB |
+| view_c | 14 KB |
+
+> Split the code the first view does not need.
+";
+
+fn render_markdown(source: &str) -> String {
+    let parser = Parser::new_ext(
+        source,
+        Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS,
+    );
+    let mut out = String::with_capacity(source.len() * 3 / 2);
+    html::push_html(&mut out, parser);
+    out
+}
+
+#[derive(Debug, Clone)]
+pub struct ViewEditor;
+
+#[cfg_attr(
+    feature = "split",
+    wasm_split::lazy_route(view_editor, route = "/editor")
+)]
+impl LazyRoute for ViewEditor {
+    fn data() -> Self {
+        Self
+    }
+
+    async fn view(_this: Self) -> AnyView {
+        let (source, set_source) = signal(SAMPLE.to_string());
+        let preview = Memo::new(move |_| source.with(|source| render_markdown(source)));
+        let words = move || source.with(|source| source.split_whitespace().count());
+        view! {
+            <h2>{t("editor-title")}</h2>
+            <div style="display: flex; gap: 16px">
+                <textarea
+                    style="flex: 1; min-height: 320px; font-family: monospace"
+                    prop:value=move || source.get()
+                    on:input=move |ev| set_source.set(event_target_value(&ev))
+                ></textarea>
+                <article style="flex: 1" inner_html=move || preview.get()></article>
+            </div>
+            <p>{words} " " {t("editor-words")}</p>
+        }
+        .into_any()
+    }
+}
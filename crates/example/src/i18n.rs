@@ -0,0 +1,140 @@
+// Translations, with English in the main module and every other locale in a split module of its
+// own, so that users only download the strings of the language they pick.
+
+use std::collections::HashMap;
+
+use leptos::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Locale {
+    En,
+    De,
+    Ja,
+}
+
+impl Locale {
+    const ALL: [Locale; 3] = [Locale::En, Locale::De, Locale::Ja];
+
+    fn code(self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::De => "de",
+            Locale::Ja => "ja",
+        }
+    }
+
+    fn native_name(self) -> &'static str {
+        match self {
+            Locale::En => "English",
+            Locale::De => "Deutsch",
+            Locale::Ja => "日本語",
+        }
+    }
+}
+
+/// Strings of a locale by key, parsed from one of the files in `locales/`.
+#[derive(Debug, Clone, Default)]
+pub struct Catalog(HashMap<&'static str, &'static str>);
+
+impl Catalog {
+    fn parse(source: &'static str) -> Self {
+        Self(
+            source
+                .lines()
+                .filter(|line| !line.starts_with('#'))
+                .filter_map(|line| line.split_once(" = "))
+                .map(|(key, value)| (key.trim(), value.trim()))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(feature = "split")]
+wasm_split::locale_data! {
+    static CATALOGS: Catalog = {
+        "de" => Catalog::parse(include_str!("../locales/de.txt")),
+        "ja" => Catalog::parse(include_str!("../locales/ja.txt")),
+    };
+}
+
+async fn load_catalog(locale: Locale) -> Catalog {
+    #[cfg(feature = "split")]
+    if let Some(catalog) = CATALOGS.get(locale.code()).await {
+        return catalog.clone();
+    }
+    match locale {
+        #[cfg(not(feature = "split"))]
+        Locale::De => Catalog::parse(include_str!("../locales/de.txt")),
+        #[cfg(not(feature = "split"))]
+        Locale::Ja => Catalog::parse(include_str!("../locales/ja.txt")),
+        _ => Catalog::parse(include_str!("../locales/en.txt")),
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct I18n {
+    locale: RwSignal<Locale>,
+    catalog: RwSignal<Catalog>,
+    english: StoredValue<Catalog>,
+}
+
+pub fn provide_i18n() {
+    let english = Catalog::parse(include_str!("../locales/en.txt"));
+    provide_context(I18n {
+        locale: RwSignal::new(Locale::En),
+        catalog: RwSignal::new(english.clone()),
+        english: StoredValue::new(english),
+    });
+}
+
+/// The string `key` in the current locale, falling back to English for keys that a locale lacks.
+pub fn t(key: &'static str) -> impl Fn() -> String + Copy + Send + Sync + 'static {
+    let i18n = expect_context::<I18n>();
+    move || {
+        i18n.catalog
+            .with(|catalog| catalog.0.get(key).copied())
+            .or_else(|| {
+                i18n.english
+                    .with_value(|english| english.0.get(key).copied())
+            })
+            .unwrap_or(key)
+            .to_string()
+    }
+}
+
+/// Switches the locale once its strings are loaded, so that the page never shows a mix of two.
+#[component]
+pub fn LocalePicker() -> impl IntoView {
+    let i18n = expect_context::<I18n>();
+    let on_change = move |ev| {
+        let code = event_target_value(&ev);
+        let Some(locale) = Locale::ALL.into_iter().find(|locale| locale.code() == code) else {
+            return;
+        };
+        leptos::task::spawn_local(async move {
+            let catalog = load_catalog(locale).await;
+            i18n.catalog.set(catalog);
+            i18n.locale.set(locale);
+        });
+    };
+    view! {
+        <label>
+            {t("locale-label")} " "
+            <select on:change=on_change>
+                {Locale::ALL
+                    .into_iter()
+                    .map(|locale| {
+                        view! {
+                            <option
+                                value=locale.code()
+                                selected=move || i18n.locale.get() == locale
+                            >
+                                {locale.native_name()}
+                            </option>
+                        }
+                    })
+                    .collect_view()}
+            </select>
+        </label>
+    }
+}
@@ -1,19 +1,68 @@
+//! A multi-route leptos app that serves as the benchmark of the splitting
+//! pipeline: `bench_example.py` at the root of the repository builds it with
+//! and without `--features split` and compares both with Lighthouse.
+//!
+//! Besides the small views A to C, it has routes with the kind of code that
+//! real apps load lazily: SVG charts (`charts.rs`), a markdown editor
+//! (`editor.rs`), translations loaded per locale (`i18n.rs`) and a large JSON
+//! document deserialized on a worker (`data.rs`). The links of the nav are
+//! `PrefetchLink`s (`prefetch.rs`), which load the chunks of their route
+//! when the user is about to follow them.
+//!
+//! `/checks` (`checks.rs`) calls split functions with all kinds of arguments,
+//! for `example_test.py`, which checks that both builds render the same.
+
 use std::future::IntoFuture;
 
 use leptos::{
     prelude::*,
     tachys::view::any_view::{AnyView, IntoAny},
 };
-use leptos_router::{components::*, Lazy, LazyRoute, Outlet, StaticSegment};
+#[cfg(feature = "split")]
+use leptos_meta::Link;
+#[cfg(feature = "split")]
+use leptos_router::hooks::use_location;
+use leptos_router::{components::*, Lazy, LazyRoute, StaticSegment};
 use serde::Deserialize;
 use wasm_bindgen::prelude::*;
-
 #[cfg(feature = "split")]
-use wasm_split::wasm_split;
+use wasm_split::Transfer;
+
+/// Calls a function annotated with `#[wasm_split]`, which is async when
+/// split, and a plain function otherwise.
+macro_rules! split_call {
+    ($call:expr) => {{
+        #[cfg(feature = "split")]
+        let result = $call.await;
+        #[cfg(not(feature = "split"))]
+        let result = $call;
+        result
+    }};
+}
+
+/// Base URL of the JSON documents that views B and C and `/data` fetch. `example_test.py` builds
+/// with `EXAMPLE_DATA_URL` set to a path of its test server, which serves copies of them, so that
+/// it does not depend on the network.
+const DATA_URL: &str = match option_env!("EXAMPLE_DATA_URL") {
+    Some(url) => url,
+    None => "https://jsonplaceholder.typicode.com",
+};
+
+mod charts;
+mod checks;
+mod data;
+mod editor;
+mod i18n;
+mod prefetch;
+
+use i18n::t;
+use prefetch::{Prefetch, PrefetchLink};
 
 #[wasm_bindgen]
 pub fn main() {
     console_error_panic_hook::set_once();
+    #[cfg(feature = "split")]
+    wasm_split::panic_hook::set_once();
 
     /*fmt()
         .with_writer(
@@ -31,24 +80,69 @@ pub fn main() {
     leptos::mount::mount_to_body(|| {
         let count = RwSignal::new(0);
         provide_context(count);
+        leptos_meta::provide_meta_context();
+        i18n::provide_i18n();
 
         view! {
             <Router>
-                <a href="/">"A"</a>
-                <a href="/b">"B"</a>
-                <a href="/c">"C"</a>
+                <RoutePreloads/>
+                <nav>
+                    <a href="/">"A"</a>
+                    <PrefetchLink href="/b">"B"</PrefetchLink>
+                    <PrefetchLink href="/c">"C"</PrefetchLink>
+                    <PrefetchLink href="/charts" prefetch=Prefetch::Visible>
+                        {t("nav-charts")}
+                    </PrefetchLink>
+                    <PrefetchLink href="/editor">{t("nav-editor")}</PrefetchLink>
+                    <PrefetchLink href="/data">{t("nav-data")}</PrefetchLink>
+                    <i18n::LocalePicker/>
+                </nav>
                 <Routes fallback=|| "Not found.">
                     <Route path=StaticSegment("") view=ViewA/>
                     <ParentRoute path=StaticSegment("") view={Lazy::<ViewB>::new()}>
                         <Route path=StaticSegment("b") view={Lazy::<ViewBChild>::new()}/>
                     </ParentRoute>
                     <Route path=StaticSegment("c") view={Lazy::<ViewC>::new()}/>
+                    <Route path=StaticSegment("charts") view={Lazy::<charts::ViewCharts>::new()}/>
+                    <Route path=StaticSegment("editor") view={Lazy::<editor::ViewEditor>::new()}/>
+                    <Route path=StaticSegment("data") view={Lazy::<data::ViewData>::new()}/>
+                    <Route path=StaticSegment("checks") view={Lazy::<checks::ViewChecks>::new()}/>
                 </Routes>
             </Router>
         }
     });
 }
 
+// Adds `<link rel="preload">` elements to the head for the chunks of the route being navigated to.
+// The location changes as soon as a navigation starts, while the lazy views of the new route are
+// still loading, so the browser fetches every chunk of the route at once, including ones that the
+// route only asks for later, such as `deserialize_comments` of `/c`.
+#[component]
+fn RoutePreloads() -> impl IntoView {
+    #[cfg(feature = "split")]
+    {
+        let location = use_location();
+        move || {
+            wasm_split::route_preload_links(&location.pathname.get())
+                .into_iter()
+                .map(|link| {
+                    // Without the loader's integrity, the browser would fetch the chunk again.
+                    view! {
+                        <Link
+                            rel="preload"
+                            as_="fetch"
+                            crossorigin="anonymous"
+                            type_="application/wasm"
+                            href=link.href().to_string()
+                            integrity=link.integrity().unwrap_or_default().to_string()
+                        />
+                    }
+                })
+                .collect_view()
+        }
+    }
+}
+
 // View A: A plain old synchronous route, just like they all currently work. The WASM binary code
 // for this is shipped as part of the main bundle.  Any data-loading code (like resources that run
 // in the body of the component) will be shipped as part of the main bundle.
@@ -69,37 +163,36 @@ pub struct ViewB;
 // This is important because it allows us to concurrently 1) load the route data, and 2) lazily
 // load the component, rather than creating a "waterfall" where we can't start loading the route
 // data until we've received the view.
-impl LazyRoute<Dom> for ViewB {
+//
+// `lazy_route` splits the view into a module of its own, which is loaded when navigating to the
+// route.
+#[cfg_attr(feature = "split", wasm_split::lazy_route(view_b, route = "/b"))]
+impl LazyRoute for ViewB {
     fn data() -> Self {
         Self
     }
 
-    // This is a bunch of boilerplate, which can be turned into a macro pretty easily.
-    async fn view(self) -> AnyView<Dom> {
-        #[cfg_attr(feature = "split", wasm_split::wasm_split(view_b))]
-        async fn view(this: ViewB) -> AnyView<Dom> {
-            view! {
-                <p>"View B"</p>
-                <hr/>
-                <Outlet/>
-            }
-            .into_any()
+    async fn view(_this: Self) -> AnyView {
+        view! {
+            <p>"View B"</p>
+            <hr/>
+            <Outlet/>
         }
-
-        view(self).await
+        .into_any()
     }
 }
 
 #[derive(Clone, Debug)]
 pub struct ViewBChild {
-    data: AsyncDerived<String>,
+    data: AsyncDerived<String, LocalStorage>,
 }
 
-impl LazyRoute<Dom> for ViewBChild {
+#[cfg_attr(feature = "split", wasm_split::lazy_route(view_b_child, route = "/b"))]
+impl LazyRoute for ViewBChild {
     fn data() -> Self {
         Self {
             data: AsyncDerived::new_unsync(|| async {
-                gloo_net::http::Request::get("https://jsonplaceholder.typicode.com/todos/1")
+                gloo_net::http::Request::get(&format!("{DATA_URL}/todos/1"))
                     .send()
                     .await
                     .unwrap()
@@ -112,25 +205,22 @@ impl LazyRoute<Dom> for ViewBChild {
 
     // Note that the view here takes the route data as its argument, which means you have
     // fully-typed access to the route data, in the view.
-    async fn view(self) -> AnyView<Dom> {
-        #[cfg_attr(feature = "split", wasm_split::wasm_split(view_b_child))]
-        async fn view(this: ViewBChild) -> AnyView<Dom> {
-            view! {
-                <p>"Nested Child"</p>
-                <Suspense fallback=|| "Loading...">
-                    <pre>{Suspend(this.data.into_future())}</pre>
-                </Suspense>
-            }
-            .into_any()
+    async fn view(this: Self) -> AnyView {
+        view! {
+            <p>"Nested Child"</p>
+            <Suspense fallback=|| "Loading...">
+                <pre>{Suspend::new(this.data.into_future())}</pre>
+            </Suspense>
         }
-
-        view(self).await
+        .into_any()
     }
 }
 
 // View C: A nested parent-child, each of which is lazy, and where the (deserialization-heavy)
 // data-loading function for the child is *also* lazy-loaded.
 #[derive(Debug, Clone, Deserialize)]
+// Only shown with `Debug` unless copied by `Transfer`.
+#[cfg_attr(not(feature = "split"), allow(dead_code))]
 pub struct Comment {
     #[serde(rename = "postId")]
     post_id: usize,
@@ -140,47 +230,67 @@ pub struct Comment {
     body: String,
 }
 
-#[cfg_attr(feature = "split", wasm_split::wasm_split(deserialize_comments))]
+// Copied back from the worker that deserializes them.
+#[cfg(feature = "split")]
+impl Transfer<'_> for Comment {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.post_id.encode(out);
+        self.id.encode(out);
+        self.name.encode(out);
+        self.email.encode(out);
+        self.body.encode(out);
+    }
+
+    fn decode(input: &mut &[u8]) -> Self {
+        Self {
+            post_id: Transfer::decode(input),
+            id: Transfer::decode(input),
+            name: Transfer::decode(input),
+            email: Transfer::decode(input),
+            body: Transfer::decode(input),
+        }
+    }
+}
+
+#[cfg_attr(
+    feature = "split",
+    wasm_split::wasm_split(deserialize_comments, worker, route = "/c")
+)]
 fn deserialize_comments(data: &str) -> Vec<Comment> {
     serde_json::from_str(data).unwrap()
 }
 
 #[derive(Clone, Debug)]
 pub struct ViewC {
-    data: AsyncDerived<Vec<Comment>>,
+    data: AsyncDerived<Vec<Comment>, LocalStorage>,
 }
 
-impl LazyRoute<Dom> for ViewC {
+#[cfg_attr(feature = "split", wasm_split::lazy_route(view_c, route = "/c"))]
+impl LazyRoute for ViewC {
     fn data() -> Self {
         Self {
             data: AsyncDerived::new_unsync(|| async move {
-                let data =
-                    gloo_net::http::Request::get("https://jsonplaceholder.typicode.com/comments")
-                        .send()
-                        .await
-                        .unwrap()
-                        .text()
-                        .await
-                        .unwrap();
-                deserialize_comments(&data).await
+                let data = gloo_net::http::Request::get(&format!("{DATA_URL}/comments"))
+                    .send()
+                    .await
+                    .unwrap()
+                    .text()
+                    .await
+                    .unwrap();
+                split_call!(deserialize_comments(&data))
             }),
         }
     }
 
-    async fn view(self) -> AnyView<Dom> {
-        #[cfg_attr(feature = "split", wasm_split::wasm_split(view_c))]
-        async fn view(this: ViewC) -> AnyView<Dom> {
-            view! {
-                <p>"Nested Child"</p>
-                <Suspense fallback=|| "Loading...">
-                    <pre>{Suspend(async move {
-                        format!("{:#?}", this.data.await)
-                    })}</pre>
-                </Suspense>
-            }
-            .into_any()
+    async fn view(this: Self) -> AnyView {
+        view! {
+            <p>"Nested Child"</p>
+            <Suspense fallback=|| "Loading...">
+                <pre>{Suspend::new(async move {
+                    format!("{:#?}", this.data.await)
+                })}</pre>
+            </Suspense>
         }
-
-        view(self).await
+        .into_any()
     }
 }
@@ -0,0 +1,116 @@
+// `<PrefetchLink>`: a link that starts loading the chunks of the route it points to before it is
+// followed, so that the navigation renders without waiting for the network, as the links of JS
+// frameworks do. Without the `split` feature, there is nothing to load, and it is a plain link.
+
+use std::time::Duration;
+
+use leptos::{html, prelude::*};
+use send_wrapper::SendWrapper;
+use wasm_bindgen::{closure::Closure, JsCast};
+
+/// When a [`PrefetchLink`] preloads the chunks of its route.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Prefetch {
+    /// Once the pointer has rested on the link for the delay, or right away when the link gets
+    /// the focus or is touched.
+    #[default]
+    Intent,
+    /// As soon as the link scrolls into view, for links that are likely to be followed, such as
+    /// the next step of a flow, and on intent.
+    Visible,
+    Never,
+}
+
+/// A link to `href` that preloads the chunks of its route with `wasm_split::preload_route`.
+///
+/// Hovering only counts once the pointer rests on the link for `delay`, so that moving it across
+/// a menu doesn't preload every route on the way. Routes that are already loaded cost nothing.
+#[component]
+pub fn PrefetchLink(
+    href: &'static str,
+    #[prop(optional)] prefetch: Prefetch,
+    #[prop(default = Duration::from_millis(80))] delay: Duration,
+    children: Children,
+) -> impl IntoView {
+    let prefetch = if cfg!(feature = "split") {
+        prefetch
+    } else {
+        Prefetch::Never
+    };
+    let link = NodeRef::<html::A>::new();
+    let pending = StoredValue::new(None::<TimeoutHandle>);
+    let cancel = move || {
+        if let Some(handle) = pending.try_update_value(Option::take).flatten() {
+            handle.clear();
+        }
+    };
+    let preload = move || {
+        cancel();
+        preload_route(href);
+    };
+    let on_hover = move |_| {
+        if prefetch != Prefetch::Never {
+            cancel();
+            pending.set_value(set_timeout_with_handle(preload, delay).ok());
+        }
+    };
+    let on_intent = move || {
+        if prefetch != Prefetch::Never {
+            preload();
+        }
+    };
+    if prefetch == Prefetch::Visible {
+        Effect::new(move |_| {
+            if let Some(element) = link.get() {
+                preload_once_visible(&element, href);
+            }
+        });
+    }
+    on_cleanup(cancel);
+
+    view! {
+        <a
+            href=href
+            node_ref=link
+            on:pointerenter=on_hover
+            on:pointerleave=move |_| cancel()
+            on:focus=move |_| on_intent()
+            on:touchstart=move |_| on_intent()
+        >
+            {children()}
+        </a>
+    }
+}
+
+fn preload_route(href: &str) {
+    #[cfg(feature = "split")]
+    wasm_split::preload_route(href);
+    #[cfg(not(feature = "split"))]
+    let _ = href;
+}
+
+/// Preloads the chunks of the route of `href` once `element` intersects the viewport, observing
+/// it until then or until the link is cleaned up.
+fn preload_once_visible(element: &web_sys::Element, href: &'static str) {
+    let callback = Closure::<dyn FnMut(js_sys::Array, web_sys::IntersectionObserver)>::new(
+        move |entries: js_sys::Array, observer: web_sys::IntersectionObserver| {
+            let visible = entries.iter().any(|entry| {
+                entry
+                    .unchecked_into::<web_sys::IntersectionObserverEntry>()
+                    .is_intersecting()
+            });
+            if visible {
+                observer.disconnect();
+                preload_route(href);
+            }
+        },
+    );
+    let Ok(observer) = web_sys::IntersectionObserver::new(callback.as_ref().unchecked_ref()) else {
+        // Without `IntersectionObserver`, the link still preloads on intent.
+        return;
+    };
+    observer.observe(element);
+    // The callback must live as long as the observer may call it.
+    let observer = SendWrapper::new((observer, callback));
+    on_cleanup(move || observer.0.disconnect());
+}
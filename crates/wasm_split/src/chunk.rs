@@ -0,0 +1,277 @@
+use alloc::{boxed::Box, string::String, vec, vec::Vec};
+use core::ffi::c_void;
+
+use core::sync::atomic::Ordering;
+
+use crate::{
+    loader::{LoadCallbackFn, SplitLoader, SplitLoaderFuture, UNLOADS},
+    trace, LoadError,
+};
+
+#[link(wasm_import_module = "./__wasm_split.js")]
+extern "C" {
+    fn __wasm_split_load_chunk(
+        name: *const u8,
+        len: usize,
+        callback: LoadCallbackFn,
+        data: *const c_void,
+    );
+    fn __wasm_split_drop_module(name: *const u8, len: usize);
+    fn __wasm_split_unload(name: *const u8, len: usize) -> u32;
+    fn __wasm_split_reload(
+        name: *const u8,
+        len: usize,
+        callback: LoadCallbackFn,
+        data: *const c_void,
+    );
+    fn __wasm_split_abort_loads(names: *const u8, len: usize) -> u32;
+    fn __wasm_split_preload(name: *const u8, len: usize);
+    fn __wasm_split_hydrate_preload(names: *const u8, len: usize);
+    fn __wasm_split_is_loaded(name: *const u8, len: usize) -> u32;
+    fn __wasm_split_loaded_chunks(buf: *mut u8, capacity: usize) -> usize;
+    fn __wasm_split_load_group(
+        names: *const u8,
+        len: usize,
+        callback: LoadCallbackFn,
+        data: *const c_void,
+    );
+}
+
+/// Handle to a split chunk, identified by the module name given to
+/// `#[wasm_split(name)]`.
+///
+/// Loading a chunk makes every `#[wasm_split]` function of that module
+/// callable without further network requests. Chunks are only ever loaded
+/// once, so a chunk may be loaded any number of times, concurrently or not:
+/// loads that start while another is in progress, including those of calls
+/// of its functions, wait for that one rather than fetching the chunk again.
+/// Use [`load_group`] to load several chunks at once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SplitChunk {
+    name: &'static str,
+}
+
+impl SplitChunk {
+    pub const fn new(name: &'static str) -> Self {
+        Self { name }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Loads the chunk along with the chunks it depends on.
+    pub async fn load(&self) -> Result<(), LoadError> {
+        let name = self.name;
+        #[cfg(feature = "rust-loader")]
+        let future = crate::rust_loader::load(vec![name.into()]);
+        #[cfg(not(feature = "rust-loader"))]
+        let future =
+            SplitLoaderFuture::new(SplitLoader::new(Box::new(move |callback, data| unsafe {
+                __wasm_split_load_chunk(name.as_ptr(), name.len(), callback, data)
+            })));
+        trace::load(name, future).await.map(|_| ())
+    }
+
+    /// Starts loading the chunk without waiting for it; see [`preload`].
+    pub fn preload(&self) {
+        preload(self.name)
+    }
+
+    /// See [`is_loaded`].
+    pub fn is_loaded(&self) -> bool {
+        is_loaded(self.name)
+    }
+
+    /// See [`load_failure`](crate::load_failure).
+    pub fn last_failure(&self) -> Option<crate::LoadFailure> {
+        crate::load_failure(self.name)
+    }
+
+    /// Cancels the download of the chunk if it is in flight; see
+    /// [`abort_loads`].
+    pub fn abort(&self) -> bool {
+        abort_loads(&[self.name]) != 0
+    }
+
+    /// See [`unload`].
+    pub fn unload(&self) -> bool {
+        unload(self.name)
+    }
+
+    /// See [`reload`].
+    pub async fn reload(&self) -> Result<(), LoadError> {
+        reload(self.name).await
+    }
+}
+
+/// Whether the split module `name` is loaded, so that calls of its functions
+/// run without waiting for the network, e.g. to only offer a heavy editor
+/// right away if its chunk is already resident. Folded modules are part of
+/// the main module and always loaded, while unknown and dropped ones never
+/// are. Loads in progress do not count until they have completed.
+pub fn is_loaded(name: &str) -> bool {
+    #[cfg(feature = "rust-loader")]
+    let loaded = crate::rust_loader::is_loaded(name);
+    #[cfg(not(feature = "rust-loader"))]
+    let loaded = unsafe { __wasm_split_is_loaded(name.as_ptr(), name.len()) != 0 };
+    loaded
+}
+
+/// Names of the chunks that are loaded, as in the manifest, in the order in
+/// which they were. Shared chunks, which the loader loads before the split
+/// modules that need them, are included, folded modules are not.
+pub fn loaded_chunks() -> impl Iterator<Item = String> {
+    #[cfg(feature = "rust-loader")]
+    let chunks: Vec<String> = crate::rust_loader::loaded_chunks();
+    #[cfg(not(feature = "rust-loader"))]
+    let chunks = {
+        let mut buf = vec![0u8; 256];
+        loop {
+            let len = unsafe { __wasm_split_loaded_chunks(buf.as_mut_ptr(), buf.len()) };
+            if len <= buf.len() {
+                buf.truncate(len);
+                break;
+            }
+            buf.resize(len, 0);
+        }
+        String::from_utf8_lossy(&buf)
+            .lines()
+            .map(String::from)
+            .collect::<Vec<_>>()
+    };
+    chunks.into_iter()
+}
+
+/// Starts loading the chunk of a split module in the background, e.g. when
+/// the pointer enters a link to the route that needs it, so that its first
+/// call doesn't have to wait for all of the download.
+///
+/// Calls of the module's `#[wasm_split]` functions, and [`SplitChunk::load`],
+/// then wait for the load in progress instead of starting another. A failed
+/// preload is only logged to the console, and the next call loads the chunk
+/// again. Names of unknown or dropped modules are ignored, so that a page
+/// can preload the modules of its links without checking them first.
+///
+/// The chunk is downloaded right away but only compiled once the browser is
+/// idle, so that a preload never causes a long task while the page is busy,
+/// unless a call needs the chunk first. Compile times are recorded as
+/// `wasm-split:chunk-compile` performance measures.
+pub fn preload(name: &str) {
+    trace::lifecycle("preload", name, true);
+    #[cfg(feature = "rust-loader")]
+    crate::rust_loader::preload(name);
+    #[cfg(not(feature = "rust-loader"))]
+    unsafe {
+        __wasm_split_preload(name.as_ptr(), name.len())
+    }
+}
+
+/// Starts loading the chunks of the split modules `names` that the route
+/// rendered by the server needs, before the page is hydrated, so that
+/// hydration does not stop at each of them as it first calls into it.
+///
+/// Unlike [`preload`], the chunks are compiled and instantiated as soon as
+/// they are downloaded, all in parallel as by [`load_group`]. Call it first
+/// thing in the hydration entry point, with the modules that the server
+/// looked up for the page with `PreloadHints::hydrate_modules` of
+/// `wasm_split_server`. If the page also has the route's preload tags, the
+/// chunks are downloaded while the main module is, and only compiled here.
+/// As with [`preload`], unknown names are ignored, and failures only logged.
+pub fn hydrate_preload(names: &[&str]) {
+    let names = names.join(",");
+    trace::lifecycle("hydrate_preload", &names, true);
+    unsafe { __wasm_split_hydrate_preload(names.as_ptr(), names.len()) }
+}
+
+/// Cancels the downloads of the chunks of the split modules `names` that are
+/// still in flight, e.g. once the user navigated away from the route that
+/// needs them, so that they don't take bandwidth from what the next route
+/// needs. Returns the number of downloads cancelled.
+///
+/// Loads waiting for the chunks, including calls of their functions, fail
+/// with [`LoadError::Aborted`], which `fallible` functions return and others
+/// panic with, so only abort chunks whose calls were dropped as well, as
+/// routers drop the views of the previous route. The next load of a chunk
+/// starts over. Chunks that have been downloaded are no longer affected, and
+/// unknown names are ignored.
+pub fn abort_loads(names: &[&str]) -> usize {
+    let names = names.join(",");
+    let aborted = unsafe { __wasm_split_abort_loads(names.as_ptr(), names.len()) as usize };
+    trace::lifecycle("abort", &names, aborted != 0);
+    aborted
+}
+
+/// Loads several chunks, such as the chunks of a route and its child routes,
+/// as one batch.
+///
+/// All of the chunks and their dependencies are fetched in parallel and then
+/// instantiated in dependency order, so chunks shared between them are only
+/// loaded once. Fails without loading anything if any name is unknown.
+pub async fn load_group(names: &[&str]) -> Result<(), LoadError> {
+    #[cfg(feature = "rust-loader")]
+    let future = crate::rust_loader::load(names.iter().map(|name| name.to_string()).collect());
+    let names = names.join(",");
+    // `names` lives until the load completes, as the load only starts when the
+    // future is first polled below.
+    #[cfg(not(feature = "rust-loader"))]
+    let (ptr, len) = (names.as_ptr(), names.len());
+    #[cfg(not(feature = "rust-loader"))]
+    let future = SplitLoaderFuture::new(SplitLoader::new(Box::new(move |callback, data| unsafe {
+        __wasm_split_load_group(ptr, len, callback, data)
+    })));
+    trace::load(&names, future).await.map(|_| ())
+}
+
+/// Releases the loader's resources for a chunk that will never be needed
+/// again, to reduce memory pressure on low-end devices.
+///
+/// The code of an already loaded chunk cannot be freed and remains callable.
+/// A chunk that has not been loaded can no longer be loaded afterwards, and
+/// neither can chunks that depend on it; such loads fail with
+/// [`LoadError::UnknownChunk`]. Panics in a dropped chunk are no longer
+/// attributed to it by [`panic_hook`](crate::panic_hook).
+pub fn drop_module(name: &str) {
+    trace::lifecycle("drop", name, true);
+    unsafe { __wasm_split_drop_module(name.as_ptr(), name.len()) }
+}
+
+/// Unloads the chunk of a split module that is no longer needed, e.g. that
+/// of a panel that the user closed, so that long-lived sessions do not keep
+/// every chunk they ever visited. Calls of its functions load it again, as if
+/// it had never been loaded. Returns whether it was unloaded.
+///
+/// The loader empties the table slots that the chunk filled in, and forgets
+/// its instance and compiled module, which the browser can then collect. It
+/// only does so if nothing else can call into the chunk any more: no loaded
+/// chunk that depends on it, and no `fn` pointer or `dyn Trait` object of
+/// one of its functions, unless the build was split with
+/// `--lazy-indirect-calls`, whose stubs load the chunk again on such a call.
+/// Otherwise, and for chunks that are not loaded yet, folded, dropped or
+/// unknown, nothing happens, and the loader logs why to the console.
+///
+/// The statics of the module, which are in the memory of the main module,
+/// are not freed, and loading the chunk again initializes them anew and runs
+/// its `on_load` hooks again.
+pub fn unload(name: &str) -> bool {
+    let unloaded = unsafe { __wasm_split_unload(name.as_ptr(), name.len()) != 0 };
+    trace::lifecycle("unload", name, unloaded);
+    if unloaded {
+        UNLOADS.fetch_add(1, Ordering::Relaxed);
+    }
+    unloaded
+}
+
+/// Unloads the chunk of a split module as [`unload`] does, and loads it again
+/// without the browser's HTTP cache, e.g. to pick up a rebuilt chunk while
+/// developing with hot reloading. A chunk that is not loaded is only loaded.
+/// Fails with [`LoadError::InUse`] if the chunk cannot be unloaded.
+pub async fn reload(name: &str) -> Result<(), LoadError> {
+    let (ptr, len) = (name.as_ptr(), name.len());
+    let future = SplitLoaderFuture::new(SplitLoader::new(Box::new(move |callback, data| {
+        unsafe { __wasm_split_reload(ptr, len, callback, data) };
+        // The loader unloads the chunk right away, before loading it anew.
+        UNLOADS.fetch_add(1, Ordering::Relaxed);
+    })));
+    trace::load(name, future).await.map(|_| ())
+}
@@ -0,0 +1,149 @@
+use alloc::{format, string::String};
+
+#[link(wasm_import_module = "./__wasm_split.js")]
+extern "C" {
+    fn __wasm_split_configure(settings: *const u8, len: usize);
+}
+
+/// Priority class of a chunk's fetches, as given by `priority = "..."` of
+/// `#[wasm_split]`. A chunk is only fetched once no chunk of a more urgent
+/// class is queued or being fetched, and browsers get the class as the
+/// `priority` hint of the request.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum Priority {
+    Critical,
+    #[default]
+    High,
+    Low,
+}
+
+impl Priority {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Critical => "critical",
+            Self::High => "high",
+            Self::Low => "low",
+        }
+    }
+}
+
+/// Whether requests for chunks and manifests send cookies and HTTP
+/// authentication, as the `credentials` option of `fetch`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Credentials {
+    Omit,
+    SameOrigin,
+    Include,
+}
+
+impl Credentials {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Omit => "omit",
+            Self::SameOrigin => "same-origin",
+            Self::Include => "include",
+        }
+    }
+}
+
+/// Settings of the loader for [`configure`], which leaves those that are not
+/// set unchanged.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LoaderConfig {
+    settings: String,
+}
+
+impl LoaderConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Most chunks fetched at once, from their request until they are
+    /// compiled, or 0 for no limit, which `max-concurrent-fetches` of the
+    /// `[loader]` table in `wasm-split.toml` sets for the start of the app.
+    pub fn max_concurrent_fetches(mut self, max: u32) -> Self {
+        self.settings += &format!("max-concurrent-fetches {max}\n");
+        self
+    }
+
+    /// Time after which the load of a chunk that has not been fetched and
+    /// compiled yet is cancelled and fails with [`LoadError::Timeout`], or 0
+    /// to wait as long as the browser does, which `timeout-ms` of the
+    /// `[loader]` table sets for the start of the app.
+    ///
+    /// [`LoadError::Timeout`]: crate::LoadError::Timeout
+    pub fn timeout_ms(mut self, ms: u32) -> Self {
+        self.settings += &format!("timeout-ms {ms}\n");
+        self
+    }
+
+    /// Fetches the chunk of the split module `name` with `priority` instead
+    /// of the priority that its `#[wasm_split]` attributes give it.
+    pub fn priority(mut self, name: &str, priority: Priority) -> Self {
+        self.settings += &format!("priority {name} {}\n", priority.as_str());
+        self
+    }
+
+    /// Requests chunks and manifests with `credentials`, which `credentials`
+    /// of the `[loader]` table sets for the start of the app.
+    pub fn credentials(mut self, credentials: Credentials) -> Self {
+        self.settings += &format!("credentials {}\n", credentials.as_str());
+        self
+    }
+
+    /// Adds the header `name` to the requests for chunks and manifests, such
+    /// as an `Authorization` header for chunks served from an authenticated
+    /// endpoint, replacing any that an earlier call added.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.settings += &format!("header {name} {value}\n");
+        self
+    }
+
+    /// No longer adds the header `name` that [`header`](Self::header) added,
+    /// e.g. once the user signed out.
+    pub fn remove_header(mut self, name: &str) -> Self {
+        self.settings += &format!("remove-header {name}\n");
+        self
+    }
+}
+
+/// Changes how the loader fetches chunks from now on, e.g. once the app
+/// knows that the connection is slow, so that the chunks of a route don't
+/// starve the route's data requests:
+///
+/// ```ignore
+/// wasm_split::configure(
+///     wasm_split::LoaderConfig::new()
+///         .max_concurrent_fetches(2)
+///         .priority("comments", wasm_split::Priority::Low),
+/// );
+/// ```
+///
+/// Loads already in flight keep their priority.
+pub fn configure(config: LoaderConfig) {
+    unsafe { __wasm_split_configure(config.settings.as_ptr(), config.settings.len()) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Credentials, LoaderConfig, Priority};
+
+    #[test]
+    fn encodes_settings_as_lines() {
+        let config = LoaderConfig::new()
+            .max_concurrent_fetches(2)
+            .timeout_ms(10_000)
+            .priority("view_b", Priority::Critical)
+            .credentials(Credentials::SameOrigin)
+            .header("Authorization", "Bearer abc 123")
+            .remove_header("X-Session");
+        assert_eq!(
+            config.settings,
+            "max-concurrent-fetches 2\ntimeout-ms 10000\npriority view_b critical\n\
+             credentials same-origin\nheader Authorization Bearer abc 123\n\
+             remove-header X-Session\n"
+        );
+    }
+}
@@ -0,0 +1,270 @@
+use alloc::{string::String, vec};
+use core::fmt;
+
+#[link(wasm_import_module = "./__wasm_split.js")]
+extern "C" {
+    fn __wasm_split_load_failure(
+        name: *const u8,
+        len: usize,
+        buf: *mut u8,
+        capacity: usize,
+    ) -> usize;
+}
+
+/// Error returned when a split chunk could not be loaded.
+///
+/// Further details, such as the underlying JS exception, are logged to the
+/// console by the loader.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LoadError {
+    /// No split chunk with the requested name exists in this build.
+    UnknownChunk,
+    /// The request for the chunk failed without a response, e.g. because the
+    /// device is offline.
+    Network,
+    /// The server responded with a non-success HTTP status.
+    Http(u16),
+    /// The chunk did not match the integrity metadata it was loaded with.
+    IntegrityMismatch,
+    /// The chunk is not a valid WebAssembly module.
+    CompileError,
+    /// The chunk trapped or panicked during instantiation, or was built with
+    /// other signatures of its split functions than the main module.
+    InstantiationError,
+    /// Loading the chunk took longer than the loader's `timeout-ms` allows.
+    Timeout,
+    /// Loading the chunk was cancelled, e.g. by [`abort_loads`].
+    ///
+    /// [`abort_loads`]: crate::abort_loads
+    Aborted,
+    /// The browser lacks a feature required to load the chunk.
+    UnsupportedFeature,
+    /// The chunk failed to link against the main module, e.g. because it
+    /// imports something that the main module does not export, as a chunk of
+    /// another build might. [`LoadFailure::import`] names the import.
+    LinkError,
+    /// The chunk was not loaded when a `sync` function was called with its
+    /// `try_<name>_sync` companion, which does not load it.
+    NotLoaded,
+    /// The chunk could not be unloaded for [`reload`], as other code may
+    /// still call into it; see [`unload`].
+    ///
+    /// [`reload`]: crate::reload
+    /// [`unload`]: crate::unload
+    InUse,
+    /// The loader reported an error code that this version of the crate does
+    /// not know, e.g. because the loader was generated by a newer
+    /// `wasm-split`.
+    Unknown(u32),
+}
+
+impl LoadError {
+    /// Whether retrying the load may succeed, i.e. the error is likely caused
+    /// by a transient condition rather than a broken or incompatible build.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            LoadError::Network | LoadError::Timeout | LoadError::Aborted | LoadError::NotLoaded => {
+                true
+            }
+            LoadError::Http(status) => *status == 408 || *status == 429 || *status >= 500,
+            LoadError::UnknownChunk
+            | LoadError::IntegrityMismatch
+            | LoadError::CompileError
+            | LoadError::InstantiationError
+            | LoadError::UnsupportedFeature
+            | LoadError::LinkError
+            | LoadError::InUse
+            | LoadError::Unknown(_) => false,
+        }
+    }
+
+    /// Decodes the error reported by the JS loader through a load callback,
+    /// returning `None` on success.
+    pub(crate) fn from_code(code: u32, detail: u32) -> Option<Self> {
+        Some(match code {
+            0 => return None,
+            1 => LoadError::UnknownChunk,
+            2 => LoadError::Network,
+            3 => LoadError::Http(detail as u16),
+            4 => LoadError::IntegrityMismatch,
+            5 => LoadError::CompileError,
+            6 => LoadError::InstantiationError,
+            7 => LoadError::Timeout,
+            8 => LoadError::Aborted,
+            9 => LoadError::UnsupportedFeature,
+            10 => LoadError::LinkError,
+            11 => LoadError::InUse,
+            code => LoadError::Unknown(code),
+        })
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::UnknownChunk => write!(f, "unknown split chunk"),
+            LoadError::Network => write!(f, "network error"),
+            LoadError::Http(status) => write!(f, "HTTP status {status}"),
+            LoadError::IntegrityMismatch => write!(f, "integrity mismatch"),
+            LoadError::CompileError => write!(f, "invalid WebAssembly module"),
+            LoadError::InstantiationError => write!(f, "instantiation failed"),
+            LoadError::Timeout => write!(f, "timed out"),
+            LoadError::Aborted => write!(f, "aborted"),
+            LoadError::UnsupportedFeature => write!(f, "unsupported browser feature"),
+            LoadError::LinkError => write!(f, "failed to link against the main module"),
+            LoadError::NotLoaded => write!(f, "not loaded"),
+            LoadError::InUse => write!(f, "in use, so it cannot be unloaded"),
+            LoadError::Unknown(code) => write!(f, "unknown load error code {code}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for LoadError {}
+
+/// What the loader knows about the failed load of a chunk, as returned by
+/// [`load_failure`], for error reports that make failures in production
+/// debuggable. The loader logs the same to the console as it fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadFailure {
+    chunk: String,
+    url: String,
+    size: Option<u32>,
+    error: LoadError,
+    import: Option<String>,
+    message: String,
+}
+
+impl LoadFailure {
+    pub fn chunk(&self) -> &str {
+        &self.chunk
+    }
+
+    /// URL that the chunk was requested from.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Size of the chunk according to the manifest.
+    pub fn size(&self) -> Option<u32> {
+        self.size
+    }
+
+    pub fn error(&self) -> LoadError {
+        self.error
+    }
+
+    /// Name of the import that a [`LoadError::LinkError`] is about, if the
+    /// browser's message names it.
+    pub fn import(&self) -> Option<&str> {
+        self.import.as_deref()
+    }
+
+    /// Message of the exception that the load failed with.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    fn decode(lines: &str) -> Option<Self> {
+        let mut fields = lines.splitn(7, '\n');
+        let mut next = || fields.next();
+        let chunk = next()?.into();
+        let url = next()?.into();
+        let size = next()?.parse().ok();
+        let code = next()?.parse().ok()?;
+        let status = next()?.parse().unwrap_or(0);
+        let import = next()?;
+        Some(Self {
+            chunk,
+            url,
+            size,
+            error: LoadError::from_code(code, status)?,
+            import: (!import.is_empty()).then(|| import.into()),
+            message: next()?.into(),
+        })
+    }
+}
+
+impl fmt::Display for LoadFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "failed to load chunk {} from {}", self.chunk, self.url)?;
+        if let Some(size) = self.size {
+            write!(f, " ({size} bytes)")?;
+        }
+        write!(f, ": {}: {}", self.error, self.message)
+    }
+}
+
+/// What the loader knows about the last load of the split module `name`, if
+/// it failed and was not retried since: the URL and size of the chunk, the
+/// [`LoadError`], and the message of the underlying exception, e.g. for an
+/// error report after a `fallible` call returned an error:
+///
+/// ```ignore
+/// if let Err(error) = view_b().await {
+///     if let Some(failure) = wasm_split::load_failure("view_b") {
+///         report_error(&failure.to_string());
+///     }
+/// }
+/// ```
+pub fn load_failure(name: &str) -> Option<LoadFailure> {
+    let mut buf = vec![0u8; 256];
+    loop {
+        let len = unsafe {
+            __wasm_split_load_failure(name.as_ptr(), name.len(), buf.as_mut_ptr(), buf.len())
+        };
+        if len == 0 {
+            return None;
+        }
+        if len <= buf.len() {
+            buf.truncate(len);
+            break;
+        }
+        buf.resize(len, 0);
+    }
+    LoadFailure::decode(&String::from_utf8_lossy(&buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::{LoadError, LoadFailure};
+
+    #[test]
+    fn decodes_unknown_codes_as_unknown() {
+        assert_eq!(LoadError::from_code(0, 0), None);
+        assert_eq!(
+            LoadError::from_code(9, 0),
+            Some(LoadError::UnsupportedFeature)
+        );
+        assert_eq!(LoadError::from_code(42, 0), Some(LoadError::Unknown(42)));
+    }
+
+    #[test]
+    fn decodes_load_failures() {
+        let failure = LoadFailure::decode(
+            "view_b\nhttps://example.com/view_b.wasm\n1200\n10\n\n__wasm_split_load_x\n\
+             Import #0 \"env\" \"__wasm_split_load_x\": function import requires a callable",
+        )
+        .unwrap();
+        assert_eq!(failure.chunk(), "view_b");
+        assert_eq!(failure.size(), Some(1200));
+        assert_eq!(failure.error(), LoadError::LinkError);
+        assert_eq!(failure.import(), Some("__wasm_split_load_x"));
+        assert_eq!(
+            failure.to_string(),
+            "failed to load chunk view_b from https://example.com/view_b.wasm (1200 bytes): \
+             failed to link against the main module: Import #0 \"env\" \"__wasm_split_load_x\": \
+             function import requires a callable"
+        );
+        let failure = LoadFailure::decode(
+            "view_b\nhttps://example.com/view_b.wasm\n\n3\n404\n\nHTTP status 404",
+        )
+        .unwrap();
+        assert_eq!(failure.error(), LoadError::Http(404));
+        assert_eq!(failure.size(), None);
+        assert_eq!(failure.import(), None);
+    }
+}
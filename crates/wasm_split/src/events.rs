@@ -0,0 +1,183 @@
+use alloc::{boxed::Box, rc::Rc, vec::Vec};
+use core::{cell::RefCell, ffi::c_void};
+
+use crate::LoadError;
+
+type EventCallbackFn = unsafe extern "C" fn(*const c_void, u32, u32, u32);
+
+#[link(wasm_import_module = "./__wasm_split.js")]
+extern "C" {
+    fn __wasm_split_subscribe(callback: EventCallbackFn, data: *const c_void) -> u32;
+    fn __wasm_split_unsubscribe(id: u32);
+    fn __wasm_split_event_chunk_len() -> usize;
+    fn __wasm_split_take_event_chunk(ptr: *mut u8);
+}
+
+/// Event codes passed to the callback, matching `LOAD_EVENT` in the loader.
+const STARTED: u32 = 0;
+const PROGRESS: u32 = 1;
+const INSTANTIATED: u32 = 2;
+const FAILED: u32 = 3;
+
+/// `total` of a [`LoadEvent::Progress`] when the size is not known.
+const UNKNOWN_TOTAL: u32 = u32::MAX;
+
+/// A step of the load of a chunk, passed to the listeners registered with
+/// [`on_event`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LoadEvent<'a> {
+    /// The chunk started to load, after dependencies of its own if any,
+    /// which have events of their own.
+    Started { chunk: &'a str },
+    /// `bytes` of the chunk have been downloaded so far. `total` is its size
+    /// according to the manifest or else the response, or `None` if neither
+    /// has it.
+    Progress {
+        chunk: &'a str,
+        bytes: u32,
+        total: Option<u32>,
+    },
+    /// The chunk was instantiated, and its functions can be called.
+    Instantiated { chunk: &'a str },
+    /// The load failed, and the next call loads the chunk again.
+    Failed { chunk: &'a str, error: LoadError },
+}
+
+impl<'a> LoadEvent<'a> {
+    pub fn chunk(&self) -> &'a str {
+        match *self {
+            Self::Started { chunk }
+            | Self::Progress { chunk, .. }
+            | Self::Instantiated { chunk }
+            | Self::Failed { chunk, .. } => chunk,
+        }
+    }
+
+    fn decode(chunk: &'a str, code: u32, a: u32, b: u32) -> Option<Self> {
+        Some(match code {
+            STARTED => Self::Started { chunk },
+            PROGRESS => Self::Progress {
+                chunk,
+                bytes: a,
+                total: (b != UNKNOWN_TOTAL).then_some(b),
+            },
+            INSTANTIATED => Self::Instantiated { chunk },
+            FAILED => Self::Failed {
+                chunk,
+                error: LoadError::from_code(a, b)?,
+            },
+            _ => return None,
+        })
+    }
+}
+
+type Listener = RefCell<Box<dyn FnMut(LoadEvent)>>;
+
+/// Listener registered with [`on_event`], which stops receiving events when
+/// dropped.
+#[must_use = "the listener is unregistered when the subscription is dropped"]
+pub struct EventSubscription {
+    id: u32,
+    listener: *const Listener,
+}
+
+impl EventSubscription {
+    /// Keeps the listener registered for the lifetime of the app.
+    pub fn forget(self) {
+        core::mem::forget(self);
+    }
+}
+
+impl Drop for EventSubscription {
+    fn drop(&mut self) {
+        unsafe {
+            __wasm_split_unsubscribe(self.id);
+            // A dispatch in progress holds another reference.
+            drop(Rc::from_raw(self.listener));
+        }
+    }
+}
+
+// Not derived, as the listener itself need not be `Debug`.
+impl core::fmt::Debug for EventSubscription {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("EventSubscription")
+            .field("id", &self.id)
+            .finish_non_exhaustive()
+    }
+}
+
+unsafe extern "C" fn dispatch(listener: *const c_void, code: u32, a: u32, b: u32) {
+    let mut chunk = Vec::new();
+    unsafe {
+        chunk.resize(__wasm_split_event_chunk_len(), 0);
+        __wasm_split_take_event_chunk(chunk.as_mut_ptr());
+    }
+    let Ok(chunk) = core::str::from_utf8(&chunk) else {
+        return;
+    };
+    let Some(event) = LoadEvent::decode(chunk, code, a, b) else {
+        return;
+    };
+    let listener = unsafe {
+        Rc::increment_strong_count(listener as *const Listener);
+        Rc::from_raw(listener as *const Listener)
+    };
+    // Already borrowed for an event that the listener caused itself.
+    if let Ok(mut listener) = listener.try_borrow_mut() {
+        listener(event);
+    };
+}
+
+/// Calls `listener` with every [`LoadEvent`] of every chunk, e.g. to show a
+/// global loading bar while any chunk is loading, or to report slow loads to
+/// analytics.
+///
+/// ```ignore
+/// wasm_split::on_event(|event| match event {
+///     LoadEvent::Progress { bytes, total: Some(total), .. } => {
+///         set_progress(bytes as f64 / total as f64)
+///     }
+///     LoadEvent::Instantiated { .. } | LoadEvent::Failed { .. } => set_progress(1.0),
+///     _ => {}
+/// })
+/// .forget();
+/// ```
+///
+/// Chunks compiled by another entry of the page have no progress events.
+/// Events that the listener causes itself, e.g. by preloading a chunk, are not
+/// passed to it.
+pub fn on_event(listener: impl FnMut(LoadEvent) + 'static) -> EventSubscription {
+    let listener = Rc::into_raw(Rc::new(RefCell::new(
+        Box::new(listener) as Box<dyn FnMut(LoadEvent)>
+    )));
+    let id = unsafe { __wasm_split_subscribe(dispatch, listener as *const c_void) };
+    EventSubscription { id, listener }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_events_of_loader() {
+        assert_eq!(
+            LoadEvent::decode("first", PROGRESS, 100, UNKNOWN_TOTAL),
+            Some(LoadEvent::Progress {
+                chunk: "first",
+                bytes: 100,
+                total: None
+            })
+        );
+        assert_eq!(
+            LoadEvent::decode("first", FAILED, 3, 404),
+            Some(LoadEvent::Failed {
+                chunk: "first",
+                error: LoadError::Http(404)
+            })
+        );
+        assert_eq!(LoadEvent::decode("first", FAILED, 0, 0), None);
+        assert_eq!(LoadEvent::decode("first", 99, 0, 0), None);
+    }
+}
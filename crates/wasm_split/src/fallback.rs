@@ -0,0 +1,107 @@
+use alloc::boxed::Box;
+use core::{
+    ffi::c_void,
+    future::{poll_fn, Future},
+    pin::pin,
+    task::Poll,
+};
+
+use crate::loader::{LoadCallbackFn, SplitLoader, SplitLoaderFuture};
+
+#[link(wasm_import_module = "./__wasm_split.js")]
+extern "C" {
+    fn __wasm_split_sleep(ms: u32, callback: LoadCallbackFn, data: *const c_void);
+    fn __wasm_split_now() -> f64;
+}
+
+/// When [`with_fallback`] shows a loading fallback, and for how long.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FallbackTiming {
+    delay_ms: u32,
+    min_display_ms: u32,
+}
+
+impl FallbackTiming {
+    /// Shows the fallback once a load has taken `delay_ms`, and then keeps
+    /// it for at least `min_display_ms`, even if the load completes sooner.
+    pub const fn new(delay_ms: u32, min_display_ms: u32) -> Self {
+        Self {
+            delay_ms,
+            min_display_ms,
+        }
+    }
+
+    pub fn delay_ms(&self) -> u32 {
+        self.delay_ms
+    }
+
+    pub fn min_display_ms(&self) -> u32 {
+        self.min_display_ms
+    }
+}
+
+/// Waits 100ms before showing the fallback, below which users perceive a
+/// load as instant, and then shows it for at least 400ms.
+impl Default for FallbackTiming {
+    fn default() -> Self {
+        Self::new(100, 400)
+    }
+}
+
+async fn sleep(ms: u32) {
+    let _ = SplitLoaderFuture::new(SplitLoader::new(Box::new(move |callback, data| unsafe {
+        __wasm_split_sleep(ms, callback, data)
+    })))
+    .await;
+}
+
+/// Awaits `load`, typically a route's `#[wasm_split]` view function or a
+/// [`load_parallel`](crate::load_parallel), and calls `set_fallback` with
+/// `true` and later `false` if the fallback should be shown meanwhile.
+///
+/// A load that completes within the [delay](FallbackTiming::delay_ms) never
+/// shows the fallback, so that fast loads do not flash it. A slower one shows
+/// it for at least the [minimum time](FallbackTiming::min_display_ms), and
+/// resolves once that has passed, so that the fallback does not flash either
+/// when the load completes right after it appeared.
+///
+/// With a router's transition component, such as Leptos' `Transition`, which
+/// keeps showing the previous route while the next one loads, drive its
+/// pending indicator with `set_fallback` instead of reading the transition's
+/// own pending state:
+///
+/// ```ignore
+/// let (loading, set_loading) = signal(false);
+/// let view = with_fallback(view_c(), FallbackTiming::default(), move |shown| {
+///     set_loading.set(shown)
+/// })
+/// .await;
+/// ```
+///
+/// Dropping the returned future drops `load`, but not a fallback it showed,
+/// which the caller then hides itself.
+pub async fn with_fallback<F: Future>(
+    load: F,
+    timing: FallbackTiming,
+    mut set_fallback: impl FnMut(bool),
+) -> F::Output {
+    let mut load = pin!(load);
+    let mut delay = pin!(sleep(timing.delay_ms));
+    let early = poll_fn(|cx| match load.as_mut().poll(cx) {
+        Poll::Ready(output) => Poll::Ready(Some(output)),
+        Poll::Pending => delay.as_mut().poll(cx).map(|()| None),
+    })
+    .await;
+    if let Some(output) = early {
+        return output;
+    }
+    set_fallback(true);
+    let shown_at = unsafe { __wasm_split_now() };
+    let output = load.await;
+    let shown_for = unsafe { __wasm_split_now() } - shown_at;
+    if shown_for < timing.min_display_ms as f64 {
+        sleep((timing.min_display_ms as f64 - shown_for) as u32).await;
+    }
+    set_fallback(false);
+    output
+}
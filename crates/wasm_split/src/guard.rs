@@ -0,0 +1,109 @@
+//! Guarded cross-chunk calls, with the `guard-calls` feature and `wasm-split
+//! --guard-calls`.
+//!
+//! Calls into another chunk go through the slot of the indirect function
+//! table that the chunk fills in once it is instantiated. If the call is made
+//! before that, e.g. because a chunk was patched to call a function of one
+//! that its split point never loads, the call traps with a bare "null
+//! function" error that takes down the whole instance. With `--guard-calls`,
+//! each such call first checks its slot, and calls
+//! `__wasm_split_guard_fault` instead if it is empty, which panics with a
+//! [`CrossChunkCallError`] naming the chunk and function.
+
+use alloc::string::String;
+use core::fmt;
+
+/// Panic payload of a cross-chunk call whose target had not been loaded,
+/// with the `guard-calls` feature.
+///
+/// Apps built with `panic = "unwind"` can catch it with `catch_unwind` and
+/// downcast the payload. Otherwise, the loader logs the call to the console
+/// before the panic hook runs, as hooks only print string payloads.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrossChunkCallError {
+    chunk: String,
+    symbol: String,
+}
+
+impl CrossChunkCallError {
+    /// The error described by the loader's report of a guarded call: the
+    /// chunk and symbol, separated by a newline.
+    #[cfg(any(target_arch = "wasm32", test))]
+    fn from_report(report: &str) -> Self {
+        let (chunk, symbol) = report.split_once('\n').unwrap_or(("", report));
+        Self {
+            chunk: chunk.into(),
+            symbol: symbol.into(),
+        }
+    }
+
+    /// Name of the chunk that defines the called function, or an empty string
+    /// if the slot does not belong to any chunk.
+    pub fn chunk(&self) -> &str {
+        &self.chunk
+    }
+
+    /// Symbol name of the called function, or its table slot if the input
+    /// has no name section.
+    pub fn symbol(&self) -> &str {
+        &self.symbol
+    }
+}
+
+impl fmt::Display for CrossChunkCallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "call to `{}` of split chunk `{}`, which is not loaded",
+            self.symbol, self.chunk
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CrossChunkCallError {}
+
+#[cfg(target_arch = "wasm32")]
+#[link(wasm_import_module = "./__wasm_split.js")]
+extern "C" {
+    /// Writes the chunk and symbol of `slot`, separated by a newline, into
+    /// the buffer, returning the number of bytes written.
+    fn __wasm_split_report_guarded_call(slot: u32, buf: *mut u8, capacity: usize) -> usize;
+}
+
+/// Called by the guard of a cross-chunk call whose slot is empty. Exported
+/// so that `wasm-split` can find it, and given a table slot by it so that
+/// guards in chunks can call it too.
+#[cfg(target_arch = "wasm32")]
+#[no_mangle]
+extern "C" fn __wasm_split_guard_fault(slot: u32) {
+    let mut buf = [0u8; 256];
+    let len = unsafe { __wasm_split_report_guarded_call(slot, buf.as_mut_ptr(), buf.len()) };
+    let report = String::from_utf8_lossy(&buf[..len.min(buf.len())]);
+    let error = CrossChunkCallError::from_report(&report);
+    #[cfg(feature = "std")]
+    std::panic::panic_any(error);
+    #[cfg(not(feature = "std"))]
+    panic!("{error}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CrossChunkCallError;
+
+    #[test]
+    fn names_the_chunk_and_function_of_the_call() {
+        let error = CrossChunkCallError::from_report("second\nno_std_app::quadruple");
+        assert_eq!(error.chunk(), "second");
+        assert_eq!(error.symbol(), "no_std_app::quadruple");
+        assert_eq!(
+            alloc::format!("{error}"),
+            "call to `no_std_app::quadruple` of split chunk `second`, which is not loaded"
+        );
+
+        // A slot that belongs to no chunk.
+        let error = CrossChunkCallError::from_report("table slot 7");
+        assert_eq!(error.chunk(), "");
+        assert_eq!(error.symbol(), "table slot 7");
+    }
+}
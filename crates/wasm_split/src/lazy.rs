@@ -0,0 +1,66 @@
+use alloc::boxed::Box;
+use core::{future::Future, pin::Pin};
+
+use async_once_cell::OnceCell;
+
+type SplitLazyInit<T> = fn() -> Pin<Box<dyn Future<Output = T>>>;
+
+/// Lazily initialized value whose initializer lives in a split chunk, created
+/// by `#[wasm_split(module_name)]` on a `static` item:
+///
+/// ```ignore
+/// #[wasm_split(parser)]
+/// static EMAIL: Regex = Regex::new(EMAIL_PATTERN).unwrap();
+///
+/// let matches = EMAIL.get().await.is_match(input);
+/// ```
+///
+/// The initializer expression becomes a split function of the chunk, so the
+/// code only needed to build the value, such as a regex compiler or a parser
+/// generator, is not part of the main module. The first call to
+/// [`get`](Self::get) loads the chunk and runs the initializer; concurrent
+/// calls wait for the same initialization.
+///
+/// Large constant tables, such as time zone data or unicode normalization
+/// tables, take `data` instead, so that the table is part of the chunk's data
+/// rather than of the main module, and `get` returns a reference to it once
+/// the chunk is loaded:
+///
+/// ```ignore
+/// #[wasm_split(timezones, data)]
+/// static TZDATA: [u8; TZDATA_LEN] = *include_bytes!("tzdata.bin");
+///
+/// let offset = lookup(TZDATA.get().await, zone);
+/// ```
+///
+/// The initializer must then be a constant expression, as that of a `static`.
+pub struct SplitLazy<T> {
+    cell: OnceCell<T>,
+    init: SplitLazyInit<T>,
+}
+
+impl<T> SplitLazy<T> {
+    pub const fn new(init: SplitLazyInit<T>) -> Self {
+        Self {
+            cell: OnceCell::new(),
+            init,
+        }
+    }
+
+    /// Returns the value, initializing it first if needed.
+    ///
+    /// Panics if the chunk fails to load, like calling a `#[wasm_split]`
+    /// function does.
+    pub async fn get(&self) -> &T {
+        if let Some(value) = self.cell.get() {
+            return value;
+        }
+        self.cell.get_or_init((self.init)()).await
+    }
+
+    /// Returns the value if it has already been initialized, without loading
+    /// anything.
+    pub fn get_if_initialized(&self) -> Option<&T> {
+        self.cell.get()
+    }
+}
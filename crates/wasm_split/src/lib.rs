@@ -1,105 +1,846 @@
-use std::{
-    cell::Cell,
-    ffi::c_void,
-    future::Future,
-    pin::Pin,
-    rc::Rc,
-    task::{Context, Poll, Waker},
-};
+//! Runtime support for code split with `#[wasm_split]`.
+//!
+//! # Toolchain requirements
+//!
+//! Everything works on stable Rust with the standard `wasm32-unknown-unknown`
+//! target. The crate using `#[wasm_split]` must be built as a `cdylib`, and
+//! should be linked with relocations so that `wasm-split` can move the whole
+//! call graph of each split function, not just the function itself:
+//!
+//! ```toml
+//! # .cargo/config.toml
+//! [build]
+//! target = "wasm32-unknown-unknown"
+//! rustflags = ["-C", "link-arg=--emit-relocs"]
+//! ```
+//!
+//! Then run `wasm-split` on the output of `cargo build`, and `wasm-bindgen
+//! --keep-lld-exports` on the `main.wasm` it produces. `wasm-split` explains
+//! which of these steps is missing if its input does not have the expected
+//! structure. It refuses inputs of the `wasm64-unknown-unknown` target and
+//! modules with the types of the GC proposal, rather than splitting them
+//! incorrectly. Modules with several memories are split, and their chunks
+//! listed with the `multi-memory` feature.
+//!
+//! Split functions may use any wasm-bindgen feature, including creating a
+//! `wasm_bindgen::closure::Closure` and handing it to JS. The glue that
+//! wasm-bindgen generates for closures and imports stays in the main module,
+//! and the closure's own code is loaded together with the split function.
+//!
+//! # Grouping
+//!
+//! The argument of `#[wasm_split]` names a split module, not a function: all
+//! functions that name the same module are emitted into one chunk, together
+//! with the code they call, and the first call of any of them loads all of
+//! them with a single fetch. `group = "..."` gives the module alike, which
+//! reads better for functions that are part of a route's group rather than a
+//! module of their own:
+//!
+//! ```ignore
+//! #[wasm_split(group = "route_b")]
+//! fn format_comments(comments: &[Comment]) -> String { ... }
+//!
+//! #[wasm_split(group = "route_b")]
+//! fn view_b() -> AnyView { ... }
+//! ```
+//!
+//! Code that functions of several modules call, but the main module does
+//! not, such as a deserializer that two routes share, is not copied into each
+//! of their chunks: `wasm-split` emits it into a shared chunk named after
+//! them, e.g. `route_b_route_c`, which the loader fetches once, before the
+//! first of their chunks that needs it.
+//!
+//! A function with a module name of its own and a group, or equivalently
+//! `with = module`, is emitted into the group's chunk too, while its module
+//! name can still be loaded on its own, e.g. with [`SplitChunk::new`], as an
+//! alias of the group.
+//!
+//! On an inline module, such as that of a feature, `#[wasm_split]` splits
+//! all of its functions that are visible outside of it into the one split
+//! module, with the same arguments:
+//!
+//! ```ignore
+//! #[wasm_split(editor)]
+//! mod editor {
+//!     pub fn render_markdown(text: &str) -> String { ... }
+//!     pub fn word_count(text: &str) -> usize { ... }
+//!     fn parse(text: &str) -> Document { ... }
+//! }
+//! ```
+//!
+//! Each of them becomes async, like any split function, including for calls
+//! within the module. Private functions stay as they are, and are loaded
+//! with the code that calls them, while functions with a `#[wasm_split]` of
+//! their own, such as generic ones with `types(...)`, keep it.
+//!
+//! # Initialization
+//!
+//! [`on_load`] runs a function of a split module once its chunk has been
+//! instantiated, before the first call of any of its functions returns, e.g.
+//! to set up thread-local state, register custom elements or warm caches
+//! that the module's code expects:
+//!
+//! ```ignore
+//! #[wasm_split::on_load(group = "charts")]
+//! fn register_elements() { ... }
+//! ```
+//!
+//! The function runs exactly once, also when the module was folded into the
+//! main module, and is loaded with the module along with the code it calls.
+//! A panic in it fails the load.
+//!
+//! # Arguments
+//!
+//! A split function takes its arguments through an `extern "C"` function of
+//! the same signature, which both the main module and its chunk are compiled
+//! with by the same compiler, so that they agree on the layout of every
+//! type. Owned values are moved. References point into the memory of the
+//! main module, which the chunk shares: `&T` and `&mut T` as a pointer,
+//! slices and `&str` as a pointer and a length, and `&dyn Trait` as a
+//! pointer and a vtable, whose functions are in the shared function table.
+//! Any pattern may bind an argument, and lifetimes may tie the result to
+//! the arguments, as in a function of the main module:
+//!
+//! ```ignore
+//! #[wasm_split(editor)]
+//! fn longest<'a>(comments: &'a [Comment], state: &mut EditorState) -> &'a str { ... }
+//! ```
+//!
+//! As with any async function, the future of a call holds its borrows until
+//! the call returns, including while the chunk loads. The arguments of
+//! `worker` functions are copied instead, and so cannot be `&mut`.
+//!
+//! # Methods
+//!
+//! Methods taking `self` in any form can be split as well, in inherent and
+//! trait impls alike, as long as the impl block is not generic. Their impl
+//! block needs `#[wasm_split]` too, without arguments, which hands them the
+//! type of `self` that the attribute on a method alone does not know:
+//!
+//! ```ignore
+//! #[wasm_split]
+//! impl LazyRoute for ViewB {
+//!     fn data() -> Self { ... }
+//!
+//!     #[wasm_split(view_b, route = "/b")]
+//!     async fn view(this: Self) -> AnyView { ... }
+//! }
+//! ```
+//!
+//! For the views of lazy routes, [`lazy_route`] on the impl block does both.
+//!
+//! # Async functions
+//!
+//! An `async fn` can be split as well, and may await JS promises with
+//! `wasm_bindgen_futures::JsFuture` and start tasks with `spawn_local`:
+//!
+//! ```ignore
+//! #[wasm_split(search)]
+//! async fn search(query: String) -> Vec<Hit> {
+//!     let response = JsFuture::from(fetch_index(&query)).await.unwrap();
+//!     spawn_local(async move { record_query(query).await });
+//!     rank(response)
+//! }
+//! ```
+//!
+//! The chunk creates the future of each call, which the caller then polls,
+//! so the state machine of the body, those of the futures it awaits or
+//! spawns, and the glue of `JsFuture` that only they use are all in the
+//! chunk, rather than in the main module. The executor of
+//! `wasm_bindgen_futures` and its wakers stay in the main module, which
+//! polls the tasks of every chunk, and a chunk whose futures may still be
+//! pending is never unloaded, as it is `referenced` through the table.
+//!
+//! The future of a call outlives the call of the chunk's function that
+//! created it, so it must not borrow from the caller: the macro rejects
+//! async split functions that take references or `&self`, as well as
+//! `worker`, `sync` and generic ones. Pass owned values instead, such as
+//! clones, and `self` by value. A `fallback` of an async split function is
+//! an async function of the same signature.
+//!
+//! # Generic functions
+//!
+//! The split tool only sees the code that the compiler generated, so a
+//! generic function is split for the types that `types(...)` lists, each of
+//! which gets code of its own in the module. Calls pick it by the `TypeId`
+//! of the type parameter, which must therefore be `'static`, and on wasm
+//! panic for other types:
+//!
+//! ```ignore
+//! #[wasm_split(deser, types(Vec<Comment>, Vec<Post>))]
+//! fn parse<T: DeserializeOwned>(data: &str) -> T { ... }
+//! ```
+//!
+//! Functions can have one type parameter at most. Arguments of `impl Trait` type
+//! are passed to the split function as a `Box<dyn Trait>` instead, and so
+//! need no `types(...)`, but must be of a trait that can be made into an
+//! object.
+//!
+//! # Locale data
+//!
+//! Translations and the formatting data of ICU are often the largest part of
+//! an app, of which each user needs one locale. [`locale_data!`] splits a
+//! value for each locale into a chunk of that locale, which the data of all
+//! its `locale_data!` statics share, and [`LocaleData::get`] loads only the
+//! chunk of the locale that best matches the active one:
+//!
+//! ```ignore
+//! wasm_split::locale_data! {
+//!     static MESSAGES: Catalog = {
+//!         "de" => Catalog::parse(include_str!("locales/de.ftl")),
+//!         "ja" => Catalog::parse(include_str!("locales/ja.ftl")),
+//!     };
+//!
+//!     static PLURAL_RULES: &'static [u8] = {
+//!         "de" => include_bytes!("icu/de.postcard"),
+//!         "ja" => include_bytes!("icu/ja.postcard"),
+//!     };
+//! }
+//!
+//! let messages = MESSAGES.get(&locale).await.unwrap_or(&ENGLISH);
+//! ```
+//!
+//! The default locale is usually best left in the main module, as above, for
+//! the first render not to wait for a chunk.
+//!
+//! # Workers
+//!
+//! A function that only computes, such as one deserializing a large
+//! response, freezes the page while it runs for the first time, and longer
+//! still as its chunk is compiled on the main thread. With `worker`, its calls
+//! run on a worker instead:
+//!
+//! ```ignore
+//! #[wasm_split(deserialize_comments, worker)]
+//! fn deserialize_comments(data: &str) -> Vec<Comment> { ... }
+//! ```
+//!
+//! The worker has an instance of the main module of its own, in which every
+//! JS import throws, so such functions must not call into JS, and they don't
+//! see the state of the app's instance, such as its statics. Their arguments
+//! and result are copied over with [`Transfer`], which they must implement.
+//! Where workers are not available, or fail to load the chunk, calls run on
+//! the main thread as without `worker`.
+//!
+//! # Failed loads
+//!
+//! A call of a split function whose module fails to load panics, and so do
+//! all later calls, as the failure is kept. On unreliable networks, mark the
+//! function `fallible` instead, which makes it return
+//! `Result<T, LoadError>`, so that the app can show a retry button, and makes
+//! the next call load the module again:
+//!
+//! ```ignore
+//! #[wasm_split(view_b, fallible)]
+//! fn view_b() -> AnyView { ... }
+//!
+//! match view_b().await {
+//!     Ok(view) => view,
+//!     Err(error) if error.is_transient() => retry_button().into_any(),
+//!     Err(error) => panic!("{error}"),
+//! }
+//! ```
+//!
+//! The loader can also retry failed requests by itself before reporting
+//! the failure, with `retries` and `retry-delay-ms` in the `[loader]` table of
+//! `wasm-split.toml`.
+//!
+//! Modules that the app works without, such as analytics that ad blockers
+//! may block, can be marked `optional`. Calls then return
+//! `Default::default()`, e.g. `()`, if the module fails to load, and keep
+//! doing so without further requests:
+//!
+//! ```ignore
+//! #[wasm_split(analytics, optional)]
+//! fn track(event: &str) { ... }
+//! ```
+//!
+//! A single eager call into a dependency keeps all the code that it reaches
+//! in the main module, however much of it a split module shares. Modules
+//! marked `strict` fail the split if any code of their dependencies, that is
+//! of crates other than their own and the standard library, is in the main
+//! module, listing the eager calls that keep it there:
+//!
+//! ```ignore
+//! #[wasm_split(editor, strict)]
+//! fn open_editor(text: String) { ... }
+//! ```
+//!
+//! `wasm-split --deny-eager <crate>` does the same for every use of a crate.
+//!
+//! # Synchronous calls
+//!
+//! Some call sites cannot await, such as event handlers or the hot path of a
+//! render, but only run once the module was loaded, e.g. with [`preload`] or
+//! an earlier call. With `sync`, a function gets two companions for them,
+//! `<name>_sync`, which panics if the module is not loaded, and
+//! `try_<name>_sync`, which returns [`LoadError::NotLoaded`] instead:
+//!
+//! ```ignore
+//! #[wasm_split(comments, sync)]
+//! fn deserialize_comments(data: &str) -> Vec<Comment> { ... }
+//!
+//! // Once `wasm_split::preload("comments")` completed:
+//! let comments = deserialize_comments_sync(&data);
+//! ```
+//!
+//! Neither loads the module nor runs on a worker, and `sync` is not
+//! supported on generic functions.
+//!
+//! # Load events
+//!
+//! [`on_event`] registers a listener for the [`LoadEvent`]s of every chunk:
+//! when its load starts, as it downloads, and once it was instantiated or
+//! failed. Frameworks use these for a global loading bar, which a single
+//! listener can drive for all routes, or to report slow loads to analytics.
+//! JS code gets the same events from `onLoadEvent` of the loader script.
+//!
+//! Without any listener, the loader records the `fetch`, `compile` and
+//! `instantiate` phases of every chunk as performance measures, such as
+//! `wasm-split:view_c:fetch`, which DevTools show in their performance
+//! waterfall, and RUM scripts can collect with a `PerformanceObserver`.
+//!
+//! For the first load of a page, [`load_route`] loads all chunks of a route
+//! at once and reports their combined progress, weighted by size, together
+//! with that of the route's data, which `loadRoute` of the loader script does
+//! for JS. Client-side routers can instead have the browser fetch them, with
+//! `<link rel="preload">` elements for the [`route_preload_links`] of the
+//! route a navigation goes to.
+//!
+//! Navigations need not wait for the network at all if the chunks of their
+//! route were loaded while the user was about to follow the link. The
+//! manifest maps each route to the chunks it needs, which [`route_chunks`]
+//! looks up, and [`preload_route`] starts loading, e.g. on `pointerenter` of
+//! a link or once an `IntersectionObserver` sees it.
+//!
+//! Pages rendered on the server, as with Leptos SSR, know their route before
+//! the client starts. The server looks up its split modules with
+//! `wasm_split_server`, and the client passes them to [`hydrate_preload`]
+//! before hydrating:
+//!
+//! ```ignore
+//! #[wasm_bindgen]
+//! pub fn hydrate() {
+//!     // E.g. `["view_b", "view_b_child"]`, as rendered into the page.
+//!     wasm_split::hydrate_preload(&hydration_modules());
+//!     leptos::mount::hydrate_body(App);
+//! }
+//! ```
+//!
+//! # Fetch scheduling
+//!
+//! Chunks are fetched by priority class, which `priority = "critical"`,
+//! `"high"` (the default) or `"low"` of `#[wasm_split]` sets: a chunk is only
+//! requested once no chunk of a more urgent class is queued or in flight.
+//! On slow connections, fetching all chunks of a route at once can still
+//! starve the route's own data requests, so [`configure`] can also limit the
+//! chunks fetched at once, and change the class of chunks at runtime, e.g.
+//! after checking `navigator.connection`:
+//!
+//! ```ignore
+//! if slow_connection() {
+//!     wasm_split::configure(wasm_split::LoaderConfig::new().max_concurrent_fetches(1));
+//! }
+//! ```
+//!
+//! `max-concurrent-fetches` in the `[loader]` table of `wasm-split.toml`
+//! sets the limit from the start.
+//!
+//! A hung request would leave the app waiting for its chunk forever, so
+//! `timeout-ms`, or [`LoaderConfig::timeout_ms`], fails loads that take
+//! longer with [`LoadError::Timeout`]. Downloads that are no longer needed,
+//! such as those of a route that the user navigated away from, can be
+//! cancelled with [`abort_loads`] or [`abort_route`].
+//!
+//! Chunks served from an authenticated endpoint or another origin may need
+//! [`LoaderConfig::credentials`] or headers such as `Authorization`, which
+//! [`LoaderConfig::header`] adds to every request for chunks and manifests.
+//! Apps with a transport of their own, such as signed URLs, can instead pass
+//! a `fetch` function to `configure` of the JS loader, which then makes all
+//! of these requests.
+//!
+//! # Unloading
+//!
+//! Long-lived sessions, such as dashboards, would otherwise keep every chunk
+//! they ever loaded. [`unload`] reverts a chunk that is no longer needed to
+//! the state before its first load, so that the next call loads it again,
+//! and [`reload`] does so right away, bypassing the HTTP cache, e.g. for
+//! hot reloading in development:
+//!
+//! ```ignore
+//! fn close_editor() {
+//!     wasm_split::unload("editor");
+//! }
+//! ```
+//!
+//! Chunks that other code may still call into through the table, that is
+//! loaded chunks depending on them, or `fn` pointers and trait objects of
+//! their functions unless split with `--lazy-indirect-calls`, are not
+//! unloaded.
+//!
+//! # Other targets
+//!
+//! Off wasm, such as in the server build of an SSR app that shares its
+//! crates with the client, `#[wasm_split]` functions keep their async
+//! signature but are called directly, with nothing to load: `fallible` ones
+//! always return `Ok`, and neither `fallback` nor `worker` take effect, so
+//! shared code builds unchanged for every target.
+//!
+//! # Rust loader
+//!
+//! With the `rust-loader` feature, the main module loads its chunks by
+//! itself, with `js-sys` and `wasm-bindgen`, rather than through the loader
+//! script that `wasm-split` writes, so that the app serves no JS besides the
+//! glue of wasm-bindgen:
+//!
+//! ```toml
+//! wasm_split = { version = "0.1", features = ["rust-loader"] }
+//! ```
+//!
+//! Calls of split functions, [`SplitChunk::load`], [`load_group`],
+//! [`preload`], [`is_loaded`] and [`loaded_chunks`] then use this loader. It
+//! fetches `wasm-split-manifest.json` on the first load, from the base URL
+//! of [`set_base_url`], or `globalThis.WASM_SPLIT_BASE_URL`, or otherwise
+//! the page, and loads chunks relative to it, along with the chunks they
+//! depend on, checking their integrity hashes. Folded modules count as
+//! loaded once the manifest was fetched.
+//!
+//! It does not support the rest of the loader script: retries, timeouts and
+//! fetch scheduling, the signatures of manifests and chunks, service
+//! workers, `worker` functions, unloading, and the JS snippets of
+//! `wasm-split snippets`. The other functions of this crate still call the
+//! loader script, which the app then needs as well, and `wasm-split` names
+//! them when it splits such an app. It also refuses `--lazy-indirect-calls`
+//! and `--signing-key` for these apps.
+//!
+//! # `no_std`
+//!
+//! Apps without `std`, such as those with a custom global allocator, disable
+//! the default `std` feature and enable `critical-section` instead, after
+//! which the runtime only depends on `core` and `alloc`:
+//!
+//! ```toml
+//! wasm_split = { version = "0.1", default-features = false, features = ["critical-section"] }
+//! ```
+//!
+//! The app must then provide a [`critical-section`] implementation, which the
+//! runtime's once-cells use for locking. As these builds do not support the
+//! atomics target feature, and so always run on one thread, the
+//! implementation can simply do nothing:
+//!
+//! ```ignore
+//! struct SingleThreaded;
+//! critical_section::set_impl!(SingleThreaded);
+//! unsafe impl critical_section::Impl for SingleThreaded {
+//!     unsafe fn acquire() {}
+//!     unsafe fn release(_: ()) {}
+//! }
+//! ```
+//!
+//! [`panic_hook`] requires `std`, and is not available otherwise.
+//!
+//! Such apps often don't use wasm-bindgen either, and so have no JS glue for
+//! the loader to reach the main module through. For these, the loader that
+//! `wasm-split` writes exports `instantiateMain(imports)` instead, which
+//! fetches and instantiates the main module with the app's own imports.
+//!
+//! [`critical-section`]: https://docs.rs/critical-section
+//!
+//! # Tracing
+//!
+//! With the `tracing` feature, lazy loading is visible to the app's existing
+//! [`tracing`] subscriber. Every chunk load is a `wasm_split::load` span at
+//! info level, with the `chunk` name, its size in `bytes` and the
+//! `duration_ms` of the load, or the `error` it failed with. Every call of a
+//! `#[wasm_split]` function is a `wasm_split::call` span at debug level, with
+//! the `module` and `function` names, which is the parent of the load that
+//! the call triggers.
+//!
+//! Within the span of a load, a `wasm_split::loaded` event at info level, or
+//! a `wasm_split::load_failed` event at warn level with the `error`, marks
+//! its end. The other changes to chunks are `wasm_split::lifecycle` events
+//! at info level, with the `action` (`preload`, `hydrate_preload`, `abort`,
+//! `drop` or `unload`), the `chunks` it applies to, comma separated, and
+//! whether it was `done`: whether [`abort_loads`] cancelled anything, and
+//! whether [`unload`] unloaded the chunk rather than refusing to.
+//!
+//! With [`tracing-wasm`] as the subscriber, the spans are performance
+//! measures of the page, next to the `wasm-split:chunk-compile` ones, and the
+//! events are logged to the console.
+//!
+//! [`tracing`]: https://docs.rs/tracing
+//! [`tracing-wasm`]: https://docs.rs/tracing-wasm
+//!
+//! # Profiles
+//!
+//! With the `profile` feature, every call of a `#[wasm_split]` function is
+//! recorded by the loader, which `getProfile()` of the loader script, or
+//! `profile()` of its entry in `window.__WASM_SPLIT__`, returns for the
+//! page: the first call and number of calls of each function. Apps collect
+//! these from their users, e.g. with `navigator.sendBeacon` on `pagehide`,
+//! and pass them to `wasm-split --profile`, which moves the modules and
+//! functions that most users call right away into the main module, and
+//! merges modules that users call together into one chunk.
+//!
+//! # Guarded calls
+//!
+//! A call into a chunk that is not loaded, such as one introduced by a
+//! mis-patched build, normally traps with an error that names neither the
+//! chunk nor the function. For debug builds, enable the `guard-calls` feature
+//! and pass `--guard-calls` to `wasm-split`, which checks every cross-chunk
+//! call first and turns such a call into a panic with a
+//! [`CrossChunkCallError`] instead.
+//!
+//! # Debug info
+//!
+//! With debug info in the input, e.g. `debug = "line-tables-only"` in the
+//! profile, every chunk carries its DWARF sections, in which the addresses
+//! of the chunk's own code are moved to where the chunk puts it, so that
+//! DevTools extensions for DWARF show source lines and variables for code
+//! loaded from chunks. Each chunk keeps the debug info of the whole build,
+//! which makes chunks of large apps much bigger, so this is for debug builds.
+//! Calls between chunks then also go through a stub that makes the
+//! `call_indirect` of the callee's table slot, rather than making it in
+//! place, which would move the code after the call away from its address.
+//!
+//! # Function pointers and trait objects
+//!
+//! The main module keeps every function that its code or data takes the
+//! address of, such as the methods in the vtable of a `Box<dyn Trait>` that
+//! it creates, since a call through the pointer cannot wait for a chunk.
+//! With `--lazy-indirect-calls`, those that a split module also reaches go
+//! into its chunk instead, and until the chunk is loaded, their table slots
+//! hold stubs of the main module that load it synchronously and then make
+//! the call. This blocks the page for the download, so chunks that are
+//! likely called this way should be preloaded.
+//!
+//! Code built with reference types, the default of recent toolchains, may
+//! declare tables of its own, e.g. of `externref`s, which the main module
+//! defines and chunks import from it, so that all modules share them as
+//! they do the indirect function table.
+//!
+//! # Unwinding
+//!
+//! Built with `panic = "unwind"` and the exception handling proposal
+//! (`-Ctarget-feature=+exception-handling`), a panic in a split function
+//! unwinds into its caller in the main module, where `catch_unwind` catches
+//! it, rather than aborting the instance: chunks import the tags of the main
+//! module, so that all modules throw and catch the same exceptions. The
+//! calls that would trap on a chunk that is not loaded, or on a lazy stub
+//! whose chunk fails to load, panic instead with `--guard-calls`, and so
+//! unwind as well. The manifest lists `exception-handling` among the
+//! features of such chunks, which the loader refuses to load in browsers
+//! without it.
+//!
+//! # Threads
+//!
+//! Apps with threads, e.g. with `wasm-bindgen-rayon`, built with
+//! `-Ctarget-feature=+atomics,+bulk-memory` and a shared memory, instantiate
+//! the main module on every thread with that memory. Chunks import it from
+//! the main module, the data of their code stays in the main module, and
+//! every thread loads the chunks that it calls into its own instance and
+//! table, as a split function called on a worker loads its chunk there
+//! whether or not the page has it. The state of the app in the shared memory
+//! is seen by the code of every chunk on every thread. The manifest lists
+//! `atomics` among the features of all chunks. Functions marked `worker`
+//! run on a worker of their own with a memory of their own, and so do not
+//! support apps whose main module imports its memory.
+//!
+//! # Integrity and Content Security Policy
+//!
+//! The manifest lists a Subresource Integrity hash for every chunk, which the
+//! loader passes to `fetch`, so that the browser rejects a chunk modified by
+//! a CDN or proxy before compiling it, and the load fails as if the network
+//! was down.
+//!
+//! Every chunk also carries a hash of the signature of each split function it
+//! defines, which the loader checks before instantiating it, so that a chunk
+//! of an earlier build, e.g. taken from Cache Storage, fails to load with an
+//! `InstantiationError` that names the functions whose signatures changed.
+//! A chunk whose imports the main module lacks fails with a `LinkError`
+//! instead, whose [`load_failure`] names the missing import.
+//! `wasm-split verify <dir>` checks the same before a deployment, along with
+//! the validity of every module and the imports of the chunks.
+//!
+//! The loader works under a strict CSP: it never uses `eval` or `Function`,
+//! and injects no scripts or styles, so it needs no nonce of its own. The
+//! page's `<script type="module" nonce="...">` that imports the glue is
+//! enough, with `'strict-dynamic'` or the origin of the loader in
+//! `script-src`. Compiling WebAssembly needs `'wasm-unsafe-eval'`, and the
+//! worker of `worker` functions needs the loader's origin in `worker-src`,
+//! and `trusted-types wasm-split` where Trusted Types are enforced. Without
+//! the latter two, those functions run on the main thread instead:
+//!
+//! ```text
+//! Content-Security-Policy: script-src 'nonce-...' 'strict-dynamic' 'wasm-unsafe-eval';
+//!     worker-src 'self'; connect-src 'self'; trusted-types wasm-split;
+//!     require-trusted-types-for 'script'
+//! ```
+//!
+//! # Stability
+//!
+//! Everything reachable from the crate root, except for `__macro_support`
+//! and other `#[doc(hidden)]` items, is public API and follows semver: a
+//! breaking change needs a new major version, or a new minor one while the
+//! crate is at 0.x, which CI enforces with `cargo semver-checks`. The
+//! attributes of `#[wasm_split]` and the other macros are public API as
+//! well. Enums are `#[non_exhaustive]`, so that new variants, such as more
+//! kinds of [`LoadError`], may be added in minor releases, and structs only
+//! expose constructors and accessors, so that new fields may be too. A
+//! [`LoadError`] only says what kind of failure it was, along with the HTTP
+//! status of [`LoadError::Http`]; the details of the failed load, which may
+//! grow, are in its [`LoadFailure`].
+//!
+//! `__macro_support` holds items used by the code that `#[wasm_split]`
+//! expands to. It is not part of the public API and may change in any
+//! release, together with the macro.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(not(any(feature = "std", feature = "critical-section")))]
+compile_error!("wasm_split requires either the `std` or the `critical-section` feature");
 
-pub use wasm_split_macros::wasm_split;
+#[cfg(all(not(feature = "std"), target_feature = "atomics"))]
+compile_error!("wasm_split does not support the atomics target feature without `std`");
 
-pub type LoadCallbackFn = unsafe extern "C" fn(*const c_void, bool) -> ();
-pub type LoadFn = unsafe extern "C" fn(LoadCallbackFn, *const c_void) -> ();
+pub use wasm_split_macros::{lazy_route, locale_data, on_load, wasm_split};
 
-type Lazy = async_once_cell::Lazy<Option<()>, SplitLoaderFuture>;
+mod chunk;
+mod config;
+mod error;
+mod events;
+mod fallback;
+#[cfg(feature = "guard-calls")]
+mod guard;
+mod lazy;
+mod loader;
+mod locale;
+mod manifest;
+#[cfg(feature = "std")]
+pub mod panic_hook;
+mod parallel;
+mod route;
+#[cfg(feature = "rust-loader")]
+mod rust_loader;
+mod table;
+mod timing;
+mod trace;
+mod worker;
+
+pub use chunk::{
+    abort_loads, drop_module, hydrate_preload, is_loaded, load_group, loaded_chunks, preload,
+    reload, unload, SplitChunk,
+};
+pub use config::{configure, Credentials, LoaderConfig, Priority};
+pub use error::{load_failure, LoadError, LoadFailure};
+pub use events::{on_event, EventSubscription, LoadEvent};
+pub use fallback::{with_fallback, FallbackTiming};
+#[cfg(feature = "guard-calls")]
+pub use guard::CrossChunkCallError;
+pub use lazy::SplitLazy;
+pub use locale::LocaleData;
+pub use manifest::{reload_manifest, set_base_url, ManifestReload};
+pub use parallel::{load_parallel, AbortHandle, ParallelLoad, ParallelProgress};
+pub use route::{
+    abort_route, load_route, preload_route, route_chunks, route_preload_links, PreloadLink,
+    RouteLoad, RouteProgress,
+};
+pub use table::TableSlot;
+pub use timing::NavigationTiming;
+pub use worker::Transfer;
 
-pub struct LazySplitLoader {
-    lazy: Pin<Rc<Lazy>>,
+#[doc(hidden)]
+pub mod __macro_support {
+    pub use crate::__split_loader as split_loader;
+    #[cfg(not(feature = "std"))]
+    pub use crate::loader::StaticSplitLoader;
+    pub use crate::loader::{
+        ensure_loaded, ensure_loaded_retrying, LazySplitLoader, LoadCallbackFn, LoadFn,
+    };
+    pub use crate::trace::call as trace_call;
+    pub use crate::worker::run_on_worker;
+    #[cfg(target_arch = "wasm32")]
+    pub use crate::worker::run_worker_entry;
+    pub use alloc::boxed::Box;
+    pub use alloc::vec::Vec;
+
+    /// The future of a call of an async split function, which the code of
+    /// its chunk creates.
+    pub type SplitFuture<T> = core::pin::Pin<Box<dyn core::future::Future<Output = T>>>;
+    #[cfg(feature = "std")]
+    pub use std::thread_local;
+
+    /// `value` as `U`, for the instantiations of generic split functions,
+    /// whose callers have checked with `TypeId`s that the type parameter is
+    /// that of the instantiation, and so that `T` and `U` are the same type.
+    ///
+    /// # Safety
+    ///
+    /// `T` and `U` must be the same type, except for lifetimes.
+    pub unsafe fn cast<T, U>(value: T) -> U {
+        debug_assert_eq!(core::mem::size_of::<T>(), core::mem::size_of::<U>());
+        let value = core::mem::ManuallyDrop::new(value);
+        unsafe { core::mem::transmute_copy(&*value) }
+    }
 }
 
-impl LazySplitLoader {
-    pub unsafe fn new(load: LoadFn) -> Self {
-        Self {
-            lazy: Rc::pin(Lazy::new(SplitLoaderFuture::new(SplitLoader::new(load)))),
+// For the expansions of the macros in tests, which name the crate.
+#[cfg(test)]
+extern crate self as wasm_split;
+
+#[cfg(test)]
+mod tests {
+    use core::{
+        future::Future,
+        pin::pin,
+        task::{Context, Poll, Waker},
+    };
+
+    use alloc::{vec, vec::Vec};
+
+    use crate::wasm_split;
+
+    fn now<F: Future>(future: F) -> F::Output {
+        match pin!(future).poll(&mut Context::from_waker(Waker::noop())) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("Split function did not return right away"),
         }
     }
-}
 
-pub async fn ensure_loaded(loader: &'static std::thread::LocalKey<LazySplitLoader>) -> Option<()> {
-    *loader.with(|inner| inner.lazy.clone()).as_ref().await
-}
+    #[wasm_split(square)]
+    fn square(x: u32) -> u32 {
+        x * x
+    }
 
-#[derive(Clone, Copy, Debug)]
-enum SplitLoaderState {
-    Deferred(LoadFn),
-    Pending,
-    Completed(Option<()>),
-}
+    #[wasm_split(squares)]
+    static SQUARES: Vec<u32> = (0..5).map(|x| x * x).collect();
 
-struct SplitLoader {
-    state: Cell<SplitLoaderState>,
-    waker: Cell<Option<Waker>>,
-}
+    #[wasm_split(squares, data)]
+    static CUBES: [u32; 4] = [0, 1, 8, 27];
 
-impl SplitLoader {
-    fn new(load: LoadFn) -> Rc<Self> {
-        Rc::new(SplitLoader {
-            state: Cell::new(SplitLoaderState::Deferred(load)),
-            waker: Cell::new(None),
-        })
-    }
-
-    fn complete(&self, value: bool) {
-        self.state.set(SplitLoaderState::Completed(if value {
-            Some(())
-        } else {
-            None
-        }));
-        match self.waker.take() {
-            Some(waker) => {
-                waker.wake();
-            }
-            _ => {}
+    #[wasm_split(cube, sync)]
+    fn cube(x: u32) -> u32 {
+        x * x * x
+    }
+
+    #[wasm_split(fallible_square, fallible)]
+    fn fallible_square(x: u32) -> u32 {
+        x * x
+    }
+
+    fn zero(_: &str) -> usize {
+        0
+    }
+
+    #[wasm_split(length, fallback = zero, worker)]
+    fn length(text: &str) -> usize {
+        text.len()
+    }
+
+    struct Counter(u32);
+
+    #[wasm_split]
+    impl Counter {
+        #[wasm_split(counter)]
+        fn next(&mut self) -> u32 {
+            self.0 += 1;
+            self.0
+        }
+
+        #[wasm_split(counter, sync)]
+        fn peek(&self) -> u32 {
+            self.0
         }
     }
-}
 
-struct SplitLoaderFuture {
-    loader: Rc<SplitLoader>,
-}
+    #[wasm_split(parse, types(u32, i64))]
+    fn parse<T: core::str::FromStr>(text: &str) -> Option<T> {
+        text.parse().ok()
+    }
 
-impl SplitLoaderFuture {
-    fn new(loader: Rc<SplitLoader>) -> Self {
-        SplitLoaderFuture { loader }
+    #[wasm_split(borrowed)]
+    fn push_longest<'a>(words: &'a [&'a str], (count, seen): &mut (usize, Vec<usize>)) -> &'a str {
+        *count += 1;
+        seen.extend(words.iter().map(|word| word.len()));
+        words
+            .iter()
+            .copied()
+            .max_by_key(|word| word.len())
+            .unwrap_or("")
+    }
+
+    #[wasm_split(apply)]
+    fn apply(x: u32, f: impl Fn(u32) -> u32) -> u32 {
+        f(f(x))
     }
-}
 
-impl Future for SplitLoaderFuture {
-    type Output = Option<()>;
-
-    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<()>> {
-        match self.loader.state.get() {
-            SplitLoaderState::Deferred(load) => {
-                self.loader.state.set(SplitLoaderState::Pending);
-                self.loader.waker.set(Some(cx.waker().clone()));
-                unsafe {
-                    load(
-                        load_callback,
-                        Rc::<SplitLoader>::into_raw(self.loader.clone()) as *const c_void,
-                    )
-                };
-                Poll::Pending
-            }
-            SplitLoaderState::Pending => {
-                self.loader.waker.set(Some(cx.waker().clone()));
-                Poll::Pending
-            }
-            SplitLoaderState::Completed(value) => Poll::Ready(value),
+    #[wasm_split(doubled)]
+    async fn double_later(values: Vec<u32>) -> u32 {
+        let mut total = 0;
+        for value in values {
+            total += core::future::ready(value * 2).await;
         }
+        total
     }
-}
 
-unsafe extern "C" fn load_callback(loader: *const c_void, success: bool) {
-    unsafe { Rc::from_raw(loader as *const SplitLoader) }.complete(success);
+    struct Total(u32);
+
+    #[wasm_split]
+    impl Total {
+        #[wasm_split(doubled)]
+        async fn add(mut self, value: u32) -> u32 {
+            self.0 += core::future::ready(value).await;
+            self.0
+        }
+    }
+
+    #[wasm_split(shapes)]
+    mod shapes {
+        pub fn area(width: u32, height: u32) -> u32 {
+            scale(width) * height / 2
+        }
+
+        pub(super) fn perimeter(width: u32, height: u32) -> u32 {
+            scale(width + height)
+        }
+
+        fn scale(x: u32) -> u32 {
+            x * 2
+        }
+    }
+
+    #[test]
+    fn calls_split_functions_directly_off_wasm() {
+        assert_eq!(now(square(7)), 49);
+        assert_eq!(now(fallible_square(3)), Ok(9));
+        assert_eq!(now(length("four")), 4);
+        let mut counter = Counter(1);
+        assert_eq!(now(counter.next()), 2);
+        assert_eq!(counter.peek_sync(), 2);
+        assert_eq!(counter.try_peek_sync(), Ok(2));
+        assert_eq!(cube_sync(3), 27);
+        assert_eq!(try_cube_sync(2), Ok(8));
+        assert_eq!(now(parse::<i64>("-3")), Some(-3));
+        // Off wasm, the function is called directly, also for types that
+        // `types(...)` does not list.
+        assert_eq!(now(parse::<u8>("3")), Some(3));
+        assert_eq!(now(apply(2, |x| x + 3)), 8);
+        let mut state = (0, Vec::new());
+        assert_eq!(now(push_longest(&["a", "abc", "ab"], &mut state)), "abc");
+        assert_eq!(state, (1, vec![1, 3, 2]));
+        assert_eq!(now(shapes::area(3, 4)), 12);
+        assert_eq!(now(shapes::perimeter(3, 4)), 14);
+        assert_eq!(now(double_later(vec![1, 2, 3])), 12);
+        assert_eq!(now(Total(1).add(2)), 3);
+    }
+
+    #[test]
+    fn initializes_split_statics_on_first_use() {
+        assert_eq!(SQUARES.get_if_initialized(), None);
+        assert_eq!(now(SQUARES.get()), &[0, 1, 4, 9, 16]);
+        assert_eq!(SQUARES.get_if_initialized(), Some(&vec![0, 1, 4, 9, 16]));
+        assert_eq!(now(CUBES.get()), &&[0, 1, 8, 27]);
+    }
 }
@@ -0,0 +1,433 @@
+use alloc::{boxed::Box, rc::Rc};
+use core::{
+    cell::{Cell, RefCell},
+    ffi::c_void,
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicU32, Ordering},
+    task::{Context, Poll, Waker},
+};
+
+use crate::LoadError;
+
+/// Invoked by the JS loader with the data pointer passed to the load function,
+/// an error code (zero on success), and a code-specific detail such as the
+/// HTTP status.
+///
+/// Futures created from a `SplitLoader` resolve to the detail on success.
+pub type LoadCallbackFn = unsafe extern "C" fn(*const c_void, u32, u32) -> ();
+pub type LoadFn = unsafe extern "C" fn(LoadCallbackFn, *const c_void) -> ();
+
+pub(crate) type LoadResult = Result<u32, LoadError>;
+
+type Lazy = async_once_cell::Lazy<LoadResult, Pin<Box<dyn Future<Output = LoadResult>>>>;
+
+/// Number of chunks unloaded by [`unload`](crate::unload) and
+/// [`reload`](crate::reload), which the loaders of loaded modules compare
+/// against the count when they loaded, so that calls of a loaded module only
+/// ask the JS loader whether it is still loaded after some chunk was
+/// unloaded.
+pub(crate) static UNLOADS: AtomicU32 = AtomicU32::new(0);
+
+/// What loads the chunk of a [`LazySplitLoader`].
+#[derive(Clone, Copy)]
+enum LoadSource {
+    /// The load function that the JS loader exports for the split module.
+    Js(LoadFn),
+    /// The loader of the `rust-loader` feature.
+    #[cfg(feature = "rust-loader")]
+    Rust,
+}
+
+pub struct LazySplitLoader {
+    source: LoadSource,
+    chunk: &'static str,
+    /// Replaced by [`ensure_loaded_retrying`] once it failed.
+    lazy: RefCell<Pin<Rc<Lazy>>>,
+    /// Whether `lazy` completed successfully, which calls check first, so
+    /// that calls of a loaded module neither clone nor poll it.
+    loaded: Cell<bool>,
+    /// [`UNLOADS`] when `loaded` was last found to be current.
+    unloads: Cell<u32>,
+}
+
+impl LazySplitLoader {
+    /// # Safety
+    ///
+    /// `load` must invoke the callback exactly once, with the data pointer it
+    /// was passed.
+    pub unsafe fn new(load: LoadFn, chunk: &'static str) -> Self {
+        unsafe { Self::with_source(LoadSource::Js(load), chunk) }
+    }
+
+    /// Loads the chunk with the loader of the `rust-loader` feature, so that
+    /// the main module does not import a load function for it.
+    #[cfg(feature = "rust-loader")]
+    pub fn new_rust(chunk: &'static str) -> Self {
+        unsafe { Self::with_source(LoadSource::Rust, chunk) }
+    }
+
+    unsafe fn with_source(source: LoadSource, chunk: &'static str) -> Self {
+        Self {
+            source,
+            chunk,
+            lazy: RefCell::new(unsafe { Self::new_lazy(source, chunk) }),
+            loaded: Cell::new(false),
+            unloads: Cell::new(0),
+        }
+    }
+
+    unsafe fn new_lazy(source: LoadSource, chunk: &'static str) -> Pin<Rc<Lazy>> {
+        let future: Pin<Box<dyn Future<Output = LoadResult>>> = match source {
+            LoadSource::Js(load) => Box::pin(SplitLoaderFuture::new(SplitLoader::new(Box::new(
+                move |callback, data| unsafe { load(callback, data) },
+            )))),
+            #[cfg(feature = "rust-loader")]
+            LoadSource::Rust => Box::pin(crate::rust_loader::load(alloc::vec![chunk.into()])),
+        };
+        Rc::pin(Lazy::new(Box::pin(crate::trace::load(chunk, future))))
+    }
+}
+
+/// Declares the loader of a split module that the loader of the `rust-loader`
+/// feature loads, leaving `$load` unused, so that it is not imported.
+#[cfg(feature = "rust-loader")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __split_loader {
+    ($name:ident, $load:ident, $chunk:expr) => {
+        $crate::__macro_support::thread_local! {
+            static $name: $crate::__macro_support::LazySplitLoader =
+                $crate::__macro_support::LazySplitLoader::new_rust($chunk);
+        }
+    };
+}
+
+/// Declares the loader of a split module, in a `thread_local!` with `std`.
+#[cfg(all(feature = "std", not(feature = "rust-loader")))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __split_loader {
+    ($name:ident, $load:ident, $chunk:expr) => {
+        $crate::__macro_support::thread_local! {
+            static $name: $crate::__macro_support::LazySplitLoader =
+                unsafe { $crate::__macro_support::LazySplitLoader::new($load, $chunk) };
+        }
+    };
+}
+
+/// Declares the loader of a split module, in a plain `static` without `std`.
+#[cfg(not(feature = "std"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __split_loader {
+    ($name:ident, $load:ident, $chunk:expr) => {
+        static $name: $crate::__macro_support::StaticSplitLoader =
+            unsafe { $crate::__macro_support::StaticSplitLoader::new($load, $chunk) };
+    };
+}
+
+#[cfg(feature = "std")]
+pub type SplitLoaderKey = std::thread::LocalKey<LazySplitLoader>;
+
+#[cfg(feature = "std")]
+fn with_loader<R>(loader: &'static SplitLoaderKey, f: impl FnOnce(&LazySplitLoader) -> R) -> R {
+    loader.with(f)
+}
+
+/// Holds the loader of a split module in a `static`, for `no_std` builds,
+/// which have no `thread_local!`. The loader is only created on first use,
+/// since it is not `const`.
+#[cfg(not(feature = "std"))]
+pub struct StaticSplitLoader {
+    load: LoadFn,
+    chunk: &'static str,
+    loader: core::cell::OnceCell<LazySplitLoader>,
+}
+
+// Without the atomics target feature, which `lib.rs` rejects for `no_std`
+// builds, wasm code only ever runs on one thread.
+#[cfg(not(feature = "std"))]
+unsafe impl Sync for StaticSplitLoader {}
+
+#[cfg(not(feature = "std"))]
+impl StaticSplitLoader {
+    /// # Safety
+    ///
+    /// As for [`LazySplitLoader::new`].
+    pub const unsafe fn new(load: LoadFn, chunk: &'static str) -> Self {
+        Self {
+            load,
+            chunk,
+            loader: core::cell::OnceCell::new(),
+        }
+    }
+}
+
+#[cfg(not(feature = "std"))]
+pub type SplitLoaderKey = StaticSplitLoader;
+
+#[cfg(not(feature = "std"))]
+fn with_loader<R>(loader: &'static SplitLoaderKey, f: impl FnOnce(&LazySplitLoader) -> R) -> R {
+    f(loader
+        .loader
+        .get_or_init(|| unsafe { LazySplitLoader::new(loader.load, loader.chunk) }))
+}
+
+fn get_lazy(loader: &'static SplitLoaderKey) -> Pin<Rc<Lazy>> {
+    with_loader(loader, |inner| inner.lazy.borrow().clone())
+}
+
+fn is_loaded(loader: &'static SplitLoaderKey) -> bool {
+    with_loader(loader, |inner| {
+        if !inner.loaded.get() {
+            return false;
+        }
+        let unloads = UNLOADS.load(Ordering::Relaxed);
+        if inner.unloads.get() == unloads {
+            return true;
+        }
+        inner.unloads.set(unloads);
+        if is_still_loaded(inner.chunk) {
+            return true;
+        }
+        // Unloaded, so the next call loads it anew.
+        inner.loaded.set(false);
+        *inner.lazy.borrow_mut() = unsafe { LazySplitLoader::new_lazy(inner.source, inner.chunk) };
+        false
+    })
+}
+
+#[cfg(target_arch = "wasm32")]
+fn is_still_loaded(chunk: &str) -> bool {
+    crate::is_loaded(chunk)
+}
+
+/// Off wasm, as in the tests of this crate, there is no JS loader to unload
+/// anything.
+#[cfg(not(target_arch = "wasm32"))]
+fn is_still_loaded(_chunk: &str) -> bool {
+    true
+}
+
+fn set_loaded(loader: &'static SplitLoaderKey, result: LoadResult) -> Result<(), LoadError> {
+    if result.is_ok() {
+        with_loader(loader, |inner| {
+            inner.loaded.set(true);
+            inner.unloads.set(UNLOADS.load(Ordering::Relaxed));
+        });
+    }
+    result.map(|_| ())
+}
+
+pub async fn ensure_loaded(loader: &'static SplitLoaderKey) -> Result<(), LoadError> {
+    if is_loaded(loader) {
+        return Ok(());
+    }
+    let result = *get_lazy(loader).as_ref().await;
+    set_loaded(loader, result)
+}
+
+/// As [`ensure_loaded`], except that a failed load is not kept, so that the
+/// next call loads the chunk again, for `#[wasm_split(module, fallible)]`.
+pub async fn ensure_loaded_retrying(loader: &'static SplitLoaderKey) -> Result<(), LoadError> {
+    if is_loaded(loader) {
+        return Ok(());
+    }
+    let lazy = get_lazy(loader);
+    let result = *lazy.as_ref().await;
+    if result.is_err() {
+        with_loader(loader, |inner| {
+            let mut current = inner.lazy.borrow_mut();
+            // Unless another call that failed alike replaced it already.
+            if core::ptr::eq::<Lazy>(&**current, &*lazy) {
+                *current = unsafe { LazySplitLoader::new_lazy(inner.source, inner.chunk) };
+            }
+        });
+    }
+    set_loaded(loader, result)
+}
+
+type DeferredLoad = Box<dyn FnOnce(LoadCallbackFn, *const c_void)>;
+
+enum SplitLoaderState {
+    Deferred(DeferredLoad),
+    Pending,
+    Completed(LoadResult),
+}
+
+pub(crate) struct SplitLoader {
+    state: RefCell<SplitLoaderState>,
+    waker: Cell<Option<Waker>>,
+}
+
+impl SplitLoader {
+    pub(crate) fn new(load: DeferredLoad) -> Rc<Self> {
+        Rc::new(SplitLoader {
+            state: RefCell::new(SplitLoaderState::Deferred(load)),
+            waker: Cell::new(None),
+        })
+    }
+
+    fn complete(&self, value: LoadResult) {
+        *self.state.borrow_mut() = SplitLoaderState::Completed(value);
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+pub(crate) struct SplitLoaderFuture {
+    loader: Rc<SplitLoader>,
+}
+
+impl SplitLoaderFuture {
+    pub(crate) fn new(loader: Rc<SplitLoader>) -> Self {
+        SplitLoaderFuture { loader }
+    }
+}
+
+impl Future for SplitLoaderFuture {
+    type Output = LoadResult;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<LoadResult> {
+        let state = core::mem::replace(
+            &mut *self.loader.state.borrow_mut(),
+            SplitLoaderState::Pending,
+        );
+        match state {
+            SplitLoaderState::Deferred(load) => {
+                self.loader.waker.set(Some(cx.waker().clone()));
+                // The state is no longer borrowed here, since `load` may invoke
+                // the callback synchronously.
+                load(
+                    load_callback,
+                    Rc::<SplitLoader>::into_raw(self.loader.clone()) as *const c_void,
+                );
+                Poll::Pending
+            }
+            SplitLoaderState::Pending => {
+                self.loader.waker.set(Some(cx.waker().clone()));
+                Poll::Pending
+            }
+            SplitLoaderState::Completed(value) => {
+                *self.loader.state.borrow_mut() = SplitLoaderState::Completed(value);
+                Poll::Ready(value)
+            }
+        }
+    }
+}
+
+unsafe extern "C" fn load_callback(loader: *const c_void, code: u32, detail: u32) {
+    let result = match LoadError::from_code(code, detail) {
+        Some(error) => Err(error),
+        None => Ok(detail),
+    };
+    unsafe { Rc::from_raw(loader as *const SplitLoader) }.complete(result);
+}
+
+// The tests load with callbacks of their own, which the loader of the
+// `rust-loader` feature does not call.
+#[cfg(all(test, not(feature = "rust-loader")))]
+mod tests {
+    use core::{
+        ffi::c_void,
+        future::Future,
+        pin::pin,
+        sync::atomic::{AtomicPtr, AtomicUsize, Ordering},
+        task::{Context, Poll, Waker},
+    };
+
+    use super::{ensure_loaded, ensure_loaded_retrying, LoadCallbackFn};
+    use crate::LoadError;
+
+    /// Polls a future whose loads complete synchronously, which takes more
+    /// than one poll as the loader only checks for completion when woken.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        for _ in 0..10 {
+            if let Poll::Ready(output) = future
+                .as_mut()
+                .poll(&mut Context::from_waker(Waker::noop()))
+            {
+                return output;
+            }
+        }
+        panic!("Load did not complete");
+    }
+
+    static LOADS: AtomicUsize = AtomicUsize::new(0);
+
+    unsafe extern "C" fn load_ok(callback: LoadCallbackFn, data: *const c_void) {
+        LOADS.fetch_add(1, Ordering::Relaxed);
+        unsafe { callback(data, 0, 0) }
+    }
+
+    unsafe extern "C" fn load_not_found(callback: LoadCallbackFn, data: *const c_void) {
+        unsafe { callback(data, 3, 404) }
+    }
+
+    static FLAKY_LOADS: AtomicUsize = AtomicUsize::new(0);
+
+    /// Fails with HTTP status 503 the first time.
+    unsafe extern "C" fn load_flaky(callback: LoadCallbackFn, data: *const c_void) {
+        match FLAKY_LOADS.fetch_add(1, Ordering::Relaxed) {
+            0 => unsafe { callback(data, 3, 503) },
+            _ => unsafe { callback(data, 0, 0) },
+        }
+    }
+
+    static DEFERRED_LOADS: AtomicUsize = AtomicUsize::new(0);
+    static DEFERRED_DATA: AtomicPtr<c_void> = AtomicPtr::new(core::ptr::null_mut());
+
+    /// Only completes once the test invokes the callback.
+    unsafe extern "C" fn load_deferred(_callback: LoadCallbackFn, data: *const c_void) {
+        DEFERRED_LOADS.fetch_add(1, Ordering::Relaxed);
+        DEFERRED_DATA.store(data as *mut c_void, Ordering::Relaxed);
+    }
+
+    // Expands to a `static` without `std`, and a `thread_local!` with it.
+    crate::__split_loader!(LOADER_OK, load_ok, "ok");
+    crate::__split_loader!(LOADER_DEFERRED, load_deferred, "deferred");
+    crate::__split_loader!(LOADER_NOT_FOUND, load_not_found, "not_found");
+    crate::__split_loader!(LOADER_FLAKY, load_flaky, "flaky");
+
+    #[test]
+    fn loads_once() {
+        assert_eq!(block_on(ensure_loaded(&LOADER_OK)), Ok(()));
+        assert_eq!(block_on(ensure_loaded(&LOADER_OK)), Ok(()));
+        assert_eq!(LOADS.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn shares_concurrent_loads() {
+        let mut first = pin!(ensure_loaded(&LOADER_DEFERRED));
+        let mut second = pin!(ensure_loaded(&LOADER_DEFERRED));
+        let mut context = Context::from_waker(Waker::noop());
+        assert!(first.as_mut().poll(&mut context).is_pending());
+        assert!(second.as_mut().poll(&mut context).is_pending());
+        unsafe { super::load_callback(DEFERRED_DATA.load(Ordering::Relaxed), 0, 0) };
+        assert_eq!(block_on(first), Ok(()));
+        assert_eq!(block_on(second), Ok(()));
+        assert_eq!(DEFERRED_LOADS.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn reports_load_errors() {
+        assert_eq!(
+            block_on(ensure_loaded(&LOADER_NOT_FOUND)),
+            Err(LoadError::Http(404))
+        );
+    }
+
+    #[test]
+    fn loads_again_after_failure_when_retrying() {
+        assert_eq!(
+            block_on(ensure_loaded_retrying(&LOADER_FLAKY)),
+            Err(LoadError::Http(503))
+        );
+        assert_eq!(block_on(ensure_loaded_retrying(&LOADER_FLAKY)), Ok(()));
+        assert_eq!(block_on(ensure_loaded_retrying(&LOADER_FLAKY)), Ok(()));
+        assert_eq!(FLAKY_LOADS.load(Ordering::Relaxed), 2);
+    }
+}
@@ -0,0 +1,161 @@
+use crate::{SplitChunk, SplitLazy};
+
+/// Data of each locale in a split module of its own, such as translations or
+/// the formatting data of ICU, created by [`locale_data!`](crate::locale_data):
+///
+/// ```ignore
+/// wasm_split::locale_data! {
+///     static MESSAGES: Catalog = {
+///         "de" => Catalog::parse(include_str!("locales/de.ftl")),
+///         "pt-BR" => Catalog::parse(include_str!("locales/pt-BR.ftl")),
+///     };
+/// }
+///
+/// let catalog = MESSAGES.get(&user_locale).await.unwrap_or(&ENGLISH);
+/// ```
+///
+/// The data of each locale is a [`SplitLazy`] of the split module
+/// `locale_<tag>`, e.g. `locale_de` and `locale_pt_br`, which every
+/// `locale_data!` shares, so that the translations and formatting data of a
+/// locale are one chunk. [`get`](Self::get) picks the locale that matches the
+/// active one and loads only its chunk.
+pub struct LocaleData<T: 'static> {
+    locales: &'static [(&'static str, SplitChunk, &'static SplitLazy<T>)],
+}
+
+impl<T> LocaleData<T> {
+    /// Data of each locale, given by its BCP 47 tag, the chunk of its split
+    /// module and its value.
+    pub const fn new(
+        locales: &'static [(&'static str, SplitChunk, &'static SplitLazy<T>)],
+    ) -> Self {
+        Self { locales }
+    }
+
+    /// Tags of the locales that have data, in the order of the macro.
+    pub fn locales(&self) -> impl Iterator<Item = &'static str> {
+        self.locales.iter().map(|(locale, ..)| *locale)
+    }
+
+    /// The locale with data that best matches the first of `requested` that
+    /// any does, such as the `navigator.languages` of the browser, with the
+    /// lookup of RFC 4647: tags are compared ignoring case and `_` for `-`,
+    /// and a tag without data falls back to shorter ones, so that `de-AT`
+    /// gets the data of `de`.
+    pub fn negotiate<'a>(
+        &self,
+        requested: impl IntoIterator<Item = &'a str>,
+    ) -> Option<&'static str> {
+        requested
+            .into_iter()
+            .find_map(|tag| self.entry(tag).map(|(locale, ..)| *locale))
+    }
+
+    /// Loads the data of the locale that matches `locale`, if any does; see
+    /// [`negotiate`](Self::negotiate). No other locale's chunk is loaded.
+    ///
+    /// Panics if the chunk fails to load, like [`SplitLazy::get`].
+    pub async fn get(&self, locale: &str) -> Option<&T> {
+        let (_, _, data) = self.entry(locale)?;
+        Some(data.get().await)
+    }
+
+    /// The data of the locale that matches `locale`, if it has already been
+    /// loaded, without loading anything.
+    pub fn get_if_loaded(&self, locale: &str) -> Option<&T> {
+        self.entry(locale)?.2.get_if_initialized()
+    }
+
+    /// Chunk of the locale that matches `locale`, e.g. to preload it while
+    /// the user picks the locale.
+    pub fn chunk(&self, locale: &str) -> Option<SplitChunk> {
+        self.entry(locale).map(|(_, chunk, _)| *chunk)
+    }
+
+    fn entry(&self, tag: &str) -> Option<&(&'static str, SplitChunk, &'static SplitLazy<T>)> {
+        let mut tag = tag;
+        loop {
+            if let Some(entry) = self
+                .locales
+                .iter()
+                .find(|(locale, ..)| same_tag(locale, tag))
+            {
+                return Some(entry);
+            }
+            let (prefix, _) = tag.rsplit_once(['-', '_'])?;
+            // Single-letter subtags introduce extensions, whose value was
+            // just removed.
+            tag = match prefix.rsplit_once(['-', '_']) {
+                Some((shorter, singleton)) if singleton.len() == 1 => shorter,
+                _ => prefix,
+            };
+        }
+    }
+}
+
+fn same_tag(a: &str, b: &str) -> bool {
+    let normalize = |c: char| {
+        if c == '_' {
+            '-'
+        } else {
+            c.to_ascii_lowercase()
+        }
+    };
+    a.len() == b.len() && a.chars().map(normalize).eq(b.chars().map(normalize))
+}
+
+#[cfg(test)]
+mod tests {
+    use core::{
+        future::Future,
+        pin::pin,
+        task::{Context, Poll, Waker},
+    };
+
+    use alloc::{string::String, vec::Vec};
+
+    use crate::wasm_split;
+
+    fn now<F: Future>(future: F) -> F::Output {
+        match pin!(future).poll(&mut Context::from_waker(Waker::noop())) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("Locale data was not loaded right away"),
+        }
+    }
+
+    wasm_split::locale_data! {
+        static GREETINGS: &'static str = {
+            "de" => "Hallo",
+            "pt-BR" => "Olá",
+            "zh-Hant" => "你好",
+        };
+
+        static WORDS: Vec<String> = {
+            "de" => "eins zwei".split(' ').map(String::from).collect(),
+        };
+    }
+
+    #[test]
+    fn loads_the_data_of_the_matching_locale() {
+        assert_eq!(
+            GREETINGS.locales().collect::<Vec<_>>(),
+            ["de", "pt-BR", "zh-Hant"]
+        );
+        assert_eq!(GREETINGS.negotiate(["fr", "de-AT"]), Some("de"));
+        assert_eq!(GREETINGS.negotiate(["PT_br"]), Some("pt-BR"));
+        assert_eq!(
+            GREETINGS.negotiate(["zh-Hant-TW-u-nu-hanidec"]),
+            Some("zh-Hant")
+        );
+        assert_eq!(GREETINGS.negotiate(["pt", "en"]), None);
+        assert_eq!(GREETINGS.chunk("de-CH").unwrap().name(), "locale_de");
+        assert_eq!(GREETINGS.chunk("pt-BR").unwrap().name(), "locale_pt_br");
+
+        assert_eq!(GREETINGS.get_if_loaded("de"), None);
+        assert_eq!(now(GREETINGS.get("de-DE")), Some(&"Hallo"));
+        assert_eq!(GREETINGS.get_if_loaded("de"), Some(&"Hallo"));
+        assert_eq!(GREETINGS.get_if_loaded("pt-BR"), None);
+        assert_eq!(now(GREETINGS.get("en")), None);
+        assert_eq!(now(WORDS.get("de")).unwrap(), &["eins", "zwei"]);
+    }
+}
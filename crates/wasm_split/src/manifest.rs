@@ -0,0 +1,94 @@
+use alloc::boxed::Box;
+use core::ffi::c_void;
+
+use crate::{
+    loader::{LoadCallbackFn, SplitLoader, SplitLoaderFuture},
+    LoadError,
+};
+
+#[link(wasm_import_module = "./__wasm_split.js")]
+extern "C" {
+    fn __wasm_split_reload_manifest(callback: LoadCallbackFn, data: *const c_void);
+    fn __wasm_split_set_base_url(url: *const u8, len: usize);
+}
+
+/// Outcome of [`reload_manifest`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ManifestReload {
+    /// The deployed manifest belongs to the running build, or to another
+    /// build whose chunks this one can load: each is the same file as the
+    /// running build's, or defines the same split functions with the same
+    /// signatures in the same table slots. Chunks that have not started
+    /// loading yet will be loaded from the URLs it lists.
+    Updated,
+    /// The deployed manifest belongs to a different build, with chunks that
+    /// cannot be loaded into this one. Chunk URLs are left unchanged; reload
+    /// the page at the next convenient opportunity.
+    Incompatible,
+}
+
+/// Re-fetches the manifest deployed next to the chunks, bypassing the HTTP
+/// cache.
+///
+/// Long-lived sessions can call this, e.g. when the page becomes visible
+/// again after a long time in the background, so that chunks which have
+/// moved since the page was loaded are fetched from their new location.
+pub async fn reload_manifest() -> Result<ManifestReload, LoadError> {
+    let detail = SplitLoaderFuture::new(SplitLoader::new(Box::new(|callback, data| unsafe {
+        __wasm_split_reload_manifest(callback, data)
+    })))
+    .await?;
+    Ok(if detail == 0 {
+        ManifestReload::Updated
+    } else {
+        ManifestReload::Incompatible
+    })
+}
+
+/// Loads chunks from `url` instead of the directory of the loader, e.g. from
+/// a CDN chosen at page load, or the sub-path that the app is served under:
+///
+/// ```ignore
+/// wasm_split::set_base_url("https://cdn.example.com/app/v2/");
+/// ```
+///
+/// Relative URLs resolve against the page. Chunks that are loading or loaded
+/// keep the URL they were requested from, and later calls of
+/// [`reload_manifest`] resolve chunks against `url` as well. Pages can also
+/// set the base before the app starts, with `globalThis.WASM_SPLIT_BASE_URL`
+/// or `<meta name="wasm-split-base-url" content="...">`.
+pub fn set_base_url(url: &str) {
+    #[cfg(feature = "rust-loader")]
+    crate::rust_loader::set_base_url(url);
+    #[cfg(not(feature = "rust-loader"))]
+    unsafe {
+        __wasm_split_set_base_url(url.as_ptr(), url.len())
+    }
+}
+
+/// Compiles the Ed25519 public key that the manifest must be signed with into
+/// the main module, for deployments whose CDN is not fully trusted:
+///
+/// ```ignore
+/// wasm_split::manifest_public_key!(*include_bytes!("../manifest-key.pub"));
+/// ```
+///
+/// The key file holds the 32 bytes written by `wasm-split public-key`. With
+/// the key in place, `wasm-split` refuses to run without the private key as
+/// `--signing-key`, and writes the manifest's signature next to it. The
+/// loader then checks that signature before using anything from a manifest
+/// it fetches, and [`reload_manifest`] fails with
+/// [`LoadError::IntegrityMismatch`] if it does not verify. The manifest
+/// embedded in the loader is signed as well, and checked before the first
+/// load. Both list the SHA-256 digest of every chunk, and a chunk whose bytes
+/// do not match it fails to load with [`LoadError::IntegrityMismatch`] before
+/// it is compiled.
+#[macro_export]
+macro_rules! manifest_public_key {
+    ($key:expr) => {
+        #[allow(non_upper_case_globals)]
+        #[no_mangle]
+        pub static __wasm_split_manifest_public_key: [u8; 32] = $key;
+    };
+}
@@ -0,0 +1,35 @@
+//! Panic hook that attributes panics in split code to the owning chunk.
+
+use std::sync::Once;
+
+#[cfg(target_arch = "wasm32")]
+#[link(wasm_import_module = "./__wasm_split.js")]
+extern "C" {
+    fn __wasm_split_report_panic(message: *const u8, len: usize);
+}
+
+/// Installs a panic hook, chained in front of the existing one, that reports
+/// the split chunk a panic originated from along with the time at which that
+/// chunk was loaded.
+///
+/// Call this after installing any other panic hook (such as
+/// `console_error_panic_hook`), since the previous hook is still invoked
+/// afterwards. Calling this more than once has no additional effect.
+pub fn set_once() {
+    static SET_HOOK: Once = Once::new();
+    SET_HOOK.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            report(&info.to_string());
+            previous(info);
+        }));
+    });
+}
+
+#[cfg(target_arch = "wasm32")]
+fn report(message: &str) {
+    unsafe { __wasm_split_report_panic(message.as_ptr(), message.len()) };
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn report(_message: &str) {}
@@ -0,0 +1,261 @@
+use alloc::{boxed::Box, rc::Rc};
+use core::{
+    cell::Cell,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+use crate::{LoadError, SplitChunk};
+
+/// Progress of a [`ParallelLoad`], passed to its
+/// [`on_progress`](ParallelLoad::on_progress) callback whenever one of its
+/// two halves completes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ParallelProgress {
+    data_ready: bool,
+    chunk_loaded: bool,
+}
+
+impl ParallelProgress {
+    pub fn data_ready(&self) -> bool {
+        self.data_ready
+    }
+
+    pub fn chunk_loaded(&self) -> bool {
+        self.chunk_loaded
+    }
+
+    /// Fraction of the two halves that have completed, for progress bars.
+    pub fn fraction(&self) -> f32 {
+        (self.data_ready as u8 + self.chunk_loaded as u8) as f32 / 2.0
+    }
+}
+
+struct AbortState {
+    aborted: Cell<bool>,
+    waker: Cell<Option<Waker>>,
+}
+
+/// Cancels a [`ParallelLoad`] from outside, e.g. when the user navigates
+/// away from the route it loads.
+#[derive(Clone)]
+pub struct AbortHandle {
+    state: Rc<AbortState>,
+}
+
+impl AbortHandle {
+    /// Makes the load resolve to [`LoadError::Aborted`] and drops its data
+    /// future. Has no effect once the load has completed.
+    pub fn abort(&self) {
+        self.state.aborted.set(true);
+        if let Some(waker) = self.state.waker.take() {
+            waker.wake();
+        }
+    }
+
+    pub fn is_aborted(&self) -> bool {
+        self.state.aborted.get()
+    }
+}
+
+type ChunkFuture = Pin<Box<dyn Future<Output = Result<(), LoadError>>>>;
+
+/// Future returned by [`load_parallel`].
+pub struct ParallelLoad<F: Future> {
+    data: Option<Pin<Box<F>>>,
+    data_output: Option<F::Output>,
+    chunk: Option<ChunkFuture>,
+    progress: ParallelProgress,
+    on_progress: Option<Box<dyn FnMut(ParallelProgress)>>,
+    abort: Rc<AbortState>,
+}
+
+// Neither future is pinned in place, so moving the combinator is fine.
+impl<F: Future> Unpin for ParallelLoad<F> {}
+
+/// Loads the data of a route and the chunk holding its view at the same time,
+/// so that neither waits for the other.
+///
+/// Resolves to the output of `data` once both have completed. If the chunk
+/// fails to load, or the load is aborted through an [`AbortHandle`], `data`
+/// is dropped without waiting for it and the error is returned instead.
+///
+/// Dropping the load, as routers do on navigation, cancels it as well. Only
+/// the wait for the chunk is cancelled, not its download: chunks are shared
+/// between all loads of them, and a chunk that has started loading finishes
+/// in the background.
+pub fn load_parallel<F: Future>(data: F, chunk: SplitChunk) -> ParallelLoad<F> {
+    ParallelLoad::new(data, Box::pin(async move { chunk.load().await }))
+}
+
+impl<F: Future> ParallelLoad<F> {
+    fn new(data: F, chunk: ChunkFuture) -> Self {
+        Self {
+            data: Some(Box::pin(data)),
+            data_output: None,
+            chunk: Some(chunk),
+            progress: ParallelProgress::default(),
+            on_progress: None,
+            abort: Rc::new(AbortState {
+                aborted: Cell::new(false),
+                waker: Cell::new(None),
+            }),
+        }
+    }
+
+    /// Invokes `callback` with the combined progress each time the data or
+    /// the chunk becomes ready.
+    pub fn on_progress(mut self, callback: impl FnMut(ParallelProgress) + 'static) -> Self {
+        self.on_progress = Some(Box::new(callback));
+        self
+    }
+
+    pub fn abort_handle(&self) -> AbortHandle {
+        AbortHandle {
+            state: self.abort.clone(),
+        }
+    }
+
+    pub fn progress(&self) -> ParallelProgress {
+        self.progress
+    }
+
+    fn report_progress(&mut self) {
+        if let Some(callback) = self.on_progress.as_mut() {
+            callback(self.progress);
+        }
+    }
+}
+
+impl<F: Future> Future for ParallelLoad<F> {
+    type Output = Result<F::Output, LoadError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        if this.abort.aborted.get() {
+            this.data = None;
+            this.chunk = None;
+            return Poll::Ready(Err(LoadError::Aborted));
+        }
+        this.abort.waker.set(Some(cx.waker().clone()));
+        if let Some(chunk) = this.chunk.as_mut() {
+            if let Poll::Ready(result) = chunk.as_mut().poll(cx) {
+                this.chunk = None;
+                if let Err(error) = result {
+                    this.data = None;
+                    return Poll::Ready(Err(error));
+                }
+                this.progress.chunk_loaded = true;
+                this.report_progress();
+            }
+        }
+        if let Some(data) = this.data.as_mut() {
+            if let Poll::Ready(output) = data.as_mut().poll(cx) {
+                this.data = None;
+                this.data_output = Some(output);
+                this.progress.data_ready = true;
+                this.report_progress();
+            }
+        }
+        if this.chunk.is_none() && this.data.is_none() {
+            let output = this
+                .data_output
+                .take()
+                .expect("ParallelLoad polled after completion");
+            Poll::Ready(Ok(output))
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{boxed::Box, rc::Rc, vec::Vec};
+    use core::{
+        cell::{Cell, RefCell},
+        future::Future,
+        pin::Pin,
+        task::{Context, Poll, Waker},
+    };
+
+    use super::{ParallelLoad, ParallelProgress};
+    use crate::LoadError;
+
+    /// A future that is ready once the test sets its output.
+    struct Later<T>(Rc<Cell<Option<T>>>);
+
+    impl<T> Future for Later<T> {
+        type Output = T;
+
+        fn poll(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<T> {
+            self.0.take().map_or(Poll::Pending, Poll::Ready)
+        }
+    }
+
+    fn later<T>() -> (Later<T>, Rc<Cell<Option<T>>>) {
+        let output = Rc::new(Cell::new(None));
+        (Later(output.clone()), output)
+    }
+
+    fn poll<F: Future>(load: &mut ParallelLoad<F>) -> Poll<Result<F::Output, LoadError>> {
+        Pin::new(load).poll(&mut Context::from_waker(Waker::noop()))
+    }
+
+    #[test]
+    fn resolves_to_the_data_once_the_chunk_is_loaded() {
+        let (data, data_output) = later();
+        let (chunk, chunk_output) = later();
+        let reports = Rc::new(RefCell::new(Vec::new()));
+        let mut load = ParallelLoad::new(data, Box::pin(chunk)).on_progress({
+            let reports = reports.clone();
+            move |progress| reports.borrow_mut().push(progress)
+        });
+        assert!(poll(&mut load).is_pending());
+
+        data_output.set(Some(7));
+        assert!(poll(&mut load).is_pending());
+        assert!(load.progress().data_ready() && !load.progress().chunk_loaded());
+        assert_eq!(load.progress().fraction(), 0.5);
+
+        chunk_output.set(Some(Ok(())));
+        assert_eq!(poll(&mut load), Poll::Ready(Ok(7)));
+        assert_eq!(load.progress().fraction(), 1.0);
+        let reports = reports.borrow();
+        assert_eq!(reports.len(), 2);
+        assert_eq!(
+            reports[0],
+            ParallelProgress {
+                data_ready: true,
+                chunk_loaded: false,
+            }
+        );
+        assert!(reports[1].data_ready() && reports[1].chunk_loaded());
+    }
+
+    #[test]
+    fn drops_the_data_when_the_chunk_fails() {
+        let (data, data_output) = later::<u32>();
+        let (chunk, chunk_output) = later();
+        let mut load = ParallelLoad::new(data, Box::pin(chunk));
+        assert!(poll(&mut load).is_pending());
+        chunk_output.set(Some(Err(LoadError::Http(404))));
+        assert_eq!(poll(&mut load), Poll::Ready(Err(LoadError::Http(404))));
+        assert_eq!(Rc::strong_count(&data_output), 1);
+    }
+
+    #[test]
+    fn aborts_through_its_handle() {
+        let (data, data_output) = later::<u32>();
+        let (chunk, _chunk_output) = later();
+        let mut load = ParallelLoad::new(data, Box::pin(chunk));
+        let handle = load.abort_handle();
+        assert!(poll(&mut load).is_pending());
+        assert!(!handle.is_aborted());
+        handle.abort();
+        assert!(handle.is_aborted());
+        assert_eq!(poll(&mut load), Poll::Ready(Err(LoadError::Aborted)));
+        assert_eq!(Rc::strong_count(&data_output), 1);
+    }
+}
@@ -0,0 +1,433 @@
+use alloc::{boxed::Box, rc::Rc, string::String, vec, vec::Vec};
+use core::{
+    cell::{Cell, RefCell},
+    future::{ready, Future, Ready},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::loader::{SplitLoader, SplitLoaderFuture};
+use crate::{on_event, EventSubscription, LoadError, LoadEvent};
+
+#[link(wasm_import_module = "./__wasm_split.js")]
+extern "C" {
+    fn __wasm_split_route_start(
+        route: *const u8,
+        len: usize,
+        callback: crate::loader::LoadCallbackFn,
+        data: *const core::ffi::c_void,
+    ) -> u32;
+    fn __wasm_split_route_progress(id: u32, ptr: *mut u32);
+    fn __wasm_split_route_release(id: u32);
+    fn __wasm_split_route_preload_links(
+        path: *const u8,
+        len: usize,
+        buf: *mut u8,
+        capacity: usize,
+    ) -> usize;
+    fn __wasm_split_route_chunks(
+        path: *const u8,
+        len: usize,
+        buf: *mut u8,
+        capacity: usize,
+    ) -> usize;
+    fn __wasm_split_preload_route(path: *const u8, len: usize);
+    fn __wasm_split_abort_route(path: *const u8, len: usize) -> u32;
+}
+
+/// Combined progress of a [`RouteLoad`], over the chunks of the route and
+/// the data loaded alongside them, if any.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RouteProgress {
+    bytes: u32,
+    total: u32,
+    data_bytes: u32,
+    data_ready: bool,
+}
+
+impl RouteProgress {
+    /// Bytes of the route's chunks downloaded so far, where chunks that were
+    /// already loaded count in full.
+    pub fn bytes(&self) -> u32 {
+        self.bytes
+    }
+
+    /// Size of all chunks of the route according to the manifest.
+    pub fn total(&self) -> u32 {
+        self.total
+    }
+
+    /// Expected size of the data, as given to [`RouteLoad::with_data`].
+    pub fn data_bytes(&self) -> u32 {
+        self.data_bytes
+    }
+
+    pub fn data_ready(&self) -> bool {
+        self.data_ready
+    }
+
+    /// Fraction of the chunks and data loaded so far, weighted by their size,
+    /// for a single progress bar. `1.0` if there is nothing to load.
+    pub fn fraction(&self) -> f32 {
+        let done = self.bytes as f64 + if self.data_ready { self.data_bytes } else { 0 } as f64;
+        let total = self.total as f64 + self.data_bytes as f64;
+        if total == 0.0 {
+            1.0
+        } else {
+            (done / total) as f32
+        }
+    }
+}
+
+type ProgressCallback = RefCell<Option<Box<dyn FnMut(RouteProgress)>>>;
+
+/// State shared with the listener that reports progress.
+struct RouteState {
+    /// ID of the load in the loader while it runs, or 0.
+    id: Cell<u32>,
+    /// Progress of the chunks when the load was released.
+    released: Cell<(u32, u32)>,
+    data_bytes: u32,
+    data_ready: Cell<bool>,
+    on_progress: ProgressCallback,
+}
+
+impl RouteState {
+    fn new(data_bytes: u32) -> Rc<Self> {
+        Rc::new(Self {
+            id: Cell::new(0),
+            released: Cell::new((0, 0)),
+            data_bytes,
+            data_ready: Cell::new(false),
+            on_progress: RefCell::new(None),
+        })
+    }
+
+    fn progress(&self) -> RouteProgress {
+        let (bytes, total) = match self.id.get() {
+            0 => self.released.get(),
+            id => {
+                let mut progress = [0; 2];
+                unsafe { __wasm_split_route_progress(id, progress.as_mut_ptr()) };
+                (progress[0], progress[1])
+            }
+        };
+        RouteProgress {
+            bytes,
+            total,
+            data_bytes: self.data_bytes,
+            data_ready: self.data_ready.get(),
+        }
+    }
+
+    fn report_progress(&self) {
+        let progress = self.progress();
+        if let Ok(mut callback) = self.on_progress.try_borrow_mut() {
+            if let Some(callback) = callback.as_mut() {
+                callback(progress);
+            }
+        }
+    }
+
+    fn release(&self) {
+        if self.id.get() != 0 {
+            let progress = self.progress();
+            self.released.set((progress.bytes, progress.total));
+            unsafe { __wasm_split_route_release(self.id.replace(0)) };
+        }
+    }
+}
+
+/// Future returned by [`load_route`].
+pub struct RouteLoad<F: Future = Ready<()>> {
+    route: Option<String>,
+    chunks: Option<SplitLoaderFuture>,
+    data: Option<Pin<Box<F>>>,
+    data_output: Option<F::Output>,
+    state: Rc<RouteState>,
+    subscription: Option<EventSubscription>,
+}
+
+// The data future is boxed, so moving the combinator is fine.
+impl<F: Future> Unpin for RouteLoad<F> {}
+
+/// Loads every chunk that `route` needs as one group, as declared in the
+/// `[routes]` table of `wasm-split.toml` or with `route = "..."` on split
+/// functions, for the first load of a page or a navigation.
+///
+/// Unlike a [`LoadEvent`] listener, which sees chunks one at a time, the
+/// load reports the progress of all of them at once, weighted by their size,
+/// so that a single indicator can show how much of the route is left:
+///
+/// ```ignore
+/// let comments = wasm_split::load_route("/comments")
+///     .with_data(fetch_comments(), 20_000)
+///     .on_progress(|progress| set_progress(progress.fraction()))
+///     .await?;
+/// ```
+///
+/// Fails with [`LoadError::UnknownChunk`] for a route the manifest does not
+/// list. As with [`load_parallel`](crate::load_parallel), the data is
+/// dropped without waiting for it if a chunk fails to load.
+pub fn load_route(route: &str) -> RouteLoad {
+    RouteLoad {
+        route: Some(route.into()),
+        chunks: None,
+        data: Some(Box::pin(ready(()))),
+        data_output: None,
+        state: RouteState::new(0),
+        subscription: None,
+    }
+}
+
+impl RouteLoad {
+    /// Loads `data` at the same time as the chunks, and resolves to its
+    /// output. `expected_bytes` is roughly the size of the response, with
+    /// which the data counts towards [`RouteProgress::fraction`] once ready.
+    pub fn with_data<F: Future>(mut self, data: F, expected_bytes: u32) -> RouteLoad<F> {
+        RouteLoad {
+            route: self.route.take(),
+            chunks: None,
+            data: Some(Box::pin(data)),
+            data_output: None,
+            state: RouteState::new(expected_bytes),
+            subscription: None,
+        }
+    }
+}
+
+impl<F: Future> RouteLoad<F> {
+    /// Invokes `callback` with the combined progress whenever a chunk of the
+    /// route progresses, and once the data is ready.
+    pub fn on_progress(mut self, callback: impl FnMut(RouteProgress) + 'static) -> Self {
+        *self.state.on_progress.borrow_mut() = Some(Box::new(callback));
+        let state = self.state.clone();
+        self.subscription = Some(on_event(move |event| {
+            if matches!(
+                event,
+                LoadEvent::Progress { .. } | LoadEvent::Instantiated { .. }
+            ) && state.id.get() != 0
+            {
+                state.report_progress();
+            }
+        }));
+        self
+    }
+
+    pub fn progress(&self) -> RouteProgress {
+        self.state.progress()
+    }
+}
+
+impl<F: Future> Future for RouteLoad<F> {
+    type Output = Result<F::Output, LoadError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        if let Some(route) = this.route.take() {
+            let state = this.state.clone();
+            this.chunks = Some(SplitLoaderFuture::new(SplitLoader::new(Box::new(
+                move |callback, data| unsafe {
+                    state.id.set(__wasm_split_route_start(
+                        route.as_ptr(),
+                        route.len(),
+                        callback,
+                        data,
+                    ));
+                },
+            ))));
+        }
+        if let Some(chunks) = this.chunks.as_mut() {
+            if let Poll::Ready(result) = Pin::new(chunks).poll(cx) {
+                this.chunks = None;
+                this.state.release();
+                if let Err(error) = result {
+                    this.data = None;
+                    return Poll::Ready(Err(error));
+                }
+            }
+        }
+        if let Some(data) = this.data.as_mut() {
+            if let Poll::Ready(output) = data.as_mut().poll(cx) {
+                this.data = None;
+                this.data_output = Some(output);
+                this.state.data_ready.set(true);
+                if this.state.data_bytes != 0 {
+                    this.state.report_progress();
+                }
+            }
+        }
+        if this.chunks.is_none() && this.data.is_none() {
+            this.subscription = None;
+            let output = this
+                .data_output
+                .take()
+                .expect("RouteLoad polled after completion");
+            Poll::Ready(Ok(output))
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl<F: Future> Drop for RouteLoad<F> {
+    fn drop(&mut self) {
+        self.state.release();
+    }
+}
+
+/// Names of the chunks that the route of `path` needs, as declared in the
+/// `[routes]` table of `wasm-split.toml` or with `route = "..."` on split
+/// functions, including the shared chunks they depend on. Folded modules
+/// have no chunk of their own and are left out.
+///
+/// This is the route's entry of the `route → chunks` table in the manifest,
+/// for apps that decide by themselves what to prefetch, e.g. only routes
+/// whose chunks are small. Paths nested under a route without being one get
+/// its chunks, as with [`route_preload_links`], and unknown paths get none.
+pub fn route_chunks(path: &str) -> Vec<String> {
+    let mut buf = vec![0u8; 256];
+    loop {
+        let len = unsafe {
+            __wasm_split_route_chunks(path.as_ptr(), path.len(), buf.as_mut_ptr(), buf.len())
+        };
+        if len <= buf.len() {
+            buf.truncate(len);
+            break;
+        }
+        buf.resize(len, 0);
+    }
+    String::from_utf8_lossy(&buf)
+        .lines()
+        .map(String::from)
+        .collect()
+}
+
+/// Starts loading the chunks that the route of `path` needs in the
+/// background, as [`preload`](crate::preload) does for each of them, so that
+/// a navigation to it does not wait for the network. Call it when a link to
+/// the route is likely to be followed: when the pointer enters it, or when
+/// an `IntersectionObserver` sees it scroll into view:
+///
+/// ```ignore
+/// view! {
+///     <A href="/comments" on:pointerenter=|_| wasm_split::preload_route("/comments")>
+///         "Comments"
+///     </A>
+/// }
+/// ```
+///
+/// Routes whose chunks are loaded or loading already cost nothing, so the
+/// call can be repeated freely. Unknown paths are ignored.
+pub fn preload_route(path: &str) {
+    unsafe { __wasm_split_preload_route(path.as_ptr(), path.len()) }
+}
+
+/// Cancels the downloads in flight of the chunks that the route of `path`
+/// needs, as [`abort_loads`](crate::abort_loads) does, e.g. when a navigation
+/// leaves the route before it finished loading:
+///
+/// ```ignore
+/// let previous = location.pathname.get_untracked();
+/// on_cleanup(move || wasm_split::abort_route(&previous));
+/// ```
+///
+/// Chunks that the new route needs as well are better left alone, so
+/// routers that know it abort the difference with `abort_loads` instead.
+pub fn abort_route(path: &str) -> usize {
+    unsafe { __wasm_split_abort_route(path.as_ptr(), path.len()) as usize }
+}
+
+/// A chunk to preload for a route, as returned by [`route_preload_links`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreloadLink {
+    href: String,
+    integrity: Option<String>,
+}
+
+impl PreloadLink {
+    /// Absolute URL of the chunk.
+    pub fn href(&self) -> &str {
+        &self.href
+    }
+
+    /// Hash that the loader fetches the chunk with, which the `integrity`
+    /// attribute of the link has to match for the browser to use the
+    /// preloaded response.
+    pub fn integrity(&self) -> Option<&str> {
+        self.integrity.as_deref()
+    }
+}
+
+/// Chunks that the route of `path` needs and that have not started loading,
+/// for `<link rel="preload" as="fetch" crossorigin>` elements that the page
+/// adds when it knows where a navigation goes, before the route's code asks
+/// for them. This helps most with chunks that the route loads only after
+/// others, which would otherwise be fetched one after another.
+///
+/// Paths nested under a route without being one get its chunks, so that
+/// e.g. `/posts/42` gets those of `/posts`. Unknown paths get none.
+pub fn route_preload_links(path: &str) -> Vec<PreloadLink> {
+    let mut buf = vec![0u8; 512];
+    loop {
+        let len = unsafe {
+            __wasm_split_route_preload_links(path.as_ptr(), path.len(), buf.as_mut_ptr(), buf.len())
+        };
+        if len <= buf.len() {
+            buf.truncate(len);
+            break;
+        }
+        buf.resize(len, 0);
+    }
+    parse_preload_links(&String::from_utf8_lossy(&buf))
+}
+
+fn parse_preload_links(lines: &str) -> Vec<PreloadLink> {
+    lines
+        .lines()
+        .map(|line| {
+            let (href, integrity) = line.split_once(' ').unwrap_or((line, ""));
+            PreloadLink {
+                href: href.into(),
+                integrity: (!integrity.is_empty()).then(|| integrity.into()),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_preload_links, RouteProgress};
+
+    #[test]
+    fn weighs_progress_by_size() {
+        let progress = RouteProgress {
+            bytes: 300,
+            total: 600,
+            data_bytes: 400,
+            data_ready: true,
+        };
+        assert_eq!(progress.fraction(), 0.7);
+        assert_eq!(
+            RouteProgress {
+                data_ready: false,
+                ..progress
+            }
+            .fraction(),
+            0.3
+        );
+        assert_eq!(RouteProgress::default().fraction(), 1.0);
+    }
+
+    #[test]
+    fn parses_preload_links() {
+        let links = parse_preload_links(
+            "https://example.com/pkg/a.wasm sha384-abc\nhttps://example.com/pkg/b.wasm \n",
+        );
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[0].href(), "https://example.com/pkg/a.wasm");
+        assert_eq!(links[0].integrity(), Some("sha384-abc"));
+        assert_eq!(links[1].href(), "https://example.com/pkg/b.wasm");
+        assert_eq!(links[1].integrity(), None);
+    }
+}
@@ -0,0 +1,603 @@
+//! Loader of the `rust-loader` feature, which loads chunks from Rust code of
+//! the main module with `js-sys`, rather than through the loader script, as
+//! documented in the Rust loader section of the crate docs.
+//!
+//! The loader reads the manifest written next to the chunks on the first
+//! load. Loading a chunk starts the downloads of the chunk and of the chunks
+//! it depends on all at once, each compiled as it arrives, and instantiates
+//! them in dependency order with the exports of the main module, as the
+//! loader script does, before running their `on_load` hooks through the
+//! table. Loads in progress are shared, and failed ones forgotten, so that
+//! the next load starts over.
+
+use alloc::{
+    boxed::Box,
+    collections::{BTreeMap, BTreeSet},
+    format,
+    rc::Rc,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::{cell::RefCell, future::Future, pin::Pin};
+
+use js_sys::{Array, Function, Object, Promise, Reflect, WebAssembly};
+use wasm_bindgen::{prelude::wasm_bindgen, JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+
+use crate::{loader::LoadResult, LoadError};
+
+/// Name of the manifest that `wasm-split` writes next to the chunks.
+const MANIFEST_FILE: &str = "wasm-split-manifest.json";
+
+/// Tells `wasm-split` that the main module loads its chunks itself, so that it
+/// rejects the options that need the loader script.
+#[allow(non_upper_case_globals)]
+#[no_mangle]
+static __wasm_split_rust_loader: u8 = 1;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_name = fetch)]
+    fn fetch_with_init(url: &str, init: &Object) -> Promise;
+
+    type Response;
+    #[wasm_bindgen(method, getter)]
+    fn ok(this: &Response) -> bool;
+    #[wasm_bindgen(method, getter)]
+    fn status(this: &Response) -> u16;
+    #[wasm_bindgen(method, getter)]
+    fn headers(this: &Response) -> Headers;
+    #[wasm_bindgen(method, js_name = arrayBuffer)]
+    fn array_buffer(this: &Response) -> Promise;
+    #[wasm_bindgen(method)]
+    fn json(this: &Response) -> Promise;
+
+    type Headers;
+    #[wasm_bindgen(method)]
+    fn get(this: &Headers, name: &str) -> Option<String>;
+
+    #[wasm_bindgen(js_name = URL)]
+    type Url;
+    #[wasm_bindgen(constructor, js_class = "URL", catch)]
+    fn new(url: &str, base: &str) -> Result<Url, JsValue>;
+    #[wasm_bindgen(method, getter)]
+    fn href(this: &Url) -> String;
+
+    #[wasm_bindgen(js_namespace = console)]
+    fn warn(message: &str);
+}
+
+/// What the loader uses of a chunk of the manifest.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Chunk {
+    pub name: String,
+    pub file: String,
+    pub size: u32,
+    pub integrity: Option<String>,
+    pub dependencies: Vec<String>,
+    pub on_load: Vec<u32>,
+}
+
+/// A split module folded into the main module, whose hooks run on its first
+/// load.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Folded {
+    pub name: String,
+    pub on_load: Vec<u32>,
+}
+
+/// What the loader uses of the manifest.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Manifest {
+    pub build_id: String,
+    pub chunks: Vec<Chunk>,
+    pub folded: Vec<Folded>,
+    pub aliases: BTreeMap<String, String>,
+}
+
+/// What the name of a split module refers to.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Module<'a> {
+    Chunk(&'a Chunk),
+    Folded(&'a Folded),
+}
+
+impl Manifest {
+    /// The chunk or folded module of the split module `name`, following its
+    /// alias if it was merged or co-located into another.
+    pub fn resolve(&self, name: &str) -> Option<Module<'_>> {
+        let name = self.aliases.get(name).map_or(name, String::as_str);
+        if let Some(chunk) = self.chunks.iter().find(|chunk| chunk.name == name) {
+            return Some(Module::Chunk(chunk));
+        }
+        self.folded
+            .iter()
+            .find(|folded| folded.name == name)
+            .map(Module::Folded)
+    }
+
+    /// The chunks to instantiate for the chunks `names`, dependencies first,
+    /// each once.
+    pub fn load_order<'a>(&'a self, names: &[&str]) -> Result<Vec<&'a Chunk>, LoadError> {
+        fn visit<'a>(
+            manifest: &'a Manifest,
+            name: &str,
+            visited: &mut BTreeSet<&'a str>,
+            order: &mut Vec<&'a Chunk>,
+        ) -> Result<(), LoadError> {
+            let chunk = manifest
+                .chunks
+                .iter()
+                .find(|chunk| chunk.name == name)
+                .ok_or(LoadError::UnknownChunk)?;
+            if !visited.insert(&chunk.name) {
+                return Ok(());
+            }
+            for dependency in chunk.dependencies.iter() {
+                visit(manifest, dependency, visited, order)?;
+            }
+            order.push(chunk);
+            Ok(())
+        }
+
+        let mut visited = BTreeSet::new();
+        let mut order = Vec::new();
+        for name in names {
+            visit(self, name, &mut visited, &mut order)?;
+        }
+        Ok(order)
+    }
+}
+
+/// URL of `chunk` relative to the base URL, with the build ID, so that caches
+/// never serve a chunk of another build.
+pub(crate) fn chunk_path(chunk: &Chunk, build_id: &str) -> String {
+    format!("./{}?build={build_id}", chunk.file)
+}
+
+type Shared<T> = async_once_cell::Lazy<T, Pin<Box<dyn Future<Output = T>>>>;
+
+type Compiled = Result<WebAssembly::Module, LoadError>;
+
+type Fetched = Result<Rc<Manifest>, LoadError>;
+
+#[derive(Default)]
+struct Registry {
+    /// Set by [`set_base_url`].
+    base_url: Option<String>,
+    manifest: Option<Pin<Rc<Shared<Fetched>>>>,
+    /// Downloads and compilations of chunks, by name, which start as soon as
+    /// a load needs the chunk.
+    compiling: BTreeMap<String, Pin<Rc<Shared<Compiled>>>>,
+    /// Instantiations of chunks, by name.
+    instantiating: BTreeMap<String, Pin<Rc<Shared<LoadResult>>>>,
+    /// Chunks instantiated, in order.
+    loaded: Vec<String>,
+    /// Folded modules whose hooks ran.
+    folded_loaded: BTreeSet<String>,
+}
+
+std::thread_local! {
+    static REGISTRY: RefCell<Registry> = RefCell::default();
+}
+
+fn with_registry<R>(f: impl FnOnce(&mut Registry) -> R) -> R {
+    REGISTRY.with(|registry| f(&mut registry.borrow_mut()))
+}
+
+pub(crate) fn set_base_url(url: &str) {
+    with_registry(|registry| registry.base_url = Some(url.to_string()));
+}
+
+/// The base URL that chunks and the manifest resolve against: that of
+/// [`set_base_url`], or `globalThis.WASM_SPLIT_BASE_URL`, each relative to
+/// the page, or the page itself.
+fn base_url() -> String {
+    let global = js_sys::global();
+    let page = Reflect::get(&global, &"location".into())
+        .and_then(|location| Reflect::get(&location, &"href".into()))
+        .ok()
+        .and_then(|href| href.as_string())
+        .unwrap_or_default();
+    let base = with_registry(|registry| registry.base_url.clone()).or_else(|| {
+        Reflect::get(&global, &"WASM_SPLIT_BASE_URL".into())
+            .ok()
+            .and_then(|base| base.as_string())
+    });
+    match base {
+        Some(base) => Url::new(&base, &page).map_or(base, |url| url.href()),
+        None => page,
+    }
+}
+
+fn resolve_url(path: &str) -> Result<String, LoadError> {
+    Url::new(path, &base_url())
+        .map(|url| url.href())
+        .map_err(|_| LoadError::UnknownChunk)
+}
+
+/// Fetches `url`, failing on a response other than a success.
+async fn fetch(url: &str, integrity: Option<&str>) -> Result<Response, LoadError> {
+    let init = Object::new();
+    if let Some(integrity) = integrity {
+        Reflect::set(&init, &"integrity".into(), &integrity.into()).unwrap();
+    }
+    let response: Response = match JsFuture::from(fetch_with_init(url, &init)).await {
+        Ok(response) => response.unchecked_into(),
+        // Browsers fail requests whose body does not match the integrity
+        // metadata like those that failed on the network.
+        Err(_) if integrity.is_some() => return Err(LoadError::IntegrityMismatch),
+        Err(_) => return Err(LoadError::Network),
+    };
+    if !response.ok() {
+        return Err(LoadError::Http(response.status()));
+    }
+    Ok(response)
+}
+
+async fn fetch_manifest() -> Fetched {
+    let response = fetch(&resolve_url(MANIFEST_FILE)?, None).await?;
+    let json = JsFuture::from(response.json())
+        .await
+        .map_err(|_| LoadError::CompileError)?;
+    parse_manifest(&json).map(Rc::new).ok_or_else(|| {
+        warn("wasm-split: the manifest is not one of a split build");
+        LoadError::CompileError
+    })
+}
+
+/// The manifest, fetched on first use, and again after that failed.
+async fn manifest() -> Fetched {
+    let shared = with_registry(|registry| {
+        registry
+            .manifest
+            .get_or_insert_with(|| Rc::pin(Shared::new(Box::pin(fetch_manifest()))))
+            .clone()
+    });
+    let result = (*shared.as_ref().await).clone();
+    if result.is_err() {
+        forget(|registry| &mut registry.manifest, &shared);
+    }
+    result
+}
+
+/// Removes the entry that `get` returns if it is still `shared`, for a failed
+/// load that another has not replaced yet.
+fn forget<T>(
+    get: impl FnOnce(&mut Registry) -> &mut Option<Pin<Rc<Shared<T>>>>,
+    shared: &Pin<Rc<Shared<T>>>,
+) {
+    with_registry(|registry| {
+        let entry = get(registry);
+        if entry
+            .as_ref()
+            .is_some_and(|current| core::ptr::eq::<Shared<T>>(&**current, &**shared))
+        {
+            *entry = None;
+        }
+    });
+}
+
+fn get_string(value: &JsValue, key: &str) -> Option<String> {
+    Reflect::get(value, &key.into()).ok()?.as_string()
+}
+
+fn get_array(value: &JsValue, key: &str) -> Vec<JsValue> {
+    Reflect::get(value, &key.into())
+        .ok()
+        .and_then(|array| array.dyn_into::<Array>().ok())
+        .map_or_else(Vec::new, |array| array.iter().collect())
+}
+
+fn get_slots(value: &JsValue) -> Vec<u32> {
+    get_array(value, "on_load")
+        .iter()
+        .filter_map(|slot| Some(slot.as_f64()? as u32))
+        .collect()
+}
+
+fn parse_manifest(json: &JsValue) -> Option<Manifest> {
+    let chunks = get_array(json, "chunks")
+        .iter()
+        .map(|chunk| {
+            Some(Chunk {
+                name: get_string(chunk, "name")?,
+                file: get_string(chunk, "file")?,
+                size: Reflect::get(chunk, &"size".into()).ok()?.as_f64()? as u32,
+                integrity: get_string(chunk, "integrity"),
+                dependencies: get_array(chunk, "dependencies")
+                    .iter()
+                    .filter_map(JsValue::as_string)
+                    .collect(),
+                on_load: get_slots(chunk),
+            })
+        })
+        .collect::<Option<_>>()?;
+    let folded = get_array(json, "folded")
+        .iter()
+        .map(|folded| {
+            Some(Folded {
+                name: get_string(folded, "name")?,
+                on_load: get_slots(folded),
+            })
+        })
+        .collect::<Option<_>>()?;
+    let aliases = Reflect::get(json, &"aliases".into())
+        .ok()
+        .and_then(|aliases| aliases.dyn_into::<Object>().ok())
+        .map_or_else(BTreeMap::new, |aliases| {
+            Object::entries(&aliases)
+                .iter()
+                .filter_map(|entry| {
+                    let entry = entry.unchecked_into::<Array>();
+                    Some((entry.get(0).as_string()?, entry.get(1).as_string()?))
+                })
+                .collect()
+        });
+    Some(Manifest {
+        build_id: get_string(json, "build_id")?,
+        chunks,
+        folded,
+        aliases,
+    })
+}
+
+/// Downloads and compiles `chunk`, streaming if it is served as WebAssembly.
+async fn compile(url: String, integrity: Option<String>) -> Compiled {
+    let response = fetch(&url, integrity.as_deref()).await?;
+    let streaming = response
+        .headers()
+        .get("content-type")
+        .is_some_and(|content_type| content_type.starts_with("application/wasm"));
+    let compiled = if streaming {
+        WebAssembly::compile_streaming(&Promise::resolve(&JsValue::from(response)))
+    } else {
+        let bytes = JsFuture::from(response.array_buffer())
+            .await
+            .map_err(|_| LoadError::Network)?;
+        WebAssembly::compile(&bytes)
+    };
+    JsFuture::from(compiled)
+        .await
+        .map(JsCast::unchecked_into)
+        .map_err(|_| LoadError::CompileError)
+}
+
+/// Starts downloading `chunk` unless it is loaded or downloading already.
+fn start_compile(chunk: &Chunk, build_id: &str) -> Result<(), LoadError> {
+    let started = with_registry(|registry| {
+        registry.loaded.contains(&chunk.name) || registry.compiling.contains_key(&chunk.name)
+    });
+    if started {
+        return Ok(());
+    }
+    let url = resolve_url(&chunk_path(chunk, build_id))?;
+    let shared = Rc::pin(Shared::new(
+        Box::pin(compile(url, chunk.integrity.clone())) as Pin<Box<dyn Future<Output = _>>>
+    ));
+    with_registry(|registry| {
+        registry
+            .compiling
+            .insert(chunk.name.clone(), shared.clone())
+    });
+    // Promises of the browser run whether or not they are awaited, while
+    // futures only run once polled.
+    wasm_bindgen_futures::spawn_local(async move {
+        let _ = shared.as_ref().await;
+    });
+    Ok(())
+}
+
+/// Runs the `on_load` hooks in the table slots `slots`.
+fn run_hooks(slots: &[u32]) -> Result<(), LoadError> {
+    let table = wasm_bindgen::function_table().unchecked_into::<WebAssembly::Table>();
+    for &slot in slots {
+        table
+            .get(slot)
+            .and_then(|hook: Function| hook.call0(&JsValue::UNDEFINED))
+            .map_err(|_| LoadError::InstantiationError)?;
+    }
+    Ok(())
+}
+
+/// Instantiates `chunk` once it is compiled, after the caller instantiated
+/// the chunks it depends on.
+async fn instantiate(chunk: Chunk) -> LoadResult {
+    let compiling = with_registry(|registry| registry.compiling.get(&chunk.name).cloned())
+        .expect("compilation started before instantiation");
+    let compiled = (*compiling.as_ref().await).clone()?;
+    // Chunks import the table, memory and globals of the main module under
+    // their export names.
+    let imports = Object::new();
+    let env = Object::new();
+    Reflect::set(&env, &"memory".into(), &wasm_bindgen::memory()).unwrap();
+    Reflect::set(&imports, &"env".into(), &env).unwrap();
+    Reflect::set(&imports, &"__wasm_split".into(), &wasm_bindgen::exports()).unwrap();
+    JsFuture::from(WebAssembly::instantiate_module(&compiled, &imports))
+        .await
+        .map_err(|error| {
+            if error.is_instance_of::<WebAssembly::LinkError>() {
+                LoadError::LinkError
+            } else {
+                LoadError::InstantiationError
+            }
+        })?;
+    run_hooks(&chunk.on_load)?;
+    with_registry(|registry| {
+        registry.compiling.remove(&chunk.name);
+        registry.loaded.push(chunk.name.clone());
+    });
+    Ok(chunk.size)
+}
+
+/// Instantiates `chunk` unless it is, sharing the instantiation with other
+/// loads of the chunk.
+async fn ensure_instantiated(chunk: &Chunk) -> LoadResult {
+    let shared = with_registry(|registry| {
+        if registry.loaded.contains(&chunk.name) {
+            return None;
+        }
+        Some(
+            registry
+                .instantiating
+                .entry(chunk.name.clone())
+                .or_insert_with(|| Rc::pin(Shared::new(Box::pin(instantiate(chunk.clone())))))
+                .clone(),
+        )
+    });
+    let Some(shared) = shared else {
+        return Ok(chunk.size);
+    };
+    let result = *shared.as_ref().await;
+    with_registry(|registry| {
+        let current = registry.instantiating.get(&chunk.name);
+        if current.is_some_and(|current| core::ptr::eq::<Shared<LoadResult>>(&**current, &*shared))
+        {
+            registry.instantiating.remove(&chunk.name);
+            if result.is_err() {
+                registry.compiling.remove(&chunk.name);
+            }
+        }
+    });
+    result
+}
+
+/// Loads the chunks of the split modules `names` with the chunks they depend
+/// on, and resolves to the size of the last of them.
+pub(crate) async fn load(names: Vec<String>) -> LoadResult {
+    let manifest = manifest().await?;
+    let mut chunks = Vec::new();
+    for name in names.iter() {
+        match manifest.resolve(name).ok_or(LoadError::UnknownChunk)? {
+            Module::Chunk(chunk) => chunks.push(chunk.name.as_str()),
+            Module::Folded(folded) => {
+                let first =
+                    with_registry(|registry| registry.folded_loaded.insert(folded.name.clone()));
+                if first {
+                    run_hooks(&folded.on_load)?;
+                }
+            }
+        }
+    }
+    let order = manifest.load_order(&chunks)?;
+    for chunk in order.iter() {
+        start_compile(chunk, &manifest.build_id)?;
+    }
+    let mut size = 0;
+    for chunk in order {
+        size = ensure_instantiated(chunk).await?;
+    }
+    Ok(size)
+}
+
+/// Starts loading the chunk of `name` in the background, logging a failure.
+pub(crate) fn preload(name: &str) {
+    let name = name.to_string();
+    wasm_bindgen_futures::spawn_local(async move {
+        match load(vec![name.clone()]).await {
+            Ok(_) | Err(LoadError::UnknownChunk) => {}
+            Err(error) => warn(&format!("wasm-split: failed to preload {name}: {error}")),
+        }
+    });
+}
+
+/// Whether the split module `name` is loaded. Before the manifest was
+/// fetched, nothing is, not even folded modules.
+pub(crate) fn is_loaded(name: &str) -> bool {
+    with_registry(|registry| {
+        let Some(manifest) = registry
+            .manifest
+            .as_ref()
+            .and_then(|manifest| manifest.try_get().cloned())
+            .and_then(Result::ok)
+        else {
+            return false;
+        };
+        match manifest.resolve(name) {
+            Some(Module::Chunk(chunk)) => registry.loaded.contains(&chunk.name),
+            Some(Module::Folded(_)) => true,
+            None => false,
+        }
+    })
+}
+
+pub(crate) fn loaded_chunks() -> Vec<String> {
+    with_registry(|registry| registry.loaded.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{collections::BTreeMap, string::ToString, vec, vec::Vec};
+
+    use super::{chunk_path, Chunk, Folded, Manifest, Module};
+    use crate::LoadError;
+
+    fn chunk(name: &str, dependencies: &[&str]) -> Chunk {
+        Chunk {
+            name: name.to_string(),
+            file: format!("{name}.wasm"),
+            dependencies: dependencies.iter().map(|name| name.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn manifest() -> Manifest {
+        Manifest {
+            build_id: "b1".to_string(),
+            chunks: vec![
+                chunk("shared", &[]),
+                chunk("view_b", &["shared"]),
+                chunk("view_c", &["shared", "view_b"]),
+            ],
+            folded: vec![Folded {
+                name: "tiny".to_string(),
+                on_load: vec![3],
+            }],
+            aliases: BTreeMap::from([("comments".to_string(), "view_b".to_string())]),
+        }
+    }
+
+    fn names(chunks: Vec<&Chunk>) -> Vec<&str> {
+        chunks.iter().map(|chunk| chunk.name.as_str()).collect()
+    }
+
+    #[test]
+    fn loads_dependencies_first_and_each_chunk_once() {
+        let manifest = manifest();
+        assert_eq!(
+            names(manifest.load_order(&["view_c"]).unwrap()),
+            ["shared", "view_b", "view_c"]
+        );
+        assert_eq!(
+            names(
+                manifest
+                    .load_order(&["view_b", "view_c", "shared"])
+                    .unwrap()
+            ),
+            ["shared", "view_b", "view_c"]
+        );
+        assert_eq!(
+            manifest.load_order(&["view_b", "missing"]),
+            Err(LoadError::UnknownChunk)
+        );
+    }
+
+    #[test]
+    fn resolves_aliases_and_folded_modules() {
+        let manifest = manifest();
+        assert_eq!(
+            manifest.resolve("comments"),
+            Some(Module::Chunk(&manifest.chunks[1]))
+        );
+        assert_eq!(
+            manifest.resolve("tiny"),
+            Some(Module::Folded(&manifest.folded[0]))
+        );
+        assert_eq!(manifest.resolve("missing"), None);
+        assert_eq!(
+            chunk_path(&manifest.chunks[1], &manifest.build_id),
+            "./view_b.wasm?build=b1"
+        );
+    }
+}
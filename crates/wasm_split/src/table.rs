@@ -0,0 +1,41 @@
+#[link(wasm_import_module = "./__wasm_split.js")]
+extern "C" {
+    fn __wasm_split_allocate_table_slot() -> u32;
+    fn __wasm_split_free_table_slot(slot: u32);
+}
+
+/// A slot of the indirect function table, for a callback registered at
+/// runtime, e.g. by JS code that adds wasm functions to the table once a chunk
+/// using them has loaded.
+///
+/// The table of a split build has a fixed size, since all chunks share it, so
+/// such callbacks cannot grow it. Instead, split modules reserve slots up
+/// front with `#[wasm_split(module_name, table_slots = N)]`, and `wasm-split`
+/// appends that many empty slots to the table. The slot is cleared and
+/// returned to the pool when dropped.
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct TableSlot {
+    index: u32,
+}
+
+impl TableSlot {
+    /// Takes a free reserved slot, or returns `None` if all of them are in
+    /// use.
+    pub fn allocate() -> Option<Self> {
+        match unsafe { __wasm_split_allocate_table_slot() } {
+            0 => None,
+            index => Some(Self { index }),
+        }
+    }
+
+    /// Index of the slot in `__indirect_function_table`.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+}
+
+impl Drop for TableSlot {
+    fn drop(&mut self) {
+        unsafe { __wasm_split_free_table_slot(self.index) }
+    }
+}
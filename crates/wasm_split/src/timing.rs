@@ -0,0 +1,78 @@
+use core::future::Future;
+
+#[link(wasm_import_module = "./__wasm_split.js")]
+extern "C" {
+    fn __wasm_split_navigation_start(route: *const u8, len: usize) -> u32;
+    fn __wasm_split_navigation_data_start(navigation: u32) -> u32;
+    fn __wasm_split_navigation_data_end(navigation: u32, interval: u32);
+    fn __wasm_split_navigation_end(navigation: u32, rendered: u32);
+}
+
+/// Measures how much of the delay of a navigation was spent loading chunks,
+/// and how much loading data, to tune how finely to split an app.
+///
+/// Start one when the router begins a transition, wrap the data loading of
+/// the new route in [`track_data`](Self::track_data), and call
+/// [`finish`](Self::finish) once the route has rendered:
+///
+/// ```ignore
+/// let timing = NavigationTiming::start("/admin/users");
+/// let users = timing.track_data(fetch_users()).await;
+/// let view = users_view(users).await; // a #[wasm_split] function
+/// // ... render the view ...
+/// timing.finish();
+/// ```
+///
+/// The loader then reports the navigation as a `wasm-split:navigation`
+/// performance measure, and to listeners registered with
+/// `onNavigationTiming` of the loader script. Its detail splits the duration
+/// into the time during which only chunks, only data, or both were loading,
+/// and the remaining time, such as rendering. Chunk loads count whether the
+/// navigation caused them or merely waited for them, e.g. for a chunk that
+/// was being preloaded. A navigation dropped without being finished, e.g.
+/// because another one superseded it, is not reported.
+#[derive(Debug)]
+pub struct NavigationTiming {
+    id: u32,
+}
+
+impl NavigationTiming {
+    pub fn start(route: &str) -> Self {
+        Self {
+            id: unsafe { __wasm_split_navigation_start(route.as_ptr(), route.len()) },
+        }
+    }
+
+    /// Awaits `data`, counting the time until it completes or is dropped as
+    /// data loading.
+    pub async fn track_data<F: Future>(&self, data: F) -> F::Output {
+        let _interval = DataInterval {
+            navigation: self.id,
+            interval: unsafe { __wasm_split_navigation_data_start(self.id) },
+        };
+        data.await
+    }
+
+    /// Reports the navigation, which has rendered.
+    pub fn finish(self) {
+        unsafe { __wasm_split_navigation_end(self.id, 1) };
+        core::mem::forget(self);
+    }
+}
+
+impl Drop for NavigationTiming {
+    fn drop(&mut self) {
+        unsafe { __wasm_split_navigation_end(self.id, 0) }
+    }
+}
+
+struct DataInterval {
+    navigation: u32,
+    interval: u32,
+}
+
+impl Drop for DataInterval {
+    fn drop(&mut self) {
+        unsafe { __wasm_split_navigation_data_end(self.navigation, self.interval) }
+    }
+}
@@ -0,0 +1,119 @@
+//! `tracing` spans and events for lazy loading, with the `tracing` feature,
+//! as documented in the Tracing section of the crate docs. Without the
+//! feature, these are plain awaits, and the events are not emitted. Calls are also recorded for the loader's profile
+//! with the `profile` feature, as documented in the Profiles section.
+
+use core::future::Future;
+
+use crate::loader::LoadResult;
+
+#[cfg(all(feature = "tracing", not(feature = "rust-loader")))]
+#[link(wasm_import_module = "./__wasm_split.js")]
+extern "C" {
+    /// `performance.now()`, as `std::time::Instant` is not available on
+    /// `wasm32-unknown-unknown`.
+    fn __wasm_split_now() -> f64;
+}
+
+#[cfg(all(feature = "tracing", not(feature = "rust-loader")))]
+fn now() -> f64 {
+    unsafe { __wasm_split_now() }
+}
+
+/// Without the loader script, as its clock is not imported.
+#[cfg(all(feature = "tracing", feature = "rust-loader"))]
+fn now() -> f64 {
+    js_sys::Date::now()
+}
+
+#[cfg(feature = "profile")]
+#[link(wasm_import_module = "./__wasm_split.js")]
+extern "C" {
+    fn __wasm_split_profile_call(
+        module: *const u8,
+        module_len: usize,
+        function: *const u8,
+        function_len: usize,
+    );
+}
+
+#[cfg(feature = "profile")]
+fn record_call(module: &str, function: &str) {
+    unsafe {
+        __wasm_split_profile_call(
+            module.as_ptr(),
+            module.len(),
+            function.as_ptr(),
+            function.len(),
+        )
+    }
+}
+
+#[cfg(not(feature = "profile"))]
+fn record_call(_module: &str, _function: &str) {}
+
+/// The span is created right away, so that `chunk` need not outlive the load.
+#[cfg(feature = "tracing")]
+pub(crate) fn load(
+    chunk: &str,
+    load: impl Future<Output = LoadResult>,
+) -> impl Future<Output = LoadResult> {
+    use tracing::{field::Empty, Instrument};
+
+    let span = tracing::info_span!(
+        "wasm_split::load",
+        chunk,
+        bytes = Empty,
+        duration_ms = Empty,
+        error = Empty,
+    );
+    async move {
+        let start = now();
+        let result = load.instrument(span.clone()).await;
+        span.record("duration_ms", now() - start);
+        match result {
+            Ok(bytes) => {
+                span.record("bytes", bytes);
+                tracing::info!(name: "wasm_split::loaded", parent: &span, bytes);
+            }
+            Err(error) => {
+                span.record("error", tracing::field::display(error));
+                tracing::warn!(name: "wasm_split::load_failed", parent: &span, %error);
+            }
+        };
+        result
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn load(
+    _chunk: &str,
+    load: impl Future<Output = LoadResult>,
+) -> impl Future<Output = LoadResult> {
+    load
+}
+
+/// Event for a change of the state of `chunks` other than a load, such as
+/// `"preload"` or `"unload"`, and whether the loader did anything.
+#[cfg(feature = "tracing")]
+pub(crate) fn lifecycle(action: &'static str, chunks: &str, done: bool) {
+    tracing::info!(name: "wasm_split::lifecycle", action, chunks, done);
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn lifecycle(_action: &'static str, _chunks: &str, _done: bool) {}
+
+#[cfg(feature = "tracing")]
+pub async fn call<F: Future>(module: &'static str, function: &'static str, call: F) -> F::Output {
+    use tracing::Instrument;
+
+    record_call(module, function);
+    call.instrument(tracing::debug_span!("wasm_split::call", module, function))
+        .await
+}
+
+#[cfg(not(feature = "tracing"))]
+pub async fn call<F: Future>(module: &'static str, function: &'static str, call: F) -> F::Output {
+    record_call(module, function);
+    call.await
+}
@@ -0,0 +1,291 @@
+//! Calls of `#[wasm_split(module, worker)]` functions on a worker.
+//!
+//! The loader starts a module worker from its own script, which instantiates
+//! another copy of the main module with every JS import stubbed out, loads
+//! the function's chunk into it, and calls the function there. Arguments and
+//! results cross over as bytes encoded with [`Transfer`], since the two
+//! instances share no memory.
+
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::ffi::c_void;
+
+use crate::loader::{LoadCallbackFn, SplitLoader, SplitLoaderFuture};
+use crate::LoadError;
+
+#[link(wasm_import_module = "./__wasm_split.js")]
+extern "C" {
+    fn __wasm_split_run_on_worker(
+        chunk_ptr: *const u8,
+        chunk_len: usize,
+        entry_ptr: *const u8,
+        entry_len: usize,
+        input_ptr: *const u8,
+        input_len: usize,
+        callback: LoadCallbackFn,
+        data: *const c_void,
+    );
+    fn __wasm_split_worker_output_len(id: u32) -> usize;
+    fn __wasm_split_take_worker_output(id: u32, ptr: *mut u8);
+}
+
+/// Values passed to and returned from functions split with `worker`, which
+/// are copied between the main thread and the worker as bytes.
+///
+/// Borrowed arguments such as `&str` are decoded from the bytes that the
+/// worker received, so `'a` is the lifetime of those.
+///
+/// Implementations write with `encode` exactly what `decode` reads, e.g.
+/// field by field for a struct:
+///
+/// ```ignore
+/// impl Transfer<'_> for Comment {
+///     fn encode(&self, out: &mut Vec<u8>) {
+///         self.id.encode(out);
+///         self.body.encode(out);
+///     }
+///
+///     fn decode(input: &mut &[u8]) -> Self {
+///         Self {
+///             id: Transfer::decode(input),
+///             body: Transfer::decode(input),
+///         }
+///     }
+/// }
+/// ```
+pub trait Transfer<'a>: Sized {
+    fn encode(&self, out: &mut Vec<u8>);
+
+    /// Reads a value from the front of `input`, and advances it past it.
+    /// Panics if `input` does not start with a value encoded by `encode`.
+    fn decode(input: &mut &'a [u8]) -> Self;
+}
+
+fn take<'a>(input: &mut &'a [u8], len: usize) -> &'a [u8] {
+    let (bytes, rest) = input.split_at(len);
+    *input = rest;
+    bytes
+}
+
+macro_rules! impl_transfer_for_number {
+    ($($ty:ty),*) => {
+        $(
+            impl Transfer<'_> for $ty {
+                fn encode(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_le_bytes());
+                }
+
+                fn decode(input: &mut &[u8]) -> Self {
+                    Self::from_le_bytes(take(input, size_of::<Self>()).try_into().unwrap())
+                }
+            }
+        )*
+    };
+}
+
+impl_transfer_for_number!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+
+impl Transfer<'_> for bool {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8);
+    }
+
+    fn decode(input: &mut &[u8]) -> Self {
+        u8::decode(input) != 0
+    }
+}
+
+impl Transfer<'_> for () {
+    fn encode(&self, _: &mut Vec<u8>) {}
+
+    fn decode(_: &mut &[u8]) -> Self {}
+}
+
+impl<'a> Transfer<'a> for &'a [u8] {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.len().encode(out);
+        out.extend_from_slice(self);
+    }
+
+    fn decode(input: &mut &'a [u8]) -> Self {
+        let len = usize::decode(input);
+        take(input, len)
+    }
+}
+
+impl<'a> Transfer<'a> for &'a str {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.as_bytes().encode(out);
+    }
+
+    fn decode(input: &mut &'a [u8]) -> Self {
+        core::str::from_utf8(<&[u8]>::decode(input)).unwrap()
+    }
+}
+
+impl Transfer<'_> for String {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.as_str().encode(out);
+    }
+
+    fn decode(input: &mut &[u8]) -> Self {
+        <&str>::decode(input).into()
+    }
+}
+
+impl<'a, T: Transfer<'a>> Transfer<'a> for Vec<T> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.len().encode(out);
+        for item in self {
+            item.encode(out);
+        }
+    }
+
+    fn decode(input: &mut &'a [u8]) -> Self {
+        let len = usize::decode(input);
+        (0..len).map(|_| T::decode(input)).collect()
+    }
+}
+
+impl<'a, T: Transfer<'a>> Transfer<'a> for Option<T> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.is_some().encode(out);
+        if let Some(value) = self {
+            value.encode(out);
+        }
+    }
+
+    fn decode(input: &mut &'a [u8]) -> Self {
+        bool::decode(input).then(|| T::decode(input))
+    }
+}
+
+impl<'a, T: Transfer<'a>, E: Transfer<'a>> Transfer<'a> for Result<T, E> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.is_ok().encode(out);
+        match self {
+            Ok(value) => value.encode(out),
+            Err(error) => error.encode(out),
+        }
+    }
+
+    fn decode(input: &mut &'a [u8]) -> Self {
+        if bool::decode(input) {
+            Ok(T::decode(input))
+        } else {
+            Err(E::decode(input))
+        }
+    }
+}
+
+macro_rules! impl_transfer_for_tuple {
+    ($($name:ident),*) => {
+        impl<'a, $($name: Transfer<'a>),*> Transfer<'a> for ($($name,)*) {
+            #[allow(non_snake_case)]
+            fn encode(&self, out: &mut Vec<u8>) {
+                let ($($name,)*) = self;
+                $($name.encode(out);)*
+            }
+
+            fn decode(input: &mut &'a [u8]) -> Self {
+                ($($name::decode(input),)*)
+            }
+        }
+    };
+}
+
+impl_transfer_for_tuple!(A);
+impl_transfer_for_tuple!(A, B);
+impl_transfer_for_tuple!(A, B, C);
+impl_transfer_for_tuple!(A, B, C, D);
+
+/// Calls `entry`, the worker entry point of a function of `chunk`, on the
+/// loader's worker with the encoded arguments, and returns its encoded
+/// result. Fails with [`LoadError::UnsupportedFeature`] where workers are not
+/// available, after which the caller runs the function itself.
+pub async fn run_on_worker(
+    chunk: &'static str,
+    entry: &'static str,
+    input: Vec<u8>,
+) -> Result<Vec<u8>, LoadError> {
+    // The loader copies the input before returning.
+    let id = SplitLoaderFuture::new(SplitLoader::new(Box::new(move |callback, data| unsafe {
+        __wasm_split_run_on_worker(
+            chunk.as_ptr(),
+            chunk.len(),
+            entry.as_ptr(),
+            entry.len(),
+            input.as_ptr(),
+            input.len(),
+            callback,
+            data,
+        )
+    })))
+    .await?;
+    let mut output = Vec::new();
+    unsafe {
+        output.resize(__wasm_split_worker_output_len(id), 0);
+        __wasm_split_take_worker_output(id, output.as_mut_ptr());
+    }
+    Ok(output)
+}
+
+/// Input of the next call of a worker entry point, and then its output.
+#[cfg(target_arch = "wasm32")]
+struct WorkerBuffer(core::cell::UnsafeCell<Vec<u8>>);
+
+// A worker's instance of the main module only ever runs on that worker.
+#[cfg(target_arch = "wasm32")]
+unsafe impl Sync for WorkerBuffer {}
+
+#[cfg(target_arch = "wasm32")]
+static WORKER_BUFFER: WorkerBuffer = WorkerBuffer(core::cell::UnsafeCell::new(Vec::new()));
+
+/// Resizes the worker buffer to `len` bytes and returns its address, which
+/// the loader copies the input into before calling an entry point, and the
+/// output out of afterwards.
+#[cfg(target_arch = "wasm32")]
+#[no_mangle]
+extern "C" fn __wasm_split_worker_buffer(len: usize) -> *mut u8 {
+    let buffer = unsafe { &mut *WORKER_BUFFER.0.get() };
+    buffer.resize(len, 0);
+    buffer.as_mut_ptr()
+}
+
+/// Body of the worker entry point of a function, which decodes the
+/// arguments from the worker buffer, calls the function, and replaces the
+/// buffer with the encoded result. Returns the length of the result.
+#[cfg(target_arch = "wasm32")]
+pub fn run_worker_entry(call: impl FnOnce(&mut &[u8], &mut Vec<u8>)) -> usize {
+    let input = core::mem::take(unsafe { &mut *WORKER_BUFFER.0.get() });
+    let mut output = Vec::new();
+    call(&mut input.as_slice(), &mut output);
+    let len = output.len();
+    unsafe { *WORKER_BUFFER.0.get() = output };
+    len
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec, vec::Vec};
+
+    use super::Transfer;
+
+    #[test]
+    fn decodes_what_it_encodes() {
+        let value = (
+            "comments",
+            vec![(1u32, String::from("first")), (2, String::new())],
+            Some(-1.5f64),
+            Ok::<bool, ()>(true),
+        );
+        let mut bytes = Vec::new();
+        value.encode(&mut bytes);
+        bytes.push(0xff);
+        let mut input = bytes.as_slice();
+        assert_eq!(
+            <(&str, Vec<(u32, String)>, Option<f64>, Result<bool, ()>)>::decode(&mut input),
+            value
+        );
+        assert_eq!(input, [0xff]);
+    }
+}
@@ -0,0 +1,136 @@
+// Runs the benchmarks of `wasm_split_bench` under Node, and writes the
+// results as JSON. Invoked by `bench.py`, with the directory holding the
+// output of `wasm-split` and `wasm-bindgen`:
+//
+//     node bench.mjs <pkg-dir> [<results.json>]
+//
+// Like criterion, every benchmark is warmed up, then timed over a number of
+// samples, and summarized by the mean, median, standard deviation and range
+// of the samples. Call benchmarks time batches of calls and report the time
+// per call.
+
+import { readFileSync, writeFileSync } from "node:fs";
+import path from "node:path";
+import { fileURLToPath, pathToFileURL } from "node:url";
+
+const [pkgDir, outFile] = process.argv.slice(2);
+if (pkgDir === undefined) {
+  console.error("Usage: node bench.mjs <pkg-dir> [<results.json>]");
+  process.exit(2);
+}
+
+const SAMPLES = Number(process.env.BENCH_SAMPLES ?? 50);
+const CALLS_PER_SAMPLE = 100_000;
+const LOAD_SAMPLES = Number(process.env.BENCH_LOAD_SAMPLES ?? 20);
+
+// Serves the chunks from disk, as the loader fetches them.
+globalThis.fetch = async (url) => {
+  const fileUrl = new URL(url);
+  fileUrl.search = "";
+  const type = fileUrl.pathname.endsWith(".wasm")
+    ? "application/wasm"
+    : "application/json";
+  return new Response(readFileSync(fileURLToPath(fileUrl)), {
+    headers: { "content-type": type },
+  });
+};
+
+const pkgUrl = (file) => pathToFileURL(path.resolve(pkgDir, file));
+const bench = await import(pkgUrl("main.js"));
+bench.initSync({ module: readFileSync(new URL(pkgUrl("main_bg.wasm"))) });
+const loader = await import(pkgUrl("__wasm_split.js"));
+const manifest = JSON.parse(
+  readFileSync(new URL(pkgUrl("wasm-split-manifest.json"))),
+);
+
+function summarize(samples) {
+  const sorted = [...samples].sort((a, b) => a - b);
+  const mean = samples.reduce((sum, x) => sum + x, 0) / samples.length;
+  const variance =
+    samples.reduce((sum, x) => sum + (x - mean) ** 2, 0) /
+    Math.max(samples.length - 1, 1);
+  return {
+    mean,
+    median: sorted[Math.floor(sorted.length / 2)],
+    stddev: Math.sqrt(variance),
+    min: sorted[0],
+    max: sorted[sorted.length - 1],
+    samples: samples.length,
+  };
+}
+
+// Times `run(CALLS_PER_SAMPLE)`, in nanoseconds per call.
+async function benchCalls(run) {
+  for (let i = 0; i < 5; i++) await run(CALLS_PER_SAMPLE);
+  const samples = [];
+  for (let i = 0; i < SAMPLES; i++) {
+    const start = performance.now();
+    await run(CALLS_PER_SAMPLE);
+    samples.push(((performance.now() - start) * 1e6) / CALLS_PER_SAMPLE);
+  }
+  return summarize(samples);
+}
+
+// Times loading a chunk, in milliseconds, from fetching it to having
+// instantiated it. The loader is reset first, so that nothing is cached.
+async function benchLoad(name) {
+  const samples = [];
+  for (let i = 0; i < LOAD_SAMPLES + 1; i++) {
+    loader.reset();
+    const start = performance.now();
+    await bench.load_chunk(name);
+    // The first load also compiles the JS and wasm code of the loader path.
+    if (i > 0) samples.push(performance.now() - start);
+  }
+  return summarize(samples);
+}
+
+// Loads every chunk once, so that the call benchmarks only measure calls.
+await bench.call_sized_chunks(1n);
+await bench.cross_chunk_calls(1);
+await bench.dispatch_calls(1);
+await bench.hot_comment_calls(1);
+
+const calls = {
+  direct: await benchCalls((n) => bench.direct_calls(n)),
+  cross_chunk: await benchCalls((n) => bench.cross_chunk_calls(n)),
+  async: await benchCalls((n) => bench.async_calls(n)),
+  dispatch: await benchCalls((n) => bench.dispatch_calls(n)),
+  direct_comments: await benchCalls((n) => bench.direct_comment_calls(n)),
+  hot_comments: await benchCalls((n) => bench.hot_comment_calls(n)),
+};
+const loads = {};
+for (const name of ["small", "medium", "large"]) {
+  const chunk = manifest.chunks.find((chunk) => chunk.name === name);
+  loads[name] = { size: chunk.size, ms: await benchLoad(name) };
+}
+
+const results = {
+  unit: { calls: "ns per call", loads: "ms per load" },
+  calls,
+  overhead_ns: {
+    cross_chunk_call: calls.cross_chunk.mean - calls.direct.mean,
+    split_point_dispatch: calls.dispatch.mean - calls.async.mean,
+    hot_split_call: calls.hot_comments.mean - calls.direct_comments.mean,
+  },
+  loads,
+};
+
+const json = JSON.stringify(results, null, 2) + "\n";
+if (outFile === undefined) {
+  process.stdout.write(json);
+} else {
+  writeFileSync(outFile, json);
+}
+for (const [name, stats] of Object.entries(calls)) {
+  console.error(
+    `${name.padEnd(15)} ${stats.mean.toFixed(2).padStart(9)} ns/call ` +
+      `(± ${stats.stddev.toFixed(2)})`,
+  );
+}
+for (const [name, { size, ms }] of Object.entries(loads)) {
+  console.error(
+    `load ${name.padEnd(10)} ${String(size).padStart(7)} bytes ` +
+      `${ms.mean.toFixed(3).padStart(9)} ms (± ${ms.stddev.toFixed(3)})`,
+  );
+}
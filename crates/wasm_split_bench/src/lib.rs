@@ -0,0 +1,237 @@
+//! Benchmarks of the runtime overhead of splitting, run by `bench.py` at the
+//! root of the repository, which builds this crate, splits it and times the
+//! exported functions under Node.
+//!
+//! Each `*_calls` function makes `n` calls of one kind, so that the runner
+//! can derive the cost of a single call from how long a batch takes:
+//!
+//! - [`direct_calls`] and [`cross_chunk_calls`] call the same function of
+//!   the main module, from the main module and from a chunk respectively.
+//!   The difference is the cost of the stub and `call_indirect` that
+//!   `wasm-split` puts in place of each call between modules.
+//! - [`async_calls`] and [`dispatch_calls`] await a trivial async function
+//!   and a `#[wasm_split]` function of an already loaded chunk. The
+//!   difference is the cost of the loader check of every split point call.
+//! - [`direct_comment_calls`] and [`hot_comment_calls`] parse a few comments,
+//!   as the example's `deserialize_comments` does, with the same code in the
+//!   main module and in a `#[wasm_split]` function of a loaded chunk. The
+//!   difference is what a hot split function costs on top of its work: the
+//!   check that its chunk is loaded, and a single `call_indirect` of its
+//!   table slot, which `wasm-split` makes in place of calling a stub.
+//!
+//! [`load_chunk`] loads one of the `small`, `medium` and `large` chunks,
+//! whose code size grows by a factor of 16 from one to the next, to measure
+//! how instantiation time scales with chunk size. The runner resets the
+//! loader before each load, so that every load compiles and instantiates the
+//! chunk afresh.
+
+use std::hint::black_box;
+
+use wasm_bindgen::prelude::*;
+use wasm_split::{wasm_split, SplitChunk};
+
+/// Called across modules by [`cross_chunk_calls`], and not inlined, so that
+/// both loops make the same call.
+#[inline(never)]
+fn step(x: u64) -> u64 {
+    black_box(x.wrapping_mul(0x9e37_79b9_7f4a_7c15).rotate_left(17))
+}
+
+#[wasm_bindgen]
+pub fn direct_calls(n: u32) -> u64 {
+    let mut x = 1;
+    for _ in 0..n {
+        x = step(x);
+    }
+    x
+}
+
+#[wasm_split(calls)]
+fn call_loop(n: u32) -> u64 {
+    let mut x = 1;
+    for _ in 0..n {
+        x = step(x);
+    }
+    x
+}
+
+#[wasm_bindgen]
+pub async fn cross_chunk_calls(n: u32) -> u64 {
+    call_loop(n).await
+}
+
+#[inline(never)]
+async fn noop(x: u64) -> u64 {
+    black_box(x)
+}
+
+#[wasm_bindgen]
+pub async fn async_calls(n: u32) -> u64 {
+    let mut x = 1;
+    for _ in 0..n {
+        x = noop(x).await;
+    }
+    x
+}
+
+#[wasm_split(dispatch)]
+fn split_noop(x: u64) -> u64 {
+    black_box(x)
+}
+
+#[wasm_bindgen]
+pub async fn dispatch_calls(n: u32) -> u64 {
+    let mut x = 1;
+    for _ in 0..n {
+        x = split_noop(x).await;
+    }
+    x
+}
+
+/// Comments as `deserialize_comments` of the example gets them, if they were
+/// one `<id>:<text>` line each rather than JSON.
+const COMMENTS: &str = "1:First!\n2:Lazy loading, nice\n3:How big is the main module now?\n\
+                        4:About half of what it was\n5:Does it work with SSR?\n";
+
+/// Parses `input` and sums the ids and lengths of its comments, inlined into
+/// both [`direct_comment_calls`] and [`deserialize_comments`], so that both
+/// make the same call.
+#[inline(always)]
+fn parse_comments(input: &str) -> usize {
+    input
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(id, text)| id.parse::<usize>().unwrap_or(0) + text.len())
+        .sum()
+}
+
+#[inline(never)]
+fn deserialize_comments_unsplit(input: &str) -> usize {
+    parse_comments(input)
+}
+
+#[wasm_bindgen]
+pub fn direct_comment_calls(n: u32) -> usize {
+    let mut sum = 0;
+    for _ in 0..n {
+        sum += deserialize_comments_unsplit(black_box(COMMENTS));
+    }
+    sum
+}
+
+#[wasm_split(comments)]
+fn deserialize_comments(input: &str) -> usize {
+    parse_comments(input)
+}
+
+#[wasm_bindgen]
+pub async fn hot_comment_calls(n: u32) -> usize {
+    let mut sum = 0;
+    for _ in 0..n {
+        sum += deserialize_comments(black_box(COMMENTS)).await;
+    }
+    sum
+}
+
+/// Expands to `2^k` steps that the optimizer can neither merge nor drop,
+/// each adding a few bytes of code.
+macro_rules! mix {
+    ($x:ident, $k:expr, 0) => {
+        $x = black_box($x.wrapping_mul(0x9e37_79b9_7f4a_7c15) ^ ($k as u64));
+    };
+    ($x:ident, $k:expr, 1) => {
+        mix!($x, $k, 0);
+        mix!($x, $k + 1, 0);
+    };
+    ($x:ident, $k:expr, 2) => {
+        mix!($x, $k, 1);
+        mix!($x, $k + 2, 1);
+    };
+    ($x:ident, $k:expr, 3) => {
+        mix!($x, $k, 2);
+        mix!($x, $k + 4, 2);
+    };
+    ($x:ident, $k:expr, 4) => {
+        mix!($x, $k, 3);
+        mix!($x, $k + 8, 3);
+    };
+    ($x:ident, $k:expr, 5) => {
+        mix!($x, $k, 4);
+        mix!($x, $k + 16, 4);
+    };
+    ($x:ident, $k:expr, 6) => {
+        mix!($x, $k, 5);
+        mix!($x, $k + 32, 5);
+    };
+    ($x:ident, $k:expr, 7) => {
+        mix!($x, $k, 6);
+        mix!($x, $k + 64, 6);
+    };
+    ($x:ident, $k:expr, 8) => {
+        mix!($x, $k, 7);
+        mix!($x, $k + 128, 7);
+    };
+    ($x:ident, $k:expr, 9) => {
+        mix!($x, $k, 8);
+        mix!($x, $k + 256, 8);
+    };
+    ($x:ident, $k:expr, 10) => {
+        mix!($x, $k, 9);
+        mix!($x, $k + 512, 9);
+    };
+    ($x:ident, $k:expr, 11) => {
+        mix!($x, $k, 10);
+        mix!($x, $k + 1024, 10);
+    };
+    ($x:ident, $k:expr, 12) => {
+        mix!($x, $k, 11);
+        mix!($x, $k + 2048, 11);
+    };
+}
+
+#[wasm_split(small)]
+fn small(x: u64) -> u64 {
+    let mut x = x;
+    mix!(x, 0, 4);
+    x
+}
+
+#[wasm_split(medium)]
+fn medium(x: u64) -> u64 {
+    let mut x = x;
+    mix!(x, 0, 8);
+    x
+}
+
+#[wasm_split(large)]
+fn large(x: u64) -> u64 {
+    let mut x = x;
+    mix!(x, 0, 12);
+    x
+}
+
+const SIZED_CHUNKS: [SplitChunk; 3] = [
+    SplitChunk::new("small"),
+    SplitChunk::new("medium"),
+    SplitChunk::new("large"),
+];
+
+/// Loads the chunk named `name`, one of `small`, `medium` and `large`.
+#[wasm_bindgen]
+pub async fn load_chunk(name: String) -> Result<(), JsError> {
+    let chunk = SIZED_CHUNKS
+        .iter()
+        .find(|chunk| chunk.name() == name)
+        .ok_or_else(|| JsError::new(&format!("Unknown chunk {name}")))?;
+    chunk
+        .load()
+        .await
+        .map_err(|error| JsError::new(&error.to_string()))
+}
+
+/// Calls the functions of the sized chunks, which would otherwise not be
+/// referenced from the main module and thus not be split out at all.
+#[wasm_bindgen]
+pub async fn call_sized_chunks(x: u64) -> u64 {
+    large(medium(small(x).await).await).await
+}
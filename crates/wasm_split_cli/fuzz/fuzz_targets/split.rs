@@ -0,0 +1,67 @@
+//! Splits arbitrary valid modules generated by wasm-smith, which must either
+//! fail with an error or produce valid modules, the main one of which still
+//! exports all that the input did, but for the exports of split functions.
+//!
+//! Generated modules have no relocations, so the splitter falls back to
+//! `--table-only` and re-encodes every section of the module around the one
+//! table that each defines.
+//!
+//!     cargo +nightly fuzz run split
+
+#![no_main]
+
+use libfuzzer_sys::{
+    arbitrary::{Result, Unstructured},
+    fuzz_target,
+};
+use wasm_split_cli::{split_wasm, SplitOptions};
+use wasmparser::{Parser, Payload, Validator, WasmFeatures};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = run(data);
+});
+
+fn run(data: &[u8]) -> Result<()> {
+    let mut u = Unstructured::new(data);
+    let mut config: wasm_smith::Config = u.arbitrary()?;
+    config.min_tables = 1;
+    config.max_tables = 1;
+    config.max_imports = 0;
+    let input = wasm_smith::Module::new(config, &mut u)?.to_bytes();
+
+    let Ok(output) = split_wasm(&input, &SplitOptions::new()) else {
+        return Ok(());
+    };
+    for (path, contents) in &output.files {
+        if path.extension().is_some_and(|extension| extension == "wasm") {
+            if let Err(err) = Validator::new_with_features(WasmFeatures::all()).validate_all(contents)
+            {
+                panic!("{} is invalid: {err}", path.display());
+            }
+        }
+    }
+    let main = output.chunk("main").expect("the split has no main module");
+    let (input_exports, main_exports) = (exports(&input), exports(main));
+    for export in input_exports {
+        if export.starts_with("__wasm_split_") {
+            continue;
+        }
+        assert!(
+            main_exports.contains(&export),
+            "the main module does not export {export}"
+        );
+    }
+    Ok(())
+}
+
+fn exports(module: &[u8]) -> Vec<String> {
+    Parser::new(0)
+        .parse_all(module)
+        .filter_map(|payload| match payload.unwrap() {
+            Payload::ExportSection(exports) => Some(exports),
+            _ => None,
+        })
+        .flatten()
+        .map(|export| export.unwrap().name.to_string())
+        .collect()
+}
@@ -0,0 +1,597 @@
+//! `wasm-split analyze`: size reports for any wasm module, to estimate what
+//! splitting would gain before adopting it.
+//!
+//! Unlike splitting, the analysis needs neither `#[wasm_split]` annotations
+//! nor relocations, so it also runs on the output of wasm-bindgen. It builds
+//! the call graph from the direct calls and function references in the code,
+//! and writes three reports:
+//!
+//! - `size`: how the file size divides into code, data and custom sections.
+//! - `attribution`: the code size of each crate, attributed by the function
+//!   names of the name section as for `deny-in-main`.
+//! - `suggest`: the functions that retain the most code, i.e. whose callees
+//!   are only reachable through them. Annotating such a function with
+//!   `#[wasm_split]` moves all of that code out of the main module.
+//!
+//! Functions that are exported, called at start or placed in a table are
+//! entry points, since JS or an indirect call may reach them at any time. As
+//! indirect calls cannot be followed, the retained sizes are lower bounds:
+//! code that is only reachable through a trait object or closure defined
+//! under a function is not counted for it.
+//!
+//! With `--explain`, a module with split points and relocations is split
+//! instead, to show why code ends up where it does; see `explain.rs`.
+
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use wasmparser::{ElementItems, Operator};
+
+use crate::{
+    config::ByteSize,
+    deny::get_crate_name,
+    explain,
+    read::{InputFuncId, InputModule},
+    split_point::ChunkingOptions,
+    symbols::Demangling,
+};
+
+/// Suggestions retaining less than this share of the code are not listed.
+const MIN_SUGGESTED_SHARE: f64 = 0.005;
+
+/// A function is not suggested if its dominator retains little more than it
+/// does, since splitting there moves the same code.
+const NESTED_SUGGESTION_SHARE: f64 = 0.9;
+
+#[derive(Debug, Serialize)]
+pub struct Analysis {
+    pub size: SizeReport,
+    /// Code size of each crate, largest first.
+    pub attribution: Vec<CrateSize>,
+    pub suggest: Vec<Suggestion>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SizeReport {
+    pub total: usize,
+    pub code: usize,
+    pub data: usize,
+    /// Size of each custom section, such as `name` and DWARF sections.
+    pub custom_sections: BTreeMap<String, usize>,
+    /// Function counts of the call graph.
+    pub defined_functions: usize,
+    pub entry_points: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CrateSize {
+    #[serde(rename = "crate")]
+    pub crate_name: String,
+    pub functions: usize,
+    pub bytes: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Suggestion {
+    pub function: String,
+    /// Size of the function's own body.
+    pub bytes: usize,
+    /// Size of the function and all code only reachable through it.
+    pub retained_bytes: usize,
+    /// The crates contributing most of the retained code, largest first.
+    pub crates: Vec<CrateSize>,
+}
+
+fn function_name(module: &InputModule, func_id: InputFuncId, demangling: Demangling) -> String {
+    match module.names.functions.get(&func_id) {
+        Some(name) => demangling.demangle(name),
+        None => format!("func[{func_id}]"),
+    }
+}
+
+/// Callees of every defined function, by direct calls and `ref.func`,
+/// indexed by defined function.
+fn get_call_graph(module: &InputModule) -> Result<Vec<Vec<usize>>> {
+    let imported = module.imported_funcs.len();
+    module
+        .defined_funcs
+        .iter()
+        .map(|func| {
+            let mut callees = Vec::new();
+            for operator in func.body.get_operators_reader()? {
+                let (Operator::Call { function_index }
+                | Operator::ReturnCall { function_index }
+                | Operator::RefFunc { function_index }) = operator?
+                else {
+                    continue;
+                };
+                if let Some(callee) = (function_index as usize).checked_sub(imported) {
+                    callees.push(callee);
+                }
+            }
+            callees.sort_unstable();
+            callees.dedup();
+            Ok(callees)
+        })
+        .collect::<Result<_>>()
+        .with_context(|| "Failed to read function bodies")
+}
+
+/// Defined functions that may be called from outside of the code.
+fn get_entry_points(module: &InputModule) -> Result<Vec<usize>> {
+    let imported = module.imported_funcs.len();
+    let mut entry_points = module
+        .exports
+        .iter()
+        .filter(|export| export.kind == wasmparser::ExternalKind::Func)
+        .map(|export| export.index as usize)
+        .chain(module.start)
+        .collect::<Vec<_>>();
+    for element in module.elements.iter() {
+        match &element.items {
+            ElementItems::Functions(reader) => {
+                for index in reader.clone() {
+                    entry_points.push(index? as usize);
+                }
+            }
+            ElementItems::Expressions(_, reader) => {
+                for expr in reader.clone() {
+                    for operator in expr?.get_operators_reader() {
+                        if let Operator::RefFunc { function_index } = operator? {
+                            entry_points.push(function_index as usize);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    let mut entry_points = entry_points
+        .into_iter()
+        .filter_map(|index| index.checked_sub(imported))
+        .collect::<Vec<_>>();
+    entry_points.sort_unstable();
+    entry_points.dedup();
+    Ok(entry_points)
+}
+
+/// Immediate dominators in a graph whose node `root` reaches all others
+/// that matter, by the iterative algorithm of Cooper, Harvey and Kennedy.
+/// Unreachable nodes and the root have none. Also returns the nodes reachable
+/// from the root in postorder, in which every node comes before its
+/// dominator.
+fn get_immediate_dominators(
+    successors: &[Vec<usize>],
+    root: usize,
+) -> (Vec<Option<usize>>, Vec<usize>) {
+    // Postorder, by an iterative depth-first search.
+    let mut postorder = Vec::new();
+    let mut visited = vec![false; successors.len()];
+    let mut stack = vec![(root, 0)];
+    visited[root] = true;
+    while let Some((node, next)) = stack.last_mut() {
+        if let Some(&successor) = successors[*node].get(*next) {
+            *next += 1;
+            if !visited[successor] {
+                visited[successor] = true;
+                stack.push((successor, 0));
+            }
+        } else {
+            postorder.push(*node);
+            stack.pop();
+        }
+    }
+    let mut order = vec![usize::MAX; successors.len()];
+    for (position, &node) in postorder.iter().enumerate() {
+        order[node] = position;
+    }
+    let mut predecessors = vec![Vec::new(); successors.len()];
+    for (node, node_successors) in successors.iter().enumerate() {
+        for &successor in node_successors {
+            predecessors[successor].push(node);
+        }
+    }
+
+    let mut dominators = vec![None; successors.len()];
+    dominators[root] = Some(root);
+    let intersect = |dominators: &[Option<usize>], mut a: usize, mut b: usize| {
+        while a != b {
+            while order[a] < order[b] {
+                a = dominators[a].unwrap();
+            }
+            while order[b] < order[a] {
+                b = dominators[b].unwrap();
+            }
+        }
+        a
+    };
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &node in postorder.iter().rev().filter(|&&node| node != root) {
+            let mut processed = predecessors[node]
+                .iter()
+                .copied()
+                .filter(|&predecessor| dominators[predecessor].is_some());
+            let Some(first) = processed.next() else {
+                continue;
+            };
+            let dominator = processed.fold(first, |dominator, predecessor| {
+                intersect(&dominators, dominator, predecessor)
+            });
+            if dominators[node] != Some(dominator) {
+                dominators[node] = Some(dominator);
+                changed = true;
+            }
+        }
+    }
+    dominators[root] = None;
+    (dominators, postorder)
+}
+
+fn get_crate_sizes(
+    module: &InputModule,
+    functions: impl IntoIterator<Item = usize>,
+    sizes: &[usize],
+) -> Vec<CrateSize> {
+    let imported = module.imported_funcs.len();
+    let mut crates = BTreeMap::<String, (usize, usize)>::new();
+    for index in functions {
+        let name = function_name(module, imported + index, Demangling::Full);
+        let entry = crates.entry(get_crate_name(&name).to_string()).or_default();
+        entry.0 += 1;
+        entry.1 += sizes[index];
+    }
+    let mut crates = crates
+        .into_iter()
+        .map(|(crate_name, (functions, bytes))| CrateSize {
+            crate_name,
+            functions,
+            bytes,
+        })
+        .collect::<Vec<_>>();
+    crates.sort_by(|a, b| b.bytes.cmp(&a.bytes).then(a.crate_name.cmp(&b.crate_name)));
+    crates
+}
+
+/// Dominator tree of the call graph, over the defined functions and a
+/// virtual root calling all entry points, which is the last node.
+pub struct Retention {
+    pub entry_points: Vec<usize>,
+    /// Size of the body of each defined function.
+    pub sizes: Vec<usize>,
+    /// Size of each function with all code only reachable through it.
+    pub retained: Vec<usize>,
+    dominators: Vec<Option<usize>>,
+    children: Vec<Vec<usize>>,
+    postorder: Vec<usize>,
+}
+
+pub fn get_retention(module: &InputModule) -> Result<Retention> {
+    let sizes = module
+        .defined_funcs
+        .iter()
+        .map(|func| func.body.range().len())
+        .collect::<Vec<_>>();
+    let entry_points = get_entry_points(module)?;
+
+    let root = sizes.len();
+    let mut successors = get_call_graph(module)?;
+    successors.push(entry_points.clone());
+    let (dominators, postorder) = get_immediate_dominators(&successors, root);
+
+    // Retained sizes accumulate from the bottom of the dominator tree up.
+    let mut retained = sizes.clone();
+    retained.push(0);
+    let mut children = vec![Vec::new(); sizes.len() + 1];
+    for &node in postorder.iter().filter(|&&node| node != root) {
+        let dominator = dominators[node].unwrap();
+        retained[dominator] += retained[node];
+        children[dominator].push(node);
+    }
+    Ok(Retention {
+        entry_points,
+        sizes,
+        retained,
+        dominators,
+        children,
+        postorder,
+    })
+}
+
+pub fn analyze(module: &InputModule, top: usize, demangling: Demangling) -> Result<Analysis> {
+    let imported = module.imported_funcs.len();
+    let Retention {
+        entry_points,
+        sizes,
+        retained,
+        dominators,
+        children,
+        postorder,
+    } = get_retention(module)?;
+    let root = sizes.len();
+
+    let code = sizes.iter().sum::<usize>();
+    let mut candidates = postorder
+        .iter()
+        .copied()
+        .filter(|&node| node != root)
+        .filter(|node| entry_points.binary_search(node).is_err())
+        .filter(|&node| retained[node] as f64 >= code as f64 * MIN_SUGGESTED_SHARE)
+        .filter(|&node| {
+            let dominator = dominators[node].unwrap();
+            dominator == root
+                || entry_points.binary_search(&dominator).is_ok()
+                || (retained[node] as f64) < retained[dominator] as f64 * NESTED_SUGGESTION_SHARE
+        })
+        .collect::<Vec<_>>();
+    candidates.sort_by(|&a, &b| retained[b].cmp(&retained[a]).then(a.cmp(&b)));
+    candidates.truncate(top);
+    let suggest = candidates
+        .into_iter()
+        .map(|node| {
+            let mut dominated = vec![node];
+            let mut index = 0;
+            while let Some(&current) = dominated.get(index) {
+                dominated.extend(children[current].iter().copied());
+                index += 1;
+            }
+            let mut crates = get_crate_sizes(module, dominated, &sizes);
+            crates.truncate(3);
+            Suggestion {
+                function: function_name(module, imported + node, demangling),
+                bytes: sizes[node],
+                retained_bytes: retained[node],
+                crates,
+            }
+        })
+        .collect();
+
+    let custom_sections =
+        module
+            .custom_sections
+            .iter()
+            .fold(BTreeMap::new(), |mut sections, section| {
+                *sections.entry(section.name.to_string()).or_default() += section.range.len();
+                sections
+            });
+    Ok(Analysis {
+        size: SizeReport {
+            total: module.raw.len(),
+            code,
+            data: module
+                .data_segments
+                .iter()
+                .map(|segment| segment.data.len())
+                .sum(),
+            custom_sections,
+            defined_functions: sizes.len(),
+            entry_points: entry_points.len(),
+        },
+        attribution: get_crate_sizes(module, 0..sizes.len(), &sizes),
+        suggest,
+    })
+}
+
+fn print_analysis(analysis: &Analysis, top: usize) {
+    let size = &analysis.size;
+    let share = |bytes: usize| bytes as f64 * 100.0 / size.total.max(1) as f64;
+    println!("Size: {}", ByteSize(size.total));
+    let sections = [("code", size.code), ("data", size.data)]
+        .into_iter()
+        .chain(
+            size.custom_sections
+                .iter()
+                .map(|(name, bytes)| (name.as_str(), *bytes)),
+        );
+    for (name, bytes) in sections {
+        println!("  {:>10} {:5.1}%  {name}", ByteSize(bytes), share(bytes));
+    }
+    println!(
+        "  {} functions, {} of which are entry points",
+        size.defined_functions, size.entry_points
+    );
+
+    println!("\nCode by crate:");
+    for crate_size in analysis.attribution.iter().take(top) {
+        println!(
+            "  {:>10} {:5.1}%  {} ({} functions)",
+            ByteSize(crate_size.bytes),
+            share(crate_size.bytes),
+            crate_size.crate_name,
+            crate_size.functions
+        );
+    }
+    if analysis.attribution.len() > top {
+        println!("  ... and {} more crates", analysis.attribution.len() - top);
+    }
+
+    println!("\nCandidates for #[wasm_split], by the code only they reach:");
+    if analysis.suggest.is_empty() {
+        println!("  None: no function retains a significant share of the code.");
+    }
+    for suggestion in analysis.suggest.iter() {
+        println!(
+            "  {:>10} {:5.1}%  {}",
+            ByteSize(suggestion.retained_bytes),
+            share(suggestion.retained_bytes),
+            suggestion.function
+        );
+        let crates = suggestion
+            .crates
+            .iter()
+            .map(|crate_size| format!("{} {}", crate_size.crate_name, ByteSize(crate_size.bytes)))
+            .collect::<Vec<_>>();
+        println!("               mostly {}", crates.join(", "));
+    }
+}
+
+/// Writes the reports of `input`, or with `explain` the explanation of the
+/// chunk of a function or crate when split with the given options.
+pub fn run(
+    input: &Path,
+    top: usize,
+    json: Option<&Path>,
+    explain: Option<(&str, ChunkingOptions)>,
+    demangling: Demangling,
+) -> Result<()> {
+    let wasm = std::fs::read(input).with_context(|| format!("Failed to read {input:?}"))?;
+    let module = InputModule::parse(&wasm)?;
+    if module.names.functions.is_empty() {
+        eprintln!(
+            "Warning: {input:?} has no name section, so code cannot be attributed to crates. \
+             Keep it with `debug = 1` or `strip = false` in the Cargo profile, and without \
+             wasm-bindgen's --remove-name-section."
+        );
+    }
+    let report = match explain {
+        Some((query, options)) => {
+            let explanation = explain::explain(&module, query, top, &options, demangling)?;
+            explain::print_explanation(&explanation);
+            serde_json::to_string_pretty(&explanation)?
+        }
+        None => {
+            let analysis = analyze(&module, top, demangling)?;
+            print_analysis(&analysis, top);
+            serde_json::to_string_pretty(&analysis)?
+        }
+    };
+    if let Some(json) = json {
+        std::fs::write(json, report).with_context(|| format!("Failed to write {json:?}"))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use wasm_encoder::{
+        CodeSection, ExportKind, ExportSection, Function, FunctionSection, Instruction, Module,
+        NameMap, NameSection, TypeSection,
+    };
+
+    use super::{analyze, run};
+    use crate::{
+        read::InputModule,
+        symbols::Demangling,
+        test_fixtures::{fixture_path, temp_dir},
+    };
+
+    /// A module without split points or relocations, whose exported `run`
+    /// calls `big`, which alone calls `helper`, and `shared`, which `big`
+    /// also calls.
+    fn module() -> Vec<u8> {
+        let mut types = TypeSection::new();
+        types.function([], []);
+        let mut functions = FunctionSection::new();
+        let mut code = CodeSection::new();
+        let mut names = NameMap::new();
+        let bodies: [(&str, &[u32], usize); 4] = [
+            ("app::run", &[1, 3], 0),
+            ("app::big", &[2, 3], 0),
+            ("dep::helper", &[], 100),
+            ("app::shared", &[], 10),
+        ];
+        for (index, (name, callees, nops)) in bodies.into_iter().enumerate() {
+            functions.function(0);
+            let mut body = Function::new([]);
+            for &callee in callees {
+                body.instruction(&Instruction::Call(callee));
+            }
+            for _ in 0..nops {
+                body.instruction(&Instruction::Nop);
+            }
+            body.instruction(&Instruction::End);
+            code.function(&body);
+            names.append(index as u32, name);
+        }
+        let mut exports = ExportSection::new();
+        exports.export("run", ExportKind::Func, 0);
+        let mut section = NameSection::new();
+        section.functions(&names);
+        let mut module = Module::new();
+        module
+            .section(&types)
+            .section(&functions)
+            .section(&exports)
+            .section(&code)
+            .section(&section);
+        module.finish()
+    }
+
+    #[test]
+    fn suggests_functions_retaining_most_code() {
+        let wasm = module();
+        let module = InputModule::parse(&wasm).unwrap();
+        let analysis = analyze(&module, 10, Demangling::Full).unwrap();
+        assert_eq!(analysis.size.total, wasm.len());
+        assert_eq!(analysis.size.defined_functions, 4);
+        assert_eq!(analysis.size.entry_points, 1);
+        assert_eq!(
+            analysis.size.custom_sections.keys().collect::<Vec<_>>(),
+            ["name"]
+        );
+
+        let sizes = module
+            .defined_funcs
+            .iter()
+            .map(|func| func.body.range().len())
+            .collect::<Vec<_>>();
+        assert_eq!(analysis.size.code, sizes.iter().sum::<usize>());
+        let crates = analysis
+            .attribution
+            .iter()
+            .map(|crate_size| {
+                (
+                    &*crate_size.crate_name,
+                    crate_size.functions,
+                    crate_size.bytes,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            crates,
+            [
+                ("dep", 1, sizes[2]),
+                ("app", 3, sizes[0] + sizes[1] + sizes[3])
+            ]
+        );
+
+        // `helper` is not suggested on its own, since splitting at `big`
+        // moves nearly the same code, and `shared` is reached by both `run`
+        // and `big`.
+        let suggested = analysis
+            .suggest
+            .iter()
+            .map(|suggestion| (&*suggestion.function, suggestion.retained_bytes))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            suggested,
+            [("app::big", sizes[1] + sizes[2]), ("app::shared", sizes[3])]
+        );
+        assert_eq!(analysis.suggest[0].crates[0].crate_name, "dep");
+    }
+
+    #[test]
+    fn writes_reports_of_unsplit_module() {
+        let dir = temp_dir();
+        let json = dir.dir.join("analysis.json");
+        run(
+            &fixture_path("no_std_app_no_relocs.wasm"),
+            5,
+            Some(&json),
+            None,
+            Demangling::Full,
+        )
+        .unwrap();
+        let analysis: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(json).unwrap()).unwrap();
+        assert!(analysis["size"]["code"].as_u64().unwrap() > 0);
+        assert!(analysis["attribution"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|crate_size| crate_size["crate"] == "no_std_app"));
+        let suggest = analysis["suggest"].as_array().unwrap();
+        assert!(!suggest.is_empty() && suggest.len() <= 5, "{suggest:?}");
+    }
+}
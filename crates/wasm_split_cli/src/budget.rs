@@ -0,0 +1,251 @@
+//! Size budgets from the config and `--budget`, checked against the emitted
+//! chunks.
+//!
+//! Sizes are those of the files written by the splitter, i.e. after
+//! `[wasm-opt]` if configured, but before wasm-bindgen processes the main
+//! module or any other tool runs on them.
+
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+
+use crate::{
+    config::{Budgets, ByteSize},
+    manifest::{Manifest, ManifestChunk},
+    symbols::Demangling,
+    treemap,
+};
+
+/// Number of crates listed for each chunk over its budget.
+const MAX_REPORTED_CONTRIBUTORS: usize = 8;
+
+/// Parses `NAME=SIZE` of `--budget`.
+pub fn parse_chunk_budget(s: &str) -> Result<(String, ByteSize)> {
+    let Some((name, size)) = s.split_once('=') else {
+        bail!("Expected a chunk name and size such as `main=350KB`, not {s:?}");
+    };
+    Ok((name.trim().to_string(), size.parse()?))
+}
+
+/// Checks the size of each chunk, by name and encoding, against its budget
+/// in `budgets`. Chunks over their budget are reported with the crates that
+/// contribute the most code to them, as read from their name sections.
+pub fn check_chunk_budgets(
+    modules: &[(String, &[u8])],
+    budgets: &BTreeMap<String, ByteSize>,
+    demangling: Demangling,
+) -> Result<()> {
+    let mut exceeded = Vec::new();
+    for (name, &budget) in budgets.iter() {
+        let Some((_, data)) = modules.iter().find(|(chunk, _)| chunk == name) else {
+            bail!(
+                "Budget for chunk {name:?} does not match any chunk; chunks are {}",
+                modules
+                    .iter()
+                    .map(|(chunk, _)| chunk.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        };
+        let size = ByteSize(data.len());
+        println!("Chunk {name}: {size} of {budget} budget");
+        if size <= budget {
+            continue;
+        }
+        let chunk = treemap::chunk_node(name, data, demangling)?;
+        let mut report = format!(
+            "  {name}: {size} exceeds {budget} by {excess}",
+            excess = ByteSize(size.0 - budget.0)
+        );
+        for contributor in chunk.children.iter().take(MAX_REPORTED_CONTRIBUTORS) {
+            report.push_str(&format!(
+                "\n    + {:>10}  {}",
+                ByteSize(contributor.size).to_string(),
+                contributor.name
+            ));
+        }
+        if chunk.children.len() > MAX_REPORTED_CONTRIBUTORS {
+            let rest = &chunk.children[MAX_REPORTED_CONTRIBUTORS..];
+            report.push_str(&format!(
+                "\n    + {:>10}  {} more",
+                ByteSize(rest.iter().map(|node| node.size).sum()).to_string(),
+                rest.len()
+            ));
+        }
+        exceeded.push(report);
+    }
+    if !exceeded.is_empty() {
+        bail!("Chunk budgets exceeded:\n{}", exceeded.join("\n"));
+    }
+    Ok(())
+}
+
+/// Whether `route` is `parent` or nested under it.
+pub fn is_within(route: &str, parent: &str) -> bool {
+    let parent = parent.trim_end_matches('/');
+    route == parent
+        || route
+            .strip_prefix(parent)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// Chunks needed by any route within `parent`, including the shared chunks
+/// they depend on, keyed by name.
+fn get_route_chunks<'a>(
+    manifest: &'a Manifest,
+    parent: &str,
+) -> BTreeMap<&'a str, &'a ManifestChunk> {
+    let mut chunks = BTreeMap::new();
+    let mut pending = manifest
+        .routes
+        .iter()
+        .filter(|(route, _)| is_within(route, parent))
+        .flat_map(|(_, modules)| modules.iter())
+        .collect::<Vec<_>>();
+    while let Some(name) = pending.pop() {
+        // Folded modules have no chunk of their own.
+        let Some(chunk) = manifest.chunks.iter().find(|chunk| chunk.name == *name) else {
+            continue;
+        };
+        if chunks.insert(chunk.name.as_str(), chunk).is_none() {
+            pending.extend(chunk.dependencies.iter());
+        }
+    }
+    chunks
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BudgetKind {
+    Chunk,
+    Route,
+}
+
+/// Size of a chunk or route of a build against its budget.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BudgetCheck {
+    pub kind: BudgetKind,
+    /// Chunk name or route path, as in the config.
+    pub name: String,
+    pub size: usize,
+    pub budget: usize,
+}
+
+impl BudgetCheck {
+    /// How much the size exceeds the budget by, if it does.
+    pub fn excess(&self) -> Option<usize> {
+        (self.size > self.budget).then(|| self.size - self.budget)
+    }
+}
+
+/// The sizes of the chunks and routes of `budgets` in `manifest`, chunks
+/// first, for reports that list every budget rather than fail on the first
+/// one exceeded. Chunk budgets may name a module co-located with another.
+pub fn get_budget_checks(manifest: &Manifest, budgets: &Budgets) -> Result<Vec<BudgetCheck>> {
+    let mut checks = Vec::new();
+    for (name, &budget) in budgets.chunks.iter() {
+        let chunk_name = manifest.aliases.get(name).unwrap_or(name);
+        let Some(chunk) = manifest
+            .chunks
+            .iter()
+            .find(|chunk| chunk.name == *chunk_name)
+        else {
+            bail!(
+                "Budget for chunk {name:?} does not match any chunk; chunks are {}",
+                manifest
+                    .chunks
+                    .iter()
+                    .map(|chunk| chunk.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        };
+        checks.push(BudgetCheck {
+            kind: BudgetKind::Chunk,
+            name: name.clone(),
+            size: chunk.size,
+            budget: budget.0,
+        });
+    }
+    for (parent, &budget) in budgets.routes.iter() {
+        if !manifest.routes.keys().any(|route| is_within(route, parent)) {
+            bail!("Budget for route {parent:?} does not match any configured route");
+        }
+        checks.push(BudgetCheck {
+            kind: BudgetKind::Route,
+            name: parent.clone(),
+            size: get_route_chunks(manifest, parent)
+                .values()
+                .map(|chunk| chunk.size)
+                .sum(),
+            budget: budget.0,
+        });
+    }
+    Ok(checks)
+}
+
+pub fn check_budgets(manifest: &Manifest, budgets: &Budgets) -> Result<()> {
+    let mut exceeded = Vec::new();
+    for (parent, &budget) in budgets.routes.iter() {
+        if !manifest.routes.keys().any(|route| is_within(route, parent)) {
+            bail!("Budget for route {parent:?} does not match any configured route");
+        }
+        let chunks = get_route_chunks(manifest, parent);
+        let size = ByteSize(chunks.values().map(|chunk| chunk.size).sum());
+        println!("Route {parent}: {size} of {budget} budget");
+        if size > budget {
+            let mut chunks = chunks.into_values().collect::<Vec<_>>();
+            chunks.sort_by_key(|chunk| std::cmp::Reverse(chunk.size));
+            exceeded.push(format!(
+                "  {parent}: {size} exceeds {budget} by {excess}, across {chunks}",
+                excess = ByteSize(size.0 - budget.0),
+                chunks = chunks
+                    .iter()
+                    .map(|chunk| format!("{} ({})", chunk.name, ByteSize(chunk.size)))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ));
+        }
+    }
+    if !exceeded.is_empty() {
+        bail!("Route budgets exceeded:\n{}", exceeded.join("\n"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_fixtures::try_split;
+
+    #[test]
+    fn exceeded_budget_writes_no_output() {
+        let (output, result) = try_split(
+            "no_std_app.wasm",
+            "[routes]\n\"/\" = [\"first\"]\n\n[budgets.routes]\n\"/\" = \"1B\"\n",
+            &[],
+        );
+        let err = result.expect_err("no chunk fits in a byte");
+        assert!(format!("{err:#}").contains("budgets exceeded"), "{err:#}");
+        assert!(!output.dir.exists());
+    }
+
+    #[test]
+    fn reports_contributors_of_chunks_over_budget() {
+        let (output, result) = try_split(
+            "no_std_app.wasm",
+            "[budgets.chunks]\nmain = \"1MB\"\nsecond = \"100KB\"\n",
+            &["--budget", "first=10B"],
+        );
+        let err = format!("{:#}", result.expect_err("no chunk fits in 10 bytes"));
+        assert!(err.contains("Chunk budgets exceeded:\n  first: "), "{err}");
+        assert!(err.contains("exceeds 10 B by"), "{err}");
+        assert!(err.contains("no_std_app"), "{err}");
+        assert!(!err.contains("  second: "), "{err}");
+        assert!(!output.dir.exists());
+
+        let (_output, result) = try_split("no_std_app.wasm", "", &["--budget", "third=1KB"]);
+        let err = format!("{:#}", result.unwrap_err());
+        assert!(err.contains("\"third\" does not match any chunk"), "{err}");
+    }
+}
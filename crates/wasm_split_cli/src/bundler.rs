@@ -0,0 +1,86 @@
+//! Module of the chunk files of the build, written with
+//! `--emit-bundler-assets` to `assets` of `[output]` for apps whose JS is
+//! built by a bundler, which must copy the chunks along with the rest of
+//! their assets. Each chunk is referenced as
+//! `new URL("./chunk.wasm", import.meta.url)`, which Vite and webpack 5
+//! resolve to the URL of the asset they emit for the file, hashed as any
+//! other, so no plugin is needed; Rollup does the same with
+//! `@web/rollup-plugin-import-meta-assets`. The module hands those URLs to
+//! the loader with `setChunkUrls`, so the app imports it once, before its
+//! first load:
+//!
+//! ```js
+//! import "./pkg/wasm-split-assets.js";
+//! import init from "./pkg/main.js";
+//!
+//! await init();
+//! ```
+//!
+//! The manifest is embedded in the loader, so only the main module, which
+//! wasm-bindgen's glue references in the same way, and the chunks need to be
+//! assets. Chunks of manifests reloaded later, which the bundler never saw,
+//! are loaded from the output directory or the base URL as without it.
+
+use crate::{config::OutputPaths, manifest::Manifest};
+
+pub const ASSETS_FILENAME: &str = "wasm-split-assets.js";
+
+/// The JS module of the chunks of `manifest` and its `.d.ts` file, for
+/// `output`.
+pub fn render_assets(manifest: &Manifest, output: &OutputPaths) -> (String, String) {
+    let mut js = format!(
+        "// Chunk files of build {}, written by wasm-split.\n\
+         import {{ setChunkUrls }} from {};\n\n\
+         export const chunkUrls = {{\n",
+        manifest.build_id,
+        serde_json::to_string(&output.loader_from_assets()).unwrap()
+    );
+    for chunk in manifest.chunks.iter().filter(|chunk| chunk.name != "main") {
+        let url = output.chunk_from_assets(&chunk.file);
+        js.push_str(&format!(
+            "  {}: new URL({}, import.meta.url).href,\n",
+            serde_json::to_string(&chunk.name).unwrap(),
+            serde_json::to_string(&url).unwrap()
+        ));
+    }
+    js.push_str("};\n\nsetChunkUrls(chunkUrls);\n");
+    let dts = format!(
+        "// Chunk files of build {}, written by wasm-split.\n\n\
+         /** URLs of the chunk files by chunk name, as given to the loader on import. */\n\
+         export const chunkUrls: Record<string, string>;\n",
+        manifest.build_id
+    );
+    (js, dts)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_fixtures::{expected_no_std_app_result, split};
+
+    #[test]
+    fn loads_chunks_from_the_urls_of_bundled_assets() {
+        let output = split("no_std_app.wasm", &["--emit-bundler-assets"]);
+        let js = String::from_utf8(output.read("wasm-split-assets.js")).unwrap();
+        assert!(js.contains("import { setChunkUrls } from \"./__wasm_split.js\";"));
+        assert!(js.contains("  \"second\": new URL(\"./second.wasm\", import.meta.url).href,\n"));
+        assert!(!js.contains("main.wasm"));
+        let dts = String::from_utf8(output.read("wasm-split-assets.d.ts")).unwrap();
+        assert!(dts.contains("export const chunkUrls: Record<string, string>;"));
+
+        // As a bundler would emit them, with the chunks left in the output
+        // directory broken to check that the loader never fetches them.
+        let manifest = output.manifest();
+        let mut bundled = js.clone();
+        for chunk in manifest["chunks"].as_array().unwrap()[1..].iter() {
+            let file = chunk["file"].as_str().unwrap();
+            let asset = format!("{}-0123abcd.wasm", file.trim_end_matches(".wasm"));
+            output.write(&asset, &output.read(file));
+            output.write(file, b"not wasm");
+            bundled = bundled.replace(&format!("\"./{file}\""), &format!("\"./{asset}\""));
+        }
+        output.write("wasm-split-assets.js", bundled.as_bytes());
+        if let Some(result) = output.run_no_std_app_with_bundled_assets(5) {
+            assert_eq!(result, expected_no_std_app_result(5));
+        }
+    }
+}
@@ -0,0 +1,182 @@
+//! `--cache`: results of the slow steps after emitting each chunk, kept
+//! between splits.
+//!
+//! Encoding a chunk is fast, but measuring it, hashing it, optimizing it and
+//! compressing it with every `--compress` encoding take most of a split. In a
+//! watch loop, most chunks come out of the emitter exactly as they did the
+//! time before, so each of these results is stored under a digest of the
+//! bytes it was computed from, and later splits read it back instead.
+//!
+//! Entries are keyed by the emitted bytes rather than by the input functions
+//! they come from: function indices, and so the bytes of every call, shift
+//! with most compiles, but a chunk whose code and layout are the same is
+//! emitted identically. The call graph analysis is not cached, as it is a
+//! small share of a split, per `--timings`. Entries that no split has used for
+//! [`MAX_AGE`] are removed.
+
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Context, Result};
+use serde::{de::DeserializeOwned, Serialize};
+use sha2::{Digest, Sha256};
+
+const MAX_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+pub struct Cache {
+    dir: PathBuf,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl Cache {
+    /// Opens the cache in `dir`, creating it if needed, and removes expired
+    /// entries.
+    pub fn open(dir: &Path) -> Result<Self> {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create cache directory {dir:?}"))?;
+        let now = SystemTime::now();
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let modified = entry.metadata()?.modified()?;
+            if now.duration_since(modified).unwrap_or_default() > MAX_AGE {
+                std::fs::remove_file(entry.path())?;
+            }
+        }
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        })
+    }
+
+    /// Path of the entry of `kind` computed from `inputs`. The version of the
+    /// splitter is part of the key, so that entries of another one, which
+    /// may have emitted or compressed differently, are never read.
+    fn entry_path(&self, kind: &str, inputs: &[&[u8]]) -> PathBuf {
+        let mut hasher = Sha256::new();
+        hasher.update(env!("CARGO_PKG_VERSION"));
+        hasher.update([0]);
+        hasher.update(kind);
+        for input in inputs {
+            hasher.update((input.len() as u64).to_le_bytes());
+            hasher.update(input);
+        }
+        let digest = hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>();
+        self.dir.join(format!("{kind}-{digest}"))
+    }
+
+    pub fn print_stats(&self) {
+        let hits = self.hits.load(Ordering::Relaxed);
+        let misses = self.misses.load(Ordering::Relaxed);
+        println!(
+            "Reused {hits} of {} results from the cache in {}",
+            hits + misses,
+            self.dir.display()
+        );
+    }
+}
+
+/// The result of `compute`, which depends on `inputs` alone, read from
+/// `cache` if an earlier call stored it there.
+pub fn cached_bytes(
+    cache: Option<&Cache>,
+    kind: &str,
+    inputs: &[&[u8]],
+    compute: impl FnOnce() -> Result<Vec<u8>>,
+) -> Result<Vec<u8>> {
+    let Some(cache) = cache else {
+        return compute();
+    };
+    let path = cache.entry_path(kind, inputs);
+    if let Ok(data) = std::fs::read(&path) {
+        // Entries count as used when read, not only when written.
+        File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(SystemTime::now()))
+            .with_context(|| format!("Failed to touch cache entry {path:?}"))?;
+        cache.hits.fetch_add(1, Ordering::Relaxed);
+        return Ok(data);
+    }
+    cache.misses.fetch_add(1, Ordering::Relaxed);
+    let data = compute()?;
+    // Chunks are emitted in parallel, and several splits may share a cache,
+    // so an entry only appears under its name once complete.
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let partial = path.with_extension(format!(
+        "partial-{}-{}",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::write(&partial, &data)
+        .and_then(|()| std::fs::rename(&partial, &path))
+        .with_context(|| format!("Failed to write cache entry {path:?}"))?;
+    Ok(data)
+}
+
+/// Like [`cached_bytes`], for a value stored as JSON.
+pub fn cached<T: Serialize + DeserializeOwned>(
+    cache: Option<&Cache>,
+    kind: &str,
+    inputs: &[&[u8]],
+    compute: impl FnOnce() -> Result<T>,
+) -> Result<T> {
+    if cache.is_none() {
+        return compute();
+    }
+    let mut computed = None;
+    let data = cached_bytes(cache, kind, inputs, || {
+        let value = compute()?;
+        let json = serde_json::to_vec(&value)?;
+        computed = Some(value);
+        Ok(json)
+    })?;
+    match computed {
+        Some(value) => Ok(value),
+        None => serde_json::from_slice(&data)
+            .with_context(|| format!("Invalid {kind} entry in the cache; remove the cache to fix")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_fixtures::split;
+
+    #[test]
+    fn reuses_results_of_unchanged_chunks() {
+        let dir = std::env::temp_dir().join(format!("wasm-split-cache-{}", std::process::id()));
+        let options = ["--compress", "gzip", "--cache", dir.to_str().unwrap()];
+        let first = split("no_std_app.wasm", &options);
+        let mut compressed = 0;
+        for entry in std::fs::read_dir(&dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path
+                .file_name()
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .starts_with("gz-")
+            {
+                std::fs::write(path, b"cached").unwrap();
+                compressed += 1;
+            }
+        }
+        assert_eq!(compressed, first.wasm_files().len());
+
+        let second = split("no_std_app.wasm", &options);
+        for file in first.wasm_files() {
+            assert_eq!(second.read(&file), first.read(&file));
+            assert_eq!(second.read(&format!("{file}.gz")), b"cached");
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,131 @@
+//! Precompressed copies of the chunks, written with `--compress` next to
+//! each `.wasm` file, for static hosts that serve `chunk.wasm.br` or
+//! `chunk.wasm.gz` for requests of `chunk.wasm` that accept the encoding,
+//! such as nginx with `gzip_static` and `brotli_static`.
+
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, clap::ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    /// Brotli at the highest quality, with the `brotli` command, which must
+    /// be installed.
+    Br,
+    /// Gzip at the highest level.
+    Gzip,
+}
+
+impl Encoding {
+    /// Extension appended to the file name of a chunk, as static hosts
+    /// expect it.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Br => "br",
+            Self::Gzip => "gz",
+        }
+    }
+
+    pub fn compress(self, data: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            Self::Br => brotli(data),
+            Self::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+                encoder.write_all(data)?;
+                Ok(encoder.finish()?)
+            }
+        }
+    }
+}
+
+/// There is no brotli encoder among the dependencies, so this runs the
+/// reference implementation's command line tool.
+fn brotli(data: &[u8]) -> Result<Vec<u8>> {
+    let mut child = match Command::new("brotli")
+        .args(["--best", "--stdout", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => bail!(
+            "--compress br requires the `brotli` command, e.g. from the `brotli` package of \
+             your system, or leave out br"
+        ),
+        Err(err) => return Err(err).context("Failed to run brotli"),
+    };
+    // Written from another thread, as brotli may fill its output pipe before
+    // it has read all of its input.
+    let mut stdin = child.stdin.take().unwrap();
+    let input = data.to_vec();
+    let writer = std::thread::spawn(move || stdin.write_all(&input));
+    let output = child.wait_with_output().context("Failed to run brotli")?;
+    writer
+        .join()
+        .unwrap()
+        .context("Failed to write to brotli")?;
+    if !output.status.success() {
+        bail!("brotli failed with {}", output.status);
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::Encoding;
+    use crate::test_fixtures::{split, try_split};
+
+    #[test]
+    fn compresses_with_gzip() {
+        let data = b"wasm-split ".repeat(100);
+        let compressed = Encoding::Gzip.compress(&data).unwrap();
+        assert!(compressed.len() < data.len());
+        let mut decompressed = Vec::new();
+        flate2::read::GzDecoder::new(compressed.as_slice())
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, data);
+    }
+
+    #[test]
+    fn writes_compressed_copies_of_chunks() {
+        let output = split("no_std_app.wasm", &["--compress", "gzip"]);
+        for chunk in output.manifest()["chunks"].as_array().unwrap() {
+            let file = chunk["file"].as_str().unwrap();
+            let compressed = output.read(&format!("{file}.gz"));
+            assert_eq!(chunk["compressed_sizes"]["gzip"], compressed.len());
+            let mut decompressed = Vec::new();
+            flate2::read::GzDecoder::new(compressed.as_slice())
+                .read_to_end(&mut decompressed)
+                .unwrap();
+            assert_eq!(decompressed, output.read(file));
+        }
+
+        let (output, result) = try_split("no_std_app.wasm", "", &["--compress", "br,gzip"]);
+        match result {
+            Ok(()) => {
+                let manifest = output.manifest();
+                let main = &manifest["chunks"][0];
+                let file = main["file"].as_str().unwrap();
+                assert_eq!(
+                    main["compressed_sizes"]["br"],
+                    output.read(&format!("{file}.br")).len()
+                );
+            }
+            Err(err) => assert!(
+                format!("{err:#}").contains("requires the `brotli` command"),
+                "{err:?}"
+            ),
+        }
+    }
+}
@@ -0,0 +1,1283 @@
+//! Project configuration, read from [`CONFIG_FILENAME`].
+//!
+//! ```toml
+//! # Version of wasm-split that the project is split with. Any other version
+//! # fails, so that every machine produces the same output.
+//! wasm-split-version = "0.1.0"
+//!
+//! # Another config whose settings this one inherits and overrides, such as
+//! # one shared by the apps of a workspace; see `Config::load`.
+//! extends = "../../wasm-split.toml"
+//!
+//! # Crates whose code must only be loaded lazily. Splitting fails if any of
+//! # their functions end up in the main module.
+//! deny-in-main = ["chrono", "regex"]
+//!
+//! # Crates whose code goes into a split module of its own, `auto_chrono`
+//! # and so on, without `#[wasm_split]` attributes: whatever of it the start
+//! # function and the `--startup-export`s do not reach. The main module calls
+//! # it through the table, and the loader loads it right after startup, so a
+//! # call that comes earlier traps, panics with `--guard-calls`, or loads the
+//! # chunk synchronously with `--lazy-indirect-calls`. Code that may run that
+//! # early can await `wasm_split::load_group(&["auto_chrono"])` first.
+//! auto-split = ["chrono", "plotters"]
+//!
+//! # Entry points other than the main thread, such as web workers, with the
+//! # exports that only they call. The code that only those reach goes into a
+//! # split module of the entry, `entry_worker` and so on, which the main
+//! # thread never loads; the entry instantiates the main module and awaits
+//! # `loadEntry("worker")` of the loader before calling them. Code that they
+//! # share with split modules or other entries goes into shared chunks.
+//! [entries]
+//! worker = ["worker_main", "on_message"]
+//!
+//! # Split modules needed by each route of the application. Routes declared
+//! # by split points with `route = "/path"` are checked against these, and
+//! # used instead if this table is missing; see
+//! # `Config::apply_declared_routes`.
+//! [routes]
+//! "/" = []
+//! "/admin" = ["view_b"]
+//! "/admin/users" = ["view_b", "view_b_child"]
+//!
+//! # Maximum total size of the chunks needed by a route and the routes
+//! # nested under it.
+//! [budgets.routes]
+//! "/admin" = "500KB"
+//!
+//! # Maximum size of single chunks, by name as in the manifest. `--budget`
+//! # adds to these and overrides them.
+//! [budgets.chunks]
+//! main = "350KB"
+//!
+//! # Where to write the main module, the loader, the manifest and the other
+//! # generated files, relative to the output directory. Chunks are always
+//! # written to the output directory itself.
+//! [output]
+//! main = "app.wasm"
+//! loader = "js/wasm-split.js"
+//! manifest = "meta/wasm-split-manifest.json"
+//! preload = "meta/wasm-split-preload.json"
+//! # Likewise `symbols`, `provenance`, `treemap`, `service-worker`,
+//! # `preload-html`, `precache`, `fallback` and `assets`.
+//! # Where the main module goes after wasm-bindgen, for build tools that
+//! # rename it, rather than next to its glue with a `_bg` suffix.
+//! main-bg = "app_bg.wasm"
+//! # File names of the other chunks, with their `{name}` and `{kind}`,
+//! # either "split" or "shared".
+//! chunks = "app-{name}.wasm"
+//!
+//! # HTTP cache modes of the loader's requests, as for the `cache` option of
+//! # `fetch`.
+//! [loader]
+//! chunk-cache = "force-cache"
+//! manifest-cache = "no-cache"
+//! # Whether the loader revalidates the manifest embedded in it; see
+//! # `ManifestStrategy`.
+//! manifest-strategy = "stale-while-revalidate"
+//! # The `credentials` option of the requests for chunks and manifests, e.g.
+//! # for chunks from another origin that requires cookies.
+//! credentials = "include"
+//! # Times to retry a chunk request that failed with a transient error,
+//! # after a delay that doubles each time.
+//! retries = 2
+//! retry-delay-ms = 500
+//! # Split modules that stay loaded once used; see `LoaderOptions::pin`.
+//! pin = ["editor"]
+//! # Whether the loader keeps chunks in the Cache Storage of the build, and
+//! # takes them from there first; see `LoaderOptions::cache_storage`.
+//! cache-storage = true
+//! # Failed chunk loads after which the page is reloaded with the main module
+//! # of `--emit-fallback`; see `LoaderOptions::fallback_after`.
+//! fallback-after = 3
+//! # Most chunks fetched at once, so that they don't starve the app's data
+//! # requests on slow connections; unlimited by default.
+//! max-concurrent-fetches = 2
+//! # Time after which a chunk load that is not done fails with a timeout;
+//! # unlimited by default.
+//! timeout-ms = 30000
+//!
+//! # How code is divided into chunks. `--fold-threshold`,
+//! # `--duplicate-threshold`, `--min-chunk-size` and `--max-chunks` override
+//! # the settings of this table.
+//! [chunking]
+//! # Split modules with less code are folded into the main module, 1KiB by
+//! # default; 0 folds none.
+//! min-size = "1KB"
+//! # Shared chunks with less code are merged into a chunk shared by more
+//! # split modules; see `split_point::merge_small_shared_chunks`.
+//! min-shared-size = "2KB"
+//! # Split modules with less code are merged into the split module they
+//! # share the most code with; see `small_chunks.rs`.
+//! min-chunk-size = "8KB"
+//! # Most chunks besides the main module, which the smallest split modules
+//! # are merged likewise to stay within.
+//! max-chunks = 20
+//! # "per-group", a chunk for each set of split modules that share code, or
+//! # "single", one chunk of all shared code.
+//! shared = "per-group"
+//! # Functions up to this size are copied into each chunk that calls them.
+//! duplicate-below = "64B"
+//!
+//! # Chunks that code goes into regardless of the `#[wasm_split]` attributes
+//! # of its split points. The split modules of `modules` become one, as with
+//! # `group`, while `crates` puts all code of those crates that is not part
+//! # of the main module into a chunk shared by the split modules using it.
+//! [chunks.admin]
+//! modules = ["view_b", "view_b_child"]
+//! [chunks.dates]
+//! crates = ["chrono"]
+//! # With `vendor`, the code of `crates` leaves the main module even where
+//! # startup code reaches it, for a chunk that the main module calls through
+//! # the table: "preload" loads it right after startup, like `auto-split`,
+//! # and "lazy" on the first call, which requires --lazy-indirect-calls. The
+//! # split reports the calls of the main module into the chunk.
+//! [chunks.vendor]
+//! crates = ["serde_json", "icu"]
+//! vendor = "preload"
+//!
+//! # Arguments of binaryen's `wasm-opt`, which every chunk but the main one
+//! # is optimized with after splitting if given; see `wasm_opt.rs`.
+//! [wasm-opt]
+//! args = ["-Oz"]
+//! # Arguments for the chunks of specific split modules instead.
+//! [wasm-opt.modules]
+//! editor = ["-O3"]
+//! ```
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    path::{Component, Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Deserializer};
+
+use crate::{
+    budget::is_within,
+    split_point::{SharedStrategy, SplitModuleIdentifier},
+};
+
+pub const CONFIG_FILENAME: &str = "wasm-split.toml";
+
+/// Parses a `KEY=PATH` of `--output-path`.
+pub fn parse_output_path(s: &str) -> Result<(String, PathBuf)> {
+    let Some((key, path)) = s.split_once('=') else {
+        bail!("Expected a key of `[output]` and a path such as `main=app.wasm`, not {s:?}");
+    };
+    Ok((key.trim().to_string(), PathBuf::from(path)))
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    /// The only version of wasm-split that may split the project.
+    #[serde(default)]
+    pub wasm_split_version: Option<String>,
+    /// Crates of which no code may end up in the main module.
+    #[serde(default)]
+    pub deny_in_main: Vec<String>,
+    /// Crates that are split out of the main module without split points.
+    #[serde(default)]
+    pub auto_split: Vec<String>,
+    /// Exports that only the given entry point calls, keyed by entry name.
+    #[serde(default)]
+    pub entries: BTreeMap<String, Vec<String>>,
+    /// Names of the split modules needed by each route, keyed by route path.
+    #[serde(default)]
+    pub routes: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    pub budgets: Budgets,
+    #[serde(default)]
+    pub output: OutputPaths,
+    #[serde(default)]
+    pub loader: LoaderOptions,
+    #[serde(default)]
+    pub wasm_opt: WasmOptOptions,
+    #[serde(default)]
+    pub chunking: ChunkingPolicy,
+    /// Chunks that split modules or crates are put into, by chunk name.
+    #[serde(default)]
+    pub chunks: BTreeMap<String, ChunkGroup>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ChunkingPolicy {
+    /// Default of `--fold-threshold`.
+    #[serde(default)]
+    pub min_size: Option<ByteSize>,
+    #[serde(default)]
+    pub min_shared_size: Option<ByteSize>,
+    /// Default of `--min-chunk-size`.
+    #[serde(default)]
+    pub min_chunk_size: Option<ByteSize>,
+    /// Default of `--max-chunks`.
+    #[serde(default)]
+    pub max_chunks: Option<usize>,
+    #[serde(default)]
+    pub shared: SharedStrategy,
+    /// Default of `--duplicate-threshold`.
+    #[serde(default)]
+    pub duplicate_below: Option<ByteSize>,
+}
+
+/// Contents of a chunk of `[chunks]`, which has either `modules` or `crates`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChunkGroup {
+    /// Split modules whose code, and the code shared only by them, goes into
+    /// the chunk, as if all their split points named it.
+    #[serde(default)]
+    pub modules: Vec<String>,
+    /// Crates whose code goes into the chunk, unless the main module needs
+    /// it.
+    #[serde(default)]
+    pub crates: Vec<String>,
+    /// Makes the chunk of `crates` a vendor chunk, which takes their code
+    /// even where the main module needs it, loaded as given.
+    #[serde(default)]
+    pub vendor: Option<VendorLoad>,
+}
+
+/// When the loader loads a vendor chunk of `[chunks]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum VendorLoad {
+    /// Right after startup, as the chunks of `auto-split` crates.
+    Preload,
+    /// On the first call of the main module into it, synchronously.
+    Lazy,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct WasmOptOptions {
+    /// Arguments of `wasm-opt` for each chunk, such as `["-Oz"]`. Chunks are
+    /// not optimized if empty.
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Arguments for the chunks of the given split modules, instead of
+    /// `args`. Shared chunks use `args`.
+    #[serde(default)]
+    pub modules: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Budgets {
+    /// Maximum total size of the chunks needed by a route and all routes
+    /// nested under it, keyed by route path.
+    #[serde(default)]
+    pub routes: BTreeMap<String, ByteSize>,
+    /// Maximum size of a chunk, keyed by chunk name.
+    #[serde(default)]
+    pub chunks: BTreeMap<String, ByteSize>,
+}
+
+/// Paths of the outputs that a deployment may expect in specific places,
+/// relative to the output directory.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct OutputPaths {
+    /// The main module, to be processed by wasm-bindgen, which is assumed to
+    /// write its JS glue next to it with the extension `.js`.
+    #[serde(default = "default_main_path")]
+    pub main: PathBuf,
+    /// The main module as processed by wasm-bindgen, if moved from where
+    /// wasm-bindgen writes it; see [`Self::main_bg`].
+    #[serde(default)]
+    pub main_bg: Option<PathBuf>,
+    /// The loader script that the main module imports.
+    #[serde(default = "default_loader_path")]
+    pub loader: PathBuf,
+    #[serde(default = "default_manifest_path")]
+    pub manifest: PathBuf,
+    /// Per-route preload hints; see `preload.rs`.
+    #[serde(default = "default_preload_path")]
+    pub preload: PathBuf,
+    /// Preload tags written with `--preload-html`; see `preload.rs`.
+    #[serde(default = "default_preload_html_path")]
+    pub preload_html: PathBuf,
+    /// Main module written with `--emit-fallback`, to be processed by
+    /// wasm-bindgen like `main`; see [`Self::fallback_bg`].
+    #[serde(default = "default_fallback_path")]
+    pub fallback: PathBuf,
+    /// Precache list for service workers; see `precache.rs`.
+    #[serde(default = "default_precache_path")]
+    pub precache: PathBuf,
+    /// Symbol map of the chunks; see `symbols.rs`.
+    #[serde(default = "default_symbols_path")]
+    pub symbols: PathBuf,
+    /// Report written with `--provenance`.
+    #[serde(default = "default_provenance_path")]
+    pub provenance: PathBuf,
+    /// Report written with `--treemap`.
+    #[serde(default = "default_treemap_path")]
+    pub treemap: PathBuf,
+    /// Script to be imported by the application's service worker.
+    #[serde(default = "default_service_worker_path")]
+    pub service_worker: PathBuf,
+    /// Directory of the JS entry points written with `--emit-js-entries`;
+    /// see `js_entries.rs`.
+    #[serde(default = "default_js_entries_path")]
+    pub js_entries: PathBuf,
+    /// Module of the chunk files written with `--emit-bundler-assets`; see
+    /// `bundler.rs`.
+    #[serde(default = "default_assets_path")]
+    pub assets: PathBuf,
+    /// Template of the file names of the chunks other than the main module,
+    /// which are written to the output directory itself.
+    #[serde(default = "default_chunks_template")]
+    pub chunks: String,
+}
+
+fn default_chunks_template() -> String {
+    "{name}.wasm".to_string()
+}
+
+fn default_main_path() -> PathBuf {
+    PathBuf::from("main.wasm")
+}
+
+fn default_loader_path() -> PathBuf {
+    PathBuf::from("__wasm_split.js")
+}
+
+fn default_manifest_path() -> PathBuf {
+    PathBuf::from(crate::manifest::MANIFEST_FILENAME)
+}
+
+fn default_preload_path() -> PathBuf {
+    PathBuf::from(crate::preload::PRELOAD_FILENAME)
+}
+
+fn default_preload_html_path() -> PathBuf {
+    PathBuf::from(crate::preload::PRELOAD_HTML_FILENAME)
+}
+
+fn default_fallback_path() -> PathBuf {
+    PathBuf::from(crate::fallback::FALLBACK_FILENAME)
+}
+
+fn default_precache_path() -> PathBuf {
+    PathBuf::from(crate::precache::PRECACHE_FILENAME)
+}
+
+fn default_symbols_path() -> PathBuf {
+    PathBuf::from(crate::symbols::SYMBOLS_FILENAME)
+}
+
+fn default_provenance_path() -> PathBuf {
+    PathBuf::from(crate::provenance::PROVENANCE_FILENAME)
+}
+
+fn default_treemap_path() -> PathBuf {
+    PathBuf::from(crate::treemap::TREEMAP_FILENAME)
+}
+
+fn default_service_worker_path() -> PathBuf {
+    PathBuf::from(crate::SERVICE_WORKER_FILENAME)
+}
+
+fn default_js_entries_path() -> PathBuf {
+    PathBuf::from("wasm-split-entries")
+}
+
+fn default_assets_path() -> PathBuf {
+    PathBuf::from(crate::bundler::ASSETS_FILENAME)
+}
+
+impl Default for OutputPaths {
+    fn default() -> Self {
+        Self {
+            main: default_main_path(),
+            main_bg: None,
+            loader: default_loader_path(),
+            manifest: default_manifest_path(),
+            preload: default_preload_path(),
+            preload_html: default_preload_html_path(),
+            fallback: default_fallback_path(),
+            precache: default_precache_path(),
+            symbols: default_symbols_path(),
+            provenance: default_provenance_path(),
+            treemap: default_treemap_path(),
+            service_worker: default_service_worker_path(),
+            js_entries: default_js_entries_path(),
+            assets: default_assets_path(),
+            chunks: default_chunks_template(),
+        }
+    }
+}
+
+/// URL of `to` relative to the directory `from_dir`, where both are relative
+/// to the output directory. An empty `to` refers to the output directory.
+fn relative_url(from_dir: &Path, to: &Path) -> String {
+    let from = from_dir.components().collect::<Vec<_>>();
+    let to = to.components().collect::<Vec<_>>();
+    let common = from
+        .iter()
+        .zip(to.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut url = if common == from.len() {
+        String::from("./")
+    } else {
+        "../".repeat(from.len() - common)
+    };
+    let rest = to[common..]
+        .iter()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>();
+    url.push_str(&rest.join("/"));
+    url
+}
+
+fn parent_dir(path: &Path) -> &Path {
+    path.parent().unwrap_or(Path::new(""))
+}
+
+impl OutputPaths {
+    fn validate(&self) -> Result<()> {
+        for (key, path) in [
+            ("main", &self.main),
+            ("loader", &self.loader),
+            ("manifest", &self.manifest),
+            ("preload", &self.preload),
+            ("preload-html", &self.preload_html),
+            ("precache", &self.precache),
+            ("fallback", &self.fallback),
+            ("symbols", &self.symbols),
+            ("provenance", &self.provenance),
+            ("treemap", &self.treemap),
+            ("service-worker", &self.service_worker),
+            ("js-entries", &self.js_entries),
+            ("assets", &self.assets),
+        ]
+        .into_iter()
+        .chain(self.main_bg.as_ref().map(|path| ("main-bg", path)))
+        {
+            if path.as_os_str().is_empty()
+                || !path
+                    .components()
+                    .all(|component| matches!(component, Component::Normal(_)))
+            {
+                bail!(
+                    "output.{key} must be a relative path within the output directory, \
+                     not {path:?}"
+                );
+            }
+        }
+        if self
+            .main
+            .extension()
+            .is_none_or(|extension| extension != "wasm")
+        {
+            bail!("output.main must end in .wasm, not {:?}", self.main);
+        }
+        let placeholders = self.chunks.replace("{name}", "").replace("{kind}", "");
+        if !self.chunks.contains("{name}")
+            || placeholders.contains(['{', '}', '/', '\\'])
+            || !self.chunks.ends_with(".wasm")
+        {
+            bail!(
+                "output.chunks must be a file name ending in .wasm with a {{name}} and optionally \
+                 a {{kind}} placeholder, not {:?}",
+                self.chunks
+            );
+        }
+        Ok(())
+    }
+
+    /// Import module of the loader, as imported by the JS glue of the main
+    /// module.
+    pub fn loader_from_main(&self) -> String {
+        relative_url(parent_dir(&self.main), &self.loader)
+    }
+
+    /// JS glue of the main module, as imported by the loader.
+    pub fn main_glue_from_loader(&self) -> String {
+        relative_url(parent_dir(&self.loader), &self.main.with_extension("js"))
+    }
+
+    /// The main module, as fetched by the loader itself for inputs built
+    /// without wasm-bindgen, which have no JS glue to load it.
+    pub fn main_from_loader(&self) -> String {
+        relative_url(parent_dir(&self.loader), &self.main)
+    }
+
+    /// The main module after wasm-bindgen, which writes it next to its glue
+    /// with a `_bg` suffix, unless `main-bg` moves it elsewhere.
+    pub fn main_bg(&self) -> PathBuf {
+        match &self.main_bg {
+            Some(path) => path.clone(),
+            None => self.bindgen_main_bg(),
+        }
+    }
+
+    /// Where wasm-bindgen writes the main module it processed.
+    pub fn bindgen_main_bg(&self) -> PathBuf {
+        let stem = self.main.file_stem().unwrap_or_default().to_string_lossy();
+        self.main.with_file_name(format!("{stem}_bg.wasm"))
+    }
+
+    /// [`Self::main_bg`], as fetched by the worker of the loader.
+    pub fn main_bg_from_loader(&self) -> String {
+        relative_url(parent_dir(&self.loader), &self.main_bg())
+    }
+
+    /// Where wasm-bindgen writes the fallback module it processed, which
+    /// keeps the glue of the main module.
+    pub fn fallback_bg(&self) -> PathBuf {
+        let stem = self
+            .fallback
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy();
+        self.fallback.with_file_name(format!("{stem}_bg.wasm"))
+    }
+
+    /// The fallback module as fetched by the loader: [`Self::fallback_bg`]
+    /// for inputs built with wasm-bindgen, or else `fallback` itself.
+    pub fn fallback_from_loader(&self, uses_wasm_bindgen: bool) -> String {
+        let fallback = if uses_wasm_bindgen {
+            self.fallback_bg()
+        } else {
+            self.fallback.clone()
+        };
+        relative_url(parent_dir(&self.loader), &fallback)
+    }
+
+    /// Sets the path of `key` of `[output]`, as given with `--output-path`.
+    pub fn set(&mut self, key: &str, path: &Path) -> Result<()> {
+        let path = path.to_path_buf();
+        match key {
+            "main" => self.main = path,
+            "main-bg" => self.main_bg = Some(path),
+            "loader" => self.loader = path,
+            "manifest" => self.manifest = path,
+            "preload" => self.preload = path,
+            "preload-html" => self.preload_html = path,
+            "precache" => self.precache = path,
+            "fallback" => self.fallback = path,
+            "symbols" => self.symbols = path,
+            "provenance" => self.provenance = path,
+            "treemap" => self.treemap = path,
+            "service-worker" => self.service_worker = path,
+            "assets" => self.assets = path,
+            "chunks" => self.chunks = path.to_string_lossy().into_owned(),
+            _ => bail!("Unknown output path {key:?}, expected a key of `[output]` such as main"),
+        }
+        self.validate()
+    }
+
+    /// The JS entry points of split module `name`, and its declarations.
+    pub fn js_entries(&self, name: &str) -> (PathBuf, PathBuf) {
+        (
+            self.js_entries.join(format!("{name}.js")),
+            self.js_entries.join(format!("{name}.d.ts")),
+        )
+    }
+
+    /// TypeScript declarations of the loader; see `types.rs`.
+    pub fn loader_types(&self) -> PathBuf {
+        self.loader.with_extension("d.ts")
+    }
+
+    /// TypeScript types of the manifest, next to it.
+    pub fn manifest_types(&self) -> PathBuf {
+        self.manifest.with_extension("d.ts")
+    }
+
+    /// The types of the manifest, as imported by those of the loader, under
+    /// the `.js` URL that TypeScript resolves to their `.d.ts` file.
+    pub fn manifest_types_from_loader(&self) -> String {
+        relative_url(
+            parent_dir(&self.loader),
+            &self.manifest.with_extension("js"),
+        )
+    }
+
+    /// The module of `--emit-bundler-assets`, and its declarations.
+    pub fn assets(&self) -> (PathBuf, PathBuf) {
+        (self.assets.clone(), self.assets.with_extension("d.ts"))
+    }
+
+    /// The loader, as imported by the module of `--emit-bundler-assets`.
+    pub fn loader_from_assets(&self) -> String {
+        relative_url(parent_dir(&self.assets), &self.loader)
+    }
+
+    /// The chunk file `file` of the manifest, as referenced by the module of
+    /// `--emit-bundler-assets`.
+    pub fn chunk_from_assets(&self, file: &str) -> String {
+        relative_url(parent_dir(&self.assets), Path::new(file))
+    }
+
+    /// The loader, as imported by the JS entry points.
+    pub fn loader_from_js_entries(&self) -> String {
+        relative_url(&self.js_entries, &self.loader)
+    }
+
+    pub fn manifest_from_loader(&self) -> String {
+        relative_url(parent_dir(&self.loader), &self.manifest)
+    }
+
+    /// The output directory, which chunk files are relative to, as seen from
+    /// the loader.
+    pub fn output_dir_from_loader(&self) -> String {
+        relative_url(parent_dir(&self.loader), Path::new(""))
+    }
+
+    /// The output directory as seen from the manifest, for chunk URLs in a
+    /// manifest fetched from a different origin.
+    pub fn output_dir_from_manifest(&self) -> String {
+        relative_url(parent_dir(&self.manifest), Path::new(""))
+    }
+
+    /// The manifest as seen from the output directory, i.e. from the testing
+    /// module.
+    pub fn manifest_from_output_dir(&self) -> String {
+        relative_url(Path::new(""), &self.manifest)
+    }
+
+    /// The manifest as seen from the service worker script.
+    pub fn manifest_from_service_worker(&self) -> String {
+        relative_url(parent_dir(&self.service_worker), &self.manifest)
+    }
+
+    /// File of an output module, relative to the output directory and with
+    /// `/` as separator, as listed in the manifest.
+    pub fn module_file(&self, identifier: &SplitModuleIdentifier) -> String {
+        match identifier {
+            SplitModuleIdentifier::Main => relative_url(Path::new(""), &self.main)
+                .trim_start_matches("./")
+                .to_string(),
+            SplitModuleIdentifier::Split(_) => self.chunk_file(identifier, "split"),
+            SplitModuleIdentifier::Chunk(_) | SplitModuleIdentifier::Named(..) => {
+                self.chunk_file(identifier, "shared")
+            }
+        }
+    }
+
+    fn chunk_file(&self, identifier: &SplitModuleIdentifier, kind: &str) -> String {
+        self.chunks
+            .replace("{name}", &identifier.name())
+            .replace("{kind}", kind)
+    }
+}
+
+/// Value of the `cache` option of `fetch`, which controls how a request
+/// interacts with the browser's HTTP cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CacheMode {
+    Default,
+    NoStore,
+    Reload,
+    NoCache,
+    ForceCache,
+}
+
+impl CacheMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Default => "default",
+            Self::NoStore => "no-store",
+            Self::Reload => "reload",
+            Self::NoCache => "no-cache",
+            Self::ForceCache => "force-cache",
+        }
+    }
+}
+
+/// Value of the `credentials` option of `fetch`, which controls whether a
+/// request sends cookies and HTTP authentication.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Credentials {
+    Omit,
+    SameOrigin,
+    Include,
+}
+
+impl Credentials {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Omit => "omit",
+            Self::SameOrigin => "same-origin",
+            Self::Include => "include",
+        }
+    }
+}
+
+/// How the loader resolves chunks against the manifest.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ManifestStrategy {
+    /// Only the manifest embedded in the loader is used, unless the app
+    /// calls `wasm_split::reload_manifest`.
+    #[default]
+    Embedded,
+    /// The embedded manifest is used right away, while the first chunk load
+    /// fetches the manifest file in the background. Once it is verified,
+    /// loads that start afterwards use the chunk URLs it lists, as after
+    /// `wasm_split::reload_manifest`, so that a deployment that moves chunks,
+    /// e.g. to a CDN, reaches pages that are already open without putting
+    /// the manifest request in the way of their first lazy load.
+    StaleWhileRevalidate,
+}
+
+impl ManifestStrategy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Embedded => "embedded",
+            Self::StaleWhileRevalidate => "stale-while-revalidate",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct LoaderOptions {
+    /// Cache mode of chunk requests. Chunk URLs include the build ID, so by
+    /// default a cached chunk is used without revalidating it.
+    #[serde(default = "default_chunk_cache")]
+    pub chunk_cache: CacheMode,
+    /// Cache mode of manifest requests by `wasm_split::reload_manifest`,
+    /// which by default always revalidates, since the manifest is replaced
+    /// in place by every deployment.
+    #[serde(default = "default_manifest_cache")]
+    pub manifest_cache: CacheMode,
+    #[serde(default)]
+    pub manifest_strategy: ManifestStrategy,
+    /// `credentials` of the requests for chunks and manifests, such as
+    /// `include` for chunks served from another origin that requires
+    /// cookies. The browser's default if not set; apps can also set it at
+    /// runtime with `wasm_split::configure`, along with headers.
+    #[serde(default)]
+    pub credentials: Option<Credentials>,
+    /// Times that the loader retries a chunk request which got no response,
+    /// or an HTTP status of 408, 429 or 5xx, before the load fails. None by default, so that
+    /// apps that retry on their own, e.g. with `#[wasm_split(module,
+    /// fallible)]`, decide when.
+    #[serde(default)]
+    pub retries: u32,
+    /// Delay before the first retry, doubled before each further one.
+    #[serde(default = "default_retry_delay_ms")]
+    pub retry_delay_ms: u32,
+    /// Split modules for features that become hot after first use. Their
+    /// chunks are always fetched from the HTTP cache if possible, keep their
+    /// URLs when the manifest is reloaded, and keep their compiled modules
+    /// referenced. Once loaded, they are loaded again when the browser is
+    /// idle on later page loads, before they are needed.
+    #[serde(default)]
+    pub pin: Vec<String>,
+    /// Keep fetched chunks in the `wasm-split-<build ID>` cache of the Cache
+    /// Storage API, which `precacheAll` of `sw.js` fills, and take them from
+    /// there before the network. Caches of other builds are deleted. Chunks
+    /// taken from the cache are not checked against their `integrity` again,
+    /// as they were when first fetched.
+    #[serde(default)]
+    pub cache_storage: bool,
+    /// Failed chunk loads after which the loader reloads the page with the
+    /// monolithic main module of `--emit-fallback`, for the rest of the
+    /// browser session. Calls waiting for a chunk when it does are not
+    /// completed, but run again in the reloaded app, whose state they lose.
+    /// Never by default.
+    #[serde(default)]
+    pub fallback_after: u32,
+    /// Most chunks that the loader fetches at once, from their request until
+    /// they are compiled, more urgent priority classes first. Unlimited by
+    /// default; apps can also set it at runtime with `wasm_split::configure`.
+    #[serde(default)]
+    pub max_concurrent_fetches: u32,
+    /// Time after which the loader cancels the request for a chunk that has
+    /// not been fetched and compiled yet, retries included, and fails its
+    /// load with `LoadError::Timeout`, so that a hung request doesn't leave
+    /// the app waiting forever. Unlimited by default; apps can also set it
+    /// at runtime with `wasm_split::configure`.
+    #[serde(default)]
+    pub timeout_ms: u32,
+}
+
+fn default_chunk_cache() -> CacheMode {
+    CacheMode::ForceCache
+}
+
+fn default_manifest_cache() -> CacheMode {
+    CacheMode::NoCache
+}
+
+fn default_retry_delay_ms() -> u32 {
+    500
+}
+
+impl Default for LoaderOptions {
+    fn default() -> Self {
+        Self {
+            chunk_cache: default_chunk_cache(),
+            manifest_cache: default_manifest_cache(),
+            manifest_strategy: ManifestStrategy::default(),
+            credentials: None,
+            retries: 0,
+            retry_delay_ms: default_retry_delay_ms(),
+            pin: Vec::new(),
+            cache_storage: false,
+            fallback_after: 0,
+            max_concurrent_fetches: 0,
+            timeout_ms: 0,
+        }
+    }
+}
+
+/// A size in bytes, written either as an integer number of bytes or as a
+/// string with a unit, such as `"500KB"` or `"1.5MiB"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ByteSize(pub usize);
+
+impl FromStr for ByteSize {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let split = s
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(s.len());
+        let (number, unit) = s.split_at(split);
+        let multiplier = match unit.trim() {
+            "" | "B" => 1,
+            "KB" | "kB" => 1000,
+            "KiB" => 1024,
+            "MB" => 1000 * 1000,
+            "MiB" => 1024 * 1024,
+            unit => return Err(anyhow!("Invalid size unit {unit:?} in {s:?}")),
+        };
+        let number: f64 = number
+            .parse()
+            .with_context(|| format!("Invalid size {s:?}"))?;
+        Ok(ByteSize((number * multiplier as f64).round() as usize))
+    }
+}
+
+impl<'de> Deserialize<'de> for ByteSize {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Bytes(usize),
+            String(String),
+        }
+        match Repr::deserialize(deserializer)? {
+            Repr::Bytes(bytes) => Ok(ByteSize(bytes)),
+            Repr::String(s) => s.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0 < 1000 {
+            write!(f, "{} B", self.0)
+        } else if self.0 < 1000 * 1000 {
+            write!(f, "{:.1} KB", self.0 as f64 / 1000.0)
+        } else {
+            write!(f, "{:.2} MB", self.0 as f64 / (1000.0 * 1000.0))
+        }
+    }
+}
+
+/// Overrides the values of `base` with those of `overrides`. Tables merge
+/// key by key, such that a crate can override a single budget or cache mode,
+/// while all other values, including arrays, are replaced as a whole.
+fn merge_tables(base: &mut toml::Table, overrides: toml::Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overrides)) => {
+                merge_tables(base, overrides);
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Reads a config file along with the files it `extends`, whose paths are
+/// relative to the extending file. `chain` holds the files being read, to
+/// detect cycles.
+fn read_table(path: &Path, chain: &mut Vec<PathBuf>) -> Result<toml::Table> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut table: toml::Table =
+        toml::from_str(&contents).with_context(|| format!("Invalid config {}", path.display()))?;
+    let Some(extends) = table.remove("extends") else {
+        return Ok(table);
+    };
+    let toml::Value::String(extends) = extends else {
+        bail!(
+            "Invalid config {}: `extends` must be the path of another config",
+            path.display()
+        );
+    };
+    let canonical = path
+        .canonicalize()
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    if chain.contains(&canonical) {
+        bail!("Config {} extends itself", path.display());
+    }
+    chain.push(canonical);
+    let base_path = parent_dir(path).join(extends);
+    let mut base = read_table(&base_path, chain)
+        .with_context(|| format!("Failed to read the config extended by {}", path.display()))?;
+    merge_tables(&mut base, table);
+    Ok(base)
+}
+
+impl Config {
+    /// Fails unless this binary is the version pinned by
+    /// `wasm-split-version`, if any.
+    pub fn check_version(&self) -> Result<()> {
+        let Some(pinned) = &self.wasm_split_version else {
+            return Ok(());
+        };
+        let pinned = pinned.trim_start_matches('v');
+        if pinned != crate::self_update::VERSION {
+            let install = if crate::self_update::AVAILABLE {
+                format!("with `wasm-split self-update {pinned}`")
+            } else {
+                format!("with `cargo install wasm_split_cli --version {pinned}`")
+            };
+            bail!(
+                "The config pins wasm-split {pinned}, but this is wasm-split {current}. \
+                 Install the pinned version {install}.",
+                current = crate::self_update::VERSION,
+            );
+        }
+        Ok(())
+    }
+
+    fn check_chunks(&self) -> Result<()> {
+        let mut modules = BTreeSet::new();
+        let mut crates = BTreeSet::new();
+        for (name, group) in self.chunks.iter() {
+            if name == "main"
+                || name.is_empty()
+                || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            {
+                bail!("chunks.{name} must be named like a split module, other than main");
+            }
+            if group.modules.is_empty() == group.crates.is_empty() {
+                bail!("chunks.{name} must have either modules or crates");
+            }
+            if group.vendor.is_some() && group.crates.is_empty() {
+                bail!("chunks.{name} has `vendor` but no crates");
+            }
+            if let Some(module) = group.modules.iter().find(|module| !modules.insert(*module)) {
+                bail!("Split module {module:?} is in more than one chunk of [chunks]");
+            }
+            if let Some(crate_name) = group
+                .crates
+                .iter()
+                .find(|crate_name| !crates.insert(crate_name.replace('-', "_")))
+            {
+                bail!("Crate {crate_name:?} is in more than one chunk of [chunks]");
+            }
+        }
+        Ok(())
+    }
+
+    /// Split modules that `[chunks]` merges, by the name of their chunk.
+    pub fn module_groups(&self) -> BTreeMap<String, Vec<String>> {
+        self.chunks
+            .iter()
+            .filter(|(_, group)| !group.modules.is_empty())
+            .map(|(name, group)| (name.clone(), group.modules.clone()))
+            .collect()
+    }
+
+    /// Crates that `[chunks]` puts into chunks of their own, with the names
+    /// of those chunks, other than vendor chunks.
+    pub fn crate_chunks(&self) -> Vec<(String, Vec<String>)> {
+        self.chunks
+            .iter()
+            .filter(|(_, group)| !group.crates.is_empty() && group.vendor.is_none())
+            .map(|(name, group)| (name.clone(), group.crates.clone()))
+            .collect()
+    }
+
+    /// Crates of the vendor chunks of `[chunks]`, with the names of those
+    /// chunks.
+    pub fn vendor_chunks(&self) -> Vec<(String, Vec<String>)> {
+        self.chunks
+            .iter()
+            .filter(|(_, group)| group.vendor.is_some())
+            .map(|(name, group)| (name.clone(), group.crates.clone()))
+            .collect()
+    }
+
+    /// Whether the loader loads the chunk `name` right after startup, as a
+    /// vendor chunk with `vendor = "preload"`.
+    pub fn preloads_vendor_chunk(&self, name: &str) -> bool {
+        self.chunks
+            .get(name)
+            .is_some_and(|group| group.vendor == Some(VendorLoad::Preload))
+    }
+
+    /// Replaces the names of split modules co-located with others by `with`,
+    /// in routes and pins, by the modules they stand for.
+    pub fn resolve_module_aliases(&mut self, aliases: &BTreeMap<String, String>) {
+        let resolve = |names: &mut Vec<String>| {
+            for name in names.iter_mut() {
+                if let Some(module_name) = aliases.get(name) {
+                    name.clone_from(module_name);
+                }
+            }
+            let mut seen = Vec::new();
+            names.retain(|name| {
+                let first = !seen.contains(name);
+                seen.push(name.clone());
+                first
+            });
+        };
+        for modules in self.routes.values_mut() {
+            resolve(modules);
+        }
+        resolve(&mut self.loader.pin);
+        self.budgets.chunks = std::mem::take(&mut self.budgets.chunks)
+            .into_iter()
+            .map(|(name, size)| (aliases.get(&name).cloned().unwrap_or(name), size))
+            .collect();
+        self.wasm_opt.modules = std::mem::take(&mut self.wasm_opt.modules)
+            .into_iter()
+            .map(|(name, args)| (aliases.get(&name).cloned().unwrap_or(name), args))
+            .collect();
+    }
+
+    /// Checks the routes of the config against those that split points
+    /// declare themselves the lazy views of, keyed by route path, so that a
+    /// misspelled path or module name fails the build rather than a
+    /// navigation. Each declared route must be configured with the modules
+    /// declared for it, and each configured route that needs any module must
+    /// be declared by one of them. Without configured routes, the declared
+    /// ones are used, each also needing the modules of the routes enclosing
+    /// it, as a nested route renders within its parents.
+    pub fn apply_declared_routes(
+        &mut self,
+        declared: &BTreeMap<String, Vec<String>>,
+    ) -> Result<()> {
+        if declared.is_empty() {
+            return Ok(());
+        }
+        if self.routes.is_empty() {
+            self.routes = declared
+                .keys()
+                .map(|route| {
+                    let modules = declared
+                        .iter()
+                        .filter(|(parent, _)| is_within(route, parent))
+                        .flat_map(|(_, modules)| modules.iter().cloned())
+                        .collect::<BTreeSet<_>>();
+                    (route.clone(), modules.into_iter().collect())
+                })
+                .collect();
+            return Ok(());
+        }
+        for (route, modules) in declared.iter() {
+            let Some(configured) = self.routes.get(route) else {
+                bail!(
+                    "Route {route:?} is declared by split module(s) {modules:?} with `route = \
+                     {route:?}`, but missing from the [routes] of the config"
+                );
+            };
+            if let Some(module) = modules.iter().find(|module| !configured.contains(module)) {
+                bail!(
+                    "Route {route:?} of the config does not list split module {module:?}, \
+                     which declares it with `route = {route:?}`"
+                );
+            }
+        }
+        if let Some((route, _)) = self
+            .routes
+            .iter()
+            .find(|(route, modules)| !modules.is_empty() && !declared.contains_key(*route))
+        {
+            bail!(
+                "Route {route:?} of the config is not declared by any split point with \
+                 `route = {route:?}`; is its path misspelled, or its view not split?"
+            );
+        }
+        Ok(())
+    }
+
+    /// Reads the configuration from `path` or, if no path is given, from
+    /// [`CONFIG_FILENAME`] in the working directory if it exists. A config
+    /// with `extends` is merged over the config it extends, recursively.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let path = match path {
+            Some(path) => path,
+            None if Path::new(CONFIG_FILENAME).exists() => Path::new(CONFIG_FILENAME),
+            None => return Ok(Self::default()),
+        };
+        let table = read_table(path, &mut Vec::new())?;
+        let config: Self = toml::Value::Table(table)
+            .try_into()
+            .with_context(|| format!("Invalid config {}", path.display()))?;
+        config
+            .output
+            .validate()
+            .and_then(|()| config.check_chunks())
+            .with_context(|| format!("Invalid config {}", path.display()))?;
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{ByteSize, Config};
+    use crate::{split_point::SharedStrategy, test_fixtures::temp_dir};
+
+    #[test]
+    fn merges_config_over_the_one_it_extends() {
+        let dir = temp_dir();
+        std::fs::create_dir_all(dir.dir.join("apps/admin")).unwrap();
+        dir.write(
+            "wasm-split.toml",
+            b"deny-in-main = [\"chrono\", \"regex\"]\n\
+              [budgets.chunks]\nmain = \"350KB\"\nsecond = \"10KB\"\n\
+              [chunking]\nmin-size = \"1KB\"\nshared = \"single\"\n",
+        );
+        dir.write(
+            "apps/base.toml",
+            b"extends = \"../wasm-split.toml\"\n[chunking]\nmin-size = \"2KB\"\n",
+        );
+        dir.write(
+            "apps/admin/wasm-split.toml",
+            b"extends = \"../base.toml\"\ndeny-in-main = [\"regex\"]\n\
+              [budgets.chunks]\nmain = \"500KB\"\n",
+        );
+        let config = Config::load(Some(&dir.dir.join("apps/admin/wasm-split.toml"))).unwrap();
+        // Arrays are replaced, while tables merge key by key, at any depth.
+        assert_eq!(config.deny_in_main, ["regex"]);
+        assert_eq!(
+            config.budgets.chunks.into_iter().collect::<Vec<_>>(),
+            [
+                ("main".to_string(), ByteSize(500_000)),
+                ("second".to_string(), ByteSize(10_000))
+            ]
+        );
+        assert_eq!(config.chunking.min_size, Some(ByteSize(2000)));
+        assert!(matches!(config.chunking.shared, SharedStrategy::Single));
+    }
+
+    #[test]
+    fn rejects_cycles_of_extended_configs() {
+        let dir = temp_dir();
+        dir.write("a.toml", b"extends = \"b.toml\"\n");
+        dir.write("b.toml", b"extends = \"./a.toml\"\n");
+        let err = Config::load(Some(&dir.dir.join("a.toml"))).unwrap_err();
+        assert!(
+            format!("{err:#}").contains("a.toml extends itself"),
+            "{err:#}"
+        );
+
+        dir.write("c.toml", b"extends = [\"a.toml\"]\n");
+        let err = Config::load(Some(&dir.dir.join("c.toml"))).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("must be the path of another config"),
+            "{err:#}"
+        );
+        dir.write("d.toml", b"extends = \"missing.toml\"\n");
+        assert!(Config::load(Some(&dir.dir.join("d.toml"))).is_err());
+    }
+
+    fn routes(routes: &[(&str, &[&str])]) -> BTreeMap<String, Vec<String>> {
+        routes
+            .iter()
+            .map(|(route, modules)| {
+                let modules = modules.iter().map(|module| module.to_string()).collect();
+                (route.to_string(), modules)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn infers_nested_routes_from_declared_ones_without_configured_routes() {
+        let mut config = Config::default();
+        config
+            .apply_declared_routes(&routes(&[
+                ("/", &["home"]),
+                ("/users", &["users"]),
+                ("/users/:id", &["profile", "users"]),
+                ("/usersettings", &["settings"]),
+            ]))
+            .unwrap();
+        // A nested route also needs the modules of the routes it renders
+        // within, while a route sharing only a prefix of the path is not
+        // nested.
+        assert_eq!(
+            config.routes,
+            routes(&[
+                ("/", &["home"]),
+                ("/users", &["home", "users"]),
+                ("/users/:id", &["home", "profile", "users"]),
+                ("/usersettings", &["home", "settings"]),
+            ])
+        );
+    }
+
+    #[test]
+    fn checks_configured_routes_against_declared_ones() {
+        let declared = routes(&[("/users", &["users"]), ("/admin", &["admin"])]);
+        let apply = |configured: &[(&str, &[&str])]| {
+            let mut config = Config {
+                routes: routes(configured),
+                ..Config::default()
+            };
+            config
+                .apply_declared_routes(&declared)
+                .map(|()| config.routes)
+        };
+
+        // Configured routes may need more modules than declared, or none.
+        let configured: &[(&str, &[&str])] = &[
+            ("/", &[]),
+            ("/users", &["users", "avatars"]),
+            ("/admin", &["admin"]),
+        ];
+        assert_eq!(apply(configured).unwrap(), routes(configured));
+
+        let err = apply(&[("/users", &["users"])]).unwrap_err();
+        assert!(
+            err.to_string().contains(
+                "Route \"/admin\" is declared by split module(s) [\"admin\"] with `route = \
+                 \"/admin\"`, but missing from the [routes] of the config"
+            ),
+            "{err:#}"
+        );
+
+        let err = apply(&[("/users", &["users"]), ("/admin", &["users"])]).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Route \"/admin\" of the config does not list split module \"admin\""),
+            "{err:#}"
+        );
+
+        let err = apply(&[
+            ("/users", &["users"]),
+            ("/admin", &["admin"]),
+            ("/setings", &["settings"]),
+        ])
+        .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Route \"/setings\" of the config is not declared by any split point"),
+            "{err:#}"
+        );
+    }
+}
@@ -0,0 +1,176 @@
+//! Which output modules get each custom section of the input.
+//!
+//! Sections that refer to functions or code offsets by index are remapped to
+//! the indices of each module: the `name` section and the DWARF sections, in
+//! `generate_name_section` and `generate_debug_sections` of `emit.rs`. Of the
+//! others, a module only gets those that tools read from it: every module is
+//! compiled and optimized on its own, so each keeps `target_features`, which
+//! `wasm-opt --detect-features` reads, while the `producers` section and the
+//! wasm-bindgen section describe the build as a whole and only stay in the
+//! main module, which is the one that wasm-bindgen processes. Sections that
+//! only make sense for the input, such as its relocations, are dropped.
+//!
+//! Other sections are copied into the main module as they are, as the input
+//! is otherwise only run as the main module; `--verbose` lists them. Any
+//! indices in them still refer to the input.
+
+use anyhow::{Context, Result};
+
+use crate::{
+    features::FEATURES_SECTION,
+    metadata::METADATA_SECTION_NAME,
+    signatures::{INPUT_SIGNATURES_SECTION, SIGNATURES_SECTION},
+    source_map::SOURCE_MAPPING_URL_SECTION,
+    toolchain::WASM_BINDGEN_SECTION,
+};
+
+pub const PRODUCERS_SECTION: &str = "producers";
+pub const TARGET_FEATURES_SECTION: &str = "target_features";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Placement {
+    /// Generated for each module, with the indices of the module.
+    Remapped,
+    /// Copied into the main module.
+    Main,
+    /// Copied into every module.
+    EveryModule,
+    /// Not copied, as it only applies to the input.
+    Dropped,
+}
+
+/// Placement of the custom section `name` of the input.
+pub fn placement(name: &str) -> Placement {
+    match name {
+        "name" => Placement::Remapped,
+        _ if name.starts_with(".debug_") => Placement::Remapped,
+        TARGET_FEATURES_SECTION => Placement::EveryModule,
+        PRODUCERS_SECTION | WASM_BINDGEN_SECTION => Placement::Main,
+        // Relocations are applied by the split, and the sections of
+        // `#[wasm_split]` are read from the input.
+        "linking" | METADATA_SECTION_NAME | INPUT_SIGNATURES_SECTION => Placement::Dropped,
+        _ if name.starts_with("reloc.") => Placement::Dropped,
+        // Point to the source map or DWARF of the input, whose addresses are
+        // those of its code rather than of any module's.
+        SOURCE_MAPPING_URL_SECTION | "external_debug_info" => Placement::Dropped,
+        // Written by an earlier split, and again by this one.
+        FEATURES_SECTION | SIGNATURES_SECTION => Placement::Dropped,
+        _ => Placement::Main,
+    }
+}
+
+/// Whether `name` is copied into the main module without wasm-split knowing
+/// what it holds.
+pub fn is_unknown(name: &str) -> bool {
+    placement(name) == Placement::Main && name != PRODUCERS_SECTION && name != WASM_BINDGEN_SECTION
+}
+
+/// The `producers` section of the main module: that of the input, given its
+/// contents, with wasm-split added to the tools that processed the module.
+pub fn producers_section(input: Option<(&[u8], usize)>) -> Result<wasm_encoder::ProducersSection> {
+    let mut fields = Vec::<(String, Vec<(String, String)>)>::new();
+    if let Some((data, offset)) = input {
+        let reader = wasmparser::ProducersSectionReader::new(data, offset)
+            .context("Invalid producers section in the input")?;
+        for field in reader {
+            let field = field?;
+            let values = field
+                .values
+                .into_iter()
+                .map(|value| value.map(|value| (value.name.into(), value.version.into())))
+                .collect::<Result<_, _>>()?;
+            fields.push((field.name.into(), values));
+        }
+    }
+    let processed_by = match fields.iter_mut().find(|(name, _)| name == "processed-by") {
+        Some((_, values)) => values,
+        None => {
+            fields.push(("processed-by".into(), Vec::new()));
+            &mut fields.last_mut().unwrap().1
+        }
+    };
+    processed_by.retain(|(name, _)| name != "wasm-split");
+    processed_by.push(("wasm-split".into(), env!("CARGO_PKG_VERSION").into()));
+
+    let mut section = wasm_encoder::ProducersSection::new();
+    for (name, values) in fields.iter() {
+        let mut field = wasm_encoder::ProducersField::new();
+        for (value, version) in values.iter() {
+            field.value(value, version);
+        }
+        section.field(name, &field);
+    }
+    Ok(section)
+}
+
+#[cfg(test)]
+mod tests {
+    use wasmparser::{Parser, Payload, ProducersSectionReader};
+
+    use crate::test_fixtures::split;
+
+    /// Names of the custom sections of `wasm`, in order.
+    fn custom_sections(wasm: &[u8]) -> Vec<String> {
+        Parser::new(0)
+            .parse_all(wasm)
+            .filter_map(|payload| match payload.unwrap() {
+                Payload::CustomSection(reader) => Some(reader.name().to_string()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn keeps_custom_sections_only_in_the_modules_that_need_them() {
+        let output = split("no_std_app.wasm", &[]);
+        let main = output.read("main.wasm");
+        let sections = custom_sections(&main);
+        for name in ["name", "producers", "target_features"] {
+            assert!(
+                sections.iter().any(|section| section == name),
+                "{sections:?}"
+            );
+        }
+        for name in ["linking", "reloc.CODE", "__wasm_split_meta"] {
+            assert!(
+                !sections.iter().any(|section| section == name),
+                "{sections:?}"
+            );
+        }
+
+        let chunk = output.read("first.wasm");
+        let sections = custom_sections(&chunk);
+        assert!(sections.iter().any(|section| section == "target_features"));
+        assert!(!sections.iter().any(|section| section == "producers"));
+
+        let producers = Parser::new(0)
+            .parse_all(&main)
+            .find_map(|payload| match payload.unwrap() {
+                Payload::CustomSection(reader) if reader.name() == "producers" => {
+                    Some(ProducersSectionReader::new(reader.data(), reader.data_offset()).unwrap())
+                }
+                _ => None,
+            })
+            .unwrap();
+        let mut processed_by = Vec::new();
+        let mut language = Vec::new();
+        for field in producers {
+            let field = field.unwrap();
+            let values = field
+                .values
+                .into_iter()
+                .map(|value| value.unwrap().name.to_string());
+            match field.name {
+                "processed-by" => processed_by.extend(values),
+                "language" => language.extend(values),
+                _ => {}
+            }
+        }
+        assert!(
+            processed_by.iter().any(|name| name == "rustc"),
+            "{processed_by:?}"
+        );
+        assert_eq!(processed_by.last().unwrap(), "wasm-split");
+        assert_eq!(language, ["Rust"]);
+    }
+}
@@ -0,0 +1,311 @@
+//! Placement of the input's read-only data in the modules whose code uses it.
+//!
+//! Data that only the code of one split module or chunk reaches, such as the
+//! unicode tables of `regex` or an `include_bytes!` blob, is moved out of the
+//! main module's data segments into active segments of that module, at the
+//! same addresses. These are written to the imported memory when the module
+//! is instantiated, before any of its code runs; until then the memory is
+//! zero there, and no other code reads it.
+//!
+//! Only `.rodata` segments are split, so that a module instantiated again by
+//! another thread writes the same bytes as before, and only if the input has
+//! no passive segments, whose indices `memory.init` and `data.drop` refer to.
+//! Data that is also reached from the copy of a function duplicated into
+//! another module stays in the main module, as that copy may run first.
+
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+};
+
+use wasmparser::{DataKind, RelocationEntry, RelocationType, SymbolInfo};
+
+use crate::{
+    dep_graph::DepNode,
+    read::{const_i32, DataSegmentId, InputModule, SymbolIndex},
+    split_point::SplitProgramInfo,
+};
+
+/// Runs of data smaller than this stay in the main module, where they cost
+/// less than the headers of the segments they would add to both modules.
+const MIN_MOVED_DATA_SIZE: usize = 64;
+
+/// Data of an input segment that an output module initializes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MovedData {
+    pub segment: DataSegmentId,
+    /// Range of the data in the input file.
+    pub range: Range<usize>,
+}
+
+/// Memory address at which `segment` of `module` is placed, if its data may
+/// be moved into other modules.
+pub fn movable_segment_address(module: &InputModule, segment: DataSegmentId) -> Option<i32> {
+    if module
+        .data_segments
+        .iter()
+        .any(|segment| matches!(segment.kind, DataKind::Passive))
+    {
+        return None;
+    }
+    // The active segments of a chunk are written each time it is
+    // instantiated, which, with a shared memory, every thread that loads it
+    // does, possibly after another thread has written to the data.
+    let shared = module
+        .imports
+        .iter()
+        .filter_map(|import| match import.ty {
+            wasmparser::TypeRef::Memory(memory) => Some(memory),
+            _ => None,
+        })
+        .chain(module.memories.iter().copied())
+        .any(|memory| memory.shared);
+    if shared {
+        return None;
+    }
+    let input_segment = &module.data_segments[segment];
+    let DataKind::Active {
+        memory_index: 0,
+        offset_expr,
+    } = &input_segment.kind
+    else {
+        return None;
+    };
+    let name = module.names.data_segments.get(&segment)?;
+    if !name.starts_with(".rodata") {
+        return None;
+    }
+    const_i32(offset_expr)
+}
+
+/// The data moved into each output module, by index of the module in
+/// `program_info`; that of the main module is empty.
+pub fn place_data(
+    module: &InputModule,
+    program_info: &SplitProgramInfo,
+    relocations_in_range: &dyn Fn(&Range<usize>) -> Vec<RelocationEntry>,
+) -> Vec<Vec<MovedData>> {
+    let mut placement = vec![Vec::new(); program_info.output_modules.len()];
+    let owners = get_data_owners(module, program_info, relocations_in_range);
+
+    for (segment, input_segment) in module.data_segments.iter().enumerate() {
+        if movable_segment_address(module, segment).is_none() {
+            continue;
+        }
+        let data_end = input_segment.range.end;
+        let data_start = data_end - input_segment.data.len();
+        let first = module
+            .data_symbols
+            .partition_point(|symbol| symbol.range.start < data_start);
+        let last = module
+            .data_symbols
+            .partition_point(|symbol| symbol.range.start < data_end);
+        let symbols = &module.data_symbols[first..last];
+
+        let mut current: Option<(usize, Range<usize>)> = None;
+        let mut flush = |current: &mut Option<(usize, Range<usize>)>| {
+            if let Some((owner, range)) = current.take() {
+                if range.len() >= MIN_MOVED_DATA_SIZE {
+                    placement[owner].push(MovedData { segment, range });
+                }
+            }
+        };
+        let mut index = 0;
+        while index < symbols.len() {
+            // Overlapping symbols are moved together, if all are owned by
+            // the same module.
+            let owner = owners.get(&symbols[index].symbol_index).copied();
+            let mut movable = owner.is_some_and(|owner| owner != 0);
+            let mut cluster = symbols[index].range.clone();
+            index += 1;
+            while index < symbols.len() && symbols[index].range.start < cluster.end {
+                cluster.end = cluster.end.max(symbols[index].range.end);
+                movable &= owners.get(&symbols[index].symbol_index).copied() == owner;
+                index += 1;
+            }
+            let Some(owner) = owner.filter(|_| movable) else {
+                flush(&mut current);
+                continue;
+            };
+            match &mut current {
+                // Padding between the data of a module, which is zero, is
+                // moved along with it rather than splitting the segment.
+                Some((current_owner, range))
+                    if *current_owner == owner
+                        && module.raw[range.end..cluster.start]
+                            .iter()
+                            .all(|byte| *byte == 0) =>
+                {
+                    range.end = cluster.end;
+                }
+                _ => {
+                    flush(&mut current);
+                    current = Some((owner, cluster));
+                }
+            }
+        }
+        flush(&mut current);
+    }
+    placement
+}
+
+/// The output module that owns each data symbol, or the main module for
+/// those that several reach.
+fn get_data_owners(
+    module: &InputModule,
+    program_info: &SplitProgramInfo,
+    relocations_in_range: &dyn Fn(&Range<usize>) -> Vec<RelocationEntry>,
+) -> HashMap<SymbolIndex, usize> {
+    let mut owners = HashMap::<SymbolIndex, usize>::new();
+    let mut claim = |symbol_index: SymbolIndex, owner: usize| {
+        let entry = owners.entry(symbol_index).or_insert(owner);
+        if *entry != owner {
+            *entry = 0;
+        }
+    };
+    for (owner, (_, info)) in program_info.output_modules.iter().enumerate() {
+        for dep in info.included_symbols.iter() {
+            if let DepNode::DataSymbol(symbol_index) = dep {
+                claim(*symbol_index, owner);
+            }
+        }
+    }
+
+    let symbol_ranges = module
+        .data_symbols
+        .iter()
+        .map(|symbol| (symbol.symbol_index, symbol.range.clone()))
+        .collect::<HashMap<_, _>>();
+    // Statics exported as the address held by a global, which JS may read
+    // at any time, stay in the main module.
+    for address in get_exported_addresses(module) {
+        for (segment, input_segment) in module.data_segments.iter().enumerate() {
+            let Some(segment_address) = movable_segment_address(module, segment) else {
+                continue;
+            };
+            let Some(offset) = address
+                .checked_sub(segment_address as usize)
+                .filter(|&offset| offset < input_segment.data.len())
+            else {
+                continue;
+            };
+            let data_start = input_segment.range.end - input_segment.data.len();
+            for symbol in module.data_symbols.iter() {
+                if symbol.range.contains(&(data_start + offset)) {
+                    claim(symbol.symbol_index, 0);
+                }
+            }
+        }
+    }
+    let referenced_data = |range: &Range<usize>| {
+        relocations_in_range(range)
+            .into_iter()
+            .filter(|relocation| relocation.ty != RelocationType::TypeIndexLeb)
+            .map(|relocation| relocation.index as SymbolIndex)
+            .filter(|&symbol_index| {
+                matches!(
+                    module.symbols.get(symbol_index),
+                    Some(SymbolInfo::Data { .. })
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    for (owner, (_, info)) in program_info.output_modules.iter().enumerate() {
+        let mut seen = HashSet::new();
+        let mut queue = info
+            .duplicated_funcs
+            .iter()
+            .filter_map(|&func_id| {
+                let defined = func_id.checked_sub(module.imported_funcs.len())?;
+                Some(module.defined_funcs[defined].body.range())
+            })
+            .flat_map(|range| referenced_data(&range))
+            .collect::<Vec<_>>();
+        while let Some(symbol_index) = queue.pop() {
+            if !seen.insert(symbol_index) {
+                continue;
+            }
+            claim(symbol_index, owner);
+            if let Some(range) = symbol_ranges.get(&symbol_index) {
+                queue.extend(referenced_data(range));
+            }
+        }
+    }
+    owners
+}
+
+/// Addresses held by the exported globals of `module`.
+fn get_exported_addresses(module: &InputModule) -> Vec<usize> {
+    let imported_globals = module
+        .imports
+        .iter()
+        .filter(|import| matches!(import.ty, wasmparser::TypeRef::Global(_)))
+        .count();
+    module
+        .exports
+        .iter()
+        .filter(|export| export.kind == wasmparser::ExternalKind::Global)
+        .filter_map(|export| {
+            let global = module
+                .globals
+                .get((export.index as usize).checked_sub(imported_globals)?)?;
+            Some(const_i32(&global.init_expr)? as usize)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_fixtures::split;
+
+    fn data_segments(module: &[u8]) -> Vec<Vec<u8>> {
+        wasmparser::Parser::new(0)
+            .parse_all(module)
+            .filter_map(|payload| match payload.unwrap() {
+                wasmparser::Payload::DataSection(reader) => Some(reader),
+                _ => None,
+            })
+            .flatten()
+            .map(|segment| segment.unwrap().data.to_vec())
+            .collect()
+    }
+
+    #[test]
+    fn moves_data_into_the_module_using_it() {
+        let output = split("no_std_app.wasm", &[]);
+        output.validate();
+        // `BONUS_TABLE` of the fixture.
+        let table = (0..=255).collect::<Vec<u8>>();
+        let contains_table = |segments: &[Vec<u8>]| {
+            segments
+                .iter()
+                .any(|data| data.windows(table.len()).any(|window| window == table))
+        };
+        assert!(contains_table(&data_segments(&output.read("bonus.wasm"))));
+        assert!(!contains_table(&data_segments(&output.read("main.wasm"))));
+        assert!(data_segments(&output.read("second.wasm")).is_empty());
+        if let Some(result) = output.run_no_std_app_export("run_optional", 3) {
+            assert_eq!(result, 2 * (3 + 100) + 27);
+        }
+    }
+
+    #[test]
+    fn keeps_the_tables_of_data_statics_in_their_module() {
+        let output = split("no_std_app.wasm", &[]);
+        output.validate();
+        // `SQUARES` of the fixture.
+        let table = (0..256u32)
+            .flat_map(|i| (i * i).to_le_bytes())
+            .collect::<Vec<u8>>();
+        let contains_table = |segments: &[Vec<u8>]| {
+            segments
+                .iter()
+                .any(|data| data.windows(table.len()).any(|window| window == table))
+        };
+        assert!(contains_table(&data_segments(&output.read("squares.wasm"))));
+        assert!(!contains_table(&data_segments(&output.read("main.wasm"))));
+        if let Some(result) = output.run_no_std_app_export("run_squares", 12) {
+            assert_eq!(result, 2 * 12 * 12);
+        }
+    }
+}
@@ -0,0 +1,337 @@
+//! Checks that code from crates listed in `deny-in-main` or `--deny-eager`,
+//! and the dependencies of split modules declared with
+//! `#[wasm_split(name, strict)]`, stay out of the main module.
+
+use std::collections::{BTreeMap, HashSet};
+
+use anyhow::{bail, Result};
+
+use crate::{
+    dep_graph::{DepGraph, DepNode},
+    metadata::SplitModuleMetadata,
+    read::{InputFuncId, InputModule},
+    split_point::{
+        find_reachable_deps, OutputModuleInfo, SplitModuleIdentifier, SplitPoint, SplitProgramInfo,
+    },
+    symbols::{demangle, Demangling},
+};
+
+/// Maximum number of offending functions listed per crate.
+const MAX_REPORTED_FUNCTIONS: usize = 5;
+
+/// Crates of the standard library and of the split runtime, whose code the
+/// main module needs anyway, so that strict split modules may share it.
+const RUNTIME_CRATES: &[&str] = &[
+    "core",
+    "alloc",
+    "std",
+    "compiler_builtins",
+    "dlmalloc",
+    "wasm_split",
+    "async_once_cell",
+    "wasm_bindgen",
+    "wasm_bindgen_futures",
+    "js_sys",
+    "web_sys",
+    "__rustc",
+];
+
+/// Returns the crate a demangled function path belongs to. For trait impls
+/// such as `<chrono::NaiveDate as core::fmt::Debug>::fmt`, this is the crate
+/// of the implementing type, unless it is a primitive type such as `[u8]` or
+/// `bool`, in which case it is the crate of the trait.
+pub fn get_crate_name(path: &str) -> &str {
+    let mut path = path;
+    loop {
+        let stripped = path
+            .trim_start_matches(['<', '&', '*', '['])
+            .trim_start_matches("mut ")
+            .trim_start_matches("const ")
+            .trim_start_matches("dyn ");
+        if stripped == path {
+            break;
+        }
+        path = stripped;
+    }
+    let end = path
+        .find(|c: char| !c.is_alphanumeric() && c != '_')
+        .unwrap_or(path.len());
+    if !path[end..].starts_with("::") {
+        if let Some(index) = path.find(" as ") {
+            return get_crate_name(&path[index + " as ".len()..]);
+        }
+    }
+    &path[..end]
+}
+
+pub fn format_node(module: &InputModule, node: &DepNode, demangling: Demangling) -> String {
+    match node {
+        DepNode::Function(index) => match module.names.functions.get(index) {
+            Some(name) => demangling.demangle(name),
+            None => format!("func[{index}]"),
+        },
+        DepNode::DataSymbol(index) => match module.symbols[*index] {
+            wasmparser::SymbolInfo::Data { name, .. } => {
+                format!("data {}", demangling.demangle(name))
+            }
+            symbol => format!("{symbol:?}"),
+        },
+    }
+}
+
+pub fn check_deny_in_main(
+    module: &InputModule,
+    program_info: &SplitProgramInfo,
+    denied_crates: &[String],
+    demangling: Demangling,
+) -> Result<()> {
+    if denied_crates.is_empty() {
+        return Ok(());
+    }
+    let denied_crates = denied_crates
+        .iter()
+        .map(|name| name.replace('-', "_"))
+        .collect::<Vec<_>>();
+    let Some((_, main)) = program_info
+        .output_modules
+        .iter()
+        .find(|(identifier, _)| *identifier == SplitModuleIdentifier::Main)
+    else {
+        return Ok(());
+    };
+
+    let mut offenders = BTreeMap::<&str, Vec<(usize, InputFuncId)>>::new();
+    for node in main.included_symbols.iter() {
+        let DepNode::Function(func_id) = *node else {
+            continue;
+        };
+        let Some(defined_index) = func_id.checked_sub(module.imported_funcs.len()) else {
+            continue;
+        };
+        let Some(name) = module.names.functions.get(&func_id) else {
+            continue;
+        };
+        let crate_name = get_crate_name(&demangle(name)).to_string();
+        if let Some(denied) = denied_crates.iter().find(|denied| **denied == crate_name) {
+            let size = module.defined_funcs[defined_index].body.range().len();
+            offenders
+                .entry(denied.as_str())
+                .or_default()
+                .push((size, func_id));
+        }
+    }
+    if offenders.is_empty() {
+        return Ok(());
+    }
+
+    let total = print_offenders(module, main, &mut offenders, demangling, |crate_name| {
+        format!("Crate {crate_name} is denied in the main module")
+    });
+    bail!(
+        "{total} functions from crates listed in deny-in-main or --deny-eager are in the main \
+         module: {}",
+        offenders.keys().copied().collect::<Vec<_>>().join(", ")
+    );
+}
+
+/// Prints the functions of `offenders` by crate, the largest first, each
+/// with the path from a root of the main module that keeps it there, and
+/// returns their number. `heading` says why a crate is listed.
+fn print_offenders(
+    module: &InputModule,
+    main: &OutputModuleInfo,
+    offenders: &mut BTreeMap<&str, Vec<(usize, InputFuncId)>>,
+    demangling: Demangling,
+    heading: impl Fn(&str) -> String,
+) -> usize {
+    let mut total = 0;
+    for (crate_name, funcs) in offenders.iter_mut() {
+        total += funcs.len();
+        funcs.sort_unstable_by(|a, b| b.cmp(a));
+        println!(
+            "{}, but {} of its functions ({} bytes) are in it:",
+            heading(crate_name),
+            funcs.len(),
+            funcs.iter().map(|(size, _)| size).sum::<usize>(),
+        );
+        for &(size, func_id) in funcs.iter().take(MAX_REPORTED_FUNCTIONS) {
+            let mut node = DepNode::Function(func_id);
+            println!(
+                "  {} ({size} bytes)",
+                format_node(module, &node, demangling)
+            );
+            while let Some(parent) = main.parents.get(&node) {
+                println!("      <== {}", format_node(module, parent, demangling));
+                node = *parent;
+            }
+        }
+        if funcs.len() > MAX_REPORTED_FUNCTIONS {
+            println!("  ... and {} more", funcs.len() - MAX_REPORTED_FUNCTIONS);
+        }
+    }
+    total
+}
+
+/// Checks that no code of the dependencies of a split module declared with
+/// `#[wasm_split(name, strict)]` is in the main module, where eager code
+/// that also calls it would have anchored it. The dependencies are the
+/// crates of the code that the module's split functions reach, other than
+/// their own crate and [`RUNTIME_CRATES`], which every main module needs.
+/// The split functions of folded modules are part of the main module, but
+/// their dependencies are checked all the same.
+pub fn check_strict_modules(
+    module: &InputModule,
+    dep_graph: &DepGraph,
+    split_points: &[SplitPoint],
+    metadata: &SplitModuleMetadata,
+    program_info: &SplitProgramInfo,
+    demangling: Demangling,
+) -> Result<()> {
+    let Some((_, main)) = program_info
+        .output_modules
+        .iter()
+        .find(|(identifier, _)| *identifier == SplitModuleIdentifier::Main)
+    else {
+        return Ok(());
+    };
+    // Of functions with Rust paths, rather than C symbols such as `memcmp`
+    // or the exports of split functions.
+    let crate_of = |node: &DepNode| {
+        let DepNode::Function(func_id) = node else {
+            return None;
+        };
+        let name = demangle(module.names.functions.get(func_id)?);
+        name.contains("::")
+            .then(|| get_crate_name(&name).to_string())
+    };
+    // Loading a chunk reaches the dependencies of the split runtime, which
+    // are none of the module's.
+    let runtime = dep_graph
+        .keys()
+        .filter(|node| crate_of(node).is_some_and(|crate_name| crate_name == "wasm_split"))
+        .copied()
+        .collect::<HashSet<_>>();
+    let mut failed = Vec::new();
+    for (module_name, attributes) in metadata
+        .iter()
+        .filter(|(_, attributes)| !attributes.strict.is_empty())
+    {
+        let roots = split_points
+            .iter()
+            .filter(|split_point| split_point.module_name == *module_name)
+            .map(|split_point| DepNode::Function(split_point.export_func))
+            .collect::<HashSet<_>>();
+        let reachable = find_reachable_deps(dep_graph, &roots, &runtime).reachable;
+
+        let mut captured = BTreeMap::<String, Vec<(usize, InputFuncId)>>::new();
+        for node in reachable
+            .iter()
+            .filter(|node| main.included_symbols.contains(node))
+        {
+            let DepNode::Function(func_id) = *node else {
+                continue;
+            };
+            let Some(defined_index) = func_id.checked_sub(module.imported_funcs.len()) else {
+                continue;
+            };
+            let Some(crate_name) = crate_of(node) else {
+                continue;
+            };
+            if attributes.strict.contains(&crate_name)
+                || RUNTIME_CRATES.contains(&crate_name.as_str())
+            {
+                continue;
+            }
+            let size = module.defined_funcs[defined_index].body.range().len();
+            captured
+                .entry(crate_name)
+                .or_default()
+                .push((size, func_id));
+        }
+        if captured.is_empty() {
+            continue;
+        }
+        let mut offenders = captured
+            .iter()
+            .map(|(crate_name, funcs)| (crate_name.as_str(), funcs.clone()))
+            .collect();
+        print_offenders(module, main, &mut offenders, demangling, |crate_name| {
+            format!("Strict split module {module_name} depends on crate {crate_name}")
+        });
+        failed.push(format!(
+            "{module_name} ({})",
+            captured.keys().cloned().collect::<Vec<_>>().join(", ")
+        ));
+    }
+    if !failed.is_empty() {
+        bail!(
+            "Eager code anchors dependencies of strict split modules in the main module: {}",
+            failed.join(", ")
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check_strict_modules;
+    use crate::{
+        dep_graph::get_dependencies,
+        metadata::{get_split_module_metadata, SplitModuleMetadata},
+        read::InputModule,
+        split_point::{compute_split_modules, get_split_points, ChunkingOptions},
+        symbols::Demangling,
+        test_fixtures::{fixture_path, try_split},
+    };
+
+    #[test]
+    fn denied_crate_in_main_writes_no_output() {
+        let (output, result) =
+            try_split("no_std_app.wasm", "deny-in-main = [\"no_std_app\"]\n", &[]);
+        let err = result.expect_err("`run` and `poll` are in the main module");
+        assert!(format!("{err:#}").contains("no_std_app"), "{err:#}");
+        assert!(!output.dir.exists());
+    }
+
+    #[test]
+    fn denied_eager_crate_fails_the_split() {
+        let (output, result) = try_split("no_std_app.wasm", "", &["--deny-eager", "checksum"]);
+        let err = result.expect_err("`run_checksum` calls `checksum` eagerly");
+        assert!(format!("{err:#}").contains("checksum"), "{err:#}");
+        assert!(!output.dir.exists());
+    }
+
+    #[test]
+    fn strict_module_fails_when_eager_code_anchors_its_dependencies() {
+        let wasm = std::fs::read(fixture_path("no_std_app.wasm")).unwrap();
+        let module = InputModule::parse(&wasm).unwrap();
+        let split_points = get_split_points(&module).unwrap();
+        let dep_graph = get_dependencies(&module).unwrap();
+        let mut metadata = get_split_module_metadata(&module).unwrap();
+        let program_info = compute_split_modules(
+            &module,
+            &dep_graph,
+            &split_points,
+            &[],
+            &ChunkingOptions::default(),
+        )
+        .unwrap();
+        let check = |metadata: &SplitModuleMetadata| {
+            check_strict_modules(
+                &module,
+                &dep_graph,
+                &split_points,
+                metadata,
+                &program_info,
+                Demangling::Full,
+            )
+        };
+        // Declared `strict` in the fixture, and only calling its own crate.
+        assert_eq!(metadata["details"].strict, ["no_std_app"]);
+        check(&metadata).unwrap();
+
+        metadata.entry("verify".to_string()).or_default().strict = vec!["no_std_app".to_string()];
+        let err = check(&metadata).expect_err("`run_checksum` also calls `checksum`");
+        assert!(format!("{err:#}").contains("verify (checksum)"), "{err:#}");
+    }
+}
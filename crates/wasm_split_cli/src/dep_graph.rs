@@ -5,9 +5,12 @@ use std::{
     ops::Range,
 };
 
-use anyhow::{anyhow, bail, Context};
+use anyhow::{bail, Context};
 
-use crate::read::{InputFuncId, InputModule, SymbolIndex};
+use crate::{
+    emit::is_indirect_function_reloc,
+    read::{InputFuncId, InputModule, SymbolIndex},
+};
 
 #[derive(Debug, PartialEq, Eq, Hash, Copy, PartialOrd, Ord, Clone)]
 pub enum DepNode {
@@ -34,6 +37,21 @@ impl<'a> SymbolTable for InputModule<'a> {
 }
 
 pub fn get_dependencies(module: &InputModule) -> anyhow::Result<DepGraph> {
+    get_dependencies_with(module, true)
+}
+
+/// As [`get_dependencies`], without the functions whose address is taken,
+/// e.g. by a `fn` pointer or a vtable, rather than called directly. Calls
+/// through those go through a table slot, which with
+/// `--lazy-indirect-calls` loads the chunk owning the function if needed.
+pub fn get_call_dependencies(module: &InputModule) -> anyhow::Result<DepGraph> {
+    get_dependencies_with(module, false)
+}
+
+fn get_dependencies_with(
+    module: &InputModule,
+    include_addresses: bool,
+) -> anyhow::Result<DepGraph> {
     let mut deps = DepGraph::new();
     let mut add_dep = |a: DepNode, b: u32| {
         if let Some(target) = module.get_symbol_dep_node(b as usize) {
@@ -51,6 +69,13 @@ pub fn get_dependencies(module: &InputModule) -> anyhow::Result<DepGraph> {
                 shift_range(entry.relocation_range(), module.code_section_offset),
             )
             .with_context(|| format!("Invalid relocation entry {entry:?}"))?;
+            // The index of the type of an indirect call is not that of a
+            // symbol.
+            if entry.ty == wasmparser::RelocationType::TypeIndexLeb
+                || (!include_addresses && is_indirect_function_reloc(entry.ty))
+            {
+                continue;
+            }
             add_dep(DepNode::Function(func_index), entry.index);
         }
     }
@@ -62,12 +87,49 @@ pub fn get_dependencies(module: &InputModule) -> anyhow::Result<DepGraph> {
                 shift_range(entry.relocation_range(), module.data_section_offset),
             )
             .with_context(|| format!("Invalid relocation entry {entry:?}"))?;
+            if !include_addresses && is_indirect_function_reloc(entry.ty) {
+                continue;
+            }
             add_dep(DepNode::DataSymbol(symbol_index), entry.index);
         }
     }
     Ok(deps)
 }
 
+/// Calls and function pointers in the code of each function, which leaves
+/// out the data and other symbols of [`get_dependencies`].
+pub fn get_function_references(module: &InputModule) -> anyhow::Result<DepGraph> {
+    use wasmparser::RelocationType::*;
+
+    let mut references = DepGraph::new();
+    let Some(relocs) = module.relocs.get(&module.code_section_index) else {
+        return Ok(references);
+    };
+    for entry in relocs {
+        if !matches!(
+            entry.ty,
+            FunctionIndexLeb | TableIndexSleb | TableIndexSleb64 | TableIndexRelSleb
+        ) {
+            continue;
+        }
+        let range = entry.relocation_range();
+        let func_index = find_function_containing_range(
+            module,
+            (range.start + module.code_section_offset)..(range.end + module.code_section_offset),
+        )
+        .with_context(|| format!("Invalid relocation entry {entry:?}"))?;
+        if let Some(target @ DepNode::Function(_)) =
+            module.get_symbol_dep_node(entry.index as usize)
+        {
+            references
+                .entry(DepNode::Function(func_index))
+                .or_default()
+                .insert(target);
+        }
+    }
+    Ok(references)
+}
+
 fn find_function_containing_range(
     module: &crate::read::InputModule,
     range: Range<usize>,
@@ -0,0 +1,188 @@
+//! `wasm-split diff-chunk`: compares two versions of a chunk function by
+//! function, to find the code responsible when a chunk grows between builds.
+//!
+//! Functions are matched by their demangled name from the name section, which
+//! `wasm-split` writes to every chunk it emits, since their indices shift with
+//! any change. Functions without a name are counted together, and so are
+//! functions of the same name, such as instances of a generic function that
+//! only differ in their (hidden) hash. The unique identifiers in the names
+//! of the functions generated by `#[wasm_split]` are left out, as they change
+//! whenever the annotated function moves in its source file. Functions are
+//! matched by their full names even if `--demangle compact` shortens them
+//! for display.
+
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::{Context, Result};
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::{
+    config::ByteSize,
+    read::InputModule,
+    symbols::{demangle, Demangling},
+};
+
+const UNNAMED: &str = "<unnamed functions>";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionChange {
+    pub name: String,
+    /// Size of the function's code in the old chunk, or `None` if added.
+    pub old_size: Option<usize>,
+    /// Size of the function's code in the new chunk, or `None` if removed.
+    pub new_size: Option<usize>,
+}
+
+impl FunctionChange {
+    pub fn delta(&self) -> isize {
+        self.new_size.unwrap_or_default() as isize - self.old_size.unwrap_or_default() as isize
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ChunkDiff {
+    /// Functions that were added, removed or resized, largest change first.
+    pub changes: Vec<FunctionChange>,
+    pub old_code_size: usize,
+    pub new_code_size: usize,
+}
+
+fn normalize_name(name: &str) -> String {
+    lazy_static! {
+        static ref UNIQUE_ID: Regex =
+            Regex::new("^(__wasm_split_00.*00_[a-z]+_)[0-9a-f]{32}_").unwrap();
+    }
+    UNIQUE_ID.replace(&demangle(name), "$1").into_owned()
+}
+
+/// Code size of every function of a chunk, by demangled name.
+fn get_function_sizes(module: &InputModule) -> BTreeMap<String, usize> {
+    let imported = module.imported_funcs.len();
+    let mut sizes = BTreeMap::new();
+    for (index, func) in module.defined_funcs.iter().enumerate() {
+        let name = match module.names.functions.get(&(imported + index)) {
+            Some(name) => normalize_name(name),
+            None => UNNAMED.to_string(),
+        };
+        *sizes.entry(name).or_default() += func.body.range().len();
+    }
+    sizes
+}
+
+impl ChunkDiff {
+    pub fn new(old: &InputModule, new: &InputModule) -> Self {
+        let old_sizes = get_function_sizes(old);
+        let new_sizes = get_function_sizes(new);
+        let mut changes = old_sizes
+            .keys()
+            .chain(
+                new_sizes
+                    .keys()
+                    .filter(|name| !old_sizes.contains_key(*name)),
+            )
+            .map(|name| FunctionChange {
+                name: name.clone(),
+                old_size: old_sizes.get(name).copied(),
+                new_size: new_sizes.get(name).copied(),
+            })
+            .filter(|change| change.old_size != change.new_size)
+            .collect::<Vec<_>>();
+        changes.sort_by(|a, b| {
+            b.delta()
+                .unsigned_abs()
+                .cmp(&a.delta().unsigned_abs())
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        Self {
+            changes,
+            old_code_size: old_sizes.values().sum(),
+            new_code_size: new_sizes.values().sum(),
+        }
+    }
+}
+
+fn signed_size(delta: isize) -> String {
+    let sign = if delta < 0 { '-' } else { '+' };
+    format!("{sign}{}", ByteSize(delta.unsigned_abs()))
+}
+
+fn read_chunk(path: &Path) -> Result<Vec<u8>> {
+    std::fs::read(path).with_context(|| format!("Failed to read chunk {path:?}"))
+}
+
+pub fn run(old: &Path, new: &Path, demangling: Demangling) -> Result<()> {
+    let old_wasm = read_chunk(old)?;
+    let new_wasm = read_chunk(new)?;
+    let old_module =
+        InputModule::parse(&old_wasm).with_context(|| format!("Failed to parse {old:?}"))?;
+    let new_module =
+        InputModule::parse(&new_wasm).with_context(|| format!("Failed to parse {new:?}"))?;
+    for (path, module) in [(old, &old_module), (new, &new_module)] {
+        if module.names.functions.is_empty() {
+            eprintln!(
+                "Warning: {path:?} has no name section, so its functions cannot be matched by \
+                 name. Chunks of wasm-split keep the names of the input; run wasm-bindgen \
+                 without --remove-name-section."
+            );
+        }
+    }
+    let diff = ChunkDiff::new(&old_module, &new_module);
+    for change in diff.changes.iter() {
+        let kind = match (change.old_size, change.new_size) {
+            (None, _) => "added".to_string(),
+            (_, None) => "removed".to_string(),
+            (Some(old_size), Some(new_size)) => {
+                format!("{} -> {}", ByteSize(old_size), ByteSize(new_size))
+            }
+        };
+        println!(
+            "{:>11}  {kind:<21}  {}",
+            signed_size(change.delta()),
+            demangling.demangle(&change.name)
+        );
+    }
+    println!(
+        "Code size {} -> {} ({}), {} functions changed",
+        ByteSize(diff.old_code_size),
+        ByteSize(diff.new_code_size),
+        signed_size(diff.new_code_size as isize - diff.old_code_size as isize),
+        diff.changes.len()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::split;
+
+    #[test]
+    fn lists_functions_of_other_chunk_as_changed() {
+        let output = split("no_std_app.wasm", &[]);
+        let (first, second) = (output.read("first.wasm"), output.read("second.wasm"));
+        let (first, second) = (
+            InputModule::parse(&first).unwrap(),
+            InputModule::parse(&second).unwrap(),
+        );
+
+        assert!(ChunkDiff::new(&first, &first).changes.is_empty());
+
+        let diff = ChunkDiff::new(&first, &second);
+        let change = |suffix: &str| {
+            diff.changes
+                .iter()
+                .find(|change| change.name.ends_with(suffix))
+                .unwrap_or_else(|| panic!("no change of {suffix} in {:?}", diff.changes))
+        };
+        assert_eq!(change("00_export_first").new_size, None);
+        assert_eq!(change("00_export_second").old_size, None);
+        assert_eq!(
+            diff.new_code_size as isize - diff.old_code_size as isize,
+            diff.changes
+                .iter()
+                .map(FunctionChange::delta)
+                .sum::<isize>()
+        );
+    }
+}
@@ -1,24 +1,40 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     convert::identity,
     ops::Range,
 };
 
 use crate::{
+    cache::{self, Cache},
+    compress::Encoding,
+    custom_sections::{self, Placement, PRODUCERS_SECTION},
+    data::{movable_segment_address, MovedData},
     dep_graph::DepNode,
-    read::{ImportId, InputFuncId, InputModule, SymbolIndex},
-    split_point::{OutputModuleInfo, SplitProgramInfo},
+    features::{self, Feature},
+    manifest::{content_hash, gzip_size, sha256_hex, sri_hash},
+    read::{DataSegmentId, GlobalId, InputFuncId, InputModule, TableId},
+    signatures::{self, Signatures},
+    split_point::{OutputModuleInfo, SplitModuleIdentifier, SplitProgramInfo},
+    toolchain::WASM_SPLIT_JS_MODULE,
 };
 use anyhow::{anyhow, bail, Context, Result};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use wasmparser::{DataKind, RelocationEntry, RelocationType, SymbolInfo};
 
-fn is_indirect_function_reloc(ty: RelocationType) -> bool {
+/// Whether a relocation is of the table slot of a function, i.e. takes its
+/// address rather than calling it.
+pub fn is_indirect_function_reloc(ty: RelocationType) -> bool {
     use RelocationType::*;
-    match ty {
-        TableIndexSleb | TableIndexI32 | TableIndexRelSleb | TableIndexSleb64 | TableIndexI64
-        | TableIndexRelSleb64 => true,
-        _ => false,
-    }
+    matches!(
+        ty,
+        TableIndexSleb
+            | TableIndexI32
+            | TableIndexRelSleb
+            | TableIndexSleb64
+            | TableIndexI64
+            | TableIndexRelSleb64
+    )
 }
 
 fn get_indirect_functions(module: &InputModule) -> Result<HashSet<InputFuncId>> {
@@ -42,6 +58,27 @@ fn get_indirect_functions(module: &InputModule) -> Result<HashSet<InputFuncId>>
     Ok(funcs)
 }
 
+/// Export of the `wasm_split` runtime, with its `guard-calls` feature, that
+/// guarded cross-chunk calls call instead if their target is not loaded.
+pub const GUARD_FAULT_EXPORT: &str = "__wasm_split_guard_fault";
+
+/// The function exported as [`GUARD_FAULT_EXPORT`], for `--guard-calls`.
+pub fn get_guard_fault_func(module: &InputModule) -> Result<InputFuncId> {
+    module
+        .exports
+        .iter()
+        .find(|export| {
+            export.name == GUARD_FAULT_EXPORT && export.kind == wasmparser::ExternalKind::Func
+        })
+        .map(|export| export.index as InputFuncId)
+        .ok_or_else(|| {
+            anyhow!(
+                "--guard-calls requires the input to export {GUARD_FAULT_EXPORT}; enable the \
+                 `guard-calls` feature of wasm_split"
+            )
+        })
+}
+
 #[derive(Debug)]
 struct EmitState {
     indirect_functions: IndirectFunctionEmitInfo,
@@ -49,13 +86,78 @@ struct EmitState {
     // the file rather than the start of the section.
     all_relocations: Vec<RelocationEntry>,
 
-    // Imports (corresponding to split points) to exclude from all modules.
-    split_point_imports: HashSet<ImportId>,
+    // Empty slots at the end of the table, for callbacks registered at
+    // runtime.
+    reserved_table_slots: Range<usize>,
+
+    // Maximum of the table, which is growable if the input's is; see
+    // `limits::table_maximum`.
+    table_maximum: Option<u32>,
+
+    // The input's indirect function table, which is table 0 of every output
+    // module, followed by the other tables of the input in order.
+    function_table: TableId,
+
+    // Table slot and type of the runtime's guard fault handler, with
+    // `--guard-calls`.
+    guard_fault: Option<(usize, usize)>,
+
+    // Whether the main module fills the slots of the other modules with
+    // stubs that load them, with `--lazy-indirect-calls`.
+    lazy_indirect_calls: bool,
+
+    // Whether calls of indirect stubs are replaced by the `call_indirect`
+    // that the stub makes; see `ModuleEmitState::inline_indirect_stub_calls`.
+    inline_indirect_stub_calls: bool,
+
+    // Number of globals imported by the input, which precede the defined
+    // ones in the global index space.
+    num_imported_globals: usize,
+
+    // Defined globals that the code of each output module refers to. Other
+    // modules import exactly these from the main module, which exports the
+    // union of them, rather than every global the input defines.
+    referenced_globals: Vec<BTreeSet<GlobalId>>,
+
+    // Read-only data that each output module initializes instead of the
+    // main module; see `data::place_data`.
+    moved_data: Vec<Vec<MovedData>>,
 }
 
 impl EmitState {
-    fn new(module: &InputModule, program_info: &SplitProgramInfo) -> Result<Self> {
-        let indirect_functions = IndirectFunctionEmitInfo::new(module, program_info)?;
+    fn new(
+        module: &InputModule,
+        program_info: &SplitProgramInfo,
+        table: &TableOptions,
+    ) -> Result<Self> {
+        let TableOptions {
+            reserved_slots: reserved_table_slots,
+            guard_fault_func,
+            lazy_indirect_calls,
+        } = *table;
+        let indirect_functions =
+            IndirectFunctionEmitInfo::new(module, program_info, guard_fault_func)?;
+        let guard_fault = guard_fault_func.map(|func_id| {
+            (
+                indirect_functions.function_table_index[&func_id],
+                module.func_type_id(func_id),
+            )
+        });
+        // + 1 due to empty entry at index 0
+        let reserved_start = indirect_functions.table_entries.len() + 1;
+        let reserved_table_slots = reserved_start..reserved_start + reserved_table_slots;
+        if module
+            .imports
+            .iter()
+            .any(|import| matches!(import.ty, wasmparser::TypeRef::Table(_)))
+        {
+            bail!("Splitting requires the tables to be defined by the input module, not imported");
+        }
+        let function_table = module.function_table();
+        let table_maximum = crate::limits::table_maximum(
+            module.tables.get(function_table).map(|table| &table.ty),
+            reserved_table_slots.end as u32,
+        )?;
         let mut all_relocations = Vec::<RelocationEntry>::new();
         for (section_index, section_offset) in [
             (module.code_section_index, module.code_section_offset),
@@ -65,7 +167,7 @@ impl EmitState {
                 continue;
             };
             for reloc in section_relocs {
-                let mut reloc = reloc.clone();
+                let mut reloc = *reloc;
                 reloc.offset =
                     reloc
                         .offset
@@ -79,50 +181,99 @@ impl EmitState {
             }
         }
         all_relocations.sort_by_key(|reloc| reloc.offset);
-        let mut split_point_imports = HashSet::<InputFuncId>::new();
-        for (_, output_module) in program_info.output_modules.iter() {
-            for split_point in output_module.split_points.iter() {
-                split_point_imports.insert(split_point.import);
-            }
-        }
-        Ok(EmitState {
+        let num_imported_globals = module
+            .imports
+            .iter()
+            .filter(|import| matches!(import.ty, wasmparser::TypeRef::Global(_)))
+            .count();
+        // Guarded calls check the slot in the stub, and moving code within a
+        // function would leave DWARF addresses past the call wrong.
+        let inline_indirect_stub_calls = guard_fault.is_none()
+            && !module
+                .custom_sections
+                .iter()
+                .any(|custom| custom.name.starts_with(".debug_"));
+        let mut emit_state = EmitState {
             indirect_functions,
             all_relocations,
-            split_point_imports,
-        })
+            reserved_table_slots,
+            table_maximum,
+            function_table,
+            guard_fault,
+            lazy_indirect_calls,
+            inline_indirect_stub_calls,
+            num_imported_globals,
+            referenced_globals: Vec::new(),
+            moved_data: Vec::new(),
+        };
+        emit_state.referenced_globals = program_info
+            .output_modules
+            .iter()
+            .map(|(_, info)| emit_state.get_referenced_globals(module, info))
+            .collect();
+        emit_state.moved_data = crate::data::place_data(module, program_info, &|range| {
+            emit_state.get_relocations_for_range(range).to_vec()
+        });
+        Ok(emit_state)
+    }
+
+    fn get_referenced_globals(
+        &self,
+        module: &InputModule,
+        info: &OutputModuleInfo,
+    ) -> BTreeSet<GlobalId> {
+        info.included_symbols
+            .iter()
+            .filter_map(|dep| match *dep {
+                DepNode::Function(func_id) if func_id >= module.imported_funcs.len() => {
+                    Some(&module.defined_funcs[func_id - module.imported_funcs.len()])
+                }
+                _ => None,
+            })
+            .flat_map(|func| self.get_relocations_for_range(&func.body.range()))
+            .filter(|reloc| reloc.ty == RelocationType::GlobalIndexLeb)
+            .filter_map(|reloc| match module.symbols.get(reloc.index as usize) {
+                Some(SymbolInfo::Global { index, .. }) => Some(*index as GlobalId),
+                _ => None,
+            })
+            .filter(|&global_id| global_id >= self.num_imported_globals)
+            .collect()
+    }
+
+    fn get_output_table_index(&self, table_id: TableId) -> u32 {
+        match table_id {
+            _ if table_id == self.function_table => 0,
+            _ if table_id < self.function_table => table_id as u32 + 1,
+            _ => table_id as u32,
+        }
     }
 
     fn get_relocations_for_range(&self, range: &Range<usize>) -> &[RelocationEntry] {
         let start = self
             .all_relocations
             .binary_search_by_key(&range.start, |reloc| reloc.offset as usize)
-            .map_or_else(identity, identity);
+            .unwrap_or_else(identity);
         let end = self
             .all_relocations
             .binary_search_by_key(&range.end, |reloc| reloc.offset as usize)
-            .map_or_else(identity, identity);
+            .unwrap_or_else(identity);
         &self.all_relocations[start..end]
     }
 }
 
-fn emit_main_module(
-    module_info: &InputModule,
-    program_info: &SplitProgramInfo,
-    emit_state: &EmitState,
-) -> anyhow::Result<()> {
-    // // Currently it is not possible to clone an existing `walrus::Module`.
-    // // Therefore, we simply reparse the wasm.
-    // let mut new_module = walrus::Module::from_buffer(module_info.wasm)?;
-
-    // // Modify indirect function table.
-    // let table = new_module
-    //     .tables
-    //     .get_mut(emit_state.indirect_function_table_id);
-    // if let Some(mut max) = table.maximum {
-    //     max = max.max(emit_state.max_indirect_function_index);
-    // };
-
-    Ok(())
+struct OutputDataSegment {
+    // Input segment that the data is part of.
+    segment: DataSegmentId,
+    mode: OutputDataMode,
+    range: Range<usize>,
+}
+
+enum OutputDataMode {
+    Passive,
+    Active {
+        memory_index: u32,
+        offset: wasm_encoder::ConstExpr,
+    },
 }
 
 #[derive(Debug, Default)]
@@ -130,13 +281,23 @@ struct IndirectFunctionEmitInfo {
     table_entries: Vec<InputFuncId>,
     function_table_index: HashMap<InputFuncId, usize>,
     table_range_for_output_module: Vec<Range<usize>>,
+    /// Functions whose address the input takes, but which no output module
+    /// defines, so that they have no slot and their address is null.
+    dropped_functions: HashSet<InputFuncId>,
 }
 
 impl IndirectFunctionEmitInfo {
-    fn new(module: &InputModule, program_info: &SplitProgramInfo) -> Result<Self> {
+    fn new(
+        module: &InputModule,
+        program_info: &SplitProgramInfo,
+        guard_fault_func: Option<InputFuncId>,
+    ) -> Result<Self> {
         let mut indirect_functions = get_indirect_functions(module)?;
 
         indirect_functions.extend(program_info.shared_funcs.iter());
+        // Guards in other modules call the fault handler of the main module
+        // through the table, like any function they share.
+        indirect_functions.extend(guard_fault_func);
 
         // Remove all split point imports. These are placeholders. Any
         // references to these functions will be replaced by a reference to the
@@ -147,12 +308,47 @@ impl IndirectFunctionEmitInfo {
             }
         }
 
+        // Shake out the slots of functions that no output module defines, such
+        // as those only referenced by data that no code uses, which the input
+        // keeps if it was linked without `--gc-sections`. Their slots would
+        // stay empty anyway, so references to them become null instead.
+        let emitted_functions: HashSet<InputFuncId> = program_info
+            .output_modules
+            .iter()
+            .flat_map(|(_, info)| info.included_symbols.iter())
+            .filter_map(|dep| match *dep {
+                DepNode::Function(func_id) => Some(func_id),
+                _ => None,
+            })
+            .collect();
+        let dropped_functions: HashSet<InputFuncId> = indirect_functions
+            .difference(&emitted_functions)
+            .copied()
+            .collect();
+        indirect_functions.retain(|func_id| emitted_functions.contains(func_id));
+        if !dropped_functions.is_empty() {
+            println!(
+                "Dropped {} table slots of functions that no module defines",
+                dropped_functions.len()
+            );
+        }
+
+        // Slots are grouped by output module, so that each module can fill in
+        // its own functions with a single element segment, and ordered by
+        // symbol name within each module rather than by function index. This
+        // keeps the layout identical across builds with the same set of
+        // symbols, even if the linker orders their definitions differently.
         let mut table_entries: Vec<_> = indirect_functions.into_iter().collect();
         table_entries.sort_by_key(|&func_id| {
             (
                 program_info
                     .symbol_output_module
-                    .get(&DepNode::Function(func_id)),
+                    .get(&DepNode::Function(func_id))
+                    .map(|&output_module_index| {
+                        &program_info.output_modules[output_module_index].0
+                    }),
+                !module.names.functions.contains_key(&func_id),
+                module.names.functions.get(&func_id),
                 func_id,
             )
         });
@@ -186,47 +382,38 @@ impl IndirectFunctionEmitInfo {
             table_entries,
             function_table_index,
             table_range_for_output_module,
+            dropped_functions,
         })
     }
 }
 
 fn encode_leb128_u32_5byte(mut value: u32, buf: &mut [u8; 5]) {
-    for i in 0..5 {
-        buf[i] = (value as u8) & 0x7f;
+    for byte in buf.iter_mut() {
+        *byte = (value as u8) & 0x7f;
         value >>= 7;
     }
-    for i in 0..4 {
-        buf[i] |= 0x80;
+    for byte in buf[..4].iter_mut() {
+        *byte |= 0x80;
     }
 }
 
 fn encode_leb128_i32_5byte(mut value: i32, buf: &mut [u8; 5]) {
-    for i in 0..5 {
-        buf[i] = (value as u8) & 0x7f;
+    for byte in buf.iter_mut() {
+        *byte = (value as u8) & 0x7f;
         value >>= 7;
     }
-    for i in 0..4 {
-        buf[i] |= 0x80;
-    }
-}
-
-fn encode_leb128_u64_10byte(mut value: u64, buf: &mut [u8; 10]) {
-    for i in 0..10 {
-        buf[i] = (value as u8) & 0x7f;
-        value >>= 7;
-    }
-    for i in 0..9 {
-        buf[i] |= 0x80;
+    for byte in buf[..4].iter_mut() {
+        *byte |= 0x80;
     }
 }
 
 fn encode_leb128_i64_10byte(mut value: i64, buf: &mut [u8; 10]) {
-    for i in 0..10 {
-        buf[i] = (value as u8) & 0x7f;
+    for byte in buf.iter_mut() {
+        *byte = (value as u8) & 0x7f;
         value >>= 7;
     }
-    for i in 0..9 {
-        buf[i] |= 0x80;
+    for byte in buf[..9].iter_mut() {
+        *byte |= 0x80;
     }
 }
 
@@ -238,21 +425,56 @@ fn encode_u64(value: u64, buf: &mut [u8; 8]) {
     *buf = value.to_le_bytes();
 }
 
-fn encode_i32(value: i32, buf: &mut [u8; 4]) {
-    *buf = value.to_le_bytes();
+fn leb128_len(mut value: usize) -> usize {
+    let mut len = 1;
+    while value >= 0x80 {
+        value >>= 7;
+        len += 1;
+    }
+    len
 }
 
-fn encode_i64(value: i64, buf: &mut [u8; 8]) {
-    *buf = value.to_le_bytes();
+/// Address that DWARF sections of an output module give the code of functions
+/// that another module defines, as wasm-ld does for functions that it drops:
+/// -1 ends a range list or location list, so those use -2.
+fn dwarf_tombstone(section_name: &str) -> u64 {
+    match section_name {
+        ".debug_ranges" | ".debug_loc" => u64::MAX - 1,
+        _ => u64::MAX,
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Copy)]
 enum OutputFunctionKind {
+    /// Import of [`LOAD_SLOT_IMPORT`] from the loader, which the
+    /// [`OutputFunctionKind::LazyStub`]s of the main module call. It has no
+    /// input function, so its `input_func_id` is `InputFuncId::MAX`.
+    SlotLoader,
     Import,
     Defined,
     IndirectStub,
+    /// Function in the main module that calls an import, or a function that
+    /// wasm-bindgen replaces by an import, on behalf of other modules, and
+    /// takes its place in the table.
+    ///
+    /// wasm-bindgen changes the signature of imports that take or return JS
+    /// values to use `externref`, and likewise replaces the functions that
+    /// convert Rust closures to JS functions. It adapts direct calls to them,
+    /// but not table entries, so other modules would otherwise fail the
+    /// signature check of `call_indirect`.
+    Forwarder,
+    /// Function in the main module that fills the table slot of a function
+    /// of another module until that module is loaded, with
+    /// `--lazy-indirect-calls`. It has the loader load the module, which
+    /// replaces it in the table, and then calls the slot again, so that
+    /// calls through `fn` pointers and vtables never find the slot empty.
+    LazyStub,
 }
 
+/// Loader function that loads the module owning a table slot synchronously,
+/// and returns whether it filled the slot.
+pub const LOAD_SLOT_IMPORT: &str = "__wasm_split_load_slot";
+
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 struct OutputFunction {
     kind: OutputFunctionKind,
@@ -264,11 +486,29 @@ struct ModuleEmitState<'a> {
     output_module_index: usize,
     output_module_info: &'a OutputModuleInfo,
     emit_state: &'a EmitState,
-    program_info: &'a SplitProgramInfo,
     output_module: wasm_encoder::Module,
     output_functions: Vec<OutputFunction>,
     input_function_output_id: HashMap<InputFuncId, usize>,
+    /// Output function index of the [`OutputFunctionKind::Forwarder`] for each
+    /// input function that has one.
+    forwarder_output_id: HashMap<InputFuncId, usize>,
+    /// Output function index of the [`OutputFunctionKind::LazyStub`] for the
+    /// table slot of each function of another module, in the main module.
+    lazy_stub_output_id: HashMap<InputFuncId, usize>,
+    /// Output global index of each defined global imported from the main
+    /// module, for modules other than the main one.
+    global_output_id: HashMap<GlobalId, u32>,
     indirect_function_table_range: Range<usize>,
+    /// Import module to use instead of [`WASM_SPLIT_JS_MODULE`].
+    loader_module: &'a str,
+    /// Name of the module in the name section: the chunk name for split
+    /// modules, by which engines name modules compiled from bytes in stack
+    /// traces, and the input's for the main module.
+    module_name: Option<String>,
+    /// Offset of the body of each defined function from the start of the
+    /// contents of the code section, by which DWARF addresses code. Filled
+    /// in by `generate_code_section`.
+    function_code_offsets: HashMap<InputFuncId, u64>,
 }
 
 impl<'a> ModuleEmitState<'a> {
@@ -277,6 +517,7 @@ impl<'a> ModuleEmitState<'a> {
         emit_state: &'a EmitState,
         output_module_index: usize,
         program_info: &'a crate::split_point::SplitProgramInfo,
+        loader_module: &'a str,
     ) -> Self {
         let output_module_info = &program_info.output_modules[output_module_index].1;
         // We need to include definitions for all of the `included_symbols`.
@@ -316,15 +557,71 @@ impl<'a> ModuleEmitState<'a> {
             })
             .collect();
 
-        output_functions.sort();
+        if output_module_index == 0 {
+            output_functions.extend(
+                program_info
+                    .shared_funcs
+                    .iter()
+                    .filter(|&func_id| {
+                        *func_id < module.imported_funcs.len()
+                            || program_info.wasm_bindgen_funcs.contains(func_id)
+                    })
+                    .map(|&input_func_id| OutputFunction {
+                        kind: OutputFunctionKind::Forwarder,
+                        input_func_id,
+                    }),
+            );
+        }
 
-        let mut input_function_output_id: HashMap<_, _> = output_functions
-            .iter()
-            .enumerate()
-            .map(|(output_func_id, &OutputFunction { input_func_id, .. })| {
-                (input_func_id, output_func_id)
-            })
-            .collect();
+        if output_module_index == 0 && emit_state.lazy_indirect_calls {
+            let lazy_stubs = emit_state.indirect_functions.table_range_for_output_module[1..]
+                .iter()
+                .flat_map(|range| range.clone())
+                .map(|table_index| OutputFunction {
+                    kind: OutputFunctionKind::LazyStub,
+                    input_func_id: emit_state.indirect_functions.table_entries[table_index - 1],
+                })
+                .collect::<Vec<_>>();
+            if !lazy_stubs.is_empty() {
+                output_functions.push(OutputFunction {
+                    kind: OutputFunctionKind::SlotLoader,
+                    input_func_id: InputFuncId::MAX,
+                });
+                output_functions.extend(lazy_stubs);
+            }
+        }
+
+        // Within each kind, functions are ordered by symbol name, as table
+        // slots are, rather than by input index. A chunk whose code did not
+        // change then keeps its function indices, and so its hash, when the
+        // linker orders the functions of the next build differently, e.g.
+        // because functions were added elsewhere. Imports keep the order of
+        // the input's import section, which they are copied in.
+        output_functions.sort_by_cached_key(|func| {
+            let name = match func.kind {
+                OutputFunctionKind::SlotLoader | OutputFunctionKind::Import => None,
+                _ => module.names.functions.get(&func.input_func_id).copied(),
+            };
+            (func.kind, name.is_none(), name, func.input_func_id)
+        });
+
+        let mut input_function_output_id = HashMap::new();
+        let mut forwarder_output_id = HashMap::new();
+        let mut lazy_stub_output_id = HashMap::new();
+        for (output_func_id, func) in output_functions.iter().enumerate() {
+            match func.kind {
+                OutputFunctionKind::SlotLoader => {}
+                OutputFunctionKind::Forwarder => {
+                    forwarder_output_id.insert(func.input_func_id, output_func_id);
+                }
+                OutputFunctionKind::LazyStub => {
+                    lazy_stub_output_id.insert(func.input_func_id, output_func_id);
+                }
+                _ => {
+                    input_function_output_id.insert(func.input_func_id, output_func_id);
+                }
+            }
+        }
 
         // Map references to `import_func` to `export_func`.
         for (_, output_module) in program_info.output_modules.iter() {
@@ -338,20 +635,42 @@ impl<'a> ModuleEmitState<'a> {
             }
         }
 
+        // Imported globals are copied from the input, so keep their indices;
+        // globals of the main module follow them.
+        let global_output_id = if output_module_index == 0 {
+            HashMap::new()
+        } else {
+            emit_state.referenced_globals[output_module_index]
+                .iter()
+                .enumerate()
+                .map(|(i, &global_id)| (global_id, (emit_state.num_imported_globals + i) as u32))
+                .collect()
+        };
+
         let indirect_function_table_range =
             emit_state.indirect_functions.table_range_for_output_module[output_module_index]
                 .clone();
 
+        let module_name = match &program_info.output_modules[output_module_index].0 {
+            SplitModuleIdentifier::Main => module.names.module.map(str::to_string),
+            identifier => Some(identifier.name()),
+        };
+
         Self {
             input_module: module,
             output_module_index,
             output_module_info,
             emit_state,
-            program_info,
             output_module: wasm_encoder::Module::new(),
             output_functions,
             input_function_output_id,
+            forwarder_output_id,
+            lazy_stub_output_id,
+            global_output_id,
             indirect_function_table_range,
+            loader_module,
+            module_name,
+            function_code_offsets: HashMap::new(),
         }
     }
 
@@ -359,6 +678,47 @@ impl<'a> ModuleEmitState<'a> {
         self.output_module_index == 0
     }
 
+    fn defined_function_range(&self) -> Range<usize> {
+        // `output_functions` is sorted by kind, so defined functions are
+        // contiguous.
+        let start = self
+            .output_functions
+            .iter()
+            .take_while(|func| {
+                matches!(
+                    func.kind,
+                    OutputFunctionKind::SlotLoader | OutputFunctionKind::Import
+                )
+            })
+            .count();
+        let len = self.output_functions[start..]
+            .iter()
+            .take_while(|func| func.kind == OutputFunctionKind::Defined)
+            .count();
+        start..(start + len)
+    }
+
+    /// Output function index of the [`OutputFunctionKind::SlotLoader`], if
+    /// this module has one.
+    fn slot_loader_output_id(&self) -> Option<usize> {
+        self.output_functions
+            .first()
+            .filter(|func| func.kind == OutputFunctionKind::SlotLoader)
+            .map(|_| 0)
+    }
+
+    fn table_slots(&self) -> Vec<(usize, InputFuncId)> {
+        self.indirect_function_table_range
+            .clone()
+            .map(|table_index| {
+                (
+                    table_index,
+                    self.emit_state.indirect_functions.table_entries[table_index - 1],
+                )
+            })
+            .collect()
+    }
+
     fn get_relocation_input_function_index(&self, relocation: &RelocationEntry) -> Result<usize> {
         let Some(SymbolInfo::Func {
             index: input_func_id,
@@ -384,6 +744,14 @@ impl<'a> ModuleEmitState<'a> {
 
     fn get_relocated_function_table_index(&self, relocation: &RelocationEntry) -> Result<usize> {
         let input_func_id = self.get_relocation_input_function_index(relocation)?;
+        if self
+            .emit_state
+            .indirect_functions
+            .dropped_functions
+            .contains(&input_func_id)
+        {
+            return Ok(0);
+        }
         self.emit_state
             .indirect_functions
             .function_table_index
@@ -399,6 +767,30 @@ impl<'a> ModuleEmitState<'a> {
             .copied()
     }
 
+    fn get_relocated_global_index(&self, relocation: &RelocationEntry) -> Result<u32> {
+        let Some(SymbolInfo::Global { index, .. }) =
+            self.input_module.symbols.get(relocation.index as usize)
+        else {
+            bail!("Relocation {relocation:?} does not refer to a valid global");
+        };
+        self.get_output_global_index(*index as GlobalId)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Dependency analysis error: \
+                     Global {index} referenced by relocation {relocation:?} \
+                     is not imported from the main module"
+                )
+            })
+    }
+
+    fn get_output_global_index(&self, global_id: GlobalId) -> Option<u32> {
+        if self.is_main() || global_id < self.emit_state.num_imported_globals {
+            Some(global_id as u32)
+        } else {
+            self.global_output_id.get(&global_id).copied()
+        }
+    }
+
     fn apply_relocation(
         &self,
         data: &mut [u8],
@@ -446,6 +838,27 @@ impl<'a> ModuleEmitState<'a> {
                     target.try_into().unwrap(),
                 );
             }
+            GlobalIndexLeb => {
+                encode_leb128_u32_5byte(
+                    self.get_relocated_global_index(relocation)?,
+                    target.try_into().unwrap(),
+                );
+            }
+            TableNumberLeb => {
+                let Some(&SymbolInfo::Table { index, .. }) =
+                    self.input_module.symbols.get(relocation.index as usize)
+                else {
+                    bail!("Relocation {relocation:?} does not refer to a valid table");
+                };
+                encode_leb128_u32_5byte(
+                    self.emit_state.get_output_table_index(index as TableId),
+                    target.try_into().unwrap(),
+                );
+            }
+            // Every output module has the tags of the input at their input
+            // indices, since the other modules import those that the main
+            // module defines after those imported by the input.
+            EventIndexLeb => {}
             FunctionOffsetI32 | SectionOffsetI32 | TableIndexRelSleb | FunctionOffsetI64
             | TableIndexRelSleb64 => {
                 bail!("Unsupported relocation type {relocation:?}");
@@ -463,23 +876,69 @@ impl<'a> ModuleEmitState<'a> {
         Ok(data)
     }
 
+    /// Replaces the calls of [`OutputFunctionKind::IndirectStub`]s in `body`,
+    /// the relocated code of the input function at `range`, by the
+    /// `call_indirect` of the slot that the stub would make. A call of
+    /// another module's function then costs a single indirect call once that
+    /// module has filled in its slot, rather than a call of the stub as well.
+    /// The stubs remain for other references, such as `ref.func`.
+    fn inline_indirect_stub_calls(&self, range: Range<usize>, body: Vec<u8>) -> Result<Vec<u8>> {
+        use wasm_encoder::Encode;
+
+        if !self.emit_state.inline_indirect_stub_calls {
+            return Ok(body);
+        }
+        let mut inlined = Vec::new();
+        let mut copied = 0;
+        for relocation in self.emit_state.get_relocations_for_range(&range) {
+            if relocation.ty != RelocationType::FunctionIndexLeb {
+                continue;
+            }
+            let start = relocation.relocation_range().start - range.start;
+            // Only plain calls, rather than e.g. `ref.func` or tail calls,
+            // whose opcode precedes the function index.
+            if start == 0 || body[start - 1] != 0x10 {
+                continue;
+            }
+            let stub = &self.output_functions[self.get_relocated_function_index(relocation)?];
+            if stub.kind != OutputFunctionKind::IndirectStub {
+                continue;
+            }
+            let slot = self.emit_state.indirect_functions.function_table_index[&stub.input_func_id];
+            inlined.extend_from_slice(&body[copied..start - 1]);
+            wasm_encoder::Instruction::I32Const(slot as i32).encode(&mut inlined);
+            wasm_encoder::Instruction::CallIndirect {
+                ty: self.input_module.func_type_id(stub.input_func_id) as u32,
+                table: 0,
+            }
+            .encode(&mut inlined);
+            copied = relocation.relocation_range().end - range.start;
+        }
+        if copied == 0 {
+            return Ok(body);
+        }
+        inlined.extend_from_slice(&body[copied..]);
+        Ok(inlined)
+    }
+
     fn generate(&mut self) -> Result<()> {
         // Encode type section
         self.generate_type_section()?;
         self.generate_import_section();
         self.generate_function_section();
-        self.generate_table_section();
+        self.generate_table_section()?;
         self.generate_memory_section();
+        self.generate_tag_section();
         self.generate_global_section();
-        self.generate_export_section();
+        self.generate_export_section()?;
         self.generate_start_section();
         self.generate_element_section()?;
-        self.generate_data_count_section();
+        self.generate_data_count_section()?;
         self.generate_code_section()?;
         self.generate_data_section()?;
-        self.generate_wasm_bindgen_sections();
+        self.generate_debug_sections()?;
         self.generate_name_section()?;
-        self.generate_target_features_section();
+        self.generate_custom_sections()?;
         Ok(())
     }
 
@@ -494,6 +953,10 @@ impl<'a> ModuleEmitState<'a> {
                 output_func_type.results().iter().cloned(),
             );
         }
+        // That of the slot loader, which follows those of the input.
+        if self.slot_loader_output_id().is_some() {
+            section.function([wasm_encoder::ValType::I32], [wasm_encoder::ValType::I32]);
+        }
         self.output_module.section(&section);
         Ok(())
     }
@@ -513,6 +976,10 @@ impl<'a> ModuleEmitState<'a> {
             .unwrap_or_else(|| format!("__global_{index}"))
     }
 
+    /// Name under which the main module exports the memory `index` of the
+    /// input, imported or defined, to the other modules. That of an imported
+    /// memory falls back to its import name, which is usually "memory", as
+    /// the loader reads the memory under.
     fn get_memory_name(&self, index: usize) -> String {
         self.input_module
             .names
@@ -525,21 +992,103 @@ impl<'a> ModuleEmitState<'a> {
                     .get(&(wasmparser::ExternalKind::Memory as isize, index))
                     .map(|(_, name)| name.to_string())
             })
+            .or_else(|| {
+                self.input_module
+                    .imports
+                    .iter()
+                    .filter(|import| matches!(import.ty, wasmparser::TypeRef::Memory(_)))
+                    .nth(index)
+                    .map(|import| import.name.to_string())
+            })
             .unwrap_or_else(|| format!("__memory_{index}"))
     }
 
+    fn get_table_name(&self, table_id: TableId) -> String {
+        self.input_module
+            .names
+            .tables
+            .get(&table_id)
+            .map(|name| name.to_string())
+            .or_else(|| {
+                self.input_module
+                    .export_map
+                    .get(&(wasmparser::ExternalKind::Table as isize, table_id))
+                    .map(|(_, name)| name.to_string())
+            })
+            .or_else(|| {
+                self.input_module
+                    .symbols
+                    .iter()
+                    .find_map(|symbol| match *symbol {
+                        SymbolInfo::Table {
+                            index,
+                            name: Some(name),
+                            ..
+                        } if index as TableId == table_id => Some(name.to_string()),
+                        _ => None,
+                    })
+            })
+            .unwrap_or_else(|| format!("__table_{table_id}"))
+    }
+
+    /// The tables of the input other than the indirect function table, in
+    /// the order of their output indices from 1.
+    fn other_tables(&self) -> impl Iterator<Item = TableId> + '_ {
+        (0..self.input_module.tables.len())
+            .filter(|&table_id| table_id != self.emit_state.function_table)
+    }
+
+    fn num_imported_memories(&self) -> usize {
+        self.input_module
+            .imports
+            .iter()
+            .filter(|import| matches!(import.ty, wasmparser::TypeRef::Memory(_)))
+            .count()
+    }
+
+    fn get_tag_name(&self, index: usize) -> String {
+        self.input_module
+            .names
+            .tags
+            .get(&index)
+            .map(|name| name.to_string())
+            .or_else(|| {
+                self.input_module
+                    .export_map
+                    .get(&(wasmparser::ExternalKind::Tag as isize, index))
+                    .map(|(_, name)| name.to_string())
+            })
+            .unwrap_or_else(|| format!("__tag_{index}"))
+    }
+
+    // Number of tags imported by the input, which precede the defined ones in
+    // the tag index space.
+    fn num_imported_tags(&self) -> usize {
+        self.input_module
+            .imports
+            .iter()
+            .filter(|import| matches!(import.ty, wasmparser::TypeRef::Tag(_)))
+            .count()
+    }
+
     fn get_indirect_function_table_type(&self) -> wasm_encoder::TableType {
-        // + 1 due to empty entry at index 0
-        let indirect_table_size = self.emit_state.indirect_functions.table_entries.len() + 1;
+        let indirect_table_size = self.emit_state.reserved_table_slots.end;
         wasm_encoder::TableType {
             element_type: wasm_encoder::RefType::FUNCREF,
             minimum: indirect_table_size as u32,
-            maximum: Some(indirect_table_size as u32),
+            maximum: self.emit_state.table_maximum,
         }
     }
 
     fn generate_import_section(&mut self) {
         let mut section = wasm_encoder::ImportSection::new();
+        if self.slot_loader_output_id().is_some() {
+            section.import(
+                self.loader_module,
+                LOAD_SLOT_IMPORT,
+                wasm_encoder::EntityType::Function(self.input_module.types.len() as u32),
+            );
+        }
         // Function imports
         for (func_id, &import_id) in self.input_module.imported_funcs.iter().enumerate() {
             if !self
@@ -550,16 +1099,36 @@ impl<'a> ModuleEmitState<'a> {
                 continue;
             }
             let import = &self.input_module.imports[import_id];
-            let ty: wasm_encoder::EntityType = import.ty.clone().try_into().unwrap();
-            section.import(import.module, import.name, ty);
+            let ty: wasm_encoder::EntityType = import.ty.try_into().unwrap();
+            let import_module = if import.module == WASM_SPLIT_JS_MODULE {
+                self.loader_module
+            } else {
+                import.module
+            };
+            section.import(import_module, import.name, ty);
         }
 
-        // Copy all non-function imports from input.
+        // Copy all non-function imports from input, except that the other
+        // modules import the memories from the main module. The main module
+        // of each thread of an app with threads, such as those of
+        // wasm-bindgen, is given the one shared memory by the JS that
+        // instantiates it, which the loader does not have for the chunks.
+        let mut memory_index = 0;
         for import in self.input_module.imports.iter() {
-            if let wasmparser::TypeRef::Func(_) = import.ty {
-                continue;
-            }
-            let ty: wasm_encoder::EntityType = import.ty.clone().try_into().unwrap();
+            let ty: wasm_encoder::EntityType = match import.ty {
+                wasmparser::TypeRef::Func(_) => continue,
+                wasmparser::TypeRef::Memory(memory) if !self.is_main() => {
+                    let name = self.get_memory_name(memory_index);
+                    memory_index += 1;
+                    section.import(
+                        "__wasm_split",
+                        name.as_str(),
+                        wasm_encoder::MemoryType::from(memory),
+                    );
+                    continue;
+                }
+                ty => ty.try_into().unwrap(),
+            };
             section.import(import.module, import.name, ty);
         }
 
@@ -571,26 +1140,41 @@ impl<'a> ModuleEmitState<'a> {
                 "__indirect_function_table",
                 self.get_indirect_function_table_type(),
             );
+            // And the other tables, e.g. of `externref`s.
+            for table_id in self.other_tables() {
+                let ty: wasm_encoder::TableType =
+                    self.input_module.tables[table_id].ty.try_into().unwrap();
+                section.import("__wasm_split", self.get_table_name(table_id).as_str(), ty);
+            }
 
-            // Import all globals defined by the input module.
-            for (global_index, global) in self.input_module.globals.iter().enumerate() {
+            // Import the globals of the main module that the code refers to.
+            for &global_id in &self.emit_state.referenced_globals[self.output_module_index] {
+                let global =
+                    &self.input_module.globals[global_id - self.emit_state.num_imported_globals];
                 let ty: wasm_encoder::GlobalType = global.ty.try_into().unwrap();
-                if !ty.mutable {
-                    continue;
-                }
+                section.import("__wasm_split", self.get_global_name(global_id).as_str(), ty);
+            }
+
+            // Import all memories defined by the input module.
+            let num_imported_memories = self.num_imported_memories();
+            for (i, memory) in self.input_module.memories.iter().enumerate() {
+                let ty: wasm_encoder::MemoryType = (*memory).into();
                 section.import(
                     "__wasm_split",
-                    self.get_global_name(global_index).as_str(),
+                    self.get_memory_name(num_imported_memories + i).as_str(),
                     ty,
                 );
             }
 
-            // Import all memories defined by the input module.
-            for (memory_index, memory) in self.input_module.memories.iter().enumerate() {
-                let ty: wasm_encoder::MemoryType = memory.clone().try_into().unwrap();
+            // Import all tags defined by the input module, so that exceptions
+            // thrown by one module are caught by the tags of the others,
+            // e.g. Rust panics unwinding into the callers in the main module.
+            let num_imported_tags = self.num_imported_tags();
+            for (i, &tag) in self.input_module.tags.iter().enumerate() {
+                let ty: wasm_encoder::TagType = tag.into();
                 section.import(
                     "__wasm_split",
-                    self.get_memory_name(memory_index).as_str(),
+                    self.get_tag_name(num_imported_tags + i).as_str(),
                     ty,
                 );
             }
@@ -600,23 +1184,39 @@ impl<'a> ModuleEmitState<'a> {
 
     fn generate_function_section(&mut self) {
         let mut section = wasm_encoder::FunctionSection::new();
-        for OutputFunction { input_func_id, .. } in self
-            .output_functions
-            .iter()
-            .filter(|OutputFunction { kind, .. }| *kind != OutputFunctionKind::Import)
+        for OutputFunction { input_func_id, .. } in
+            self.output_functions
+                .iter()
+                .filter(|OutputFunction { kind, .. }| {
+                    !matches!(
+                        kind,
+                        OutputFunctionKind::SlotLoader | OutputFunctionKind::Import
+                    )
+                })
         {
             section.function(self.input_module.func_type_id(*input_func_id) as u32);
         }
         self.output_module.section(&section);
     }
 
-    fn generate_table_section(&mut self) {
+    fn generate_table_section(&mut self) -> Result<()> {
         if !self.is_main() {
-            return;
+            return Ok(());
         }
         let mut section = wasm_encoder::TableSection::new();
         section.table(self.get_indirect_function_table_type());
+        for table_id in self.other_tables() {
+            let table = &self.input_module.tables[table_id];
+            let ty = table.ty.try_into().unwrap();
+            match &table.init {
+                wasmparser::TableInit::RefNull => section.table(ty),
+                wasmparser::TableInit::Expr(expr) => {
+                    section.table_with_init(ty, &(*expr).try_into()?)
+                }
+            };
+        }
         self.output_module.section(&section);
+        Ok(())
     }
 
     fn generate_memory_section(&mut self) {
@@ -625,7 +1225,18 @@ impl<'a> ModuleEmitState<'a> {
         }
         let mut section = wasm_encoder::MemorySection::new();
         for memory in self.input_module.memories.iter() {
-            section.memory(memory.clone().try_into().unwrap());
+            section.memory((*memory).into());
+        }
+        self.output_module.section(&section);
+    }
+
+    fn generate_tag_section(&mut self) {
+        if !self.is_main() || self.input_module.tags.is_empty() {
+            return;
+        }
+        let mut section = wasm_encoder::TagSection::new();
+        for &tag in self.input_module.tags.iter() {
+            section.tag(tag.into());
         }
         self.output_module.section(&section);
     }
@@ -637,16 +1248,16 @@ impl<'a> ModuleEmitState<'a> {
         let mut section = wasm_encoder::GlobalSection::new();
         for global in self.input_module.globals.iter() {
             section.global(
-                global.ty.clone().try_into().unwrap(),
-                &global.init_expr.clone().try_into().unwrap(),
+                global.ty.try_into().unwrap(),
+                &global.init_expr.try_into().unwrap(),
             );
         }
         self.output_module.section(&section);
     }
 
-    fn generate_export_section(&mut self) {
+    fn generate_export_section(&mut self) -> Result<()> {
         if !self.is_main() {
-            return;
+            return Ok(());
         }
         let mut section = wasm_encoder::ExportSection::new();
         let mut existing_exports = HashSet::<&str>::new();
@@ -658,8 +1269,10 @@ impl<'a> ModuleEmitState<'a> {
                     continue;
                 };
                 index = func_id as u32;
+            } else if export.kind == wasmparser::ExternalKind::Table {
+                index = self.emit_state.get_output_table_index(index as TableId);
             }
-            section.export(export.name, export.kind.try_into().unwrap(), index);
+            section.export(export.name, export.kind.into(), index);
             existing_exports.insert(export.name);
         }
 
@@ -672,22 +1285,56 @@ impl<'a> ModuleEmitState<'a> {
             );
         }
 
-        // Export globals.
-        for (global_index, global) in self.input_module.globals.iter().enumerate() {
-            let name = self.get_global_name(global_index);
+        // Export the globals, tables, memories and tags that other modules
+        // import.
+        let imported_globals: BTreeSet<GlobalId> = self.emit_state.referenced_globals[1..]
+            .iter()
+            .flatten()
+            .copied()
+            .collect();
+        let num_memories = self.num_imported_memories() + self.input_module.memories.len();
+        let num_imported_tags = self.num_imported_tags();
+        let shared_items = imported_globals
+            .into_iter()
+            .map(|global_id| (wasmparser::ExternalKind::Global, global_id))
+            .chain(
+                self.other_tables()
+                    .map(|table_id| (wasmparser::ExternalKind::Table, table_id)),
+            )
+            .chain((0..num_memories).map(|index| (wasmparser::ExternalKind::Memory, index)))
+            .chain(
+                (num_imported_tags..num_imported_tags + self.input_module.tags.len())
+                    .map(|tag_id| (wasmparser::ExternalKind::Tag, tag_id)),
+            );
+        for (kind, index) in shared_items {
+            let (name, description) = match kind {
+                wasmparser::ExternalKind::Global => (self.get_global_name(index), "global"),
+                wasmparser::ExternalKind::Table => (self.get_table_name(index), "table"),
+                wasmparser::ExternalKind::Memory => (self.get_memory_name(index), "memory"),
+                _ => (self.get_tag_name(index), "tag"),
+            };
+            let output_index = match kind {
+                wasmparser::ExternalKind::Table => self.emit_state.get_output_table_index(index),
+                _ => index as u32,
+            };
             if existing_exports.contains(name.as_str()) {
+                let exported_as = self
+                    .input_module
+                    .export_map
+                    .get(&(kind as isize, index))
+                    .map(|&(_, name)| name);
+                if exported_as != Some(name.as_str()) {
+                    bail!(
+                        "Split modules import {description} {name:?} from the main module, but \
+                         the input exports another item under that name"
+                    );
+                }
                 continue;
             }
-            if !global.ty.mutable {
-                break;
-            }
-            section.export(
-                name.as_str(),
-                wasm_encoder::ExportKind::Global,
-                global_index as u32,
-            );
+            section.export(name.as_str(), kind.into(), output_index);
         }
         self.output_module.section(&section);
+        Ok(())
     }
 
     fn generate_start_section(&mut self) {
@@ -706,19 +1353,52 @@ impl<'a> ModuleEmitState<'a> {
 
     fn generate_element_section(&mut self) -> Result<()> {
         let indirect_range = self.indirect_function_table_range.clone();
+        let mut section = wasm_encoder::ElementSection::new();
+        // The lazy stubs fill the slots of each other module, which it
+        // overwrites with its own segment once it is instantiated.
+        if !self.lazy_stub_output_id.is_empty() {
+            for range in &self
+                .emit_state
+                .indirect_functions
+                .table_range_for_output_module[1..]
+            {
+                if range.is_empty() {
+                    continue;
+                }
+                let func_ids: Vec<u32> = range
+                    .clone()
+                    .map(|table_index| {
+                        let input_func_id =
+                            self.emit_state.indirect_functions.table_entries[table_index - 1];
+                        self.lazy_stub_output_id[&input_func_id] as u32
+                    })
+                    .collect();
+                section.segment(wasm_encoder::ElementSegment {
+                    mode: wasm_encoder::ElementMode::Active {
+                        table: Some(0),
+                        offset: &wasm_encoder::ConstExpr::i32_const(range.start as i32),
+                    },
+                    elements: wasm_encoder::Elements::Functions(&func_ids),
+                });
+            }
+        }
+        // A module may own no table slots, e.g. if everything it calls
+        // indirectly was duplicated into it.
         if indirect_range.is_empty() {
-            panic!("No indirect range");
+            if !section.is_empty() {
+                self.output_module.section(&section);
+            }
             return Ok(());
         }
-        let mut section = wasm_encoder::ElementSection::new();
         let func_ids: Vec<u32> = indirect_range
             .clone()
             .map(|table_index| -> Result<u32> {
                 let input_func_id =
                     self.emit_state.indirect_functions.table_entries[table_index - 1];
                 let output_func_id = *self
-                    .input_function_output_id
+                    .forwarder_output_id
                     .get(&input_func_id)
+                    .or_else(|| self.input_function_output_id.get(&input_func_id))
                     .ok_or_else(|| {
                         anyhow!(
                             "No output function corresponding to input function {input_func_id:?}"
@@ -738,15 +1418,12 @@ impl<'a> ModuleEmitState<'a> {
         Ok(())
     }
 
-    fn generate_data_count_section(&mut self) {
+    fn generate_data_count_section(&mut self) -> Result<()> {
         let section = wasm_encoder::DataCountSection {
-            count: if self.is_main() {
-                self.input_module.data_segments.len() as u32
-            } else {
-                0
-            },
+            count: self.output_data_segments()?.len() as u32,
         };
         self.output_module.section(&section);
+        Ok(())
     }
 
     fn generate_indirect_stub(
@@ -756,6 +1433,59 @@ impl<'a> ModuleEmitState<'a> {
     ) -> wasm_encoder::Function {
         let func_type = &self.input_module.types[type_id];
         let mut func = wasm_encoder::Function::new([]);
+        if let Some((fault_slot, fault_type_id)) = self.emit_state.guard_fault {
+            // Report an empty slot, i.e. a call into a module that is not
+            // loaded, rather than trapping on it.
+            func.instruction(&wasm_encoder::Instruction::I32Const(indirect_index as i32));
+            func.instruction(&wasm_encoder::Instruction::TableGet(0));
+            func.instruction(&wasm_encoder::Instruction::RefIsNull);
+            func.instruction(&wasm_encoder::Instruction::If(
+                wasm_encoder::BlockType::Empty,
+            ));
+            func.instruction(&wasm_encoder::Instruction::I32Const(indirect_index as i32));
+            func.instruction(&wasm_encoder::Instruction::I32Const(fault_slot as i32));
+            func.instruction(&wasm_encoder::Instruction::CallIndirect {
+                ty: fault_type_id as u32,
+                table: 0,
+            });
+            func.instruction(&wasm_encoder::Instruction::End);
+        }
+        for (param_i, _param_type) in func_type.params().iter().enumerate() {
+            func.instruction(&wasm_encoder::Instruction::LocalGet(param_i as u32));
+        }
+        func.instruction(&wasm_encoder::Instruction::I32Const(indirect_index as i32));
+        func.instruction(&wasm_encoder::Instruction::CallIndirect {
+            ty: type_id as u32,
+            table: 0,
+        });
+        func.instruction(&wasm_encoder::Instruction::End);
+        func
+    }
+
+    fn generate_lazy_stub(&self, indirect_index: usize, type_id: usize) -> wasm_encoder::Function {
+        let func_type = &self.input_module.types[type_id];
+        let mut func = wasm_encoder::Function::new([]);
+        func.instruction(&wasm_encoder::Instruction::I32Const(indirect_index as i32));
+        func.instruction(&wasm_encoder::Instruction::Call(
+            self.slot_loader_output_id().unwrap() as u32,
+        ));
+        // A failed load is reported as one of an empty slot with
+        // `--guard-calls`, whose handler panics, so that the panic unwinds to
+        // the caller with `panic = "unwind"`. The handler does not return.
+        func.instruction(&wasm_encoder::Instruction::I32Eqz);
+        func.instruction(&wasm_encoder::Instruction::If(
+            wasm_encoder::BlockType::Empty,
+        ));
+        if let Some((fault_slot, fault_type_id)) = self.emit_state.guard_fault {
+            func.instruction(&wasm_encoder::Instruction::I32Const(indirect_index as i32));
+            func.instruction(&wasm_encoder::Instruction::I32Const(fault_slot as i32));
+            func.instruction(&wasm_encoder::Instruction::CallIndirect {
+                ty: fault_type_id as u32,
+                table: 0,
+            });
+        }
+        func.instruction(&wasm_encoder::Instruction::Unreachable);
+        func.instruction(&wasm_encoder::Instruction::End);
         for (param_i, _param_type) in func_type.params().iter().enumerate() {
             func.instruction(&wasm_encoder::Instruction::LocalGet(param_i as u32));
         }
@@ -768,15 +1498,37 @@ impl<'a> ModuleEmitState<'a> {
         func
     }
 
+    fn generate_forwarder(&self, input_func_id: InputFuncId) -> wasm_encoder::Function {
+        let func_type = &self.input_module.types[self.input_module.func_type_id(input_func_id)];
+        let mut func = wasm_encoder::Function::new([]);
+        for (param_i, _param_type) in func_type.params().iter().enumerate() {
+            func.instruction(&wasm_encoder::Instruction::LocalGet(param_i as u32));
+        }
+        func.instruction(&wasm_encoder::Instruction::Call(
+            self.input_function_output_id[&input_func_id] as u32,
+        ));
+        func.instruction(&wasm_encoder::Instruction::End);
+        func
+    }
+
     fn generate_code_section(&mut self) -> Result<()> {
         let mut section = wasm_encoder::CodeSection::new();
+        let mut body_offsets = Vec::new();
         for output_func in self.output_functions.iter() {
             match output_func.kind {
-                OutputFunctionKind::Import => {}
+                OutputFunctionKind::SlotLoader | OutputFunctionKind::Import => {}
                 OutputFunctionKind::Defined => {
                     let input_func = &self.input_module.defined_funcs
                         [output_func.input_func_id - self.input_module.imported_funcs.len()];
-                    section.raw(&self.get_relocated_data(input_func.body.range())?);
+                    let body = self.inline_indirect_stub_calls(
+                        input_func.body.range(),
+                        self.get_relocated_data(input_func.body.range())?,
+                    )?;
+                    body_offsets.push((
+                        output_func.input_func_id,
+                        section.byte_len() + leb128_len(body.len()),
+                    ));
+                    section.raw(&body);
                 }
                 OutputFunctionKind::IndirectStub => {
                     let indirect_index = self
@@ -791,28 +1543,192 @@ impl<'a> ModuleEmitState<'a> {
                     );
                     section.function(&function);
                 }
+                OutputFunctionKind::Forwarder => {
+                    let function = self.generate_forwarder(output_func.input_func_id);
+                    section.function(&function);
+                }
+                OutputFunctionKind::LazyStub => {
+                    let function = self.generate_lazy_stub(
+                        self.emit_state.indirect_functions.function_table_index
+                            [&output_func.input_func_id],
+                        self.input_module.func_type_id(output_func.input_func_id),
+                    );
+                    section.function(&function);
+                }
             }
         }
+        // The contents start with the number of functions.
+        let count_len = leb128_len(section.len() as usize);
+        self.function_code_offsets = body_offsets
+            .into_iter()
+            .map(|(func_id, offset)| (func_id, (count_len + offset) as u64))
+            .collect();
         self.output_module.section(&section);
         Ok(())
     }
 
-    fn generate_data_section(&mut self) -> Result<()> {
+    /// Copies the DWARF sections of the input, with the code addresses of the
+    /// functions of this module moved to where it puts their bodies, and
+    /// those of other modules' functions replaced by tombstones, so that
+    /// debuggers map the code of every module to its source lines. Requires
+    /// the relocations of the sections, which `--emit-relocs` includes.
+    fn generate_debug_sections(&mut self) -> Result<()> {
+        for custom in self.input_module.custom_sections.iter() {
+            if !custom.name.starts_with(".debug_") {
+                continue;
+            }
+            let mut data = custom.data.to_vec();
+            for relocation in self
+                .input_module
+                .relocs
+                .get(&custom.index)
+                .into_iter()
+                .flatten()
+            {
+                self.apply_debug_relocation(custom.name, &mut data, relocation)?;
+            }
+            self.output_module.section(&wasm_encoder::CustomSection {
+                name: custom.name.into(),
+                data: data.into(),
+            });
+        }
+        Ok(())
+    }
+
+    fn apply_debug_relocation(
+        &self,
+        section_name: &str,
+        data: &mut [u8],
+        relocation: &RelocationEntry,
+    ) -> Result<()> {
+        let range = relocation.relocation_range();
+        let target = data
+            .get_mut(range)
+            .ok_or_else(|| anyhow!("Invalid relocation {relocation:?} of {section_name}"))?;
+        use RelocationType::*;
+        match relocation.ty {
+            FunctionOffsetI32 | FunctionOffsetI64 => {
+                let Some(SymbolInfo::Func { index, .. }) =
+                    self.input_module.symbols.get(relocation.index as usize)
+                else {
+                    bail!("Relocation {relocation:?} does not refer to a valid function");
+                };
+                let address = match self.function_code_offsets.get(&(*index as InputFuncId)) {
+                    Some(&offset) => offset.wrapping_add_signed(relocation.addend),
+                    None => dwarf_tombstone(section_name),
+                };
+                if relocation.ty == FunctionOffsetI32 {
+                    encode_u32(address as u32, target.try_into().unwrap());
+                } else {
+                    encode_u64(address, target.try_into().unwrap());
+                }
+            }
+            // The frame base of functions, i.e. the stack pointer, which
+            // other modules import.
+            GlobalIndexI32 => {
+                let Some(SymbolInfo::Global { index, .. }) =
+                    self.input_module.symbols.get(relocation.index as usize)
+                else {
+                    bail!("Relocation {relocation:?} does not refer to a valid global");
+                };
+                if let Some(index) = self.get_output_global_index(*index as GlobalId) {
+                    encode_u32(index, target.try_into().unwrap());
+                }
+            }
+            // Offsets into the other DWARF sections, which are copied whole,
+            // and addresses in memory, which the split keeps.
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// The data segments of this module, with their data as ranges of the
+    /// input: those of the input for the main module, less the data moved
+    /// into other modules, and the moved data for the others.
+    fn output_data_segments(&self) -> Result<Vec<OutputDataSegment>> {
+        let moved_data = &self.emit_state.moved_data;
+        let active_at = |segment, data_start, range: &Range<usize>| OutputDataMode::Active {
+            memory_index: 0,
+            offset: wasm_encoder::ConstExpr::i32_const(
+                movable_segment_address(self.input_module, segment).unwrap()
+                    + (range.start - data_start) as i32,
+            ),
+        };
+        let mut segments = Vec::new();
         if !self.is_main() {
+            for moved in moved_data[self.output_module_index].iter() {
+                let input_segment = &self.input_module.data_segments[moved.segment];
+                let data_start = input_segment.range.end - input_segment.data.len();
+                segments.push(OutputDataSegment {
+                    segment: moved.segment,
+                    mode: active_at(moved.segment, data_start, &moved.range),
+                    range: moved.range.clone(),
+                });
+            }
+            return Ok(segments);
+        }
+        for (segment, input_segment) in self.input_module.data_segments.iter().enumerate() {
+            // Note: `input_segment.range` includes the segment header.
+            let data_end = input_segment.range.end;
+            let data_start = data_end - input_segment.data.len();
+            let mut omitted = moved_data
+                .iter()
+                .flatten()
+                .filter(|moved| moved.segment == segment)
+                .map(|moved| moved.range.clone())
+                .collect::<Vec<_>>();
+            if omitted.is_empty() {
+                let mode = match input_segment.kind {
+                    DataKind::Passive => OutputDataMode::Passive,
+                    DataKind::Active {
+                        memory_index,
+                        offset_expr,
+                    } => OutputDataMode::Active {
+                        memory_index,
+                        offset: offset_expr.try_into()?,
+                    },
+                };
+                segments.push(OutputDataSegment {
+                    segment,
+                    mode,
+                    range: data_start..data_end,
+                });
+                continue;
+            }
+            omitted.sort_by_key(|range| range.start);
+            let mut start = data_start;
+            for range in omitted
+                .into_iter()
+                .chain(std::iter::once(data_end..data_end))
+            {
+                if range.start > start {
+                    let kept = start..range.start;
+                    segments.push(OutputDataSegment {
+                        segment,
+                        mode: active_at(segment, data_start, &kept),
+                        range: kept,
+                    });
+                }
+                start = range.end;
+            }
+        }
+        Ok(segments)
+    }
+
+    fn generate_data_section(&mut self) -> Result<()> {
+        let segments = self.output_data_segments()?;
+        if !self.is_main() && segments.is_empty() {
             return Ok(());
         }
         let mut section = wasm_encoder::DataSection::new();
-        for input_segment in self.input_module.data_segments.iter() {
-            // Note: `input_segment.range` includes the segment header.
-            let range_end = input_segment.range.end;
-            let data =
-                self.get_relocated_data((range_end - input_segment.data.len())..range_end)?;
-            match input_segment.kind {
-                DataKind::Passive => section.passive(data),
-                DataKind::Active {
+        for segment in segments {
+            let data = self.get_relocated_data(segment.range)?;
+            match segment.mode {
+                OutputDataMode::Passive => section.passive(data),
+                OutputDataMode::Active {
                     memory_index,
-                    offset_expr,
-                } => section.active(memory_index, &offset_expr.try_into()?, data),
+                    offset,
+                } => section.active(memory_index, &offset, data),
             };
         }
         self.output_module.section(&section);
@@ -841,14 +1757,32 @@ impl<'a> ModuleEmitState<'a> {
             encoder_map
         }
         let mut section = wasm_encoder::NameSection::new();
+        if let Some(name) = &self.module_name {
+            section.module(name);
+        }
         // Function names
         {
             let mut name_map = wasm_encoder::NameMap::new();
             let mut locals_map = wasm_encoder::IndirectNameMap::new();
             let mut labels_map = wasm_encoder::IndirectNameMap::new();
-            for (output_func_id, OutputFunction { input_func_id, .. }) in
-                self.output_functions.iter().enumerate()
-            {
+            let mut generated_names = Vec::new();
+            for (output_func_id, output_func) in self.output_functions.iter().enumerate() {
+                let input_func_id = &output_func.input_func_id;
+                let suffix = match output_func.kind {
+                    OutputFunctionKind::SlotLoader => {
+                        name_map.append(output_func_id as u32, LOAD_SLOT_IMPORT);
+                        continue;
+                    }
+                    OutputFunctionKind::Forwarder => Some("forwarder"),
+                    OutputFunctionKind::LazyStub => Some("lazy stub"),
+                    _ => None,
+                };
+                if let Some(suffix) = suffix {
+                    if let Some(name) = self.input_module.names.functions.get(input_func_id) {
+                        generated_names.push((output_func_id, format!("{name} {suffix}")));
+                    }
+                    continue;
+                }
                 if let Some(name) = self.input_module.names.functions.get(input_func_id) {
                     name_map.append(output_func_id as u32, name);
                 }
@@ -859,72 +1793,785 @@ impl<'a> ModuleEmitState<'a> {
                     labels_map.append(output_func_id as u32, &convert_name_map(name_map)?);
                 }
             }
+            for (output_func_id, name) in generated_names.iter() {
+                name_map.append(*output_func_id as u32, name);
+            }
             section.functions(&name_map);
             section.locals(&locals_map);
             section.labels(&labels_map);
         }
         section.types(&convert_name_hash_map(&self.input_module.names.types));
-        section.tables(&convert_name_hash_map(&self.input_module.names.tables));
+        section.tables(&convert_name_hash_map(
+            &self
+                .input_module
+                .names
+                .tables
+                .iter()
+                .map(|(&table_id, &name)| {
+                    (
+                        self.emit_state.get_output_table_index(table_id) as usize,
+                        name,
+                    )
+                })
+                .collect(),
+        ));
         section.memories(&convert_name_hash_map(&self.input_module.names.memories));
-        section.globals(&convert_name_hash_map(&self.input_module.names.globals));
+        {
+            let mut names = self.input_module.names.globals.iter().collect::<Vec<_>>();
+            names.sort();
+            let mut name_map = wasm_encoder::NameMap::new();
+            for (&global_id, &name) in names {
+                if let Some(index) = self.get_output_global_index(global_id) {
+                    name_map.append(index, name);
+                }
+            }
+            section.globals(&name_map);
+        }
         // elements
-        if self.is_main() {
-            section.data(&convert_name_hash_map(
-                &self.input_module.names.data_segments,
-            ));
+        let data_segments = self.output_data_segments()?;
+        if self.is_main() || !data_segments.is_empty() {
+            // Moved data splits segments, each part of which keeps the name of
+            // its input segment.
+            let mut name_map = wasm_encoder::NameMap::new();
+            for (index, segment) in data_segments.iter().enumerate() {
+                if let Some(name) = self.input_module.names.data_segments.get(&segment.segment) {
+                    name_map.append(index as u32, name);
+                }
+            }
+            section.data(&name_map);
+        }
+        if !self.input_module.names.tags.is_empty() {
+            section.tags(&convert_name_hash_map(&self.input_module.names.tags));
         }
-        // tag
-        // fields
-        // tags
         self.output_module.section(&section);
         Ok(())
         // Type names
     }
 
-    fn generate_wasm_bindgen_sections(&mut self) {
+    /// Copies the other custom sections of the input that this module gets;
+    /// see `custom_sections.rs`.
+    fn generate_custom_sections(&mut self) -> Result<()> {
+        let mut producers = None;
         for custom in self.input_module.custom_sections.iter() {
-            if self.is_main() && custom.name == "__wasm_bindgen_unstable" {
+            let copied = match custom_sections::placement(custom.name) {
+                Placement::EveryModule => true,
+                Placement::Main => self.is_main(),
+                Placement::Remapped | Placement::Dropped => false,
+            };
+            if custom.name == PRODUCERS_SECTION {
+                producers = Some((custom.data, custom.data_offset));
+            } else if copied {
                 self.output_module.section(&wasm_encoder::CustomSection {
                     name: custom.name.into(),
                     data: custom.data.into(),
                 });
             }
         }
+        if self.is_main() {
+            self.output_module
+                .section(&custom_sections::producers_section(producers)?);
+        }
+        Ok(())
     }
+}
 
-    fn generate_target_features_section(&mut self) {
-        for custom in self.input_module.custom_sections.iter() {
-            if custom.name == "target_features" {
-                self.output_module.section(&wasm_encoder::CustomSection {
-                    name: custom.name.into(),
-                    data: custom.data.into(),
-                });
-            }
-        }
+/// Sizes and hashes of an encoded module, which take much longer to compute
+/// than encoding it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ModuleDigest {
+    pub gzip_size: usize,
+    pub sha256: String,
+    pub integrity: String,
+}
+
+impl ModuleDigest {
+    pub fn new(data: &[u8], cache: Option<&Cache>) -> Result<Self> {
+        cache::cached(cache, "digest", &[data], || {
+            Ok(Self {
+                gzip_size: gzip_size(data),
+                sha256: sha256_hex(data),
+                integrity: sri_hash(data),
+            })
+        })
     }
 }
 
+/// Layout of an emitted output module.
+#[derive(Debug, Clone, Default)]
+pub struct EmittedModule {
+    /// Function indices, within the output module, of the functions it
+    /// defines. Excludes imports and indirect stubs.
+    pub defined_functions: Range<usize>,
+    /// Input function corresponding to each output function index.
+    pub functions: Vec<InputFuncId>,
+    /// Size of the encoded module in bytes.
+    pub size: usize,
+    /// [`gzip_size`] of the encoded module.
+    pub gzip_size: usize,
+    /// [`content_hash`] of the encoded module.
+    pub hash: String,
+    /// [`sha256_hex`] of the encoded module.
+    pub sha256: String,
+    /// [`sri_hash`] of the encoded module.
+    pub integrity: String,
+    /// Sizes of the copies written with `--compress`, which are compressed
+    /// as the module is written and filled in afterwards.
+    pub compressed_sizes: BTreeMap<Encoding, usize>,
+    /// Names of the functions by index, read back from the module if it was
+    /// optimized with `[wasm-opt]`, after which `functions` no longer
+    /// matches its function indices.
+    pub optimized_names: Option<BTreeMap<usize, String>>,
+    /// Features used by the module, also listed in its
+    /// [`FEATURES_SECTION`](crate::features::FEATURES_SECTION).
+    pub features: Vec<Feature>,
+    /// Signatures of the split points that the module defines, also listed
+    /// in its [`SIGNATURES_SECTION`](crate::signatures::SIGNATURES_SECTION).
+    /// Empty for the main module.
+    pub signatures: Signatures,
+    /// Indirect function table slots filled in by this module, along with the
+    /// function placed in each.
+    pub table_slots: Vec<(usize, InputFuncId)>,
+    /// Empty table slots reserved with `#[wasm_split(table_slots = ...)]`,
+    /// which are part of the main module's table. Empty for other modules.
+    pub reserved_table_slots: Range<usize>,
+}
+
+/// How the output modules lay out and fill the indirect function table.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TableOptions {
+    /// Empty slots at the end of the table, reserved with
+    /// `#[wasm_split(table_slots = ...)]`.
+    pub reserved_slots: usize,
+    /// The runtime's guard fault handler, with `--guard-calls`.
+    pub guard_fault_func: Option<InputFuncId>,
+    /// Whether the main module fills the slots of the other modules with
+    /// [`OutputFunctionKind::LazyStub`]s, with `--lazy-indirect-calls`.
+    pub lazy_indirect_calls: bool,
+}
+
 pub fn emit_modules(
     module: &InputModule,
     program_info: &SplitProgramInfo,
+    loader_module: &str,
+    table: &TableOptions,
+    source_map_urls: Option<&[String]>,
+    cache: Option<&Cache>,
     emit_fn: &dyn Fn(usize, &[u8]) -> anyhow::Result<()>,
-) -> anyhow::Result<()> {
-    // For now we will ignore data symbols because that simplifies things quite a bit.
+) -> anyhow::Result<Vec<EmittedModule>> {
+    let emit_state = EmitState::new(module, program_info, table)?;
+
+    // Modules are independent of each other once the table layout is fixed,
+    // so they are generated in parallel, and then handed to `emit_fn` in
+    // order.
+    let phase = tracing::Span::current();
+    let generated = (0..program_info.output_modules.len())
+        .into_par_iter()
+        .map(|output_module_index| -> Result<(Vec<u8>, EmittedModule)> {
+            let mut emit_state = ModuleEmitState::new(
+                module,
+                &emit_state,
+                output_module_index,
+                program_info,
+                loader_module,
+            );
+            let identifier = &program_info.output_modules[output_module_index].0;
+            let span = tracing::debug_span!(
+                parent: &phase,
+                "encode",
+                module = identifier.name(),
+                bytes = tracing::field::Empty,
+            )
+            .entered();
+
+            emit_state
+                .generate()
+                .with_context(|| format!("Error generating {:?}", identifier))?;
+            let unfinished = emit_state.output_module.as_slice();
+            let features = cache::cached(cache, "features", &[unfinished], || {
+                features::detect(unfinished)
+            })?;
+            if !features.is_empty() {
+                emit_state
+                    .output_module
+                    .section(&features::section(&features));
+            }
+            let signatures = if emit_state.is_main() {
+                Signatures::new()
+            } else {
+                signatures::get_signatures(
+                    module,
+                    program_info.output_modules[output_module_index]
+                        .1
+                        .split_points
+                        .iter(),
+                )?
+            };
+            if !signatures.is_empty() {
+                emit_state
+                    .output_module
+                    .section(&signatures::section(&signatures));
+            }
+            if let Some(urls) = source_map_urls {
+                emit_state
+                    .output_module
+                    .section(&crate::source_map::section(&urls[output_module_index]));
+            }
 
-    let emit_state = EmitState::new(module, program_info)?;
+            let data = emit_state.output_module.as_slice();
+            span.record("bytes", data.len());
+            let digest = ModuleDigest::new(data, cache)?;
+            let emitted = EmittedModule {
+                defined_functions: emit_state.defined_function_range(),
+                functions: emit_state
+                    .output_functions
+                    .iter()
+                    .map(|func| func.input_func_id)
+                    .collect(),
+                size: data.len(),
+                gzip_size: digest.gzip_size,
+                hash: content_hash(data),
+                sha256: digest.sha256,
+                integrity: digest.integrity,
+                compressed_sizes: BTreeMap::new(),
+                optimized_names: None,
+                features,
+                signatures,
+                table_slots: emit_state.table_slots(),
+                reserved_table_slots: if emit_state.is_main() {
+                    emit_state.emit_state.reserved_table_slots.clone()
+                } else {
+                    0..0
+                },
+            };
+            Ok((emit_state.output_module.finish(), emitted))
+        })
+        .collect::<Result<Vec<_>>>()?;
 
-    for output_module_index in 0..program_info.output_modules.len() {
-        let mut emit_state =
-            ModuleEmitState::new(module, &emit_state, output_module_index, program_info);
+    let mut emitted_modules = Vec::new();
+    for (output_module_index, (data, emitted)) in generated.into_iter().enumerate() {
         let identifier = &program_info.output_modules[output_module_index].0;
+        emit_fn(output_module_index, &data)
+            .with_context(|| format!("Error emitting {:?}", identifier))?;
+        emitted_modules.push(emitted);
+    }
 
-        emit_state
-            .generate()
-            .with_context(|| format!("Error generating {:?}", identifier))?;
+    Ok(emitted_modules)
+}
 
-        emit_fn(output_module_index, emit_state.output_module.as_slice())
-            .with_context(|| format!("Error emitting {:?}", identifier))?;
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+
+    use crate::{
+        read::InputModule,
+        symbols::{demangle, get_function_locations},
+        test_fixtures::{
+            expected_no_std_app_result, fixture_path, split, try_split, SplitOutput,
+            CLOSURE_APP_RESULTS,
+        },
+    };
+
+    #[test]
+    fn calls_closures_created_in_split_functions() {
+        let output = split("closure_app.wasm", &[]);
+        output.validate();
+        assert_eq!(
+            output.wasm_files(),
+            ["counter.wasm", "main.wasm", "scale.wasm", "totals.wasm"]
+        );
+
+        if let Some(results) = output.run_closure_app() {
+            assert_eq!(results, CLOSURE_APP_RESULTS);
+        }
+    }
+
+    #[test]
+    fn reports_the_chunk_that_panicked() {
+        let output = split("closure_app_panic.wasm", &[]);
+        let data = output.read("checked.wasm");
+        assert_eq!(
+            InputModule::parse(&data).unwrap().names.module,
+            Some("checked")
+        );
+
+        // Streamed chunks are named by their URL in stack traces, and those
+        // compiled from their bytes by the module name.
+        for content_type in ["application/wasm", "application/octet-stream"] {
+            if let Some(report) = output.run_closure_app_panic(content_type) {
+                assert!(
+                    report.starts_with("panic in split chunk \"checked\" "),
+                    "{content_type}: {report}"
+                );
+                assert!(report.ends_with("-2 is not positive"), "{report}");
+            }
+        }
+    }
+
+    #[test]
+    fn keeps_the_futures_of_async_split_functions_in_their_chunk() {
+        let output = split("closure_app.wasm", &[]);
+        let functions = |file: &str| {
+            let data = output.read(file);
+            let module = InputModule::parse(&data).unwrap();
+            module
+                .names
+                .functions
+                .values()
+                .map(|name| demangle(name))
+                .collect::<Vec<_>>()
+        };
+        // Those of the body, of the task that it spawns, and of the async
+        // function that it awaits JS promises with.
+        let is_state_machine = |name: &str| {
+            (name.starts_with("closure_app::sum_doubled::{closure#0}::__wasm_split_")
+                && name.ends_with("{closure#0}"))
+                || name == "closure_app::doubled::{closure#0}"
+        };
+        let totals = functions("totals.wasm");
+        assert_eq!(
+            totals.iter().filter(|name| is_state_machine(name)).count(),
+            3,
+            "{totals:#?}"
+        );
+        assert!(!functions("main.wasm")
+            .iter()
+            .any(|name| is_state_machine(name)));
+    }
+
+    #[test]
+    fn lazy_stubs_load_the_chunk_of_a_slot_on_the_first_call() {
+        let output = split(
+            "no_std_app.wasm",
+            &["--target", "node", "--lazy-indirect-calls"],
+        );
+        output.validate();
+        if let Some(result) = output.run_no_std_app_export_without_fetch("run_lazy_pointer", 5) {
+            assert_eq!(result, 8 * 5 + 1000);
+        }
+        if let Some(result) = output.run_no_std_app_without_fetch(5) {
+            assert_eq!(result, expected_no_std_app_result(5));
+        }
+
+        // The main module keeps the functions it takes the address of.
+        let output = split("no_std_app.wasm", &[]);
+        if let Some(result) = output.run_no_std_app_export("run_lazy_pointer", 5) {
+            assert_eq!(result, 8 * 5);
+        }
+    }
+
+    #[test]
+    fn guarded_calls_report_the_chunk_that_is_not_loaded() {
+        let options = ["--target", "node", "--lazy-indirect-calls", "--guard-calls"];
+        let (_output, result) = try_split("no_std_app.wasm", "", &options);
+        assert!(format!("{:#}", result.unwrap_err())
+            .contains("--guard-calls requires the input to export __wasm_split_guard_fault"));
+
+        let output = split("no_std_app_guarded.wasm", &options);
+        output.validate();
+        if let Some(result) = output.run_no_std_app_without_fetch(5) {
+            assert_eq!(result, expected_no_std_app_result(5));
+        }
+        if let Some(result) = output.run_no_std_app_export_without_fetch("run_lazy_pointer", 5) {
+            assert_eq!(result, 8 * 5 + 1000);
+        }
+
+        // The stub of `quadruple` fails to load `second`, and calls the
+        // guard's handler rather than the empty slot.
+        std::fs::remove_file(output.dir.join("second.wasm")).unwrap();
+        if let Some(result) = output.try_run_no_std_app_export_without_fetch("run_lazy_pointer", 5)
+        {
+            let stderr = result.unwrap_err();
+            assert!(
+                stderr.contains("quadruple of chunk \"second\", which is not loaded"),
+                "{stderr}"
+            );
+        }
+    }
+
+    /// Calls of indirect stubs, i.e. of functions that only call a table
+    /// slot with their arguments, in the modules of `output`.
+    fn indirect_stub_calls(output: &SplitOutput) -> usize {
+        let mut calls = 0;
+        for file in output.wasm_files() {
+            let data = output.read(&file);
+            let mut num_imported_funcs = 0;
+            let mut bodies = Vec::new();
+            for payload in wasmparser::Parser::new(0).parse_all(&data) {
+                match payload.unwrap() {
+                    wasmparser::Payload::ImportSection(imports) => {
+                        num_imported_funcs += imports
+                            .into_iter()
+                            .filter(|import| {
+                                matches!(import.as_ref().unwrap().ty, wasmparser::TypeRef::Func(_))
+                            })
+                            .count();
+                    }
+                    wasmparser::Payload::CodeSectionEntry(body) => {
+                        bodies.push(
+                            body.get_operators_reader()
+                                .unwrap()
+                                .into_iter()
+                                .collect::<Result<Vec<_>, _>>()
+                                .unwrap(),
+                        );
+                    }
+                    _ => {}
+                }
+            }
+            let is_stub = |operators: &[wasmparser::Operator]| {
+                use wasmparser::Operator::*;
+                let n = operators.len();
+                n >= 3
+                    && matches!(operators[n - 3], I32Const { .. })
+                    && matches!(operators[n - 2], CallIndirect { table_index: 0, .. })
+                    && operators[..n - 3]
+                        .iter()
+                        .all(|operator| matches!(operator, LocalGet { .. }))
+            };
+            let stubs = bodies
+                .iter()
+                .enumerate()
+                .filter(|(_, operators)| is_stub(operators))
+                .map(|(i, _)| (num_imported_funcs + i) as u32)
+                .collect::<HashSet<_>>();
+            calls += bodies
+                .iter()
+                .flatten()
+                .filter(|operator| {
+                    matches!(operator, wasmparser::Operator::Call { function_index } if stubs.contains(function_index))
+                })
+                .count();
+        }
+        calls
+    }
+
+    #[test]
+    fn calls_functions_of_other_modules_with_a_single_indirect_call() {
+        let output = split("no_std_app.wasm", &[]);
+        output.validate();
+        assert_eq!(indirect_stub_calls(&output), 0);
+        if let Some(result) = output.run_no_std_app(5) {
+            assert_eq!(result, expected_no_std_app_result(5));
+        }
+        // The stubs stay in place where moving code would break DWARF.
+        let output = split("no_std_app_debug.wasm", &[]);
+        assert!(indirect_stub_calls(&output) > 0);
+    }
+
+    #[test]
+    fn orders_the_functions_of_chunks_by_symbol_name() {
+        let output = split("no_std_app.wasm", &[]);
+        for chunk in output.manifest()["chunks"].as_array().unwrap() {
+            let defined = &chunk["defined_functions"];
+            let defined =
+                defined["start"].as_u64().unwrap() as u32..defined["end"].as_u64().unwrap() as u32;
+            let mut names = Vec::new();
+            for payload in
+                wasmparser::Parser::new(0).parse_all(&output.read(chunk["file"].as_str().unwrap()))
+            {
+                if let wasmparser::Payload::CustomSection(section) = payload.unwrap() {
+                    if section.name() != "name" {
+                        continue;
+                    }
+                    let reader =
+                        wasmparser::NameSectionReader::new(section.data(), section.data_offset());
+                    for name in reader {
+                        if let wasmparser::Name::Function(map) = name.unwrap() {
+                            names.extend(
+                                map.into_iter()
+                                    .map(|naming| naming.unwrap())
+                                    .filter(|naming| defined.contains(&naming.index))
+                                    .map(|naming| naming.name.to_string()),
+                            );
+                        }
+                    }
+                }
+            }
+            assert!(!names.is_empty(), "{chunk}");
+            assert!(names.is_sorted(), "{chunk}: {names:?}");
+        }
+    }
+
+    #[test]
+    fn debug_sections_map_the_code_of_every_module_to_its_source_lines() {
+        let input_data = std::fs::read(fixture_path("no_std_app_debug.wasm")).unwrap();
+        let input = InputModule::parse(&input_data).unwrap();
+        let input_locations = get_function_locations(&input)
+            .unwrap()
+            .into_iter()
+            .map(|(func_id, location)| (input.names.functions[&func_id], location))
+            .collect::<HashMap<_, _>>();
+
+        let output = split("no_std_app_debug.wasm", &[]);
+        output.validate();
+        for file in output.wasm_files() {
+            let data = output.read(&file);
+            let module = InputModule::parse(&data).unwrap();
+            let locations = get_function_locations(&module).unwrap();
+            assert!(!locations.is_empty(), "{file} has no line information");
+            for (func_id, location) in locations {
+                let name = module.names.functions[&func_id];
+                assert_eq!(
+                    Some(&location),
+                    input_locations.get(name),
+                    "{name} of {file}"
+                );
+            }
+        }
+        if let Some(result) = output.run_no_std_app(4) {
+            assert_eq!(result, expected_no_std_app_result(4));
+        }
+    }
+
+    #[test]
+    fn exceptions_unwind_from_chunks_into_the_main_module() {
+        let output = split(
+            "eh_app.wasm",
+            &["--target", "node", "--lazy-indirect-calls"],
+        );
+        // wasmparser does not validate the `try` blocks of the fixture, of
+        // the legacy version of the proposal, which Node does on running it.
+        let imports_tag = |file: &str| {
+            wasmparser::Parser::new(0)
+                .parse_all(&output.read(file))
+                .any(|payload| match payload.unwrap() {
+                    wasmparser::Payload::ImportSection(imports) => imports
+                        .into_iter()
+                        .any(|import| matches!(import.unwrap().ty, wasmparser::TypeRef::Tag(_))),
+                    _ => false,
+                })
+        };
+        assert!(!imports_tag("main.wasm"));
+        assert!(imports_tag("unwind.wasm"));
+        let manifest = output.manifest();
+        for chunk in manifest["chunks"].as_array().unwrap() {
+            assert_eq!(chunk["features"], serde_json::json!(["exception-handling"]));
+        }
+        // `boom` throws for 7, which `run` catches.
+        if let Some(result) = output.run_no_std_app_without_fetch(2) {
+            assert_eq!(result, 2);
+        }
+        if let Some(result) = output.run_no_std_app_without_fetch(7) {
+            assert_eq!(result, 1007);
+        }
+    }
+
+    #[test]
+    fn chunks_import_the_shared_memory_from_the_main_module() {
+        let output = split("threads_app.wasm", &["--target", "node"]);
+        output.validate();
+        let memory_imports = |file: &str| {
+            wasmparser::Parser::new(0)
+                .parse_all(&output.read(file))
+                .filter_map(|payload| match payload.unwrap() {
+                    wasmparser::Payload::ImportSection(imports) => Some(imports),
+                    _ => None,
+                })
+                .flatten()
+                .map(|import| import.unwrap())
+                .filter_map(|import| match import.ty {
+                    wasmparser::TypeRef::Memory(memory) => Some((
+                        import.module.to_string(),
+                        import.name.to_string(),
+                        memory.shared,
+                    )),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            memory_imports("main.wasm"),
+            [("env".to_string(), "memory".to_string(), true)]
+        );
+        assert_eq!(
+            memory_imports("add.wasm"),
+            [("__wasm_split".to_string(), "memory".to_string(), true)]
+        );
+        let manifest = output.manifest();
+        for chunk in manifest["chunks"].as_array().unwrap() {
+            assert!(
+                chunk["features"]
+                    .as_array()
+                    .unwrap()
+                    .contains(&"atomics".into()),
+                "{chunk}"
+            );
+        }
+        // The worker's main module and chunk add to the counter that the main
+        // thread's do.
+        if let Some(result) = output.run_threads_app() {
+            assert_eq!(result, "2,7,10");
+        }
     }
 
-    Ok(())
+    #[test]
+    fn chunks_import_only_the_globals_they_refer_to() {
+        /// Names of the globals that `data` imports, those of them that its
+        /// code refers to, and the names of the globals it exports.
+        fn globals(data: &[u8]) -> (Vec<String>, BTreeSet<String>, BTreeSet<String>) {
+            let mut imported = Vec::new();
+            let mut referenced = BTreeSet::new();
+            let mut exported = BTreeSet::new();
+            for payload in wasmparser::Parser::new(0).parse_all(data) {
+                match payload.unwrap() {
+                    wasmparser::Payload::ImportSection(imports) => {
+                        for import in imports {
+                            let import = import.unwrap();
+                            if let wasmparser::TypeRef::Global(_) = import.ty {
+                                imported.push(import.name.to_string());
+                            }
+                        }
+                    }
+                    wasmparser::Payload::ExportSection(exports) => {
+                        for export in exports {
+                            let export = export.unwrap();
+                            if export.kind == wasmparser::ExternalKind::Global {
+                                exported.insert(export.name.to_string());
+                            }
+                        }
+                    }
+                    wasmparser::Payload::CodeSectionEntry(body) => {
+                        for operator in body.get_operators_reader().unwrap() {
+                            let (wasmparser::Operator::GlobalGet { global_index }
+                            | wasmparser::Operator::GlobalSet { global_index }) = operator.unwrap()
+                            else {
+                                continue;
+                            };
+                            if let Some(name) = imported.get(global_index as usize) {
+                                referenced.insert(name.clone());
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            (imported, referenced, exported)
+        }
+
+        let output = split("no_std_app.wasm", &[]);
+        output.validate();
+        let mut imported_by_chunks = BTreeSet::new();
+        let mut chunks_without_globals = Vec::new();
+        for file in output.wasm_files() {
+            let (imported, referenced, exported) = globals(&output.read(&file));
+            if file == "main.wasm" {
+                assert!(imported.is_empty(), "{imported:?}");
+                assert_eq!(exported, BTreeSet::from(["__stack_pointer".to_string()]));
+                continue;
+            }
+            assert!(exported.is_empty(), "{file}: {exported:?}");
+            assert_eq!(
+                imported.iter().cloned().collect::<BTreeSet<_>>(),
+                referenced,
+                "{file}"
+            );
+            assert_eq!(imported.len(), referenced.len(), "{file}: {imported:?}");
+            if imported.is_empty() {
+                chunks_without_globals.push(file);
+            }
+            imported_by_chunks.extend(imported);
+        }
+        // The main module exports the globals that some chunk imports.
+        assert_eq!(
+            imported_by_chunks,
+            BTreeSet::from(["__stack_pointer".to_string()])
+        );
+        // Code without a stack frame needs no stack pointer.
+        assert_eq!(
+            chunks_without_globals,
+            ["details.wasm", "first_second.wasm", "squares.wasm"]
+        );
+        if let Some(result) = output.run_no_std_app(3) {
+            assert_eq!(result, expected_no_std_app_result(3));
+        }
+    }
+
+    #[test]
+    fn chunks_import_the_other_tables_after_the_indirect_function_table() {
+        let output = split(
+            "refs_app.wasm",
+            &["--target", "node", "--lazy-indirect-calls"],
+        );
+        output.validate();
+        let tables = |file: &str| {
+            let mut tables = Vec::new();
+            for payload in wasmparser::Parser::new(0).parse_all(&output.read(file)) {
+                match payload.unwrap() {
+                    wasmparser::Payload::ImportSection(imports) => {
+                        for import in imports {
+                            let import = import.unwrap();
+                            if let wasmparser::TypeRef::Table(ty) = import.ty {
+                                tables.push((import.name.to_string(), ty.element_type));
+                            }
+                        }
+                    }
+                    wasmparser::Payload::TableSection(reader) => {
+                        for table in reader {
+                            tables.push((String::new(), table.unwrap().ty.element_type));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            tables
+        };
+        let types = |tables: Vec<(String, wasmparser::RefType)>| {
+            tables.into_iter().map(|(_, ty)| ty).collect::<Vec<_>>()
+        };
+        let expected = [
+            wasmparser::RefType::FUNCREF,
+            wasmparser::RefType::EXTERNREF,
+            wasmparser::RefType::FUNCREF,
+        ];
+        assert_eq!(types(tables("main.wasm")), expected);
+        let chunk_tables = tables("keep.wasm");
+        assert_eq!(
+            chunk_tables
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>(),
+            ["__indirect_function_table", "externrefs", "callbacks"]
+        );
+        assert_eq!(types(chunk_tables), expected);
+        if let Some(result) = output.run_no_std_app_without_fetch(2) {
+            assert_eq!(result, 1006);
+        }
+    }
+
+    #[test]
+    fn drops_table_slots_of_functions_that_no_module_defines() {
+        let output = split(
+            "dead_data_app.wasm",
+            &["--target", "node", "--lazy-indirect-calls"],
+        );
+        output.validate();
+        let manifest = output.manifest();
+        let table = manifest["table"].as_array().unwrap();
+        let symbols = table
+            .iter()
+            .map(|slot| slot["symbol"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert!(symbols.contains(&"triple"), "{symbols:?}");
+        assert!(!symbols.contains(&"unused"), "{symbols:?}");
+        assert_eq!(
+            table
+                .iter()
+                .map(|slot| slot["slot"].as_u64().unwrap())
+                .collect::<Vec<_>>(),
+            (1..=table.len() as u64).collect::<Vec<_>>()
+        );
+        if let Some(result) = output.run_no_std_app_without_fetch(2) {
+            assert_eq!(result, 6);
+        }
+    }
+
+    #[test]
+    fn duplicating_everything_leaves_modules_without_table_slots() {
+        let output = split("no_std_app.wasm", &["--duplicate-threshold", "1000000"]);
+        output.validate();
+        assert!(output.wasm_files().contains(&"second.wasm".to_string()));
+        if let Some(result) = output.run_no_std_app(5) {
+            assert_eq!(result, expected_no_std_app_result(5));
+        }
+    }
 }
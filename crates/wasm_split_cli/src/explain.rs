@@ -0,0 +1,300 @@
+//! `wasm-split analyze --explain NAME`: which chunk the code of a function or
+//! crate ends up in, and why.
+//!
+//! The input is split as `wasm-split` would split it with the same
+//! thresholds, without writing anything. For each function named `NAME`, or
+//! of the crate `NAME`, the report lists its chunk, its retained size as in
+//! the `suggest` report, and the calls through which the chunk reaches it:
+//! from an export, the start function or a function that wasm-bindgen needs
+//! for the main module, and from a split function for other chunks. Code in
+//! a chunk shared by several split modules is explained from each of them.
+//! This is what to look at when code is in the main module that should not
+//! be, since cutting any of the calls of its chain may move it out.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+
+use crate::{
+    analyze::get_retention,
+    config::ByteSize,
+    deny::{format_node, get_crate_name},
+    dep_graph::{self, DepNode},
+    metadata,
+    read::{InputFuncId, InputModule},
+    split_point::{self, ChunkingOptions, SplitModuleIdentifier},
+    symbols::{demangle, Demangling},
+};
+
+#[derive(Debug, Serialize)]
+pub struct Explanation {
+    pub query: String,
+    /// Code of the matching functions in each chunk, largest first.
+    pub chunks: Vec<ChunkShare>,
+    /// The largest matching functions, largest first.
+    pub functions: Vec<ExplainedFunction>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChunkShare {
+    pub chunk: String,
+    pub functions: usize,
+    pub bytes: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExplainedFunction {
+    pub function: String,
+    pub bytes: usize,
+    pub retained_bytes: usize,
+    pub chunk: String,
+    /// Other chunks with a copy of the function, with `--duplicate-threshold`.
+    pub duplicated_into: Vec<String>,
+    /// Chains of calls that reach the function, each from the function up to
+    /// a root of its chunk. Shared chunks have one for each of their modules.
+    pub reached_from: Vec<Vec<String>>,
+}
+
+/// Whether the function `name` is one the query asks for, by its mangled or
+/// demangled name or its crate.
+fn matches(query: &str, name: &str) -> bool {
+    let demangled = demangle(name);
+    name == query
+        || demangled == query
+        || Demangling::Compact.demangle(name) == query
+        || get_crate_name(&demangled) == query.replace('-', "_")
+}
+
+/// Chain of `parents` from `node` up to a node without a parent.
+fn chain(
+    module: &InputModule,
+    parents: &HashMap<DepNode, DepNode>,
+    mut node: DepNode,
+    demangling: Demangling,
+) -> Vec<String> {
+    let mut chain = vec![format_node(module, &node, demangling)];
+    while let Some(parent) = parents.get(&node) {
+        // Parents of a breadth-first search cannot form a cycle, but stop at
+        // any revisited node rather than trusting that.
+        if chain.len() > parents.len() {
+            break;
+        }
+        chain.push(format_node(module, parent, demangling));
+        node = *parent;
+    }
+    chain
+}
+
+pub fn explain(
+    module: &InputModule,
+    query: &str,
+    top: usize,
+    options: &ChunkingOptions,
+    demangling: Demangling,
+) -> Result<Explanation> {
+    let imported = module.imported_funcs.len();
+    let matching = (imported..imported + module.defined_funcs.len())
+        .filter(|func_id| {
+            module
+                .names
+                .functions
+                .get(func_id)
+                .is_some_and(|name| matches(query, name))
+        })
+        .collect::<Vec<InputFuncId>>();
+    if matching.is_empty() {
+        bail!("No function or crate of the input is named {query:?}");
+    }
+    if !module.relocs.contains_key(&module.code_section_index) {
+        bail!(
+            "--explain splits the input, which requires relocations. Link with \
+             `-C link-arg=--emit-relocs`, and explain the input of wasm-bindgen rather than its \
+             output."
+        );
+    }
+
+    let split_points = split_point::get_split_points(module)?;
+    let module_aliases =
+        metadata::get_module_aliases(&metadata::get_split_module_metadata(module)?);
+    let on_load_hooks = split_point::get_on_load_hooks(module, &split_points, &module_aliases)?;
+    let dep_graph = dep_graph::get_dependencies(module)?;
+    let program_info = split_point::compute_split_modules(
+        module,
+        &dep_graph,
+        &split_points,
+        &on_load_hooks,
+        options,
+    )?;
+    let retention = get_retention(module)?;
+    let main_symbols = program_info
+        .output_modules
+        .iter()
+        .find(|(identifier, _)| *identifier == SplitModuleIdentifier::Main)
+        .map(|(_, info)| info.included_symbols.clone())
+        .unwrap_or_default();
+    // Parents of the breadth-first search from the split functions of each
+    // split module, for chains into shared chunks, computed as needed.
+    let mut split_module_parents = HashMap::<String, HashMap<DepNode, DepNode>>::new();
+
+    let mut chunks = BTreeMap::<String, (usize, usize)>::new();
+    let mut functions = Vec::new();
+    for &func_id in matching.iter() {
+        let node = DepNode::Function(func_id);
+        let bytes = retention.sizes[func_id - imported];
+        let Some(&output_index) = program_info.symbol_output_module.get(&node) else {
+            // Unreachable code, which the linker usually removes.
+            continue;
+        };
+        let (identifier, info) = &program_info.output_modules[output_index];
+        let share = chunks.entry(identifier.name()).or_default();
+        share.0 += 1;
+        share.1 += bytes;
+        let reached_from = match identifier.dependents() {
+            Some(names) => names
+                .iter()
+                .map(|name| {
+                    let parents = split_module_parents.entry(name.clone()).or_insert_with(|| {
+                        let roots = split_points
+                            .iter()
+                            .filter(|split_point| split_point.module_name == *name)
+                            .map(|split_point| DepNode::Function(split_point.export_func))
+                            .collect::<HashSet<_>>();
+                        split_point::find_reachable_deps(&dep_graph, &roots, &main_symbols).parents
+                    });
+                    chain(module, parents, node, demangling)
+                })
+                .collect(),
+            None => vec![chain(module, &info.parents, node, demangling)],
+        };
+        functions.push(ExplainedFunction {
+            function: format_node(module, &node, demangling),
+            bytes,
+            retained_bytes: retention.retained[func_id - imported],
+            chunk: identifier.name(),
+            duplicated_into: program_info
+                .output_modules
+                .iter()
+                .filter(|(_, info)| info.duplicated_funcs.contains(&func_id))
+                .map(|(identifier, _)| identifier.name())
+                .collect(),
+            reached_from,
+        });
+    }
+    functions.sort_by(|a, b| b.bytes.cmp(&a.bytes).then(a.function.cmp(&b.function)));
+    functions.truncate(top);
+    let mut chunks = chunks
+        .into_iter()
+        .map(|(chunk, (functions, bytes))| ChunkShare {
+            chunk,
+            functions,
+            bytes,
+        })
+        .collect::<Vec<_>>();
+    chunks.sort_by(|a, b| b.bytes.cmp(&a.bytes).then(a.chunk.cmp(&b.chunk)));
+    Ok(Explanation {
+        query: query.to_string(),
+        chunks,
+        functions,
+    })
+}
+
+pub fn print_explanation(explanation: &Explanation) {
+    println!("Code of {:?} by chunk:", explanation.query);
+    for share in explanation.chunks.iter() {
+        println!(
+            "  {:>10}  {} ({} functions)",
+            ByteSize(share.bytes),
+            share.chunk,
+            share.functions
+        );
+    }
+    for function in explanation.functions.iter() {
+        println!(
+            "\n{} ({}, retaining {}) is in chunk {}",
+            function.function,
+            ByteSize(function.bytes),
+            ByteSize(function.retained_bytes),
+            function.chunk
+        );
+        if !function.duplicated_into.is_empty() {
+            println!("  with copies in {}", function.duplicated_into.join(", "));
+        }
+        for chain in function.reached_from.iter() {
+            let Some(root) = chain.last() else {
+                continue;
+            };
+            println!("  reached from {root}:");
+            println!("      {}", chain[0]);
+            for caller in chain[1..].iter() {
+                println!("      <== {caller}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::explain;
+    use crate::{
+        read::InputModule, split_point::ChunkingOptions, symbols::Demangling,
+        test_fixtures::fixture_path,
+    };
+
+    #[test]
+    fn explains_chunk_of_function() {
+        let wasm = std::fs::read(fixture_path("no_std_app.wasm")).unwrap();
+        let module = InputModule::parse(&wasm).unwrap();
+        let explanation = explain(
+            &module,
+            "no_std_app::sum_of_squares",
+            10,
+            &ChunkingOptions::default(),
+            Demangling::Full,
+        )
+        .unwrap();
+        let [function] = &explanation.functions[..] else {
+            panic!("{explanation:?}");
+        };
+        // Both split modules call it, so it is in their shared chunk, and
+        // reached from each of them.
+        assert_eq!(function.chunk, "first_second");
+        assert!(function.retained_bytes >= function.bytes);
+        let [first, second] = &function.reached_from[..] else {
+            panic!("{function:?}");
+        };
+        for (chain, name) in [(first, "first"), (second, "second")] {
+            assert_eq!(chain[0], "no_std_app::sum_of_squares");
+            let root = chain.last().unwrap();
+            assert!(
+                root.starts_with(&format!("__wasm_split_00{name}00_export")),
+                "{chain:?}"
+            );
+        }
+
+        let explanation = explain(
+            &module,
+            "no_std_app",
+            3,
+            &ChunkingOptions::default(),
+            Demangling::Full,
+        )
+        .unwrap();
+        assert!(explanation.functions.len() <= 3);
+        assert!(explanation.chunks.iter().any(|share| share.chunk == "main"));
+        assert!(explanation
+            .chunks
+            .iter()
+            .any(|share| share.chunk == "first"));
+
+        let err = explain(
+            &module,
+            "nothing",
+            3,
+            &ChunkingOptions::default(),
+            Demangling::Full,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("\"nothing\""), "{err}");
+    }
+}
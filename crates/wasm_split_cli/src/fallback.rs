@@ -0,0 +1,228 @@
+//! The monolithic fallback of `--emit-fallback`: the main module with every
+//! split module folded into it, for the loader to start the app with on
+//! networks whose proxies block or mangle the requests of other `.wasm`
+//! files, so that it needs no chunks at all; see `fallback-after` of
+//! `[loader]`.
+//!
+//! The fallback also lets developers rule out the split when users report a
+//! bug, without a redeploy: a page opened with `?wasm_split=off` starts with
+//! the fallback, and so do later pages of the origin, as the loader keeps the
+//! switch in local storage until a page is opened with `?wasm_split=on`.
+//! Apps built with wasm-bindgen pass `mainModuleUrl()` of the loader to
+//! `init` for either to take effect.
+//!
+//! The fallback is emitted from the same input and with the same loader as
+//! the split build, so that the loader can instantiate either. Its table
+//! slots differ, so the loader is given the `on_load` hooks of its folded
+//! modules separately from those of the manifest.
+
+use anyhow::{bail, Result};
+
+use crate::{
+    cache::Cache,
+    config::Config,
+    dep_graph::DepGraph,
+    emit,
+    js_entries::{self, EntrySlots},
+    manifest::{FoldedModule, Manifest},
+    metadata::SplitModuleMetadata,
+    read::{InputFuncId, InputModule},
+    split_point::{self, ChunkingOptions, OnLoadHook, SplitModuleIdentifier, SplitPoint},
+};
+
+pub const FALLBACK_FILENAME: &str = "wasm-split-fallback.wasm";
+
+pub struct Fallback {
+    pub main: Vec<u8>,
+    /// Every split module, as folded into `main`.
+    pub folded: Vec<FoldedModule>,
+    /// Slots of the split functions in `main`, for `--emit-js-entries`.
+    pub entry_slots: Option<EntrySlots>,
+}
+
+/// Options of the split build.
+pub struct FallbackInput<'a> {
+    pub module: &'a InputModule<'a>,
+    pub dep_graph: &'a DepGraph,
+    pub split_points: &'a [SplitPoint],
+    pub on_load_hooks: &'a [OnLoadHook],
+    pub chunking_options: &'a ChunkingOptions,
+    pub metadata: &'a SplitModuleMetadata,
+    pub config: &'a Config,
+    pub loader_module: &'a str,
+    pub reserved_table_slots: usize,
+    pub guard_fault_func: Option<InputFuncId>,
+    pub cache: Option<&'a Cache>,
+}
+
+pub fn emit_fallback(input: &FallbackInput) -> Result<Fallback> {
+    // Hoisted modules are folded regardless of their size, and without the
+    // report of each that the fold threshold prints.
+    let hoisted_modules = input
+        .split_points
+        .iter()
+        .map(|split_point| split_point.module_name.clone())
+        .chain(
+            input
+                .chunking_options
+                .auto_split_crates
+                .iter()
+                .map(|crate_name| split_point::auto_split_module_name(crate_name)),
+        )
+        .chain(
+            input
+                .chunking_options
+                .entries
+                .iter()
+                .map(|(entry, _)| split_point::entry_module_name(entry)),
+        )
+        .collect();
+    let program_info = split_point::compute_split_modules(
+        input.module,
+        input.dep_graph,
+        input.split_points,
+        input.on_load_hooks,
+        &ChunkingOptions {
+            hoisted_modules,
+            ..input.chunking_options.clone()
+        },
+    )?;
+    if let Some((identifier, _)) = program_info
+        .output_modules
+        .iter()
+        .find(|(identifier, _)| *identifier != SplitModuleIdentifier::Main)
+    {
+        bail!(
+            "--emit-fallback could not fold chunk {} into the main module",
+            identifier.name()
+        );
+    }
+    let main = std::cell::RefCell::new(Vec::new());
+    let emitted_modules = emit::emit_modules(
+        input.module,
+        &program_info,
+        input.loader_module,
+        &emit::TableOptions {
+            reserved_slots: input.reserved_table_slots,
+            guard_fault_func: input.guard_fault_func,
+            lazy_indirect_calls: false,
+        },
+        None,
+        input.cache,
+        &|_, data: &[u8]| {
+            *main.borrow_mut() = data.to_vec();
+            Ok(())
+        },
+    )?;
+    let manifest = Manifest::new(
+        input.module,
+        &program_info,
+        input.metadata,
+        &emitted_modules,
+        input.config,
+        None,
+    )?;
+    let entry_slots = input
+        .chunking_options
+        .folded_split_point_slots
+        .then(|| js_entries::get_entry_slots(input.module, &program_info, &emitted_modules))
+        .transpose()?;
+    Ok(Fallback {
+        main: main.into_inner(),
+        folded: manifest.folded,
+        entry_slots,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_fixtures::{expected_no_std_app_result, split};
+
+    #[test]
+    fn folds_every_split_module() {
+        let output = split("no_std_app.wasm", &["--emit-fallback"]);
+        let manifest = output.manifest();
+        let fallback = output.read("wasm-split-fallback.wasm");
+        wasmparser::Validator::new_with_features(wasmparser::WasmFeatures::all())
+            .validate_all(&fallback)
+            .unwrap();
+        let main = output.read("main.wasm");
+        assert!(fallback.len() > main.len());
+
+        let loader = String::from_utf8(output.read("__wasm_split.js")).unwrap();
+        let folded = loader
+            .lines()
+            .find_map(|line| line.strip_prefix("const FALLBACK_FOLDED = "))
+            .unwrap();
+        let folded: Vec<serde_json::Value> =
+            serde_json::from_str(folded.trim_end_matches(';')).unwrap();
+        let mut names = folded
+            .iter()
+            .map(|module| module["name"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        names.sort();
+        let mut split_names = manifest["chunks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|chunk| chunk["kind"] == "split")
+            .map(|chunk| chunk["name"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        split_names.sort();
+        assert_eq!(names, split_names);
+        assert!(loader.contains("const FALLBACK_URL = new URL(\"./wasm-split-fallback.wasm\""));
+        // Lets `?wasm_split=off` turn splitting off.
+        assert!(loader.contains("const HAS_FALLBACK = true;"));
+
+        let output = split("no_std_app.wasm", &[]);
+        let loader = String::from_utf8(output.read("__wasm_split.js")).unwrap();
+        assert!(loader.contains("const HAS_FALLBACK = false;"));
+    }
+
+    #[test]
+    fn turns_splitting_off_until_turned_back_on() {
+        let output = split("no_std_app.wasm", &["--emit-fallback"]);
+        let queries = ["?wasm_split=off", "", "?wasm_split=on", ""];
+        let Some((result, pages)) = output.run_no_std_app_on_pages("run", 4, &queries) else {
+            return;
+        };
+        assert_eq!(result, expected_no_std_app_result(4));
+        // Turned off, splitting stays off for later pages without the query
+        // parameter, which load no chunks.
+        let fallback = pages
+            .iter()
+            .map(|page| page["fallback"].as_bool().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(fallback, [true, true, false, false]);
+        for page in &pages {
+            assert_eq!(page["result"], expected_no_std_app_result(4));
+            assert_eq!(page["warnings"], serde_json::json!([]));
+            let modules = page["modules"].as_array().unwrap();
+            if page["fallback"] == true {
+                assert_eq!(modules, &["wasm-split-fallback.wasm"]);
+            } else {
+                assert!(modules.contains(&"main.wasm".into()), "{modules:?}");
+                assert!(modules.len() > 1, "{modules:?}");
+            }
+        }
+
+        // Without a fallback, splitting stays on.
+        let output = split("no_std_app.wasm", &[]);
+        let Some((_, pages)) = output.run_no_std_app_on_pages("run", 4, &["?wasm_split=off"])
+        else {
+            return;
+        };
+        assert_eq!(pages[0]["fallback"], false);
+        assert!(pages[0]["modules"]
+            .as_array()
+            .unwrap()
+            .contains(&"main.wasm".into()));
+        assert_eq!(
+            pages[0]["warnings"],
+            serde_json::json!([
+                "wasm-split: splitting cannot be turned off, as the build has no fallback; split \
+                 it with --emit-fallback"
+            ])
+        );
+    }
+}
@@ -0,0 +1,74 @@
+// Service worker that injects faults into chunk requests, for integration
+// tests of an application's loading and error handling. Only written with
+// `--emit-test-utils`, and configured through `wasm-split-testing.js`; see
+// there for usage.
+//
+// Faults are keyed by the pathname of the chunk URL, so they apply regardless
+// of the `build` query parameter. Requests without a fault pass through.
+// Faults only live in memory, and browsers stop idle workers, so inject them
+// shortly before the loads they are meant for.
+
+const faults = new Map();
+
+self.addEventListener("install", () => self.skipWaiting());
+self.addEventListener("activate", (event) =>
+  event.waitUntil(self.clients.claim()),
+);
+
+self.addEventListener("message", (event) => {
+  if (event.data?.type !== "wasm-split-faults") return;
+  faults.clear();
+  for (const fault of event.data.faults) {
+    faults.set(fault.path, { ...fault, remaining: fault.times ?? Infinity });
+  }
+  event.ports[0]?.postMessage("ok");
+});
+
+function delay(ms) {
+  return new Promise((resolve) => setTimeout(resolve, ms));
+}
+
+async function applyFault(fault, request) {
+  switch (fault.kind) {
+    case "network":
+      // `fetch` in the page rejects with a TypeError, as if offline.
+      return Response.error();
+    case "http":
+      return new Response(null, { status: fault.status ?? 503 });
+    case "delay":
+      await delay(fault.ms ?? 1000);
+      return await fetch(request);
+    case "hang":
+      return await new Promise(() => {});
+    case "corrupt": {
+      const response = await fetch(request);
+      const bytes = new Uint8Array(await response.arrayBuffer());
+      // The default offset clobbers the magic number, which is guaranteed to
+      // fail compilation; later offsets may only change the code.
+      const offset = Math.min(fault.offset ?? 0, bytes.length - 1);
+      bytes[offset] ^= 0xff;
+      return new Response(bytes, {
+        status: response.status,
+        headers: response.headers,
+      });
+    }
+    case "content-type": {
+      const response = await fetch(request);
+      const headers = new Headers(response.headers);
+      headers.set("Content-Type", fault.type ?? "application/octet-stream");
+      return new Response(response.body, {
+        status: response.status,
+        headers,
+      });
+    }
+    default:
+      throw new Error(`wasm-split: unknown fault kind ${fault.kind}`);
+  }
+}
+
+self.addEventListener("fetch", (event) => {
+  const fault = faults.get(new URL(event.request.url).pathname);
+  if (fault === undefined || fault.remaining <= 0) return;
+  fault.remaining -= 1;
+  event.respondWith(applyFault(fault, event.request));
+});
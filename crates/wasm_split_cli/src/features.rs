@@ -0,0 +1,161 @@
+//! Detection of the WebAssembly features used by each output module.
+//!
+//! A build may enable features such as SIMD for all of its code, but only use
+//! them in some chunks. The features each chunk actually uses are recorded in
+//! a [`FEATURES_SECTION`] custom section of the chunk and in its manifest
+//! entry, so that the loader only refuses the chunks that the browser cannot
+//! compile, rather than the whole build.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use wasm_encoder::{Encode, Section};
+use wasmparser::Payload;
+
+pub const FEATURES_SECTION: &str = "wasm_split_features";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Feature {
+    /// 128-bit SIMD instructions.
+    Simd,
+    /// Atomic memory instructions, which also require a shared memory.
+    Atomics,
+    /// `memory.copy`, `memory.fill` and the other bulk memory instructions.
+    BulkMemory,
+    /// Tags and the instructions throwing and catching exceptions, which
+    /// Rust code built with `panic = "unwind"` unwinds panics with.
+    ExceptionHandling,
+    /// More than one memory, which chunks import all of from the main module.
+    MultiMemory,
+}
+
+impl Feature {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Simd => "simd",
+            Self::Atomics => "atomics",
+            Self::BulkMemory => "bulk-memory",
+            Self::ExceptionHandling => "exception-handling",
+            Self::MultiMemory => "multi-memory",
+        }
+    }
+
+    /// Classifies an instruction by its encoding, which starts with a prefix
+    /// byte for all instructions of these proposals.
+    fn of_instruction(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [0xfd, ..] => Some(Self::Simd),
+            [0xfe, ..] => Some(Self::Atomics),
+            // `memory.init` through `table.copy`. The other `0xfc`
+            // instructions are non-trapping conversions and table
+            // instructions of the reference types proposal.
+            [0xfc, 8..=14, ..] => Some(Self::BulkMemory),
+            // `try`, `throw`, `rethrow`, `throw_ref` and `try_table`. The
+            // `catch` and `delegate` of `try` blocks need their `try`.
+            [0x06 | 0x08 | 0x09 | 0x0a | 0x1f, ..] => Some(Self::ExceptionHandling),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the features used by the code of the module `data`, in a stable
+/// order.
+pub fn detect(data: &[u8]) -> Result<Vec<Feature>> {
+    let mut features = Vec::new();
+    let mut num_memories = 0;
+    for payload in wasmparser::Parser::new(0).parse_all(data) {
+        let body = match payload? {
+            Payload::CodeSectionEntry(body) => body,
+            // Chunks import the tags of the main module, even those that
+            // only call code throwing them.
+            Payload::TagSection(_) => {
+                features.push(Feature::ExceptionHandling);
+                continue;
+            }
+            Payload::ImportSection(imports) => {
+                for import in imports {
+                    match import?.ty {
+                        wasmparser::TypeRef::Tag(_) => features.push(Feature::ExceptionHandling),
+                        wasmparser::TypeRef::Memory(memory) => {
+                            num_memories += 1;
+                            // Chunks of apps with threads import the shared
+                            // memory, which browsers without threads support
+                            // refuse, even without atomic instructions.
+                            if memory.shared {
+                                features.push(Feature::Atomics);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                continue;
+            }
+            Payload::MemorySection(memories) => {
+                for memory in memories {
+                    num_memories += 1;
+                    if memory?.shared {
+                        features.push(Feature::Atomics);
+                    }
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        for operator in body.get_operators_reader()?.into_iter_with_offsets() {
+            let (_, offset) = operator?;
+            if let Some(feature) = Feature::of_instruction(&data[offset..]) {
+                if !features.contains(&feature) {
+                    features.push(feature);
+                }
+            }
+        }
+    }
+    if num_memories > 1 {
+        features.push(Feature::MultiMemory);
+    }
+    features.sort();
+    features.dedup();
+    Ok(features)
+}
+
+/// Custom section listing `features`, separated by commas.
+pub fn section(features: &[Feature]) -> wasm_encoder::CustomSection<'static> {
+    let names = features
+        .iter()
+        .map(|feature| feature.as_str())
+        .collect::<Vec<_>>()
+        .join(",");
+    wasm_encoder::CustomSection {
+        name: FEATURES_SECTION.into(),
+        data: names.into_bytes().into(),
+    }
+}
+
+/// Appends the [`FEATURES_SECTION`] to the encoded module `data`, unless it
+/// uses none of the features, and returns them.
+pub fn add_section(data: &mut Vec<u8>) -> Result<Vec<Feature>> {
+    let features = detect(data)?;
+    if !features.is_empty() {
+        let section = section(&features);
+        data.push(section.id());
+        section.encode(data);
+    }
+    Ok(features)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{detect, Feature};
+
+    #[test]
+    fn detects_multiple_memories() {
+        let header = [0, 97, 115, 109, 1, 0, 0, 0];
+        let imported = [2, 8, 1, 1, b'e', 1, b'm', 2, 0, 1];
+        let defined = [5, 3, 1, 0, 1];
+        assert_eq!(detect(&[&header[..], &defined].concat()).unwrap(), []);
+        assert_eq!(
+            detect(&[&header[..], &imported, &defined].concat()).unwrap(),
+            [Feature::MultiMemory]
+        );
+    }
+}
@@ -0,0 +1,365 @@
+//! `wasm-split graph`: the graph of the chunks of a build, from its
+//! manifest, as Graphviz DOT or as a single HTML page with no dependencies,
+//! for reviewing how modules are grouped:
+//!
+//! ```sh
+//! wasm-split graph pkg | dot -Tsvg > chunks.svg
+//! wasm-split graph pkg --format html --out chunks.html
+//! ```
+//!
+//! The main module loads each split module's chunk, which loads the shared
+//! chunks of its `dependencies` first. With `--input`, the module that was
+//! split, the graph also shows the calls and function references from the
+//! code of one chunk into a shared chunk, which are what put that code in a
+//! shared chunk rather than in the chunk of a single module. These come from
+//! splitting the input again with the thresholds given, as for
+//! `analyze --explain`, so they only match the build if it was split with
+//! the same ones.
+
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+    path::PathBuf,
+};
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+
+use crate::{
+    config::ByteSize,
+    deny::format_node,
+    dep_graph::{self, DepNode},
+    manifest::{ChunkKind, Manifest},
+    metadata,
+    read::InputModule,
+    size_diff::read_build,
+    split_point::{self, ChunkingOptions},
+    symbols::Demangling,
+};
+
+/// Calls shown for each pair of chunks, those of the largest callees first.
+const MAX_CALLS_PER_EDGE: usize = 10;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum GraphFormat {
+    /// Graphviz DOT, to render with `dot`.
+    #[default]
+    Dot,
+    /// An interactive HTML page.
+    Html,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct GraphArgs {
+    /// Manifest or output directory of the build.
+    build: PathBuf,
+
+    /// The module that the build was split from, to also show the calls into
+    /// shared chunks. Requires relocations.
+    #[arg(long, value_name = "PATH")]
+    input: Option<PathBuf>,
+
+    /// `--fold-threshold` that the build was split with.
+    #[arg(
+        long,
+        value_name = "BYTES",
+        default_value_t = crate::DEFAULT_FOLD_THRESHOLD,
+        requires = "input"
+    )]
+    fold_threshold: usize,
+
+    /// `--duplicate-threshold` that the build was split with.
+    #[arg(long, value_name = "BYTES", requires = "input")]
+    duplicate_threshold: Option<usize>,
+
+    #[arg(long, value_enum, default_value_t)]
+    format: GraphFormat,
+
+    /// File to write the graph to. Defaults to stdout.
+    #[arg(long, value_name = "PATH")]
+    out: Option<PathBuf>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChunkGraph {
+    pub chunks: Vec<GraphChunk>,
+    /// Which chunks load which, from the main module to the split modules and
+    /// from those to their shared chunks.
+    pub edges: Vec<GraphEdge>,
+    /// Calls from the code of a chunk into a shared chunk, with `--input`.
+    pub calls: Vec<GraphCall>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GraphChunk {
+    pub name: String,
+    pub kind: ChunkKind,
+    pub size: usize,
+    /// Split functions of the chunk, by export name.
+    pub entries: Vec<String>,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+pub struct GraphEdge {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct GraphCall {
+    pub from: String,
+    pub to: String,
+    pub caller: String,
+    pub callee: String,
+}
+
+/// The graph of the chunks of `manifest`.
+pub fn get_chunk_graph(manifest: &Manifest) -> ChunkGraph {
+    let chunks = manifest
+        .chunks
+        .iter()
+        .map(|chunk| GraphChunk {
+            name: chunk.name.clone(),
+            kind: chunk.kind,
+            size: chunk.size,
+            entries: chunk.entries.clone(),
+        })
+        .collect();
+    let mut edges = BTreeSet::new();
+    for chunk in manifest.chunks.iter() {
+        if chunk.kind == ChunkKind::Split {
+            edges.insert(GraphEdge {
+                from: "main".to_string(),
+                to: chunk.name.clone(),
+            });
+        }
+        for dependency in chunk.dependencies.iter() {
+            edges.insert(GraphEdge {
+                from: chunk.name.clone(),
+                to: dependency.clone(),
+            });
+        }
+    }
+    ChunkGraph {
+        chunks,
+        edges: edges.into_iter().collect(),
+        calls: Vec::new(),
+    }
+}
+
+/// Calls and function references from one chunk into a shared chunk, when
+/// `module` is split with `options`, for the chunks of `graph`.
+pub fn get_shared_calls(
+    module: &InputModule,
+    graph: &ChunkGraph,
+    options: &ChunkingOptions,
+    demangling: Demangling,
+) -> Result<Vec<GraphCall>> {
+    if !module.relocs.contains_key(&module.code_section_index) {
+        bail!(
+            "--input is split again to find the calls between chunks, which requires \
+             relocations; pass the module that cargo built, before wasm-bindgen"
+        );
+    }
+    let split_points = split_point::get_split_points(module)?;
+    let module_aliases =
+        metadata::get_module_aliases(&metadata::get_split_module_metadata(module)?);
+    let on_load_hooks = split_point::get_on_load_hooks(module, &split_points, &module_aliases)?;
+    let dep_graph = dep_graph::get_dependencies(module)?;
+    let program_info = split_point::compute_split_modules(
+        module,
+        &dep_graph,
+        &split_points,
+        &on_load_hooks,
+        options,
+    )?;
+
+    let imported = module.imported_funcs.len();
+    let size = |node: &DepNode| match *node {
+        DepNode::Function(func_id) if func_id >= imported => {
+            module.defined_funcs[func_id - imported].body.range().len()
+        }
+        _ => 0,
+    };
+    // By pair of chunks, the calls and the size of their callee.
+    let mut calls = BTreeMap::<(String, String), BTreeSet<(Reverse<usize>, String, String)>>::new();
+    for (caller, callees) in dep_graph.iter() {
+        let DepNode::Function(_) = caller else {
+            continue;
+        };
+        let Some(&caller_index) = program_info.symbol_output_module.get(caller) else {
+            continue;
+        };
+        let (caller_chunk, caller_info) = &program_info.output_modules[caller_index];
+        for callee in callees.iter() {
+            let &DepNode::Function(callee_id) = callee else {
+                continue;
+            };
+            let Some(&callee_index) = program_info.symbol_output_module.get(callee) else {
+                continue;
+            };
+            let callee_chunk = &program_info.output_modules[callee_index].0;
+            if callee_index == caller_index
+                || callee_chunk.dependents().is_none()
+                || caller_info.duplicated_funcs.contains(&callee_id)
+            {
+                continue;
+            }
+            let (from, to) = (caller_chunk.name(), callee_chunk.name());
+            if !graph.chunks.iter().any(|chunk| chunk.name == from)
+                || !graph.chunks.iter().any(|chunk| chunk.name == to)
+            {
+                continue;
+            }
+            calls.entry((from, to)).or_default().insert((
+                Reverse(size(callee)),
+                format_node(module, caller, demangling),
+                format_node(module, callee, demangling),
+            ));
+        }
+    }
+    Ok(calls
+        .into_iter()
+        .flat_map(|((from, to), calls)| {
+            calls
+                .into_iter()
+                .take(MAX_CALLS_PER_EDGE)
+                .map(move |(_, caller, callee)| GraphCall {
+                    from: from.clone(),
+                    to: to.clone(),
+                    caller,
+                    callee,
+                })
+        })
+        .collect())
+}
+
+/// Quoted DOT identifier or label, of `lines`.
+fn quote(lines: &[&str]) -> String {
+    let lines = lines
+        .iter()
+        .map(|line| line.replace('\\', "\\\\").replace('"', "\\\""))
+        .collect::<Vec<_>>();
+    format!("\"{}\"", lines.join("\\n"))
+}
+
+/// The graph as Graphviz DOT, with the calls as dashed edges.
+pub fn render_dot(graph: &ChunkGraph) -> String {
+    let mut dot = String::from(
+        "digraph chunks {\n  rankdir=LR;\n  node [shape=box, fontname=\"sans-serif\"];\n",
+    );
+    for chunk in graph.chunks.iter() {
+        let style = match chunk.kind {
+            ChunkKind::Main => ", style=filled, fillcolor=\"#c6dbef\"",
+            ChunkKind::Split => "",
+            ChunkKind::Shared => ", style=dashed",
+        };
+        writeln!(
+            dot,
+            "  {} [label={}{style}];",
+            quote(&[&chunk.name]),
+            quote(&[&chunk.name, &ByteSize(chunk.size).to_string()])
+        )
+        .unwrap();
+    }
+    for edge in graph.edges.iter() {
+        writeln!(dot, "  {} -> {};", quote(&[&edge.from]), quote(&[&edge.to])).unwrap();
+    }
+    for call in graph.calls.iter() {
+        writeln!(
+            dot,
+            "  {} -> {} [style=dashed, color=\"#888888\", fontsize=9, label={}];",
+            quote(&[&call.from]),
+            quote(&[&call.to]),
+            quote(&[&format!("{} -> {}", call.caller, call.callee)])
+        )
+        .unwrap();
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+/// The page, with the graph embedded in it.
+pub fn render_html(graph: &ChunkGraph) -> Result<String> {
+    // Keeps function names such as `<T as Trait>` from closing the script.
+    let data = serde_json::to_string(graph)?.replace("</", "<\\/");
+    Ok(include_str!("graph.html").replacen("/*GRAPH*/null", &data, 1))
+}
+
+pub fn run(args: &GraphArgs, demangling: Demangling) -> Result<()> {
+    let mut graph = get_chunk_graph(&read_build(&args.build)?);
+    if let Some(input) = args.input.as_deref() {
+        let wasm = std::fs::read(input).with_context(|| format!("Failed to read {input:?}"))?;
+        let module = InputModule::parse(&wasm)?;
+        let options = ChunkingOptions {
+            fold_threshold: args.fold_threshold,
+            duplicate_threshold: args.duplicate_threshold,
+            ..Default::default()
+        };
+        graph.calls = get_shared_calls(&module, &graph, &options, demangling)?;
+    }
+    let output = match args.format {
+        GraphFormat::Dot => render_dot(&graph),
+        GraphFormat::Html => render_html(&graph)?,
+    };
+    match args.out.as_deref() {
+        Some(out) => {
+            std::fs::write(out, output).with_context(|| format!("Failed to write {out:?}"))?
+        }
+        None => print!("{output}"),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_chunk_graph, get_shared_calls, render_dot, render_html, GraphEdge};
+    use crate::{
+        read::InputModule,
+        size_diff::read_build,
+        split_point::ChunkingOptions,
+        symbols::Demangling,
+        test_fixtures::{fixture_path, split},
+    };
+
+    #[test]
+    fn graphs_chunks_and_calls_into_shared_chunks() {
+        let output = split("no_std_app.wasm", &[]);
+        let mut graph = get_chunk_graph(&read_build(&output.dir).unwrap());
+        let edge = |from: &str, to: &str| GraphEdge {
+            from: from.to_string(),
+            to: to.to_string(),
+        };
+        assert!(graph.edges.contains(&edge("main", "first")));
+        assert!(graph.edges.contains(&edge("first", "first_second")));
+        assert!(graph.edges.contains(&edge("second", "first_second")));
+        assert!(!graph.edges.contains(&edge("main", "first_second")));
+
+        let wasm = std::fs::read(fixture_path("no_std_app.wasm")).unwrap();
+        let module = InputModule::parse(&wasm).unwrap();
+        let options = ChunkingOptions {
+            fold_threshold: 0,
+            ..Default::default()
+        };
+        graph.calls = get_shared_calls(&module, &graph, &options, Demangling::Full).unwrap();
+        assert!(graph
+            .calls
+            .iter()
+            .any(|call| call.to == "first_second" && call.callee == "no_std_app::sum_of_squares"));
+        for call in graph.calls.iter() {
+            assert!(
+                graph.edges.contains(&edge(&call.from, &call.to)),
+                "{call:?}"
+            );
+        }
+
+        let dot = render_dot(&graph);
+        assert!(dot.starts_with("digraph chunks {"));
+        assert!(dot.contains("  \"main\" -> \"first\";\n"));
+        assert!(dot.contains("no_std_app::sum_of_squares"));
+        let html = render_html(&graph).unwrap();
+        assert!(html.contains("\"name\":\"first_second\""));
+        assert!(!html.contains("/*GRAPH*/"));
+    }
+}
@@ -0,0 +1,198 @@
+//! JS entry points of split modules, written with `--emit-js-entries` to
+//! `js-entries` of `[output]` for hand-written JS, such as the wrapper of a
+//! web component, to call split functions without going through Rust:
+//!
+//! ```js
+//! import { draw } from "./wasm-split-entries/chart.js";
+//!
+//! const total = await draw(40);
+//! ```
+//!
+//! Each split module gets a JS module exporting its `#[wasm_split]`
+//! functions under their Rust names, along with a `.d.ts` file declaring
+//! them. The first call loads the module, as a call from Rust would, and
+//! every call then goes through the function's slot in the indirect function
+//! table, with the wasm signature of its `extern "C"` export: numbers for
+//! `i32`, `f32` and `f64`, and `BigInt`s for `i64`. Other Rust types are
+//! passed as the C ABI lays them out, e.g. a `&str` as a pointer into the
+//! memory of the main module and a length, so the entry points suit
+//! functions of numbers best. The main module must have been instantiated
+//! before the first call.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{anyhow, Result};
+use wasmparser::ValType;
+
+use crate::{
+    emit::EmittedModule,
+    read::InputModule,
+    split_point::{get_split_function_name, SplitProgramInfo},
+};
+
+/// Table slots of the split functions of each split module, by function
+/// name, as the loader looks them up for the entry points.
+pub type EntrySlots = BTreeMap<String, BTreeMap<String, usize>>;
+
+/// [`EntrySlots`] of the split functions of `program_info`, which all have a
+/// table slot in one of `emitted_modules`, including those of folded modules
+/// as split with `folded_split_point_slots`.
+///
+/// Split functions that share their name with another of their split module,
+/// such as the instantiations of a generic function or methods of different
+/// types, cannot be told apart by JS, and get no entry point.
+pub fn get_entry_slots(
+    module: &InputModule,
+    program_info: &SplitProgramInfo,
+    emitted_modules: &[EmittedModule],
+) -> Result<EntrySlots> {
+    let mut slots = EntrySlots::new();
+    let mut ambiguous = BTreeSet::new();
+    for (_, info) in program_info.output_modules.iter() {
+        for split_point in info.split_points.iter() {
+            let name = get_split_function_name(module, split_point);
+            let slot = emitted_modules
+                .iter()
+                .flat_map(|emitted| emitted.table_slots.iter())
+                .find(|&&(_, func_id)| func_id == split_point.export_func)
+                .map(|&(slot, _)| slot)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Split function {name} of split module {} has no table slot for its JS \
+                         entry point",
+                        split_point.module_name
+                    )
+                })?;
+            if slots
+                .entry(split_point.module_name.clone())
+                .or_default()
+                .insert(name.clone(), slot)
+                .is_some()
+            {
+                ambiguous.insert((split_point.module_name.clone(), name));
+            }
+        }
+    }
+    for (module_name, name) in ambiguous {
+        println!(
+            "Warning: split module {module_name} has several split functions named {name}, \
+             which get no JS entry point; rename them to call them from JS"
+        );
+        let module_slots = slots.get_mut(&module_name).unwrap();
+        module_slots.remove(&name);
+        if module_slots.is_empty() {
+            slots.remove(&module_name);
+        }
+    }
+    Ok(slots)
+}
+
+/// The JS module and `.d.ts` file of the entry points of `module_name`, those
+/// of its split functions in `slots`, importing the loader from `loader_url`.
+pub fn render_entries(
+    module: &InputModule,
+    program_info: &SplitProgramInfo,
+    module_name: &str,
+    slots: &EntrySlots,
+    loader_url: &str,
+) -> (String, String) {
+    let mut functions = program_info
+        .output_modules
+        .iter()
+        .flat_map(|(_, info)| info.split_points.iter())
+        .filter(|split_point| split_point.module_name == module_name)
+        .map(|split_point| {
+            let func_type = &module.types[module.func_type_id(split_point.export_func)];
+            (get_split_function_name(module, split_point), func_type)
+        })
+        .filter(|(name, _)| slots[module_name].contains_key(name))
+        .collect::<Vec<_>>();
+    functions.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let module_literal = serde_json::to_string(module_name).unwrap();
+    let mut js = format!(
+        "// Entry points of split module {module_name}, written by wasm-split.\n\
+         import {{ callEntry }} from {};\n",
+        serde_json::to_string(loader_url).unwrap()
+    );
+    let mut dts =
+        format!("// Entry points of split module {module_name}, written by wasm-split.\n");
+    for (name, func_type) in functions {
+        let params = (0..func_type.params().len())
+            .map(|index| format!("arg{index}"))
+            .collect::<Vec<_>>();
+        js.push_str(&format!(
+            "\nexport function {name}({params}) {{\n  return callEntry({module_literal}, \"{name}\", [{params}]);\n}}\n",
+            params = params.join(", ")
+        ));
+        let typed_params = params
+            .iter()
+            .zip(func_type.params())
+            .map(|(param, ty)| format!("{param}: {}", ts_type(*ty)))
+            .collect::<Vec<_>>();
+        let result = match func_type.results() {
+            [] => "void",
+            [ty] => ts_type(*ty),
+            _ => "unknown[]",
+        };
+        dts.push_str(&format!(
+            "\n/** Loads split module `{module_name}` on the first call, then calls `{name}`. */\n\
+             export function {name}({}): Promise<{result}>;\n",
+            typed_params.join(", ")
+        ));
+    }
+    (js, dts)
+}
+
+/// TypeScript type of a wasm value as passed to and from JS.
+fn ts_type(ty: ValType) -> &'static str {
+    match ty {
+        ValType::I32 | ValType::F32 | ValType::F64 => "number",
+        ValType::I64 => "bigint",
+        ValType::V128 | ValType::Ref(_) => "unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_fixtures::split;
+
+    #[test]
+    fn calls_split_functions_from_js() {
+        let output = split("no_std_app.wasm", &["--emit-js-entries"]);
+        output.validate();
+        let js = String::from_utf8(output.read("wasm-split-entries/second.js")).unwrap();
+        assert!(js.contains("import { callEntry } from \"../__wasm_split.js\";"));
+        assert!(js.contains(
+            "export function second(arg0) {\n  return callEntry(\"second\", \"second\", [arg0]);\n}"
+        ));
+        // JS cannot pick an instantiation of the generic `sum_as`.
+        assert!(js.contains("export function apply_twice("));
+        assert!(!js.contains("sum_as"));
+        let dts = String::from_utf8(output.read("wasm-split-entries/second.d.ts")).unwrap();
+        assert!(dts.contains("export function second(arg0: number): Promise<number>;"));
+        let loader = String::from_utf8(output.read("__wasm_split.js")).unwrap();
+        assert!(!loader.contains("const JS_ENTRY_SLOTS = {};"));
+        if let Some(result) = output.call_no_std_app_js_entry("second", "second", 5) {
+            assert_eq!(result, 3 * 5 + 5 * 5);
+        }
+    }
+
+    #[test]
+    fn calls_split_functions_of_folded_modules() {
+        let output = split(
+            "no_std_app.wasm",
+            &["--emit-js-entries", "--fold-threshold", "256"],
+        );
+        output.validate();
+        let manifest = output.manifest();
+        assert!(manifest["folded"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|folded| folded["name"] == "details"));
+        if let Some(result) = output.call_no_std_app_js_entry("details", "details", 4) {
+            assert_eq!(result, 4 + 1);
+        }
+    }
+}
@@ -0,0 +1,242 @@
+//! `wasm-split leptos`: splits the hydration module of a site that
+//! cargo-leptos built, after `cargo leptos build`.
+//!
+//! cargo-leptos builds the hydration module into its own target directory,
+//! `target/front`, and writes what wasm-bindgen makes of it to the `pkg`
+//! directory of the site as `<output-name>.js` and `<output-name>.wasm`,
+//! which the server's `HydrationScripts` load. The module in `target/front`
+//! still has the relocations that splitting needs, so it is split instead,
+//! and its main module processed by wasm-bindgen into the same two files,
+//! with the chunks, the loader and the other outputs of the split next to
+//! them. The server can thus load the split app without changes, and send
+//! the chunks of each route as preload hints with `wasm_split_server`:
+//!
+//! ```ignore
+//! let hints = PreloadHints::load(
+//!     format!("{}/{}", leptos_options.site_root, leptos_options.site_pkg_dir),
+//!     &format!("/{}", leptos_options.site_pkg_dir),
+//! )?;
+//! ```
+//!
+//! Build tools can split a site in process with [`split_leptos_site`]. Sites
+//! built with `hash-files` are not supported, as the server then expects the
+//! hashes of the files that cargo-leptos wrote.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+
+#[derive(Debug, clap::Args)]
+pub struct LeptosArgs {
+    /// Root directory of the site. Defaults to `LEPTOS_SITE_ROOT`, or else
+    /// `target/site`.
+    #[arg(long, value_name = "DIR")]
+    site_root: Option<PathBuf>,
+
+    /// Directory of the app within the site root. Defaults to
+    /// `LEPTOS_SITE_PKG_DIR`, or else `pkg`.
+    #[arg(long, value_name = "DIR")]
+    site_pkg_dir: Option<PathBuf>,
+
+    /// Name of the app's JS and wasm files. Defaults to
+    /// `LEPTOS_OUTPUT_NAME`, or else the name of the only pair of them in
+    /// the site's pkg directory.
+    #[arg(long, value_name = "NAME")]
+    output_name: Option<String>,
+
+    /// Hydration module to split, as built by cargo. Defaults to the one
+    /// that cargo-leptos built in `target/front`.
+    #[arg(long, value_name = "PATH")]
+    input: Option<PathBuf>,
+
+    /// Split the module of `cargo leptos build --release`, rather than of a
+    /// debug build.
+    #[arg(long)]
+    release: bool,
+
+    /// wasm-bindgen binary to run, which must be of the version that the app
+    /// depends on.
+    #[arg(long, value_name = "PATH", default_value = "wasm-bindgen")]
+    wasm_bindgen: PathBuf,
+
+    /// Options of the split, after `--`, such as `-- --fold-threshold 0`.
+    #[arg(last = true, value_name = "SPLIT_OPTIONS")]
+    split_options: Vec<String>,
+}
+
+/// A site built by cargo-leptos, as split by [`split_leptos_site`].
+#[derive(Debug, Clone)]
+pub struct LeptosSite {
+    /// As `site-root` of cargo-leptos, such as `target/site`.
+    pub site_root: PathBuf,
+    /// As `site-pkg-dir` of cargo-leptos, such as `pkg`.
+    pub site_pkg_dir: PathBuf,
+    /// As `output-name` of cargo-leptos.
+    pub output_name: String,
+    /// The hydration module built by cargo, before wasm-bindgen.
+    pub input: PathBuf,
+    /// The wasm-bindgen binary to process the main module with.
+    pub wasm_bindgen: PathBuf,
+}
+
+pub fn run(args: &LeptosArgs) -> Result<()> {
+    let from_env = |name| std::env::var_os(name).map(PathBuf::from);
+    let site_root = args
+        .site_root
+        .clone()
+        .or_else(|| from_env("LEPTOS_SITE_ROOT"))
+        .unwrap_or_else(|| PathBuf::from("target/site"));
+    let site_pkg_dir = args
+        .site_pkg_dir
+        .clone()
+        .or_else(|| from_env("LEPTOS_SITE_PKG_DIR"))
+        .unwrap_or_else(|| PathBuf::from("pkg"));
+    let output_name = match args
+        .output_name
+        .clone()
+        .or_else(|| std::env::var("LEPTOS_OUTPUT_NAME").ok())
+    {
+        Some(name) => name,
+        None => find_output_name(&site_root.join(&site_pkg_dir))?,
+    };
+    let input = args.input.clone().unwrap_or_else(|| {
+        front_module_path(
+            Path::new("target"),
+            if args.release { "release" } else { "debug" },
+            &output_name,
+        )
+    });
+    split_leptos_site(
+        &LeptosSite {
+            site_root,
+            site_pkg_dir,
+            output_name,
+            input,
+            wasm_bindgen: args.wasm_bindgen.clone(),
+        },
+        &args.split_options,
+    )
+}
+
+/// Splits the hydration module of `site` with `split_options`, the options
+/// of `wasm-split` other than its input and output, replacing the files that
+/// cargo-leptos wrote for it.
+pub fn split_leptos_site(site: &LeptosSite, split_options: &[String]) -> Result<()> {
+    let pkg_dir = site.site_root.join(&site.site_pkg_dir);
+    let name = &site.output_name;
+    for staged in [format!("{name}.js"), format!("{name}.wasm")] {
+        if !pkg_dir.join(&staged).exists() {
+            bail!(
+                "Found no {staged} in {}; was the site built by cargo-leptos, without hash-files?",
+                pkg_dir.display()
+            );
+        }
+    }
+    if split_options
+        .iter()
+        .any(|option| option.starts_with("--asset-version"))
+    {
+        bail!(
+            "--asset-version is not supported for cargo-leptos sites, whose server loads the \
+             app from the pkg directory itself"
+        );
+    }
+    if !site.input.exists() {
+        bail!(
+            "Found no hydration module at {}; build the site with cargo-leptos first, or pass \
+             the module with --input",
+            site.input.display()
+        );
+    }
+    if !crate::serve::uses_wasm_bindgen(&site.input)? {
+        bail!(
+            "{} does not use wasm-bindgen, unlike the hydration modules of Leptos",
+            site.input.display()
+        );
+    }
+
+    // The server loads the main module as `<name>.wasm` rather than with the
+    // `_bg` suffix of wasm-bindgen, so it is written there.
+    let main = format!("{name}.wasm");
+    let options = split_options
+        .iter()
+        .cloned()
+        .chain(["--output-path".into(), format!("main={main}")])
+        .chain(["--output-path".into(), format!("main-bg={main}")])
+        .collect::<Vec<_>>();
+    let output = crate::serve::split_and_bind(&options, &site.input, &pkg_dir, &site.wasm_bindgen)?;
+    println!(
+        "Split {} into {}; preload hints for the server are in {}",
+        site.input.display(),
+        pkg_dir.display(),
+        pkg_dir.join(&output.preload).display()
+    );
+    Ok(())
+}
+
+/// Where cargo-leptos builds the hydration module of `output_name` for
+/// `profile`, in the `front` directory of `target_dir`, which is named after
+/// the library crate, with underscores.
+fn front_module_path(target_dir: &Path, profile: &str, output_name: &str) -> PathBuf {
+    target_dir
+        .join("front/wasm32-unknown-unknown")
+        .join(profile)
+        .join(format!("{}.wasm", output_name.replace('-', "_")))
+}
+
+/// The output name of the app in `pkg_dir`, the only one with both a `.js`
+/// and a `.wasm` file.
+fn find_output_name(pkg_dir: &Path) -> Result<String> {
+    let mut names = std::fs::read_dir(pkg_dir)
+        .with_context(|| format!("Failed to read {}", pkg_dir.display()))?
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name().into_string().ok()?;
+            let stem = name.strip_suffix(".wasm")?;
+            pkg_dir
+                .join(format!("{stem}.js"))
+                .exists()
+                .then(|| stem.to_string())
+        })
+        .collect::<Vec<_>>();
+    match names.len() {
+        1 => Ok(names.remove(0)),
+        0 => bail!(
+            "Found no app in {}; build the site with cargo-leptos first",
+            pkg_dir.display()
+        ),
+        _ => bail!(
+            "Found several apps in {}, so pass the one to split with --output-name",
+            pkg_dir.display()
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_app_of_site() {
+        let dir = std::env::temp_dir().join(format!("wasm-split-leptos-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for file in ["my-app.js", "my-app.wasm", "my-app.css", "view_b.wasm"] {
+            std::fs::write(dir.join(file), "").unwrap();
+        }
+        assert_eq!(find_output_name(&dir).unwrap(), "my-app");
+        assert_eq!(
+            front_module_path(Path::new("target"), "release", "my-app"),
+            Path::new("target/front/wasm32-unknown-unknown/release/my_app.wasm")
+        );
+
+        let site = LeptosSite {
+            site_root: dir.parent().unwrap().to_path_buf(),
+            site_pkg_dir: PathBuf::from(dir.file_name().unwrap()),
+            output_name: "my-app".to_string(),
+            input: crate::test_fixtures::fixture_path("no_std_app.wasm"),
+            wasm_bindgen: PathBuf::from("wasm-bindgen"),
+        };
+        let error = split_leptos_site(&site, &[]).unwrap_err();
+        assert!(format!("{error}").contains("does not use wasm-bindgen"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,2899 @@
+//! The `wasm-split` splitter, as a library for build services that run it in
+//! process. [`split`] hands the split output to an [`OutputSink`], such as
+//! one that uploads to object storage, while [`run`] is the command line tool
+//! itself. [`split_wasm`] splits a module in memory, with typed
+//! [`SplitOptions`], and [`split_leptos_site`] a site that cargo-leptos
+//! built.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand};
+
+pub use compress::Encoding;
+pub use leptos::{split_leptos_site, LeptosSite};
+pub use manifest::{ChunkKind, Manifest, ManifestChunk};
+pub use options::{split_wasm, SplitOptions, SplitOutput};
+pub use sink::{DirectorySink, MemorySink, OutputSink};
+
+/// Arguments of `wasm-split`, which build services can also parse with
+/// [`Parser::parse_from`] to split with [`split`].
+#[derive(Debug, Parser)]
+#[command(
+    name = "wasm-split",
+    version,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Input .wasm file.
+    #[arg(required = true)]
+    input: Option<Box<Path>>,
+
+    /// Output directory.
+    #[arg(required = true)]
+    output: Option<Box<Path>>,
+
+    /// Configuration file. Defaults to `wasm-split.toml` in the working
+    /// directory, if it exists.
+    #[arg(long, value_name = "PATH")]
+    config: Option<Box<Path>>,
+
+    /// Write an output elsewhere than `[output]` of the config says, as
+    /// `KEY=PATH` such as `main=app.wasm`. May be given several times.
+    #[arg(long = "output-path", value_name = "KEY=PATH", value_parser = config::parse_output_path)]
+    output_paths: Vec<(String, PathBuf)>,
+
+    /// Print verbose split information, and log the phases of the split to
+    /// stderr, down to the encoding of each module.
+    #[arg(short, long)]
+    verbose: bool,
+
+    /// Log the phases of the split to stderr in this format, with the time
+    /// spent in each. Defaults to text with `--verbose`, and to no logs
+    /// otherwise.
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    log_format: Option<logging::LogFormat>,
+
+    /// Print how long each phase of the split took. Reachability analysis and
+    /// the emission of modules run on all cores, or on as many threads as
+    /// `RAYON_NUM_THREADS` says.
+    #[arg(long)]
+    timings: bool,
+
+    /// Copy functions of at most this many bytes into each split module that
+    /// calls them, rather than calling them through the main module. Defaults
+    /// to `duplicate-below` of the config's `[chunking]`.
+    #[arg(long, value_name = "BYTES")]
+    duplicate_threshold: Option<usize>,
+
+    /// Fold split modules with less than this many bytes of code back into
+    /// the main module. Defaults to `min-size` of the config's `[chunking]`,
+    /// or else 1024; 0 folds none.
+    #[arg(long, value_name = "BYTES")]
+    fold_threshold: Option<usize>,
+
+    /// Merge split modules with less than this many bytes of code into the
+    /// split module that they share the most code with, rather than loading
+    /// them as chunks of their own; see `small_chunks.rs`. Defaults to
+    /// `min-chunk-size` of the config's `[chunking]`. Requires relocations.
+    #[arg(long, value_name = "BYTES")]
+    min_chunk_size: Option<usize>,
+
+    /// Merge the smallest split modules likewise until there are at most this
+    /// many chunks besides the main module. Defaults to `max-chunks` of the
+    /// config's `[chunking]`.
+    #[arg(long, value_name = "COUNT")]
+    max_chunks: Option<usize>,
+
+    /// Move the code of this crate that startup code does not reach into a
+    /// split module of its own, `auto_<crate>`, without any `#[wasm_split]`
+    /// attributes. May be given several times, and adds to `auto-split` of
+    /// the config.
+    #[arg(long = "auto-split", value_name = "CRATE")]
+    auto_split: Vec<String>,
+
+    /// Fail if any code of this crate is in the main module, listing the
+    /// eager calls that keep it there. May be given several times, and adds
+    /// to `deny-in-main` of the config.
+    #[arg(long = "deny-eager", value_name = "CRATE")]
+    deny_eager: Vec<String>,
+
+    /// Counts of navigations between pages, exported from analytics as CSV or
+    /// JSON; see `navigation.rs`. The shared code of routes that users visit
+    /// one after another is co-located in fewer chunks. Has no effect with
+    /// `--table-only`, which creates no shared chunks.
+    #[arg(long, value_name = "PATH")]
+    navigation_flows: Option<Box<Path>>,
+
+    /// Share of the navigations away from a route that must go to another
+    /// route for their code to be co-located.
+    #[arg(long, value_name = "SHARE", default_value_t = 0.25)]
+    min_flow_share: f64,
+
+    /// Calls of split functions recorded with the `profile` feature of
+    /// wasm_split, as a JSON array of sessions or one per line; see
+    /// `profile.rs`. Split modules that most sessions call at startup are
+    /// folded into the main module, and those that sessions call together
+    /// are merged into one.
+    #[arg(long, value_name = "PATH")]
+    profile: Option<Box<Path>>,
+
+    /// Share of the profiled sessions that must call a split module within
+    /// `--profile-startup-ms` of startup for it to be folded.
+    #[arg(
+        long,
+        value_name = "SHARE",
+        default_value_t = 0.9,
+        requires = "profile"
+    )]
+    profile_hot_share: f64,
+
+    #[arg(
+        long,
+        value_name = "MS",
+        default_value_t = 1000.0,
+        requires = "profile"
+    )]
+    profile_startup_ms: f64,
+
+    /// Share of the profiled sessions calling either of two split modules
+    /// that must call both for them to be merged.
+    #[arg(
+        long,
+        value_name = "SHARE",
+        default_value_t = 0.9,
+        requires = "profile"
+    )]
+    profile_co_load_share: f64,
+
+    /// Move only the `#[wasm_split]` functions themselves into split modules,
+    /// calling everything else through the indirect function table. Does not
+    /// require the input to be linked with `--emit-relocs`, and is used
+    /// automatically if it was not.
+    #[arg(long)]
+    table_only: bool,
+
+    /// Also write a service worker and module that inject faults into chunk
+    /// requests, for integration tests of loading and error handling.
+    #[arg(long)]
+    emit_test_utils: bool,
+
+    /// Also write `fallback` of `[output]`, the main module with every split
+    /// module folded into it, which the loader starts the app with instead
+    /// once `fallback-after` of `[loader]` chunk loads failed, or on pages
+    /// opened with `?wasm_split=off`; see `fallback.rs`. Requires
+    /// relocations, so it cannot be used with `--table-only`.
+    #[arg(long)]
+    emit_fallback: bool,
+
+    /// Also write a JS module for each split module to `js-entries` of
+    /// `[output]`, with a `.d.ts` file, exporting its split functions for
+    /// hand-written JS to call, which load the module on the first call; see
+    /// `js_entries.rs`.
+    #[arg(long)]
+    emit_js_entries: bool,
+
+    /// Also write a JS module to `assets` of `[output]` that references the
+    /// chunk files as `new URL(..., import.meta.url)`, for bundlers such as
+    /// Vite and webpack to emit them as assets, and gives the loader their
+    /// URLs; see `bundler.rs`.
+    #[arg(long)]
+    emit_bundler_assets: bool,
+
+    /// Kind of JS to write the loader as: an ES module, a classic script for
+    /// wasm-bindgen's `no-modules` target, which defines the global
+    /// `__wasm_split` (see `no_modules.rs`), or an ES module for Node, which
+    /// reads chunks from the filesystem (see `node.rs`).
+    #[arg(long, value_enum, default_value_t)]
+    target: no_modules::Target,
+
+    /// Global that the glue of the main module defines with `--target
+    /// no-modules`, as given to wasm-bindgen's `--no-modules-global`.
+    #[arg(long, value_name = "NAME", default_value = "wasm_bindgen")]
+    no_modules_global: String,
+
+    /// Write a report of the crates that contributed code to each chunk,
+    /// with the licenses of their packages according to `cargo metadata` for
+    /// the given manifest.
+    #[arg(long, value_name = "CARGO_TOML")]
+    provenance: Option<Box<Path>>,
+
+    /// Write an interactive HTML treemap of the chunks as written, by crate
+    /// and function, for deciding what else to split; see `treemap.rs`.
+    #[arg(long)]
+    treemap: bool,
+
+    /// Sign the manifest with this Ed25519 private key, in PKCS#8 PEM format.
+    /// Required if the app embeds a public key with `manifest_public_key!`.
+    #[arg(long, value_name = "PEM")]
+    signing_key: Option<Box<Path>>,
+
+    /// Check the table slot of every call between modules before making it,
+    /// panicking with a `wasm_split::CrossChunkCallError` rather than
+    /// trapping if its target is not loaded. Requires the `guard-calls`
+    /// feature of wasm_split, and is meant for debug builds. Has no effect
+    /// with `--table-only`.
+    #[arg(long)]
+    guard_calls: bool,
+
+    /// Fill the table slots of the other chunks in the main module with stubs
+    /// that load the owning chunk on the first call through them, and then
+    /// make the call, and let the functions that the main module only takes
+    /// the address of, such as the methods of its `dyn Trait` objects, go
+    /// into the split modules that reach them. The stubs block on a
+    /// synchronous request, so chunks that are likely called this way should
+    /// still be preloaded. Cannot be used with --signing-key, whose digests
+    /// the loader cannot check synchronously. Has no effect with
+    /// `--table-only`.
+    #[arg(long)]
+    lazy_indirect_calls: bool,
+
+    /// Write a source map of every module next to it, as `<file>.map`, for
+    /// crash reporters that symbolicate the frames of lazily loaded chunks
+    /// with source maps, and link each module to its map with a
+    /// `sourceMappingURL` section. Made from the DWARF line tables of the
+    /// input, so it requires debug info, e.g. `debug = "line-tables-only"` in
+    /// the profile. wasm-bindgen rewrites the main module afterwards, which
+    /// leaves its map stale, but not those of the chunks. Cannot be used with
+    /// `--table-only`.
+    #[arg(long)]
+    source_maps: bool,
+
+    /// URL under which the `sourceMappingURL` sections of `--source-maps`
+    /// point to the maps, e.g. of a host that only crash reporters can
+    /// read, rather than next to each module.
+    #[arg(long, value_name = "URL", requires = "source_maps")]
+    source_map_base: Option<String>,
+
+    /// Warn about split functions that are called at startup, or only from
+    /// the code of another chunk, with how to fix each; see `lint.rs`.
+    /// Requires relocations, so it cannot be used with `--table-only`.
+    #[arg(long)]
+    lint: bool,
+
+    /// Export that the app calls at startup, whose code `--lint` checks for
+    /// calls of split functions, and `auto-split` keeps in the main module.
+    /// May be given several times. Defaults to
+    /// `main`, as exported for `#[wasm_bindgen(start)] fn main`.
+    #[arg(long = "startup-export", value_name = "NAME", default_value = "main")]
+    startup_exports: Vec<String>,
+
+    /// Routes that the app's router registers, exported by the app at build
+    /// time as a JSON array or one path per line. `--lint` then only counts
+    /// these of the configured routes as needing their modules, and warns
+    /// about the others.
+    #[arg(long, value_name = "PATH", requires = "lint")]
+    registered_routes: Option<Box<Path>>,
+
+    /// Write the build into this subdirectory of the output directory, such
+    /// as `v123`, so that the files of several deployments can coexist on a
+    /// CDN. The loader of each version only ever loads the chunks and
+    /// manifest of its own, so a session keeps the version it started with.
+    #[arg(long, value_name = "VERSION")]
+    asset_version: Option<String>,
+
+    /// Write `<link>` tags that preload the main module and the loader to
+    /// `preload-html` of `[output]`, for pages to include, with URLs below
+    /// this URL of the output directory, such as `/pkg`; see `preload.rs`.
+    #[arg(long, value_name = "BASE_URL")]
+    preload_html: Option<String>,
+
+    /// Also preload this chunk, and those it depends on, with
+    /// `--preload-html`. May be given several times.
+    #[arg(long = "preload-chunk", value_name = "NAME", requires = "preload_html")]
+    preload_chunks: Vec<String>,
+
+    /// Put the tags of `--preload-html` into the `<head>` of this page, such
+    /// as the app's `index.html`, replacing those put there before.
+    #[arg(long, value_name = "PATH", requires = "preload_html")]
+    patch_html: Option<Box<Path>>,
+
+    /// Fail if a chunk is larger than this, as `NAME=SIZE` such as
+    /// `main=350KB`, with a report of the crates that contribute the most
+    /// code to it. May be given several times, and overrides
+    /// `[budgets.chunks]` of the config.
+    #[arg(long = "budget", value_name = "NAME=SIZE", value_parser = budget::parse_chunk_budget)]
+    budgets: Vec<(String, config::ByteSize)>,
+
+    /// Also write every chunk compressed with these encodings, e.g.
+    /// `br,gzip`, as `.wasm.br` and `.wasm.gz` next to it, for static hosts
+    /// to serve without compressing on the fly; see `compress.rs`. Their
+    /// sizes are listed in the manifest.
+    #[arg(long, value_enum, value_delimiter = ',', value_name = "ENCODINGS")]
+    compress: Vec<compress::Encoding>,
+
+    /// Keep the sizes, hashes, and optimized and compressed copies of the
+    /// chunks in this directory, for later splits to reuse for the chunks
+    /// that come out the same; see `cache.rs`. Meant for watch loops, in
+    /// which most chunks do not change from one compile to the next.
+    #[arg(long, value_name = "DIR")]
+    cache: Option<Box<Path>>,
+
+    /// Make the loader listen for the rebuilds of `wasm-split serve`, which
+    /// passes this, and swap the chunks that a rebuild changed into the
+    /// running page rather than reload it, if the main module stayed the
+    /// same; see `serve.rs`. For development only.
+    #[arg(long)]
+    hot_reload: bool,
+
+    /// How reports, warnings and the symbol map display function names.
+    #[arg(long, value_enum, global = true, default_value_t)]
+    demangle: symbols::Demangling,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// List the chunk files that are new or changed in a build, compared to a
+    /// previous one, for deployments that only upload what changed.
+    PublishDiff {
+        /// Manifest of the previous build.
+        old: Box<Path>,
+
+        /// Manifest of the new build.
+        new: Box<Path>,
+
+        /// File to write the list to, one path per line. Defaults to stdout.
+        #[arg(long, value_name = "PATH")]
+        out: Option<Box<Path>>,
+    },
+    /// Compare the chunk sizes of two builds, and count the chunks whose
+    /// contents changed, from their manifests or output directories, such as
+    /// for a comment on a pull request; see `size_diff.rs`.
+    Diff {
+        /// Manifest or output directory of the previous build.
+        old: Box<Path>,
+
+        /// Manifest or output directory of the new build.
+        new: Box<Path>,
+
+        /// Write the report as a Markdown table.
+        #[arg(long)]
+        markdown: bool,
+
+        /// File to write the report to. Defaults to stdout.
+        #[arg(long, value_name = "PATH")]
+        out: Option<Box<Path>>,
+    },
+    /// Report the chunk sizes of a build, their changes since a baseline
+    /// build and its size budgets, as GitHub Actions annotations, text,
+    /// Markdown or JSON, and fail if a budget is exceeded; see `report.rs`.
+    Report(report::ReportArgs),
+    /// List the functions added, removed or resized between two versions of a
+    /// chunk, largest change first; see `diff_chunk.rs`.
+    DiffChunk {
+        /// The chunk of the previous build.
+        old: Box<Path>,
+
+        /// The chunk of the new build.
+        new: Box<Path>,
+    },
+    /// Write the graph of the chunks of a build, from the main module to the
+    /// split modules and their shared chunks, as Graphviz DOT or an HTML
+    /// page; see `graph.rs`.
+    Graph(graph::GraphArgs),
+    /// Report the size of a module by crate, and the functions to annotate
+    /// with `#[wasm_split]` to move the most code out of the main module.
+    /// Works on any module, including wasm-bindgen output without split
+    /// points; see `analyze.rs`.
+    Analyze {
+        input: Box<Path>,
+
+        /// Number of crates and suggested functions to list.
+        #[arg(long, default_value_t = 20)]
+        top: usize,
+
+        /// Also write the reports to this file as JSON.
+        #[arg(long, value_name = "PATH")]
+        json: Option<Box<Path>>,
+
+        /// Instead, explain which chunk the functions of this name, or of the
+        /// crate of this name, end up in and through which calls.
+        #[arg(long, value_name = "NAME")]
+        explain: Option<String>,
+
+        /// `--fold-threshold` of the split to explain.
+        #[arg(
+            long,
+            value_name = "BYTES",
+            default_value_t = DEFAULT_FOLD_THRESHOLD,
+            requires = "explain"
+        )]
+        fold_threshold: usize,
+
+        /// `--duplicate-threshold` of the split to explain.
+        #[arg(long, value_name = "BYTES", requires = "explain")]
+        duplicate_threshold: Option<usize>,
+    },
+    /// Write the raw public key of a signing key, for the app to embed with
+    /// `wasm_split::manifest_public_key!`.
+    PublicKey {
+        /// Ed25519 private key, in PKCS#8 PEM format.
+        signing_key: Box<Path>,
+
+        /// File to write the 32-byte public key to.
+        out: Box<Path>,
+    },
+    /// Make the glue that wasm-bindgen generated for the main module import
+    /// the JS snippets that only chunks use along with them; see
+    /// `snippets.rs`.
+    Snippets {
+        /// The glue, such as `main.js`, which is processed in place.
+        glue: Box<Path>,
+
+        /// Manifest of the split that wasm-bindgen ran on.
+        manifest: Box<Path>,
+    },
+    /// Make the glue that wasm-bindgen generated for the main module with
+    /// `--target no-modules` get the loader from its global instead of
+    /// `require`ing it; see `no_modules.rs`.
+    NoModulesGlue {
+        /// The glue, such as `main.js`, which is processed in place.
+        glue: Box<Path>,
+
+        /// Module name that the main module imports the loader by.
+        #[arg(long, value_name = "NAME", default_value = toolchain::WASM_SPLIT_JS_MODULE)]
+        loader_module: String,
+    },
+    /// Build the app with cargo, split it and run wasm-bindgen on it, serve
+    /// the result over HTTP, and rebuild whenever its sources change; see
+    /// `serve.rs`.
+    Serve(serve::ServeArgs),
+    /// Split the app that Trunk staged, as its `post_build` hook; see
+    /// `trunk.rs`.
+    Trunk(trunk::TrunkArgs),
+    /// Split the hydration module of a site built by cargo-leptos; see
+    /// `leptos.rs`.
+    Leptos(leptos::LeptosArgs),
+    /// Split the modules of several apps of a workspace, each into a
+    /// directory of its own, and report the code they duplicate in a
+    /// combined manifest; see `workspace.rs`.
+    Workspace(workspace::WorkspaceArgs),
+    /// Check that the modules of a build are valid, that the chunks link
+    /// against the main module, and that they define the split functions
+    /// with the signatures in the manifest, e.g. before deploying a build
+    /// that was assembled from several; see `signatures.rs`.
+    Verify {
+        /// Output directory of the build.
+        dir: Box<Path>,
+
+        /// Manifest of the build. Defaults to the one in the output
+        /// directory.
+        #[arg(long, value_name = "PATH")]
+        manifest: Option<Box<Path>>,
+    },
+    /// Replace this binary by a prebuilt release; see `self_update.rs`.
+    /// Hidden until releases are signed, without which it cannot install
+    /// anything.
+    #[command(hide = !self_update::AVAILABLE)]
+    SelfUpdate {
+        /// Version to install. Defaults to the one pinned by
+        /// `wasm-split-version` in `wasm-split.toml` in the working directory,
+        /// or else the latest release.
+        version: Option<String>,
+    },
+}
+
+mod analyze;
+mod budget;
+mod bundler;
+mod cache;
+mod compress;
+mod config;
+mod custom_sections;
+mod data;
+mod deny;
+mod dep_graph;
+mod diff_chunk;
+mod emit;
+mod explain;
+mod fallback;
+mod features;
+mod graph;
+mod js_entries;
+mod leptos;
+mod limits;
+mod lint;
+mod logging;
+mod manifest;
+mod metadata;
+mod navigation;
+mod no_modules;
+mod node;
+mod options;
+mod precache;
+mod preload;
+mod profile;
+mod provenance;
+mod publish_diff;
+mod read;
+mod report;
+mod self_update;
+mod serve;
+mod signatures;
+mod signing;
+mod sink;
+mod size_diff;
+mod small_chunks;
+mod snippets;
+mod source_map;
+mod split_point;
+mod symbols;
+mod table_only;
+#[cfg(test)]
+mod test_fixtures;
+mod timings;
+mod toolchain;
+mod treemap;
+mod trunk;
+mod types;
+mod validate;
+mod vendor;
+mod wasm_opt;
+mod workspace;
+
+/// `--fold-threshold` unless the config sets `[chunking] min-size`: the
+/// overhead of a chunk, below which its code costs less in the main module.
+/// Splitting off a module with next to no code adds some 550 to 900 bytes
+/// to a build after gzip, as measured on the `no_std_app` fixture: the
+/// chunk's own sections, its stub and table slot in the main module, and
+/// its entries in the manifest and the loader. Its request adds a few
+/// hundred bytes of headers and a round trip on top.
+pub(crate) const DEFAULT_FOLD_THRESHOLD: usize = 1024;
+
+/// Script to be imported by the application's service worker; see `sw.js`.
+const SERVICE_WORKER_FILENAME: &str = "wasm-split-sw.js";
+
+/// Fault injection for tests, written with `--emit-test-utils`; see
+/// `testing.js` and `fault_sw.js`.
+const TESTING_FILENAME: &str = "wasm-split-testing.js";
+const FAULT_SERVICE_WORKER_FILENAME: &str = "wasm-split-fault-sw.js";
+
+/// Replaces the literal `default`, found right after `context`, in one of
+/// the bundled scripts by a configured value, such as a path from `[output]`.
+fn replace_literal<T: serde::Serialize + ?Sized>(
+    script: &str,
+    context: &str,
+    default: &T,
+    path: &T,
+) -> String {
+    let pattern = format!("{context}{}", serde_json::to_string(default).unwrap());
+    assert!(
+        script.contains(&pattern),
+        "Script is missing the default value {pattern}"
+    );
+    script.replacen(
+        &pattern,
+        &format!("{context}{}", serde_json::to_string(path).unwrap()),
+        1,
+    )
+}
+
+/// Whether `version` names a directory directly within the output directory,
+/// which it is also part of chunk URLs with.
+fn is_version_dir(version: &str) -> bool {
+    !version.is_empty()
+        && version != "."
+        && version != ".."
+        && version
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+}
+
+/// Runs `wasm-split` with `args`, as the binary does.
+pub fn run(args: &Cli) -> Result<()> {
+    logging::init(args.verbose, args.log_format);
+    match &args.command {
+        Some(Command::PublishDiff { old, new, out }) => {
+            return publish_diff::run(old, new, out.as_deref());
+        }
+        Some(Command::Diff {
+            old,
+            new,
+            markdown,
+            out,
+        }) => {
+            return size_diff::run(old, new, *markdown, out.as_deref());
+        }
+        Some(Command::DiffChunk { old, new }) => {
+            return diff_chunk::run(old, new, args.demangle);
+        }
+        Some(Command::Analyze {
+            input,
+            top,
+            json,
+            explain,
+            fold_threshold,
+            duplicate_threshold,
+        }) => {
+            let explain = explain.as_deref().map(|query| {
+                (
+                    query,
+                    split_point::ChunkingOptions {
+                        duplicate_threshold: *duplicate_threshold,
+                        fold_threshold: *fold_threshold,
+                        ..Default::default()
+                    },
+                )
+            });
+            return analyze::run(input, *top, json.as_deref(), explain, args.demangle);
+        }
+        Some(Command::Report(report_args)) => {
+            return report::run(report_args);
+        }
+        Some(Command::Graph(graph_args)) => {
+            return graph::run(graph_args, args.demangle);
+        }
+        Some(Command::PublicKey { signing_key, out }) => {
+            return signing::write_public_key(signing_key, out);
+        }
+        Some(Command::Snippets { glue, manifest }) => {
+            return snippets::run(glue, manifest);
+        }
+        Some(Command::NoModulesGlue {
+            glue,
+            loader_module,
+        }) => {
+            return no_modules::run(glue, loader_module);
+        }
+        Some(Command::Serve(serve_args)) => {
+            return serve::run(serve_args);
+        }
+        Some(Command::Trunk(trunk_args)) => {
+            return trunk::run(trunk_args);
+        }
+        Some(Command::Leptos(leptos_args)) => {
+            return leptos::run(leptos_args);
+        }
+        Some(Command::Workspace(workspace_args)) => {
+            return workspace::run(workspace_args);
+        }
+        Some(Command::Verify { dir, manifest }) => {
+            return signatures::verify(dir, manifest.as_deref());
+        }
+        Some(Command::SelfUpdate { version }) => {
+            let version = match version {
+                Some(version) => Some(version.clone()),
+                None => config::Config::load(None)?.wasm_split_version,
+            };
+            return self_update::run(version.as_deref());
+        }
+        None => {}
+    }
+    // Required unless a subcommand is given.
+    let Some(output) = args.output.as_deref() else {
+        unreachable!();
+    };
+    let output = match args.asset_version.as_deref() {
+        Some(version) => output.join(version),
+        None => output.to_path_buf(),
+    };
+    split(args, &mut DirectorySink::new(&output))?;
+    if let Some(page) = args.patch_html.as_deref() {
+        let tags_path = output.join(load_config(args)?.output.preload_html);
+        let tags = std::fs::read_to_string(&tags_path)
+            .with_context(|| format!("Failed to read {}", tags_path.display()))?;
+        let html = std::fs::read_to_string(page)
+            .with_context(|| format!("Failed to read {}", page.display()))?;
+        std::fs::write(page, preload::patch_html(&html, &tags)?)
+            .with_context(|| format!("Failed to write {}", page.display()))?;
+    }
+    Ok(())
+}
+
+/// The config of `args`, with its `--output-path`s.
+fn load_config(args: &Cli) -> Result<config::Config> {
+    let mut config = config::Config::load(args.config.as_deref())?;
+    for (key, path) in args.output_paths.iter() {
+        config
+            .output
+            .set(key, path)
+            .with_context(|| format!("Invalid --output-path {key}={}", path.display()))?;
+    }
+    Ok(config)
+}
+
+/// Splits the input of `args`, handing the output to `sink` rather than
+/// writing it to the output directory of `args`, which is ignored. Nothing is
+/// handed over unless the split succeeds, and then every file before the
+/// manifest.
+pub fn split(args: &Cli, sink: &mut dyn OutputSink) -> Result<()> {
+    let Some(input) = args.input.as_deref() else {
+        bail!("No input module to split");
+    };
+    let input_wasm =
+        std::fs::read(input).with_context(|| format!("Failed to read {}", input.display()))?;
+    split_module(args, &input_wasm, sink)
+}
+
+/// Like [`split`], for the module `input_wasm` rather than the input of
+/// `args`.
+fn split_module(args: &Cli, input_wasm: &[u8], sink: &mut dyn OutputSink) -> Result<()> {
+    let _span = tracing::info_span!("split", input_bytes = input_wasm.len()).entered();
+    let mut timings = timings::Timings::new(args.timings);
+    let mut config = load_config(args)?;
+    config.check_version()?;
+    config.budgets.chunks.extend(args.budgets.iter().cloned());
+    config.deny_in_main.extend(args.deny_eager.iter().cloned());
+    let cache = args.cache.as_deref().map(cache::Cache::open).transpose()?;
+    if let Some(version) = args.asset_version.as_deref() {
+        if !is_version_dir(version) {
+            bail!("--asset-version must be a single directory name such as v123, not {version:?}");
+        }
+    }
+    let module = crate::read::InputModule::parse(input_wasm)?;
+    let mut split_points = split_point::get_split_points(&module)?;
+    toolchain::check_input(&module, &split_points)?;
+    signatures::check(&module, &split_points)?;
+    let mut split_module_metadata = metadata::get_split_module_metadata(&module)?;
+    metadata::check_module_aliases(&split_module_metadata, &split_points)?;
+    metadata::merge_module_groups(
+        &mut split_module_metadata,
+        &mut split_points,
+        &config.module_groups(),
+    )?;
+    let (hoisted_modules, hoisted_split_points) = match args.profile.as_deref() {
+        Some(path) => {
+            let mut split_functions = BTreeMap::<String, BTreeSet<String>>::new();
+            for split_point in split_points.iter() {
+                split_functions
+                    .entry(split_point.module_name.clone())
+                    .or_default()
+                    .insert(split_point::get_split_function_name(&module, split_point));
+            }
+            let advice = profile::get_profile_advice(
+                &profile::read_profile(path)?,
+                &split_functions,
+                &metadata::get_module_aliases(&split_module_metadata),
+                profile::ProfileThresholds {
+                    hot_share: args.profile_hot_share,
+                    startup_ms: args.profile_startup_ms,
+                    co_load_share: args.profile_co_load_share,
+                },
+            );
+            // By export function, as merging renames the modules.
+            let hoisted_split_points = split_points
+                .iter()
+                .filter(|split_point| {
+                    advice
+                        .hoisted_functions
+                        .iter()
+                        .any(|(module_name, function)| {
+                            split_point.module_name == *module_name
+                                && split_point::get_split_function_name(&module, split_point)
+                                    == *function
+                        })
+                })
+                .map(|split_point| split_point.export_func)
+                .collect();
+            metadata::merge_module_groups(
+                &mut split_module_metadata,
+                &mut split_points,
+                &advice.groups,
+            )?;
+            (advice.hoisted_modules, hoisted_split_points)
+        }
+        None => Default::default(),
+    };
+    let mut module_aliases = metadata::get_module_aliases(&split_module_metadata);
+    config.resolve_module_aliases(&module_aliases);
+    let mut on_load_hooks =
+        split_point::get_on_load_hooks(&module, &split_points, &module_aliases)?;
+    config.apply_declared_routes(&metadata::get_declared_routes(&split_module_metadata))?;
+    config.wasm_opt.check_modules(&split_points)?;
+    let signing_key = args
+        .signing_key
+        .as_deref()
+        .map(signing::read_signing_key)
+        .transpose()?;
+    signing::check_signing_key(&module, signing_key.as_ref())?;
+    if args.lazy_indirect_calls && signing_key.is_some() {
+        bail!(
+            "--lazy-indirect-calls cannot be used with --signing-key, as the loader cannot check \
+             the digest of a chunk that it loads synchronously"
+        );
+    }
+    if toolchain::uses_rust_loader(&module) {
+        if args.lazy_indirect_calls {
+            bail!(
+                "--lazy-indirect-calls cannot be used with the `rust-loader` feature of \
+                 wasm_split, as its stubs load chunks through the loader script"
+            );
+        }
+        if signing_key.is_some() {
+            bail!(
+                "--signing-key cannot be used with the `rust-loader` feature of wasm_split, \
+                 which does not check the signature of the manifest"
+            );
+        }
+        let imports = toolchain::loader_imports(&module, &split_points);
+        if imports.is_empty() {
+            println!(
+                "The main module loads its chunks with the `rust-loader` feature of \
+                 wasm_split, and does not need the loader script"
+            );
+        } else {
+            println!(
+                "The main module loads its chunks with the `rust-loader` feature of \
+                 wasm_split, but still needs the loader script for {}",
+                imports.join(", ")
+            );
+        }
+    }
+    if args.source_maps
+        && !module
+            .custom_sections
+            .iter()
+            .any(|section| section.name == ".debug_line")
+    {
+        bail!(
+            "--source-maps requires DWARF line tables in the input; build it with debug info, \
+             e.g. `debug = \"line-tables-only\"` in the profile"
+        );
+    }
+    if args.verbose {
+        for section in module
+            .custom_sections
+            .iter()
+            .filter(|section| custom_sections::is_unknown(section.name))
+        {
+            println!(
+                "Copying custom section {} of the input into the main module as is",
+                section.name
+            );
+        }
+    }
+    timings.end_phase("read");
+    let colocated_modules = match args.navigation_flows.as_deref() {
+        Some(path) => navigation::get_colocated_modules(
+            &navigation::read_transitions(path)?,
+            &config.routes,
+            args.min_flow_share,
+        ),
+        None => Vec::new(),
+    };
+    let policy = &config.chunking;
+    let chunking_options = split_point::ChunkingOptions {
+        duplicate_threshold: args
+            .duplicate_threshold
+            .or(policy.duplicate_below.map(|size| size.0)),
+        fold_threshold: args
+            .fold_threshold
+            .or(policy.min_size.map(|size| size.0))
+            .unwrap_or(DEFAULT_FOLD_THRESHOLD),
+        colocated_modules,
+        shared_strategy: policy.shared,
+        min_shared_size: policy.min_shared_size.map_or(0, |size| size.0),
+        crate_chunks: config.crate_chunks(),
+        vendor_chunks: config.vendor_chunks(),
+        auto_split_crates: config
+            .auto_split
+            .iter()
+            .chain(args.auto_split.iter())
+            .cloned()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect(),
+        startup_exports: args.startup_exports.clone(),
+        entries: config
+            .entries
+            .iter()
+            .map(|(entry, exports)| (entry.clone(), exports.clone()))
+            .collect(),
+        hoisted_modules,
+        hoisted_split_points,
+        folded_split_point_slots: args.emit_js_entries,
+        lazy_indirect_calls: args.lazy_indirect_calls,
+    };
+    let has_relocs = module.relocs.contains_key(&module.code_section_index);
+    let chunk_limits = small_chunks::ChunkLimits {
+        min_chunk_size: args
+            .min_chunk_size
+            .or(policy.min_chunk_size.map(|size| size.0))
+            .unwrap_or_default(),
+        max_chunks: args.max_chunks.or(policy.max_chunks),
+    };
+    let mut dep_graph = None;
+    let mut merged_modules = Vec::new();
+    if !chunk_limits.is_unlimited() {
+        if args.table_only || !has_relocs {
+            bail!(
+                "--min-chunk-size and --max-chunks are not supported with --table-only, which \
+                 cannot move code between split modules. Link with `-C link-arg=--emit-relocs` \
+                 and split without it."
+            );
+        }
+        let graph = dep_graph::get_dependencies(&module)?;
+        timings.end_phase("dependency graph");
+        merged_modules = small_chunks::merge_small_modules(
+            &module,
+            &graph,
+            &mut split_points,
+            &mut split_module_metadata,
+            &chunking_options,
+            chunk_limits,
+        )?;
+        for merge in merged_modules.iter() {
+            println!(
+                "Merged {} ({} of code) into {}",
+                merge.name,
+                config::ByteSize(merge.code_size),
+                merge.into
+            );
+        }
+        module_aliases = metadata::get_module_aliases(&split_module_metadata);
+        config.resolve_module_aliases(&module_aliases);
+        on_load_hooks = split_point::get_on_load_hooks(&module, &split_points, &module_aliases)?;
+        dep_graph = Some(graph);
+        timings.end_phase("merge small chunks");
+    }
+    // Files to write, by paths relative to the output directory, which may be
+    // in subdirectories with `[output]`. They are only handed to the sink once
+    // the `deny-in-main` and budget checks have passed, so that a failed check
+    // does not leave a partial build behind.
+    let outputs = RefCell::new(Vec::<(PathBuf, Vec<u8>)>::new());
+    let write_output = |path: &Path, contents: &[u8]| -> Result<()> {
+        outputs
+            .borrow_mut()
+            .push((path.to_path_buf(), contents.to_vec()));
+        Ok(())
+    };
+    // Sizes of the compressed copies of each module, by file, for the
+    // manifest.
+    let compressed_sizes = RefCell::new(BTreeMap::<String, BTreeMap<_, _>>::new());
+    // Modules optimized with `[wasm-opt]`, by file, which the manifest
+    // describes instead of the emitted ones.
+    let optimized_modules = RefCell::new(BTreeMap::<String, Vec<u8>>::new());
+    // Time spent optimizing and compressing, which happens as each module is
+    // emitted.
+    let write_time = Cell::new(Duration::ZERO);
+    let write_module =
+        |identifier: &split_point::SplitModuleIdentifier, data: &[u8]| -> Result<()> {
+            let started = Instant::now();
+            let file = config.output.module_file(identifier);
+            let optimized = config
+                .wasm_opt
+                .args_for(identifier)
+                .map(|args| {
+                    cache::cached_bytes(
+                        cache.as_ref(),
+                        "wasm-opt",
+                        &[data, args.join("\0").as_bytes()],
+                        || wasm_opt::optimize(data, args),
+                    )
+                })
+                .transpose()?;
+            let data = optimized.as_deref().unwrap_or(data);
+            for &encoding in args.compress.iter() {
+                let compressed =
+                    cache::cached_bytes(cache.as_ref(), encoding.extension(), &[data], || {
+                        encoding.compress(data)
+                    })?;
+                compressed_sizes
+                    .borrow_mut()
+                    .entry(file.clone())
+                    .or_default()
+                    .insert(encoding, compressed.len());
+                write_output(
+                    Path::new(&format!("{file}.{}", encoding.extension())),
+                    &compressed,
+                )?;
+            }
+            write_output(Path::new(&file), data)?;
+            if args.source_maps {
+                let map = cache::cached(cache.as_ref(), "source-map", &[data], || {
+                    source_map::source_map(data, &file)
+                })?;
+                if let Some(map) = map {
+                    write_output(Path::new(&format!("{file}.map")), map.as_bytes())?;
+                }
+            }
+            if let Some(optimized) = optimized {
+                optimized_modules.borrow_mut().insert(file, optimized);
+            }
+            write_time.set(write_time.get() + started.elapsed());
+            Ok(())
+        };
+    let loader_module = config.output.loader_from_main();
+    let reserved_table_slots = metadata::get_reserved_table_slots(&split_module_metadata);
+    let guard_fault_func = args
+        .guard_calls
+        .then(|| emit::get_guard_fault_func(&module))
+        .transpose()?;
+
+    if let Some((name, _)) = config
+        .chunks
+        .iter()
+        .find(|(_, group)| group.vendor == Some(config::VendorLoad::Lazy))
+    {
+        if !args.lazy_indirect_calls {
+            bail!(
+                "chunks.{name} is loaded on the first call with `vendor = \"lazy\"`, which \
+                 requires --lazy-indirect-calls"
+            );
+        }
+    }
+    if config.loader.fallback_after > 0 && !args.emit_fallback {
+        bail!("`fallback-after` of `[loader]` requires --emit-fallback");
+    }
+    let (split_program_info, mut emitted_modules, import_slots, fallback) =
+        if args.table_only || !has_relocs {
+            if !on_load_hooks.is_empty() {
+                bail!(
+                    "`#[wasm_split::on_load]` hooks are not supported with --table-only. Link \
+                 with `-C link-arg=--emit-relocs` and split without it."
+                );
+            }
+            if args.source_maps {
+                bail!(
+                    "--source-maps is not supported with --table-only, which drops the DWARF \
+                 sections. Link with `-C link-arg=--emit-relocs` and split without it."
+                );
+            }
+            if args.lint {
+                bail!(
+                    "--lint is not supported with --table-only. Link with \
+                 `-C link-arg=--emit-relocs` and split without it."
+                );
+            }
+            if args.emit_fallback {
+                bail!(
+                    "--emit-fallback is not supported with --table-only, which cannot fold split \
+                 modules into the main module. Link with `-C link-arg=--emit-relocs` and split \
+                 without it."
+                );
+            }
+            if !config.auto_split.is_empty() || !args.auto_split.is_empty() {
+                bail!(
+                    "`auto-split` is not supported with --table-only, which cannot move code \
+                 out of the main module. Link with `-C link-arg=--emit-relocs` and split \
+                 without it."
+                );
+            }
+            for (name, _) in split_module_metadata
+                .iter()
+                .filter(|(_, attributes)| !attributes.strict.is_empty())
+            {
+                println!(
+                    "Split module {name} is `strict`, which is not checked with --table-only, as \
+                 it keeps all code that the main module reaches. Link with \
+                 `-C link-arg=--emit-relocs` to check it."
+                );
+            }
+            if !chunking_options.vendor_chunks.is_empty() {
+                bail!(
+                    "Vendor chunks of [chunks] are not supported with --table-only, which cannot \
+                 move code out of the main module. Link with `-C link-arg=--emit-relocs` and \
+                 split without it."
+                );
+            }
+            if !config.entries.is_empty() {
+                bail!(
+                    "`[entries]` is not supported with --table-only, which cannot move code \
+                 out of the main module. Link with `-C link-arg=--emit-relocs` and split \
+                 without it."
+                );
+            }
+            if !args.table_only {
+                println!(
+                    "Input has no relocations, falling back to --table-only. Link with \
+                 `-C link-arg=--emit-relocs` to split out more code."
+                );
+            }
+            let split = table_only::split(
+                &module,
+                &split_points,
+                &loader_module,
+                reserved_table_slots,
+                &write_module,
+            )?;
+            deny::check_deny_in_main(
+                &module,
+                &split.program_info,
+                &config.deny_in_main,
+                args.demangle,
+            )?;
+            timings.end_phase_with(
+                "table-only split",
+                Some(("optimize and compress", write_time.get())),
+            );
+            (
+                split.program_info,
+                split.emitted_modules,
+                split.import_slots,
+                None,
+            )
+        } else {
+            let dep_graph = match dep_graph {
+                Some(dep_graph) => dep_graph,
+                None => {
+                    let dep_graph = dep_graph::get_dependencies(&module)?;
+                    timings.end_phase("dependency graph");
+                    dep_graph
+                }
+            };
+            let mut split_program_info = split_point::compute_split_modules(
+                &module,
+                &dep_graph,
+                &split_points,
+                &on_load_hooks,
+                &chunking_options,
+            )?;
+            split_program_info.merged_modules = merged_modules;
+            deny::check_deny_in_main(
+                &module,
+                &split_program_info,
+                &config.deny_in_main,
+                args.demangle,
+            )?;
+            deny::check_strict_modules(
+                &module,
+                &dep_graph,
+                &split_points,
+                &split_module_metadata,
+                &split_program_info,
+                args.demangle,
+            )?;
+            vendor::print_vendor_calls(
+                &vendor::get_vendor_calls(
+                    &module,
+                    &dep_graph,
+                    &split_program_info,
+                    &args.startup_exports,
+                    args.demangle,
+                ),
+                args.verbose,
+            );
+            timings.end_phase("reachability");
+            if args.lint {
+                let registered_routes = args
+                    .registered_routes
+                    .as_deref()
+                    .map(lint::read_registered_routes)
+                    .transpose()?;
+                lint::print_lints(&lint::get_lints(
+                    &module,
+                    &dep_graph,
+                    &dep_graph::get_function_references(&module)?,
+                    &split_points,
+                    &split_program_info,
+                    &lint::AppEntries {
+                        startup_exports: &args.startup_exports,
+                        routes: &config.routes,
+                        registered_routes: registered_routes.as_deref(),
+                    },
+                    args.demangle,
+                ));
+                timings.end_phase("lint");
+            }
+
+            if args.verbose {
+                for (name, split_deps) in split_program_info.output_modules.iter() {
+                    split_deps.print(format!("{:?}", name).as_str(), &module);
+                }
+            }
+
+            let source_map_urls = args.source_maps.then(|| {
+                split_program_info
+                    .output_modules
+                    .iter()
+                    .map(|(identifier, _)| {
+                        source_map::url(
+                            &config.output.module_file(identifier),
+                            args.source_map_base.as_deref(),
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            });
+            let emitted_modules = crate::emit::emit_modules(
+                &module,
+                &split_program_info,
+                &loader_module,
+                &emit::TableOptions {
+                    reserved_slots: reserved_table_slots,
+                    guard_fault_func,
+                    lazy_indirect_calls: args.lazy_indirect_calls,
+                },
+                source_map_urls.as_deref(),
+                cache.as_ref(),
+                &|output_module_index: usize, data: &[u8]| -> Result<()> {
+                    write_module(
+                        &split_program_info.output_modules[output_module_index].0,
+                        data,
+                    )
+                },
+            )?;
+            timings.end_phase_with("emit", Some(("optimize and compress", write_time.get())));
+            let fallback = args
+                .emit_fallback
+                .then(|| {
+                    fallback::emit_fallback(&fallback::FallbackInput {
+                        module: &module,
+                        dep_graph: &dep_graph,
+                        split_points: &split_points,
+                        on_load_hooks: &on_load_hooks,
+                        chunking_options: &chunking_options,
+                        metadata: &split_module_metadata,
+                        config: &config,
+                        loader_module: &loader_module,
+                        reserved_table_slots,
+                        guard_fault_func,
+                        cache: cache.as_ref(),
+                    })
+                })
+                .transpose()?;
+            if fallback.is_some() {
+                timings.end_phase("fallback");
+            }
+            (
+                split_program_info,
+                emitted_modules,
+                Default::default(),
+                fallback,
+            )
+        };
+    let mut compressed_sizes = compressed_sizes.into_inner();
+    let optimized_modules = optimized_modules.into_inner();
+    for ((identifier, _), emitted) in split_program_info
+        .output_modules
+        .iter()
+        .zip(emitted_modules.iter_mut())
+    {
+        let file = config.output.module_file(identifier);
+        emitted.compressed_sizes = compressed_sizes.remove(&file).unwrap_or_default();
+        if let Some(optimized) = optimized_modules.get(&file) {
+            emitted.set_optimized(optimized, cache.as_ref())?;
+        }
+    }
+    let treemap = {
+        let outputs = outputs.borrow();
+        let modules = split_program_info
+            .output_modules
+            .iter()
+            .filter_map(|(identifier, _)| {
+                let file = config.output.module_file(identifier);
+                let (_, data) = outputs.iter().find(|(path, _)| *path == Path::new(&file))?;
+                Some((identifier.name(), data.as_slice()))
+            })
+            .collect::<Vec<_>>();
+        limits::check(&modules, args.verbose)?;
+        validate::check(&modules)?;
+        budget::check_chunk_budgets(&modules, &config.budgets.chunks, args.demangle)?;
+        args.treemap
+            .then(|| treemap::render(&treemap::get_treemap(&modules, args.demangle)?))
+            .transpose()?
+    };
+    if let Some(treemap) = treemap {
+        write_output(&config.output.treemap, treemap.as_bytes())?;
+    }
+
+    let manifest = manifest::Manifest::new(
+        &module,
+        &split_program_info,
+        &split_module_metadata,
+        &emitted_modules,
+        &config,
+        args.asset_version.as_deref(),
+    )?;
+    // The chunk sizes of the manifest are those of the emitted modules.
+    budget::check_budgets(&manifest, &config.budgets)?;
+    for chunk in manifest.chunks.iter() {
+        if let Some(duplicated) = &chunk.duplicated {
+            println!(
+                "Duplicated {} functions ({} bytes) into {}",
+                duplicated.functions, duplicated.bytes, chunk.name
+            );
+        }
+    }
+    let manifest_json = serde_json::to_string_pretty(&manifest)?;
+    write_output(&config.output.manifest, manifest_json.as_bytes())?;
+    if let Some(signing_key) = &signing_key {
+        let mut signature_path = config.output.manifest.clone().into_os_string();
+        signature_path.push(".");
+        signature_path.push(signing::SIGNATURE_EXTENSION);
+        write_output(
+            Path::new(&signature_path),
+            &signing::sign(signing_key, manifest_json.as_bytes()),
+        )?;
+    }
+    write_output(
+        &config.output.preload,
+        serde_json::to_string_pretty(&preload::Preload::new(&manifest, signing_key.is_some()))?
+            .as_bytes(),
+    )?;
+    if let Some(base_url) = args.preload_html.as_deref() {
+        let tags = preload::html_tags(
+            &manifest,
+            &config.output,
+            toolchain::uses_wasm_bindgen(&module),
+            args.target,
+            &args.preload_chunks,
+            signing_key.is_some(),
+            base_url,
+        )?;
+        write_output(&config.output.preload_html, tags.as_bytes())?;
+    }
+
+    if let Some(manifest_path) = args.provenance.as_deref() {
+        let provenance = provenance::Provenance::new(
+            &module,
+            &split_program_info,
+            &manifest,
+            &emitted_modules,
+            manifest_path,
+        )?;
+        write_output(
+            &config.output.provenance,
+            serde_json::to_string_pretty(&provenance)?.as_bytes(),
+        )?;
+    }
+
+    write_output(
+        &config.output.symbols,
+        symbols::get_symbol_map(
+            &module,
+            &split_program_info,
+            &emitted_modules,
+            args.demangle,
+        )?
+        .as_bytes(),
+    )?;
+
+    let output_paths = &config.output;
+    let mut javascript = include_str!("loader.js").to_string();
+    let uses_wasm_bindgen = toolchain::uses_wasm_bindgen(&module);
+    let glue_replacements = if uses_wasm_bindgen {
+        vec![
+            ("from ", "./main.js", output_paths.main_glue_from_loader()),
+            (
+                "const WORKER_MAIN_URL = new URL(",
+                "./main_bg.wasm",
+                output_paths.main_bg_from_loader(),
+            ),
+        ]
+    } else {
+        println!(
+            "The input does not use wasm-bindgen; instantiate {main} with `instantiateMain` \
+             from {loader}.",
+            main = output_paths.main.display(),
+            loader = output_paths.loader.display(),
+        );
+        let glue_import = "import * as mainGlue from \"./main.js\";\n";
+        assert!(javascript.starts_with(glue_import));
+        javascript = javascript.replacen(glue_import, include_str!("standalone.js"), 1);
+        vec![
+            (
+                "const MAIN_URL = new URL(",
+                "./main.wasm",
+                output_paths.main_from_loader(),
+            ),
+            (
+                "const WORKER_MAIN_URL = new URL(",
+                "./main_bg.wasm",
+                output_paths.main_from_loader(),
+            ),
+        ]
+    };
+    for (context, default, path) in glue_replacements.into_iter().chain([
+        (
+            "const LOADER_MODULE = ",
+            toolchain::WASM_SPLIT_JS_MODULE,
+            loader_module.clone(),
+        ),
+        (
+            "const MANIFEST_URL = new URL(",
+            "./wasm-split-manifest.json",
+            output_paths.manifest_from_loader(),
+        ),
+        (
+            "const OUTPUT_DIR_URL = new URL(",
+            "./",
+            output_paths.output_dir_from_loader(),
+        ),
+        (
+            "const OUTPUT_DIR_FROM_MANIFEST = ",
+            "./",
+            output_paths.output_dir_from_manifest(),
+        ),
+        (
+            "const CHUNK_CACHE = ",
+            "force-cache",
+            config.loader.chunk_cache.as_str().to_string(),
+        ),
+        (
+            "const MANIFEST_CACHE = ",
+            "no-cache",
+            config.loader.manifest_cache.as_str().to_string(),
+        ),
+        (
+            "const MANIFEST_STRATEGY = ",
+            "embedded",
+            config.loader.manifest_strategy.as_str().to_string(),
+        ),
+    ]) {
+        javascript = replace_literal(&javascript, context, default, path.as_str());
+    }
+    for (context, default, value) in [
+        ("const CHUNK_RETRIES = ", 0, config.loader.retries),
+        ("const RETRY_DELAY_MS = ", 500, config.loader.retry_delay_ms),
+        (
+            "const MAX_CONCURRENT_FETCHES = ",
+            0,
+            config.loader.max_concurrent_fetches,
+        ),
+        ("const LOAD_TIMEOUT_MS = ", 0, config.loader.timeout_ms),
+    ] {
+        javascript = replace_literal(&javascript, context, &default, &value);
+    }
+    javascript = replace_literal(
+        &javascript,
+        "const REQUEST_CREDENTIALS = ",
+        &None,
+        &config
+            .loader
+            .credentials
+            .map(|credentials| credentials.as_str()),
+    );
+    javascript = replace_literal(
+        &javascript,
+        "const CACHE_STORAGE = ",
+        &false,
+        &config.loader.cache_storage,
+    );
+    javascript = replace_literal(&javascript, "const HOT_RELOAD = ", &false, &args.hot_reload);
+    if let Some(fallback) = &fallback {
+        javascript = replace_literal(&javascript, "const HAS_FALLBACK = ", &false, &true);
+        javascript = replace_literal(
+            &javascript,
+            "const FALLBACK_AFTER = ",
+            &0,
+            &config.loader.fallback_after,
+        );
+        javascript = replace_literal(
+            &javascript,
+            "const FALLBACK_URL = new URL(",
+            "./wasm-split-fallback.wasm",
+            &output_paths.fallback_from_loader(uses_wasm_bindgen),
+        );
+        // wasm-bindgen changes the module, so only a standalone one is
+        // fetched with its integrity.
+        javascript = replace_literal(
+            &javascript,
+            "const FALLBACK_INTEGRITY = ",
+            &None,
+            &(!uses_wasm_bindgen).then(|| manifest::sri_hash(&fallback.main)),
+        );
+        javascript = replace_literal(
+            &javascript,
+            "const FALLBACK_FOLDED = ",
+            &Vec::new(),
+            &fallback.folded,
+        );
+        if let Some(entry_slots) = &fallback.entry_slots {
+            javascript = replace_literal(
+                &javascript,
+                "const FALLBACK_JS_ENTRY_SLOTS = ",
+                &js_entries::EntrySlots::new(),
+                entry_slots,
+            );
+        }
+        for &encoding in args.compress.iter() {
+            let mut path = output_paths.fallback.clone().into_os_string();
+            path.push(".");
+            path.push(encoding.extension());
+            write_output(Path::new(&path), &encoding.compress(&fallback.main)?)?;
+        }
+        write_output(&output_paths.fallback, &fallback.main)?;
+    }
+    if args.emit_js_entries {
+        if args.target == no_modules::Target::NoModules {
+            bail!(
+                "--emit-js-entries writes ES modules, which --target no-modules is for apps \
+                 without; call split functions through `__wasm_split.callEntry` instead"
+            );
+        }
+        let entry_slots =
+            js_entries::get_entry_slots(&module, &split_program_info, &emitted_modules)?;
+        javascript = replace_literal(
+            &javascript,
+            "const JS_ENTRY_SLOTS = ",
+            &js_entries::EntrySlots::new(),
+            &entry_slots,
+        );
+        let loader_url = output_paths.loader_from_js_entries();
+        for module_name in entry_slots.keys() {
+            let (js, dts) = js_entries::render_entries(
+                &module,
+                &split_program_info,
+                module_name,
+                &entry_slots,
+                &loader_url,
+            );
+            let (js_path, dts_path) = output_paths.js_entries(module_name);
+            write_output(&js_path, js.as_bytes())?;
+            write_output(&dts_path, dts.as_bytes())?;
+        }
+    }
+    let inline_manifest_json = serde_json::to_string(&manifest::Manifest {
+        // Guarded calls look up the chunk and symbol of their slot, and lazy
+        // stubs the chunk to load.
+        table: if guard_fault_func.is_some() || args.lazy_indirect_calls {
+            manifest.table.clone()
+        } else {
+            Vec::new()
+        },
+        ..manifest.clone()
+    })?;
+    // Embedded as a string, so that the loader can check its signature
+    // against the exact bytes that were signed.
+    let inline_manifest_signature = match &signing_key {
+        Some(signing_key) => serde_json::to_string(
+            &signing::sign(signing_key, inline_manifest_json.as_bytes())
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect::<String>(),
+        )?,
+        None => "undefined".to_string(),
+    };
+    javascript.push_str(
+        format!(
+            "const MANIFEST_JSON = {json};\nconst MANIFEST = JSON.parse(MANIFEST_JSON);\n\
+             const MANIFEST_SIGNATURE = {inline_manifest_signature};\n",
+            json = serde_json::to_string(&inline_manifest_json)?,
+        )
+        .as_str(),
+    );
+    let split_names = manifest
+        .chunks
+        .iter()
+        .rev()
+        .filter(|chunk| chunk.kind == manifest::ChunkKind::Split)
+        .map(|chunk| &chunk.name)
+        .chain(manifest.folded.iter().map(|folded| &folded.name))
+        // Modules merged by `[chunks]` keep the imports of their own names.
+        .chain(manifest.aliases.keys());
+    for name in split_names {
+        javascript.push_str(
+            format!("export const __wasm_split_load_{name} = makeLoad(\"{name}\");\n").as_str(),
+        )
+    }
+    for (import_name, slot) in import_slots.iter() {
+        javascript.push_str(
+            format!(
+                "export function {import_name}(...args) {{\n  return callTableSlot({slot}, args);\n}}\n"
+            )
+            .as_str(),
+        )
+    }
+
+    if !uses_wasm_bindgen {
+        // Everything the main module imports from the loader, for
+        // `instantiateMain`.
+        let main_imports = toolchain::loader_imports(&module, &split_points)
+            .into_iter()
+            .map(String::from)
+            .chain(import_slots.keys().cloned())
+            .chain(
+                args.lazy_indirect_calls
+                    .then(|| emit::LOAD_SLOT_IMPORT.to_string()),
+            )
+            .collect::<Vec<_>>();
+        javascript.push_str(
+            format!("const MAIN_IMPORTS = {{ {} }};\n", main_imports.join(", ")).as_str(),
+        );
+    }
+
+    match args.target {
+        no_modules::Target::Web => {}
+        no_modules::Target::NoModules => {
+            javascript = no_modules::classic_loader(
+                &javascript,
+                uses_wasm_bindgen.then_some(args.no_modules_global.as_str()),
+            )?;
+        }
+        no_modules::Target::Node => javascript = node::node_loader(&javascript),
+    }
+    write_output(&output_paths.loader, javascript.as_bytes())?;
+    write_output(
+        &output_paths.loader_types(),
+        types::loader_types(
+            &output_paths.manifest_types_from_loader(),
+            uses_wasm_bindgen,
+        )
+        .as_bytes(),
+    )?;
+    write_output(
+        &output_paths.manifest_types(),
+        types::manifest_types(&manifest).as_bytes(),
+    )?;
+    write_output(
+        &output_paths.precache,
+        serde_json::to_string_pretty(&precache::get_precache_list(
+            &manifest,
+            output_paths,
+            uses_wasm_bindgen,
+            javascript.as_bytes(),
+        ))?
+        .as_bytes(),
+    )?;
+    if args.emit_bundler_assets {
+        if args.target == no_modules::Target::NoModules {
+            bail!(
+                "--emit-bundler-assets writes an ES module, which --target no-modules is for                  apps without"
+            );
+        }
+        let (js, dts) = bundler::render_assets(&manifest, output_paths);
+        let (js_path, dts_path) = output_paths.assets();
+        write_output(&js_path, js.as_bytes())?;
+        write_output(&dts_path, dts.as_bytes())?;
+    }
+    let manifest_path = output_paths.manifest_from_output_dir();
+    write_output(
+        &output_paths.service_worker,
+        replace_literal(
+            include_str!("sw.js"),
+            "new URL(",
+            "./wasm-split-manifest.json",
+            &output_paths.manifest_from_service_worker(),
+        )
+        .as_bytes(),
+    )?;
+    if args.emit_test_utils {
+        write_output(
+            Path::new(TESTING_FILENAME),
+            replace_literal(
+                include_str!("testing.js"),
+                "new URL(",
+                "./wasm-split-manifest.json",
+                &manifest_path,
+            )
+            .as_bytes(),
+        )?;
+        write_output(
+            Path::new(FAULT_SERVICE_WORKER_FILENAME),
+            include_str!("fault_sw.js").as_bytes(),
+        )?;
+    }
+
+    timings.end_phase("manifest and loader");
+
+    for (path, contents) in outputs.into_inner() {
+        sink.write(&path, &contents)?;
+    }
+    sink.finish(&manifest)?;
+    timings.end_last_phase("write");
+    if let Some(cache) = &cache {
+        cache.print_stats();
+    }
+    timings.print();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use crate::{
+        manifest,
+        test_fixtures::{assert_golden, expected_no_std_app_result, split, try_split, SplitOutput},
+    };
+
+    #[test]
+    fn instantiates_main_module_without_wasm_bindgen() {
+        let output = split("no_std_app.wasm", &[]);
+        output.validate();
+        let loader = String::from_utf8(output.read("__wasm_split.js")).unwrap();
+        assert!(loader.contains("export function instantiateMain("));
+        assert!(!loader.contains("./main.js"));
+        if let Some(result) = output.run_no_std_app(4) {
+            assert_eq!(result, expected_no_std_app_result(4));
+        }
+    }
+
+    /// Fixtures built with different optimization levels, with and without
+    /// LTO and relocations, and with wasm-bindgen, or written in assembly for
+    /// the proposals that they use, and the options to split them with. The
+    /// `eh_app` fixture is left out, as wasmparser does not validate its
+    /// legacy `try` blocks.
+    const CORPUS: &[(&str, &[&str])] = &[
+        ("no_std_app.wasm", &[]),
+        ("no_std_app_o1.wasm", &[]),
+        ("no_std_app_o3.wasm", &[]),
+        ("no_std_app_debug.wasm", &[]),
+        ("no_std_app_no_relocs.wasm", &[]),
+        ("closure_app.wasm", &[]),
+        ("closure_app_no_lto.wasm", &[]),
+        ("closure_app_no_relocs.wasm", &[]),
+        ("threads_app.wasm", &["--target", "node"]),
+        (
+            "refs_app.wasm",
+            &["--target", "node", "--lazy-indirect-calls"],
+        ),
+        (
+            "dead_data_app.wasm",
+            &["--target", "node", "--lazy-indirect-calls"],
+        ),
+    ];
+
+    #[test]
+    fn splits_corpus_as_in_golden_outlines() {
+        for (name, options) in CORPUS {
+            let output = split(name, options);
+            output.validate();
+            assert_golden(
+                &format!("{}.txt", name.trim_end_matches(".wasm")),
+                &output.manifest_outline(),
+            );
+        }
+    }
+
+    #[test]
+    fn drops_module_while_it_is_loading() {
+        let output = split("no_std_app.wasm", &[]);
+        if let Some(result) = output.run_no_std_app_export("run_dropping_first", 4) {
+            assert_eq!(result, expected_no_std_app_result(4));
+        }
+    }
+
+    #[test]
+    fn loads_module_called_from_another_chunk() {
+        let output = split("no_std_app.wasm", &["--lint"]);
+        output.validate();
+        if let Some(result) = output.run_no_std_app_export("run_nested", 4) {
+            assert_eq!(result, 2 * (4 + 1));
+        }
+    }
+
+    #[test]
+    fn writes_build_into_version_directory() {
+        let output = split("no_std_app.wasm", &["--asset-version", "v123"]);
+        assert!(output.wasm_files().is_empty());
+        let versioned = SplitOutput {
+            dir: output.dir.join("v123"),
+        };
+        assert_eq!(versioned.manifest()["version"], "v123");
+        versioned.validate();
+        if let Some(result) = versioned.run_no_std_app(4) {
+            assert_eq!(result, expected_no_std_app_result(4));
+        }
+
+        let (_output, result) = try_split("no_std_app.wasm", "", &["--asset-version", "../v123"]);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("--asset-version must be a single directory name"));
+    }
+
+    #[test]
+    fn overrides_output_paths_of_config() {
+        let (output, result) = try_split(
+            "no_std_app.wasm",
+            "[output]\nmain = \"app.wasm\"\nloader = \"loader.js\"\n",
+            &["--output-path", "loader=js/loader.js"],
+        );
+        result.unwrap();
+        assert!(output.dir.join("app.wasm").exists());
+        let loader = String::from_utf8(output.read("js/loader.js")).unwrap();
+        assert!(loader.contains("\"../app.wasm\""));
+
+        let (_output, result) = try_split(
+            "no_std_app.wasm",
+            "",
+            &["--output-path", "main=../app.wasm"],
+        );
+        assert!(format!("{:#}", result.unwrap_err())
+            .contains("output.main must be a relative path within the output directory"));
+        let (_output, result) =
+            try_split("no_std_app.wasm", "", &["--output-path", "mian=app.wasm"]);
+        assert!(format!("{:#}", result.unwrap_err()).contains("Unknown output path \"mian\""));
+    }
+
+    #[test]
+    fn rejects_loader_script_options_with_the_rust_loader() {
+        let output = split("rust_loader_app.wasm", &[]);
+        assert_eq!(output.manifest()["chunks"][1]["name"], "triple");
+        let (_output, result) = try_split("rust_loader_app.wasm", "", &["--lazy-indirect-calls"]);
+        assert!(format!("{:#}", result.unwrap_err()).contains(
+            "--lazy-indirect-calls cannot be used with the `rust-loader` feature of wasm_split"
+        ));
+    }
+
+    #[test]
+    fn reports_chunk_states_to_devtools() {
+        let output = split("no_std_app.wasm", &[]);
+        let Some(report) = output.inspect_no_std_app("run", 4) else {
+            return;
+        };
+        assert_eq!(report["buildId"], output.manifest()["build_id"]);
+        let chunk = |name: &str| {
+            report["chunks"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|chunk| chunk["name"] == name)
+                .unwrap_or_else(|| panic!("no chunk {name} in {report}"))
+                .clone()
+        };
+        for name in ["main", "first", "second", "first_second"] {
+            assert_eq!(chunk(name)["state"], "loaded", "{name}");
+        }
+        assert_eq!(chunk("details")["state"], "unloaded");
+        let first = chunk("first");
+        assert!(first["loadedAt"].as_f64().unwrap() >= first["startedAt"].as_f64().unwrap());
+        let reserved = &report["table"]["reservedSlots"];
+        assert_eq!(
+            reserved["end"].as_u64().unwrap() - reserved["start"].as_u64().unwrap(),
+            4
+        );
+        assert!(report["table"]["length"].as_u64().unwrap() >= reserved["end"].as_u64().unwrap());
+    }
+
+    #[test]
+    fn preloads_pinned_chunks_that_an_earlier_page_loaded() {
+        let (output, result) = try_split("no_std_app.wasm", "[loader]\npin = [\"first\"]\n", &[]);
+        result.unwrap();
+        let Some((_, pages)) = output.run_no_std_app_on_pages("run", 4, &["", ""]) else {
+            return;
+        };
+        let pinned = |page: &serde_json::Value| {
+            let (_, names) = page["storage"]
+                .as_object()
+                .unwrap()
+                .iter()
+                .find(|(key, _)| key.starts_with("wasm-split:pinned:"))
+                .unwrap();
+            serde_json::from_str::<BTreeSet<String>>(names.as_str().unwrap()).unwrap()
+        };
+        // Once loaded by the export, `first` is remembered along with the
+        // shared chunk it depends on, unlike `second`, which is not pinned.
+        assert_eq!(pages[0]["preloaded"], serde_json::json!([]));
+        assert_eq!(
+            pinned(&pages[0]),
+            BTreeSet::from(["first".to_string(), "first_second".to_string()])
+        );
+        // The next page loads them while idle, before the export needs them.
+        let preloaded = pages[1]["preloaded"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|name| name.as_str().unwrap())
+            .collect::<BTreeSet<_>>();
+        assert_eq!(preloaded, BTreeSet::from(["first", "first_second"]));
+        assert_eq!(pinned(&pages[1]), pinned(&pages[0]));
+    }
+
+    #[test]
+    fn allocates_and_frees_reserved_table_slots() {
+        let output = split("no_std_app.wasm", &[]);
+        // The 4 slots that `second` reserves, and the one or all of them that
+        // were freed, each allocated again.
+        if let Some(result) = output.run_no_std_app_export("run_table_slots", 1) {
+            assert_eq!(result, 400 + 1);
+        }
+        if let Some(result) = output.run_no_std_app_export("run_table_slots", 4) {
+            assert_eq!(result, 400 + 4);
+        }
+    }
+
+    #[test]
+    fn loads_chunks_from_configured_base_url() {
+        let output = split("no_std_app.wasm", &[]);
+        std::fs::create_dir(output.dir.join("cdn")).unwrap();
+        for file in output.wasm_files() {
+            if file != "main.wasm" {
+                std::fs::rename(output.dir.join(&file), output.dir.join("cdn").join(&file))
+                    .unwrap();
+            }
+        }
+        if let Some(result) = output.run_no_std_app_export("run_with_base_url", 4) {
+            assert_eq!(result, expected_no_std_app_result(4));
+        }
+        if let Some(result) = output.run_no_std_app_with_base_url(4, "cdn/") {
+            assert_eq!(result, expected_no_std_app_result(4));
+        }
+        // The chunks are no longer where the loader would look by default.
+        assert!(output
+            .try_run_no_std_app(4)
+            .unwrap_or(Err(String::new()))
+            .is_err());
+    }
+
+    #[test]
+    fn shares_loaded_chunks_between_copies_of_the_loader() {
+        let output = split("no_std_app.wasm", &[]);
+        let Some((result, second)) = output.run_no_std_app_with_second_loader("run", 4) else {
+            return;
+        };
+        assert_eq!(result, expected_no_std_app_result(4));
+        assert_eq!(second["shared"], true);
+        let counts = |key: &str| {
+            let counts = second[key].as_object().unwrap();
+            assert!(counts.contains_key("first.wasm"), "{second}");
+            counts
+                .values()
+                .map(|count| count.as_u64().unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert!(
+            counts("fetches").iter().all(|&count| count == 1),
+            "{second}"
+        );
+        assert!(
+            counts("refetches").iter().all(|&count| count == 2),
+            "{second}"
+        );
+        // `second` reserves 4 slots, the one freed after `reset` included.
+        let slots = second["slots"].as_array().unwrap();
+        let distinct = slots.iter().map(|slot| slot.as_u64().unwrap());
+        assert_eq!(distinct.collect::<BTreeSet<_>>().len(), 4, "{second}");
+        assert_eq!(slots.len(), 4, "{second}");
+    }
+
+    #[test]
+    fn calls_split_functions_of_preloaded_modules() {
+        let output = split("no_std_app.wasm", &[]);
+        if let Some(result) = output.run_no_std_app_export("run_preloaded", 4) {
+            assert_eq!(result, expected_no_std_app_result(4));
+        }
+    }
+
+    #[test]
+    fn measures_compiles_deferring_those_of_preloads() {
+        let output = split("no_std_app.wasm", &[]);
+        let deferred = |export| {
+            let (result, measures) = output.measure_no_std_app_compiles(export, 4)?;
+            assert_eq!(result, expected_no_std_app_result(4));
+            let mut chunks = measures
+                .iter()
+                .map(|detail| {
+                    assert!(detail["size"].as_u64().unwrap() > 0, "{detail}");
+                    (
+                        detail["chunk"].as_str().unwrap().to_string(),
+                        detail["deferred"].as_bool().unwrap(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            chunks.sort();
+            Some(chunks)
+        };
+        let Some(called) = deferred("run") else {
+            return;
+        };
+        assert!(called.iter().all(|(_, deferred)| !deferred), "{called:?}");
+        assert!(called.iter().any(|(chunk, _)| chunk == "first"));
+        // Calls follow the preloads right away, which compile their chunks
+        // once downloaded.
+        let preloaded = deferred("run_preloaded").unwrap();
+        assert!(
+            preloaded.contains(&("first".to_string(), true)),
+            "{preloaded:?}"
+        );
+        assert!(
+            preloaded.contains(&("second".to_string(), true)),
+            "{preloaded:?}"
+        );
+    }
+
+    #[test]
+    fn measures_phases_of_each_chunk_load() {
+        let output = split("no_std_app.wasm", &[]);
+        let Some((result, measures)) = output.measure_no_std_app_phases("run", 4) else {
+            return;
+        };
+        assert_eq!(result, expected_no_std_app_result(4));
+        let mut phases = BTreeMap::<&str, Vec<(&str, f64, f64)>>::new();
+        for measure in &measures {
+            let chunk = measure["detail"]["chunk"].as_str().unwrap();
+            let phase = measure["detail"]["phase"].as_str().unwrap();
+            assert_eq!(measure["name"], format!("wasm-split:{chunk}:{phase}"));
+            let (start, end) = (
+                measure["start"].as_f64().unwrap(),
+                measure["end"].as_f64().unwrap(),
+            );
+            assert!(start <= end, "{measure}");
+            phases.entry(chunk).or_default().push((phase, start, end));
+        }
+        assert_eq!(
+            phases.keys().copied().collect::<Vec<_>>(),
+            ["first", "first_second", "second"]
+        );
+        // One of each phase, each starting once the one before it ended.
+        for (chunk, phases) in &phases {
+            let names = phases.iter().map(|(phase, ..)| *phase).collect::<Vec<_>>();
+            assert_eq!(names, ["fetch", "compile", "instantiate"], "{chunk}");
+            for ((_, _, end), (phase, start, _)) in phases.iter().zip(&phases[1..]) {
+                assert!(end <= start, "{chunk} {phase}: {phases:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn measures_time_of_navigations_spent_loading_chunks() {
+        let output = split("no_std_app.wasm", &[]);
+        let Some((result, measures)) =
+            output.measure_no_std_app_navigations("run_timing_navigation", 3)
+        else {
+            return;
+        };
+        assert_eq!(result, 27);
+        // The dropped navigation is not reported.
+        let [detail] = &measures[..] else {
+            panic!("{measures:?}");
+        };
+        assert_eq!(detail["route"], "/cube");
+        assert!(detail["chunks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|chunk| chunk == "second"));
+        let time = |key: &str| detail[key].as_f64().unwrap();
+        assert!(time("chunkTime") > 0.0, "{detail}");
+        // The data was loaded before the view.
+        assert_eq!(time("overlapTime"), 0.0, "{detail}");
+        let total = time("chunkTime") + time("dataTime") + time("otherTime");
+        assert!((total - time("duration")).abs() < 1e-6, "{detail}");
+    }
+    #[test]
+    fn calls_split_methods() {
+        let output = split("no_std_app.wasm", &[]);
+        if let Some(result) = output.run_no_std_app_export("run_methods", 5) {
+            // The sum of squares below 5, plus 5, 5 to the fourth, and 5 cubed.
+            assert_eq!(result, 30 + 5 + 625 + 125);
+        }
+    }
+
+    #[test]
+    fn calls_instantiations_of_generic_split_functions() {
+        let output = split("no_std_app.wasm", &[]);
+        output.validate();
+        let manifest = output.manifest();
+        let second = manifest["chunks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|chunk| chunk["name"] == "second")
+            .unwrap();
+        let entries = second["entries"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| crate::split_point::split_function_name(entry.as_str().unwrap()))
+            .collect::<Vec<_>>();
+        // An export of each type of `types(...)`.
+        assert_eq!(
+            entries.iter().filter(|&&name| name == "sum_as").count(),
+            2,
+            "{entries:?}"
+        );
+        assert!(entries.contains(&"apply_twice"), "{entries:?}");
+        if let Some(result) = output.run_no_std_app_export("run_generic", 5) {
+            assert_eq!(result, 5 * 4 + 3 * 5);
+        }
+    }
+
+    #[test]
+    fn passes_borrowed_arguments_to_split_functions() {
+        let output = split("no_std_app.wasm", &[]);
+        output.validate();
+        if let Some(result) = output.run_no_std_app_export("run_borrowing", 5) {
+            // The heaviest point, one call, the total weight, and a result
+            // borrowed from the argument.
+            assert_eq!(result, 3 * 4 + 1000 + 3 * 5 * 4 / 2 + 10000);
+        }
+    }
+
+    #[test]
+    fn runs_calls_on_worker() {
+        let output = split("no_std_app.wasm", &[]);
+        // The sum of 2 to 7, computed on the main thread without workers.
+        if let Some(result) = output.run_no_std_app_export("run_on_worker", 7) {
+            assert_eq!(result, 27);
+        }
+        if let Some(result) = output.run_no_std_app_with_workers("run_on_worker", 7) {
+            assert_eq!(result, 27);
+        }
+    }
+
+    /// Moves the chunk of `second` to another directory, as listed by the
+    /// manifest file but not the manifest embedded in the loader.
+    fn move_second_chunk(output: &SplitOutput) {
+        let mut manifest = output.manifest();
+        let chunk = manifest["chunks"]
+            .as_array_mut()
+            .unwrap()
+            .iter_mut()
+            .find(|chunk| chunk["name"] == "second")
+            .unwrap();
+        chunk["file"] = "moved/second.wasm".into();
+        std::fs::create_dir(output.dir.join("moved")).unwrap();
+        std::fs::rename(
+            output.dir.join("second.wasm"),
+            output.dir.join("moved/second.wasm"),
+        )
+        .unwrap();
+        output.write(
+            "wasm-split-manifest.json",
+            &serde_json::to_vec(&manifest).unwrap(),
+        );
+    }
+
+    #[test]
+    fn revalidates_manifest_on_first_load() {
+        let (output, result) = try_split(
+            "no_std_app.wasm",
+            "[loader]\nmanifest-strategy = \"stale-while-revalidate\"\n",
+            &[],
+        );
+        result.unwrap();
+        move_second_chunk(&output);
+        // `first` loads with the embedded manifest, and `second` afterwards
+        // with the revalidated one.
+        if let Some(result) = output.run_no_std_app(4) {
+            assert_eq!(result, expected_no_std_app_result(4));
+        }
+
+        let output = split("no_std_app.wasm", &[]);
+        move_second_chunk(&output);
+        if let Some(result) = output.try_run_no_std_app(4) {
+            assert!(result.is_err(), "{result:?}");
+        }
+    }
+
+    /// Deploys the chunks and manifest of `next`, another build of the same
+    /// app, to `output`, as a redeployment would, with `edit` applied to its
+    /// manifest. `second.wasm` of `output` is removed, so that `second` only
+    /// loads if the loader moves it to the chunk of `next`.
+    fn deploy_next_build(
+        output: &SplitOutput,
+        next: &SplitOutput,
+        edit: impl FnOnce(&mut serde_json::Value),
+    ) {
+        let mut manifest = next.manifest();
+        for chunk in manifest["chunks"].as_array().unwrap() {
+            if chunk["kind"] != "main" {
+                let file = chunk["file"].as_str().unwrap();
+                output.write(file, &next.read(file));
+            }
+        }
+        edit(&mut manifest);
+        std::fs::remove_file(output.dir.join("second.wasm")).unwrap();
+        output.write(
+            "wasm-split-manifest.json",
+            &serde_json::to_vec(&manifest).unwrap(),
+        );
+    }
+
+    #[test]
+    fn reloads_manifest_of_compatible_build() {
+        let split_with_chunks = |chunks: &str| {
+            let (output, result) = try_split(
+                "no_std_app.wasm",
+                &format!(
+                    "[loader]\nmanifest-strategy = \"stale-while-revalidate\"\n\
+                     [output]\nchunks = \"{chunks}\"\n"
+                ),
+                &[],
+            );
+            result.unwrap();
+            output
+        };
+        let next = split_with_chunks("v2-{name}.wasm");
+        assert_ne!(
+            next.manifest()["build_id"],
+            split_with_chunks("{name}.wasm").manifest()["build_id"]
+        );
+        fn second(manifest: &mut serde_json::Value) -> &mut serde_json::Value {
+            let chunks = manifest["chunks"].as_array_mut().unwrap();
+            chunks
+                .iter_mut()
+                .find(|chunk| chunk["name"] == "second")
+                .unwrap()
+        }
+
+        // The same chunks, under other names.
+        let output = split_with_chunks("{name}.wasm");
+        deploy_next_build(&output, &next, |_| {});
+        if let Some(result) = output.run_no_std_app(4) {
+            assert_eq!(result, expected_no_std_app_result(4));
+        }
+
+        // A chunk that changed, with the same split functions.
+        let output = split_with_chunks("{name}.wasm");
+        deploy_next_build(&output, &next, |manifest| {
+            second(manifest)["hash"] = "0000000000000000".into();
+        });
+        if let Some(result) = output.run_no_std_app(4) {
+            assert_eq!(result, expected_no_std_app_result(4));
+        }
+
+        // One of whose split functions changed its signature.
+        let output = split_with_chunks("{name}.wasm");
+        deploy_next_build(&output, &next, |manifest| {
+            let chunk = second(manifest);
+            chunk["hash"] = "0000000000000000".into();
+            for signature in chunk["signatures"].as_object_mut().unwrap().values_mut() {
+                *signature = "(i64) -> i64".into();
+            }
+        });
+        if let Some(result) = output.try_run_no_std_app(4) {
+            let stderr = result.expect_err("the chunk of the next build was loaded");
+            assert!(
+                stderr.contains("belongs to an incompatible build"),
+                "{stderr}"
+            );
+        }
+    }
+
+    #[test]
+    fn retries_failed_loads() {
+        // Without retries in the loader, the load fails and the app calls
+        // `cube(3)` again.
+        let output = split("no_std_app.wasm", &[]);
+        if let Some(result) = output.run_no_std_app_with_failing_fetches("run_retrying", 3, 1) {
+            assert_eq!(result, 1000 + 27);
+        }
+
+        let (output, result) = try_split(
+            "no_std_app.wasm",
+            "[loader]\nretries = 2\nretry-delay-ms = 1\n",
+            &[],
+        );
+        result.unwrap();
+        if let Some(result) = output.run_no_std_app_with_failing_fetches("run_retrying", 3, 2) {
+            assert_eq!(result, 27);
+        }
+    }
+
+    #[test]
+    fn rejects_chunk_not_matching_integrity() {
+        let output = split("no_std_app.wasm", &[]);
+        let mut chunk = output.read("first.wasm");
+        chunk.extend([0, 2, 1, b'x']);
+        output.write("first.wasm", &chunk);
+        if let Some(result) = output.try_run_no_std_app(4) {
+            let stderr = result.expect_err("the chunk was modified");
+            assert!(stderr.contains("Integrity mismatch"), "{stderr}");
+        }
+    }
+
+    #[test]
+    fn describes_failed_loads() {
+        let output = split("no_std_app.wasm", &[]);
+        if let Some(result) =
+            output.run_no_std_app_with_failing_fetches("run_reporting_failure", 3, 1)
+        {
+            assert_eq!(result, 27 + 1000 + 10000 + 100000);
+        }
+    }
+
+    #[test]
+    fn reports_import_that_chunk_fails_to_link_with() {
+        let output = split("no_std_app.wasm", &[]);
+        let chunk = output.read("first.wasm");
+        let name = b"__stack_pointer";
+        let position = chunk
+            .windows(name.len())
+            .position(|window| window == name)
+            .unwrap();
+        let mut renamed = chunk.clone();
+        renamed[position + name.len() - 1] = b'X';
+        output.write("first.wasm", &renamed);
+        let loader = String::from_utf8(output.read("__wasm_split.js")).unwrap();
+        output.write(
+            "__wasm_split.js",
+            loader
+                .replace(&manifest::sri_hash(&chunk), &manifest::sri_hash(&renamed))
+                .as_bytes(),
+        );
+        if let Some(result) = output.try_run_no_std_app(4) {
+            let stderr = result.expect_err("the chunk imports what main does not export");
+            assert!(
+                stderr.contains("wasm-split: Failed to load chunk first from "),
+                "{stderr}"
+            );
+            assert!(stderr.contains("LinkError"), "{stderr}");
+            assert!(stderr.contains("import: '__stack_pointeX'"), "{stderr}");
+        }
+    }
+
+    #[test]
+    fn rejects_chunk_of_other_signatures() {
+        let output = split("no_std_app.wasm", &[]);
+        let manifest = output.manifest();
+        let first = manifest["chunks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|chunk| chunk["file"] == "first.wasm")
+            .unwrap();
+        let (_, signature) = first["signatures"]
+            .as_object()
+            .unwrap()
+            .iter()
+            .next()
+            .unwrap();
+        let loader = String::from_utf8(output.read("__wasm_split.js")).unwrap();
+        let signature = signature.as_str().unwrap();
+        assert!(loader.contains(signature));
+        output.write(
+            "__wasm_split.js",
+            loader.replace(signature, "0000000000000000").as_bytes(),
+        );
+        if let Some(result) = output.try_run_no_std_app(4) {
+            let stderr = result.expect_err("the chunk has other signatures");
+            assert!(
+                stderr.contains("Chunk first was built with other signatures of"),
+                "{stderr}"
+            );
+        }
+    }
+
+    #[test]
+    fn loads_chunks_once_for_racing_first_calls() {
+        let output = split("no_std_app.wasm", &[]);
+        let squares = (0..5).map(|value| value * value).sum::<u32>();
+        if let Some((result, fetches)) = output.count_no_std_app_fetches("run_racing", 5) {
+            assert_eq!(result, 8 * (expected_no_std_app_result(5) + squares));
+            assert!(fetches.contains_key("first_second.wasm"), "{fetches:?}");
+            assert!(fetches.values().all(|&count| count == 1), "{fetches:?}");
+        }
+        if let Some(inspect) = output.inspect_no_std_app("run_racing", 5) {
+            for chunk in inspect["chunks"].as_array().unwrap() {
+                assert_eq!(chunk["deduplicated"], false, "{chunk}");
+            }
+        }
+    }
+
+    #[test]
+    fn limits_concurrent_chunk_fetches() {
+        let squares = (0..5).map(|value| value * value).sum::<u32>();
+        let expected = 8 * (expected_no_std_app_result(5) + squares);
+        let output = split("no_std_app.wasm", &[]);
+        if let Some((result, in_flight)) = output.run_no_std_app_with_slow_fetches("run_racing", 5)
+        {
+            assert_eq!(result, expected);
+            assert!(in_flight > 1, "{in_flight}");
+        }
+        // Limited by the app at runtime.
+        if let Some((result, in_flight)) =
+            output.run_no_std_app_with_slow_fetches("run_configured", 5)
+        {
+            assert_eq!(result, expected);
+            assert_eq!(in_flight, 1);
+        }
+        let (output, result) = try_split(
+            "no_std_app.wasm",
+            "[loader]\nmax-concurrent-fetches = 1\n",
+            &[],
+        );
+        result.unwrap();
+        if let Some((result, in_flight)) = output.run_no_std_app_with_slow_fetches("run_racing", 5)
+        {
+            assert_eq!(result, expected);
+            assert_eq!(in_flight, 1);
+        }
+    }
+
+    #[test]
+    fn aborts_and_times_out_chunk_downloads() {
+        let output = split("no_std_app.wasm", &[]);
+        if let Some((result, _)) = output.run_no_std_app_with_slow_fetches("run_aborting", 3) {
+            assert_eq!(result, 27 + 1000 + 10000);
+        }
+        if let Some((result, _)) = output.run_no_std_app_with_slow_fetches("run_timing_out", 3) {
+            assert_eq!(result, 27);
+        }
+        let (output, result) = try_split("no_std_app.wasm", "[loader]\ntimeout-ms = 5\n", &[]);
+        result.unwrap();
+        if let Some((result, _)) = output.run_no_std_app_with_slow_fetches("run_timing_out", 3) {
+            assert_eq!(result, 1);
+        }
+    }
+
+    #[test]
+    fn shows_fallbacks_of_slow_loads_only() {
+        let output = split("no_std_app.wasm", &[]);
+        if let Some((result, _)) = output.run_no_std_app_with_slow_fetches("run_with_fallback", 3) {
+            assert_eq!(result, 2 * 27 + 12000);
+        }
+    }
+
+    #[test]
+    fn fetches_chunks_with_configured_fetch_and_headers() {
+        let output = split("no_std_app.wasm", &[]);
+        if let Some((result, fetches)) = output.run_no_std_app_with_custom_fetch("run", 5) {
+            assert_eq!(result, expected_no_std_app_result(5));
+            assert!(fetches > 0);
+        }
+        let (output, result) = try_split(
+            "no_std_app.wasm",
+            "[loader]\ncredentials = \"same-origin\"\n",
+            &[],
+        );
+        result.unwrap();
+        let loader = String::from_utf8(output.read("__wasm_split.js")).unwrap();
+        assert!(loader.contains("const REQUEST_CREDENTIALS = \"same-origin\";"));
+    }
+
+    #[test]
+    fn returns_default_of_optional_module_that_failed_to_load() {
+        let output = split("no_std_app.wasm", &[]);
+        if let Some(result) = output.run_no_std_app_export("run_optional", 3) {
+            assert_eq!(result, 2 * 103 + 27);
+        }
+        // Only the request for `bonus` fails, which is not made again.
+        if let Some(result) = output.run_no_std_app_with_failing_fetches("run_optional", 3, 1) {
+            assert_eq!(result, 27);
+        }
+    }
+
+    #[test]
+    fn calls_fallback_of_module_that_failed_to_load() {
+        let output = split("no_std_app.wasm", &[]);
+        if let Some(result) = output.run_no_std_app_export("run_falling_back", 3) {
+            assert_eq!(result, 20 * 3);
+        }
+        // The second call goes to the fallback as well, without a request.
+        if let Some(result) = output.run_no_std_app_with_failing_fetches("run_falling_back", 3, 1) {
+            assert_eq!(result, 2 * 3);
+        }
+    }
+
+    #[test]
+    fn moves_initializers_of_split_statics_into_their_chunk() {
+        let output = split("no_std_app.wasm", &[]);
+        output.validate();
+        // The initializer of `PRIMES` of the fixture, for which the main
+        // module has a stub of the same name that loads the chunk.
+        let symbols = String::from_utf8(output.read("wasm-split-symbols.tsv")).unwrap();
+        let chunks = symbols
+            .lines()
+            .filter(|line| line.contains("_export_") && line.contains("_primes_init"))
+            .map(|line| line.split('\t').next().unwrap())
+            .collect::<BTreeSet<_>>();
+        assert_eq!(chunks, BTreeSet::from(["main", "second"]));
+        // 2, 3, 5, 7, 11, 13, 17, 19, 23 and 29, counted twice, after loading
+        // the chunk once.
+        if let Some((result, fetches)) = output.count_no_std_app_fetches("run_split_static", 30) {
+            assert_eq!(result, 2 * 10);
+            assert_eq!(fetches.get("second.wasm"), Some(&1));
+        }
+    }
+
+    #[test]
+    fn reports_load_events() {
+        // `second` and the shared chunk it depends on, and then `first`.
+        let output = split("no_std_app.wasm", &[]);
+        if let Some(result) = output.run_no_std_app_export("run_counting_events", 3) {
+            assert_eq!(result, 3000 + 300 + 30);
+        }
+        // The first request, for `second`, fails, and is started again.
+        if let Some(result) =
+            output.run_no_std_app_with_failing_fetches("run_counting_events", 3, 1)
+        {
+            assert_eq!(result, 4000 + 300 + 30 + 1);
+        }
+    }
+
+    #[test]
+    fn reports_combined_progress_of_route() {
+        let (output, result) = try_split(
+            "no_std_app.wasm",
+            "[routes]\n\"/both\" = [\"first\", \"second\"]\n",
+            &[],
+        );
+        result.unwrap();
+        if let Some(result) = output.run_no_std_app_export("run_route", 3) {
+            assert_eq!(result, 3 + 1000 + 10000 + expected_no_std_app_result(3));
+        }
+    }
+
+    #[test]
+    fn lists_preload_links_of_chunks_not_yet_loaded() {
+        let (output, result) = try_split(
+            "no_std_app.wasm",
+            "[routes]\n\"/both\" = [\"first\", \"second\"]\n",
+            &[],
+        );
+        result.unwrap();
+        if let Some(result) = output.run_no_std_app_export("run_route_preload_links", 3) {
+            assert_eq!(result, 3 + 1000 + 10000 + expected_no_std_app_result(3));
+        }
+    }
+
+    #[test]
+    fn looks_up_and_preloads_chunks_of_route() {
+        let (output, result) = try_split(
+            "no_std_app.wasm",
+            "[routes]\n\"/both\" = [\"first\", \"second\"]\n",
+            &[],
+        );
+        result.unwrap();
+        if let Some(result) = output.run_no_std_app_export("run_route_chunks", 3) {
+            assert_eq!(result, 3 + 1000 + 10000 + expected_no_std_app_result(3));
+        }
+    }
+
+    #[test]
+    fn compiles_from_buffers_without_streaming_compilation() {
+        let output = split("no_std_app.wasm", &[]);
+        if let Some(result) = output.run_no_std_app_without_streaming(4) {
+            assert_eq!(result, expected_no_std_app_result(4));
+        }
+    }
+
+    #[test]
+    fn applies_chunk_policy_of_config() {
+        let (output, result) = try_split(
+            "no_std_app.wasm",
+            "[chunks.together]\nmodules = [\"first\", \"second\"]\n\
+             [chunks.app]\ncrates = [\"no_std_app\"]\n\
+             [output]\nchunks = \"{kind}-{name}.wasm\"\n\
+             [chunking]\nmin-size = 0\n",
+            &[],
+        );
+        result.unwrap();
+        let manifest = output.manifest();
+        let chunk = |name: &str| {
+            manifest["chunks"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|chunk| chunk["name"] == name)
+                .cloned()
+        };
+        assert!(chunk("first").is_none() && chunk("first_second").is_none());
+        let together = chunk("together").unwrap();
+        assert_eq!(together["file"], "split-together.wasm");
+        assert_eq!(together["dependencies"], serde_json::json!(["app"]));
+        assert_eq!(chunk("app").unwrap()["file"], "shared-app.wasm");
+        assert_eq!(manifest["aliases"]["first"], "together");
+        // The main module still loads the merged modules by their own names.
+        if let Some(result) = output.run_no_std_app(4) {
+            assert_eq!(result, expected_no_std_app_result(4));
+        }
+
+        let (_output, result) = try_split(
+            "no_std_app.wasm",
+            "[chunks.main]\ncrates = [\"no_std_app\"]\n",
+            &[],
+        );
+        assert!(format!("{:#}", result.unwrap_err()).contains("chunks.main"));
+        let (_output, result) = try_split(
+            "no_std_app.wasm",
+            "[chunks.both]\nmodules = [\"first\"]\ncrates = [\"no_std_app\"]\n",
+            &[],
+        );
+        assert!(format!("{:#}", result.unwrap_err()).contains("either modules or crates"));
+    }
+
+    #[test]
+    fn auto_splits_crates_of_config() {
+        let (output, result) = try_split("no_std_app.wasm", "auto-split = [\"alloc\"]\n", &[]);
+        result.unwrap();
+        output.validate();
+        let manifest = output.manifest();
+        let chunk = manifest["chunks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|chunk| chunk["name"] == "auto_alloc")
+            .cloned()
+            .unwrap();
+        assert_eq!(chunk["kind"], "split");
+        assert_eq!(chunk["auto"], true);
+        assert!(chunk.get("entries").is_none());
+        let symbols = String::from_utf8(output.read("wasm-split-symbols.tsv")).unwrap();
+        assert!(symbols
+            .lines()
+            .any(|line| line.starts_with("auto_alloc\t") && line.contains("alloc::")));
+        // The main module calls into the chunk directly, which only works
+        // once the loader has loaded it after startup.
+        if let Some(result) = output.run_no_std_app_after_auto_chunks(6) {
+            assert_eq!(result, expected_no_std_app_result(6));
+        }
+
+        let (_output, result) = try_split(
+            "no_std_app.wasm",
+            "[chunking]\nmin-size = 0\n",
+            &["--auto-split", "wasm_split"],
+        );
+        assert!(format!("{:#}", result.unwrap_err()).contains("cannot split out wasm_split"));
+    }
+
+    #[test]
+    fn moves_crates_of_vendor_chunks_out_of_the_main_module() {
+        let config = "[chunks.vendor]\ncrates = [\"alloc\"]\nvendor = \"preload\"\n";
+        let (output, result) = try_split("no_std_app.wasm", config, &[]);
+        result.unwrap();
+        output.validate();
+        let manifest = output.manifest();
+        let chunks = manifest["chunks"].as_array().unwrap();
+        let vendor = chunks
+            .iter()
+            .find(|chunk| chunk["name"] == "vendor")
+            .unwrap();
+        assert_eq!(vendor["kind"], "split");
+        assert_eq!(vendor["auto"], true);
+        // Unlike `auto-split`, which leaves what `run` reaches at startup.
+        let symbols = String::from_utf8(output.read("wasm-split-symbols.tsv")).unwrap();
+        assert!(symbols
+            .lines()
+            .any(|line| line.starts_with("vendor\t") && line.contains("SpecFromIter")));
+        if let Some(result) = output.run_no_std_app_after_auto_chunks(6) {
+            assert_eq!(result, expected_no_std_app_result(6));
+        }
+
+        let config = config.replace("preload", "lazy");
+        let (_output, result) = try_split("no_std_app.wasm", &config, &[]);
+        assert!(format!("{:#}", result.unwrap_err()).contains("requires --lazy-indirect-calls"));
+        let (output, result) = try_split(
+            "no_std_app.wasm",
+            &config,
+            &["--target", "node", "--lazy-indirect-calls"],
+        );
+        result.unwrap();
+        let manifest = output.manifest();
+        let vendor = manifest["chunks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|chunk| chunk["name"] == "vendor")
+            .cloned()
+            .unwrap();
+        assert!(vendor.get("auto").is_none());
+        if let Some(result) = output.run_no_std_app_without_fetch(5) {
+            assert_eq!(result, expected_no_std_app_result(5));
+        }
+    }
+
+    #[test]
+    fn splits_code_of_entries() {
+        let (output, result) = try_split(
+            "no_std_app.wasm",
+            "[entries]\nworker = [\"run_entry\"]\n",
+            &[],
+        );
+        result.unwrap();
+        output.validate();
+        let manifest = output.manifest();
+        let chunk = manifest["chunks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|chunk| chunk["name"] == "entry_worker")
+            .cloned()
+            .unwrap();
+        assert_eq!(chunk["kind"], "split");
+        assert_eq!(chunk["entry"], "worker");
+        let symbols = String::from_utf8(output.read("wasm-split-symbols.tsv")).unwrap();
+        assert!(symbols
+            .lines()
+            .any(|line| line.starts_with("entry_worker\t") && line.contains("count_primes")));
+        if let Some(result) = output.run_no_std_app_entry("worker", "run_entry", 30) {
+            assert_eq!(result, 10);
+        }
+        // The main thread does not load the chunk of the entry.
+        if let Some(inspection) = output.inspect_no_std_app("run", 6) {
+            let chunk = inspection["chunks"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|chunk| chunk["name"] == "entry_worker")
+                .cloned()
+                .unwrap();
+            assert_eq!(chunk["state"], "unloaded");
+        }
+
+        let (_output, result) = try_split(
+            "no_std_app.wasm",
+            "[entries]\nworker = [\"missing\"]\n",
+            &[],
+        );
+        assert!(format!("{:#}", result.unwrap_err()).contains("not an exported function"));
+    }
+
+    #[test]
+    fn splits_public_functions_of_modules() {
+        let output = split("no_std_app.wasm", &[]);
+        output.validate();
+        let manifest = output.manifest();
+        let chunk = manifest["chunks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|chunk| chunk["name"] == "geometry")
+            .cloned()
+            .unwrap();
+        let mut entries = chunk["entries"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| crate::split_point::split_function_name(entry.as_str().unwrap()))
+            .collect::<Vec<_>>();
+        entries.sort();
+        assert_eq!(entries, ["area", "perimeter"]);
+        if let Some(result) = output.run_no_std_app_export("run_module", 5) {
+            assert_eq!(result, 2 * 5 * 5 + 4 * 5);
+        }
+    }
+
+    #[test]
+    fn calls_split_functions_synchronously_once_loaded() {
+        let output = split("no_std_app.wasm", &[]);
+        output.validate();
+        if let Some(result) = output.run_no_std_app_export("run_sync", 6) {
+            assert_eq!(result, 6 * 7 + 1000);
+        }
+    }
+
+    #[test]
+    fn runs_on_load_hooks_of_groups_once() {
+        let output = split("no_std_app.wasm", &[]);
+        output.validate();
+        let manifest = output.manifest();
+        let chunk = manifest["chunks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|chunk| chunk["name"] == "tally")
+            .cloned()
+            .unwrap();
+        assert_eq!(chunk["on_load"].as_array().unwrap().len(), 1);
+        if let Some(result) = output.run_no_std_app_export("run_on_load", 6) {
+            assert_eq!(result, 6 * 7 + 1000);
+        }
+    }
+
+    #[test]
+    fn unloads_and_reloads_chunks_without_references() {
+        let output = split("no_std_app.wasm", &[]);
+        output.validate();
+        let manifest = output.manifest();
+        let chunk = |name: &str| {
+            manifest["chunks"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|chunk| chunk["name"] == name)
+                .cloned()
+                .unwrap()
+        };
+        let tally = chunk("tally");
+        assert!(tally["referenced"].is_null());
+        let slots = &tally["table_slots"];
+        assert!(slots["end"].as_u64().unwrap() > slots["start"].as_u64().unwrap());
+        // The vtable of the future of `with_details` refers to its code.
+        assert_eq!(chunk("first")["referenced"], true);
+        if let Some(result) = output.run_no_std_app_export("run_unloading", 6) {
+            assert_eq!(result, 3 * 6 * 7 / 2 + 3000 + 10000);
+        }
+    }
+
+    #[test]
+    fn applies_profile_of_calls() {
+        let session = serde_json::json!({
+            "build_id": "earlier",
+            "calls": [
+                {"module": "first", "function": "first", "first_call_ms": 10.0},
+                {"module": "first", "function": "sum", "first_call_ms": 20.0},
+                {"module": "first", "function": "with_details", "first_call_ms": 30.0},
+                {"module": "second", "function": "second", "first_call_ms": 40.0, "count": 3},
+                {"module": "details", "function": "details", "first_call_ms": 2000.0},
+                {"module": "bonus", "function": "bonus", "first_call_ms": 2100.0},
+            ],
+        });
+        let profile = std::env::temp_dir().join(format!(
+            "wasm-split-test-profile-{}.jsonl",
+            std::process::id()
+        ));
+        std::fs::write(&profile, format!("{session}\n").repeat(5)).unwrap();
+        let (output, result) = try_split(
+            "no_std_app.wasm",
+            "",
+            &["--profile", profile.to_str().unwrap()],
+        );
+        std::fs::remove_file(&profile).unwrap();
+        result.unwrap();
+        let manifest = output.manifest();
+        let chunks = manifest["chunks"].as_array().unwrap();
+        let chunk = |name: &str| chunks.iter().find(|chunk| chunk["name"] == name);
+        // All of `first` is called at startup, but only one function of
+        // `second`, whose others stay in its chunk.
+        assert!(chunk("first").is_none());
+        assert_eq!(manifest["folded"][0]["name"], "first");
+        let main_entries = chunk("main").unwrap()["entries"].as_array().unwrap();
+        assert!(main_entries
+            .iter()
+            .any(|entry| entry.as_str().unwrap().ends_with("_second")));
+        assert!(!chunk("second").unwrap()["entries"]
+            .as_array()
+            .unwrap()
+            .is_empty());
+        assert!(chunk("details").is_none());
+        assert_eq!(manifest["aliases"]["details"], "bonus");
+        if let Some(result) = output.run_no_std_app(5) {
+            assert_eq!(result, expected_no_std_app_result(5));
+        }
+    }
+
+    #[test]
+    fn merges_small_split_modules_into_their_neighbors() {
+        let output = split("no_std_app.wasm", &["--max-chunks", "2"]);
+        output.validate();
+        let manifest = output.manifest();
+        let chunks = manifest["chunks"].as_array().unwrap();
+        assert!(chunks.len() <= 3);
+        let merged = manifest["merged"].as_array().unwrap();
+        assert!(!merged.is_empty());
+        // `first` and `second`, which share code, go in the same chunk.
+        let first_second = merged
+            .iter()
+            .find(|merge| {
+                (merge["name"] == "first" && merge["into"] == "second")
+                    || (merge["name"] == "second" && merge["into"] == "first")
+            })
+            .unwrap();
+        assert!(first_second["shared_size"].as_u64().unwrap() > 0);
+        for merge in merged {
+            let name = merge["name"].as_str().unwrap();
+            assert!(chunks.iter().all(|chunk| chunk["name"] != name));
+            assert!(manifest["aliases"][name].is_string());
+        }
+        if let Some(result) = output.run_no_std_app(5) {
+            assert_eq!(result, expected_no_std_app_result(5));
+        }
+
+        let output = split("no_std_app.wasm", &["--min-chunk-size", "200"]);
+        let manifest = output.manifest();
+        let chunks = manifest["chunks"].as_array().unwrap();
+        assert!(chunks.iter().any(|chunk| chunk["name"] == "first"));
+        assert!(chunks.iter().any(|chunk| chunk["name"] == "second"));
+        assert!(manifest["merged"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|merge| merge["code_size"].as_u64().unwrap() < 200));
+        if let Some(result) = output.run_no_std_app(5) {
+            assert_eq!(result, expected_no_std_app_result(5));
+        }
+    }
+
+    #[test]
+    fn splits_reproducibly() {
+        // Each split hashes with different keys, so any output that depended
+        // on the iteration order of a hash map would differ between the two.
+        for (name, options) in [
+            ("no_std_app.wasm", &["--auto-split", "alloc"][..]),
+            ("closure_app.wasm", &["--duplicate-threshold", "100"]),
+        ] {
+            let outputs = [split(name, options), split(name, options)];
+            let [first, second] = outputs.each_ref().map(|output| {
+                std::fs::read_dir(&output.dir)
+                    .unwrap()
+                    .map(|entry| entry.unwrap().path())
+                    .filter(|path| path.is_file())
+                    .map(|path| {
+                        let file = path.file_name().unwrap().to_str().unwrap().to_string();
+                        (file, std::fs::read(path).unwrap())
+                    })
+                    .collect::<BTreeMap<_, _>>()
+            });
+            assert_eq!(
+                first.keys().collect::<Vec<_>>(),
+                second.keys().collect::<Vec<_>>()
+            );
+            for (file, contents) in first.iter() {
+                assert!(second[file] == *contents, "{name}: {file} differs");
+            }
+        }
+    }
+}
@@ -0,0 +1,323 @@
+//! Limits of the indirect function table and memory of the main module,
+//! which every chunk shares once loaded.
+//!
+//! Chunks fill in table slots of the main module and import its table and
+//! memory with the limits they expect. A chunk whose slots are beyond the
+//! table, or whose imports the main module's table or memory do not satisfy,
+//! only fails when it is loaded, so [`check`] checks every written module
+//! against the main one before anything is written.
+
+use anyhow::{bail, Result};
+use wasmparser::{DataKind, ElementItems, ElementKind, Operator, Payload, TypeRef};
+
+/// Maximum of the table of the main module, which holds `size` slots, given
+/// the table of the input. A table that the input lets grow keeps its
+/// maximum, so code that grows it at runtime still can, while a table of
+/// fixed size, as linked by default, is fixed at the new size.
+pub fn table_maximum(input: Option<&wasmparser::TableType>, size: u32) -> Result<Option<u32>> {
+    let Some(input) = input else {
+        return Ok(Some(size));
+    };
+    match input.maximum {
+        None => Ok(None),
+        Some(maximum) if maximum <= input.initial => Ok(Some(size)),
+        Some(maximum) if size > maximum => bail!(
+            "The table needs {size} slots once all chunks are loaded, but the input declares a \
+             maximum of {maximum}. Link with a larger maximum, or with \
+             `-C link-arg=--growable-table` for none."
+        ),
+        Some(maximum) => Ok(Some(maximum)),
+    }
+}
+
+#[derive(Debug, Default)]
+struct ModuleLimits {
+    /// Table 0, and whether the module imports it.
+    table: Option<(wasmparser::TableType, bool)>,
+    /// Memory 0, and whether the module imports it.
+    memory: Option<(wasmparser::MemoryType, bool)>,
+    /// End of the last table slot filled in by the module's elements.
+    table_end: u64,
+    /// End of the last byte of memory written by the module's data.
+    memory_end: u64,
+}
+
+fn const_offset(expr: &wasmparser::ConstExpr) -> Result<Option<u64>> {
+    Ok(match expr.get_operators_reader().read()? {
+        Operator::I32Const { value } => Some(value as u32 as u64),
+        Operator::I64Const { value } => Some(value as u64),
+        // Offsets of position-independent modules are only known at runtime.
+        _ => None,
+    })
+}
+
+fn read_limits(data: &[u8]) -> Result<ModuleLimits> {
+    let mut limits = ModuleLimits::default();
+    for payload in wasmparser::Parser::new(0).parse_all(data) {
+        match payload? {
+            Payload::ImportSection(reader) => {
+                for import in reader {
+                    match import?.ty {
+                        TypeRef::Table(ty) if limits.table.is_none() => {
+                            limits.table = Some((ty, true))
+                        }
+                        TypeRef::Memory(ty) if limits.memory.is_none() => {
+                            limits.memory = Some((ty, true))
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Payload::TableSection(reader) => {
+                if let (None, Some(table)) = (limits.table, reader.into_iter().next()) {
+                    limits.table = Some((table?.ty, false));
+                }
+            }
+            Payload::MemorySection(reader) => {
+                if let (None, Some(memory)) = (limits.memory, reader.into_iter().next()) {
+                    limits.memory = Some((memory?, false));
+                }
+            }
+            Payload::ElementSection(reader) => {
+                for element in reader {
+                    let element = element?;
+                    let ElementKind::Active {
+                        table_index: None | Some(0),
+                        offset_expr,
+                    } = element.kind
+                    else {
+                        continue;
+                    };
+                    let count = match element.items {
+                        ElementItems::Functions(items) => items.count(),
+                        ElementItems::Expressions(_, items) => items.count(),
+                    };
+                    if let Some(offset) = const_offset(&offset_expr)? {
+                        limits.table_end = limits.table_end.max(offset + count as u64);
+                    }
+                }
+            }
+            Payload::DataSection(reader) => {
+                for segment in reader {
+                    let segment = segment?;
+                    let DataKind::Active {
+                        memory_index: 0,
+                        offset_expr,
+                    } = segment.kind
+                    else {
+                        continue;
+                    };
+                    if let Some(offset) = const_offset(&offset_expr)? {
+                        limits.memory_end =
+                            limits.memory_end.max(offset + segment.data.len() as u64);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(limits)
+}
+
+/// Whether an import of a table or memory with these limits accepts one that
+/// has the limits of the main module.
+pub fn accepts(
+    (import_initial, import_maximum): (u64, Option<u64>),
+    (initial, maximum): (u64, Option<u64>),
+) -> bool {
+    import_initial <= initial
+        && match (import_maximum, maximum) {
+            (None, _) => true,
+            (Some(import_maximum), Some(maximum)) => maximum <= import_maximum,
+            (Some(_), None) => false,
+        }
+}
+
+fn describe((initial, maximum): (u64, Option<u64>)) -> String {
+    match maximum {
+        Some(maximum) => format!("{initial} to {maximum}"),
+        None => format!("at least {initial}"),
+    }
+}
+
+/// Checks the chunks, by name and encoding, against the main module among
+/// them, and prints the table and memory that all of them need together
+/// with `verbose`.
+pub fn check(modules: &[(String, &[u8])], verbose: bool) -> Result<()> {
+    let modules = modules
+        .iter()
+        .map(|(name, data)| Ok((name.as_str(), read_limits(data)?)))
+        .collect::<Result<Vec<_>>>()?;
+    let Some((_, main)) = modules.iter().find(|(name, _)| *name == "main") else {
+        return Ok(());
+    };
+    let table = main
+        .table
+        .map(|(ty, _)| (ty.initial as u64, ty.maximum.map(u64::from)));
+    let memory = main.memory.map(|(ty, _)| (ty.initial, ty.maximum));
+    let mut table_end = 0;
+    let mut memory_end = 0;
+    for (name, limits) in modules.iter() {
+        if let (Some((ty, true)), Some(table)) = (limits.table, table) {
+            let import = (ty.initial as u64, ty.maximum.map(u64::from));
+            if !accepts(import, table) {
+                bail!(
+                    "Chunk {name:?} imports a table of {} slots, which the table of {} slots \
+                     of the main module does not satisfy, so it would fail to load",
+                    describe(import),
+                    describe(table)
+                );
+            }
+        }
+        if let (Some((ty, true)), Some(memory)) = (limits.memory, memory) {
+            if !accepts((ty.initial, ty.maximum), memory)
+                || Some(ty.shared) != main.memory.map(|(ty, _)| ty.shared)
+            {
+                bail!(
+                    "Chunk {name:?} imports a memory of {} pages, which the memory of {} pages \
+                     of the main module does not satisfy, so it would fail to load",
+                    describe((ty.initial, ty.maximum)),
+                    describe(memory)
+                );
+            }
+        }
+        if let Some((initial, _)) = table {
+            if limits.table_end > initial {
+                bail!(
+                    "Chunk {name:?} fills in table slots up to {}, beyond the {initial} slots \
+                     of the table of the main module, so it would fail to load",
+                    limits.table_end
+                );
+            }
+        }
+        table_end = table_end.max(limits.table_end);
+        memory_end = memory_end.max(limits.memory_end);
+    }
+    if let Some((initial, _)) = memory {
+        if memory_end > initial * PAGE_SIZE {
+            bail!(
+                "The data of the modules ends at byte {memory_end}, beyond the {initial} pages \
+                 of the memory of the main module"
+            );
+        }
+    }
+    if verbose {
+        if let Some(table) = table {
+            println!(
+                "Table: {table_end} slots filled with all chunks loaded, of {} slots",
+                describe(table)
+            );
+        }
+        if let Some(memory) = memory {
+            println!(
+                "Memory: data ends at byte {memory_end}, in a memory of {} pages",
+                describe(memory)
+            );
+        }
+    }
+    Ok(())
+}
+
+const PAGE_SIZE: u64 = 65536;
+
+#[cfg(test)]
+mod tests {
+    use wasm_encoder::{
+        ConstExpr, ElementSection, Elements, ImportSection, Module, RefType, TableSection,
+        TableType,
+    };
+
+    use super::{check, table_maximum};
+    use crate::test_fixtures::split;
+
+    fn table(initial: u32, maximum: Option<u32>) -> wasmparser::TableType {
+        wasmparser::TableType {
+            element_type: wasmparser::RefType::FUNCREF,
+            initial,
+            maximum,
+        }
+    }
+
+    #[test]
+    fn keeps_tables_growable() {
+        assert_eq!(table_maximum(None, 10).unwrap(), Some(10));
+        assert_eq!(
+            table_maximum(Some(&table(4, Some(4))), 10).unwrap(),
+            Some(10)
+        );
+        assert_eq!(table_maximum(Some(&table(4, None)), 10).unwrap(), None);
+        assert_eq!(
+            table_maximum(Some(&table(4, Some(20))), 10).unwrap(),
+            Some(20)
+        );
+        let err = table_maximum(Some(&table(4, Some(8))), 10).unwrap_err();
+        assert!(err.to_string().contains("declares a maximum of 8"), "{err}");
+    }
+
+    #[test]
+    fn rejects_chunks_beyond_the_table() {
+        let table_type = |minimum, maximum| TableType {
+            element_type: RefType::FUNCREF,
+            minimum,
+            maximum,
+        };
+        let mut main = Module::new();
+        let mut tables = TableSection::new();
+        tables.table(table_type(4, Some(4)));
+        main.section(&tables);
+        let chunk = |minimum, offset| {
+            let mut chunk = Module::new();
+            let mut imports = ImportSection::new();
+            imports.import(
+                "__wasm_split",
+                "__indirect_function_table",
+                table_type(minimum, None),
+            );
+            chunk.section(&imports);
+            let mut elements = ElementSection::new();
+            elements.active(
+                None,
+                &ConstExpr::i32_const(offset),
+                Elements::Functions(&[0, 0]),
+            );
+            chunk.section(&elements);
+            chunk.finish()
+        };
+        let main = main.finish();
+        let fits = chunk(4, 2);
+        check(&[("main".into(), &main), ("a".into(), &fits)], false).unwrap();
+        let beyond = chunk(4, 3);
+        let err = check(&[("main".into(), &main), ("a".into(), &beyond)], false).unwrap_err();
+        assert!(
+            err.to_string().contains("up to 5, beyond the 4 slots"),
+            "{err}"
+        );
+        let larger = chunk(5, 0);
+        let err = check(&[("main".into(), &main), ("a".into(), &larger)], false).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("imports a table of at least 5 slots"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn checks_split_output() {
+        let output = split("no_std_app.wasm", &[]);
+        let modules = output
+            .wasm_files()
+            .into_iter()
+            .map(|file| {
+                (
+                    file.trim_end_matches(".wasm").to_string(),
+                    output.read(&file),
+                )
+            })
+            .collect::<Vec<_>>();
+        let modules = modules
+            .iter()
+            .map(|(name, data)| (name.clone(), data.as_slice()))
+            .collect::<Vec<_>>();
+        check(&modules, true).unwrap();
+    }
+}
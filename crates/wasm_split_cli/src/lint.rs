@@ -0,0 +1,673 @@
+//! `--lint`: warnings about split points whose chunk is fetched at a bad
+//! time, each with a change that avoids it.
+//!
+//! A split function called at startup has its chunk fetched on every page
+//! load, right after the main module and without saving anything. Startup
+//! code is what the start function and the `--startup-export`s reach
+//! through code alone: data, such as the vtable of a boxed future or closure,
+//! is not followed, as tasks and event handlers need not run at startup.
+//!
+//! A split function called from the code of another chunk only starts its
+//! fetch once that chunk loaded and ran, so that the two requests form a
+//! waterfall rather than going out together, as do longer chains of such
+//! modules. A module that only one chunk calls and that is smaller than it is
+//! best merged into that chunk; any other is best preloaded by the code that
+//! calls into the chunk, so that both load in parallel.
+//!
+//! A split module that no registered route needs, and that no code reachable
+//! from the exports calls, is an orphan: its chunk ships with every build but
+//! is never loaded, as when the feature it belonged to was refactored away
+//! but its `#[wasm_split]` functions were not. Routes are those of the
+//! config, or with `--registered-routes` only those that the router still
+//! registers, and configured routes that it does not are reported as well.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    config::ByteSize,
+    dep_graph::{DepGraph, DepNode},
+    read::{InputFuncId, InputModule},
+    split_point::{
+        find_reachable_deps, get_main_module_roots, get_split_function_name, SplitModuleIdentifier,
+        SplitPoint, SplitProgramInfo,
+    },
+    symbols::Demangling,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Lint {
+    /// `function` of `module_name` is called by the startup code of `root`.
+    CalledAtStartup {
+        module_name: String,
+        function: String,
+        root: String,
+    },
+    /// The split functions of `module_name` are called from the chunk of
+    /// `caller`, which then requests the chunk of `module_name` only once
+    /// its own has loaded.
+    Waterfall {
+        module_name: String,
+        caller: String,
+        /// Modules whose chunks are requested one after another, starting
+        /// with one that is called from elsewhere and ending with
+        /// `module_name`.
+        chain: Vec<String>,
+        /// Whether only `caller` calls the module.
+        exclusive: bool,
+        /// Split functions of `caller` that the main module calls.
+        call_sites: Vec<String>,
+        code_size: usize,
+        caller_code_size: usize,
+    },
+    /// No registered route needs `module_name`, and no code reachable from
+    /// the exports calls it.
+    Orphan {
+        module_name: String,
+        /// Code size of its chunk, or `None` if it was folded into the main
+        /// module.
+        code_size: Option<usize>,
+    },
+    /// `route` of the config is not among the routes registered with the
+    /// router.
+    UnregisteredRoute { route: String },
+}
+
+impl Lint {
+    pub fn message(&self) -> String {
+        match self {
+            Self::CalledAtStartup {
+                module_name,
+                function,
+                root,
+            } => format!(
+                "`{function}` of module {module_name} is called at startup by `{root}`, so its \
+                 chunk is fetched on every page load, right after the main module"
+            ),
+            Self::Waterfall {
+                module_name,
+                caller,
+                chain,
+                exclusive,
+                ..
+            } => format!(
+                "module {module_name} is {only}called from the chunk of {caller}, so that its chunk \
+                 is only requested once that one has loaded, in a waterfall of {count} requests \
+                 ({chain})",
+                only = if *exclusive { "only " } else { "" },
+                count = chain.len(),
+                chain = chain.join(" -> "),
+            ),
+            Self::Orphan {
+                module_name,
+                code_size,
+            } => format!(
+                "module {module_name} is not needed by any registered route and no exported \
+                 function calls it, so {ships}",
+                ships = match code_size {
+                    Some(size) => format!(
+                        "its chunk of {} of code ships with every build but is never loaded",
+                        ByteSize(*size)
+                    ),
+                    None => "its code ships in the main module but never runs".to_string(),
+                },
+            ),
+            Self::UnregisteredRoute { route } => {
+                format!("route {route} of the config is not registered with the router")
+            }
+        }
+    }
+
+    pub fn help(&self) -> String {
+        match self {
+            Self::CalledAtStartup { function, .. } => format!(
+                "remove `#[wasm_split]` from `{function}` to keep it in the main module, or call \
+                 it only once the app is running"
+            ),
+            Self::Waterfall {
+                module_name,
+                caller,
+                exclusive: true,
+                code_size,
+                caller_code_size,
+                ..
+            } if code_size <= caller_code_size => format!(
+                "merge it into the chunk of {caller}, which adds {size} of code to it, with \
+                 `#[wasm_split({module_name}, with = {caller})]`",
+                size = ByteSize(*code_size),
+            ),
+            Self::Waterfall {
+                module_name,
+                caller,
+                call_sites,
+                ..
+            } if !call_sites.is_empty() => format!(
+                "preload it while {caller} loads, with `wasm_split::preload(\"{module_name}\")` \
+                 next to the calls of {call_sites}",
+                call_sites = call_sites
+                    .iter()
+                    .map(|function| format!("`{function}`"))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ),
+            Self::Waterfall {
+                module_name,
+                caller,
+                ..
+            } => format!(
+                "preload it while {caller} loads, with `wasm_split::preload(\"{module_name}\")` \
+                 where {caller} is loaded"
+            ),
+            Self::Orphan { module_name, .. } => format!(
+                "remove the `#[wasm_split({module_name})]` functions along with the feature they \
+                 belong to, or list {module_name} for the route that still uses it"
+            ),
+            Self::UnregisteredRoute { route } => format!(
+                "remove {route} from the [routes] of the config, or the `route = \"{route}\"` \
+                 of its split points"
+            ),
+        }
+    }
+}
+
+/// Reads the routes that the router registers, as exported by the app at
+/// build time: a JSON array of paths, or one path per line, with blank lines
+/// and lines starting with `#` ignored.
+pub fn read_registered_routes(path: &Path) -> Result<Vec<String>> {
+    let contents =
+        std::fs::read_to_string(path).with_context(|| format!("Failed to read {path:?}"))?;
+    if contents.trim_start().starts_with('[') {
+        return serde_json::from_str(&contents)
+            .with_context(|| format!("Invalid route list {path:?}"));
+    }
+    Ok(contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect())
+}
+
+fn get_func_name(module: &InputModule, func_id: InputFuncId, demangling: Demangling) -> String {
+    match module.names.functions.get(&func_id) {
+        Some(name) => demangling.demangle(name),
+        None => format!("func[{func_id}]"),
+    }
+}
+
+fn get_startup_lints(
+    module: &InputModule,
+    references: &DepGraph,
+    split_points: &[SplitPoint],
+    startup_exports: &[String],
+    demangling: Demangling,
+) -> Vec<Lint> {
+    let mut roots = module
+        .exports
+        .iter()
+        .filter(|export| {
+            export.kind == wasmparser::ExternalKind::Func
+                && startup_exports.iter().any(|name| name == export.name)
+        })
+        .map(|export| DepNode::Function(export.index as InputFuncId))
+        .collect::<HashSet<_>>();
+    roots.extend(module.start.map(DepNode::Function));
+    let exclude = split_points
+        .iter()
+        .map(|split_point| DepNode::Function(split_point.export_func))
+        .collect();
+    let startup = find_reachable_deps(references, &roots, &exclude);
+    split_points
+        .iter()
+        .filter_map(|split_point| {
+            let mut node = &DepNode::Function(split_point.import_func);
+            if !startup.reachable.contains(node) {
+                return None;
+            }
+            while let Some(parent) = startup.parents.get(node) {
+                node = parent;
+            }
+            let DepNode::Function(root) = *node else {
+                unreachable!();
+            };
+            Some(Lint::CalledAtStartup {
+                module_name: split_point.module_name.clone(),
+                function: get_split_function_name(module, split_point),
+                root: get_func_name(module, root, demangling),
+            })
+        })
+        .collect()
+}
+
+/// Whether the split function `function` of `caller` calls into `callee`,
+/// directly or through code it creates, such as a future that it returns.
+fn calls_module(
+    module: &InputModule,
+    dep_graph: &DepGraph,
+    split_points: &[SplitPoint],
+    caller: &str,
+    function: &str,
+    callee: &str,
+) -> bool {
+    let Some(split_point) = split_points.iter().find(|split_point| {
+        split_point.module_name == caller
+            && get_split_function_name(module, split_point) == function
+    }) else {
+        return false;
+    };
+    let roots = HashSet::from([DepNode::Function(split_point.export_func)]);
+    let exclude = split_points
+        .iter()
+        .map(|split_point| DepNode::Function(split_point.export_func))
+        .filter(|node| !roots.contains(node))
+        .collect();
+    let reachable = find_reachable_deps(dep_graph, &roots, &exclude).reachable;
+    split_points.iter().any(|split_point| {
+        split_point.module_name == callee
+            && reachable.contains(&DepNode::Function(split_point.import_func))
+    })
+}
+
+fn get_chunk_lints(
+    module: &InputModule,
+    dep_graph: &DepGraph,
+    references: &DepGraph,
+    split_points: &[SplitPoint],
+    program_info: &SplitProgramInfo,
+) -> Vec<Lint> {
+    // For each split module with a chunk of its own, the other output
+    // modules with code calling it, with the split functions that they call.
+    let mut calls = BTreeMap::<&str, BTreeMap<&SplitModuleIdentifier, BTreeSet<String>>>::new();
+    for split_point in split_points {
+        let own_identifier = SplitModuleIdentifier::Split(split_point.module_name.clone());
+        if !program_info
+            .output_module_identifiers
+            .contains_key(&own_identifier)
+        {
+            // Folded into the main module.
+            continue;
+        }
+        let import = DepNode::Function(split_point.import_func);
+        let callers = references
+            .iter()
+            .filter(|(_, deps)| deps.contains(&import))
+            .map(|(caller, _)| caller)
+            .collect::<Vec<_>>();
+        let module_calls = calls.entry(&split_point.module_name).or_default();
+        for (identifier, info) in program_info.output_modules.iter() {
+            if *identifier != own_identifier
+                && callers
+                    .iter()
+                    .any(|caller| info.included_symbols.contains(caller))
+            {
+                module_calls
+                    .entry(identifier)
+                    .or_default()
+                    .insert(get_split_function_name(module, split_point));
+            }
+        }
+    }
+    let code_size = |identifier: &SplitModuleIdentifier| {
+        program_info
+            .output_module_identifiers
+            .get(identifier)
+            .map_or(0, |&index| {
+                program_info.output_modules[index].1.code_size(module)
+            })
+    };
+
+    let mut lints = Vec::new();
+    for (&module_name, callers) in calls.iter() {
+        for &caller in callers.keys() {
+            if *caller == SplitModuleIdentifier::Main {
+                continue;
+            }
+            // Walk back through the modules that only one other chunk calls.
+            let mut chain = vec![module_name.to_string(), caller.name()];
+            let mut first = caller;
+            while let SplitModuleIdentifier::Split(name) = first {
+                let Some(callers) = calls.get(name.as_str()) else {
+                    break;
+                };
+                match callers.keys().collect::<Vec<_>>()[..] {
+                    [&only]
+                        if *only != SplitModuleIdentifier::Main
+                            && !chain.contains(&only.name()) =>
+                    {
+                        chain.push(only.name());
+                        first = only;
+                    }
+                    _ => break,
+                }
+            }
+            chain.reverse();
+            let call_sites = match caller {
+                SplitModuleIdentifier::Split(name) => calls
+                    .get(name.as_str())
+                    .and_then(|callers| callers.get(&SplitModuleIdentifier::Main))
+                    .into_iter()
+                    .flatten()
+                    .filter(|function| {
+                        calls_module(module, dep_graph, split_points, name, function, module_name)
+                    })
+                    .cloned()
+                    .collect(),
+                _ => Vec::new(),
+            };
+            lints.push(Lint::Waterfall {
+                module_name: module_name.to_string(),
+                caller: caller.name(),
+                chain,
+                exclusive: callers.len() == 1,
+                call_sites,
+                code_size: code_size(&SplitModuleIdentifier::Split(module_name.to_string())),
+                caller_code_size: code_size(caller),
+            });
+        }
+    }
+    lints
+}
+
+fn get_orphan_lints(
+    module: &InputModule,
+    dep_graph: &DepGraph,
+    split_points: &[SplitPoint],
+    program_info: &SplitProgramInfo,
+    routes: &BTreeMap<String, Vec<String>>,
+    registered_routes: Option<&[String]>,
+    entries: &HashSet<DepNode>,
+) -> Vec<Lint> {
+    let is_registered =
+        |route: &String| registered_routes.is_none_or(|routes| routes.contains(route));
+    let mut lints = routes
+        .keys()
+        .filter(|route| !is_registered(route))
+        .map(|route| Lint::UnregisteredRoute {
+            route: route.clone(),
+        })
+        .collect::<Vec<_>>();
+
+    // Modules are live if a registered route needs them, or code reachable
+    // from the exports or from live modules calls them.
+    let mut live = routes
+        .iter()
+        .filter(|(route, _)| is_registered(route))
+        .flat_map(|(_, modules)| modules.iter().map(String::as_str))
+        .collect::<BTreeSet<_>>();
+    let mut roots = entries.clone();
+    loop {
+        roots.extend(
+            split_points
+                .iter()
+                .filter(|split_point| live.contains(split_point.module_name.as_str()))
+                .map(|split_point| DepNode::Function(split_point.export_func)),
+        );
+        let reachable = find_reachable_deps(dep_graph, &roots, &HashSet::new()).reachable;
+        let called = split_points
+            .iter()
+            .filter(|split_point| {
+                !live.contains(split_point.module_name.as_str())
+                    && reachable.contains(&DepNode::Function(split_point.import_func))
+            })
+            .map(|split_point| split_point.module_name.as_str())
+            .collect::<Vec<_>>();
+        if called.is_empty() {
+            break;
+        }
+        live.extend(called);
+    }
+    let modules = split_points
+        .iter()
+        .map(|split_point| split_point.module_name.as_str())
+        .collect::<BTreeSet<_>>();
+    lints.extend(modules.difference(&live).map(|&module_name| {
+        let identifier = SplitModuleIdentifier::Split(module_name.to_string());
+        Lint::Orphan {
+            module_name: module_name.to_string(),
+            code_size: program_info
+                .output_module_identifiers
+                .get(&identifier)
+                .map(|&index| program_info.output_modules[index].1.code_size(module)),
+        }
+    }));
+    lints
+}
+
+/// What the app does at startup and which routes it has, for the lints.
+#[derive(Debug)]
+pub struct AppEntries<'a> {
+    /// Exports called at startup, as given with `--startup-export`.
+    pub startup_exports: &'a [String],
+    /// Routes of the config, with the split modules each needs.
+    pub routes: &'a BTreeMap<String, Vec<String>>,
+    /// Routes that the router registers, if known.
+    pub registered_routes: Option<&'a [String]>,
+}
+
+pub fn get_lints(
+    module: &InputModule,
+    dep_graph: &DepGraph,
+    references: &DepGraph,
+    split_points: &[SplitPoint],
+    program_info: &SplitProgramInfo,
+    entries: &AppEntries,
+    demangling: Demangling,
+) -> Vec<Lint> {
+    let mut lints = get_startup_lints(
+        module,
+        references,
+        split_points,
+        entries.startup_exports,
+        demangling,
+    );
+    lints.extend(get_chunk_lints(
+        module,
+        dep_graph,
+        references,
+        split_points,
+        program_info,
+    ));
+    lints.extend(get_orphan_lints(
+        module,
+        dep_graph,
+        split_points,
+        program_info,
+        entries.routes,
+        entries.registered_routes,
+        &get_main_module_roots(module, split_points),
+    ));
+    lints.sort();
+    lints.dedup();
+    lints
+}
+
+pub fn print_lints(lints: &[Lint]) {
+    for lint in lints {
+        eprintln!("Warning: {}.\n  help: {}.", lint.message(), lint.help());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dep_graph::{get_dependencies, get_function_references},
+        split_point::{compute_split_modules, get_split_points, ChunkingOptions},
+        test_fixtures::fixture_path,
+    };
+
+    #[test]
+    fn reports_calls_at_startup_and_from_other_chunks() {
+        let wasm = std::fs::read(fixture_path("no_std_app.wasm")).unwrap();
+        let module = InputModule::parse(&wasm).unwrap();
+        let split_points = get_split_points(&module).unwrap();
+        let dep_graph = get_dependencies(&module).unwrap();
+        let program_info = compute_split_modules(
+            &module,
+            &dep_graph,
+            &split_points,
+            &[],
+            &ChunkingOptions::default(),
+        )
+        .unwrap();
+        let lints = |startup_exports: &[&str]| {
+            get_lints(
+                &module,
+                &dep_graph,
+                &get_function_references(&module).unwrap(),
+                &split_points,
+                &program_info,
+                &AppEntries {
+                    startup_exports: &startup_exports
+                        .iter()
+                        .map(|name| name.to_string())
+                        .collect::<Vec<_>>(),
+                    routes: &BTreeMap::new(),
+                    registered_routes: None,
+                },
+                Demangling::Full,
+            )
+        };
+
+        let [details] = &lints(&[])[..] else {
+            panic!("expected the waterfall of details, found {:?}", lints(&[]));
+        };
+        let Lint::Waterfall {
+            module_name,
+            caller,
+            chain,
+            exclusive,
+            call_sites,
+            code_size,
+            caller_code_size,
+        } = details
+        else {
+            panic!("expected the waterfall of details, found {details:?}");
+        };
+        assert_eq!(
+            (module_name.as_str(), caller.as_str()),
+            ("details", "first")
+        );
+        assert_eq!(chain, &["first", "details"]);
+        assert!(exclusive);
+        assert_eq!(call_sites, &["with_details"]);
+        assert!(code_size < caller_code_size);
+        assert!(details.help().contains("with = first"));
+
+        assert_eq!(
+            lints(&["prefetch"]),
+            [
+                Lint::CalledAtStartup {
+                    module_name: "second".to_string(),
+                    function: "second".to_string(),
+                    root: "prefetch".to_string(),
+                },
+                details.clone()
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_orphan_modules_and_unregistered_routes() {
+        let wasm = std::fs::read(fixture_path("no_std_app.wasm")).unwrap();
+        let module = InputModule::parse(&wasm).unwrap();
+        let split_points = get_split_points(&module).unwrap();
+        let dep_graph = get_dependencies(&module).unwrap();
+        let program_info = compute_split_modules(
+            &module,
+            &dep_graph,
+            &split_points,
+            &[],
+            &ChunkingOptions::default(),
+        )
+        .unwrap();
+        let routes = BTreeMap::from([
+            ("/".to_string(), vec![]),
+            ("/first".to_string(), vec!["first".to_string()]),
+        ]);
+        let export = |name: &str| {
+            let export = module.exports.iter().find(|export| export.name == name);
+            DepNode::Function(export.unwrap().index as InputFuncId)
+        };
+        let lints = |registered: Option<&[String]>, entries: &[&str]| {
+            get_orphan_lints(
+                &module,
+                &dep_graph,
+                &split_points,
+                &program_info,
+                &routes,
+                registered,
+                &entries.iter().map(|name| export(name)).collect(),
+            )
+        };
+
+        // Every split module is called by code of the exports.
+        let entries = get_main_module_roots(&module, &split_points);
+        assert_eq!(
+            get_orphan_lints(
+                &module,
+                &dep_graph,
+                &split_points,
+                &program_info,
+                &routes,
+                None,
+                &entries
+            ),
+            []
+        );
+        // With no entry points, only the route of first and the modules that
+        // its code calls are live.
+        let orphans = lints(None, &[]);
+        let names = orphans
+            .iter()
+            .map(|lint| match lint {
+                Lint::Orphan { module_name, .. } => module_name.as_str(),
+                lint => panic!("{lint:?}"),
+            })
+            .collect::<Vec<_>>();
+        assert!(!names.contains(&"first") && !names.contains(&"details"));
+        assert!(names.contains(&"second"), "{orphans:?}");
+        assert!(orphans[0]
+            .message()
+            .contains("no exported function calls it"));
+
+        // Once /first is no longer registered, nothing needs first either.
+        let registered = ["/".to_string()];
+        let lints = lints(Some(&registered), &[]);
+        assert_eq!(
+            lints[0],
+            Lint::UnregisteredRoute {
+                route: "/first".to_string()
+            }
+        );
+        assert!(lints.iter().any(|lint| matches!(
+            lint,
+            Lint::Orphan { module_name, .. } if module_name == "first"
+        )));
+    }
+
+    #[test]
+    fn suggests_preloading_modules_larger_than_their_caller() {
+        let lint = |exclusive, call_sites: &[&str]| Lint::Waterfall {
+            module_name: "editor".to_string(),
+            caller: "settings".to_string(),
+            chain: vec!["settings".to_string(), "editor".to_string()],
+            exclusive,
+            call_sites: call_sites.iter().map(|name| name.to_string()).collect(),
+            code_size: 2000,
+            caller_code_size: 1000,
+        };
+        assert_eq!(
+            lint(true, &["view_settings"]).help(),
+            "preload it while settings loads, with `wasm_split::preload(\"editor\")` next to \
+             the calls of `view_settings`"
+        );
+        assert!(lint(false, &[])
+            .help()
+            .ends_with("where settings is loaded"));
+    }
+}
@@ -0,0 +1,176 @@
+// Types of the API of the loader, `__wasm_split.js`, for JS and TypeScript
+// apps that use it besides the Rust functions of `wasm_split`.
+import type { ChunkName, Manifest, ManifestChunk, Priority, RoutePath } from "./wasm-split-manifest.js";
+
+export type * from "./wasm-split-manifest.js";
+
+/** Error codes of failed loads, matching `wasm_split::LoadError`. */
+export type LoadErrorCode = 1 | 2 | 3 | 4 | 5 | 6 | 7 | 8 | 9 | 10;
+
+/** What the loader knows about a failed chunk load, as returned by `loadFailure`. */
+export interface LoadFailure {
+  chunk: ChunkName;
+  url: string;
+  size?: number;
+  /** Name of the matching `wasm_split::LoadError` variant. */
+  error: string;
+  code: LoadErrorCode;
+  status?: number;
+  /** Import that a `LinkError` is about, if the browser names it. */
+  import?: string;
+  message: string;
+}
+
+export type LoadEvent =
+  | { type: "started"; chunk: ChunkName }
+  | { type: "progress"; chunk: ChunkName; bytes: number; total?: number }
+  | { type: "instantiated"; chunk: ChunkName }
+  | { type: "failed"; chunk: ChunkName; error: unknown };
+
+/** Report of a finished navigation of `wasm_split::navigation`. */
+export interface NavigationTiming {
+  route: string;
+  duration: number;
+  chunkTime: number;
+  dataTime: number;
+  overlapTime: number;
+  otherTime: number;
+  chunks: ChunkName[];
+}
+
+export interface LoadProgress {
+  bytes: number;
+  total: number;
+}
+
+export interface PreloadLink {
+  href: string;
+  integrity?: string;
+}
+
+export type ChunkLoadState = "unloaded" | "loading" | "loaded" | "failed";
+
+export interface ChunkInspection {
+  name: ChunkName;
+  kind: ManifestChunk["kind"];
+  file: string;
+  url?: string;
+  size: number;
+  dependencies: ChunkName[];
+  pinned: boolean;
+  auto: boolean;
+  entry?: string;
+  state: ChunkLoadState;
+  startedAt?: number;
+  loadedAt?: number;
+  fromCache?: boolean;
+  deduplicated: boolean;
+  error?: string;
+}
+
+export interface Inspection {
+  manifestUrl: string;
+  buildId: string;
+  manifest: Manifest;
+  chunks: ChunkInspection[];
+  folded: ChunkName[];
+  aliases: Partial<Record<ChunkName, ChunkName>>;
+  table: {
+    length?: number;
+    reservedSlots: { start: number; end: number };
+    freeSlots?: number[];
+  };
+}
+
+/** A session of the profiles that `wasm-split --profile` reads. */
+export interface Profile {
+  build_id: string;
+  calls: { module: string; function: string; first_call_ms: number; count: number }[];
+}
+
+/** Starts loading a chunk and its dependencies ahead of its first call. */
+export function preload(name: ChunkName): Promise<void>;
+
+/** Loads the chunks of an entry point of the `[entries]` config, e.g. in a worker. */
+export function loadEntry(name: string): Promise<void>;
+
+/** Loads all chunks that `route` needs. Fails for unknown routes. */
+export function loadRoute(
+  route: RoutePath,
+  onProgress?: (progress: LoadProgress) => void,
+): Promise<void>;
+
+/** Names of the chunks that the route of `path` needs, with their dependencies. */
+export function routeChunks(path: string): string[];
+
+/** Starts loading the chunks of the route of `path`, e.g. when the pointer enters a link to it. */
+export function preloadRoute(path: string): Promise<void>;
+
+/** Chunks of the route of `path` that have not started loading. */
+export function routePreloadLinks(path: string): PreloadLink[];
+
+/** What the loader knows about the last load of a chunk, if it failed. */
+export function loadFailure(name: ChunkName): LoadFailure | undefined;
+
+/** Whether the chunk of a split module has been instantiated. */
+export function isLoaded(name: ChunkName): boolean;
+
+/** Names of the instantiated chunks, in the order they were instantiated. */
+export function loadedChunks(): ChunkName[];
+
+/** Unloads the chunk of a split module unless other code may still call into it, and returns whether it did. */
+export function unload(name: ChunkName): boolean;
+
+/** Unloads the chunk of a split module and loads it again, bypassing the HTTP cache. */
+export function reload(name: ChunkName): Promise<void>;
+
+/** Changes how chunks are fetched from now on. */
+export function configure(options: {
+  maxConcurrentFetches?: number;
+  timeoutMs?: number;
+  priorities?: Partial<Record<ChunkName, Priority>>;
+  /** Makes the requests of chunks, manifests and signatures instead of the global `fetch`. */
+  fetch?: (url: URL | string, init: RequestInit) => Promise<Response>;
+  credentials?: RequestCredentials;
+  /** Headers added to those requests, or removed if `null`. */
+  headers?: Record<string, string | null>;
+}): void;
+
+/** Cancels the downloads in flight of the chunks of `names`, and returns their number. */
+export function abortLoads(names: string[]): number;
+
+/** Cancels the downloads in flight of the chunks of the route of `path`, and returns their number. */
+export function abortRoute(path: string): number;
+
+/** Loads chunks from `url` from now on. */
+export function setBaseUrl(url: string | URL): void;
+
+/** Loads the chunks of `urls`, by name, from their URL from now on, such as the assets that a bundler copied them to. */
+export function setChunkUrls(urls: Partial<Record<ChunkName, string | URL>>): void;
+
+/** URL of the main module to instantiate, which is the fallback once chunks failed to load. */
+export function mainModuleUrl(): URL;
+
+/** Registers a listener for the events of every chunk load, and returns a function unregistering it. */
+export function onLoadEvent(listener: (event: LoadEvent) => void): () => void;
+
+/** Registers a listener for the report of every finished navigation, and returns a function unregistering it. */
+export function onNavigationTiming(listener: (timing: NavigationTiming) => void): () => void;
+
+/** Hands out a reserved slot of the indirect function table, or undefined if all are in use. */
+export function allocateTableSlot(): number | undefined;
+
+/** Returns a slot obtained from `allocateTableSlot`. */
+export function freeTableSlot(slot: number): void;
+
+/** Snapshot of what the loader knows, for devtools and browser tests. */
+export function inspect(): Inspection;
+
+/** The calls of `#[wasm_split]` functions recorded with the `profile` feature. */
+export function getProfile(): Profile;
+
+/** Calls a split function once its module is loaded, for the entry points of `--emit-js-entries`. */
+export function callEntry(module: ChunkName, name: string, args: unknown[]): Promise<unknown>;
+
+/** Forgets every loaded chunk, for tests only. */
+export function reset(): void;
@@ -0,0 +1,2994 @@
+import * as mainGlue from "./main.js";
+
+// Loads are scheduled by priority class: a load only starts once no load of a
+// more urgent class is queued or in flight, so that route-critical chunks are
+// always fetched before speculative ones.
+const PRIORITIES = ["critical", "high", "low"];
+const FETCH_PRIORITIES = { critical: "high", high: "auto", low: "low" };
+const pendingTasks = PRIORITIES.map(() => []);
+const activeTasks = PRIORITIES.map(() => 0);
+
+// Most chunks fetched at once, from their request until they are compiled,
+// or 0 for no limit, so that on slow connections the chunks of a route don't
+// take the bandwidth that the route's data requests need; set by
+// `max-concurrent-fetches` in the `[loader]` table, or by `configure`.
+const MAX_CONCURRENT_FETCHES = 0;
+let maxConcurrentFetches = MAX_CONCURRENT_FETCHES;
+// Priorities that `configure` set for chunks, over those of the manifest.
+const priorityOverrides = new Map();
+
+function canStartTask() {
+  const active = activeTasks.reduce((sum, count) => sum + count, 0);
+  return maxConcurrentFetches === 0 || active < maxConcurrentFetches;
+}
+
+function pumpTasks() {
+  for (let i = 0; i < PRIORITIES.length; ++i) {
+    while (pendingTasks[i].length > 0 && canStartTask()) {
+      pendingTasks[i].shift()();
+    }
+    if (activeTasks[i] > 0 || pendingTasks[i].length > 0) return;
+  }
+}
+
+// Runs `task` once its class is next, handing it a function that frees its
+// place early, for a task that waits for something other than the network
+// once it has downloaded its chunk.
+function schedule(priority, task) {
+  const index = PRIORITIES.indexOf(priority);
+  return new Promise((resolve, reject) => {
+    pendingTasks[index].push(() => {
+      ++activeTasks[index];
+      let released = false;
+      const release = () => {
+        if (released) return;
+        released = true;
+        --activeTasks[index];
+        pumpTasks();
+      };
+      task(release).then(resolve, reject).finally(release);
+    });
+    pumpTasks();
+  });
+}
+
+function chunkPriority(chunk) {
+  return priorityOverrides.get(chunk.name) ?? chunk.priority;
+}
+
+// Changes how chunks are fetched from now on: `maxConcurrentFetches` limits
+// the chunks fetched at once, with 0 for no limit, `timeoutMs` fails loads
+// that take longer, with 0 for none, and `priorities` gives chunks by name the
+// priority class of their fetches, which `#[wasm_split(..., priority =
+// "...")]` otherwise sets. `fetch` replaces the global `fetch` for the
+// requests of chunks, manifests and their signatures, e.g. for a transport
+// of the app's own, `credentials` sets the option of the same name of these
+// requests, and `headers` adds headers to them, such as an `Authorization`
+// header for chunks served from an authenticated endpoint, or removes those
+// whose value is `null`. Loads already in flight are not affected.
+export function configure({
+  maxConcurrentFetches: max,
+  timeoutMs,
+  priorities,
+  fetch: customFetch,
+  credentials,
+  headers,
+} = {}) {
+  if (max !== undefined) maxConcurrentFetches = max;
+  if (timeoutMs !== undefined) loadTimeoutMs = timeoutMs;
+  if (customFetch !== undefined) requestFetch = customFetch;
+  if (credentials !== undefined) requestCredentials = credentials;
+  for (const [name, value] of Object.entries(headers ?? {})) {
+    if (value === null) {
+      requestHeaders.delete(name.toLowerCase());
+    } else {
+      requestHeaders.set(name.toLowerCase(), String(value));
+    }
+  }
+  for (const [name, priority] of Object.entries(priorities ?? {})) {
+    if (!PRIORITIES.includes(priority)) {
+      throw new TypeError(`Unknown priority "${priority}" of chunk "${name}"`);
+    }
+    priorityOverrides.set(name, priority);
+  }
+  pumpTasks();
+}
+
+// Called by `wasm_split::configure`, with lines of the setting and its value
+// separated by spaces, e.g. `priority view_b critical`.
+export function __wasm_split_configure(ptr, len) {
+  const priorities = {};
+  const headers = {};
+  let max;
+  let timeoutMs;
+  let credentials;
+  for (const line of decodeString(ptr, len).split("\n")) {
+    const [key, ...values] = line.split(" ");
+    if (key === "max-concurrent-fetches") max = Number(values[0]);
+    if (key === "timeout-ms") timeoutMs = Number(values[0]);
+    if (key === "priority") priorities[values[0]] = values[1];
+    if (key === "credentials") credentials = values[0];
+    if (key === "header") headers[values[0]] = values.slice(1).join(" ");
+    if (key === "remove-header") headers[values[0]] = null;
+  }
+  configure({ maxConcurrentFetches: max, timeoutMs, priorities, credentials, headers });
+}
+
+// Cancels the download of the chunks of `names` that are still downloading,
+// e.g. once the user navigated away from the route that needs them, so that
+// they don't take bandwidth from what the next route needs. Loads waiting
+// for them fail with `Aborted`, and the next load starts over. Returns the
+// number of downloads cancelled.
+export function abortLoads(names) {
+  let aborted = 0;
+  for (const name of names) {
+    const controller = getChunkState(name)?.abortController;
+    if (controller === undefined) continue;
+    controller.abort(new DOMException(`Load of ${name} aborted`, "AbortError"));
+    ++aborted;
+  }
+  return aborted;
+}
+
+// Cancels the downloads of the chunks of the route of `path`, as
+// `abortLoads` does.
+export function abortRoute(path) {
+  return abortLoads(routeChunks(path));
+}
+
+// Called by `wasm_split::abort_loads` with a comma-separated list of names.
+export function __wasm_split_abort_loads(namesPtr, namesLen) {
+  return abortLoads(decodeString(namesPtr, namesLen).split(","));
+}
+
+// Called by `wasm_split::abort_route`.
+export function __wasm_split_abort_route(pathPtr, pathLen) {
+  return abortRoute(decodeString(pathPtr, pathLen));
+}
+
+// Set on the worker that `#[wasm_split(..., worker)]` functions run on, which
+// instantiates a main module of its own.
+let workerMainExports;
+
+function getMainExports() {
+  return workerMainExports ?? mainGlue.initSync(undefined, undefined);
+}
+
+// Imports the JS snippets that the wasm-bindgen imports `imports` of a chunk
+// call, if the glue was processed by `wasm-split snippets` to import them
+// along with the chunks that use them instead of with the main module.
+function importSnippets(imports) {
+  if (!imports?.length) return undefined;
+  const snippets = mainGlue.__wasm_split_import_snippets?.(imports);
+  // Awaited once the chunk is compiled.
+  snippets?.catch(() => {});
+  return snippets;
+}
+
+function decodeString(ptr, len) {
+  // Copied, since browsers do not decode views of the `SharedArrayBuffer` of
+  // a shared memory.
+  return new TextDecoder().decode(
+    new Uint8Array(getMainExports().memory.buffer, ptr, len).slice(),
+  );
+}
+
+// Error codes passed to load callbacks, matching `wasm_split::LoadError`.
+const LOAD_OK = 0;
+const LOAD_ERROR = {
+  UnknownChunk: 1,
+  Network: 2,
+  Http: 3,
+  IntegrityMismatch: 4,
+  CompileError: 5,
+  InstantiationError: 6,
+  Timeout: 7,
+  Aborted: 8,
+  UnsupportedFeature: 9,
+  LinkError: 10,
+  InUse: 11,
+};
+
+// Thrown by the loader itself for failures that don't surface as exceptions.
+class ChunkLoadError extends Error {
+  constructor(code, detail, message) {
+    super(message);
+    this.code = code;
+    this.detail = detail;
+  }
+}
+
+// Maps an exception thrown while loading a chunk to `[code, detail]`.
+function classifyError(e) {
+  if (e instanceof ChunkLoadError) return [e.code, e.detail];
+  if (e instanceof WebAssembly.CompileError) {
+    return [LOAD_ERROR.CompileError, 0];
+  }
+  if (e instanceof WebAssembly.LinkError) return [LOAD_ERROR.LinkError, 0];
+  // Exceptions of wasm code are those of Rust panics unwinding out of the
+  // `on_load` hooks that instantiating a chunk runs.
+  if (
+    e instanceof WebAssembly.RuntimeError ||
+    (WebAssembly.Exception && e instanceof WebAssembly.Exception)
+  ) {
+    return [LOAD_ERROR.InstantiationError, 0];
+  }
+  if (e instanceof DOMException && e.name === "AbortError") {
+    return [LOAD_ERROR.Aborted, 0];
+  }
+  if (e instanceof DOMException && e.name === "TimeoutError") {
+    return [LOAD_ERROR.Timeout, 0];
+  }
+  // `fetch` rejects with a `TypeError` when no response could be obtained.
+  return [LOAD_ERROR.Network, 0];
+}
+
+// Name of the import that a `WebAssembly.LinkError` is about, from the
+// messages of V8 (`Import #0 "env" "name": ...`, or `Import #0
+// module="env" function="name" error: ...`), SpiderMonkey (`import object
+// field 'name' is not a Function`) and JavaScriptCore (`import function
+// env:name must be callable`).
+function linkErrorImport(e) {
+  if (!(e instanceof WebAssembly.LinkError)) return undefined;
+  const match =
+    /Import #\d+ (?:module=)?"[^"]*" (?:function=)?"([^"]*)"/.exec(e.message) ??
+    /field '([^']*)'/.exec(e.message) ??
+    /import \w+ [^:\s]*:(\S+)/.exec(e.message);
+  return match?.[1];
+}
+
+// What the loader knows about the failed load of the chunk of `state`, as
+// logged to the console and returned by `loadFailure`.
+function describeFailure(name, state, e) {
+  const [code, detail] = classifyError(e);
+  return {
+    chunk: name,
+    url: state.url.href,
+    size: state.chunk.size,
+    error: Object.keys(LOAD_ERROR).find((key) => LOAD_ERROR[key] === code),
+    code,
+    status: code === LOAD_ERROR.Http ? detail : undefined,
+    import: linkErrorImport(e),
+    message: String(e?.message ?? e),
+  };
+}
+
+function invokeCallback(callbackIndex, callbackData, code, detail = 0) {
+  getMainExports().__indirect_function_table.get(callbackIndex)(
+    callbackData,
+    code,
+    detail,
+  );
+}
+
+
+// Matches the location of a wasm frame in an `Error.stack` string, e.g.
+// `https://example.com/view_c.wasm:wasm-function[12]:0x3a4` (Chrome/Firefox)
+// for a chunk compiled while it downloads. Chrome names a module compiled
+// from its bytes after the module name in its name section instead, which
+// is the chunk name, e.g. `wasm://wasm/view_c-1a2b3c4d:wasm-function[12]`.
+const WASM_FRAME_PATTERN = /([^\s(@]+):wasm-function\[(\d+)\]/g;
+const WASM_BYTES_URL_PATTERN = /^wasm:\/\/wasm\/(.+)-[0-9a-f]{8}$/;
+
+// The chunk that a frame with `url` belongs to, if it is loaded.
+function findLoadedChunk(url) {
+  const { loadedChunks } = getRegistry();
+  const loaded = loadedChunks.get(url);
+  if (loaded !== undefined) return loaded;
+  const name = WASM_BYTES_URL_PATTERN.exec(url)?.[1];
+  for (const loaded of loadedChunks.values()) {
+    if (loaded.chunk.name === name) return loaded;
+  }
+  return undefined;
+}
+
+// Called by `wasm_split::panic_hook` to attribute a panic to the chunk
+// containing the innermost split-code stack frame, if any.
+export function __wasm_split_report_panic(messagePtr, messageLen) {
+  const message = decodeString(messagePtr, messageLen);
+  // The frames of the panic machinery and the hook alone exceed the 10
+  // that V8 keeps by default, which would cut off those of the split code.
+  const stackTraceLimit = Error.stackTraceLimit;
+  if (typeof stackTraceLimit === "number") Error.stackTraceLimit = Infinity;
+  let stack;
+  try {
+    stack = new Error().stack;
+  } finally {
+    if (typeof stackTraceLimit === "number") {
+      Error.stackTraceLimit = stackTraceLimit;
+    }
+  }
+  for (const [, url, index] of stack.matchAll(WASM_FRAME_PATTERN)) {
+    const loaded = findLoadedChunk(url);
+    if (loaded === undefined) continue;
+    const functionIndex = Number(index);
+    const { start, end } = loaded.chunk.defined_functions;
+    if (functionIndex < start || functionIndex >= end) continue;
+    console.error(
+      `panic in split chunk "${loaded.chunk.name}" ` +
+        `(function ${functionIndex}, loaded at ${loaded.loadedAt.toFixed(1)}ms): ` +
+        message,
+    );
+    return;
+  }
+}
+
+function getImports() {
+  const mainExports = getMainExports();
+  return {
+    env: {
+      memory: mainExports.memory,
+    },
+    // Split modules import the table, memory and globals of the main module
+    // under their export names.
+    __wasm_split: mainExports,
+  };
+}
+
+// Calls a function in a split module through its slot in the indirect
+// function table of the main module. Used by split points of modules that
+// were split with `--table-only`.
+function callTableSlot(slot, args) {
+  return getMainExports().__indirect_function_table.get(slot)(...args);
+}
+
+// Table slots of the split functions that the JS entry points written with
+// `--emit-js-entries` call, by split module and function name, and those of
+// the fallback of `--emit-fallback`, which has every module folded into it.
+const JS_ENTRY_SLOTS = {};
+const FALLBACK_JS_ENTRY_SLOTS = {};
+
+// Calls the split function `name` of split module `module` with `args` once
+// the module is loaded, for its JS entry point.
+export async function callEntry(module, name, args) {
+  await loadChunk(module);
+  const slots = USE_FALLBACK ? FALLBACK_JS_ENTRY_SLOTS : JS_ENTRY_SLOTS;
+  return callTableSlot(slots[module][name], args);
+}
+
+// Runs the `#[wasm_split::on_load]` functions of a module, given by their
+// table slots, before any split point of the module is called. A panicking
+// hook traps, or throws with `panic = "unwind"`, which fails the load like a
+// failed instantiation.
+function runOnLoadHooks(slots) {
+  for (const slot of slots ?? []) callTableSlot(slot, []);
+}
+
+// The reserved slots of the indirect function table that are not in use,
+// all of them until the first is allocated.
+function getFreeTableSlots() {
+  const registry = getRegistry();
+  if (registry.freeTableSlots === undefined) {
+    const { start, end } = MANIFEST.reserved_table_slots ?? {
+      start: 0,
+      end: 0,
+    };
+    registry.freeTableSlots = [];
+    for (let slot = end - 1; slot >= start; --slot) {
+      registry.freeTableSlots.push(slot);
+    }
+  }
+  return registry.freeTableSlots;
+}
+
+// Hands out a reserved slot of the indirect function table, for JS code that
+// registers callbacks with `__indirect_function_table.set` once a chunk has
+// loaded. Returns undefined if all reserved slots are in use: the table has a
+// fixed size, so it cannot be grown instead.
+export function allocateTableSlot() {
+  return getFreeTableSlots().pop();
+}
+
+// Returns a slot obtained from `allocateTableSlot`, clearing it so that the
+// callback it held can be garbage collected. A slot allocated before `reset`
+// is already free in the pool that replaced its own.
+export function freeTableSlot(slot) {
+  getMainExports().__indirect_function_table.set(slot, null);
+  const free = getFreeTableSlots();
+  if (!free.includes(slot)) free.push(slot);
+}
+
+// Called by `wasm_split::TableSlot`, with slot 0, which is never reserved,
+// standing for none.
+export function __wasm_split_allocate_table_slot() {
+  return allocateTableSlot() ?? 0;
+}
+
+export function __wasm_split_free_table_slot(slot) {
+  freeTableSlot(slot);
+}
+
+// What has been loaded into the main module, shared through `globalThis` by
+// every copy of this script for the same build, so that an app that remounts
+// and imports the loader anew, e.g. under another URL, does not fetch its
+// chunks again. Registries are kept per instance of the main module, whose
+// memory identifies it, since a fresh instance has none of the chunks. Each
+// holds:
+//
+// - `chunkStates`: the load state of every non-main chunk, keyed by chunk
+//   name. `promise` is set once a load of the chunk has started and is reset
+//   if it fails, so that concurrent loads share a single instantiation while
+//   failed ones can be retried. `instantiated` is set once it is, which a
+//   call through one of its table slots may do while a load is under way;
+//   see `loadChunkSync`. Pinned chunks also keep their compiled `module`
+//   referenced once loaded. `buildId` is that of the manifest the chunk was
+//   moved to by `reloadManifest`, if it belongs to another build.
+// - `loadedChunks`: chunks that have been instantiated, keyed by chunk URL.
+// - `freeTableSlots`: slots of the indirect function table reserved with
+//   `#[wasm_split(table_slots = ...)]` that are not in use.
+//
+// A registry is created on first use, since `MANIFEST` follows this code.
+const REGISTRIES = Symbol.for("wasm-split:registries");
+
+function getRegistry() {
+  const byBuild = (globalThis[REGISTRIES] ??= new Map());
+  let byInstance = byBuild.get(MANIFEST.build_id);
+  if (byInstance === undefined) {
+    byInstance = new WeakMap();
+    byBuild.set(MANIFEST.build_id, byInstance);
+  }
+  const { memory } = getMainExports();
+  let registry = byInstance.get(memory);
+  if (registry === undefined) {
+    registry = {
+      chunkStates: createChunkStates(),
+      loadedChunks: new Map(),
+      freeTableSlots: undefined,
+    };
+    byInstance.set(memory, registry);
+  }
+  return registry;
+}
+
+// Forgets every chunk loaded by any copy of this script, as well as shared
+// compilations and, without wasm-bindgen, instances of the main module, for
+// tests that load chunks into mocked imports and need each test to start
+// from scratch. Copies that instantiated the main module keep their instance.
+// Real apps must not call it: chunks that are loaded again into the same
+// main module would reinitialize its memory.
+export function reset() {
+  globalThis[REGISTRIES]?.clear();
+  globalThis[COMPILED_BY_HASH]?.clear();
+  globalThis[Symbol.for("wasm-split:main-instantiations")]?.clear();
+}
+
+// URL of a chunk file, relative to `base`, unless `setChunkUrls` gave it one.
+// The ID of the build whose manifest lists the chunk is included so that
+// caches never serve a chunk of another build, which the URLs of bundled
+// assets already ensure by their hash.
+function chunkUrl(chunk, base, buildId = MANIFEST.build_id) {
+  const asset = chunkAssetUrls.get(chunk.name);
+  if (asset !== undefined) return new URL(asset, base);
+  const url = new URL("./" + chunk.file, base);
+  url.searchParams.set("build", buildId);
+  return url;
+}
+
+function createChunkStates() {
+  const chunkStates = new Map();
+  if (USE_FALLBACK) {
+    // Every split module is part of the fallback's main module, whose code
+    // still only calls the snippets of a chunk once it is loaded.
+    for (const { name, on_load } of FALLBACK_FOLDED) {
+      const imports = fallbackSnippetImports(name);
+      chunkStates.set(name, {
+        chunk: undefined,
+        onLoad: on_load,
+        imports,
+        promise:
+          on_load === undefined && imports.length === 0
+            ? Promise.resolve(0)
+            : undefined,
+      });
+    }
+    for (const [alias, name] of Object.entries(MANIFEST.aliases ?? {})) {
+      const state = chunkStates.get(name);
+      if (state !== undefined) chunkStates.set(alias, state);
+    }
+    return chunkStates;
+  }
+  const baseUrl = getChunkBaseUrl() ?? OUTPUT_DIR_URL;
+  for (const chunk of MANIFEST.chunks) {
+    if (chunk.kind === "main") continue;
+    const url = chunkUrl(chunk, baseUrl);
+    chunkStates.set(chunk.name, { chunk, url, promise: undefined });
+  }
+  // Folded modules are part of the main module and thus always loaded, but
+  // their `on_load` hooks still run on the first load.
+  for (const { name, on_load } of MANIFEST.folded ?? []) {
+    chunkStates.set(name, {
+      chunk: undefined,
+      onLoad: on_load,
+      promise: on_load === undefined ? Promise.resolve(0) : undefined,
+    });
+  }
+  // Modules co-located with another by `with` load its chunk.
+  for (const [alias, name] of Object.entries(MANIFEST.aliases ?? {})) {
+    const state = chunkStates.get(name);
+    if (state !== undefined) chunkStates.set(alias, state);
+  }
+  return chunkStates;
+}
+
+// The `imports` of the chunk of the module `name` in the manifest, and of its
+// dependencies.
+function fallbackSnippetImports(name, seen = new Set()) {
+  const chunkName = MANIFEST.aliases?.[name] ?? name;
+  if (seen.has(chunkName)) return [];
+  seen.add(chunkName);
+  const chunk = MANIFEST.chunks.find((chunk) => chunk.name === chunkName);
+  if (chunk === undefined) return [];
+  return [
+    ...(chunk.imports ?? []),
+    ...(chunk.dependencies ?? []).flatMap((dep) =>
+      fallbackSnippetImports(dep, seen),
+    ),
+  ];
+}
+
+function getChunkState(name) {
+  return getRegistry().chunkStates.get(name);
+}
+
+// Paths relative to this script, and of the output directory relative to the
+// manifest, which wasm-split rewrites according to the `[output]` table of
+// `wasm-split.toml` (as it does the import of the glue above). Chunk files in
+// the manifest are relative to the output directory. A build written with
+// `--asset-version` has all of these within its version directory, so that
+// reloading the manifest never moves a session to the chunks of another
+// deployment.
+const MANIFEST_URL = new URL("./wasm-split-manifest.json", import.meta.url);
+const OUTPUT_DIR_URL = new URL("./", import.meta.url);
+const OUTPUT_DIR_FROM_MANIFEST = "./";
+
+// Base URL of the chunk files, for apps whose chunks are served from a CDN or
+// under another path than the loader, as decided at page load. Set by
+// `setBaseUrl`, or else read on first use from `globalThis.WASM_SPLIT_BASE_URL`
+// or the page's `<meta name="wasm-split-base-url" content="...">`. Relative
+// URLs resolve against the page.
+let chunkBaseUrl;
+let readBaseUrlConfig = false;
+
+function resolveBaseUrl(url) {
+  const resolved = new URL(url, globalThis.location?.href ?? import.meta.url);
+  // Chunk files resolve against the base as a directory.
+  if (!resolved.pathname.endsWith("/")) resolved.pathname += "/";
+  return resolved;
+}
+
+function getChunkBaseUrl() {
+  if (!readBaseUrlConfig) {
+    readBaseUrlConfig = true;
+    const configured =
+      globalThis.WASM_SPLIT_BASE_URL ??
+      globalThis.document?.querySelector('meta[name="wasm-split-base-url"]')
+        ?.content;
+    if (configured !== undefined) chunkBaseUrl = resolveBaseUrl(configured);
+  }
+  return chunkBaseUrl;
+}
+
+// Loads chunks from `url` from now on, including those that have not started
+// loading yet, while chunks that are loading or loaded keep their URL.
+// Manifests reloaded later are resolved against it too.
+export function setBaseUrl(url) {
+  chunkBaseUrl = resolveBaseUrl(url);
+  readBaseUrlConfig = true;
+  let registry;
+  try {
+    registry = getRegistry();
+  } catch {
+    // The main module is not instantiated, so neither are the chunk states,
+    // which start out with the new base.
+    return;
+  }
+  // Aliases share the state of the module they are co-located with.
+  for (const state of new Set(registry.chunkStates.values())) {
+    if (state.chunk === undefined || state.promise !== undefined) continue;
+    state.url = chunkUrl(state.chunk, chunkBaseUrl, state.buildId);
+  }
+}
+
+// URLs of chunks by name, which the module of `--emit-bundler-assets` sets to
+// those of the assets that a bundler copied the chunk files to.
+const chunkAssetUrls = new Map();
+
+// Loads the chunks of `urls`, by name, from their URL from now on, rather
+// than from the base URL, as `setBaseUrl` does for chunks of no URL here.
+export function setChunkUrls(urls) {
+  for (const [name, url] of Object.entries(urls)) {
+    chunkAssetUrls.set(name, String(url));
+  }
+  let registry;
+  try {
+    registry = getRegistry();
+  } catch {
+    return;
+  }
+  for (const state of new Set(registry.chunkStates.values())) {
+    if (state.chunk === undefined || state.promise !== undefined) continue;
+    state.url = chunkUrl(
+      state.chunk,
+      getChunkBaseUrl() ?? OUTPUT_DIR_URL,
+      state.buildId,
+    );
+  }
+}
+
+// Called by `wasm_split::set_base_url`.
+export function __wasm_split_set_base_url(urlPtr, urlLen) {
+  setBaseUrl(decodeString(urlPtr, urlLen));
+}
+
+// Cache modes of chunk and manifest requests, rewritten according to the
+// `[loader]` table of `wasm-split.toml`.
+const CHUNK_CACHE = "force-cache";
+const MANIFEST_CACHE = "no-cache";
+
+// `credentials` option of the requests of chunks and manifests, set by
+// `credentials` in the `[loader]` table, or by `configure`, along with the
+// `fetch` that makes them and the headers that they add, by lowercase name.
+const REQUEST_CREDENTIALS = null;
+let requestCredentials = REQUEST_CREDENTIALS;
+let requestFetch = null;
+const requestHeaders = new Map();
+
+// Fetches a chunk, manifest or signature with the settings of `configure`.
+// `fetch` is looked up on each call, as tests replace it, and is the one that
+// reads files with `--target node`.
+function fetchResource(url, options = {}) {
+  const init = { ...options };
+  if (requestCredentials !== null) init.credentials = requestCredentials;
+  if (requestHeaders.size > 0) {
+    init.headers = { ...init.headers, ...Object.fromEntries(requestHeaders) };
+  }
+  return (requestFetch ?? fetch)(url, init);
+}
+
+// Retries of chunk requests that fail with a transient error, and the delay
+// before the first, which doubles for each further one; set by `retries` and
+// `retry-delay-ms` in the `[loader]` table.
+const CHUNK_RETRIES = 0;
+const RETRY_DELAY_MS = 500;
+
+// Time after which a chunk that has not been fetched and compiled yet fails
+// with a `Timeout`, including its retries, or 0 to wait as long as the
+// browser does; set by `timeout-ms` in the `[loader]` table, or by
+// `configure`.
+const LOAD_TIMEOUT_MS = 0;
+let loadTimeoutMs = LOAD_TIMEOUT_MS;
+
+// As `wasm_split::LoadError::is_transient`.
+function isTransientStatus(status) {
+  return status === 408 || status === 429 || status >= 500;
+}
+
+async function fetchChunk(url, options) {
+  for (let attempt = 0; ; ++attempt) {
+    const retry = attempt < CHUNK_RETRIES;
+    let response;
+    try {
+      response = await fetchResource(url, options);
+    } catch (e) {
+      // Aborted or timed out requests are not retried.
+      if (!retry || options?.signal?.aborted) throw e;
+    }
+    if (response !== undefined) {
+      if (response.ok || !retry || !isTransientStatus(response.status)) {
+        return response;
+      }
+    }
+    const delay = RETRY_DELAY_MS * 2 ** attempt;
+    console.warn(`Retrying ${url} in ${delay}ms`);
+    await new Promise((resolve) => setTimeout(resolve, delay));
+    options?.signal?.throwIfAborted();
+  }
+}
+
+// After this many chunk loads failed as if the network mangled them, the page
+// is reloaded with the main module of `--emit-fallback`, which has every split
+// module folded into it and so needs no chunks; never if 0. Set by
+// `fallback-after` in the `[loader]` table. Calls waiting for chunks are not
+// moved over, as the state of the app is in the memory of the main module
+// that they were made from, but run again in the reloaded app.
+const FALLBACK_AFTER = 0;
+const FALLBACK_URL = new URL("./wasm-split-fallback.wasm", import.meta.url);
+const FALLBACK_INTEGRITY = null;
+// The split modules folded into the fallback, with the table slots that
+// their `on_load` hooks have in it.
+const FALLBACK_FOLDED = [];
+const FALLBACK_STORAGE_KEY = "wasm-split:fallback";
+
+// Whether the build has the fallback of `--emit-fallback`.
+const HAS_FALLBACK = false;
+// Query parameter and local storage key that turn splitting off for
+// debugging, e.g. to rule out the splitting layer when a user reports a bug
+// of a route: `?wasm_split=off` runs the page with the fallback, and keeps
+// doing so for later pages of the origin, until `?wasm_split=on`.
+const SPLIT_SWITCH_PARAM = "wasm_split";
+const SPLIT_SWITCH_STORAGE_KEY = "wasm-split:off";
+
+// Whether this page runs the fallback: if splitting was turned off, and as
+// every page does for the rest of the browser session once the fallback was
+// needed. Workers have no storage of the page, and are not used by the
+// fallback.
+const USE_FALLBACK =
+  readSplitSwitch() || (FALLBACK_AFTER > 0 && readFallbackFlag());
+
+function readFallbackFlag() {
+  try {
+    return (globalThis.sessionStorage?.getItem(FALLBACK_STORAGE_KEY) ?? null) !== null;
+  } catch {
+    return false;
+  }
+}
+
+// Whether splitting is turned off for this page, remembering the query
+// parameter of the page in local storage.
+function readSplitSwitch() {
+  if (globalThis.document === undefined) return false;
+  let off;
+  try {
+    const param = new URLSearchParams(globalThis.location.search).get(
+      SPLIT_SWITCH_PARAM,
+    );
+    if (param === "off") {
+      localStorage.setItem(SPLIT_SWITCH_STORAGE_KEY, "1");
+    } else if (param === "on") {
+      localStorage.removeItem(SPLIT_SWITCH_STORAGE_KEY);
+    }
+    off =
+      param === "off" ||
+      (param !== "on" && localStorage.getItem(SPLIT_SWITCH_STORAGE_KEY) !== null);
+  } catch {
+    // Without local storage, only the query parameter counts.
+    off =
+      new URLSearchParams(globalThis.location.search).get(SPLIT_SWITCH_PARAM) ===
+      "off";
+  }
+  if (!off) return false;
+  if (!HAS_FALLBACK) {
+    console.warn(
+      "wasm-split: splitting cannot be turned off, as the build has no " +
+        "fallback; split it with --emit-fallback",
+    );
+    return false;
+  }
+  console.info(
+    `wasm-split: splitting is off, running ${FALLBACK_URL}; ` +
+      `?${SPLIT_SWITCH_PARAM}=on turns it back on`,
+  );
+  return true;
+}
+
+let failedLoads = 0;
+
+function countFailedLoad(e) {
+  const [code] = classifyError(e);
+  const mangled = [
+    LOAD_ERROR.Network,
+    LOAD_ERROR.Http,
+    LOAD_ERROR.IntegrityMismatch,
+    LOAD_ERROR.CompileError,
+    LOAD_ERROR.Timeout,
+  ];
+  if (FALLBACK_AFTER === 0 || !mangled.includes(code)) return;
+  if (++failedLoads < FALLBACK_AFTER || globalThis.location === undefined) return;
+  try {
+    sessionStorage.setItem(FALLBACK_STORAGE_KEY, MANIFEST.build_id);
+  } catch {
+    // Without session storage, reloading would split the app again.
+    return;
+  }
+  console.warn(
+    `wasm-split: ${failedLoads} chunk loads failed, reloading with ${FALLBACK_URL}`,
+  );
+  globalThis.location.reload();
+}
+
+// URL of the main module that the page instantiates, for apps built with
+// wasm-bindgen to pass to its `init`, as in
+// `await init({ module_or_path: mainModuleUrl() })`, so that the fallback
+// is used once chunks failed to load.
+export function mainModuleUrl() {
+  return USE_FALLBACK ? FALLBACK_URL : WORKER_MAIN_URL;
+}
+
+// Whether chunks are taken from, and kept in, the cache of their build in
+// Cache Storage, the same one that `precacheAll` of `wasm-split-sw.js` fills;
+// set by `cache-storage` in the `[loader]` table.
+const CACHE_STORAGE = false;
+const CACHE_PREFIX = "wasm-split-";
+
+const buildCaches = new Map();
+
+// The cache of `build`, opened once the caches of all other builds are
+// deleted, or undefined where Cache Storage is unavailable, such as on
+// insecure origins.
+function openBuildCache(build) {
+  let cache = buildCaches.get(build);
+  if (cache === undefined) {
+    cache = (async () => {
+      if (globalThis.caches === undefined) return undefined;
+      const name = CACHE_PREFIX + build;
+      try {
+        for (const key of await caches.keys()) {
+          if (key.startsWith(CACHE_PREFIX) && key !== name) {
+            await caches.delete(key);
+          }
+        }
+        return await caches.open(name);
+      } catch (e) {
+        console.warn("wasm-split: Cache Storage is unavailable: " + e);
+        return undefined;
+      }
+    })();
+    buildCaches.set(build, cache);
+  }
+  return cache;
+}
+
+// As `fetchChunk`, but cache first with `CACHE_STORAGE`. A cached chunk was
+// checked against its `integrity` when it was first fetched, so it is not
+// checked again.
+async function fetchCachedChunk(url, options) {
+  const build = url.searchParams.get("build");
+  const cache =
+    CACHE_STORAGE && build !== null ? await openBuildCache(build) : undefined;
+  if (cache === undefined) return fetchChunk(url, options);
+  const cached =
+    options.cache === "reload"
+      ? undefined
+      : await cache.match(url).catch(() => undefined);
+  if (cached !== undefined) return cached;
+  const response = await fetchChunk(url, options);
+  if (response.ok) {
+    // Not awaited, so that the chunk is compiled while it is stored.
+    cache
+      .put(url, response.clone())
+      .catch((e) => console.warn(`wasm-split: Failed to cache ${url}: ${e}`));
+  }
+  return response;
+}
+
+// Whether the first chunk load revalidates the embedded manifest against the
+// manifest file, set by `manifest-strategy` in the `[loader]` table.
+const MANIFEST_STRATEGY = "embedded";
+
+// Public key exported by main modules built with
+// `wasm_split::manifest_public_key!`, which fetched manifests must be signed
+// with.
+function getManifestPublicKey() {
+  const { __wasm_split_manifest_public_key: address, memory } =
+    getMainExports();
+  if (address === undefined) return undefined;
+  return new Uint8Array(memory.buffer, address.value, 32).slice();
+}
+
+// Checks an Ed25519 signature of `bytes` by the manifest public key, where
+// `what` names the signed manifest in errors.
+async function verifySignature(publicKey, signature, bytes, what) {
+  let key;
+  try {
+    key = await crypto.subtle.importKey(
+      "raw",
+      publicKey,
+      { name: "Ed25519" },
+      false,
+      ["verify"],
+    );
+  } catch (e) {
+    throw new ChunkLoadError(
+      LOAD_ERROR.UnsupportedFeature,
+      0,
+      "Ed25519 signatures are not supported: " + e,
+    );
+  }
+  const valid = await crypto.subtle.verify(
+    { name: "Ed25519" },
+    key,
+    signature,
+    bytes,
+  );
+  if (!valid) {
+    throw new ChunkLoadError(
+      LOAD_ERROR.IntegrityMismatch,
+      0,
+      "Invalid signature of " + what,
+    );
+  }
+}
+
+// Parses a fetched manifest, after checking that the `.sig` file next to it
+// holds its signature if the main module embeds a public key. Nothing in a
+// manifest is used before that.
+async function readManifest(response) {
+  const bytes = await response.arrayBuffer();
+  const publicKey = getManifestPublicKey();
+  if (publicKey !== undefined) {
+    const signatureUrl = new URL(response.url);
+    signatureUrl.pathname += ".sig";
+    const signatureResponse = await fetchResource(signatureUrl, {
+      cache: MANIFEST_CACHE,
+    });
+    if (!signatureResponse.ok) {
+      throw new ChunkLoadError(
+        LOAD_ERROR.Http,
+        signatureResponse.status,
+        "HTTP status " + signatureResponse.status + " for " + signatureUrl,
+      );
+    }
+    await verifySignature(
+      publicKey,
+      await signatureResponse.arrayBuffer(),
+      bytes,
+      response.url,
+    );
+  }
+  return JSON.parse(new TextDecoder().decode(bytes));
+}
+
+let embeddedManifestCheck;
+
+// Checks the signature of the manifest embedded in this script, which
+// wasm-split signs along with the manifest file, once before the first chunk
+// is fetched, so that chunks are only fetched and checked against the
+// digests of a signed manifest.
+function checkEmbeddedManifest() {
+  embeddedManifestCheck ??= (async () => {
+    const publicKey = getManifestPublicKey();
+    if (publicKey === undefined) return;
+    if (MANIFEST_SIGNATURE === undefined) {
+      throw new ChunkLoadError(
+        LOAD_ERROR.IntegrityMismatch,
+        0,
+        "The manifest embedded in the loader is not signed",
+      );
+    }
+    const signature = new Uint8Array(
+      MANIFEST_SIGNATURE.match(/../g).map((byte) => parseInt(byte, 16)),
+    );
+    await verifySignature(
+      publicKey,
+      signature,
+      new TextEncoder().encode(MANIFEST_JSON),
+      "the manifest embedded in " + import.meta.url,
+    );
+  })();
+  return embeddedManifestCheck;
+}
+
+// Checks a chunk against the SHA-256 digest that a signed manifest lists for
+// it, as the signature only covers the manifest itself.
+async function checkChunkDigest(state, bytes) {
+  const digest = new Uint8Array(await crypto.subtle.digest("SHA-256", bytes));
+  const hex = Array.from(digest, (byte) =>
+    byte.toString(16).padStart(2, "0"),
+  ).join("");
+  if (hex !== state.chunk.sha256) {
+    throw new ChunkLoadError(
+      LOAD_ERROR.IntegrityMismatch,
+      0,
+      "Chunk " +
+        state.chunk.name +
+        " does not match the SHA-256 digest in the signed manifest",
+    );
+  }
+}
+
+// Checks that a compiled chunk defines the split functions of its entries
+// with the signatures in the manifest, which a chunk of another build, e.g.
+// one left in a cache or on the server by an earlier deployment, may not. Its
+// functions would otherwise only fail once called, with a signature mismatch
+// of the table slot.
+function checkSignatures(state, compiledModule) {
+  const expected = state.chunk.signatures;
+  if (expected === undefined) return;
+  const actual = new Map();
+  for (const section of WebAssembly.Module.customSections(
+    compiledModule,
+    "wasm_split_signatures",
+  )) {
+    for (const line of new TextDecoder().decode(section).split("\n")) {
+      const [entry, signature] = line.split(" ");
+      if (signature !== undefined) actual.set(entry, signature);
+    }
+  }
+  const mismatched = Object.entries(expected)
+    .filter(([entry, signature]) => actual.get(entry) !== signature)
+    .map(([entry]) => entry.replace(/^.*?_export_[0-9a-f]{32}_/, ""));
+  if (mismatched.length > 0) {
+    throw new ChunkLoadError(
+      LOAD_ERROR.InstantiationError,
+      0,
+      "Chunk " +
+        state.chunk.name +
+        " was built with other signatures of " +
+        mismatched.join(", ") +
+        " than the main module, and is likely left over from another build",
+    );
+  }
+}
+
+// Whether `next`, a chunk of the manifest of another build, can be loaded in
+// place of `chunk` of the running one: it is the same file, or it defines
+// the same split functions, with the same signatures, in the same table
+// slots. What else it imports from the main module is checked as it links.
+function isCompatibleChunk(chunk, next) {
+  if (next === undefined) return false;
+  if (chunk.hash !== undefined && next.hash === chunk.hash) return true;
+  // Of objects and arrays of the manifest, whose fields may be missing.
+  const same = (a = {}, b = {}) =>
+    Object.keys(a).length === Object.keys(b).length &&
+    Object.keys(a).every((key) => a[key] === b[key]);
+  return (
+    chunk.signatures !== undefined &&
+    same(next.signatures, chunk.signatures) &&
+    same(next.table_slots, chunk.table_slots) &&
+    same(next.dependencies, chunk.dependencies)
+  );
+}
+
+// Re-fetches the manifest and, if the running build can load its chunks,
+// moves chunks that haven't started loading to the URLs it lists. Returns
+// whether it can: the manifest belongs to the running build, or to another
+// build, such as a redeployment, with a compatible chunk for each of the
+// running build's.
+async function reloadManifest() {
+  const response = await fetchResource(MANIFEST_URL, { cache: MANIFEST_CACHE });
+  if (!response.ok) {
+    throw new ChunkLoadError(
+      LOAD_ERROR.Http,
+      response.status,
+      `HTTP status ${response.status}`,
+    );
+  }
+  const manifest = await readManifest(response);
+  if (manifest.build_id !== MANIFEST.build_id) {
+    const chunks = new Map(manifest.chunks.map((chunk) => [chunk.name, chunk]));
+    const compatible = MANIFEST.chunks.every(
+      (chunk) =>
+        chunk.kind === "main" ||
+        isCompatibleChunk(
+          getChunkState(chunk.name)?.chunk ?? chunk,
+          chunks.get(chunk.name),
+        ),
+    );
+    if (!compatible) return false;
+  }
+  for (const chunk of manifest.chunks) {
+    const state = getChunkState(chunk.name);
+    if (state?.chunk === undefined || state.promise !== undefined) continue;
+    // Pinned chunks stay at the URL they may already be cached under.
+    if (state.chunk.pinned) continue;
+    state.chunk = chunk;
+    state.buildId = manifest.build_id;
+    state.url = chunkUrl(
+      chunk,
+      getChunkBaseUrl() ?? new URL(OUTPUT_DIR_FROM_MANIFEST, response.url),
+      manifest.build_id,
+    );
+  }
+  return true;
+}
+
+let manifestRevalidation;
+
+// Reloads the manifest in the background with the `stale-while-revalidate`
+// strategy, once per page, while loads that already started keep the URLs of
+// the embedded manifest.
+function revalidateManifest() {
+  if (MANIFEST_STRATEGY !== "stale-while-revalidate" || isWorker) return;
+  manifestRevalidation ??= reloadManifest().then(
+    (compatible) => {
+      // The app finds out with `wasm_split::reload_manifest`, and reloads.
+      if (!compatible) {
+        console.warn(
+          MANIFEST_URL.href +
+            " belongs to an incompatible build; keeping the embedded manifest",
+        );
+      }
+    },
+    (e) => console.error("Failed to revalidate " + MANIFEST_URL.href, e),
+  );
+}
+
+// Called by `wasm_split::reload_manifest`. On success, the callback detail is
+// 1 if the manifest belongs to an incompatible build.
+export function __wasm_split_reload_manifest(callbackIndex, callbackData) {
+  reloadManifest().then(
+    (compatible) =>
+      invokeCallback(callbackIndex, callbackData, LOAD_OK, compatible ? 0 : 1),
+    (e) => {
+      console.error("Failed to reload " + MANIFEST_URL.href, e);
+      invokeCallback(callbackIndex, callbackData, ...classifyError(e));
+    },
+  );
+}
+
+// Smallest modules using each feature that the manifest may list for a chunk,
+// to probe for support with `WebAssembly.validate`.
+const FEATURE_PROBES = {
+  simd: [
+    0, 97, 115, 109, 1, 0, 0, 0, 1, 5, 1, 96, 0, 1, 123, 3, 2, 1, 0, 10, 8, 1,
+    6, 0, 65, 0, 253, 15, 11,
+  ],
+  atomics: [
+    0, 97, 115, 109, 1, 0, 0, 0, 1, 4, 1, 96, 0, 0, 3, 2, 1, 0, 5, 4, 1, 3, 1,
+    1, 10, 11, 1, 9, 0, 65, 0, 254, 16, 2, 0, 26, 11,
+  ],
+  "bulk-memory": [
+    0, 97, 115, 109, 1, 0, 0, 0, 1, 4, 1, 96, 0, 0, 3, 2, 1, 0, 5, 3, 1, 0, 1,
+    10, 14, 1, 12, 0, 65, 0, 65, 0, 65, 0, 252, 10, 0, 0, 11,
+  ],
+  // A module defining a tag.
+  "exception-handling": [0, 97, 115, 109, 1, 0, 0, 0, 1, 4, 1, 96, 0, 0, 13, 3, 1, 0, 0],
+  // A module defining two memories.
+  "multi-memory": [0, 97, 115, 109, 1, 0, 0, 0, 5, 5, 2, 0, 0, 0, 0],
+};
+const featureSupport = new Map();
+
+// Features used by a chunk that this browser cannot compile. Features without
+// a probe are assumed to be supported.
+function missingFeatures(chunk) {
+  return (chunk.features ?? []).filter((feature) => {
+    const probe = FEATURE_PROBES[feature];
+    if (!probe) return false;
+    if (!featureSupport.has(feature)) {
+      featureSupport.set(feature, WebAssembly.validate(new Uint8Array(probe)));
+    }
+    return !featureSupport.get(feature);
+  });
+}
+
+// Whether the response for `url`, whose body has been consumed, came from the
+// HTTP cache (or a service worker's cache), according to Resource Timing.
+// Undefined if that is unknown, e.g. for cross-origin chunks served without a
+// `Timing-Allow-Origin` header.
+function wasServedFromCache(url) {
+  const entry = performance.getEntriesByName?.(url.href, "resource")?.at(-1);
+  if (entry === undefined || entry.decodedBodySize === 0) return undefined;
+  return entry.transferSize === 0;
+}
+
+// Records a `wasm-split:chunk-loaded` performance mark for every instantiated
+// chunk, which analytics can collect with a `PerformanceObserver` observing
+// `mark` entries to measure cache hit rates.
+function reportChunkLoaded(state) {
+  performance.mark?.("wasm-split:chunk-loaded", {
+    detail: {
+      chunk: state.chunk.name,
+      url: state.url.href,
+      cacheMode: CHUNK_CACHE,
+      fromCache: state.fromCache,
+      // Compiled by the loader of another entry, or for another chunk.
+      deduplicated: state.deduplicated ?? false,
+      compileMs: state.compileMs,
+    },
+  });
+}
+
+// Compiles taking longer than this block the main thread for a long task, if
+// the browser compiles on it.
+const LONG_TASK_MS = 50;
+
+// Records a `wasm-split:chunk-compile` performance measure for the compile of
+// every chunk, to spot chunks large enough to be worth splitting further.
+// Streamed compiles overlap the download, so they span the time from the
+// response to the compiled module, while others span the compile alone.
+function reportChunkCompiled(state, start, streamed, deferred) {
+  const end = performance.now();
+  state.compileMs = end - start;
+  performance.measure?.("wasm-split:chunk-compile", {
+    start,
+    end,
+    detail: {
+      chunk: state.chunk.name,
+      size: state.chunk.size,
+      streamed,
+      deferred,
+      longTask: !streamed && state.compileMs > LONG_TASK_MS,
+    },
+  });
+  measureChunkPhase(state, "compile", start, end);
+}
+
+// Records a `wasm-split:<chunk>:<phase>` performance measure for the `fetch`,
+// `compile` and `instantiate` phases of every chunk load, named so that each
+// chunk has a row of its own in the timings track of DevTools. RUM scripts
+// collect them with a `PerformanceObserver` observing `measure` entries.
+function measureChunkPhase(state, phase, start, end) {
+  performance.measure?.(`wasm-split:${state.chunk.name}:${phase}`, {
+    start,
+    end,
+    detail: { chunk: state.chunk.name, phase },
+  });
+}
+
+// Measures the request of a chunk by the resource timing of its URL, which
+// covers the download of the whole body, even while a streamed compile
+// consumes it, or of a `<link rel="preload">` that the request reused.
+// Responses without one, such as those from Cache Storage, are measured from
+// `start` until the response arrived at `responded`.
+function reportChunkFetched(state, start, responded) {
+  const entry = performance
+    .getEntriesByName?.(state.url.href, "resource")
+    ?.at(-1);
+  if (entry !== undefined && entry.responseEnd > 0) {
+    measureChunkPhase(state, "fetch", entry.startTime, entry.responseEnd);
+  } else {
+    measureChunkPhase(state, "fetch", start, responded);
+  }
+}
+
+// Codes of the events of chunk loads passed to the callbacks of
+// `wasm_split::on_event`, matching `events.rs`.
+const LOAD_EVENT = { started: 0, progress: 1, instantiated: 2, failed: 3 };
+const UNKNOWN_TOTAL = 0xffffffff;
+
+const loadEventListeners = new Set();
+// Callbacks registered by `wasm_split::on_event`, by subscription ID.
+const loadEventCallbacks = new Map();
+let nextLoadEventSubscription = 1;
+// UTF-8 name of the chunk of the event being passed to a callback.
+let eventChunk;
+
+// Registers a listener for the events of every chunk load: objects with the
+// `type` of the event, one of the keys of `LOAD_EVENT`, and the `chunk`
+// name, as well as the `bytes` downloaded so far and the `total` size, if
+// known, for progress, and the `error` for failures. Returns a function that
+// unregisters it.
+export function onLoadEvent(listener) {
+  loadEventListeners.add(listener);
+  return () => loadEventListeners.delete(listener);
+}
+
+export function __wasm_split_subscribe(callbackIndex, callbackData) {
+  const id = nextLoadEventSubscription++;
+  loadEventCallbacks.set(id, { callbackIndex, callbackData });
+  return id;
+}
+
+export function __wasm_split_unsubscribe(id) {
+  loadEventCallbacks.delete(id);
+}
+
+export function __wasm_split_event_chunk_len() {
+  return eventChunk.length;
+}
+
+export function __wasm_split_take_event_chunk(ptr) {
+  new Uint8Array(getMainExports().memory.buffer, ptr, eventChunk.length).set(
+    eventChunk,
+  );
+}
+
+function hasLoadEventListeners() {
+  return loadEventListeners.size > 0 || loadEventCallbacks.size > 0;
+}
+
+// A listener that throws is reported, but does not fail the load.
+function emitLoadEvent(event) {
+  for (const listener of loadEventListeners) {
+    try {
+      listener(event);
+    } catch (e) {
+      console.error("Load event listener failed", e);
+    }
+  }
+  const [a, b] =
+    event.type === "progress"
+      ? [event.bytes, event.total ?? UNKNOWN_TOTAL]
+      : event.type === "failed"
+        ? classifyError(event.error)
+        : [0, 0];
+  for (const { callbackIndex, callbackData } of [
+    ...loadEventCallbacks.values(),
+  ]) {
+    // Set for each callback, as one may cause events of its own.
+    eventChunk = new TextEncoder().encode(event.chunk);
+    try {
+      getMainExports().__indirect_function_table.get(callbackIndex)(
+        callbackData,
+        LOAD_EVENT[event.type],
+        a,
+        b,
+      );
+    } catch (e) {
+      console.error("Load event listener failed", e);
+    }
+  }
+}
+
+// Passes the body of a chunk response through a stream that emits progress
+// events, if anything listens for them. The total is the size in the
+// manifest, since `Content-Length` counts the bytes before decoding any
+// `Content-Encoding`, while the stream counts them after.
+function trackProgress(state, response) {
+  if (
+    !hasLoadEventListeners() ||
+    response.body === null ||
+    typeof TransformStream !== "function"
+  ) {
+    return response;
+  }
+  const chunk = state.chunk.name;
+  const length = Number(response.headers.get("Content-Length"));
+  const total =
+    state.chunk.size ??
+    (length > 0 && !response.headers.has("Content-Encoding")
+      ? length
+      : undefined);
+  let bytes = 0;
+  const counter = new TransformStream({
+    transform(part, controller) {
+      bytes += part.byteLength;
+      emitLoadEvent({ type: "progress", chunk, bytes, total });
+      controller.enqueue(part);
+    },
+  });
+  return new Response(response.body.pipeThrough(counter), {
+    status: response.status,
+    statusText: response.statusText,
+    headers: response.headers,
+  });
+}
+
+// Hosting misconfigurations that make `compileStreaming` fail with an opaque
+// `TypeError`, or succeed only by accident, detected from the response:
+// streaming compilation needs the `application/wasm` content type, and a
+// redirected chunk request usually ends up at a login page or a fallback
+// route rather than at the chunk. Returns a description of each one found.
+function diagnoseResponse(response) {
+  const contentType = response.headers.get("Content-Type");
+  const problems = [];
+  if (contentType?.split(";")[0].trim().toLowerCase() !== "application/wasm") {
+    problems.push({
+      kind: "content-type",
+      message:
+        "served with Content-Type " +
+        (contentType ?? "(none)") +
+        " instead of application/wasm",
+    });
+  }
+  if (response.redirected) {
+    problems.push({
+      kind: "redirect",
+      message: "redirected to " + response.url,
+    });
+  }
+  return problems;
+}
+
+// Reports what `diagnoseResponse` found for a chunk, on the console and as a
+// `wasm-split:chunk-diagnostic` performance mark, to surface hosting issues
+// in monitoring as well.
+function reportDiagnostic(state, response, problems) {
+  const detail = {
+    chunk: state.chunk.name,
+    url: state.url.href,
+    responseUrl: response.url,
+    status: response.status,
+    contentType: response.headers.get("Content-Type"),
+    problems: problems.map(({ kind }) => kind),
+  };
+  console.warn(
+    "wasm-split: chunk " +
+      state.chunk.name +
+      " was " +
+      problems.map(({ message }) => message).join(" and ") +
+      "; check the server configuration for " +
+      state.url.href,
+    detail,
+  );
+  performance.mark?.("wasm-split:chunk-diagnostic", { detail });
+}
+
+const WASM_MAGIC = [0x00, 0x61, 0x73, 0x6d];
+
+// Compiles a chunk from an `ArrayBuffer` instead of streaming, which works
+// regardless of the content type, for responses that `diagnoseResponse`
+// found problems with, or if streaming compilation is not supported. Checks
+// the chunk against the digest in a signed manifest first if `checkDigest`.
+// Fails with an error naming the problems if the response isn't a
+// WebAssembly module at all.
+async function compileBuffered(state, response, problems, checkDigest) {
+  const bytes = new Uint8Array(await response.arrayBuffer());
+  if (!WASM_MAGIC.every((byte, i) => bytes[i] === byte)) {
+    const start = new TextDecoder().decode(bytes.subarray(0, 64)).trim();
+    throw new ChunkLoadError(
+      LOAD_ERROR.CompileError,
+      0,
+      "Chunk " +
+        state.chunk.name +
+        (problems.length > 0
+          ? " was " +
+            problems.map(({ message }) => message).join(" and ") +
+            ", and"
+          : "") +
+        " is not a WebAssembly module" +
+        (start.startsWith("<")
+          ? ", but looks like an HTML page, as served by a fallback route " +
+            "or login redirect: " +
+            JSON.stringify(start)
+          : ""),
+    );
+  }
+  if (checkDigest) await checkChunkDigest(state, bytes);
+  return await WebAssembly.compile(bytes);
+}
+
+// Compiles a chunk of a signed manifest while it downloads, like any other,
+// and reads a copy of the body alongside to check it against the digest in the
+// manifest, which the caller waits for before instantiating it. A mismatch is
+// reported over the compile error of a chunk that was modified into an
+// invalid module.
+async function compileStreamingWithDigest(state, response) {
+  const [compiling, checking] = response.body.tee();
+  const [module, digest] = await Promise.allSettled([
+    WebAssembly.compileStreaming(
+      new Response(compiling, { headers: response.headers }),
+    ),
+    new Response(checking)
+      .arrayBuffer()
+      .then((bytes) => checkChunkDigest(state, new Uint8Array(bytes))),
+  ]);
+  if (digest.status === "rejected") throw digest.reason;
+  if (module.status === "rejected") throw module.reason;
+  return module.value;
+}
+
+// Compilations of chunks by content hash, shared by the loaders of all
+// entries on the page, which are built separately and may each have a
+// byte-identical copy of the same shared chunk under another name or URL.
+// Only compilation is shared: every entry instantiates the module with the
+// memory and table of its own main module. Entries are only kept while the
+// chunk is being loaded, and afterwards for pinned chunks, so that the
+// compiled modules of other chunks can be garbage collected.
+const COMPILED_BY_HASH = Symbol.for("wasm-split:compiled-by-hash");
+const compiledByHash = (globalThis[COMPILED_BY_HASH] ??= new Map());
+
+// Forgets the compilation of this chunk, and removes the one that
+// `compileSharedChunk` shared for it, if it is still the one in the map.
+function releaseCompilation(state) {
+  const { key, compiled } = state.sharedCompilation ?? {};
+  if (key !== undefined && compiledByHash.get(key) === compiled) {
+    compiledByHash.delete(key);
+  }
+  state.sharedCompilation = undefined;
+  state.compilation = undefined;
+}
+
+// Fetches and compiles a chunk once, however many loads race for it before
+// it is instantiated: a group compiles its chunks up front, before `loadChunk`
+// claims them one by one, so that a call of one of their functions in the
+// meantime finds the compilation of the group here rather than fetching the
+// chunk again. A failed compilation is forgotten, so that the next load
+// retries it. Loads that are `deferred` compile in idle time; any other load
+// of the chunk in the meantime compiles it right away.
+function compileChunk(state, deferred = false) {
+  if (state.compilation !== undefined) {
+    if (!deferred) state.compilation.promote?.();
+  } else {
+    const compilation = compileSharedChunk(state, deferred);
+    state.compilation = compilation;
+    compilation.catch(() => {
+      if (state.compilation === compilation) state.compilation = undefined;
+    });
+  }
+  return state.compilation;
+}
+
+// Fetches and compiles a chunk, or reuses the compilation of an identical
+// chunk, according to the hash and size in the manifest. Instantiation is
+// left to the caller, since it has to happen in dependency order.
+function compileSharedChunk(state, deferred) {
+  const { hash, size } = state.chunk;
+  if (hash === undefined || state.refetch) {
+    return compileChunkFile(state, deferred);
+  }
+  const key = hash + ":" + size;
+  const shared = compiledByHash.get(key);
+  if (shared !== undefined) {
+    state.deduplicated = true;
+    if (!deferred) shared.promote?.();
+    return shared;
+  }
+  const compiled = compileChunkFile(state, deferred);
+  compiledByHash.set(key, compiled);
+  state.sharedCompilation = { key, compiled };
+  // Let the next load retry, possibly from another entry.
+  compiled.catch(() => releaseCompilation(state));
+  return compiled;
+}
+
+// Idle time to wait for at most before compiling a deferred chunk anyway.
+const IDLE_COMPILE_TIMEOUT_MS = 2000;
+
+// Resolves `opened` once the browser is idle, or once `open` is called.
+function idleGate() {
+  let open;
+  const opened = new Promise((resolve) => (open = resolve));
+  whenIdle(() => open(), { timeout: IDLE_COMPILE_TIMEOUT_MS });
+  return { opened, open };
+}
+
+// A `deferred` compile, for a load that nothing waits for yet such as a
+// preload, downloads the chunk right away but compiles it from a buffer once
+// the browser is idle, so that compiling a large chunk does not cause a long
+// task while the page is busy. `promote` on the returned promise compiles it
+// as soon as it is downloaded instead.
+function compileChunkFile(state, deferred = false) {
+  const priority = chunkPriority(state.chunk);
+  const gate = deferred ? idleGate() : undefined;
+  const compiled = schedule(priority, async (release) => {
+    const controller = new AbortController();
+    state.abortController = controller;
+    const timeoutMs = loadTimeoutMs;
+    const timer =
+      timeoutMs > 0
+        ? setTimeout(
+            () =>
+              controller.abort(
+                new DOMException(
+                  `Chunk ${state.chunk.name} took longer than ${timeoutMs}ms`,
+                  "TimeoutError",
+                ),
+              ),
+            timeoutMs,
+          )
+        : undefined;
+    // Once downloaded, the chunk can no longer be aborted or time out.
+    const downloaded = () => {
+      clearTimeout(timer);
+      if (state.abortController === controller) state.abortController = undefined;
+    };
+    try {
+      return await fetchAndCompile(
+        state,
+        priority,
+        gate,
+        () => {
+          downloaded();
+          release();
+        },
+        controller.signal,
+      );
+    } catch (e) {
+      // Compiling from an aborted stream fails with an error of its own.
+      throw controller.signal.aborted ? controller.signal.reason : e;
+    } finally {
+      downloaded();
+    }
+  });
+  // Failures are reported through `loadChunk`, which may never await this if
+  // a dependency fails first.
+  compiled.catch(() => {});
+  if (gate !== undefined) compiled.promote = gate.open;
+  return compiled;
+}
+
+// Fetches and compiles the chunk of `state` as scheduled by
+// `compileChunkFile`, which `signal` aborts while it downloads.
+async function fetchAndCompile(state, priority, gate, release, signal) {
+  // Checked before fetching, so that unusable chunks are not downloaded.
+  const missing = missingFeatures(state.chunk);
+  if (missing.length > 0) {
+    throw new ChunkLoadError(
+      LOAD_ERROR.UnsupportedFeature,
+      0,
+      "Chunk " +
+        state.chunk.name +
+        " requires unsupported WebAssembly features: " +
+        missing.join(", "),
+    );
+  }
+  await checkEmbeddedManifest();
+  const fetchStart = performance.now();
+  const response = await fetchCachedChunk(state.url, {
+    // Pinned chunks are never revalidated, as their URL includes the build,
+    // while chunks are fetched anew for `reload`.
+    cache: state.refetch
+      ? "reload"
+      : state.chunk.pinned
+        ? "force-cache"
+        : CHUNK_CACHE,
+    priority: FETCH_PRIORITIES[priority],
+    signal,
+    // The browser fails the request, before anything is compiled, if the
+    // body does not match the hash in the manifest. Chunks of a signed
+    // manifest are checked by `checkChunkDigest` instead, which fails with
+    // an `IntegrityMismatch` rather than as if the network was down.
+    integrity:
+      getManifestPublicKey() === undefined
+        ? state.chunk.integrity
+        : undefined,
+  });
+  const responded = performance.now();
+  state.refetch = false;
+  if (!response.ok) {
+    throw new ChunkLoadError(
+      LOAD_ERROR.Http,
+      response.status,
+      `HTTP status ${response.status}`,
+    );
+  }
+  const problems = diagnoseResponse(response);
+  if (problems.length > 0) reportDiagnostic(state, response, problems);
+  const body = trackProgress(state, response);
+  const signed = getManifestPublicKey() !== undefined;
+  if (gate !== undefined) {
+    const bytes = await body.arrayBuffer();
+    // Other chunks are fetched while this one waits to be compiled.
+    release();
+    await gate.opened;
+    const start = performance.now();
+    const module = await compileBuffered(
+      state,
+      new Response(bytes),
+      problems,
+      signed,
+    );
+    reportChunkFetched(state, fetchStart, responded);
+    reportChunkCompiled(state, start, false, true);
+    state.fromCache = wasServedFromCache(state.url);
+    return module;
+  }
+  // Browsers without `WebAssembly.compileStreaming`, such as older Safari
+  // versions, compile from a buffer as well.
+  const streamed =
+    problems.length === 0 &&
+    typeof WebAssembly.compileStreaming === "function" &&
+    body.body !== null;
+  const start = performance.now();
+  const module = !streamed
+    ? await compileBuffered(state, body, problems, signed)
+    : signed
+      ? await compileStreamingWithDigest(state, body)
+      : await WebAssembly.compileStreaming(body);
+  reportChunkFetched(state, fetchStart, responded);
+  reportChunkCompiled(state, start, streamed, false);
+  state.fromCache = wasServedFromCache(state.url);
+  return module;
+}
+
+// Loads a chunk and its dependencies, using the already started compilation
+// `compiled` if given. A `deferred` load, such as a preload, compiles the
+// chunks in idle time, until a load that is not deferred needs them.
+function loadChunk(name, compiled = undefined, deferred = false) {
+  const state = getChunkState(name);
+  if (state === undefined) {
+    // A dependency dropped with `drop_module`.
+    return Promise.reject(
+      new ChunkLoadError(
+        LOAD_ERROR.UnknownChunk,
+        0,
+        `Chunk "${name}" has been dropped`,
+      ),
+    );
+  }
+  if (state.chunk === undefined && state.promise === undefined) {
+    // A folded module with `on_load` hooks, or snippets of the fallback.
+    state.promise = Promise.resolve(importSnippets(state.imports)).then(() => {
+      runOnLoadHooks(state.onLoad);
+      return 0;
+    });
+    state.promise.catch(() => {
+      state.promise = undefined;
+    });
+    return state.promise;
+  }
+  if (state.promise === undefined && state.instantiated) {
+    // Loaded by `loadChunkSync` while an earlier load failed.
+    state.promise = Promise.resolve(state.chunk.size ?? 0);
+  }
+  if (state.promise === undefined) {
+    state.startedAt = performance.now();
+    state.error = undefined;
+    state.failure = undefined;
+    state.promise = (async () => {
+      emitLoadEvent({ type: "started", chunk: name });
+      const module = compiled ?? compileChunk(state, deferred);
+      const snippets = importSnippets(state.chunk.imports);
+      revalidateManifest();
+      for (const dep of state.chunk.dependencies ?? []) {
+        await loadChunk(dep, undefined, deferred);
+      }
+      const compiledModule = await module;
+      await snippets;
+      // Instantiated in the meantime by a call through one of its slots.
+      if (state.instantiated) return state.chunk.size ?? 0;
+      const instantiateStart = performance.now();
+      try {
+        checkSignatures(state, compiledModule);
+        saveTableSlots(state);
+        await WebAssembly.instantiate(compiledModule, getImports());
+      } finally {
+        if (!state.chunk.pinned) releaseCompilation(state);
+      }
+      finishInstantiation(name, state, compiledModule, instantiateStart);
+      return state.chunk.size ?? 0;
+    })();
+    recordChunkLoad(name, state.promise);
+    state.promise.catch((e) => {
+      state.promise = undefined;
+      state.error = e;
+      state.failure = describeFailure(name, state, e);
+      emitLoadEvent({ type: "failed", chunk: name, error: e });
+      const { url, size, error, message } = state.failure;
+      console.error(
+        `wasm-split: Failed to load chunk ${name} from ${url}` +
+          (size === undefined ? "" : ` (${size} bytes)`) +
+          `: ${error}: ${message}`,
+        { ...state.failure, cause: e },
+      );
+      countFailedLoad(e);
+    });
+  } else if (!deferred) {
+    promoteLoad(state);
+  }
+  return state.promise;
+}
+
+// Runs the hooks of a chunk that was just instantiated, and records it as
+// loaded.
+function finishInstantiation(name, state, compiledModule, instantiateStart) {
+  state.instantiated = true;
+  runOnLoadHooks(state.chunk.on_load);
+  measureChunkPhase(state, "instantiate", instantiateStart, performance.now());
+  if (state.chunk.pinned) {
+    state.module = compiledModule;
+    rememberPinnedChunk(name);
+  }
+  getRegistry().loadedChunks.set(state.url.href, {
+    chunk: state.chunk,
+    loadedAt: performance.now(),
+  });
+  reportChunkLoaded(state);
+  emitLoadEvent({ type: "instantiated", chunk: name });
+}
+
+// Reads a chunk with a synchronous request. Those of windows cannot set a
+// `responseType`, so the body is read as text with one character per byte.
+function fetchChunkSync(url) {
+  const request = new XMLHttpRequest();
+  request.open("GET", url.href, false);
+  request.overrideMimeType("text/plain; charset=x-user-defined");
+  request.send();
+  // Requests of `file:` URLs have no status.
+  if (request.status !== 200 && request.status !== 0) {
+    throw new ChunkLoadError(
+      LOAD_ERROR.Http,
+      request.status,
+      `HTTP status ${request.status}`,
+    );
+  }
+  const text = request.responseText;
+  const bytes = new Uint8Array(text.length);
+  for (let i = 0; i < text.length; ++i) bytes[i] = text.charCodeAt(i) & 0xff;
+  return bytes;
+}
+
+// Loads a chunk and its dependencies before returning, for a call through a
+// table slot of the chunk that cannot wait. A load of the chunk that is
+// under way is not waited for, since it cannot be, and finds the chunk
+// instantiated once it finishes. The JS snippets of the chunk's imports are
+// only starting to load when it returns.
+function loadChunkSync(name) {
+  const state = getChunkState(name);
+  if (state === undefined) {
+    throw new ChunkLoadError(
+      LOAD_ERROR.UnknownChunk,
+      0,
+      `Chunk "${name}" has been dropped`,
+    );
+  }
+  if (state.chunk === undefined || state.instantiated) return;
+  const missing = missingFeatures(state.chunk);
+  if (missing.length > 0) {
+    throw new ChunkLoadError(
+      LOAD_ERROR.UnsupportedFeature,
+      0,
+      "Chunk " +
+        state.chunk.name +
+        " requires unsupported WebAssembly features: " +
+        missing.join(", "),
+    );
+  }
+  if (state.promise === undefined) {
+    state.startedAt = performance.now();
+    emitLoadEvent({ type: "started", chunk: name });
+  }
+  for (const dep of state.chunk.dependencies ?? []) loadChunkSync(dep);
+  importSnippets(state.chunk.imports);
+  const compiledModule = new WebAssembly.Module(fetchChunkSync(state.url));
+  checkSignatures(state, compiledModule);
+  const instantiateStart = performance.now();
+  saveTableSlots(state);
+  new WebAssembly.Instance(compiledModule, getImports());
+  finishInstantiation(name, state, compiledModule, instantiateStart);
+  state.promise ??= Promise.resolve(state.chunk.size ?? 0);
+}
+
+// Called by the stubs that fill the table slots of the chunks that are not
+// loaded with `--lazy-indirect-calls`, on a call through a `fn` pointer or
+// `dyn Trait` object that the main module created, before they call the slot
+// again. The call cannot wait, so this loads the chunk synchronously, which
+// blocks the page while it downloads and compiles. Returns 0 if that failed,
+// for the stub to panic with `--guard-calls`, or trap otherwise, rather than
+// to throw a JS exception through the Rust frames of the call.
+export function __wasm_split_load_slot(slot) {
+  const table = getMainExports().__indirect_function_table;
+  const stub = table.get(slot);
+  const chunk = MANIFEST.table?.find((entry) => entry.slot === slot)?.chunk;
+  try {
+    if (chunk === undefined) {
+      throw new Error(`wasm-split: no chunk fills table slot ${slot}`);
+    }
+    loadChunkSync(chunk);
+    // The stub would call itself otherwise.
+    if (table.get(slot) === stub) {
+      throw new Error(
+        `wasm-split: chunk "${chunk}" did not fill table slot ${slot}`,
+      );
+    }
+  } catch (e) {
+    console.error(`wasm-split: failed to load table slot ${slot}`, e);
+    return 0;
+  }
+  return 1;
+}
+
+// Compiles the chunks of a deferred load, i.e. its own and those of its
+// dependencies, as soon as they are downloaded.
+function promoteLoad(state, seen = new Set()) {
+  if (seen.has(state)) return;
+  seen.add(state);
+  state.compilation?.promote?.();
+  for (const dep of state.chunk?.dependencies ?? []) {
+    const depState = getChunkState(dep);
+    if (depState !== undefined) promoteLoad(depState, seen);
+  }
+}
+
+// Pinned chunks that have been loaded on this or an earlier page load, which
+// the loader loads again when the browser is idle, so that features that
+// users have needed once are ready before they are needed again. Stored per
+// manifest URL, since several apps may share an origin.
+const PINNED_STORAGE_KEY = "wasm-split:pinned:" + MANIFEST_URL.pathname;
+
+function readPinnedChunks() {
+  try {
+    return JSON.parse(localStorage.getItem(PINNED_STORAGE_KEY)) ?? [];
+  } catch {
+    // No `localStorage`, e.g. in a worker, or storage is disabled.
+    return [];
+  }
+}
+
+function rememberPinnedChunk(name) {
+  const names = readPinnedChunks();
+  if (names.includes(name)) return;
+  try {
+    localStorage.setItem(PINNED_STORAGE_KEY, JSON.stringify([...names, name]));
+  } catch {}
+}
+
+const whenIdle =
+  globalThis.requestIdleCallback ?? ((callback) => setTimeout(callback, 1));
+
+// Retried on later idle periods until the main module has been instantiated,
+// which happens after this script is evaluated.
+function preloadPinnedChunks(attempts = 20) {
+  if (!MANIFEST.chunks.some((chunk) => chunk.pinned)) return;
+  try {
+    getMainExports();
+  } catch {
+    if (attempts > 0) whenIdle(() => preloadPinnedChunks(attempts - 1));
+    return;
+  }
+  for (const name of readPinnedChunks()) {
+    if (getChunkState(name)?.chunk?.pinned) {
+      loadChunk(name, undefined, true).catch(() => {});
+    }
+  }
+}
+
+whenIdle(() => preloadPinnedChunks());
+
+// Chunks of `auto-split` crates, which the main module calls without going
+// through a split point, and which thus have to be loaded before those calls
+// happen. They are loaded as soon as the main module has been instantiated,
+// without waiting for the browser to be idle, which is checked for at
+// growing intervals.
+function loadAutoChunks(attempts = 100, delay = 1) {
+  if (!MANIFEST.chunks.some((chunk) => chunk.auto)) return;
+  try {
+    getMainExports();
+  } catch {
+    const retry = () => loadAutoChunks(attempts - 1, Math.min(2 * delay, 250));
+    if (attempts > 0) setTimeout(retry, delay);
+    return;
+  }
+  for (const chunk of MANIFEST.chunks) {
+    if (chunk.auto) loadChunk(chunk.name).catch(() => {});
+  }
+}
+
+setTimeout(() => loadAutoChunks());
+
+// Navigations measured with `wasm_split::NavigationTiming`, by ID, each with
+// the intervals during which it was loading data.
+const navigations = new Map();
+let nextNavigationId = 1;
+const navigationListeners = new Set();
+
+// Intervals during which chunks were loading, kept while a navigation that
+// they may overlap is being measured.
+let chunkLoadIntervals = [];
+
+function recordChunkLoad(name, promise) {
+  const interval = { chunk: name, start: performance.now(), end: undefined };
+  chunkLoadIntervals.push(interval);
+  const end = () => {
+    interval.end = performance.now();
+    pruneChunkLoadIntervals();
+  };
+  promise.then(end, end);
+}
+
+function pruneChunkLoadIntervals() {
+  const oldest = Math.min(
+    ...[...navigations.values()].map((navigation) => navigation.start),
+  );
+  chunkLoadIntervals = chunkLoadIntervals.filter(
+    (interval) => interval.end === undefined || interval.end > oldest,
+  );
+}
+
+// Time within `[start, end]` covered by any of `intervals`, of which those
+// still in progress last until `end`.
+function coveredTime(intervals, start, end) {
+  const clipped = intervals
+    .map((interval) => [
+      Math.max(interval.start, start),
+      Math.min(interval.end ?? end, end),
+    ])
+    .filter(([from, to]) => from < to)
+    .sort((a, b) => a[0] - b[0]);
+  let covered = 0;
+  let coveredUntil = start;
+  for (const [from, to] of clipped) {
+    covered += Math.max(0, to - Math.max(from, coveredUntil));
+    coveredUntil = Math.max(coveredUntil, to);
+  }
+  return covered;
+}
+
+// Registers a listener for the report of every finished navigation, as in
+// the detail of its `wasm-split:navigation` performance measure. Returns a
+// function that unregisters it.
+export function onNavigationTiming(listener) {
+  navigationListeners.add(listener);
+  return () => navigationListeners.delete(listener);
+}
+
+export function __wasm_split_navigation_start(routePtr, routeLen) {
+  const id = nextNavigationId++;
+  navigations.set(id, {
+    route: decodeString(routePtr, routeLen),
+    start: performance.now(),
+    dataIntervals: [],
+  });
+  return id;
+}
+
+export function __wasm_split_navigation_data_start(id) {
+  const navigation = navigations.get(id);
+  navigation.dataIntervals.push({ start: performance.now(), end: undefined });
+  return navigation.dataIntervals.length - 1;
+}
+
+export function __wasm_split_navigation_data_end(id, interval) {
+  const navigation = navigations.get(id);
+  if (navigation !== undefined) {
+    navigation.dataIntervals[interval].end ??= performance.now();
+  }
+}
+
+export function __wasm_split_navigation_end(id, rendered) {
+  const navigation = navigations.get(id);
+  navigations.delete(id);
+  const end = performance.now();
+  if (rendered) {
+    const { route, start, dataIntervals } = navigation;
+    const chunkIntervals = chunkLoadIntervals.filter(
+      (interval) => interval.start < end && (interval.end ?? end) > start,
+    );
+    const chunkTime = coveredTime(chunkIntervals, start, end);
+    const dataTime = coveredTime(dataIntervals, start, end);
+    const busyTime = coveredTime(
+      [...chunkIntervals, ...dataIntervals],
+      start,
+      end,
+    );
+    const overlapTime = chunkTime + dataTime - busyTime;
+    const detail = {
+      route,
+      duration: end - start,
+      // Only chunks, only data, or both were loading.
+      chunkTime: chunkTime - overlapTime,
+      dataTime: dataTime - overlapTime,
+      overlapTime,
+      // Neither was loading, e.g. while rendering.
+      otherTime: end - start - busyTime,
+      chunks: [...new Set(chunkIntervals.map((interval) => interval.chunk))],
+    };
+    performance.measure?.("wasm-split:navigation", { start, end, detail });
+    for (const listener of navigationListeners) listener(detail);
+  }
+  pruneChunkLoadIntervals();
+}
+
+// Loads several chunks and their dependencies as one batch: all of them are
+// fetched and compiled in parallel, then instantiated in dependency order.
+async function loadGroup(names) {
+  const order = [];
+  const visit = (name) => {
+    if (order.includes(name)) return;
+    for (const dep of getChunkState(name)?.chunk?.dependencies ?? []) {
+      visit(dep);
+    }
+    order.push(name);
+  };
+  names.forEach(visit);
+  const compiled = new Map();
+  for (const name of order) {
+    const state = getChunkState(name);
+    if (state !== undefined && state.promise === undefined) {
+      compiled.set(name, compileChunk(state));
+    }
+  }
+  let bytes = 0;
+  try {
+    for (const name of order) {
+      bytes += await loadChunk(name, compiled.get(name));
+    }
+  } catch (e) {
+    // Chunks after the one that failed are not instantiated.
+    for (const name of compiled.keys()) {
+      const state = getChunkState(name);
+      if (state !== undefined && state.promise === undefined) {
+        releaseCompilation(state);
+      }
+    }
+    throw e;
+  }
+  return bytes;
+}
+
+// Chunk loads succeed with the size of the chunks in bytes as the detail,
+// which the `tracing` spans of the runtime record.
+function invokeCallbackWhenLoaded(promise, callbackIndex, callbackData) {
+  promise.then(
+    (bytes) => invokeCallback(callbackIndex, callbackData, LOAD_OK, bytes),
+    (e) => invokeCallback(callbackIndex, callbackData, ...classifyError(e)),
+  );
+}
+
+// Called by the `tracing` spans of the runtime to time chunk loads, and by
+// `wasm_split::with_fallback` to time how long a fallback was shown.
+export function __wasm_split_now() {
+  return performance.now();
+}
+
+// Called by `wasm_split::with_fallback` to wait before showing a fallback,
+// and before hiding it.
+export function __wasm_split_sleep(ms, callbackIndex, callbackData) {
+  setTimeout(() => invokeCallback(callbackIndex, callbackData, LOAD_OK), ms);
+}
+
+// Called by `wasm_split::SplitChunk` to load a chunk by name.
+export function __wasm_split_load_chunk(
+  namePtr,
+  nameLen,
+  callbackIndex,
+  callbackData,
+) {
+  const name = decodeString(namePtr, nameLen);
+  if (getChunkState(name) === undefined) {
+    invokeCallback(callbackIndex, callbackData, LOAD_ERROR.UnknownChunk);
+    return;
+  }
+  invokeCallbackWhenLoaded(loadChunk(name), callbackIndex, callbackData);
+}
+
+// Called by `wasm_split::load_group` with a comma-separated list of names.
+export function __wasm_split_load_group(
+  namesPtr,
+  namesLen,
+  callbackIndex,
+  callbackData,
+) {
+  const names = decodeString(namesPtr, namesLen).split(",");
+  if (names.some((name) => getChunkState(name) === undefined)) {
+    invokeCallback(callbackIndex, callbackData, LOAD_ERROR.UnknownChunk);
+    return;
+  }
+  invokeCallbackWhenLoaded(loadGroup(names), callbackIndex, callbackData);
+}
+
+// Loads of the chunks of a route, by the ID handed to `wasm_split::load_route`,
+// each with the states of the chunks it loads and the bytes of them that have
+// been downloaded so far.
+const routeLoads = new Map();
+let nextRouteLoad = 1;
+
+// States of the chunks of `modules` and of their dependencies.
+function routeChunkStates(modules) {
+  const states = new Set();
+  const visit = (name) => {
+    const state = getChunkState(name);
+    if (state?.chunk === undefined || states.has(state)) return;
+    states.add(state);
+    (state.chunk.dependencies ?? []).forEach(visit);
+  };
+  modules.forEach(visit);
+  return states;
+}
+
+// Loads the chunks of the split modules that `MANIFEST.routes` lists for
+// `route`, and their dependencies, as a group. `onProgress` is called with the
+// combined `{ bytes, total }` of all of them whenever any of them progresses,
+// where `total` is their size in the manifest and chunks that were already
+// loaded count as downloaded. Chunks whose download had already started, e.g.
+// by a preload, only count once they are loaded.
+function startRouteLoad(route, onProgress) {
+  const modules = MANIFEST.routes?.[route];
+  if (modules === undefined) {
+    return {
+      promise: Promise.reject(
+        new ChunkLoadError(LOAD_ERROR.UnknownChunk, 0, `Unknown route "${route}"`),
+      ),
+      progress: () => ({ bytes: 0, total: 0 }),
+    };
+  }
+  const states = routeChunkStates(modules);
+  const { loadedChunks } = getRegistry();
+  const downloaded = new Map();
+  const progress = () => {
+    let bytes = 0;
+    let total = 0;
+    for (const state of states) {
+      const size = state.chunk.size ?? 0;
+      total += size;
+      bytes += loadedChunks.has(state.url.href)
+        ? size
+        : Math.min(downloaded.get(state) ?? 0, size);
+    }
+    return { bytes, total };
+  };
+  // Registered before the group starts, so that its downloads are tracked.
+  const listener = (event) => {
+    const state = getChunkState(event.chunk);
+    if (!states.has(state)) return;
+    if (event.type === "progress") downloaded.set(state, event.bytes);
+    if (event.type === "progress" || event.type === "instantiated") {
+      onProgress?.(progress());
+    }
+  };
+  loadEventListeners.add(listener);
+  const promise = loadGroup(modules).finally(() =>
+    loadEventListeners.delete(listener),
+  );
+  return { promise, progress };
+}
+
+// Loads all chunks that `route` needs, as declared in the `[routes]` table of
+// `wasm-split.toml` or by `route = "..."` of split points, calling
+// `onProgress` with their combined `{ bytes, total }` as they download, for a
+// single progress indicator for a navigation. Fails for unknown routes.
+export function loadRoute(route, onProgress) {
+  return startRouteLoad(route, onProgress).promise.then(() => {});
+}
+
+// Called by `wasm_split::load_route`, whose callback gets the ID to query the
+// progress of the load with until it is released.
+export function __wasm_split_route_start(
+  routePtr,
+  routeLen,
+  callbackIndex,
+  callbackData,
+) {
+  const id = nextRouteLoad++;
+  const load = startRouteLoad(decodeString(routePtr, routeLen));
+  routeLoads.set(id, load);
+  invokeCallbackWhenLoaded(load.promise, callbackIndex, callbackData);
+  return id;
+}
+
+// Writes the `bytes` and `total` of a route load as two u32s.
+export function __wasm_split_route_progress(id, ptr) {
+  const { bytes, total } = routeLoads.get(id)?.progress() ?? {
+    bytes: 0,
+    total: 0,
+  };
+  new Uint32Array(getMainExports().memory.buffer, ptr, 2).set([bytes, total]);
+}
+
+export function __wasm_split_route_release(id) {
+  routeLoads.delete(id);
+}
+
+// Split modules of the route of `path`, as listed by `MANIFEST.routes`.
+// Paths that are not a route use the closest route they are nested under, as
+// `wasm_split_server` does for `Link` headers, so `/posts/42` gets the
+// modules of `/posts`.
+function routeModules(path) {
+  let modules;
+  for (let route = path.replace(/\/+$/, ""); modules === undefined; ) {
+    modules = MANIFEST.routes?.[route === "" ? "/" : route];
+    if (route === "") break;
+    route = route.slice(0, route.lastIndexOf("/"));
+  }
+  return modules ?? [];
+}
+
+// Names of the chunks that the route of `path` needs, including the shared
+// chunks they depend on, e.g. to prefetch the route of a link on
+// `pointerenter` or once an `IntersectionObserver` sees it. Folded modules
+// have no chunk, and unknown paths get none.
+export function routeChunks(path) {
+  return [...routeChunkStates(routeModules(path))].map(
+    (state) => state.chunk.name,
+  );
+}
+
+// Starts loading the chunks of the route of `path`, as `preload` does for
+// each of its split modules. Resolves once all of them are loaded, and
+// right away for unknown paths.
+export function preloadRoute(path) {
+  return Promise.all(routeModules(path).map(preload)).then(() => {});
+}
+
+// Called by `wasm_split::route_chunks`, which retries with the returned
+// length if the names, one per line, did not fit.
+export function __wasm_split_route_chunks(pathPtr, pathLen, ptr, capacity) {
+  const encoded = new TextEncoder().encode(
+    routeChunks(decodeString(pathPtr, pathLen))
+      .map((name) => name + "\n")
+      .join(""),
+  );
+  if (encoded.length <= capacity) {
+    new Uint8Array(getMainExports().memory.buffer, ptr, capacity).set(encoded);
+  }
+  return encoded.length;
+}
+
+// Called by `wasm_split::preload_route`, which leaves logging failures to
+// `loadChunk`.
+export function __wasm_split_preload_route(pathPtr, pathLen) {
+  preloadRoute(decodeString(pathPtr, pathLen)).catch(() => {});
+}
+
+// Chunks of the route of `path` that have not started loading, as
+// `{ href, integrity }` for `<link rel="preload" as="fetch" crossorigin>`
+// elements, with which the browser fetches them ahead of the loader. Paths
+// that are not a route use the closest route they are nested under, as for
+// `routeChunks`. `integrity` is what the loader fetches the chunk with, which
+// the preload must match for the browser to reuse its response.
+export function routePreloadLinks(path) {
+  const signed = getManifestPublicKey() !== undefined;
+  const { loadedChunks } = getRegistry();
+  return [...routeChunkStates(routeModules(path))]
+    .filter(
+      (state) =>
+        state.promise === undefined && !loadedChunks.has(state.url.href),
+    )
+    .map((state) => ({
+      href: state.url.href,
+      integrity: signed ? undefined : state.chunk.integrity,
+    }));
+}
+
+// Called by `wasm_split::route_preload_links`. Writes the links as lines of
+// the URL and integrity separated by a space, and returns their length, with
+// which the caller retries if they did not fit.
+export function __wasm_split_route_preload_links(
+  pathPtr,
+  pathLen,
+  ptr,
+  capacity,
+) {
+  const lines = routePreloadLinks(decodeString(pathPtr, pathLen))
+    .map(({ href, integrity }) => `${href} ${integrity ?? ""}\n`)
+    .join("");
+  const encoded = new TextEncoder().encode(lines);
+  if (encoded.length <= capacity) {
+    new Uint8Array(getMainExports().memory.buffer, ptr, capacity).set(encoded);
+  }
+  return encoded.length;
+}
+
+// Starts loading a chunk and its dependencies ahead of its first call, e.g.
+// from a JS `pointerenter` handler of a link. The returned promise, which
+// calls of the chunk's functions share, resolves once it is loaded. Fails
+// for unknown names. The chunk is compiled once the browser is idle, unless
+// one of its functions is called first.
+export function preload(name) {
+  if (getChunkState(name) === undefined) {
+    return Promise.reject(
+      new ChunkLoadError(LOAD_ERROR.UnknownChunk, 0, `Unknown chunk "${name}"`),
+    );
+  }
+  return loadChunk(name, undefined, true).then(() => {});
+}
+
+// Loads the chunks of the entry point `name` of the `[entries]` config, such
+// as a web worker, which the exports that only it calls call into directly,
+// and which nothing else loads eagerly. The entry awaits this once it has
+// instantiated the main module, before calling those exports. The loader
+// only uses the DOM where there is one, so it runs as is in a `Worker`
+// scope. Resolves at once for entries without chunks, e.g. as all their code
+// was folded into the main module.
+export function loadEntry(name) {
+  return Promise.all(
+    MANIFEST.chunks
+      .filter((chunk) => chunk.entry === name)
+      .map((chunk) => loadChunk(chunk.name)),
+  ).then(() => {});
+}
+
+// What the loader knows about the last failed load of the chunk of a split
+// module, as `{ chunk, url, size, error, code, status, import, message }`,
+// where `error` is the name of the `wasm_split::LoadError`, `status` that of
+// an HTTP error and `import` the import that a `LinkError` is about. Undefined
+// if its last load did not fail.
+export function loadFailure(name) {
+  const state = getChunkState(name);
+  return state?.promise === undefined ? state?.failure : undefined;
+}
+
+// Called by `wasm_split::load_failure`. Writes the chunk, URL, size, error
+// code, HTTP status and import on a line each, followed by the message, and
+// returns their length, with which the caller retries if they did not fit,
+// or 0 if the last load did not fail.
+export function __wasm_split_load_failure(namePtr, nameLen, ptr, capacity) {
+  const failure = loadFailure(decodeString(namePtr, nameLen));
+  if (failure === undefined) return 0;
+  const { chunk, url, size, code, status, import: name, message } = failure;
+  const encoded = new TextEncoder().encode(
+    [chunk, url, size ?? "", code, status ?? "", name ?? "", message].join("\n"),
+  );
+  if (encoded.length <= capacity) {
+    new Uint8Array(getMainExports().memory.buffer, ptr, capacity).set(encoded);
+  }
+  return encoded.length;
+}
+
+// Whether the chunk of a split module has been instantiated, e.g. to only
+// offer a feature right away if its code is already resident. Folded modules
+// always are, unknown and dropped ones never.
+export function isLoaded(name) {
+  const state = getChunkState(name);
+  if (state === undefined) return false;
+  return (
+    state.chunk === undefined ||
+    getRegistry().loadedChunks.has(state.url.href)
+  );
+}
+
+// Names of the instantiated chunks, in the order they were instantiated.
+export function loadedChunks() {
+  return [...getRegistry().loadedChunks.values()].map(({ chunk }) => chunk.name);
+}
+
+// Called by `wasm_split::is_loaded`.
+export function __wasm_split_is_loaded(namePtr, nameLen) {
+  return isLoaded(decodeString(namePtr, nameLen)) ? 1 : 0;
+}
+
+// Called by `wasm_split::loaded_chunks`, which retries with the returned
+// length if the names, one per line, did not fit.
+export function __wasm_split_loaded_chunks(ptr, capacity) {
+  const encoded = new TextEncoder().encode(
+    loadedChunks()
+      .map((name) => name + "\n")
+      .join(""),
+  );
+  if (encoded.length <= capacity) {
+    new Uint8Array(getMainExports().memory.buffer, ptr, capacity).set(encoded);
+  }
+  return encoded.length;
+}
+
+// Called by `wasm_split::preload`, which ignores unknown names and leaves
+// logging failures to `loadChunk`.
+export function __wasm_split_preload(namePtr, nameLen) {
+  const name = decodeString(namePtr, nameLen);
+  if (getChunkState(name) !== undefined) {
+    loadChunk(name, undefined, true).catch(() => {});
+  }
+}
+
+// Called by `wasm_split::hydrate_preload` with a comma-separated list of
+// names, which loads them as `loadGroup` does, without deferring compiles as
+// preloads do. Unknown names are ignored.
+export function __wasm_split_hydrate_preload(namesPtr, namesLen) {
+  const names = decodeString(namesPtr, namesLen)
+    .split(",")
+    .filter((name) => getChunkState(name) !== undefined);
+  if (names.length > 0) loadGroup(names).catch(() => {});
+}
+
+// Called by the runtime when a cross-chunk call guarded by `--guard-calls`
+// finds its table slot empty. Logs the call, since panic hooks don't print
+// the `CrossChunkCallError` that the runtime then panics with, and writes the
+// chunk and symbol of the slot into it, separated by a newline.
+export function __wasm_split_report_guarded_call(slot, ptr, capacity) {
+  const entry = MANIFEST.table?.find((entry) => entry.slot === slot);
+  const chunk = entry?.chunk ?? "";
+  const symbol = entry?.symbol ?? `table slot ${slot}`;
+  const state = getChunkState(chunk);
+  const status = getRegistry().loadedChunks.has(state?.url?.href)
+    ? "loaded, but did not fill in its slot"
+    : state?.promise !== undefined
+      ? "still loading"
+      : "not loaded";
+  console.error(
+    `wasm-split: call to ${symbol} of chunk "${chunk}", which is ${status}`,
+  );
+  const buffer = new Uint8Array(getMainExports().memory.buffer, ptr, capacity);
+  return new TextEncoder().encodeInto(`${chunk}\n${symbol}`, buffer).written;
+}
+
+// Called by `wasm_split::drop_module`. The loader retains neither the bytes
+// nor the compiled module of a chunk once it is instantiated, so this releases
+// what remains: the manifest entry and the bookkeeping for panic attribution.
+// Loaded chunks stay callable, since their code is kept alive by the function
+// table, while chunks that were never loaded can no longer be loaded.
+export function __wasm_split_drop_module(namePtr, nameLen) {
+  const name = decodeString(namePtr, nameLen);
+  const state = getChunkState(name);
+  if (state?.chunk === undefined) return;
+  const { chunkStates, loadedChunks } = getRegistry();
+  releaseCompilation(state);
+  const drop = () => {
+    loadedChunks.delete(state.url.href);
+    // The chunk stays loaded, so later loads, including those of chunks
+    // depending on it and of groups, succeed right away without any bytes.
+    chunkStates.set(name, {
+      chunk: state.chunk,
+      url: state.url,
+      promise: Promise.resolve(0),
+      instantiated: true,
+    });
+  };
+  if (state.promise === undefined) {
+    chunkStates.delete(name);
+  } else {
+    // Let an in-progress load finish before replacing its state, so that
+    // loads waiting for it, which hold its promise, still get its result.
+    state.promise.then(drop, () => chunkStates.delete(name));
+  }
+  MANIFEST.chunks = MANIFEST.chunks.filter((chunk) => chunk.name !== name);
+}
+
+// Remembers what the table slots of a chunk hold before it fills them in:
+// nothing, or the stubs of `--lazy-indirect-calls`, which `unloadChunk`
+// puts back.
+function saveTableSlots(state) {
+  const { start, end } = state.chunk.table_slots ?? { start: 0, end: 0 };
+  const table = getMainExports().__indirect_function_table;
+  state.savedTableSlots = [];
+  for (let slot = start; slot < end; ++slot) {
+    state.savedTableSlots.push(table.get(slot));
+  }
+}
+
+// Why the instantiated chunk of `state` cannot be unloaded, if it cannot:
+// code that may still call into it would find its table slots empty.
+function unloadBlocker(state) {
+  const { chunkStates, loadedChunks } = getRegistry();
+  for (const other of new Set(chunkStates.values())) {
+    if (
+      other.chunk?.dependencies?.includes(state.chunk.name) &&
+      loadedChunks.has(other.url.href)
+    ) {
+      return `chunk "${other.chunk.name}", which depends on it, is loaded`;
+    }
+  }
+  if (
+    state.chunk.referenced &&
+    (state.savedTableSlots ?? [null]).some((saved) => saved === null)
+  ) {
+    return (
+      "function pointers may refer to its functions; split with " +
+      "--lazy-indirect-calls to unload it"
+    );
+  }
+  return undefined;
+}
+
+// Puts back what the table slots of the chunk of `state` held before it was
+// instantiated, and forgets the chunk, so that the next load instantiates it
+// anew and the browser can collect its instance.
+function unloadChunk(state) {
+  const table = getMainExports().__indirect_function_table;
+  const { start } = state.chunk.table_slots ?? { start: 0 };
+  state.savedTableSlots?.forEach((saved, i) => table.set(start + i, saved));
+  state.savedTableSlots = undefined;
+  getRegistry().loadedChunks.delete(state.url.href);
+  releaseCompilation(state);
+  state.module = undefined;
+  state.instantiated = false;
+  state.promise = undefined;
+}
+
+// Unloads the chunk of a split module that is no longer needed, unless
+// other code may still call into it, and returns whether it did; see
+// `wasm_split::unload`.
+export function unload(name) {
+  const state = getChunkState(name);
+  if (state?.chunk === undefined || !state.instantiated) return false;
+  const blocker = unloadBlocker(state);
+  if (blocker !== undefined) {
+    console.warn(
+      `wasm-split: cannot unload chunk "${state.chunk.name}": ${blocker}`,
+    );
+    return false;
+  }
+  unloadChunk(state);
+  return true;
+}
+
+// Unloads the chunk of a split module as `unload` does, and loads it again
+// bypassing the HTTP cache, e.g. to pick up a rebuilt chunk in development.
+// Rejects with an error of code `InUse` if it cannot be unloaded.
+export function reload(name) {
+  const state = getChunkState(name);
+  if (state === undefined) {
+    return Promise.reject(
+      new ChunkLoadError(LOAD_ERROR.UnknownChunk, 0, `Unknown chunk "${name}"`),
+    );
+  }
+  if (state.chunk !== undefined && state.instantiated) {
+    const blocker = unloadBlocker(state);
+    if (blocker !== undefined) {
+      return Promise.reject(
+        new ChunkLoadError(
+          LOAD_ERROR.InUse,
+          0,
+          `Cannot unload chunk "${state.chunk.name}": ${blocker}`,
+        ),
+      );
+    }
+    unloadChunk(state);
+  }
+  if (state.chunk !== undefined && state.promise === undefined) {
+    state.refetch = true;
+  }
+  return loadChunk(name);
+}
+
+// Called by `wasm_split::unload`.
+export function __wasm_split_unload(namePtr, nameLen) {
+  return unload(decodeString(namePtr, nameLen)) ? 1 : 0;
+}
+
+// Called by `wasm_split::reload`.
+export function __wasm_split_reload(
+  namePtr,
+  nameLen,
+  callbackIndex,
+  callbackData,
+) {
+  invokeCallbackWhenLoaded(
+    reload(decodeString(namePtr, nameLen)),
+    callbackIndex,
+    callbackData,
+  );
+}
+
+// Set by `--hot-reload`, which `wasm-split serve` passes, for the loader to
+// listen for its rebuilds on `HOT_RELOAD_EVENTS`, an event stream of the
+// page's origin.
+const HOT_RELOAD = false;
+const HOT_RELOAD_EVENTS = "/__wasm_split/events";
+
+// Swaps in the chunks of a rebuild that left the main module as it was, as
+// listed by a `swap` event: a loaded chunk is fetched anew and instantiated
+// over the table slots of its old instance, so that the next calls through
+// them run the new code, and its `on_load` hooks run again. Nothing else is
+// reset, so state that its code keeps in the main module's memory survives.
+// Chunks that are not loaded are loaded from their new file when they next
+// are. Returns false if the page has to be reloaded instead, as it does for
+// chunks that are loading and for signed manifests, whose signature only
+// covers the chunks of the running build. The build ID of the registry stays
+// that of the running build.
+async function hotSwap({ build_id: buildId, chunks }) {
+  if (getManifestPublicKey() !== undefined) return false;
+  const states = chunks.map((chunk) => getChunkState(chunk.name));
+  if (
+    states.some(
+      (state) =>
+        state?.chunk === undefined ||
+        (state.promise !== undefined && !state.instantiated),
+    )
+  ) {
+    return false;
+  }
+  const baseUrl = getChunkBaseUrl() ?? OUTPUT_DIR_URL;
+  for (const [i, chunk] of chunks.entries()) {
+    const state = states[i];
+    const loaded = state.instantiated;
+    getRegistry().loadedChunks.delete(state.url.href);
+    releaseCompilation(state);
+    state.module = undefined;
+    state.chunk = chunk;
+    state.url = chunkUrl(chunk, baseUrl);
+    state.url.searchParams.set("build", buildId);
+    MANIFEST.chunks = MANIFEST.chunks.map((entry) =>
+      entry.name === chunk.name ? chunk : entry,
+    );
+    if (!loaded) continue;
+    state.refetch = true;
+    const compiledModule = await compileChunk(state);
+    checkSignatures(state, compiledModule);
+    await importSnippets(chunk.imports);
+    const instantiateStart = performance.now();
+    try {
+      await WebAssembly.instantiate(compiledModule, getImports());
+    } finally {
+      if (!chunk.pinned) releaseCompilation(state);
+    }
+    finishInstantiation(chunk.name, state, compiledModule, instantiateStart);
+    state.promise = Promise.resolve(chunk.size ?? 0);
+    console.info(`wasm-split: swapped in the rebuilt chunk ${chunk.name}`);
+  }
+  return true;
+}
+
+// Follows the rebuilds of `wasm-split serve`: a `swap` event lists the
+// chunks that changed, and a `reload` event that the page needs reloading,
+// as the main module changed. Swaps run one at a time, in order.
+function listenForRebuilds() {
+  const events = new EventSource(HOT_RELOAD_EVENTS);
+  let swaps = Promise.resolve();
+  events.addEventListener("swap", ({ data }) => {
+    swaps = swaps
+      .then(() => hotSwap(JSON.parse(data)))
+      .then(
+        (swapped) => {
+          if (!swapped) location.reload();
+        },
+        (e) => {
+          console.error("wasm-split: failed to swap rebuilt chunks", e);
+          location.reload();
+        },
+      );
+  });
+  events.addEventListener("reload", () => location.reload());
+}
+
+// Snapshot of what the loader knows, for devtools extensions and for
+// assertions of browser tests: the embedded manifest, the state and timings of
+// every chunk, and the slots of the indirect function table that the loader
+// manages. Timings are `performance.now()` values. Chunks of a main module
+// that is not instantiated yet are all `unloaded`. The manifest at
+// `manifestUrl` also lists the functions of every table slot.
+//
+// Every copy of this script registers `inspect` in `window.__WASM_SPLIT__`, so
+// that an extension can find the loaders of a page without importing them:
+//
+//   for (const loader of window.__WASM_SPLIT__.loaders) {
+//     console.table(loader.inspect().chunks);
+//   }
+export function inspect() {
+  let registry;
+  try {
+    registry = getRegistry();
+  } catch {
+    // The main module is not instantiated.
+  }
+  const chunks = MANIFEST.chunks.map((chunk) => {
+    const state = registry?.chunkStates.get(chunk.name);
+    const loaded = registry?.loadedChunks.get(state?.url?.href);
+    return {
+      name: chunk.name,
+      kind: chunk.kind,
+      file: chunk.file,
+      url: state?.url?.href,
+      size: chunk.size,
+      dependencies: chunk.dependencies ?? [],
+      pinned: chunk.pinned ?? false,
+      auto: chunk.auto ?? false,
+      entry: chunk.entry,
+      // The fallback has every chunk folded into it.
+      state:
+        chunk.kind === "main" || USE_FALLBACK
+          ? registry === undefined
+            ? "unloaded"
+            : "loaded"
+          : loaded !== undefined
+            ? "loaded"
+            : state?.promise !== undefined
+              ? "loading"
+              : state?.error !== undefined
+                ? "failed"
+                : "unloaded",
+      startedAt: state?.startedAt,
+      loadedAt: loaded?.loadedAt,
+      fromCache: state?.fromCache,
+      deduplicated: state?.deduplicated ?? false,
+      error: state?.error === undefined ? undefined : String(state.error),
+    };
+  });
+  const table = registry && getMainExports().__indirect_function_table;
+  return {
+    manifestUrl: MANIFEST_URL.href,
+    buildId: MANIFEST.build_id,
+    // Whether the page runs the fallback, with every split module folded.
+    fallback: USE_FALLBACK,
+    manifest: MANIFEST,
+    chunks,
+    folded: (MANIFEST.folded ?? []).map((folded) => folded.name),
+    aliases: MANIFEST.aliases ?? {},
+    table: {
+      length: table?.length,
+      reservedSlots: MANIFEST.reserved_table_slots ?? { start: 0, end: 0 },
+      // Undefined until a reserved slot is first allocated.
+      freeSlots: registry?.freeTableSlots?.slice().reverse(),
+    },
+  };
+}
+
+// Calls of `#[wasm_split]` functions on this page, recorded by the runtime
+// with its `profile` feature, by module and function name.
+const profileCalls = new Map();
+
+export function __wasm_split_profile_call(
+  modulePtr,
+  moduleLen,
+  functionPtr,
+  functionLen,
+) {
+  const module = decodeString(modulePtr, moduleLen);
+  const name = decodeString(functionPtr, functionLen);
+  const key = module + "\0" + name;
+  const call = profileCalls.get(key);
+  if (call !== undefined) {
+    call.count++;
+  } else {
+    profileCalls.set(key, {
+      module,
+      function: name,
+      first_call_ms: performance.now(),
+      count: 1,
+    });
+  }
+}
+
+// The calls recorded so far, as a session of the profiles that
+// `wasm-split --profile` reads. Empty without the `profile` feature.
+export function getProfile() {
+  return {
+    build_id: MANIFEST.build_id,
+    calls: [...profileCalls.values()].map((call) => ({ ...call })),
+  };
+}
+
+// Replaces the entry of an earlier copy of this script for the same
+// manifest, e.g. of an app that remounted.
+const INSPECTOR = (globalThis.__WASM_SPLIT__ ??= { loaders: [] });
+INSPECTOR.loaders = INSPECTOR.loaders
+  .filter((loader) => loader.manifestUrl !== MANIFEST_URL.href)
+  .concat({ manifestUrl: MANIFEST_URL.href, inspect, profile: getProfile });
+
+// Calls of `#[wasm_split(..., worker)]` functions run on a module worker
+// started from this script, which instantiates the main module at
+// `WORKER_MAIN_URL` with every import outside of the loader's throwing, loads
+// the chunk of the function into it on first use, and calls the function's
+// entry point there. Arguments and results are copied through the main
+// module's worker buffer. Like the URL of the loader's module, it is rewritten
+// by wasm-split.
+const WORKER_MAIN_URL = new URL("./main_bg.wasm", import.meta.url);
+const LOADER_MODULE = "./__wasm_split.js";
+const WORKER_PARAM = "wasm-split-worker";
+const isWorker = new URL(import.meta.url).searchParams.has(WORKER_PARAM);
+
+// `null` once the worker failed, after which calls run on the main thread.
+let worker;
+let nextWorkerCall = 1;
+// Callbacks of calls awaiting the worker, and results awaiting the runtime
+// to take them, by call ID.
+const workerCalls = new Map();
+const workerOutputs = new Map();
+
+// Under a CSP with `require-trusted-types-for 'script'`, the worker's URL
+// must come from a Trusted Types policy, which the CSP must allow with
+// `trusted-types wasm-split`. `undefined` where the policy can't be created,
+// and then the URL is passed as is.
+let workerUrlPolicy;
+
+function getWorkerUrl() {
+  const url = new URL(import.meta.url);
+  url.searchParams.set(WORKER_PARAM, "");
+  if (workerUrlPolicy === undefined && globalThis.trustedTypes !== undefined) {
+    try {
+      workerUrlPolicy = trustedTypes.createPolicy("wasm-split", {
+        // Only ever given the URL of this very script.
+        createScriptURL: (url) => url,
+      });
+    } catch {
+      workerUrlPolicy = null;
+    }
+  }
+  return workerUrlPolicy ? workerUrlPolicy.createScriptURL(url.href) : url;
+}
+
+function getWorker() {
+  if (worker !== undefined) return worker;
+  try {
+    worker = new Worker(getWorkerUrl(), { type: "module" });
+  } catch (e) {
+    // Such as a SecurityError where the CSP has no `worker-src` for the
+    // loader's origin.
+    console.error("wasm-split: worker failed", e);
+    worker = null;
+    return null;
+  }
+  worker.onmessage = ({ data: { id, output, error } }) => {
+    const { callbackIndex, callbackData } = workerCalls.get(id);
+    workerCalls.delete(id);
+    if (error !== undefined) {
+      invokeCallback(callbackIndex, callbackData, ...error);
+      return;
+    }
+    workerOutputs.set(id, new Uint8Array(output));
+    invokeCallback(callbackIndex, callbackData, LOAD_OK, id);
+  };
+  // The worker script failed to load, so calls run on the main thread.
+  worker.onerror = (e) => {
+    console.error("wasm-split: worker failed", e);
+    worker.terminate();
+    worker = null;
+    for (const { callbackIndex, callbackData } of workerCalls.values()) {
+      invokeCallback(callbackIndex, callbackData, LOAD_ERROR.UnsupportedFeature);
+    }
+    workerCalls.clear();
+  };
+  return worker;
+}
+
+// Called by `wasm_split::__macro_support::run_on_worker`, whose callback gets
+// the ID of the call's result on success.
+export function __wasm_split_run_on_worker(
+  chunkPtr,
+  chunkLen,
+  entryPtr,
+  entryLen,
+  inputPtr,
+  inputLen,
+  callbackIndex,
+  callbackData,
+) {
+  if (typeof Worker !== "function" || worker === null || USE_FALLBACK) {
+    invokeCallback(callbackIndex, callbackData, LOAD_ERROR.UnsupportedFeature);
+    return;
+  }
+  const id = nextWorkerCall++;
+  const input = new Uint8Array(
+    getMainExports().memory.buffer,
+    inputPtr,
+    inputLen,
+  ).slice();
+  const target = getWorker();
+  if (target === null) {
+    invokeCallback(callbackIndex, callbackData, LOAD_ERROR.UnsupportedFeature);
+    return;
+  }
+  workerCalls.set(id, { callbackIndex, callbackData });
+  target.postMessage(
+    {
+      id,
+      chunk: decodeString(chunkPtr, chunkLen),
+      entry: decodeString(entryPtr, entryLen),
+      input: input.buffer,
+      // The worker has no page to read the configured base from.
+      baseUrl: getChunkBaseUrl()?.href,
+      chunkUrls: Object.fromEntries(chunkAssetUrls),
+      // Nor the settings of `configure` for its requests, but for `fetch`,
+      // which cannot be posted.
+      credentials: requestCredentials,
+      headers: Object.fromEntries(requestHeaders),
+    },
+    [input.buffer],
+  );
+}
+
+export function __wasm_split_worker_output_len(id) {
+  return workerOutputs.get(id).length;
+}
+
+export function __wasm_split_take_worker_output(id, ptr) {
+  const output = workerOutputs.get(id);
+  workerOutputs.delete(id);
+  new Uint8Array(getMainExports().memory.buffer, ptr, output.length).set(
+    output,
+  );
+}
+
+async function instantiateWorkerMain() {
+  const response = await fetchResource(WORKER_MAIN_URL);
+  if (!response.ok) {
+    throw new ChunkLoadError(
+      LOAD_ERROR.Http,
+      response.status,
+      `HTTP status ${response.status}`,
+    );
+  }
+  const module = await WebAssembly.compile(await response.arrayBuffer());
+  // This very module, with the imports the main module has from the loader.
+  const loader = await import(import.meta.url);
+  const imports = {};
+  for (const { module: name, name: field, kind } of WebAssembly.Module.imports(
+    module,
+  )) {
+    const namespace = (imports[name] ??= {});
+    if (name === LOADER_MODULE) {
+      namespace[field] = loader[field];
+    } else if (kind === "function") {
+      namespace[field] = () => {
+        throw new Error(`${name}.${field} cannot be called on a wasm-split worker`);
+      };
+    }
+  }
+  const instance = await WebAssembly.instantiate(module, imports);
+  workerMainExports = instance.exports;
+}
+
+function runOnWorker() {
+  let mainInstantiation;
+  globalThis.onmessage = async ({
+    data: { id, chunk, entry, input, baseUrl, chunkUrls, credentials, headers },
+  }) => {
+    try {
+      requestHeaders.clear();
+      configure({ credentials, headers });
+      setChunkUrls(chunkUrls);
+      await (mainInstantiation ??= instantiateWorkerMain());
+      if (baseUrl !== undefined && baseUrl !== chunkBaseUrl?.href) {
+        setBaseUrl(baseUrl);
+      }
+      await loadChunk(chunk);
+      const exports = getMainExports();
+      const inputPtr = exports.__wasm_split_worker_buffer(input.byteLength);
+      new Uint8Array(exports.memory.buffer, inputPtr, input.byteLength).set(
+        new Uint8Array(input),
+      );
+      const outputLen = exports[entry]();
+      const outputPtr = exports.__wasm_split_worker_buffer(outputLen);
+      const output = new Uint8Array(
+        exports.memory.buffer,
+        outputPtr,
+        outputLen,
+      ).slice();
+      globalThis.postMessage({ id, output: output.buffer }, [output.buffer]);
+    } catch (e) {
+      console.error(`wasm-split: ${entry} failed on the worker`, e);
+      globalThis.postMessage({ id, error: classifyError(e) });
+    }
+  };
+}
+
+if (isWorker) runOnWorker();
+if (HOT_RELOAD && !isWorker && typeof EventSource === "function") {
+  listenForRebuilds();
+}
+
+// Returns the load function imported by `#[wasm_split]` functions of a module.
+function makeLoad(name) {
+  return (callbackIndex, callbackData) =>
+    invokeCallbackWhenLoaded(loadChunk(name), callbackIndex, callbackData);
+}
@@ -0,0 +1,49 @@
+//! Logs of a split on stderr, with `--verbose` or `--log-format`: the spans
+//! of its phases, as listed by `--timings`, and within the `emit` phase, an
+//! `encode` span for each module, at debug level, with its size. Each span is
+//! logged as it closes, with the time spent in it, e.g. to collect the
+//! timings of CI builds as JSON lines:
+//!
+//! ```sh
+//! wasm-split app.wasm pkg --log-format json 2> split.log
+//! ```
+//!
+//! Without either option, nothing is logged, and the output is that of
+//! `println!` alone.
+
+use std::io::IsTerminal;
+
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::fmt::format::FmtSpan;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum LogFormat {
+    /// One line of text per span or event.
+    #[default]
+    Text,
+    /// One JSON object per line, with the fields of the span or event.
+    Json,
+}
+
+/// Installs the subscriber for `format`, logging debug spans as well when
+/// `verbose`. Does nothing if neither is given, or if a subscriber is
+/// already installed.
+pub fn init(verbose: bool, format: Option<LogFormat>) {
+    if !verbose && format.is_none() {
+        return;
+    }
+    let level = if verbose {
+        LevelFilter::DEBUG
+    } else {
+        LevelFilter::INFO
+    };
+    let builder = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_ansi(std::io::stderr().is_terminal())
+        .with_writer(std::io::stderr);
+    let _ = match format.unwrap_or_default() {
+        LogFormat::Text => builder.try_init(),
+        LogFormat::Json => builder.json().try_init(),
+    };
+}
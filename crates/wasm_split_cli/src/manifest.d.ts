@@ -0,0 +1,107 @@
+// Types of `wasm-split-manifest.json`, as written by `manifest.rs`, and of
+// the names of the build it was written for. Optional fields are left out of
+// the manifest when they are empty or unset.
+
+/** Name of a chunk, split module or alias of this build. */
+export type ChunkName = never;
+
+/** Route of the `[routes]` table or of `route = "..."` of a split point. */
+export type RoutePath = never;
+
+export type ChunkKind = "main" | "split" | "shared";
+
+export type Priority = "critical" | "high" | "low";
+
+export type Feature =
+  | "simd"
+  | "atomics"
+  | "bulk-memory"
+  | "exception-handling"
+  | "multi-memory";
+
+export type Encoding = "br" | "gzip";
+
+/** A range of indices, from `start` inclusive to `end` exclusive. */
+export interface Range {
+  start: number;
+  end: number;
+}
+
+export interface ManifestChunk {
+  name: ChunkName;
+  /** Relative to the output directory. */
+  file: string;
+  kind: ChunkKind;
+  size: number;
+  gzip_size?: number;
+  compressed_sizes?: Partial<Record<Encoding, number>>;
+  hash?: string;
+  /** Hex SHA-256 digest, checked by the loader if the manifest is signed. */
+  sha256?: string;
+  /** Subresource Integrity hash, which the loader fetches the chunk with. */
+  integrity?: string;
+  priority?: Priority;
+  features?: Feature[];
+  /** Export names of the `#[wasm_split]` functions of the chunk. */
+  entries?: string[];
+  /**
+   * Signatures of the functions of `entries`, by export name, which the
+   * loader checks the chunk against before instantiating it.
+   */
+  signatures?: Record<string, string>;
+  /** Chunks loaded before this one. */
+  dependencies?: ChunkName[];
+  defined_functions: Range;
+  duplicated?: { functions: number; bytes: number };
+  pinned?: boolean;
+  auto?: boolean;
+  /** Entry point whose exports call into the chunk; see `loadEntry`. */
+  entry?: string;
+  /** Table slots of the `#[wasm_split::on_load]` hooks of the chunk. */
+  on_load?: number[];
+  /** wasm-bindgen imports whose JS snippets are imported with the chunk. */
+  imports?: string[];
+  /** Table slots that the chunk fills in, reset when it is unloaded. */
+  table_slots?: Range;
+  /** Whether code other than its split points may call it through the table. */
+  referenced?: boolean;
+}
+
+export interface FoldedModule {
+  name: ChunkName;
+  code_size: number;
+  on_load?: number[];
+}
+
+export interface MergedModule {
+  name: ChunkName;
+  /** Split module whose chunk holds its code. */
+  into: ChunkName;
+  code_size: number;
+  shared_size: number;
+}
+
+export interface TableSlot {
+  slot: number;
+  chunk: ChunkName;
+  function_index: number;
+  symbol?: string;
+}
+
+export interface Manifest {
+  /** Changes whenever any chunk does. */
+  build_id: string;
+  /** Subdirectory of `--asset-version`. */
+  version?: string;
+  chunks: ManifestChunk[];
+  folded?: FoldedModule[];
+  /** By `--min-chunk-size` and `--max-chunks`, in order. */
+  merged?: MergedModule[];
+  /** Split modules needed by each route. */
+  routes?: Partial<Record<RoutePath, ChunkName[]>>;
+  /** Split modules co-located with others, and the module holding them. */
+  aliases?: Partial<Record<ChunkName, ChunkName>>;
+  /** Left out of the manifest embedded in the loader. */
+  table?: TableSlot[];
+  reserved_table_slots?: Range;
+}
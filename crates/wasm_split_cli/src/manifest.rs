@@ -0,0 +1,592 @@
+use std::{collections::BTreeMap, io::Write, ops::Range};
+
+use anyhow::{bail, Result};
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha384};
+
+use crate::{
+    compress::Encoding,
+    config::Config,
+    emit::EmittedModule,
+    features::Feature,
+    metadata::{get_module_aliases, Priority, SplitModuleMetadata},
+    read::InputModule,
+    small_chunks::MergedModule,
+    split_point::{OutputModuleInfo, SplitModuleIdentifier, SplitProgramInfo},
+    toolchain::WASM_BINDGEN_PLACEHOLDER_MODULE,
+};
+
+pub const MANIFEST_FILENAME: &str = "wasm-split-manifest.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChunkKind {
+    /// The main module, loaded eagerly by the wasm-bindgen glue.
+    Main,
+    /// A module loaded on demand by one or more `#[wasm_split]` functions.
+    Split,
+    /// Code shared by several split modules, loaded before any of them.
+    Shared,
+}
+
+/// Description of a single output module.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestChunk {
+    /// Logical name, as used by `__wasm_split_load_{name}`.
+    pub name: String,
+    /// Output filename, relative to the output directory.
+    pub file: String,
+    pub kind: ChunkKind,
+    /// Size of the file in bytes, as emitted by the splitter.
+    pub size: usize,
+    /// Size of the file once compressed with gzip, which is closer to what
+    /// loading it costs when served compressed. Missing from manifests
+    /// written by older versions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gzip_size: Option<usize>,
+    /// Sizes of the precompressed copies written next to the file with
+    /// `--compress`, by encoding, e.g. `{ "br": 1234 }` for `chunk.wasm.br`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub compressed_sizes: BTreeMap<Encoding, usize>,
+    /// Hash of the file as emitted by the splitter, which only changes if its
+    /// contents do. Missing from manifests written by older versions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    /// Full SHA-256 digest of the file in hex, which the loader checks the
+    /// chunk against if the manifest is signed. Missing from manifests
+    /// written by older versions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Subresource Integrity hash of the file, `sha384-` and the base64
+    /// digest, which the loader passes to `fetch` so that the browser rejects
+    /// a chunk that was modified on its way. Missing from manifests written by
+    /// older versions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub integrity: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<Priority>,
+    /// WebAssembly features used by the chunk's code, which the browser must
+    /// support to compile it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub features: Vec<Feature>,
+    /// Export names of the split points whose code is in this chunk, i.e.
+    /// the `#[wasm_split]` functions it is loaded for, sorted. Those of the
+    /// main module are of folded modules, or were hoisted by `--profile`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub entries: Vec<String>,
+    /// Signatures of the split functions of `entries`, by export name, which
+    /// the loader checks against those that the chunk carries, so that a
+    /// chunk of another build is not instantiated; see `signatures.rs`.
+    /// Missing for the main module, and from manifests written by older
+    /// versions.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub signatures: BTreeMap<String, String>,
+    /// Names of the chunks that must be loaded before this one.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<String>,
+    /// Function indices, within this chunk, of the functions it defines.
+    /// Used to attribute stack frames (e.g. of a panic) to the chunk.
+    pub defined_functions: Range<usize>,
+    /// Functions copied into this chunk rather than called indirectly.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duplicated: Option<DuplicationStats>,
+    /// Set for the chunks of split modules listed in `pin` of the `[loader]`
+    /// config, and their dependencies.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+    /// Set for the split modules of `auto-split` crates and for vendor chunks
+    /// with `vendor = "preload"`, which the main module calls directly, and
+    /// the loader thus loads right after startup.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub auto: bool,
+    /// Name of the entry point, of the `[entries]` config, whose exports call
+    /// into this chunk, which that entry loads before calling them with
+    /// `loadEntry` of the loader, and nothing else loads eagerly.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entry: Option<String>,
+    /// Table slots of the `#[wasm_split::on_load]` hooks of the chunk's split
+    /// module, which the loader calls once it is instantiated.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub on_load: Vec<usize>,
+    /// Slots of the indirect function table that the chunk fills in, which
+    /// the loader resets when `wasm_split::unload` unloads it. Missing for
+    /// the main module.
+    #[serde(default, skip_serializing_if = "Range::is_empty")]
+    pub table_slots: Range<usize>,
+    /// Set if functions of the chunk other than its split functions and
+    /// hooks have table slots, as `fn` pointers, `dyn Trait` objects and the
+    /// code of other chunks may call them, so that the chunk can only be
+    /// unloaded where stubs of `--lazy-indirect-calls` take their place.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub referenced: bool,
+    /// Names of the wasm-bindgen imports that only the code of this chunk
+    /// calls, sorted, whose JS snippets the loader imports along with the
+    /// chunk from glue processed by `wasm-split snippets`; see `snippets.rs`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub imports: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DuplicationStats {
+    pub functions: usize,
+    /// Total size of the copied function bodies.
+    pub bytes: usize,
+}
+
+/// Machine-readable description of the split output, written to
+/// [`MANIFEST_FILENAME`] alongside the chunks unless `[output]` configures
+/// another path.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Identifies the build: a hash of the rest of the manifest, including
+    /// the digests of all chunks, so that it changes whenever any of them
+    /// does. Chunks can only be loaded into a main module with the same build
+    /// ID.
+    pub build_id: String,
+    /// Subdirectory of the output directory that the build was written to
+    /// with `--asset-version`, which the paths of the manifest are within.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    pub chunks: Vec<ManifestChunk>,
+    /// Split modules that were folded back into the main module because they
+    /// were too small to be worth loading separately.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub folded: Vec<FoldedModule>,
+    /// Split modules that were merged into others with `--min-chunk-size` or
+    /// `--max-chunks`, in order. Each is also an alias of the module it was
+    /// merged into.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub merged: Vec<MergedModule>,
+    /// Names of the split modules needed by each route, from the config.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub routes: BTreeMap<String, Vec<String>>,
+    /// Split modules co-located with others by `#[wasm_split(name, with =
+    /// module)]`, mapped to the module whose chunk holds their code.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub aliases: BTreeMap<String, String>,
+    /// Layout of the indirect function table shared by all chunks, ordered by
+    /// slot.
+    ///
+    /// Calls between chunks go through this table: each chunk fills in a
+    /// contiguous range of slots with its functions when it is instantiated,
+    /// and calls functions of other chunks by slot. Ranges follow the order of
+    /// `chunks`, and within a range functions are ordered by symbol name, so
+    /// slots only move if the set of functions called across chunks changes.
+    /// Slot 0 is always empty.
+    ///
+    /// With `--table-only`, the main module keeps the table of the input, and
+    /// only the slots appended to it are listed: first those of the split
+    /// modules, ordered by module and export name, then the main module
+    /// functions they call.
+    ///
+    /// Not included in the manifest embedded in the loader, which only needs
+    /// it to report guarded calls with `--guard-calls`, and to find the chunk
+    /// of a slot for its stub with `--lazy-indirect-calls`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub table: Vec<TableSlot>,
+    /// Empty slots at the end of the table, reserved with
+    /// `#[wasm_split(table_slots = ...)]` for callbacks registered at runtime
+    /// and handed out by the loader.
+    #[serde(default, skip_serializing_if = "Range::is_empty")]
+    pub reserved_table_slots: Range<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TableSlot {
+    pub slot: usize,
+    /// Name of the chunk that fills in the slot.
+    pub chunk: String,
+    /// Index of the function within that chunk.
+    pub function_index: usize,
+    /// Symbol name of the function, if the input has a name section.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
+}
+
+/// Hex SHA-256 digest of `data`.
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Subresource Integrity metadata of `data`, as in `integrity` attributes.
+pub fn sri_hash(data: &[u8]) -> String {
+    format!(
+        "sha384-{}",
+        base64::engine::general_purpose::STANDARD.encode(Sha384::digest(data))
+    )
+}
+
+/// Size of `data` compressed with gzip at the default level, as most servers
+/// compress responses.
+pub fn gzip_size(data: &[u8]) -> usize {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap().len()
+}
+
+/// Short hex digest of `data`, used for build IDs and chunk hashes.
+pub fn content_hash(data: &[u8]) -> String {
+    sha256_hex(data)[..16].to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FoldedModule {
+    pub name: String,
+    /// Code size that the split module would have had.
+    pub code_size: usize,
+    /// Table slots of the module's hooks, which are part of the main module
+    /// but only called on the first load of the module.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub on_load: Vec<usize>,
+}
+
+impl Manifest {
+    pub fn new(
+        module: &InputModule,
+        program_info: &SplitProgramInfo,
+        metadata: &SplitModuleMetadata,
+        emitted_modules: &[EmittedModule],
+        config: &Config,
+        version: Option<&str>,
+    ) -> Result<Self> {
+        let split_priority = |name: &String| {
+            metadata
+                .get(name)
+                .and_then(|attributes| attributes.priority)
+                .unwrap_or_default()
+        };
+        // Table slots of the hooks of `module_name` that are part of an output
+        // module.
+        let hook_slots = |info: &OutputModuleInfo, emitted: &EmittedModule, module_name: &str| {
+            info.on_load_hooks
+                .iter()
+                .filter(|hook| hook.module_name == module_name)
+                .filter_map(|hook| {
+                    emitted
+                        .table_slots
+                        .iter()
+                        .find(|&&(_, func_id)| func_id == hook.export_func)
+                        .map(|&(slot, _)| slot)
+                })
+                .collect::<Vec<_>>()
+        };
+        let mut chunks = program_info
+            .output_modules
+            .iter()
+            .zip(emitted_modules)
+            .map(|((identifier, info), emitted)| {
+                let name = identifier.name();
+                let on_load = hook_slots(info, emitted, &name);
+                let mut entries = info
+                    .split_points
+                    .iter()
+                    .map(|split_point| module.exports[split_point.export].name.to_string())
+                    .collect::<Vec<_>>();
+                entries.sort();
+                let (table_slots, referenced) = match identifier {
+                    SplitModuleIdentifier::Main => (0..0, false),
+                    _ => (
+                        emitted.table_slots.first().map_or(0, |&(slot, _)| slot)
+                            ..emitted.table_slots.last().map_or(0, |&(slot, _)| slot + 1),
+                        emitted.table_slots.iter().any(|&(_, func_id)| {
+                            !info
+                                .split_points
+                                .iter()
+                                .any(|split_point| split_point.export_func == func_id)
+                                && !info
+                                    .on_load_hooks
+                                    .iter()
+                                    .any(|hook| hook.export_func == func_id)
+                        }),
+                    ),
+                };
+                let (kind, priority, dependencies) = match identifier {
+                    SplitModuleIdentifier::Main => (ChunkKind::Main, None, Vec::new()),
+                    SplitModuleIdentifier::Split(split) => (
+                        ChunkKind::Split,
+                        Some(split_priority(split)),
+                        program_info
+                            .output_modules
+                            .iter()
+                            .filter(|(other, _)| {
+                                other
+                                    .dependents()
+                                    .is_some_and(|splits| splits.contains(split))
+                            })
+                            .map(|(other, _)| other.name())
+                            .collect(),
+                    ),
+                    // A shared chunk is needed as urgently as the most urgent
+                    // module that depends on it.
+                    SplitModuleIdentifier::Chunk(splits)
+                    | SplitModuleIdentifier::Named(_, splits) => (
+                        ChunkKind::Shared,
+                        splits.iter().map(split_priority).min(),
+                        Vec::new(),
+                    ),
+                };
+                ManifestChunk {
+                    file: config.output.module_file(identifier),
+                    name,
+                    kind,
+                    size: emitted.size,
+                    gzip_size: Some(emitted.gzip_size),
+                    compressed_sizes: emitted.compressed_sizes.clone(),
+                    hash: Some(emitted.hash.clone()),
+                    sha256: Some(emitted.sha256.clone()),
+                    integrity: Some(emitted.integrity.clone()),
+                    priority,
+                    features: emitted.features.clone(),
+                    entries,
+                    signatures: emitted.signatures.clone(),
+                    dependencies,
+                    defined_functions: emitted.defined_functions.clone(),
+                    duplicated: (!info.duplicated_funcs.is_empty()).then(|| DuplicationStats {
+                        functions: info.duplicated_funcs.len(),
+                        bytes: info
+                            .duplicated_funcs
+                            .iter()
+                            .map(|&func_id| {
+                                module.defined_funcs[func_id - module.imported_funcs.len()]
+                                    .body
+                                    .range()
+                                    .len()
+                            })
+                            .sum(),
+                    }),
+                    pinned: false,
+                    auto: matches!(identifier, SplitModuleIdentifier::Split(split)
+                        if program_info.auto_split_modules.contains(split)
+                            || config.preloads_vendor_chunk(split)),
+                    entry: match identifier {
+                        SplitModuleIdentifier::Split(split) => program_info
+                            .entry_modules
+                            .iter()
+                            .find(|(_, module_name)| module_name == split)
+                            .map(|(entry, _)| entry.clone()),
+                        _ => None,
+                    },
+                    on_load,
+                    imports: get_chunk_imports(module, program_info, identifier, info),
+                    table_slots,
+                    referenced,
+                }
+            })
+            .collect::<Vec<_>>();
+        let folded = program_info
+            .folded_modules
+            .iter()
+            .map(|(name, code_size)| FoldedModule {
+                name: name.clone(),
+                code_size: *code_size,
+                on_load: program_info
+                    .output_module_identifiers
+                    .get(&SplitModuleIdentifier::Main)
+                    .map(|&main| {
+                        hook_slots(
+                            &program_info.output_modules[main].1,
+                            &emitted_modules[main],
+                            name,
+                        )
+                    })
+                    .unwrap_or_default(),
+            })
+            .collect::<Vec<_>>();
+        for (route, modules) in config.routes.iter() {
+            for name in modules {
+                let is_split = chunks
+                    .iter()
+                    .any(|chunk| chunk.kind == ChunkKind::Split && chunk.name == *name)
+                    || folded.iter().any(|folded| folded.name == *name);
+                if !is_split {
+                    bail!("Route {route:?} refers to unknown split module {name:?}");
+                }
+            }
+        }
+        for name in config.loader.pin.iter() {
+            // Folded modules are part of the main module, which needs no
+            // pinning.
+            if folded.iter().any(|folded| folded.name == *name) {
+                continue;
+            }
+            let Some(chunk) = chunks
+                .iter()
+                .find(|chunk| chunk.kind == ChunkKind::Split && chunk.name == *name)
+            else {
+                bail!("`pin` of the [loader] config refers to unknown split module {name:?}");
+            };
+            let pinned = std::iter::once(&chunk.name)
+                .chain(chunk.dependencies.iter())
+                .cloned()
+                .collect::<Vec<_>>();
+            for chunk in chunks.iter_mut() {
+                chunk.pinned |= pinned.contains(&chunk.name);
+            }
+        }
+        let mut table = program_info
+            .output_modules
+            .iter()
+            .zip(emitted_modules)
+            .flat_map(|((identifier, _), emitted)| {
+                emitted
+                    .table_slots
+                    .iter()
+                    .map(move |&(slot, func_id)| TableSlot {
+                        slot,
+                        chunk: identifier.name(),
+                        function_index: emitted
+                            .functions
+                            .iter()
+                            .position(|&output_func_id| output_func_id == func_id)
+                            .unwrap_or_default(),
+                        symbol: module
+                            .names
+                            .functions
+                            .get(&func_id)
+                            .map(|name| name.to_string()),
+                    })
+            })
+            .collect::<Vec<_>>();
+        table.sort_by_key(|slot| slot.slot);
+        let mut manifest = Self {
+            build_id: String::new(),
+            version: version.map(str::to_string),
+            chunks,
+            folded,
+            merged: program_info.merged_modules.clone(),
+            routes: config.routes.clone(),
+            aliases: get_module_aliases(metadata),
+            table,
+            reserved_table_slots: emitted_modules
+                .first()
+                .map(|main| main.reserved_table_slots.clone())
+                .unwrap_or_default(),
+        };
+        // Chunk URLs are cached for good once they carry a build ID, so it
+        // must change with any chunk, and not only with the input: the same
+        // input splits differently with another config or version of the
+        // splitter. The digests of the chunks are part of the manifest.
+        manifest.build_id = content_hash(&serde_json::to_vec(&manifest)?);
+        Ok(manifest)
+    }
+}
+
+/// Names of the wasm-bindgen imports that the code of a chunk other than
+/// the main module calls and the main module's own code does not.
+fn get_chunk_imports(
+    module: &InputModule,
+    program_info: &SplitProgramInfo,
+    identifier: &SplitModuleIdentifier,
+    info: &OutputModuleInfo,
+) -> Vec<String> {
+    if *identifier == SplitModuleIdentifier::Main {
+        return Vec::new();
+    }
+    let mut imports = info
+        .shared_imports
+        .iter()
+        .filter(|func_id| !program_info.main_imports.contains(func_id))
+        .filter_map(|&func_id| module.imports.get(*module.imported_funcs.get(func_id)?))
+        .filter(|import| import.module == WASM_BINDGEN_PLACEHOLDER_MODULE)
+        .map(|import| import.name.to_string())
+        .collect::<Vec<_>>();
+    imports.sort();
+    imports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sri_hash;
+    use crate::test_fixtures::split;
+
+    fn build_id(options: &[&str]) -> String {
+        let manifest = split("no_std_app.wasm", options).manifest();
+        manifest["build_id"].as_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn build_id_changes_with_chunks_of_same_input() {
+        assert_eq!(build_id(&[]), build_id(&[]));
+        // With folding, the small split modules get no chunks of their own.
+        assert_ne!(build_id(&[]), build_id(&["--fold-threshold", "256"]));
+    }
+
+    #[test]
+    fn puts_code_of_several_modules_into_shared_dependency() {
+        let output = split("no_std_app.wasm", &[]);
+        let manifest = output.manifest();
+        let chunk = |name: &str| {
+            manifest["chunks"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|chunk| chunk["name"] == name)
+                .unwrap_or_else(|| panic!("no chunk {name} in {manifest}"))
+                .clone()
+        };
+        assert_eq!(chunk("first_second")["kind"], "shared");
+        for name in ["first", "second"] {
+            assert_eq!(
+                chunk(name)["dependencies"],
+                serde_json::json!(["first_second"])
+            );
+        }
+        // `sum_of_squares` is called by both split modules, and not by main.
+        let symbols = String::from_utf8(output.read("wasm-split-symbols.tsv")).unwrap();
+        let chunks_of_sum_of_squares = symbols
+            .lines()
+            .filter(|line| line.contains("no_std_app::sum_of_squares"))
+            .map(|line| line.split('\t').next().unwrap())
+            .collect::<Vec<_>>();
+        assert!(chunks_of_sum_of_squares.contains(&"first_second"));
+        assert!(!chunks_of_sum_of_squares.contains(&"main"));
+    }
+
+    #[test]
+    fn lists_entries_and_compressed_size_of_chunks() {
+        let manifest = split("no_std_app.wasm", &[]).manifest();
+        let chunks = manifest["chunks"].as_array().unwrap();
+        let entries = |name: &str| {
+            let chunk = chunks.iter().find(|chunk| chunk["name"] == name).unwrap();
+            chunk["entries"]
+                .as_array()
+                .map(|entries| {
+                    entries
+                        .iter()
+                        .map(|entry| {
+                            let (_, function) =
+                                entry.as_str().unwrap().split_once("_export_").unwrap();
+                            function.split_once('_').unwrap().1
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default()
+        };
+        let mut first = entries("first");
+        first.sort();
+        assert_eq!(first, ["first", "sum", "with_details"]);
+        assert_eq!(entries("details"), ["details"]);
+        assert!(entries("main").is_empty());
+        for chunk in chunks {
+            let gzip_size = chunk["gzip_size"].as_u64().unwrap();
+            assert!(gzip_size > 0 && gzip_size < chunk["size"].as_u64().unwrap());
+        }
+    }
+
+    #[test]
+    fn lists_integrity_of_chunks() {
+        let output = split("no_std_app.wasm", &[]);
+        for chunk in output.manifest()["chunks"].as_array().unwrap() {
+            let data = output.read(chunk["file"].as_str().unwrap());
+            assert_eq!(chunk["integrity"], sri_hash(&data));
+        }
+        assert_eq!(
+            sri_hash(b"alert('Hello, world.');"),
+            "sha384-H8BRh8j48O9oYatfu5AZzq6A9RINhZO5H16dQZngK7T62em8MUt1FLm52t+eX6xO"
+        );
+    }
+}
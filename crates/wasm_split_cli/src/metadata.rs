@@ -0,0 +1,360 @@
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{read::InputModule, split_point::SplitPoint};
+
+/// Name of the custom section to which `#[wasm_split]` writes per-module
+/// attributes.
+pub const METADATA_SECTION_NAME: &str = "__wasm_split_meta";
+
+/// Load priority of an output module, from most to least urgent.
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    Critical,
+    #[default]
+    High,
+    Low,
+}
+
+impl std::str::FromStr for Priority {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "critical" => Ok(Self::Critical),
+            "high" => Ok(Self::High),
+            "low" => Ok(Self::Low),
+            _ => bail!("Invalid priority {s:?}"),
+        }
+    }
+}
+
+/// Attributes of a single split module, merged from all of its split points.
+#[derive(Debug, Default, Clone)]
+pub struct SplitModuleAttributes {
+    pub priority: Option<Priority>,
+    /// Indirect function table slots reserved for callbacks that the module
+    /// registers at runtime.
+    pub table_slots: usize,
+    /// Names of the modules co-located with this one by
+    /// `#[wasm_split(name, with = module)]`, whose code is part of it.
+    pub aliases: Vec<String>,
+    /// Router routes whose lazy views are split points of the module, as
+    /// declared with `#[wasm_split(name, route = "/path")]`.
+    pub routes: Vec<String>,
+    /// Crates declaring split functions of the module with
+    /// `#[wasm_split(name, strict)]`, whose dependencies, the code of other
+    /// crates, may not be in the main module. Empty unless it is strict.
+    pub strict: Vec<String>,
+}
+
+/// Attributes by module name, ordered so that diagnostics about several modules
+/// are the same from one run to the next.
+pub type SplitModuleMetadata = BTreeMap<String, SplitModuleAttributes>;
+
+pub fn get_split_module_metadata(module: &InputModule) -> Result<SplitModuleMetadata> {
+    let mut metadata = SplitModuleMetadata::new();
+    for section in module
+        .custom_sections
+        .iter()
+        .filter(|section| section.name == METADATA_SECTION_NAME)
+    {
+        let data = std::str::from_utf8(section.data)?;
+        for record in data.lines() {
+            let mut parts = record.splitn(3, ' ');
+            let (Some(module_name), Some(key), Some(value)) =
+                (parts.next(), parts.next(), parts.next())
+            else {
+                bail!("Invalid split metadata record {record:?}");
+            };
+            let attributes = metadata.entry(module_name.to_string()).or_default();
+            match key {
+                "priority" => {
+                    let priority: Priority = value.parse()?;
+                    // If split points of the same module disagree, the most
+                    // urgent priority wins.
+                    attributes.priority = Some(
+                        attributes
+                            .priority
+                            .map_or(priority, |existing| existing.min(priority)),
+                    );
+                }
+                "table_slots" => {
+                    let table_slots: usize = value
+                        .parse()
+                        .map_err(|_| anyhow!("Invalid table slot count {value:?}"))?;
+                    // Split points of the same module share its reservation,
+                    // so repeating it on each of them doesn't add up.
+                    attributes.table_slots = attributes.table_slots.max(table_slots);
+                }
+                "alias" => {
+                    if !attributes.aliases.iter().any(|alias| alias == value) {
+                        attributes.aliases.push(value.to_string());
+                    }
+                }
+                "route" => {
+                    if !attributes.routes.iter().any(|route| route == value) {
+                        attributes.routes.push(value.to_string());
+                    }
+                }
+                "strict" => {
+                    if !attributes
+                        .strict
+                        .iter()
+                        .any(|crate_name| crate_name == value)
+                    {
+                        attributes.strict.push(value.to_string());
+                    }
+                }
+                _ => {
+                    return Err(anyhow!(
+                        "Unknown split metadata key {key:?} for module {module_name:?}"
+                    ));
+                }
+            }
+        }
+    }
+    Ok(metadata)
+}
+
+/// Total number of indirect function table slots reserved by all split
+/// modules, including those folded into the main module.
+pub fn get_reserved_table_slots(metadata: &SplitModuleMetadata) -> usize {
+    metadata
+        .values()
+        .map(|attributes| attributes.table_slots)
+        .sum()
+}
+
+/// The module that each alias declared with `with` stands for.
+pub fn get_module_aliases(metadata: &SplitModuleMetadata) -> BTreeMap<String, String> {
+    metadata
+        .iter()
+        .flat_map(|(module_name, attributes)| {
+            attributes
+                .aliases
+                .iter()
+                .map(move |alias| (alias.clone(), module_name.clone()))
+        })
+        .collect()
+}
+
+/// The modules whose split points are declared as the lazy views of each
+/// route, keyed by route path.
+pub fn get_declared_routes(metadata: &SplitModuleMetadata) -> BTreeMap<String, Vec<String>> {
+    let mut routes = BTreeMap::<String, Vec<String>>::new();
+    for (module_name, attributes) in metadata.iter() {
+        for route in attributes.routes.iter() {
+            routes
+                .entry(route.clone())
+                .or_default()
+                .push(module_name.clone());
+        }
+    }
+    for modules in routes.values_mut() {
+        modules.sort();
+    }
+    routes
+}
+
+/// Merges the split modules of each chunk of `[chunks]` in the config into a
+/// module of the chunk's name, which may be one of them, as if their split
+/// points were declared with `group`: the others become its aliases, and
+/// their attributes are merged into its own. Call after
+/// [`check_module_aliases`], which this would otherwise fail.
+pub fn merge_module_groups(
+    metadata: &mut SplitModuleMetadata,
+    split_points: &mut [SplitPoint],
+    groups: &BTreeMap<String, Vec<String>>,
+) -> Result<()> {
+    for (chunk_name, modules) in groups.iter() {
+        for module_name in modules.iter().filter(|name| *name != chunk_name) {
+            let mut found = false;
+            for split_point in split_points
+                .iter_mut()
+                .filter(|split_point| split_point.module_name == *module_name)
+            {
+                split_point.module_name.clone_from(chunk_name);
+                found = true;
+            }
+            if !found {
+                bail!("[chunks.{chunk_name}] refers to unknown split module {module_name:?}");
+            }
+            let merged = metadata.remove(module_name).unwrap_or_default();
+            let attributes = metadata.entry(chunk_name.clone()).or_default();
+            attributes.priority = match (attributes.priority, merged.priority) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+            // Each module registers its own callbacks.
+            attributes.table_slots += merged.table_slots;
+            for crate_name in merged.strict {
+                if !attributes.strict.contains(&crate_name) {
+                    attributes.strict.push(crate_name);
+                }
+            }
+            for alias in merged.aliases.into_iter().chain([module_name.clone()]) {
+                if !attributes.aliases.contains(&alias) {
+                    attributes.aliases.push(alias);
+                }
+            }
+            for route in merged.routes {
+                if !attributes.routes.contains(&route) {
+                    attributes.routes.push(route);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks that every alias stands for a single module, and that no split
+/// point puts its function into a module of the same name as an alias, which
+/// would mean that `with` is only given at some of the split points of that
+/// module.
+pub fn check_module_aliases(
+    metadata: &SplitModuleMetadata,
+    split_points: &[SplitPoint],
+) -> Result<()> {
+    let mut targets = BTreeMap::<&str, &str>::new();
+    for (module_name, attributes) in metadata.iter() {
+        for alias in attributes.aliases.iter() {
+            if let Some(other) = targets.insert(alias, module_name) {
+                bail!(
+                    "Split module {alias:?} is co-located with both {other:?} and \
+                     {module_name:?} by `with`"
+                );
+            }
+            if split_points
+                .iter()
+                .any(|split_point| split_point.module_name == *alias)
+            {
+                bail!(
+                    "Split module {alias:?} is co-located with {module_name:?} by `with` at \
+                     some of its split points, but not at all of them"
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    fn parse(records: &str) -> Result<SplitModuleMetadata> {
+        let mut wasm = wasm_encoder::Module::new();
+        wasm.section(&wasm_encoder::CustomSection {
+            name: METADATA_SECTION_NAME.into(),
+            data: records.as_bytes().into(),
+        });
+        let wasm = wasm.finish();
+        get_split_module_metadata(&InputModule::parse(&wasm)?)
+    }
+
+    fn split_point(module_name: &str) -> SplitPoint {
+        SplitPoint {
+            module_name: module_name.to_string(),
+            import: 0,
+            import_func: 0,
+            export: 0,
+            export_func: 0,
+        }
+    }
+
+    #[test]
+    fn merges_attributes_of_split_points() {
+        let metadata = parse(
+            "editor priority low\n\
+             editor priority critical\n\
+             editor table_slots 2\n\
+             editor table_slots 3\n\
+             editor alias toolbar\n\
+             editor alias toolbar\n\
+             editor route /edit\n\
+             settings priority low\n\
+             settings table_slots 1\n\
+             settings route /edit\n",
+        )
+        .unwrap();
+        let editor = &metadata["editor"];
+        assert_eq!(editor.priority, Some(Priority::Critical));
+        assert_eq!(editor.table_slots, 3);
+        assert_eq!(editor.aliases, ["toolbar"]);
+        assert_eq!(metadata["settings"].priority, Some(Priority::Low));
+        assert_eq!(get_reserved_table_slots(&metadata), 4);
+        assert_eq!(
+            get_module_aliases(&metadata),
+            BTreeMap::from([("toolbar".to_string(), "editor".to_string())])
+        );
+        assert_eq!(
+            get_declared_routes(&metadata),
+            BTreeMap::from([(
+                "/edit".to_string(),
+                vec!["editor".to_string(), "settings".to_string()]
+            )])
+        );
+
+        for (records, error) in [
+            ("editor priority urgent", "Invalid priority"),
+            ("editor table_slots many", "Invalid table slot count"),
+            ("editor color red", "Unknown split metadata key"),
+            ("editor", "Invalid split metadata record"),
+        ] {
+            let message = parse(records).unwrap_err().to_string();
+            assert!(message.contains(error), "{records}: {message}");
+        }
+    }
+
+    #[test]
+    fn merges_modules_of_config_chunks() {
+        let mut metadata = parse(
+            "editor priority low\n\
+             editor table_slots 2\n\
+             preview priority high\n\
+             preview table_slots 1\n\
+             preview alias thumbnails\n",
+        )
+        .unwrap();
+        let mut split_points = vec![split_point("editor"), split_point("preview")];
+        let groups = BTreeMap::from([(
+            "editor".to_string(),
+            vec!["editor".to_string(), "preview".to_string()],
+        )]);
+        merge_module_groups(&mut metadata, &mut split_points, &groups).unwrap();
+        assert!(split_points
+            .iter()
+            .all(|split_point| split_point.module_name == "editor"));
+        assert_eq!(metadata.keys().collect::<Vec<_>>(), ["editor"]);
+        let editor = &metadata["editor"];
+        assert_eq!(editor.priority, Some(Priority::High));
+        assert_eq!(editor.table_slots, 3);
+        assert_eq!(editor.aliases, ["thumbnails", "preview"]);
+        check_module_aliases(&metadata, &split_points).unwrap();
+
+        let groups = BTreeMap::from([("editor".to_string(), vec!["missing".to_string()])]);
+        let error = merge_module_groups(&mut metadata, &mut split_points, &groups).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("unknown split module \"missing\""));
+    }
+
+    #[test]
+    fn rejects_aliases_of_several_modules() {
+        let metadata = parse("editor alias shared\npreview alias shared\n").unwrap();
+        let error = check_module_aliases(&metadata, &[]).unwrap_err();
+        assert!(error.to_string().contains("both"), "{error}");
+
+        let metadata = parse("editor alias preview\n").unwrap();
+        let error = check_module_aliases(&metadata, &[split_point("preview")]).unwrap_err();
+        assert!(error.to_string().contains("not at all of them"), "{error}");
+    }
+}
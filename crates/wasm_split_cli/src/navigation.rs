@@ -0,0 +1,252 @@
+//! Navigation flows imported from analytics, used to co-locate the shared
+//! code of routes that users commonly visit one after another.
+//!
+//! The dataset counts the navigations from one page to another, either as
+//! CSV with `from,to,count` columns and an optional header, or as a JSON
+//! array of objects with these fields. Paths are attributed to the most
+//! specific route of the config that they are within, so that visits of
+//! `/admin/users/42` count as `/admin/users`.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+use crate::budget::is_within;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Transition {
+    pub from: String,
+    pub to: String,
+    pub count: u64,
+}
+
+pub fn read_transitions(path: &Path) -> Result<Vec<Transition>> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read navigation flows {path:?}"))?;
+    if path
+        .extension()
+        .is_some_and(|extension| extension == "json")
+    {
+        serde_json::from_str(&text)
+            .with_context(|| format!("Failed to parse navigation flows {path:?}"))
+    } else {
+        parse_csv(&text).with_context(|| format!("Failed to parse navigation flows {path:?}"))
+    }
+}
+
+fn parse_csv(text: &str) -> Result<Vec<Transition>> {
+    let mut transitions = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields = line
+            .split(',')
+            .map(|field| field.trim().trim_matches('"'))
+            .collect::<Vec<_>>();
+        let [from, to, count] = fields[..] else {
+            bail!(
+                "Line {}: expected the 3 columns from,to,count, found {}",
+                index + 1,
+                fields.len()
+            );
+        };
+        let Ok(count) = count.parse() else {
+            if transitions.is_empty() && index == 0 {
+                // Header row.
+                continue;
+            }
+            bail!("Line {}: invalid count {count:?}", index + 1);
+        };
+        transitions.push(Transition {
+            from: from.to_string(),
+            to: to.to_string(),
+            count,
+        });
+    }
+    Ok(transitions)
+}
+
+/// The most specific configured route that the visited `path` is within.
+fn resolve_route<'a>(routes: &'a BTreeMap<String, Vec<String>>, path: &str) -> Option<&'a str> {
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    routes
+        .keys()
+        .filter(|route| is_within(path, route))
+        .max_by_key(|route| route.trim_end_matches('/').len())
+        .map(String::as_str)
+}
+
+/// Groups of split modules that should share chunks, from the transitions
+/// between routes that account for at least `min_share` of the navigations
+/// away from their origin route.
+///
+/// The modules a route needs are grouped with those that the next route
+/// additionally needs, since these are the ones fetched on arrival.
+pub fn get_colocated_modules(
+    transitions: &[Transition],
+    routes: &BTreeMap<String, Vec<String>>,
+    min_share: f64,
+) -> Vec<Vec<String>> {
+    let mut counts = BTreeMap::<(&str, &str), u64>::new();
+    let mut totals = BTreeMap::<&str, u64>::new();
+    let mut unmatched = 0;
+    for transition in transitions {
+        let (Some(from), Some(to)) = (
+            resolve_route(routes, &transition.from),
+            resolve_route(routes, &transition.to),
+        ) else {
+            unmatched += transition.count;
+            continue;
+        };
+        if from == to {
+            continue;
+        }
+        *counts.entry((from, to)).or_default() += transition.count;
+        *totals.entry(from).or_default() += transition.count;
+    }
+    if unmatched > 0 {
+        println!("Ignoring {unmatched} navigations from or to paths outside the configured routes");
+    }
+
+    let mut groups = Vec::<BTreeSet<String>>::new();
+    for ((from, to), count) in counts {
+        let share = count as f64 / totals[from] as f64;
+        if share < min_share {
+            continue;
+        }
+        let from_modules = &routes[from];
+        let arriving_modules = routes[to]
+            .iter()
+            .filter(|module| !from_modules.contains(module))
+            .collect::<Vec<_>>();
+        if from_modules.is_empty() || arriving_modules.is_empty() {
+            continue;
+        }
+        println!(
+            "Co-locating the shared code of {from} and {to}: {percent:.0}% of the \
+             navigations from {from}",
+            percent = share * 100.0
+        );
+        let mut group = from_modules
+            .iter()
+            .chain(arriving_modules)
+            .cloned()
+            .collect::<BTreeSet<_>>();
+        // Merge with all existing groups that overlap the new one.
+        groups.retain(|existing| {
+            if existing.is_disjoint(&group) {
+                return true;
+            }
+            group.extend(existing.iter().cloned());
+            false
+        });
+        groups.push(group);
+    }
+    groups
+        .into_iter()
+        .filter(|group| group.len() > 1)
+        .map(|group| group.into_iter().collect())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{get_colocated_modules, parse_csv, read_transitions};
+    use crate::test_fixtures::{expected_no_std_app_result, temp_dir, try_split};
+
+    fn routes() -> BTreeMap<String, Vec<String>> {
+        [
+            ("/first", vec!["first"]),
+            ("/second", vec!["second"]),
+            ("/squares", vec!["squares"]),
+            ("/squares/all", vec!["squares", "first"]),
+        ]
+        .into_iter()
+        .map(|(route, modules)| {
+            let modules = modules.into_iter().map(String::from).collect();
+            (route.to_string(), modules)
+        })
+        .collect()
+    }
+
+    #[test]
+    fn reads_transitions_from_csv_and_json() {
+        let transitions = parse_csv("from,to,count\n\n/a, \"/b\",3\n").unwrap();
+        assert_eq!(transitions.len(), 1);
+        assert_eq!(
+            (
+                &*transitions[0].from,
+                &*transitions[0].to,
+                transitions[0].count
+            ),
+            ("/a", "/b", 3)
+        );
+        assert!(parse_csv("/a,/b,3\n/b,/c,many\n").is_err());
+        assert!(parse_csv("/a,/b\n").is_err());
+
+        let dir = temp_dir();
+        dir.write("flows.json", br#"[{"from": "/a", "to": "/b", "count": 3}]"#);
+        let transitions = read_transitions(&dir.dir.join("flows.json")).unwrap();
+        assert_eq!(transitions[0].count, 3);
+    }
+
+    #[test]
+    fn groups_modules_of_common_transitions() {
+        let transitions = parse_csv(
+            "/first/1,/squares?page=2,8\n/first,/second,2\n/elsewhere,/first,5\n\
+             /squares/all/3,/squares,9\n",
+        )
+        .unwrap();
+        // Only 20% of the navigations from /first go to /second, and the
+        // modules of /squares/all already include those of /squares.
+        assert_eq!(
+            get_colocated_modules(&transitions, &routes(), 0.25),
+            [["first", "squares"]]
+        );
+        assert_eq!(
+            get_colocated_modules(&transitions, &routes(), 0.1),
+            [["first", "second", "squares"]]
+        );
+        assert!(get_colocated_modules(&transitions, &routes(), 0.9).is_empty());
+    }
+
+    #[test]
+    fn colocates_shared_code_along_transitions() {
+        let config = "[routes]\n\"/first\" = [\"first\"]\n\"/second\" = [\"second\"]\n\
+                      \"/squares\" = [\"squares\"]\n";
+        let (output, result) = try_split("no_std_app.wasm", config, &[]);
+        result.unwrap();
+        assert!(output
+            .wasm_files()
+            .contains(&"first_second.wasm".to_string()));
+
+        let flows = temp_dir();
+        flows.write(
+            "flows.csv",
+            b"from,to,count\n/first/1,/squares,8\n/first,/second,2\n",
+        );
+        let flows = flows.dir.join("flows.csv");
+        let (output, result) = try_split(
+            "no_std_app.wasm",
+            config,
+            &["--navigation-flows", flows.to_str().unwrap()],
+        );
+        result.unwrap();
+        // The code that `first` shares with `second` is fetched along with
+        // `first` on the way to `squares`.
+        let files = output.wasm_files();
+        assert!(files.contains(&"first_second_squares.wasm".to_string()));
+        assert!(!files.contains(&"first_second.wasm".to_string()));
+        if let Some(result) = output.run_no_std_app(3) {
+            assert_eq!(result, expected_no_std_app_result(3));
+        }
+    }
+}
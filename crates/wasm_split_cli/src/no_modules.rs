@@ -0,0 +1,162 @@
+//! `--target no-modules`: the loader as a classic script, for apps built with
+//! wasm-bindgen's `no-modules` target, such as the content scripts of
+//! browser extensions and embedded webviews without ES modules.
+//!
+//! The loader is wrapped in a function whose exports become the properties
+//! of the global [`LOADER_GLOBAL`], and which looks up the main module's glue
+//! as the global that wasm-bindgen's `--no-modules-global` names once it
+//! needs it. The glue reads the loader's global as it runs, so the loader's
+//! script goes first:
+//!
+//! ```html
+//! <script src="pkg/__wasm_split.js"></script>
+//! <script src="pkg/main.js"></script>
+//! <script>wasm_bindgen({ module_or_path: "pkg/main_bg.wasm" }).then(...)</script>
+//! ```
+//!
+//! URLs are resolved against the loader's own, `document.currentScript`,
+//! which content scripts have none of; they set `WASM_SPLIT_SCRIPT_URL`
+//! instead, e.g. to `chrome.runtime.getURL("pkg/__wasm_split.js")`. Workers
+//! of `#[wasm_split(..., worker)]` functions run the loader as a classic
+//! worker script. wasm-bindgen `require`s the loader in the glue it
+//! generates for this target, which `wasm-split no-modules-glue` replaces by
+//! the global.
+
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+
+/// Global holding the exports of the classic loader.
+pub const LOADER_GLOBAL: &str = "__wasm_split";
+
+/// Kind of JS that the loader is written as.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Target {
+    /// An ES module, as for wasm-bindgen's `web` and `bundler` targets.
+    #[default]
+    Web,
+    /// A classic script; see `no_modules.rs`.
+    NoModules,
+    /// An ES module for Node, which reads the files of `file:` URLs; see
+    /// `node.rs`.
+    Node,
+}
+
+/// `loader` as a classic script, getting the glue from `glue_global`, or
+/// as written with `standalone.js` for inputs without wasm-bindgen if
+/// `None`.
+pub fn classic_loader(loader: &str, glue_global: Option<&str>) -> Result<String> {
+    let mut body = loader.to_string();
+    if let Some(glue_global) = glue_global {
+        let Some(end) = body
+            .strip_prefix("import * as mainGlue from ")
+            .and_then(|rest| rest.find('\n'))
+        else {
+            bail!("The loader does not start with the import of the glue");
+        };
+        body.replace_range(
+            ..end + "import * as mainGlue from \n".len(),
+            &format!(
+                "// Defined by the glue's script, which runs after this one.\n\
+                 const mainGlue = new Proxy({{}}, {{ get: (_, name) => {glue_global}[name] }});\n"
+            ),
+        );
+    }
+    body = body
+        // The worker gets the imports of its main module from the loader.
+        .replace("await import(import.meta.url)", LOADER_GLOBAL)
+        .replace("import.meta.url", "SCRIPT_URL")
+        .replace(
+            "new Worker(getWorkerUrl(), { type: \"module\" })",
+            "new Worker(getWorkerUrl())",
+        );
+
+    let mut exports = Vec::new();
+    let mut script = String::with_capacity(body.len());
+    for line in body.split_inclusive('\n') {
+        let Some(declaration) = line.strip_prefix("export ") else {
+            if line.starts_with("import ") {
+                bail!("The loader imports a module: {line}");
+            }
+            script.push_str(line);
+            continue;
+        };
+        let name = ["async function ", "function ", "const "]
+            .iter()
+            .find_map(|keyword| declaration.strip_prefix(keyword))
+            .and_then(|rest| {
+                rest.split(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '$'))
+                    .next()
+            })
+            .with_context(|| format!("Unexpected export of the loader: {line}"))?;
+        exports.push(name.to_string());
+        script.push_str(declaration);
+    }
+    Ok(format!(
+        "// The wasm-split loader as a classic script, for wasm-bindgen's `no-modules`\n\
+         // target. Its exports are the properties of `{LOADER_GLOBAL}`.\n\
+         var {LOADER_GLOBAL} = (() => {{\n\
+         \"use strict\";\n\
+         const SCRIPT_URL =\n  \
+           globalThis.WASM_SPLIT_SCRIPT_URL ??\n  \
+           globalThis.document?.currentScript?.src ??\n  \
+           globalThis.location.href;\n\
+         {script}\
+         return {{ {} }};\n\
+         }})();\n",
+        exports.join(", ")
+    ))
+}
+
+/// Replaces the `require` of `loader_module` in the glue that wasm-bindgen
+/// generated for the `no-modules` target by the global of the loader, or
+/// returns `None` if it has none.
+pub fn fix_glue(glue: &str, loader_module: &str) -> Option<String> {
+    let require = format!("require({})", serde_json::to_string(loader_module).unwrap());
+    glue.contains(&require)
+        .then(|| glue.replace(&require, LOADER_GLOBAL))
+}
+
+/// `wasm-split no-modules-glue`: [`fix_glue`] of the glue at `glue_path`, in
+/// place.
+pub fn run(glue_path: &Path, loader_module: &str) -> Result<()> {
+    let glue = std::fs::read_to_string(glue_path)
+        .with_context(|| format!("Failed to read {}", glue_path.display()))?;
+    let Some(glue) = fix_glue(&glue, loader_module) else {
+        bail!(
+            "{} does not require {loader_module:?}; was it generated by wasm-bindgen with \
+             `--target no-modules` for a split main module?",
+            glue_path.display()
+        );
+    };
+    std::fs::write(glue_path, glue)
+        .with_context(|| format!("Failed to write {}", glue_path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_fixtures::split;
+
+    #[test]
+    fn writes_the_loader_as_a_classic_script() {
+        let output = split("no_std_app.wasm", &["--target", "no-modules"]);
+        let loader = String::from_utf8(output.read("__wasm_split.js")).unwrap();
+        assert!(loader.starts_with("// The wasm-split loader as a classic script"));
+        assert!(!loader.contains("import.meta"));
+        assert!(!loader.lines().any(|line| line.starts_with("export ")));
+        assert!(loader.contains("\nreturn { instantiateMain, "));
+        if let Some(result) = output.run_no_std_app_classic(4) {
+            assert_eq!(result, crate::test_fixtures::expected_no_std_app_result(4));
+        }
+    }
+
+    #[test]
+    fn replaces_the_require_of_the_loader() {
+        let glue = "    const import1 = require(\"./__wasm_split.js\");\n";
+        assert_eq!(
+            super::fix_glue(glue, "./__wasm_split.js").unwrap(),
+            "    const import1 = __wasm_split;\n"
+        );
+        assert!(super::fix_glue(glue, "../__wasm_split.js").is_none());
+    }
+}
@@ -0,0 +1,71 @@
+// Put at the top of the loader with `--target node`, for tests and
+// server-side rendering under Node 18 or later, whose `fetch` does not read
+// `file:` URLs. The loader's requests for those, of chunks, the manifest and
+// the main module next to it, are read from the filesystem instead, and
+// checked against their `integrity` as browsers do; others go to the global
+// `fetch`. So are those of the synchronous requests of chunks called through
+// their table slots with `--lazy-indirect-calls`, for which Node has no
+// `XMLHttpRequest`.
+import { createHash as nodeCreateHash } from "node:crypto";
+import { readFileSync as nodeReadFileSync } from "node:fs";
+import { readFile as nodeReadFile } from "node:fs/promises";
+import { fileURLToPath as nodeFileURLToPath } from "node:url";
+
+async function fetch(resource, options = {}) {
+  const url = new URL(resource);
+  if (url.protocol !== "file:") return globalThis.fetch(resource, options);
+  const path = new URL(url);
+  path.search = "";
+  let body;
+  try {
+    body = await nodeReadFile(nodeFileURLToPath(path));
+  } catch (e) {
+    if (e.code !== "ENOENT") throw new TypeError(`Failed to read ${url}: ${e}`);
+    return new Response(null, { status: 404, statusText: "Not Found" });
+  }
+  const { integrity } = options;
+  if (
+    integrity &&
+    !integrity.split(/\s+/).some((metadata) => {
+      const [algorithm, digest] = metadata.split("-", 2);
+      return nodeCreateHash(algorithm).update(body).digest("base64") === digest;
+    })
+  ) {
+    throw new TypeError(`Integrity mismatch for ${url}`);
+  }
+  const response = new Response(body, {
+    headers: {
+      "content-type": path.pathname.endsWith(".wasm")
+        ? "application/wasm"
+        : "application/json",
+    },
+  });
+  // Which the loader resolves the URLs of the manifest against.
+  Object.defineProperty(response, "url", { value: url.href });
+  return response;
+}
+
+class XMLHttpRequest {
+  open(method, url) {
+    this.url = new URL(url);
+  }
+
+  overrideMimeType() {}
+
+  send() {
+    if (this.url.protocol !== "file:") {
+      throw new TypeError(`Cannot request ${this.url} synchronously`);
+    }
+    const path = new URL(this.url);
+    path.search = "";
+    try {
+      // One character per byte, as `overrideMimeType` has it in browsers.
+      this.responseText = nodeReadFileSync(nodeFileURLToPath(path)).toString("latin1");
+      this.status = 200;
+    } catch (e) {
+      if (e.code !== "ENOENT") throw new TypeError(`Failed to read ${this.url}: ${e}`);
+      this.status = 404;
+    }
+  }
+}
+
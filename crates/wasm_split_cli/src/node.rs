@@ -0,0 +1,34 @@
+//! `--target node`: the loader for Node, such as for integration tests of a
+//! split app and for rendering it on a Node server, which reads the files of
+//! its `file:` URLs from the filesystem; see `node.js`. The output directory
+//! is used in place:
+//!
+//! ```js
+//! import { instantiateMain } from "./pkg/__wasm_split.js";
+//!
+//! const instance = await instantiateMain({ env: { ... } });
+//! ```
+//!
+//! With wasm-bindgen, the app initializes the glue with `initSync` and the
+//! bytes of the main module, as wasm-bindgen's `web` target has it fetch the
+//! module otherwise.
+
+/// `loader` for Node.
+pub fn node_loader(loader: &str) -> String {
+    format!("{}{loader}", include_str!("node.js"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_fixtures::split;
+
+    #[test]
+    fn reads_chunks_from_the_filesystem() {
+        let output = split("no_std_app.wasm", &["--target", "node"]);
+        let loader = String::from_utf8(output.read("__wasm_split.js")).unwrap();
+        assert!(loader.starts_with("// Put at the top of the loader with `--target node`"));
+        if let Some(result) = output.run_no_std_app_without_fetch(4) {
+            assert_eq!(result, crate::test_fixtures::expected_no_std_app_result(4));
+        }
+    }
+}
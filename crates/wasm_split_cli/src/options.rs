@@ -0,0 +1,221 @@
+//! Typed options and output of [`split_wasm`], for build tools, proc-macros
+//! and test harnesses that split a module they hold in memory.
+//!
+//! ```ignore
+//! let output = wasm_split_cli::split_wasm(
+//!     &std::fs::read("app.wasm")?,
+//!     &SplitOptions::new().compress(&[Encoding::Gzip]),
+//! )?;
+//! let main = output.chunk("main").unwrap();
+//! for chunk in output.manifest.chunks.iter() {
+//!     println!("{}: {} bytes", chunk.name, output.files[Path::new(&chunk.file)].len());
+//! }
+//! ```
+//!
+//! Each option is that of the command line tool of the same name, which
+//! documents it, and options without a method here can be passed with
+//! [`SplitOptions::arg`]. The split still prints its report to stdout, but
+//! everything it writes is in the [`SplitOutput`].
+
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Result};
+use clap::{Parser, ValueEnum};
+
+use crate::{compress::Encoding, manifest::Manifest, sink::MemorySink, Cli};
+
+#[derive(Debug, Clone, Default)]
+pub struct SplitOptions {
+    args: Vec<OsString>,
+}
+
+impl SplitOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Options of `wasm-split` other than its input and output, such as
+    /// `--treemap`, in the form the command line tool takes them.
+    pub fn arg(mut self, arg: impl Into<OsString>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    fn option(self, name: &str, value: impl Into<OsString>) -> Self {
+        self.arg(name).arg(value)
+    }
+
+    fn flag(self, name: &str, enabled: bool) -> Self {
+        if enabled {
+            self.arg(name)
+        } else {
+            self
+        }
+    }
+
+    /// The config file, rather than `wasm-split.toml` in the working
+    /// directory if it exists.
+    pub fn config(self, path: impl AsRef<Path>) -> Self {
+        self.option("--config", path.as_ref())
+    }
+
+    pub fn fold_threshold(self, bytes: usize) -> Self {
+        self.option("--fold-threshold", bytes.to_string())
+    }
+
+    pub fn duplicate_threshold(self, bytes: usize) -> Self {
+        self.option("--duplicate-threshold", bytes.to_string())
+    }
+
+    pub fn min_chunk_size(self, bytes: usize) -> Self {
+        self.option("--min-chunk-size", bytes.to_string())
+    }
+
+    pub fn max_chunks(self, count: usize) -> Self {
+        self.option("--max-chunks", count.to_string())
+    }
+
+    pub fn auto_split(self, crate_name: &str) -> Self {
+        self.option("--auto-split", crate_name)
+    }
+
+    pub fn table_only(self, table_only: bool) -> Self {
+        self.flag("--table-only", table_only)
+    }
+
+    pub fn guard_calls(self, guard_calls: bool) -> Self {
+        self.flag("--guard-calls", guard_calls)
+    }
+
+    pub fn lint(self, lint: bool) -> Self {
+        self.flag("--lint", lint)
+    }
+
+    pub fn startup_export(self, name: &str) -> Self {
+        self.option("--startup-export", name)
+    }
+
+    pub fn compress(self, encodings: &[Encoding]) -> Self {
+        let encodings = encodings
+            .iter()
+            .filter_map(|encoding| Some(encoding.to_possible_value()?.get_name().to_string()))
+            .collect::<Vec<_>>();
+        self.option("--compress", encodings.join(","))
+    }
+
+    pub fn signing_key(self, path: impl AsRef<Path>) -> Self {
+        self.option("--signing-key", path.as_ref())
+    }
+
+    pub fn asset_version(self, version: &str) -> Self {
+        self.option("--asset-version", version)
+    }
+
+    /// Where to write the output of `key` of `[output]`, such as `main`.
+    pub fn output_path(self, key: &str, path: impl AsRef<Path>) -> Self {
+        let mut value = OsString::from(format!("{key}="));
+        value.push(path.as_ref());
+        self.option("--output-path", value)
+    }
+
+    pub fn cache(self, dir: impl AsRef<Path>) -> Self {
+        self.option("--cache", dir.as_ref())
+    }
+
+    pub fn profile(self, path: impl AsRef<Path>) -> Self {
+        self.option("--profile", path.as_ref())
+    }
+
+    fn to_cli(&self) -> Result<Cli> {
+        // Neither is read: the input is given as bytes, and the output
+        // returned.
+        let cli = Cli::try_parse_from(
+            ["wasm-split", "input.wasm", "output"]
+                .into_iter()
+                .map(OsString::from)
+                .chain(self.args.iter().cloned()),
+        )?;
+        if cli.command.is_some() {
+            bail!("The options of a split must not include a subcommand");
+        }
+        Ok(cli)
+    }
+}
+
+/// Everything that a split writes.
+#[derive(Debug, Clone)]
+pub struct SplitOutput {
+    /// The files of the build, by path relative to the output directory, or
+    /// to its subdirectory of the asset version if given.
+    pub files: BTreeMap<PathBuf, Vec<u8>>,
+    pub manifest: Manifest,
+}
+
+impl SplitOutput {
+    /// The contents of the chunk `name` of the manifest, such as `main`.
+    pub fn chunk(&self, name: &str) -> Option<&[u8]> {
+        let chunk = self
+            .manifest
+            .chunks
+            .iter()
+            .find(|chunk| chunk.name == name)?;
+        self.files.get(Path::new(&chunk.file)).map(Vec::as_slice)
+    }
+}
+
+/// Splits the module `input_wasm` with `options`.
+pub fn split_wasm(input_wasm: &[u8], options: &SplitOptions) -> Result<SplitOutput> {
+    let cli = options.to_cli()?;
+    let mut sink = MemorySink::default();
+    crate::split_module(&cli, input_wasm, &mut sink)?;
+    let Some(manifest) = sink.manifest else {
+        bail!("The split did not finish");
+    };
+    Ok(SplitOutput {
+        files: sink.files,
+        manifest,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{fixture_path, split};
+
+    #[test]
+    fn splits_bytes_like_the_command_line_tool() {
+        let input = std::fs::read(fixture_path("no_std_app.wasm")).unwrap();
+        let output = split_wasm(
+            &input,
+            &SplitOptions::new()
+                .fold_threshold(0)
+                .compress(&[Encoding::Gzip])
+                .output_path("loader", "js/loader.js"),
+        )
+        .unwrap();
+        let on_disk = split(
+            "no_std_app.wasm",
+            &["--compress", "gzip", "--output-path", "loader=js/loader.js"],
+        );
+        assert_eq!(
+            serde_json::to_value(&output.manifest).unwrap(),
+            on_disk.manifest()
+        );
+        for chunk in output.manifest.chunks.iter() {
+            assert_eq!(
+                output.chunk(&chunk.name).unwrap(),
+                on_disk.read(&chunk.file)
+            );
+        }
+        assert!(output.files.contains_key(Path::new("main.wasm.gz")));
+        assert!(output.files.contains_key(Path::new("js/loader.js")));
+        assert_eq!(output.chunk("nonexistent"), None);
+
+        let error = split_wasm(&input, &SplitOptions::new().arg("--no-such-option")).unwrap_err();
+        assert!(format!("{error}").contains("--no-such-option"));
+    }
+}
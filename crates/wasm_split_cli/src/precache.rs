@@ -0,0 +1,125 @@
+//! Precache list of the build, written to [`PRECACHE_FILENAME`] for offline
+//! capable apps to download every chunk when their service worker installs,
+//! rather than as each one is first needed.
+//!
+//! Entries are in the format of Workbox's `precacheAndRoute`, with URLs
+//! relative to the output directory, which `modifyURLPrefix` of workbox-build
+//! or the service worker itself resolves:
+//!
+//! ```js
+//! const response = await fetch("/pkg/wasm-split-precache.json");
+//! const entries = (await response.json()).map((entry) => ({
+//!   ...entry,
+//!   url: new URL(entry.url, new URL("/pkg/", self.location.href)).href,
+//! }));
+//! workbox.precaching.precacheAndRoute(entries);
+//! ```
+//!
+//! Chunk URLs carry the build ID, as requested by the loader, so they need no
+//! revision. The other files keep their URLs across builds, and are revised
+//! with the build ID, or with a digest of the loader for the loader, whose
+//! options are not part of the manifest. Service workers without Workbox can
+//! use `precacheAll` of `sw.js` instead.
+
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::{
+    config::OutputPaths,
+    manifest::{sha256_hex, Manifest},
+};
+
+pub const PRECACHE_FILENAME: &str = "wasm-split-precache.json";
+
+#[derive(Debug, Serialize)]
+pub struct PrecacheEntry {
+    pub url: String,
+    pub revision: Option<String>,
+    /// Checked by Workbox when downloading the file, as by the loader.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub integrity: Option<String>,
+}
+
+/// Entries for every file that the app loads from the output directory:
+/// the main module and its glue, the `loader` script, the manifest and all
+/// other chunks.
+pub fn get_precache_list(
+    manifest: &Manifest,
+    output: &OutputPaths,
+    uses_wasm_bindgen: bool,
+    loader: &[u8],
+) -> Vec<PrecacheEntry> {
+    let url = |path: &Path| {
+        path.components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+    };
+    let revised = |path: &Path, revision: &str| PrecacheEntry {
+        url: url(path),
+        revision: Some(revision.to_string()),
+        integrity: None,
+    };
+    let mut entries = Vec::new();
+    if uses_wasm_bindgen {
+        entries.push(revised(
+            &output.main.with_extension("js"),
+            &manifest.build_id,
+        ));
+        entries.push(revised(&output.main_bg(), &manifest.build_id));
+    } else {
+        entries.push(PrecacheEntry {
+            integrity: manifest
+                .chunks
+                .iter()
+                .find(|chunk| chunk.name == "main")
+                .and_then(|chunk| chunk.integrity.clone()),
+            ..revised(&output.main, &manifest.build_id)
+        });
+    }
+    entries.push(revised(&output.loader, &sha256_hex(loader)));
+    entries.push(revised(&output.manifest, &manifest.build_id));
+    for chunk in manifest.chunks.iter().filter(|chunk| chunk.name != "main") {
+        entries.push(PrecacheEntry {
+            url: format!("{}?build={}", chunk.file, manifest.build_id),
+            revision: None,
+            integrity: chunk.integrity.clone(),
+        });
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{manifest::sha256_hex, test_fixtures::split};
+
+    #[test]
+    fn lists_every_file_the_app_loads() {
+        let output = split("no_std_app.wasm", &[]);
+        let manifest = output.manifest();
+        let build_id = manifest["build_id"].as_str().unwrap();
+        let entries: Vec<serde_json::Value> =
+            serde_json::from_slice(&output.read("wasm-split-precache.json")).unwrap();
+        let chunks = manifest["chunks"].as_array().unwrap();
+        assert_eq!(entries.len(), chunks.len() + 2);
+
+        assert_eq!(entries[0]["url"], "main.wasm");
+        assert_eq!(entries[0]["revision"], build_id);
+        assert_eq!(entries[0]["integrity"], chunks[0]["integrity"]);
+        assert_eq!(entries[1]["url"], "__wasm_split.js");
+        assert_eq!(
+            entries[1]["revision"],
+            sha256_hex(&output.read("__wasm_split.js"))
+        );
+        assert_eq!(entries[2]["url"], "wasm-split-manifest.json");
+        for (entry, chunk) in entries[3..].iter().zip(&chunks[1..]) {
+            assert_eq!(
+                entry["url"],
+                format!("{}?build={build_id}", chunk["file"].as_str().unwrap())
+            );
+            assert!(entry["revision"].is_null());
+            assert_eq!(entry["integrity"], chunk["integrity"]);
+        }
+    }
+}
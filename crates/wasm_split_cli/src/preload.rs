@@ -0,0 +1,315 @@
+//! Per-route preload hints, written to [`PRELOAD_FILENAME`] for servers to
+//! send as `Link` headers (and thus HTTP 103 Early Hints), or render as
+//! `<link>` tags, when serving the page of a route.
+//!
+//! With `--preload-html`, [`html_tags`] also writes the tags that start
+//! downloading the main module and the loader, along with any
+//! `--preload-chunk`s, to [`PRELOAD_HTML_FILENAME`], for static pages to
+//! include; `--patch-html` puts them into a page directly, between
+//! [`HTML_START_MARKER`] and [`HTML_END_MARKER`].
+
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+
+use crate::{config::OutputPaths, manifest::Manifest, no_modules::Target};
+
+pub const PRELOAD_FILENAME: &str = "wasm-split-preload.json";
+pub const PRELOAD_HTML_FILENAME: &str = "wasm-split-preload.html";
+pub const HTML_START_MARKER: &str = "<!-- wasm-split preload -->";
+pub const HTML_END_MARKER: &str = "<!-- /wasm-split preload -->";
+
+#[derive(Debug, Default, Serialize)]
+pub struct Preload {
+    /// Chunk URLs needed by each route, relative to the output directory and
+    /// in the order they are instantiated.
+    ///
+    /// URLs include the same `build` query parameter as those requested by
+    /// the loader, so that preloaded responses can be reused.
+    pub routes: BTreeMap<String, Vec<String>>,
+    /// Subresource Integrity metadata of the URLs of `routes`, which preload
+    /// tags must carry for the loader's requests, made with the same, to
+    /// reuse their responses. Empty for signed manifests, whose chunks the
+    /// loader requests without.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub integrity: BTreeMap<String, String>,
+    /// Split modules of each route, as in the manifest, for servers to hand
+    /// to `wasm_split::hydrate_preload` on the client of the page they
+    /// rendered.
+    pub modules: BTreeMap<String, Vec<String>>,
+}
+
+impl Preload {
+    pub fn new(manifest: &Manifest, signed: bool) -> Self {
+        let mut routes = BTreeMap::new();
+        let mut integrity = BTreeMap::new();
+        for (route, modules) in manifest.routes.iter() {
+            let mut names = Vec::new();
+            for name in modules {
+                add_with_dependencies(manifest, name, &mut names);
+            }
+            let urls = names
+                .into_iter()
+                .filter_map(|name| manifest.chunks.iter().find(|chunk| chunk.name == name))
+                .map(|chunk| {
+                    let url = chunk_url(manifest, &chunk.file);
+                    if let Some(hash) = chunk.integrity.as_ref().filter(|_| !signed) {
+                        integrity.insert(url.clone(), hash.clone());
+                    }
+                    url
+                })
+                .collect();
+            routes.insert(route.clone(), urls);
+        }
+        Self {
+            routes,
+            integrity,
+            modules: manifest.routes.clone(),
+        }
+    }
+}
+
+/// URL of a chunk `file`, as the loader requests it.
+fn chunk_url(manifest: &Manifest, file: &str) -> String {
+    format!("{file}?build={}", manifest.build_id)
+}
+
+/// `path`, relative to the output directory, as a URL relative to it.
+fn url_path(path: &Path) -> String {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// `<link>` tags that start downloading the main module, its JS glue and the
+/// loader, and the chunks of `chunks` with their dependencies, from below
+/// `base_url`, the URL of the output directory. Each matches the request
+/// that it anticipates, with the same `integrity` if any, so that the
+/// browser reuses its response. Scripts of the `NoModules` target are
+/// preloaded as classic scripts.
+pub fn html_tags(
+    manifest: &Manifest,
+    output: &OutputPaths,
+    uses_wasm_bindgen: bool,
+    target: Target,
+    chunks: &[String],
+    signed: bool,
+    base_url: &str,
+) -> Result<String> {
+    let base_url = match base_url {
+        "" => String::new(),
+        url if url.ends_with('/') => url.to_string(),
+        url => format!("{url}/"),
+    };
+    let wasm_tag = |url: &str, integrity: Option<&str>| {
+        let integrity = integrity
+            .map(|integrity| format!(" integrity=\"{}\"", escape_attribute(integrity)))
+            .unwrap_or_default();
+        format!(
+            "<link rel=\"preload\" href=\"{}\" as=\"fetch\" type=\"application/wasm\" \
+             crossorigin{integrity}>",
+            escape_attribute(&format!("{base_url}{url}"))
+        )
+    };
+    let module_tag = |path: &Path| {
+        let href = escape_attribute(&format!("{base_url}{}", url_path(path)));
+        match target {
+            Target::Web | Target::Node => {
+                format!("<link rel=\"modulepreload\" href=\"{href}\">")
+            }
+            Target::NoModules => format!("<link rel=\"preload\" href=\"{href}\" as=\"script\">"),
+        }
+    };
+
+    let mut tags = Vec::new();
+    if uses_wasm_bindgen {
+        // The glue requests the module itself, without `integrity`.
+        tags.push(module_tag(&output.main.with_extension("js")));
+        tags.push(module_tag(&output.loader));
+        tags.push(wasm_tag(&url_path(&output.main_bg()), None));
+    } else {
+        let main = manifest.chunks.iter().find(|chunk| chunk.name == "main");
+        tags.push(module_tag(&output.loader));
+        tags.push(wasm_tag(
+            &url_path(&output.main),
+            main.and_then(|chunk| chunk.integrity.as_deref()),
+        ));
+    }
+    let mut names = Vec::new();
+    for name in chunks {
+        if !manifest.chunks.iter().any(|chunk| &chunk.name == name) {
+            bail!(
+                "--preload-chunk {name} is not a chunk; chunks are {}",
+                manifest
+                    .chunks
+                    .iter()
+                    .map(|chunk| chunk.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        add_with_dependencies(manifest, name, &mut names);
+    }
+    for chunk in names
+        .into_iter()
+        .filter(|&name| name != "main")
+        .filter_map(|name| manifest.chunks.iter().find(|chunk| chunk.name == name))
+    {
+        tags.push(wasm_tag(
+            &chunk_url(manifest, &chunk.file),
+            chunk.integrity.as_deref().filter(|_| !signed),
+        ));
+    }
+    Ok(tags.join("\n") + "\n")
+}
+
+fn escape_attribute(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+}
+
+/// `html` with `tags` at the end of its `<head>`, or in place of those that
+/// an earlier call put there.
+pub fn patch_html(html: &str, tags: &str) -> Result<String> {
+    let block = format!("{HTML_START_MARKER}\n{tags}{HTML_END_MARKER}");
+    if let Some(start) = html.find(HTML_START_MARKER) {
+        let Some(end) = html[start..].find(HTML_END_MARKER) else {
+            bail!("The page has a {HTML_START_MARKER} without {HTML_END_MARKER}");
+        };
+        let end = start + end + HTML_END_MARKER.len();
+        return Ok(format!("{}{block}{}", &html[..start], &html[end..]));
+    }
+    let Some(head_end) = html.find("</head>") else {
+        bail!("The page has no </head> to add preload tags to");
+    };
+    Ok(format!(
+        "{}{block}\n{}",
+        &html[..head_end],
+        &html[head_end..]
+    ))
+}
+
+/// Appends `name` to `names` after the chunks it depends on, unless already
+/// present. Folded modules are skipped, since they have no chunk of their own.
+fn add_with_dependencies<'a>(manifest: &'a Manifest, name: &'a str, names: &mut Vec<&'a str>) {
+    if names.contains(&name) {
+        return;
+    }
+    let Some(chunk) = manifest.chunks.iter().find(|chunk| chunk.name == name) else {
+        return;
+    };
+    for dependency in chunk.dependencies.iter() {
+        add_with_dependencies(manifest, dependency, names);
+    }
+    names.push(name);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{expected_no_std_app_result, split, try_split};
+
+    #[test]
+    fn renders_tags_matching_the_loaders_requests() {
+        let output = split("no_std_app.wasm", &[]);
+        let manifest: Manifest = serde_json::from_value(output.manifest()).unwrap();
+        let chunk = manifest
+            .chunks
+            .iter()
+            .find(|chunk| !chunk.dependencies.is_empty())
+            .unwrap();
+        let tags = html_tags(
+            &manifest,
+            &OutputPaths::default(),
+            false,
+            Target::Web,
+            std::slice::from_ref(&chunk.name),
+            false,
+            "/pkg",
+        )
+        .unwrap();
+        let lines = tags.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[0],
+            r#"<link rel="modulepreload" href="/pkg/__wasm_split.js">"#
+        );
+        assert!(lines[1].starts_with(r#"<link rel="preload" href="/pkg/main.wasm" as="fetch""#));
+        // Dependencies first, as in the order that they are loaded.
+        assert_eq!(lines.len(), 3 + chunk.dependencies.len());
+        assert!(lines.last().unwrap().contains(&format!(
+            "href=\"/pkg/{}?build={}\" as=\"fetch\" type=\"application/wasm\" crossorigin \
+             integrity=\"{}\"",
+            chunk.file,
+            manifest.build_id,
+            chunk.integrity.as_ref().unwrap()
+        )));
+
+        let classic = html_tags(
+            &manifest,
+            &OutputPaths::default(),
+            false,
+            Target::NoModules,
+            &[],
+            false,
+            "/pkg",
+        )
+        .unwrap();
+        assert!(
+            classic.starts_with(r#"<link rel="preload" href="/pkg/__wasm_split.js" as="script">"#)
+        );
+
+        let error = html_tags(
+            &manifest,
+            &OutputPaths::default(),
+            false,
+            Target::NoModules,
+            &["nonexistent".to_string()],
+            false,
+            "",
+        )
+        .unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("--preload-chunk nonexistent is not a chunk"));
+    }
+
+    #[test]
+    fn lists_modules_to_hydrate_of_routes() {
+        let (output, result) = try_split(
+            "no_std_app.wasm",
+            "[routes]\n\"/\" = []\n\"/both\" = [\"first\", \"second\"]\n",
+            &[],
+        );
+        result.unwrap();
+        let json = String::from_utf8(output.read("wasm-split-preload.json")).unwrap();
+        let hints = wasm_split_server::PreloadHints::from_json(&json, "/pkg").unwrap();
+        assert_eq!(
+            hints.hydrate_modules("/both/42").unwrap(),
+            ["first", "second"]
+        );
+        assert_eq!(hints.hydrate_modules("/about").unwrap(), [] as [String; 0]);
+
+        // The client loads them before calling into them.
+        if let Some(result) = output.run_no_std_app_export("run_hydrating", 3) {
+            assert_eq!(result, 3 + 1000 + expected_no_std_app_result(3));
+        }
+    }
+
+    #[test]
+    fn patches_head_of_page_once() {
+        let html = "<html><head><title>App</title></head><body></body></html>";
+        let patched = patch_html(html, "<link a>\n").unwrap();
+        assert_eq!(
+            patched,
+            "<html><head><title>App</title><!-- wasm-split preload -->\n<link a>\n\
+             <!-- /wasm-split preload -->\n</head><body></body></html>"
+        );
+        let repatched = patch_html(&patched, "<link b>\n").unwrap();
+        assert_eq!(repatched, patched.replace("<link a>", "<link b>"));
+        assert!(patch_html("<p>", "").is_err());
+    }
+}
@@ -0,0 +1,340 @@
+//! Runtime profiles of split function calls, used to assign code to chunks by
+//! how the app is really used rather than by its `#[wasm_split]` attributes
+//! alone.
+//!
+//! With the `profile` feature of `wasm_split`, the loader records the first
+//! call and the number of calls of each split function on a page, which
+//! `getProfile()` of the loader script, or `profile()` of its entry in
+//! `window.__WASM_SPLIT__`, returns as one session:
+//!
+//! ```json
+//! {"build_id": "…", "calls": [{"module": "view_b", "function": "view_b", "first_call_ms": 812.5, "count": 3}]}
+//! ```
+//!
+//! A profile is a JSON array of such sessions, or one per line, as collected
+//! from many page loads. Split modules that most sessions call right after
+//! startup are hoisted into the main module, whose download they would
+//! otherwise follow anyway, and modules that sessions almost always call
+//! together are merged into one chunk, as if declared with `group`.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+/// The calls of one page load, as returned by `getProfile()`.
+#[derive(Debug, Deserialize)]
+pub struct Session {
+    /// Build that the session ran, which may be an earlier one: module names
+    /// rather than code are compared.
+    #[serde(default)]
+    pub build_id: Option<String>,
+    pub calls: Vec<Call>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Call {
+    pub module: String,
+    pub function: String,
+    /// Time of the first call since the page started loading.
+    pub first_call_ms: f64,
+    #[serde(default = "one")]
+    pub count: u64,
+}
+
+fn one() -> u64 {
+    1
+}
+
+pub fn read_profile(path: &Path) -> Result<Vec<Session>> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read profile {path:?}"))?;
+    parse_profile(&text).with_context(|| format!("Failed to parse profile {path:?}"))
+}
+
+fn parse_profile(text: &str) -> Result<Vec<Session>> {
+    if text.trim_start().starts_with('[') {
+        return Ok(serde_json::from_str(text)?);
+    }
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line).with_context(|| format!("Line {}", index + 1))
+        })
+        .collect()
+}
+
+/// Thresholds of [`get_profile_advice`].
+#[derive(Debug, Clone, Copy)]
+pub struct ProfileThresholds {
+    /// Share of the sessions that must call a module within `startup_ms`
+    /// for it to be hoisted.
+    pub hot_share: f64,
+    pub startup_ms: f64,
+    /// Share of the sessions calling either of two modules that must call
+    /// both for them to be merged.
+    pub co_load_share: f64,
+}
+
+/// Modules with fewer sessions are never merged, as their co-loads could be
+/// chance.
+const MIN_MERGE_SESSIONS: usize = 5;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct ProfileAdvice {
+    /// Split modules to fold into the main module, as all of their
+    /// functions are hot.
+    pub hoisted_modules: Vec<String>,
+    /// Hot split functions of other modules to fold into the main module, by
+    /// module and function name.
+    pub hoisted_functions: Vec<(String, String)>,
+    /// Groups of split modules to merge, by the module that the others are
+    /// merged into: the first of each group by name.
+    pub groups: BTreeMap<String, Vec<String>>,
+}
+
+/// What `sessions` suggest for `split_functions`, the names of the split
+/// functions of each split module. Modules are recorded by the name at their
+/// split points, which `aliases` maps to the module holding their code, if
+/// another. Calls of other functions, such as ones removed since the profile
+/// was recorded, are ignored.
+pub fn get_profile_advice(
+    sessions: &[Session],
+    split_functions: &BTreeMap<String, BTreeSet<String>>,
+    aliases: &BTreeMap<String, String>,
+    thresholds: ProfileThresholds,
+) -> ProfileAdvice {
+    let mut advice = ProfileAdvice::default();
+    if sessions.is_empty() {
+        return advice;
+    }
+    let builds = sessions
+        .iter()
+        .filter_map(|session| session.build_id.as_deref())
+        .collect::<BTreeSet<_>>();
+    println!(
+        "Profile of {} sessions, of {} builds",
+        sessions.len(),
+        builds.len()
+    );
+    // For each session, the time of the first call of each of its functions.
+    let session_calls = sessions
+        .iter()
+        .map(|session| {
+            let mut first_calls = BTreeMap::<(&str, &str), f64>::new();
+            for call in session.calls.iter().filter(|call| call.count > 0) {
+                let module = aliases.get(&call.module).unwrap_or(&call.module);
+                if !split_functions
+                    .get(module)
+                    .is_some_and(|functions| functions.contains(&call.function))
+                {
+                    continue;
+                }
+                let time = first_calls
+                    .entry((module, &call.function))
+                    .or_insert(call.first_call_ms);
+                *time = time.min(call.first_call_ms);
+            }
+            first_calls
+        })
+        .collect::<Vec<_>>();
+
+    for (module, functions) in split_functions.iter() {
+        let hot = functions
+            .iter()
+            .filter(|function| {
+                let at_startup = session_calls
+                    .iter()
+                    .filter(|first_calls| {
+                        first_calls
+                            .get(&(module.as_str(), function.as_str()))
+                            .is_some_and(|&time| time <= thresholds.startup_ms)
+                    })
+                    .count();
+                at_startup as f64 / sessions.len() as f64 >= thresholds.hot_share
+            })
+            .collect::<Vec<_>>();
+        if hot.is_empty() {
+            continue;
+        }
+        let percent = thresholds.hot_share * 100.0;
+        if hot.len() == functions.len() {
+            println!(
+                "Hoisting split module {module} into main: all of its functions are called \
+                 within {} ms of startup in at least {percent:.0}% of the profiled sessions",
+                thresholds.startup_ms
+            );
+            advice.hoisted_modules.push(module.clone());
+            continue;
+        }
+        for function in hot {
+            println!(
+                "Hoisting split function {function} of {module} into main: called within {} ms \
+                 of startup in at least {percent:.0}% of the profiled sessions",
+                thresholds.startup_ms
+            );
+            advice
+                .hoisted_functions
+                .push((module.clone(), function.clone()));
+        }
+    }
+
+    // For each session, the modules whose chunks it loaded, i.e. that it
+    // called other than through hoisted functions.
+    let session_modules = session_calls
+        .iter()
+        .map(|first_calls| {
+            first_calls
+                .keys()
+                .filter(|&&(module, function)| {
+                    !advice
+                        .hoisted_functions
+                        .iter()
+                        .any(|hoisted| hoisted.0 == module && hoisted.1 == function)
+                })
+                .map(|&(module, _)| module)
+                .collect::<BTreeSet<_>>()
+        })
+        .collect::<Vec<_>>();
+    let sessions_of = |module: &str| {
+        session_modules
+            .iter()
+            .enumerate()
+            .filter(|(_, modules)| modules.contains(module))
+            .map(|(index, _)| index)
+            .collect::<BTreeSet<_>>()
+    };
+    let co_load_share = |a: &BTreeSet<usize>, b: &BTreeSet<usize>| {
+        a.intersection(b).count() as f64 / a.union(b).count() as f64
+    };
+    let candidates = split_functions
+        .keys()
+        .filter(|module| !advice.hoisted_modules.contains(module))
+        .map(|module| (module.as_str(), sessions_of(module)))
+        .filter(|(_, sessions)| sessions.len() >= MIN_MERGE_SESSIONS)
+        .collect::<BTreeMap<_, _>>();
+    // Modules are only merged into a group if they are co-loaded with each
+    // of its members, rather than just with one of them.
+    let mut groups = Vec::<BTreeSet<&str>>::new();
+    for (&module, sessions) in candidates.iter() {
+        let group = groups.iter_mut().find(|group| {
+            group.iter().all(|member| {
+                co_load_share(sessions, &candidates[member]) >= thresholds.co_load_share
+            })
+        });
+        match group {
+            Some(group) => {
+                group.insert(module);
+            }
+            None => groups.push(BTreeSet::from([module])),
+        }
+    }
+    for group in groups.into_iter().filter(|group| group.len() > 1) {
+        let modules = group
+            .iter()
+            .map(|module| module.to_string())
+            .collect::<Vec<_>>();
+        println!(
+            "Merging split modules {}: called together in the profiled sessions",
+            modules.join(", ")
+        );
+        advice.groups.insert(modules[0].clone(), modules);
+    }
+    advice
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(calls: &[(&str, &str, f64)]) -> Session {
+        Session {
+            build_id: None,
+            calls: calls
+                .iter()
+                .map(|&(module, function, first_call_ms)| Call {
+                    module: module.to_string(),
+                    function: function.to_string(),
+                    first_call_ms,
+                    count: 1,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn hoists_hot_modules_and_merges_co_loaded_ones() {
+        let mut sessions = (0..9)
+            .map(|_| {
+                session(&[
+                    ("nav", "nav", 50.0),
+                    ("editor", "open", 100.0),
+                    ("editor", "edit", 3000.0),
+                    ("toolbar", "toolbar", 3100.0),
+                ])
+            })
+            .collect::<Vec<_>>();
+        sessions.push(session(&[
+            ("nav", "nav", 5000.0),
+            ("admin", "admin", 100.0),
+            ("gone", "gone", 1.0),
+        ]));
+        let split_functions = [
+            ("admin", &["admin"][..]),
+            ("editor", &["edit", "open"]),
+            ("nav", &["nav"]),
+            ("tool_bar", &["toolbar"]),
+        ]
+        .into_iter()
+        .map(|(module, functions)| {
+            (
+                module.to_string(),
+                functions
+                    .iter()
+                    .map(|function| function.to_string())
+                    .collect(),
+            )
+        })
+        .collect();
+        let aliases = BTreeMap::from([("toolbar".to_string(), "tool_bar".to_string())]);
+        let advice = get_profile_advice(
+            &sessions,
+            &split_functions,
+            &aliases,
+            ProfileThresholds {
+                hot_share: 0.9,
+                startup_ms: 1000.0,
+                co_load_share: 0.9,
+            },
+        );
+        assert_eq!(advice.hoisted_modules, ["nav"]);
+        assert_eq!(
+            advice.hoisted_functions,
+            [("editor".to_string(), "open".to_string())]
+        );
+        assert_eq!(
+            advice.groups,
+            BTreeMap::from([(
+                "editor".to_string(),
+                vec!["editor".to_string(), "tool_bar".to_string()]
+            )])
+        );
+    }
+
+    #[test]
+    fn parses_sessions_as_array_or_lines() {
+        let line = r#"{"calls": [{"module": "a", "function": "f", "first_call_ms": 1.5}]}"#;
+        assert_eq!(
+            parse_profile(&format!("{line}\n\n{line}\n")).unwrap().len(),
+            2
+        );
+        let sessions = parse_profile(&format!("[{line}]")).unwrap();
+        assert_eq!(sessions[0].calls[0].count, 1);
+        let error = parse_profile(&format!("{line}\n{{")).unwrap_err();
+        assert!(format!("{error:#}").starts_with("Line 2"));
+    }
+}
@@ -0,0 +1,308 @@
+//! Per-chunk provenance report, written to [`PROVENANCE_FILENAME`] with
+//! `--provenance`.
+//!
+//! Since each chunk is a separately downloadable artifact, attribution
+//! notices may have to accompany each of them. The report lists the crates
+//! that contributed code to every chunk, along with the packages providing
+//! these crates according to `cargo metadata`, including their licenses.
+//!
+//! Functions are attributed to crates by their symbol names, in the same way
+//! as for `deny-in-main`. Generic functions are attributed to the crate that
+//! defines them, not to the crate that instantiated them.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::Path,
+    process::Command,
+};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    deny::get_crate_name, dep_graph::DepNode, emit::EmittedModule, manifest::Manifest,
+    read::InputModule, split_point::SplitProgramInfo, symbols::demangle,
+};
+
+pub const PROVENANCE_FILENAME: &str = "wasm-split-provenance.json";
+
+#[derive(Debug, Serialize)]
+pub struct Provenance {
+    pub build_id: String,
+    /// In the same order as the chunks of the manifest.
+    pub chunks: Vec<ChunkProvenance>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChunkProvenance {
+    pub name: String,
+    pub file: String,
+    /// Ordered by decreasing code size.
+    pub crates: Vec<CrateContribution>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CrateContribution {
+    /// Crate name as it appears in symbol names, or `None` if the symbol name
+    /// does not tell, as for `#[no_mangle]` functions and inherent methods of
+    /// primitive types, which are part of the standard library.
+    #[serde(rename = "crate")]
+    pub name: Option<String>,
+    /// Number of functions of the crate defined by the chunk.
+    pub functions: usize,
+    /// Total size of their bodies in bytes.
+    pub bytes: usize,
+    /// Packages of the dependency graph with a library target of this name.
+    /// Usually a single one, but there can be several if multiple versions of
+    /// a package are used. Empty for crates of the standard library, which
+    /// are not part of the dependency graph.
+    pub packages: Vec<Package>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Package {
+    pub name: String,
+    pub version: String,
+    /// SPDX license expression.
+    pub license: Option<String>,
+    /// Path of a non-standard license file, if the package specifies one.
+    pub license_file: Option<String>,
+    pub repository: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Metadata {
+    packages: Vec<MetadataPackage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MetadataPackage {
+    #[serde(flatten)]
+    package: Package,
+    targets: Vec<MetadataTarget>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MetadataTarget {
+    name: String,
+    kind: Vec<String>,
+}
+
+/// Whether `name`, as returned by [`get_crate_name`], is that of a primitive
+/// type rather than a crate, e.g. for `<char>::encode_utf8`.
+fn is_primitive_type(name: &str) -> bool {
+    matches!(
+        name,
+        "" | "bool"
+            | "char"
+            | "str"
+            | "f32"
+            | "f64"
+            | "i8"
+            | "i16"
+            | "i32"
+            | "i64"
+            | "i128"
+            | "isize"
+            | "u8"
+            | "u16"
+            | "u32"
+            | "u64"
+            | "u128"
+            | "usize"
+    )
+}
+
+/// Runs `cargo metadata` for the package at `manifest_path`, and returns the
+/// packages of its dependency graph by the names of their library crates.
+fn get_packages_by_crate(manifest_path: &Path) -> Result<HashMap<String, Vec<Package>>> {
+    let cargo = std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let output = Command::new(cargo)
+        .args([
+            "metadata",
+            "--format-version",
+            "1",
+            "--filter-platform",
+            "wasm32-unknown-unknown",
+            "--manifest-path",
+        ])
+        .arg(manifest_path)
+        .output()
+        .context("Failed to run cargo metadata")?;
+    if !output.status.success() {
+        bail!(
+            "cargo metadata failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let metadata: Metadata =
+        serde_json::from_slice(&output.stdout).context("Invalid output of cargo metadata")?;
+    let mut packages = HashMap::<String, Vec<Package>>::new();
+    for package in metadata.packages {
+        for target in package.targets.iter() {
+            if target
+                .kind
+                .iter()
+                .any(|kind| matches!(kind.as_str(), "lib" | "rlib" | "cdylib" | "staticlib"))
+            {
+                packages
+                    .entry(target.name.replace('-', "_"))
+                    .or_default()
+                    .push(package.package.clone());
+            }
+        }
+    }
+    Ok(packages)
+}
+
+impl Provenance {
+    pub fn new(
+        module: &InputModule,
+        program_info: &SplitProgramInfo,
+        manifest: &Manifest,
+        emitted_modules: &[EmittedModule],
+        manifest_path: &Path,
+    ) -> Result<Self> {
+        let packages = get_packages_by_crate(manifest_path)?;
+        let mut unknown_crates = BTreeSet::new();
+        let chunks = manifest
+            .chunks
+            .iter()
+            .zip(program_info.output_modules.iter())
+            .zip(emitted_modules)
+            .map(|((chunk, (_, info)), emitted)| {
+                let mut crates = BTreeMap::<Option<String>, (usize, usize)>::new();
+                for &func_id in emitted.functions[emitted.defined_functions.clone()].iter() {
+                    // With `--table-only`, the main module keeps empty bodies
+                    // of the functions moved out of it.
+                    // wasm-bindgen removes its descriptors from the main
+                    // module, so they are not part of the published chunk.
+                    if !info.included_symbols.contains(&DepNode::Function(func_id))
+                        || program_info.wasm_bindgen_funcs.contains(&func_id)
+                    {
+                        continue;
+                    }
+                    let name = module
+                        .names
+                        .functions
+                        .get(&func_id)
+                        .map(|name| demangle(name))
+                        .filter(|path| path.contains("::"))
+                        .map(|path| get_crate_name(&path).to_string())
+                        .filter(|name| !is_primitive_type(name));
+                    let size = module.defined_funcs[func_id - module.imported_funcs.len()]
+                        .body
+                        .range()
+                        .len();
+                    let (functions, bytes) = crates.entry(name).or_default();
+                    *functions += 1;
+                    *bytes += size;
+                }
+                let mut crates = crates
+                    .into_iter()
+                    .map(|(name, (functions, bytes))| {
+                        let crate_packages = name
+                            .as_ref()
+                            .and_then(|name| packages.get(name))
+                            .cloned()
+                            .unwrap_or_default();
+                        if crate_packages.is_empty() {
+                            unknown_crates.extend(name.clone());
+                        }
+                        CrateContribution {
+                            name,
+                            functions,
+                            bytes,
+                            packages: crate_packages,
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                crates.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.name.cmp(&b.name)));
+                ChunkProvenance {
+                    name: chunk.name.clone(),
+                    file: chunk.file.clone(),
+                    crates,
+                }
+            })
+            .collect();
+        if !unknown_crates.is_empty() {
+            println!(
+                "No package found for crates {}, which are most likely part of the standard \
+                 library; their licenses are not included in {PROVENANCE_FILENAME}",
+                unknown_crates.into_iter().collect::<Vec<_>>().join(", ")
+            );
+        }
+        Ok(Self {
+            build_id: manifest.build_id.clone(),
+            chunks,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PROVENANCE_FILENAME;
+    use crate::test_fixtures::{fixture_path, split, temp_dir};
+
+    #[test]
+    fn attributes_code_of_chunks_to_crates() {
+        // A package of the app's crate that only depends on path packages,
+        // so that `cargo metadata` needs no registry.
+        let package = temp_dir();
+        package.write(
+            "Cargo.toml",
+            format!(
+                "[package]\nname = \"no_std_app\"\nversion = \"1.2.3\"\nedition = \"2021\"\n\
+                 license = \"MIT OR Apache-2.0\"\n[lib]\npath = \"lib.rs\"\n\
+                 [dependencies]\nchecksum = {{ path = {:?} }}\n[workspace]\n",
+                fixture_path("no_std_app/checksum")
+            )
+            .as_bytes(),
+        );
+        package.write("lib.rs", b"");
+        let manifest_path = package.dir.join("Cargo.toml");
+        let output = split(
+            "no_std_app.wasm",
+            &["--provenance", manifest_path.to_str().unwrap()],
+        );
+        let provenance: serde_json::Value =
+            serde_json::from_slice(&output.read(PROVENANCE_FILENAME)).unwrap();
+        let manifest = output.manifest();
+        let chunks = provenance["chunks"].as_array().unwrap();
+        assert_eq!(
+            chunks
+                .iter()
+                .map(|chunk| &chunk["name"])
+                .collect::<Vec<_>>(),
+            manifest["chunks"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|chunk| &chunk["name"])
+                .collect::<Vec<_>>()
+        );
+        let contribution = |chunk: &str, crate_name: &str| {
+            let chunk = chunks.iter().find(|c| c["name"] == chunk).unwrap();
+            chunk["crates"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|contribution| contribution["crate"] == crate_name)
+                .unwrap_or_else(|| panic!("No code of {crate_name} in {chunk}"))
+                .clone()
+        };
+
+        let app = contribution("first", "no_std_app");
+        assert!(app["functions"].as_u64().unwrap() > 0);
+        assert_eq!(app["packages"][0]["version"], "1.2.3");
+        assert_eq!(app["packages"][0]["license"], "MIT OR Apache-2.0");
+        let checksum = contribution("main", "checksum");
+        assert_eq!(checksum["packages"][0]["name"], "checksum");
+        assert!(checksum["packages"][0]["license"].is_null());
+        // The standard library is not part of the dependency graph.
+        assert_eq!(
+            contribution("main", "core")["packages"],
+            serde_json::json!([])
+        );
+    }
+}
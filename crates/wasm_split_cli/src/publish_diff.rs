@@ -0,0 +1,129 @@
+//! `wasm-split publish-diff`: compares the manifests of two builds and lists
+//! the chunk files that a deployment needs to upload, and for CDNs that
+//! cache by path alone, invalidate.
+//!
+//! The other files written by `wasm-split`, such as the manifest and the
+//! loader script, embed the build ID and thus change with every build. They
+//! are not listed and should always be published.
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::manifest::{Manifest, ManifestChunk};
+
+#[derive(Debug, Default)]
+pub struct PublishDiff {
+    /// Files of chunks that are new or whose contents changed, in manifest
+    /// order, each followed by its compressed copies.
+    pub changed: Vec<String>,
+    /// Files of the old build that the new one no longer uses. They can be
+    /// deleted once no client runs the old build anymore.
+    pub removed: Vec<String>,
+}
+
+impl PublishDiff {
+    pub fn new(old: &Manifest, new: &Manifest) -> Self {
+        let changed = new
+            .chunks
+            .iter()
+            .filter(|chunk| {
+                // Without a hash on either side, the chunk may have changed.
+                !old.chunks.iter().any(|old_chunk| {
+                    old_chunk.file == chunk.file
+                        && old_chunk.hash.is_some()
+                        && old_chunk.hash == chunk.hash
+                })
+            })
+            .flat_map(chunk_files)
+            .collect();
+        let new_files = new.chunks.iter().flat_map(chunk_files).collect::<Vec<_>>();
+        let removed = old
+            .chunks
+            .iter()
+            .flat_map(chunk_files)
+            .filter(|file| !new_files.contains(file))
+            .collect();
+        Self { changed, removed }
+    }
+}
+
+/// The file of a chunk, and those of its copies written with `--compress`.
+fn chunk_files(chunk: &ManifestChunk) -> impl Iterator<Item = String> + '_ {
+    std::iter::once(chunk.file.clone()).chain(
+        chunk
+            .compressed_sizes
+            .keys()
+            .map(|encoding| format!("{}.{}", chunk.file, encoding.extension())),
+    )
+}
+
+pub fn read_manifest(path: &Path) -> Result<Manifest> {
+    let data = std::fs::read(path).with_context(|| format!("Failed to read manifest {path:?}"))?;
+    serde_json::from_slice(&data).with_context(|| format!("Failed to parse manifest {path:?}"))
+}
+
+/// Writes the changed files, one per line and relative to the output
+/// directory, to `out` or else to stdout. A summary goes to stderr, so that
+/// the list can be piped to an upload command.
+pub fn run(old: &Path, new: &Path, out: Option<&Path>) -> Result<()> {
+    let old = read_manifest(old)?;
+    let new = read_manifest(new)?;
+    let diff = PublishDiff::new(&old, &new);
+    let list: String = diff
+        .changed
+        .iter()
+        .map(|file| format!("{file}\n"))
+        .collect();
+    match out {
+        Some(out) => std::fs::write(out, list)
+            .with_context(|| format!("Failed to write upload list {out:?}"))?,
+        None => print!("{list}"),
+    }
+    eprintln!(
+        "{} of {} chunk files changed between builds {} and {}",
+        diff.changed.len(),
+        new.chunks.iter().flat_map(chunk_files).count(),
+        old.build_id,
+        new.build_id
+    );
+    if !diff.removed.is_empty() {
+        eprintln!("No longer used: {}", diff.removed.join(", "));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_manifest, run, PublishDiff};
+    use crate::{manifest::MANIFEST_FILENAME, test_fixtures::split};
+
+    #[test]
+    fn lists_changed_chunk_files() {
+        let output = split("no_std_app.wasm", &["--compress", "gzip"]);
+        let path = output.dir.join(MANIFEST_FILENAME);
+        let old = read_manifest(&path).unwrap();
+        let diff = PublishDiff::new(&old, &old);
+        assert!(
+            diff.changed.is_empty() && diff.removed.is_empty(),
+            "{diff:?}"
+        );
+
+        let mut new = old.clone();
+        let second = new.chunks.iter_mut().find(|chunk| chunk.name == "second");
+        second.unwrap().hash = Some("0000000000000000".to_string());
+        new.chunks.retain(|chunk| chunk.name != "first");
+        let diff = PublishDiff::new(&old, &new);
+        assert_eq!(diff.changed, ["second.wasm", "second.wasm.gz"]);
+        assert_eq!(diff.removed, ["first.wasm", "first.wasm.gz"]);
+
+        let new_path = output.dir.join("new-manifest.json");
+        output.write("new-manifest.json", &serde_json::to_vec(&new).unwrap());
+        let list = output.dir.join("upload.txt");
+        run(&path, &new_path, Some(&list)).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(list).unwrap(),
+            "second.wasm\nsecond.wasm.gz\n"
+        );
+    }
+}
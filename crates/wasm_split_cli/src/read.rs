@@ -9,10 +9,11 @@ use wasmparser::{Payload, TypeRef};
 pub type InputRange = Range<usize>;
 
 pub struct CustomSection<'a> {
+    /// Index of the section, which its relocations refer to it by.
+    pub index: SectionIndex,
     pub name: &'a str,
     pub data_offset: usize,
     pub data: &'a [u8],
-    pub section_index: usize,
     pub range: InputRange,
 }
 
@@ -60,13 +61,13 @@ fn convert_name_map<'a>(name_map: wasmparser::NameMap<'a>) -> Result<HashMap<usi
 fn convert_indirect_name_map<'a>(
     indirect_name_map: wasmparser::IndirectNameMap<'a>,
 ) -> Result<HashMap<usize, wasmparser::NameMap<'a>>> {
-    Ok(indirect_name_map
+    indirect_name_map
         .into_iter()
         .map(|r| -> Result<(usize, wasmparser::NameMap<'a>)> {
             let indirect_naming = r?;
             Ok((indirect_naming.index as usize, indirect_naming.names))
         })
-        .collect::<Result<HashMap<_, _>, _>>()?)
+        .collect::<Result<HashMap<_, _>, _>>()
 }
 
 impl<'a> Names<'a> {
@@ -122,6 +123,15 @@ impl<'a> Names<'a> {
 
 pub type SymbolIndex = usize;
 
+/// The value of a constant expression that is a single `i32.const`, such as
+/// the offset of an active data segment.
+pub fn const_i32(expr: &wasmparser::ConstExpr) -> Option<i32> {
+    match expr.get_operators_reader().read() {
+        Ok(wasmparser::Operator::I32Const { value }) => Some(value),
+        _ => None,
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct DataSymbol {
     pub symbol_index: SymbolIndex,
@@ -209,9 +219,25 @@ impl<'a> InputModule<'a> {
             match payload? {
                 Payload::Version { .. } => {}
                 Payload::TypeSection(reader) => {
-                    module.types = reader
-                        .into_iter_err_on_gc_types()
-                        .collect::<Result<Vec<_>, _>>()?;
+                    for group in reader.into_iter_with_offsets() {
+                        let (offset, group) = group?;
+                        let mut types = group.into_types();
+                        match (types.next(), types.next()) {
+                            (
+                                Some(wasmparser::SubType {
+                                    is_final: true,
+                                    supertype_idx: None,
+                                    composite_type: wasmparser::CompositeType::Func(ty),
+                                }),
+                                None,
+                            ) => module.types.push(ty),
+                            _ => bail!(
+                                "The input declares a struct, array or recursive type of the GC \
+                                 proposal (at offset {offset:#x}), which wasm-split does not \
+                                 support; it splits modules whose types are all function types"
+                            ),
+                        }
+                    }
                     section_index += 1;
                 }
                 Payload::ImportSection(reader) => {
@@ -281,14 +307,14 @@ impl<'a> InputModule<'a> {
                 Payload::CodeSectionEntry(body) => {
                     let index = module.defined_funcs.len();
                     module.defined_funcs.push(DefinedFunc {
-                        type_id: function_types[index].clone(),
+                        type_id: function_types[index],
                         body,
                     });
                 }
                 Payload::CustomSection(reader) => {
                     module.custom_sections.push(CustomSection {
+                        index: section_index,
                         name: reader.name(),
-                        section_index,
                         data: reader.data(),
                         range: reader.range(),
                         data_offset: reader.data_offset(),
@@ -302,6 +328,30 @@ impl<'a> InputModule<'a> {
             }
         }
 
+        let memories = module
+            .imports
+            .iter()
+            .filter_map(|import| match import.ty {
+                TypeRef::Memory(memory) => Some(memory),
+                _ => None,
+            })
+            .chain(module.memories.iter().copied());
+        for memory in memories {
+            if memory.memory64 {
+                bail!(
+                    "The input has a 64-bit memory of the memory64 proposal, e.g. of the \
+                     wasm64-unknown-unknown target, which wasm-split does not support; build for \
+                     wasm32-unknown-unknown"
+                );
+            }
+            if memory.page_size_log2.is_some() {
+                bail!(
+                    "The input has a memory with a custom page size, which wasm-split does not \
+                     support"
+                );
+            }
+        }
+
         for section in module.custom_sections.iter() {
             if section.name == "name" {
                 module.names = Names::new(section.data, section.data_offset)?;
@@ -309,11 +359,8 @@ impl<'a> InputModule<'a> {
                 let reader =
                     wasmparser::LinkingSectionReader::new(section.data, section.data_offset)?;
                 for subsection in reader.subsections() {
-                    match subsection? {
-                        wasmparser::Linking::SymbolTable(map) => {
-                            module.symbols = map.into_iter().collect::<Result<Vec<_>, _>>()?;
-                        }
-                        _ => {}
+                    if let wasmparser::Linking::SymbolTable(map) = subsection? {
+                        module.symbols = map.into_iter().collect::<Result<Vec<_>, _>>()?;
                     }
                 }
             } else if section.name.starts_with("reloc.") {
@@ -347,6 +394,32 @@ impl<'a> InputModule<'a> {
         Ok(module)
     }
 
+    /// The indirect function table among the defined tables, which is not
+    /// always the first with reference types, e.g. after tables that the
+    /// code declares itself.
+    pub fn function_table(&self) -> TableId {
+        self.symbols
+            .iter()
+            .find_map(|symbol| match *symbol {
+                SymbolInfo::Table {
+                    index,
+                    name: Some("__indirect_function_table"),
+                    ..
+                } => Some(index as TableId),
+                _ => None,
+            })
+            .or_else(|| {
+                self.exports
+                    .iter()
+                    .find(|export| {
+                        export.kind == wasmparser::ExternalKind::Table
+                            && export.name == "__indirect_function_table"
+                    })
+                    .map(|export| export.index as TableId)
+            })
+            .unwrap_or(0)
+    }
+
     pub fn func_type_id(&self, func_id: InputFuncId) -> FuncTypeId {
         if func_id < self.imported_funcs.len() {
             let import_id = self.imported_funcs[func_id];
@@ -359,3 +432,34 @@ impl<'a> InputModule<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::InputModule;
+
+    const HEADER: [u8; 8] = [0, 97, 115, 109, 1, 0, 0, 0];
+
+    fn parse_error(sections: &[u8]) -> String {
+        let wasm = [&HEADER[..], sections].concat();
+        match InputModule::parse(&wasm) {
+            Ok(_) => panic!("Parsed a module with sections {sections:?}"),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn rejects_gc_types() {
+        // A struct type without fields.
+        let error = parse_error(&[1, 3, 1, 0x5f, 0]);
+        assert!(error.contains("struct, array or recursive type"), "{error}");
+    }
+
+    #[test]
+    fn rejects_64_bit_memories() {
+        // A memory64 of one page, defined and imported.
+        for sections in [&[5, 3, 1, 4, 1][..], &[2, 8, 1, 1, b'e', 1, b'm', 2, 4, 1]] {
+            let error = parse_error(sections);
+            assert!(error.contains("memory64"), "{error}");
+        }
+    }
+}
@@ -0,0 +1,370 @@
+//! `wasm-split report`: the chunk sizes of a build, their changes since a
+//! baseline build and its size budgets, for CI to gate changes on without
+//! scripts of its own:
+//!
+//! ```sh
+//! wasm-split report pkg --baseline main-pkg --format github
+//! ```
+//!
+//! Budgets are those of the config, as for a split, and `--budget`, checked
+//! against the sizes in the manifest. The command fails once the report is
+//! written if any is exceeded.
+//!
+//! With `--format github`, the report is a summary and [workflow commands]
+//! that GitHub Actions shows as annotations of the run: an error for each
+//! budget exceeded and a warning for each chunk that grew, largest first, as
+//! GitHub only shows the first few of each. The Markdown report is also
+//! appended to the job summary, if the step has one. The other formats are
+//! a text table, the Markdown of `diff --markdown` and JSON.
+//!
+//! [workflow commands]: https://docs.github.com/en/actions/using-workflows/workflow-commands-for-github-actions
+
+use std::{fmt::Write, io::Write as _, path::PathBuf};
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+
+use crate::{
+    budget::{self, BudgetCheck, BudgetKind},
+    config::{Budgets, ByteSize, Config},
+    manifest::Manifest,
+    size_diff::{
+        format_change, format_delta, format_markdown, format_size, format_text, get_deltas,
+        read_build, total, ChunkDelta,
+    },
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReportFormat {
+    /// A table of the chunks, and the budgets.
+    #[default]
+    Text,
+    /// Annotations of a GitHub Actions run, and a job summary.
+    Github,
+    /// A Markdown comment for the pull request.
+    Markdown,
+    Json,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct ReportArgs {
+    /// Manifest or output directory of the build.
+    build: PathBuf,
+
+    /// Manifest or output directory of the build to compare with, such as
+    /// that of the target branch.
+    #[arg(long, value_name = "PATH")]
+    baseline: Option<PathBuf>,
+
+    #[arg(long, value_enum, default_value_t)]
+    format: ReportFormat,
+
+    /// Configuration file whose `[budgets]` to check. Defaults to
+    /// `wasm-split.toml` in the working directory, if it exists.
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Size budget for a chunk, as for a split, in addition to those of the
+    /// config. May be given several times.
+    #[arg(long = "budget", value_name = "NAME=SIZE", value_parser = budget::parse_chunk_budget)]
+    budgets: Vec<(String, ByteSize)>,
+
+    /// File to write the report to. Defaults to stdout.
+    #[arg(long, value_name = "PATH")]
+    out: Option<PathBuf>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SizeReport {
+    pub build_id: String,
+    /// Build ID of the baseline, if the report compares with one.
+    pub baseline: Option<String>,
+    /// Chunks of either build, largest change first. Without a baseline,
+    /// the `old` sizes are all null.
+    pub chunks: Vec<ChunkDelta>,
+    pub total: ChunkDelta,
+    pub budgets: Vec<BudgetCheck>,
+}
+
+impl SizeReport {
+    fn exceeded(&self) -> impl Iterator<Item = &BudgetCheck> {
+        self.budgets.iter().filter(|check| check.excess().is_some())
+    }
+}
+
+pub fn get_report(
+    build: &Manifest,
+    baseline: Option<&Manifest>,
+    budgets: &Budgets,
+) -> Result<SizeReport> {
+    let chunks = get_deltas(baseline.unwrap_or(&Manifest::default()), build);
+    Ok(SizeReport {
+        build_id: build.build_id.clone(),
+        baseline: baseline.map(|baseline| baseline.build_id.clone()),
+        total: total(&chunks),
+        chunks,
+        budgets: budget::get_budget_checks(build, budgets)?,
+    })
+}
+
+fn describe_budget(check: &BudgetCheck) -> String {
+    let kind = match check.kind {
+        BudgetKind::Chunk => "Chunk",
+        BudgetKind::Route => "Route",
+    };
+    let mut description = format!(
+        "{kind} {}: {} of {} budget",
+        check.name,
+        ByteSize(check.size),
+        ByteSize(check.budget)
+    );
+    if let Some(excess) = check.excess() {
+        write!(description, ", over by {}", ByteSize(excess)).unwrap();
+    }
+    description
+}
+
+pub fn format_text_report(report: &SizeReport) -> String {
+    let mut out = if report.baseline.is_some() {
+        format_text(&report.chunks)
+    } else {
+        let width = report
+            .chunks
+            .iter()
+            .map(|chunk| chunk.name.len())
+            .max()
+            .unwrap_or(0)
+            .max("total".len());
+        let mut out = String::new();
+        for chunk in report.chunks.iter().chain([&report.total]) {
+            writeln!(
+                out,
+                "{:width$}  {:>10}  {:>10}",
+                chunk.name,
+                format_size(chunk.new),
+                chunk
+                    .new_gzip
+                    .map_or_else(String::new, |gzip| format!("{} gz", ByteSize(gzip)))
+            )
+            .unwrap();
+        }
+        out
+    };
+    if !report.budgets.is_empty() {
+        out.push('\n');
+        for check in report.budgets.iter() {
+            writeln!(out, "{}", describe_budget(check)).unwrap();
+        }
+    }
+    out
+}
+
+pub fn format_markdown_report(report: &SizeReport) -> String {
+    let mut out = if report.baseline.is_some() {
+        format_markdown(&report.chunks)
+    } else {
+        let mut out = format!(
+            "**wasm-split:** {} in {} chunks\n\n| Chunk | Size | Gzip |\n|:--|--:|--:|\n",
+            format_size(report.total.new),
+            report.chunks.len()
+        );
+        for chunk in report.chunks.iter() {
+            writeln!(
+                out,
+                "| `{}` | {} | {} |",
+                chunk.name,
+                format_size(chunk.new),
+                format_size(chunk.new_gzip)
+            )
+            .unwrap();
+        }
+        out
+    };
+    if !report.budgets.is_empty() {
+        out.push_str("\n| Budget | Size | Limit | |\n|:--|--:|--:|:--|\n");
+        for check in report.budgets.iter() {
+            let kind = match check.kind {
+                BudgetKind::Chunk => "chunk",
+                BudgetKind::Route => "route",
+            };
+            writeln!(
+                out,
+                "| {kind} `{}` | {} | {} | {} |",
+                check.name,
+                ByteSize(check.size),
+                ByteSize(check.budget),
+                check.excess().map_or_else(
+                    || "within".to_string(),
+                    |excess| format!("**over by {}**", ByteSize(excess))
+                )
+            )
+            .unwrap();
+        }
+    }
+    out
+}
+
+/// `text` as the message of a workflow command.
+fn escape_data(text: &str) -> String {
+    text.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// `text` as a property of a workflow command, such as its title.
+fn escape_property(text: &str) -> String {
+    escape_data(text).replace(':', "%3A").replace(',', "%2C")
+}
+
+fn annotation(level: &str, title: &str, message: &str) -> String {
+    format!(
+        "::{level} title={}::{}\n",
+        escape_property(title),
+        escape_data(message)
+    )
+}
+
+pub fn format_github_report(report: &SizeReport) -> String {
+    let mut summary = format!(
+        "{} in {} chunks",
+        format_size(report.total.new),
+        report
+            .chunks
+            .iter()
+            .filter(|chunk| chunk.new.is_some())
+            .count()
+    );
+    if report.baseline.is_some() {
+        write!(
+            summary,
+            ", {} since the baseline",
+            format_change(report.total.delta(), report.total.old)
+        )
+        .unwrap();
+        if let Some(delta) = report.total.gzip_delta() {
+            write!(summary, ", {} gzipped", format_delta(delta)).unwrap();
+        }
+    }
+    let mut out = annotation("notice", "wasm-split", &summary);
+    for check in report.exceeded() {
+        out.push_str(&annotation(
+            "error",
+            "wasm-split budget exceeded",
+            &describe_budget(check),
+        ));
+    }
+    if report.baseline.is_some() {
+        for chunk in report.chunks.iter().filter(|chunk| chunk.delta() > 0) {
+            let message = match chunk.old {
+                Some(_) => format!(
+                    "Chunk {} grew to {}, {}",
+                    chunk.name,
+                    format_size(chunk.new),
+                    format_change(chunk.delta(), chunk.old)
+                ),
+                None => format!("Chunk {} was added, {}", chunk.name, format_size(chunk.new)),
+            };
+            out.push_str(&annotation("warning", "wasm-split chunk grew", &message));
+        }
+    }
+    out
+}
+
+pub fn run(args: &ReportArgs) -> Result<()> {
+    let build = read_build(&args.build)?;
+    let baseline = args.baseline.as_deref().map(read_build).transpose()?;
+    let mut config = Config::load(args.config.as_deref())?;
+    config.budgets.chunks.extend(args.budgets.iter().cloned());
+    let report = get_report(&build, baseline.as_ref(), &config.budgets)?;
+
+    let output = match args.format {
+        ReportFormat::Text => format_text_report(&report),
+        ReportFormat::Github => format_github_report(&report),
+        ReportFormat::Markdown => format_markdown_report(&report),
+        ReportFormat::Json => serde_json::to_string_pretty(&report)? + "\n",
+    };
+    match args.out.as_deref() {
+        Some(out) => {
+            std::fs::write(out, output).with_context(|| format!("Failed to write {out:?}"))?
+        }
+        None => print!("{output}"),
+    }
+    if args.format == ReportFormat::Github {
+        if let Some(summary) = std::env::var_os("GITHUB_STEP_SUMMARY") {
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&summary)
+                .and_then(|mut file| file.write_all(format_markdown_report(&report).as_bytes()))
+                .with_context(|| format!("Failed to write the job summary {summary:?}"))?;
+        }
+    }
+
+    let exceeded = report.exceeded().count();
+    if exceeded > 0 {
+        bail!("{exceeded} size budget(s) exceeded");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{format_github_report, format_markdown_report, format_text_report, get_report};
+    use crate::{
+        config::{Budgets, ByteSize},
+        size_diff::read_build,
+        test_fixtures::split,
+    };
+
+    #[test]
+    fn reports_sizes_changes_and_budgets_as_github_annotations() {
+        let old = split("no_std_app.wasm", &[]);
+        // Folds every split module into the main module.
+        let new = split("no_std_app.wasm", &["--fold-threshold", "100000000"]);
+        let old = read_build(&old.dir).unwrap();
+        let new = read_build(&new.dir).unwrap();
+        let budgets = Budgets {
+            routes: BTreeMap::new(),
+            chunks: BTreeMap::from([("main".to_string(), ByteSize(1))]),
+        };
+        let report = get_report(&new, Some(&old), &budgets).unwrap();
+        assert_eq!(report.baseline.as_deref(), Some(old.build_id.as_str()));
+        assert_eq!(report.budgets.len(), 1);
+        assert!(report.budgets[0].excess().is_some());
+
+        let github = format_github_report(&report);
+        let lines = github.lines().collect::<Vec<_>>();
+        assert!(
+            lines[0].starts_with("::notice title=wasm-split::") && lines[0].contains("baseline"),
+            "{github}"
+        );
+        assert!(
+            lines[1].starts_with("::error title=wasm-split budget exceeded::Chunk main: "),
+            "{github}"
+        );
+        assert!(
+            lines[2].starts_with("::warning title=wasm-split chunk grew::Chunk main grew to "),
+            "{github}"
+        );
+        assert_eq!(lines.len(), 3, "{github}");
+
+        let markdown = format_markdown_report(&report);
+        assert!(markdown.contains("| `first` (removed) |"), "{markdown}");
+        assert!(markdown.contains("| chunk `main` |"), "{markdown}");
+        assert!(markdown.contains("**over by "), "{markdown}");
+
+        // Without a baseline, only the sizes are listed.
+        let report = get_report(&old, None, &Budgets::default()).unwrap();
+        let text = format_text_report(&report);
+        assert!(
+            text.lines().any(|line| line.starts_with("first ")),
+            "{text}"
+        );
+        assert!(!text.contains("added"), "{text}");
+        let github = format_github_report(&report);
+        assert_eq!(github.lines().count(), 1, "{github}");
+        assert!(!github.contains("baseline"), "{github}");
+    }
+}
@@ -0,0 +1,192 @@
+//! `wasm-split self-update`: replaces the running binary by a prebuilt
+//! release, for CI setups that install wasm-split without a Rust toolchain.
+//!
+//! `.github/workflows/release.yml` attaches one binary per platform to each
+//! release, named by [`asset_name`], along with a `.sig` file holding its
+//! Ed25519 signature by the release key, which is checked against
+//! [`RELEASE_PUBLIC_KEY`] before the binary is replaced. A digest published
+//! next to the binary would not do, as whoever can replace the one can
+//! replace the other, e.g. on a mirror. Releases are downloaded from
+//! [`DEFAULT_RELEASES_URL`] unless `WASM_SPLIT_RELEASES_URL` points to a
+//! mirror with the same layout.
+//!
+//! The maintainers generate the release key, and keep its private key only
+//! in the `RELEASE_SIGNING_KEY` secret of the repository:
+//!
+//! ```sh
+//! openssl genpkey -algorithm ed25519 -out release-key.pem
+//! wasm-split public-key release-key.pem release-key.pub
+//! base64 release-key.pub
+//! ```
+//!
+//! The last line is the value of [`RELEASE_PUBLIC_KEY`]. Until it is set,
+//! the `self-update` subcommand is hidden and refuses to install anything,
+//! and the release workflow only runs when started by hand, failing as well.
+//! Once it is, restore the workflow's trigger on version tags.
+
+use std::{ffi::OsString, path::Path, process::Command};
+
+use anyhow::{bail, Context, Result};
+use base64::Engine;
+use ed25519_dalek::PUBLIC_KEY_LENGTH;
+
+use crate::signing;
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Public key of the release signing key, in base64, whose private key is
+/// only known to the release workflow, as the `RELEASE_SIGNING_KEY` secret.
+/// Empty until the maintainers generate the key.
+const RELEASE_PUBLIC_KEY: &str = "";
+
+/// Whether this build can install releases, i.e. has a release key.
+pub const AVAILABLE: bool = !RELEASE_PUBLIC_KEY.is_empty();
+
+const DEFAULT_RELEASES_URL: &str = "https://github.com/gbj/wasm-split-prototype/releases";
+
+/// Upper bound on the size of a downloaded binary, well above that of an
+/// actual release.
+const MAX_DOWNLOAD_SIZE: u64 = 200 << 20;
+
+/// Name of the release asset for the platform this binary was built for, e.g.
+/// `wasm-split-aarch64-macos`.
+pub fn asset_name() -> String {
+    format!(
+        "wasm-split-{}-{}{}",
+        std::env::consts::ARCH,
+        std::env::consts::OS,
+        std::env::consts::EXE_SUFFIX
+    )
+}
+
+/// The release public key `encoded` in base64, failing for a build without
+/// one rather than installing binaries that nothing was checked against.
+fn release_public_key(encoded: &str) -> Result<[u8; PUBLIC_KEY_LENGTH]> {
+    if encoded.is_empty() {
+        bail!(
+            "This build of wasm-split has no release key to verify releases with; install it \
+             with `cargo install` instead"
+        );
+    }
+    base64::engine::general_purpose::STANDARD
+        .decode(encoded)
+        .ok()
+        .and_then(|key| key.try_into().ok())
+        .context("Invalid release public key")
+}
+
+fn download(url: &str) -> Result<Vec<u8>> {
+    let mut response = ureq::get(url)
+        .call()
+        .with_context(|| format!("Failed to download {url}"))?;
+    response
+        .body_mut()
+        .with_config()
+        .limit(MAX_DOWNLOAD_SIZE)
+        .read_to_vec()
+        .with_context(|| format!("Failed to download {url}"))
+}
+
+/// Writes `binary` next to `exe` and moves it into place. Windows does not
+/// allow overwriting a running executable, but does allow renaming it, so the
+/// current binary is first moved aside.
+fn replace_exe(exe: &Path, binary: &[u8]) -> Result<()> {
+    let with_suffix = |suffix: &str| {
+        let mut path = OsString::from(exe);
+        path.push(suffix);
+        path
+    };
+    let new = with_suffix(".new");
+    let old = with_suffix(".old");
+    std::fs::write(&new, binary).with_context(|| format!("Failed to write {new:?}"))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&new, std::fs::Permissions::from_mode(0o755))?;
+    }
+    let _ = std::fs::remove_file(&old);
+    std::fs::rename(exe, &old).with_context(|| format!("Failed to replace {exe:?}"))?;
+    if let Err(error) = std::fs::rename(&new, exe) {
+        let _ = std::fs::rename(&old, exe);
+        return Err(error).with_context(|| format!("Failed to replace {exe:?}"));
+    }
+    // Fails on Windows while the old binary is still running, in which case
+    // the next update removes it.
+    let _ = std::fs::remove_file(&old);
+    Ok(())
+}
+
+/// Installs `version`, or the latest release if `None`.
+pub fn run(version: Option<&str>) -> Result<()> {
+    let version = version.map(|version| version.trim_start_matches('v'));
+    if version == Some(VERSION) {
+        println!("wasm-split {VERSION} is already installed");
+        return Ok(());
+    }
+    let public_key = release_public_key(RELEASE_PUBLIC_KEY)?;
+    let releases = std::env::var("WASM_SPLIT_RELEASES_URL")
+        .unwrap_or_else(|_| DEFAULT_RELEASES_URL.to_string());
+    let releases = releases.trim_end_matches('/');
+    let base = match version {
+        Some(version) => format!("{releases}/download/v{version}"),
+        None => format!("{releases}/latest/download"),
+    };
+    let asset = asset_name();
+    let binary = download(&format!("{base}/{asset}"))?;
+    let signature = download(&format!("{base}/{asset}.sig"))?;
+    signing::verify(&public_key, &binary, &signature)
+        .with_context(|| format!("{base}/{asset} is not signed by the release key"))?;
+    let exe = std::env::current_exe().context("Failed to locate the running binary")?;
+    replace_exe(&exe, &binary)?;
+    let installed = Command::new(&exe)
+        .arg("--version")
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_default();
+    println!("Updated {} from {VERSION} to {installed}", exe.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::Engine;
+
+    use super::{asset_name, release_public_key, replace_exe, RELEASE_PUBLIC_KEY};
+    use crate::{
+        signing::{read_signing_key, sign, verify},
+        test_fixtures::{fixture_path, temp_dir},
+    };
+
+    #[test]
+    fn refuses_releases_without_a_release_key() {
+        // Set once the maintainers generate the key, which nothing else may
+        // stand in for.
+        if RELEASE_PUBLIC_KEY.is_empty() {
+            let error = release_public_key(RELEASE_PUBLIC_KEY).unwrap_err();
+            assert!(error.to_string().contains("no release key"), "{error}");
+        } else {
+            release_public_key(RELEASE_PUBLIC_KEY).unwrap();
+        }
+        assert!(release_public_key("c2hvcnQ=").is_err());
+
+        let public_key = std::fs::read(fixture_path("manifest-key.pub")).unwrap();
+        let public_key =
+            release_public_key(&base64::engine::general_purpose::STANDARD.encode(public_key))
+                .unwrap();
+        let signing_key = read_signing_key(&fixture_path("signing-key.pem")).unwrap();
+        let signature = sign(&signing_key, b"binary");
+        verify(&public_key, b"binary", &signature).unwrap();
+        assert!(verify(&public_key, b"tampered binary", &signature).is_err());
+    }
+
+    #[test]
+    fn replaces_the_binary() {
+        assert!(asset_name().starts_with(&format!("wasm-split-{}-", std::env::consts::ARCH)));
+        let dir = temp_dir();
+        let exe = dir.dir.join("wasm-split");
+        std::fs::write(&exe, "old").unwrap();
+        replace_exe(&exe, b"new").unwrap();
+        assert_eq!(std::fs::read(&exe).unwrap(), b"new");
+        assert_eq!(std::fs::read_dir(&dir.dir).unwrap().count(), 1);
+    }
+}
@@ -0,0 +1,675 @@
+//! `wasm-split serve`: builds the app with cargo, splits it, runs
+//! wasm-bindgen on its main module and serves the result, then does it all
+//! over whenever a source file changes, for developing against split builds.
+//!
+//! The steps are those of the example's `build.py`. The main module that the
+//! split writes is processed by wasm-bindgen into the output directory, and
+//! the other files of the split are copied next to it, unless the app does
+//! not use wasm-bindgen, in which case the split writes to the output
+//! directory directly. Files are served from `--root`, which holds the
+//! output directory and the app's `index.html`, with the content types and
+//! cache headers of [`StaticFiles`], and compressed with gzip unless the split
+//! wrote a precompressed variant with `--compress`.
+//!
+//! Changes are found by polling the modification times of the watched files,
+//! which needs no platform-specific file watching, and a failed build leaves
+//! the previous one being served.
+//!
+//! The split is made with `--hot-reload`, whose loader listens on the event
+//! stream at [`EVENTS_PATH`]. After each build, the server compares its
+//! manifest with that of the build before: if the main module came out the
+//! same, and so did the table slots and dependencies of every chunk, it sends
+//! a `swap` event with the chunks that changed, which the loader fetches and
+//! instantiates into the running page, over the table slots of their old
+//! instances. Any other change sends a `reload` event, on which the page
+//! reloads.
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use wasm_split_server::{accepts_encoding, StaticFiles};
+
+use crate::{
+    config::OutputPaths,
+    manifest::{ChunkKind, Manifest, ManifestChunk},
+    sink::DirectorySink,
+    size_diff::read_build,
+    Cli, OutputSink,
+};
+
+/// How often watched files are checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(300);
+
+/// Directories of build output, which are never watched.
+const IGNORED_DIRS: [&str; 3] = ["target", "node_modules", ".git"];
+
+/// Path of the event stream that the loaders of `--hot-reload` listen on.
+const EVENTS_PATH: &str = "/__wasm_split/events";
+
+/// Connections of the pages listening on [`EVENTS_PATH`].
+type Listeners = Arc<Mutex<Vec<TcpStream>>>;
+
+#[derive(Debug, clap::Args)]
+pub struct ServeArgs {
+    /// Directory of the app's cargo package, in which to run `cargo build`.
+    #[arg(long, value_name = "DIR", default_value = ".")]
+    package_dir: PathBuf,
+
+    /// Cargo features to build the app with, such as `split`.
+    #[arg(long, value_delimiter = ',', value_name = "FEATURES")]
+    features: Vec<String>,
+
+    /// Build the app without `--release`.
+    #[arg(long)]
+    dev: bool,
+
+    /// Directory to serve, such as the one holding the app's `index.html`.
+    #[arg(long, value_name = "DIR", default_value = ".")]
+    root: PathBuf,
+
+    /// Directory within `--root` to write the app to.
+    #[arg(long, value_name = "DIR", default_value = "pkg")]
+    out_dir: PathBuf,
+
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    address: String,
+
+    /// Also rebuild when this file, or a file in this directory, changes.
+    /// The `src` directory and `Cargo.toml` of the package, and the config
+    /// file of the split, are always watched.
+    #[arg(long = "watch", value_name = "PATH")]
+    watch: Vec<PathBuf>,
+
+    /// Options of the split, after `--`, such as `-- --fold-threshold 0`.
+    #[arg(last = true, value_name = "SPLIT_OPTIONS")]
+    split_options: Vec<String>,
+}
+
+pub fn run(args: &ServeArgs) -> Result<()> {
+    let out_dir = args.root.join(&args.out_dir);
+    // Checked once up front, rather than failing every build.
+    let split_cli = split_args(&args.split_options, Path::new("main.wasm"), &out_dir)?;
+
+    let listener = TcpListener::bind(&args.address)
+        .with_context(|| format!("Failed to listen on {}", args.address))?;
+    let files = Arc::new(StaticFiles::new(&args.root).precompressed(true));
+    let listeners = Listeners::default();
+    let connections = listeners.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let (files, listeners) = (files.clone(), connections.clone());
+            std::thread::spawn(move || handle_connection(&files, &listeners, stream));
+        }
+    });
+
+    let mut watched = vec![
+        args.package_dir.join("src"),
+        args.package_dir.join("Cargo.toml"),
+        split_cli
+            .config
+            .as_deref()
+            .unwrap_or(Path::new(crate::config::CONFIG_FILENAME))
+            .to_path_buf(),
+    ];
+    watched.extend(args.watch.iter().cloned());
+    let mut snapshot = None;
+    let mut manifest = None;
+    loop {
+        let current = get_snapshot(&watched, &out_dir);
+        if snapshot.as_ref() == Some(&current) {
+            std::thread::sleep(POLL_INTERVAL);
+            continue;
+        }
+        if snapshot.is_some() {
+            // Editors often write a file in several steps.
+            std::thread::sleep(POLL_INTERVAL);
+            if get_snapshot(&watched, &out_dir) != current {
+                continue;
+            }
+            println!("Change detected, rebuilding");
+        }
+        snapshot = Some(current);
+        let started = Instant::now();
+        match build(args, &out_dir) {
+            Ok(built) => {
+                println!(
+                    "Built in {:.1} s, serving {} at http://{}/",
+                    started.elapsed().as_secs_f64(),
+                    args.root.display(),
+                    args.address
+                );
+                if let Some(update) = manifest
+                    .as_ref()
+                    .and_then(|manifest| get_hot_update(manifest, &built))
+                {
+                    notify(&listeners, &update);
+                }
+                manifest = Some(built);
+            }
+            Err(error) => eprintln!("Build failed, still serving the previous one: {error:?}"),
+        }
+    }
+}
+
+/// Arguments of the split of `input` with `split_options`, as if given to
+/// `wasm-split` itself.
+pub(crate) fn split_args(split_options: &[String], input: &Path, output: &Path) -> Result<Cli> {
+    let cli = Cli::try_parse_from(
+        ["wasm-split".as_ref(), input.as_os_str(), output.as_os_str()]
+            .into_iter()
+            .chain(split_options.iter().map(|option| option.as_ref())),
+    )?;
+    if cli.command.is_some() {
+        bail!("The options of the split after `--` must not include a subcommand");
+    }
+    Ok(cli)
+}
+
+/// Builds the app into `out_dir` and returns the manifest of the split.
+fn build(args: &ServeArgs, out_dir: &Path) -> Result<Manifest> {
+    let input = cargo_build(args)?;
+    let mut split_options = args.split_options.clone();
+    if !split_options.iter().any(|option| option == "--hot-reload") {
+        split_options.push("--hot-reload".to_string());
+    }
+    let output = split_and_bind(&split_options, &input, out_dir, Path::new("wasm-bindgen"))?;
+    read_build(&out_dir.join(output.manifest))
+}
+
+/// What the pages of a build are told to do once `new` replaced it.
+#[derive(Debug)]
+enum HotUpdate {
+    /// Swap in these chunks of the new build.
+    Swap {
+        build_id: String,
+        chunks: Vec<ManifestChunk>,
+    },
+    Reload,
+}
+
+/// How pages running the build of `old` pick up that of `new`, if they need
+/// to: chunks can only be swapped if the main module is the same, which
+/// their code was linked against, and if each fills the same table slots
+/// and depends on the same chunks as before.
+fn get_hot_update(old: &Manifest, new: &Manifest) -> Option<HotUpdate> {
+    if old.build_id == new.build_id {
+        return None;
+    }
+    if old.chunks.len() != new.chunks.len() || old.aliases != new.aliases {
+        return Some(HotUpdate::Reload);
+    }
+    let find = |name: &str| old.chunks.iter().find(|chunk| chunk.name == name);
+    let mut chunks = Vec::new();
+    for chunk in new.chunks.iter() {
+        let Some(previous) = find(&chunk.name) else {
+            return Some(HotUpdate::Reload);
+        };
+        if previous.hash.is_some() && previous.hash == chunk.hash {
+            continue;
+        }
+        if chunk.kind == ChunkKind::Main
+            || previous.kind != chunk.kind
+            || previous.table_slots != chunk.table_slots
+            || previous.dependencies != chunk.dependencies
+        {
+            return Some(HotUpdate::Reload);
+        }
+        chunks.push(chunk.clone());
+    }
+    if chunks.is_empty() {
+        return Some(HotUpdate::Reload);
+    }
+    Some(HotUpdate::Swap {
+        build_id: new.build_id.clone(),
+        chunks,
+    })
+}
+
+/// The message of `update` on the event stream.
+fn event_message(update: &HotUpdate) -> String {
+    let (event, data) = match update {
+        HotUpdate::Swap { build_id, chunks } => (
+            "swap",
+            serde_json::json!({ "build_id": build_id, "chunks": chunks }),
+        ),
+        HotUpdate::Reload => ("reload", serde_json::json!({})),
+    };
+    format!("event: {event}\ndata: {data}\n\n")
+}
+
+/// Sends `update` to the pages listening for it, and forgets those that went
+/// away.
+fn notify(listeners: &Listeners, update: &HotUpdate) {
+    let message = event_message(update);
+    let mut listeners = listeners.lock().unwrap();
+    listeners.retain_mut(|stream| stream.write_all(message.as_bytes()).is_ok());
+    if let HotUpdate::Swap { chunks, .. } = update {
+        let names = chunks
+            .iter()
+            .map(|chunk| chunk.name.as_str())
+            .collect::<Vec<_>>();
+        println!(
+            "Swapping {} into {} open pages",
+            names.join(", "),
+            listeners.len()
+        );
+    }
+}
+
+/// Whether the module at `input` uses wasm-bindgen, whose output for it
+/// then replaces the main module.
+pub(crate) fn uses_wasm_bindgen(input: &Path) -> Result<bool> {
+    let module_bytes =
+        std::fs::read(input).with_context(|| format!("Failed to read {}", input.display()))?;
+    let module = crate::read::InputModule::parse(&module_bytes)?;
+    Ok(crate::toolchain::uses_wasm_bindgen(&module))
+}
+
+/// Splits `input` with `split_options` into `out_dir` and, if it uses
+/// wasm-bindgen, replaces its main module by the output of `wasm_bindgen`
+/// for it, moved to `main-bg` of `[output]` if set. Returns the output paths
+/// of the config, relative to `out_dir`.
+pub(crate) fn split_and_bind(
+    split_options: &[String],
+    input: &Path,
+    out_dir: &Path,
+    wasm_bindgen: &Path,
+) -> Result<OutputPaths> {
+    let cli = split_args(split_options, input, out_dir)?;
+    let output = crate::load_config(&cli)?.output;
+    if !uses_wasm_bindgen(input)? {
+        crate::split(&cli, &mut DirectorySink::new(out_dir))?;
+        return Ok(output);
+    }
+
+    let split_dir = std::env::temp_dir().join(format!("wasm-split-bind-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&split_dir);
+    let cli = split_args(split_options, input, &split_dir)?;
+    crate::split(&cli, &mut DirectorySink::new(&split_dir))?;
+    // Moved by `[output]`, along with its glue.
+    run_wasm_bindgen(
+        wasm_bindgen,
+        &split_dir.join(&output.main),
+        &out_dir.join(output.main.parent().unwrap_or(Path::new(""))),
+    )?;
+    let bindgen_main_bg = out_dir.join(output.bindgen_main_bg());
+    let main_bg = out_dir.join(output.main_bg());
+    if main_bg != bindgen_main_bg {
+        std::fs::rename(&bindgen_main_bg, &main_bg)
+            .with_context(|| format!("Failed to move {}", bindgen_main_bg.display()))?;
+    }
+    crate::snippets::run(
+        &out_dir.join(output.main.with_extension("js")),
+        &split_dir.join(&output.manifest),
+    )?;
+    // The fallback of `--emit-fallback` is instantiated by the glue of the
+    // main module, so only the module that wasm-bindgen makes of it is kept.
+    let split_fallback = split_dir.join(&output.fallback);
+    if split_fallback.exists() {
+        let bind_dir = split_dir.join("wasm-split-bind-fallback");
+        run_wasm_bindgen(wasm_bindgen, &split_fallback, &bind_dir)?;
+        let bound = bind_dir.join(output.fallback_bg().file_name().unwrap_or_default());
+        DirectorySink::new(out_dir).write(&output.fallback_bg(), &std::fs::read(&bound)?)?;
+        std::fs::remove_dir_all(&bind_dir)?;
+    }
+    let mut sink = DirectorySink::new(out_dir);
+    copy_files(
+        &split_dir,
+        Path::new(""),
+        &[&output.main, &output.fallback],
+        &mut sink,
+    )?;
+    std::fs::remove_dir_all(&split_dir)?;
+    Ok(output)
+}
+
+fn run_wasm_bindgen(wasm_bindgen: &Path, module: &Path, out_dir: &Path) -> Result<()> {
+    let status = Command::new(wasm_bindgen)
+        .arg(module)
+        .arg("--out-dir")
+        .arg(out_dir)
+        .args([
+            "--no-demangle",
+            "--no-typescript",
+            "--target",
+            "web",
+            "--keep-lld-exports",
+        ])
+        .status()
+        .with_context(|| format!("Failed to run {}", wasm_bindgen.display()))?;
+    if !status.success() {
+        bail!("wasm-bindgen failed with {status}");
+    }
+    Ok(())
+}
+
+/// Copies the files of the split other than the modules that wasm-bindgen
+/// replaces, including those in subdirectories with `[output]`.
+fn copy_files(
+    dir: &Path,
+    relative: &Path,
+    skipped: &[&Path],
+    sink: &mut DirectorySink,
+) -> Result<()> {
+    for entry in std::fs::read_dir(dir.join(relative))? {
+        let entry = entry?;
+        let path = relative.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_files(dir, &path, skipped, sink)?;
+        } else if !skipped.contains(&path.as_path()) {
+            sink.write(&path, &std::fs::read(entry.path())?)?;
+        }
+    }
+    Ok(())
+}
+
+/// Runs `cargo build` for the wasm target and returns the path of the module
+/// it built.
+fn cargo_build(args: &ServeArgs) -> Result<PathBuf> {
+    let cargo = std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let mut command = Command::new(cargo);
+    command
+        .current_dir(&args.package_dir)
+        .args([
+            "build",
+            "--target",
+            "wasm32-unknown-unknown",
+            "--message-format=json-render-diagnostics",
+        ])
+        .stderr(Stdio::inherit());
+    if !args.dev {
+        command.arg("--release");
+    }
+    if !args.features.is_empty() {
+        command.args(["--features", &args.features.join(",")]);
+    }
+    let output = command.output().context("Failed to run cargo build")?;
+    if !output.status.success() {
+        bail!("cargo build failed with {}", output.status);
+    }
+    get_wasm_artifact(&String::from_utf8_lossy(&output.stdout))
+        .context("cargo build did not build a .wasm file")
+}
+
+/// The last `.wasm` file among the artifacts of cargo's JSON messages, which
+/// is that of the package itself, as its dependencies are built first.
+fn get_wasm_artifact(messages: &str) -> Option<PathBuf> {
+    messages
+        .lines()
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        .filter(|message| message["reason"] == "compiler-artifact")
+        .flat_map(|message| message["filenames"].as_array().cloned().unwrap_or_default())
+        .filter_map(|filename| filename.as_str().map(PathBuf::from))
+        .rfind(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "wasm")
+        })
+}
+
+/// Paths and modification times of the files in `paths`, except for those in
+/// `out_dir`, which each build writes.
+fn get_snapshot(paths: &[PathBuf], out_dir: &Path) -> Vec<(PathBuf, SystemTime)> {
+    fn visit(path: &Path, out_dir: &Path, snapshot: &mut Vec<(PathBuf, SystemTime)>) {
+        let Ok(metadata) = std::fs::metadata(path) else {
+            return;
+        };
+        if !metadata.is_dir() {
+            if let Ok(modified) = metadata.modified() {
+                snapshot.push((path.to_path_buf(), modified));
+            }
+            return;
+        }
+        if path == out_dir
+            || path
+                .file_name()
+                .is_some_and(|name| IGNORED_DIRS.iter().any(|ignored| name == *ignored))
+        {
+            return;
+        }
+        for entry in std::fs::read_dir(path).into_iter().flatten().flatten() {
+            visit(&entry.path(), out_dir, snapshot);
+        }
+    }
+    let mut snapshot = Vec::new();
+    for path in paths {
+        visit(path, out_dir, &mut snapshot);
+    }
+    snapshot.sort();
+    snapshot
+}
+
+fn handle_connection(files: &StaticFiles, listeners: &Listeners, mut stream: TcpStream) {
+    let mut head = Vec::new();
+    let mut reader = BufReader::new(&stream);
+    loop {
+        let mut line = String::new();
+        match reader.read_line(&mut line) {
+            Ok(0) | Err(_) => return,
+            Ok(_) if line == "\r\n" || line == "\n" => break,
+            Ok(_) => head.push(line.trim_end().to_string()),
+        }
+    }
+    if is_events_request(&head) {
+        // Kept open, for `notify` to write the events of later builds to.
+        let head = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\
+                    Cache-Control: no-cache\r\n\r\n";
+        if stream.write_all(head.as_bytes()).is_ok() {
+            listeners.lock().unwrap().push(stream);
+        }
+        return;
+    }
+    let response = respond(files, &head);
+    // The client may have gone away, which is no concern of the server.
+    let _ = stream.write_all(&response);
+}
+
+/// Whether the request with `head` is one for the event stream.
+fn is_events_request(head: &[String]) -> bool {
+    let mut request_line = head.first().map_or("", String::as_str).split(' ');
+    let (method, target) = (request_line.next(), request_line.next().unwrap_or("/"));
+    method == Some("GET") && target.split('?').next() == Some(EVENTS_PATH)
+}
+
+/// The response to a request with `head`, the request line and header lines.
+fn respond(files: &StaticFiles, head: &[String]) -> Vec<u8> {
+    let mut request_line = head.first().map_or("", String::as_str).split(' ');
+    let (method, target) = (request_line.next(), request_line.next().unwrap_or("/"));
+    if !matches!(method, Some("GET" | "HEAD")) {
+        return response("405 Method Not Allowed", &[], b"", method != Some("HEAD"));
+    }
+    let header = |name: &str| {
+        head.iter().skip(1).find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.trim().eq_ignore_ascii_case(name).then(|| value.trim())
+        })
+    };
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (target, None),
+    };
+    let path = if path.ends_with('/') {
+        format!("{path}index.html")
+    } else {
+        path.to_string()
+    };
+    let accept_encoding = header("Accept-Encoding");
+    let body = method == Some("GET");
+    let Some(file) = files.resolve(&path, query, accept_encoding) else {
+        return response("404 Not Found", &[], b"Not found", body);
+    };
+    let contents = match file.contents.read() {
+        Ok(contents) => contents,
+        Err(_) => return response("404 Not Found", &[], b"Not found", body),
+    };
+    let mut headers = vec![
+        ("Content-Type", file.content_type),
+        ("Cache-Control", file.cache_control),
+    ];
+    let compressible = file.content_type != "application/octet-stream";
+    let compressed = match file.content_encoding {
+        Some(encoding) => {
+            headers.push(("Content-Encoding", encoding));
+            None
+        }
+        None if compressible && accepts_encoding(accept_encoding, "gzip") => {
+            headers.push(("Content-Encoding", "gzip"));
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+            encoder
+                .write_all(&contents)
+                .and_then(|()| encoder.finish())
+                .ok()
+        }
+        None => None,
+    };
+    headers.push(("Vary", "Accept-Encoding"));
+    response(
+        "200 OK",
+        &headers,
+        compressed.as_deref().unwrap_or(&contents),
+        body,
+    )
+}
+
+fn response(status: &str, headers: &[(&str, &str)], contents: &[u8], body: bool) -> Vec<u8> {
+    let mut response = format!(
+        "HTTP/1.1 {status}\r\nContent-Length: {}\r\n",
+        contents.len()
+    );
+    for (name, value) in headers {
+        response.push_str(&format!("{name}: {value}\r\n"));
+    }
+    response.push_str("Connection: close\r\n\r\n");
+    let mut response = response.into_bytes();
+    if body {
+        response.extend_from_slice(contents);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    fn get(files: &StaticFiles, target: &str, accept_encoding: &str) -> (String, Vec<u8>) {
+        let head = [
+            format!("GET {target} HTTP/1.1"),
+            format!("Accept-Encoding: {accept_encoding}"),
+        ];
+        let response = respond(files, &head);
+        let end = response
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+            .unwrap();
+        (
+            String::from_utf8(response[..end].to_vec()).unwrap(),
+            response[end + 4..].to_vec(),
+        )
+    }
+
+    #[test]
+    fn serves_split_output_compressed() {
+        let output = crate::test_fixtures::split("no_std_app.wasm", &["--compress", "gzip"]);
+        let files = StaticFiles::new(&output.dir).precompressed(true);
+
+        let (head, body) = get(&files, "/main.wasm", "gzip, br;q=0");
+        assert!(head.contains("Content-Type: application/wasm"));
+        assert!(head.contains("Content-Encoding: gzip"));
+        assert_eq!(body, output.read("main.wasm.gz"));
+
+        // Compressed on the fly, as there is no precompressed variant.
+        let (head, body) = get(&files, "/__wasm_split.js", "gzip");
+        assert!(head.contains("Content-Type: text/javascript"));
+        let mut script = Vec::new();
+        flate2::read::GzDecoder::new(&body[..])
+            .read_to_end(&mut script)
+            .unwrap();
+        assert_eq!(script, output.read("__wasm_split.js"));
+
+        let (head, body) = get(&files, "/__wasm_split.js", "identity");
+        assert!(!head.contains("Content-Encoding"));
+        assert_eq!(body, output.read("__wasm_split.js"));
+
+        let (head, _) = get(&files, "/../secret", "");
+        assert!(head.starts_with("HTTP/1.1 404"));
+    }
+
+    #[test]
+    fn swaps_chunks_of_builds_with_the_same_main_module() {
+        let output = crate::test_fixtures::split("no_std_app.wasm", &[]);
+        let old = read_build(&output.dir).unwrap();
+        assert!(get_hot_update(&old, &old).is_none());
+
+        let rebuilt = |name: &str| {
+            let mut new = old.clone();
+            new.build_id = "rebuilt".to_string();
+            let chunk = new
+                .chunks
+                .iter_mut()
+                .find(|chunk| chunk.name == name)
+                .unwrap();
+            chunk.hash = Some("changed".to_string());
+            new
+        };
+        let Some(HotUpdate::Swap { build_id, chunks }) = get_hot_update(&old, &rebuilt("first"))
+        else {
+            panic!("expected a swap");
+        };
+        assert_eq!(build_id, "rebuilt");
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].name, "first");
+        assert_eq!(chunks[0].hash.as_deref(), Some("changed"));
+        assert!(event_message(&HotUpdate::Swap { build_id, chunks })
+            .starts_with("event: swap\ndata: {\"build_id\":\"rebuilt\",\"chunks\":[{"));
+
+        assert!(matches!(
+            get_hot_update(&old, &rebuilt("main")),
+            Some(HotUpdate::Reload)
+        ));
+        let mut moved = rebuilt("first");
+        let first = moved
+            .chunks
+            .iter_mut()
+            .find(|chunk| chunk.name == "first")
+            .unwrap();
+        first.table_slots = first.table_slots.start + 1..first.table_slots.end + 1;
+        assert!(matches!(
+            get_hot_update(&old, &moved),
+            Some(HotUpdate::Reload)
+        ));
+        assert_eq!(
+            event_message(&HotUpdate::Reload),
+            "event: reload\ndata: {}\n\n"
+        );
+
+        assert!(is_events_request(&[
+            "GET /__wasm_split/events HTTP/1.1".to_string()
+        ]));
+        assert!(!is_events_request(&["GET / HTTP/1.1".to_string()]));
+    }
+
+    #[test]
+    fn finds_wasm_artifact_of_package() {
+        let messages = [
+            r#"{"reason":"compiler-artifact","filenames":["/t/deps/libserde.rlib"]}"#,
+            r#"{"reason":"compiler-artifact","filenames":["/t/wasm32-unknown-unknown/release/app.wasm"]}"#,
+            r#"{"reason":"build-finished","success":true}"#,
+        ]
+        .join("\n");
+        assert_eq!(
+            get_wasm_artifact(&messages),
+            Some(PathBuf::from("/t/wasm32-unknown-unknown/release/app.wasm"))
+        );
+        assert_eq!(get_wasm_artifact(""), None);
+    }
+}
@@ -0,0 +1,247 @@
+//! Signatures of the split functions, so that a chunk left over from another
+//! build fails to load with the names of the functions whose signatures
+//! changed, rather than with a `RuntimeError` on the first call through a
+//! table slot of the wrong type.
+//!
+//! `#[wasm_split]` records a hash of the Rust signature of every split
+//! function in the [`INPUT_SIGNATURES_SECTION`], which tells apart types
+//! that wasm does not, such as references to two different structs. The
+//! signature of a split point is a hash of that and of its wasm type. Every
+//! chunk carries those of the split points it defines in a
+//! [`SIGNATURES_SECTION`], and its manifest entry lists them as well, for the
+//! loader and `wasm-split verify` to check the chunk against.
+
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::{bail, Context, Result};
+use wasmparser::Payload;
+
+use crate::{
+    manifest::{content_hash, ChunkKind, MANIFEST_FILENAME},
+    publish_diff::read_manifest,
+    read::InputModule,
+    split_point::{get_split_function_name, split_function_name, SplitPoint},
+};
+
+/// Custom section of the input, with a `<export> <hash>` line for each split
+/// function.
+pub const INPUT_SIGNATURES_SECTION: &str = "__wasm_split_signatures";
+
+/// Custom section of a chunk, with a `<export> <signature>` line for each
+/// split point that it defines.
+pub const SIGNATURES_SECTION: &str = "wasm_split_signatures";
+
+/// Signatures by export name of the split point.
+pub type Signatures = BTreeMap<String, String>;
+
+fn parse(data: &[u8], section: &str) -> Result<Signatures> {
+    let data = std::str::from_utf8(data)
+        .with_context(|| format!("The {section} custom section is not UTF-8"))?;
+    data.lines()
+        .map(|line| match line.split_once(' ') {
+            Some((export, hash)) => Ok((export.to_string(), hash.to_string())),
+            None => bail!("Invalid record {line:?} of the {section} custom section"),
+        })
+        .collect()
+}
+
+fn describe(ty: &wasmparser::FuncType) -> String {
+    let list =
+        |types: &[wasmparser::ValType]| types.iter().map(|ty| format!(" {ty}")).collect::<String>();
+    format!(
+        "(param{}) (result{})",
+        list(ty.params()),
+        list(ty.results())
+    )
+}
+
+/// Checks that the stub of every split point in the main module has the type
+/// of the split function, which both are generated with by `#[wasm_split]`.
+pub fn check(module: &InputModule, split_points: &[SplitPoint]) -> Result<()> {
+    for split_point in split_points.iter() {
+        let stub = &module.types[module.func_type_id(split_point.import_func)];
+        let split = &module.types[module.func_type_id(split_point.export_func)];
+        if stub != split {
+            bail!(
+                "Split function `{}` of module {:?} has the type {}, but its stub in the main \
+                 module calls it as {}. As both are generated from the same #[wasm_split] \
+                 function, part of the build is stale; rebuild the crate after `cargo clean -p`.",
+                get_split_function_name(module, split_point),
+                split_point.module_name,
+                describe(split),
+                describe(stub)
+            );
+        }
+    }
+    Ok(())
+}
+
+/// The signatures of `split_points`.
+pub fn get_signatures<'a>(
+    module: &InputModule,
+    split_points: impl IntoIterator<Item = &'a SplitPoint>,
+) -> Result<Signatures> {
+    let mut declared = Signatures::new();
+    for section in module
+        .custom_sections
+        .iter()
+        .filter(|section| section.name == INPUT_SIGNATURES_SECTION)
+    {
+        declared.extend(parse(section.data, INPUT_SIGNATURES_SECTION)?);
+    }
+    Ok(split_points
+        .into_iter()
+        .map(|split_point| {
+            let export = module.exports[split_point.export].name;
+            let ty = describe(&module.types[module.func_type_id(split_point.export_func)]);
+            // Inputs built with an earlier version of the macro have none.
+            let declared = declared.get(export).map_or("", String::as_str);
+            let signature = content_hash(format!("{ty} {declared}").as_bytes());
+            (export.to_string(), signature)
+        })
+        .collect())
+}
+
+/// Custom section listing `signatures`.
+pub fn section(signatures: &Signatures) -> wasm_encoder::CustomSection<'static> {
+    let records = signatures
+        .iter()
+        .map(|(export, signature)| format!("{export} {signature}\n"))
+        .collect::<String>();
+    wasm_encoder::CustomSection {
+        name: SIGNATURES_SECTION.into(),
+        data: records.into_bytes().into(),
+    }
+}
+
+/// The signatures in the [`SIGNATURES_SECTION`] of the encoded module `data`.
+pub fn read(data: &[u8]) -> Result<Signatures> {
+    let mut signatures = Signatures::new();
+    for payload in wasmparser::Parser::new(0).parse_all(data) {
+        if let Payload::CustomSection(reader) = payload? {
+            if reader.name() == SIGNATURES_SECTION {
+                signatures.extend(parse(reader.data(), SIGNATURES_SECTION)?);
+            }
+        }
+    }
+    Ok(signatures)
+}
+
+/// The export names of the split points whose signatures differ in
+/// `actual` from those in `expected`, or are missing.
+pub fn mismatches<'a>(expected: &'a Signatures, actual: &Signatures) -> Vec<&'a str> {
+    expected
+        .iter()
+        .filter(|&(export, signature)| actual.get(export) != Some(signature))
+        .map(|(export, _)| export.as_str())
+        .collect()
+}
+
+/// Checks the chunks in the output directory `dir` against the manifest
+/// there, or at `manifest` if given: that they are valid, link against the
+/// main module, and define the split functions with the signatures of the
+/// build, as `wasm-split verify`.
+pub fn verify(dir: &Path, manifest: Option<&Path>) -> Result<()> {
+    let manifest_path = manifest.map_or_else(|| dir.join(MANIFEST_FILENAME), Path::to_path_buf);
+    let manifest = read_manifest(&manifest_path)?;
+    let mut modules = Vec::new();
+    for chunk in manifest.chunks.iter() {
+        let path = dir.join(&chunk.file);
+        let data = std::fs::read(&path).with_context(|| format!("Failed to read {path:?}"))?;
+        modules.push((chunk.name.clone(), data));
+    }
+    let mut problems = Vec::new();
+    for (chunk, (_, data)) in manifest.chunks.iter().zip(modules.iter()) {
+        if chunk.kind == ChunkKind::Main {
+            continue;
+        }
+        let mismatched = mismatches(&chunk.signatures, &read(data)?);
+        if !mismatched.is_empty() {
+            problems.push(format!(
+                "chunk {:?} ({}) defines {} with other signatures than those of the build, so \
+                 it is likely left over from another one",
+                chunk.name,
+                chunk.file,
+                mismatched
+                    .iter()
+                    .map(|export| format!("`{}`", split_function_name(export)))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+    }
+    let modules = modules
+        .iter()
+        .map(|(name, data)| (name.clone(), data.as_slice()))
+        .collect::<Vec<_>>();
+    if let Err(error) = crate::validate::check(&modules) {
+        problems.push(format!("{error:#}"));
+    }
+    if !problems.is_empty() {
+        bail!(
+            "The build in {dir:?} would fail to load:\n  {}",
+            problems.join("\n  ")
+        );
+    }
+    eprintln!(
+        "Verified {} modules against {manifest_path:?}",
+        manifest.chunks.len()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{mismatches, read, Signatures};
+    use crate::{manifest::ChunkKind, test_fixtures::split};
+
+    #[test]
+    fn chunks_carry_the_signatures_of_their_entries() {
+        let output = split("no_std_app.wasm", &[]);
+        let manifest = output.manifest();
+        let mut checked = 0;
+        for chunk in manifest["chunks"].as_array().unwrap() {
+            let expected: Signatures =
+                serde_json::from_value(chunk["signatures"].clone()).unwrap_or_default();
+            let entries = chunk["entries"].as_array().map_or(0, Vec::len);
+            let actual = read(&output.read(chunk["file"].as_str().unwrap())).unwrap();
+            if chunk["kind"] == serde_json::json!(ChunkKind::Main) {
+                assert!(expected.is_empty() && actual.is_empty());
+                continue;
+            }
+            assert_eq!(expected.len(), entries);
+            assert_eq!(actual, expected);
+            checked += entries;
+        }
+        assert!(checked > 0);
+        super::verify(&output.dir, None).unwrap();
+    }
+
+    #[test]
+    fn reports_chunks_of_another_build() {
+        let output = split("no_std_app.wasm", &[]);
+        let closures = split("closure_app.wasm", &[]);
+        let manifest = output.manifest();
+        let chunk = manifest["chunks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|chunk| chunk["kind"] != serde_json::json!(ChunkKind::Main))
+            .unwrap();
+        let file = chunk["file"].as_str().unwrap();
+        let expected: Signatures = serde_json::from_value(chunk["signatures"].clone()).unwrap();
+        assert_eq!(
+            mismatches(&expected, &Signatures::new()),
+            expected.keys().map(String::as_str).collect::<Vec<_>>()
+        );
+        std::fs::copy(closures.dir.join("counter.wasm"), output.dir.join(file)).unwrap();
+        let error = super::verify(&output.dir, None).unwrap_err().to_string();
+        assert!(
+            error.contains(&format!(
+                "chunk {:?} ({file}) defines `",
+                chunk["name"].as_str().unwrap()
+            )),
+            "{error}"
+        );
+    }
+}
@@ -0,0 +1,211 @@
+//! Ed25519 signatures of the manifest, for deployments whose CDN is not fully
+//! trusted.
+//!
+//! The app compiles the public key into its main module with
+//! `wasm_split::manifest_public_key!`, which exports it as
+//! [`PUBLIC_KEY_EXPORT`]. If the input has that export, the manifest must be
+//! signed with the matching private key given by `--signing-key`, and the
+//! signature is written next to the manifest with the extension
+//! [`SIGNATURE_EXTENSION`]. The loader and the service worker then only
+//! honor the chunk URLs of a manifest they fetch once its signature verifies.
+//! The manifest embedded in the loader gets its own signature, as a CDN
+//! serving the loader can as well change it, and since a signature of the
+//! manifest says nothing about the chunks it points to, the loader compares
+//! each chunk with the SHA-256 digest the manifest lists for it.
+
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context, Result};
+use ed25519_dalek::{
+    pkcs8::DecodePrivateKey, Signature, Signer, SigningKey, VerifyingKey, PUBLIC_KEY_LENGTH,
+};
+use wasmparser::{DataKind, ExternalKind};
+
+use crate::read::{const_i32, InputModule};
+
+pub const PUBLIC_KEY_EXPORT: &str = "__wasm_split_manifest_public_key";
+
+pub const SIGNATURE_EXTENSION: &str = "sig";
+
+/// Reads a PKCS#8 private key in PEM format, as generated by `openssl genpkey
+/// -algorithm ed25519`.
+pub fn read_signing_key(path: &Path) -> Result<SigningKey> {
+    let pem = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read signing key {path:?}"))?;
+    SigningKey::from_pkcs8_pem(&pem)
+        .map_err(|error| anyhow!("Invalid Ed25519 signing key {path:?}: {error}"))
+}
+
+/// The public key exported by the input, read from its data segments.
+fn get_embedded_public_key(module: &InputModule) -> Result<Option<[u8; PUBLIC_KEY_LENGTH]>> {
+    let Some(export) = module
+        .exports
+        .iter()
+        .find(|export| export.name == PUBLIC_KEY_EXPORT)
+    else {
+        return Ok(None);
+    };
+    // A `static` is exported as an immutable global holding its address.
+    let imported_globals = module
+        .imports
+        .iter()
+        .filter(|import| matches!(import.ty, wasmparser::TypeRef::Global(_)))
+        .count();
+    let address = (export.kind == ExternalKind::Global)
+        .then(|| (export.index as usize).checked_sub(imported_globals))
+        .flatten()
+        .and_then(|index| module.globals.get(index))
+        .and_then(|global| const_i32(&global.init_expr))
+        .ok_or_else(|| anyhow!("Export {PUBLIC_KEY_EXPORT} is not the address of a static"))?
+        as usize;
+    for segment in module.data_segments.iter() {
+        let DataKind::Active { offset_expr, .. } = &segment.kind else {
+            continue;
+        };
+        let Some(offset) = const_i32(offset_expr) else {
+            continue;
+        };
+        let Some(start) = address.checked_sub(offset as usize) else {
+            continue;
+        };
+        if let Some(key) = segment.data.get(start..start + PUBLIC_KEY_LENGTH) {
+            return Ok(Some(key.try_into().unwrap()));
+        }
+    }
+    bail!("The data of export {PUBLIC_KEY_EXPORT} was not found in the input's data segments")
+}
+
+/// Checks that the manifest is signed if and only if the input embeds a public
+/// key, and with the matching private key.
+pub fn check_signing_key(module: &InputModule, signing_key: Option<&SigningKey>) -> Result<()> {
+    let embedded = get_embedded_public_key(module)?;
+    match (embedded, signing_key) {
+        (None, None) => Ok(()),
+        (Some(_), None) => bail!(
+            "The input embeds a manifest public key with `manifest_public_key!`, so the \
+             loader only accepts signed manifests. Pass the private key with --signing-key."
+        ),
+        (None, Some(_)) => bail!(
+            "--signing-key was given, but the input does not embed the public key to verify \
+             the manifest with. Add `wasm_split::manifest_public_key!` to the app, with the \
+             key written by `wasm-split public-key`."
+        ),
+        (Some(embedded), Some(signing_key)) => {
+            if embedded != signing_key.verifying_key().to_bytes() {
+                bail!(
+                    "The manifest public key embedded in the input does not belong to the \
+                     signing key given by --signing-key."
+                );
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Signature of the manifest file's exact contents.
+pub fn sign(signing_key: &SigningKey, manifest: &[u8]) -> Vec<u8> {
+    signing_key.sign(manifest).to_bytes().to_vec()
+}
+
+/// Checks an Ed25519 signature of `data` made with the private key of
+/// `public_key`, as written by [`sign`] or `openssl pkeyutl -sign -rawin`.
+pub fn verify(public_key: &[u8; PUBLIC_KEY_LENGTH], data: &[u8], signature: &[u8]) -> Result<()> {
+    let public_key = VerifyingKey::from_bytes(public_key).context("Invalid Ed25519 public key")?;
+    let signature = Signature::from_slice(signature).context("Invalid Ed25519 signature")?;
+    public_key
+        .verify_strict(data, &signature)
+        .context("The signature does not verify")
+}
+
+/// `wasm-split public-key`: writes the raw public key of a signing key, for
+/// `include_bytes!` in `manifest_public_key!`.
+pub fn write_public_key(signing_key: &Path, out: &Path) -> Result<()> {
+    let signing_key = read_signing_key(signing_key)?;
+    std::fs::write(out, signing_key.verifying_key().to_bytes())
+        .with_context(|| format!("Failed to write {out:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_fixtures::{
+        expected_no_std_app_result, fixture_path, split, try_split, SplitOutput,
+    };
+
+    use super::{read_signing_key, sign, verify};
+
+    #[test]
+    fn verifies_signatures_of_matching_key() {
+        let signing_key = read_signing_key(&fixture_path("signing-key.pem")).unwrap();
+        let public_key = std::fs::read(fixture_path("manifest-key.pub")).unwrap();
+        let public_key = public_key.try_into().unwrap();
+        let signature = sign(&signing_key, b"data");
+        verify(&public_key, b"data", &signature).unwrap();
+        assert!(verify(&public_key, b"other data", &signature).is_err());
+        assert!(verify(&public_key, b"data", &signature[1..]).is_err());
+    }
+
+    fn split_signed() -> SplitOutput {
+        let signing_key = fixture_path("signing-key.pem");
+        split(
+            "no_std_app_signed.wasm",
+            &["--signing-key", signing_key.to_str().unwrap()],
+        )
+    }
+
+    #[test]
+    fn requires_signing_key_for_embedded_public_key() {
+        let (_, result) = try_split("no_std_app_signed.wasm", "", &[]);
+        let err = result.expect_err("the fixture embeds a public key");
+        assert!(format!("{err:#}").contains("--signing-key"), "{err:#}");
+    }
+
+    #[test]
+    fn loads_chunks_of_signed_manifest() {
+        let output = split_signed();
+        assert!(output.dir.join("wasm-split-manifest.json.sig").exists());
+        if let Some(result) = output.run_no_std_app(4) {
+            assert_eq!(result, expected_no_std_app_result(4));
+        }
+        // Chunks compile while they download, even though they are checked.
+        if let Some(result) = output.run_no_std_app_streaming_only(4) {
+            assert_eq!(result, expected_no_std_app_result(4));
+        }
+    }
+
+    #[test]
+    fn rejects_chunk_not_matching_digest() {
+        let output = split_signed();
+        // Still a valid module, with an empty custom section named "x".
+        let mut chunk = output.read("first.wasm");
+        chunk.extend([0, 2, 1, b'x']);
+        output.write("first.wasm", &chunk);
+        if let Some(result) = output.try_run_no_std_app(4) {
+            let stderr = result.expect_err("the chunk was modified");
+            assert!(
+                stderr.contains("does not match the SHA-256 digest in the signed manifest"),
+                "{stderr}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_modified_embedded_manifest() {
+        let output = split_signed();
+        let loader = String::from_utf8(output.read("__wasm_split.js")).unwrap();
+        let start = loader.find("const MANIFEST_JSON = ").unwrap();
+        let end = start + loader[start..].find('\n').unwrap();
+        let modified = loader[start..end].replacen("first.wasm", "second.wasm", 1);
+        assert_ne!(modified, loader[start..end]);
+        output.write(
+            "__wasm_split.js",
+            format!("{}{modified}{}", &loader[..start], &loader[end..]).as_bytes(),
+        );
+        if let Some(result) = output.try_run_no_std_app(4) {
+            let stderr = result.expect_err("the embedded manifest was modified");
+            assert!(
+                stderr.contains("Invalid signature of the manifest embedded in"),
+                "{stderr}"
+            );
+        }
+    }
+}
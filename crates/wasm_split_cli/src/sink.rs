@@ -0,0 +1,148 @@
+//! Where [`crate::split`] hands its output: the files of the build, and then
+//! its manifest as a typed value.
+//!
+//! The command line tool writes to a directory with [`DirectorySink`]. A
+//! build service can implement [`OutputSink`] to stream each file straight
+//! to object storage, such as with one PUT request per file to an S3 bucket,
+//! and publish the build once the manifest arrives. [`MemorySink`] keeps
+//! everything in memory, for test harnesses.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+
+use crate::manifest::Manifest;
+
+pub trait OutputSink {
+    /// Receives the file at `path`, relative to the output directory, or to
+    /// its subdirectory of `--asset-version` if given. Paths are relative
+    /// and normalized, but may have several components with `[output]`.
+    fn write(&mut self, path: &Path, contents: &[u8]) -> Result<()>;
+
+    /// Receives the manifest once every file of the build has been written,
+    /// including its own JSON encoding at the path of `[output]`.
+    fn finish(&mut self, manifest: &Manifest) -> Result<()> {
+        let _ = manifest;
+        Ok(())
+    }
+}
+
+/// Writes the output to a directory, creating it and its subdirectories as
+/// needed. Files that already have the same contents are left alone, so that
+/// a dev server watching the directory only sees the chunks that changed.
+#[derive(Debug)]
+pub struct DirectorySink {
+    dir: PathBuf,
+}
+
+impl DirectorySink {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+impl OutputSink for DirectorySink {
+    fn write(&mut self, path: &Path, contents: &[u8]) -> Result<()> {
+        let path = self.dir.join(path);
+        std::fs::create_dir_all(path.parent().unwrap_or(&self.dir))
+            .with_context(|| format!("Failed to create the directory of {path:?}"))?;
+        if std::fs::read(&path).is_ok_and(|existing| existing == contents) {
+            return Ok(());
+        }
+        std::fs::write(&path, contents).with_context(|| format!("Failed to write {path:?}"))
+    }
+}
+
+/// Keeps the output in memory.
+#[derive(Debug, Default)]
+pub struct MemorySink {
+    pub files: BTreeMap<PathBuf, Vec<u8>>,
+    pub manifest: Option<Manifest>,
+}
+
+impl OutputSink for MemorySink {
+    fn write(&mut self, path: &Path, contents: &[u8]) -> Result<()> {
+        self.files.insert(path.to_path_buf(), contents.to_vec());
+        Ok(())
+    }
+
+    fn finish(&mut self, manifest: &Manifest) -> Result<()> {
+        self.manifest = Some(manifest.clone());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::File,
+        path::Path,
+        time::{Duration, SystemTime},
+    };
+
+    use clap::Parser;
+
+    use super::{DirectorySink, MemorySink, OutputSink};
+    use crate::{
+        test_fixtures::{fixture_path, split},
+        Cli,
+    };
+
+    #[test]
+    fn splits_into_memory() {
+        let input = fixture_path("no_std_app.wasm");
+        let args = Cli::parse_from([
+            "wasm-split".as_ref(),
+            input.as_os_str(),
+            // Not written to.
+            "/nonexistent".as_ref(),
+            "--fold-threshold".as_ref(),
+            "0".as_ref(),
+        ]);
+        let mut sink = MemorySink::default();
+        crate::split(&args, &mut sink).unwrap();
+        assert!(!Path::new("/nonexistent").exists());
+
+        let manifest = sink.manifest.unwrap();
+        let on_disk = split("no_std_app.wasm", &[]);
+        assert_eq!(serde_json::to_value(&manifest).unwrap(), on_disk.manifest());
+        for chunk in manifest.chunks.iter() {
+            assert_eq!(
+                sink.files[Path::new(&chunk.file)],
+                on_disk.read(&chunk.file)
+            );
+        }
+        assert!(sink.files.contains_key(Path::new("__wasm_split.js")));
+    }
+
+    #[test]
+    fn leaves_unchanged_files_alone() {
+        let dir = std::env::temp_dir().join(format!("wasm-split-sink-{}", std::process::id()));
+        let mut sink = DirectorySink::new(&dir);
+        let path = Path::new("chunks/a.wasm");
+        sink.write(path, b"a").unwrap();
+        let earlier = SystemTime::now() - Duration::from_secs(60);
+        File::options()
+            .write(true)
+            .open(dir.join(path))
+            .unwrap()
+            .set_modified(earlier)
+            .unwrap();
+        let modified = || {
+            std::fs::metadata(dir.join(path))
+                .unwrap()
+                .modified()
+                .unwrap()
+        };
+
+        sink.write(path, b"a").unwrap();
+        assert_eq!(modified(), earlier);
+        sink.write(path, b"b").unwrap();
+        assert_ne!(modified(), earlier);
+        assert_eq!(std::fs::read(dir.join(path)).unwrap(), b"b");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,383 @@
+//! `wasm-split diff`: compares the chunk sizes of two builds, from their
+//! manifests, to see what a change costs before it is merged.
+//!
+//! Chunks are matched by name, so the report lists the size of each chunk in
+//! either build, the chunks that only one of them has, and the totals. With
+//! `--markdown`, it is a table to post as a comment on the pull request, in
+//! which chunks whose size did not change are only counted.
+//!
+//! Chunks whose contents changed are downloaded again by returning visitors,
+//! even if their size did not change, so both formats also report this churn
+//! from the hashes of the chunks: ideally, a change only changes the chunks
+//! of the code it touches.
+
+use std::{collections::BTreeMap, fmt::Write, path::Path};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::{
+    config::ByteSize,
+    manifest::{Manifest, MANIFEST_FILENAME},
+    publish_diff::read_manifest,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChunkDelta {
+    pub name: String,
+    /// Size in the old build, if it has the chunk.
+    pub old: Option<usize>,
+    /// Size in the new build, if it has the chunk.
+    pub new: Option<usize>,
+    /// Gzip sizes, if both manifests record them.
+    pub old_gzip: Option<usize>,
+    pub new_gzip: Option<usize>,
+    /// Content hashes, if both manifests record them.
+    pub old_hash: Option<String>,
+    pub new_hash: Option<String>,
+}
+
+impl ChunkDelta {
+    pub fn delta(&self) -> i64 {
+        self.new.unwrap_or(0) as i64 - self.old.unwrap_or(0) as i64
+    }
+
+    pub fn gzip_delta(&self) -> Option<i64> {
+        match (self.old, self.old_gzip, self.new, self.new_gzip) {
+            (Some(_), Some(old), Some(_), Some(new)) => Some(new as i64 - old as i64),
+            (None, _, Some(_), Some(new)) => Some(new as i64),
+            (Some(_), Some(old), None, _) => Some(-(old as i64)),
+            _ => None,
+        }
+    }
+
+    /// Whether the chunk is in both builds with other contents, if both
+    /// manifests record its hash.
+    pub fn content_changed(&self) -> Option<bool> {
+        Some(self.old_hash.as_ref()? != self.new_hash.as_ref()?)
+    }
+
+    fn status(&self) -> &'static str {
+        match (self.old, self.new) {
+            (None, _) => " (added)",
+            (_, None) => " (removed)",
+            _ => "",
+        }
+    }
+}
+
+/// Chunks of both builds, largest change first and then by name.
+pub fn get_deltas(old: &Manifest, new: &Manifest) -> Vec<ChunkDelta> {
+    let mut chunks = BTreeMap::<&str, ChunkDelta>::new();
+    for (manifest, is_new) in [(old, false), (new, true)] {
+        for chunk in manifest.chunks.iter() {
+            let delta = chunks.entry(&chunk.name).or_insert_with(|| ChunkDelta {
+                name: chunk.name.clone(),
+                old: None,
+                new: None,
+                old_gzip: None,
+                new_gzip: None,
+                old_hash: None,
+                new_hash: None,
+            });
+            if is_new {
+                delta.new = Some(chunk.size);
+                delta.new_gzip = chunk.gzip_size;
+                delta.new_hash = chunk.hash.clone();
+            } else {
+                delta.old = Some(chunk.size);
+                delta.old_gzip = chunk.gzip_size;
+                delta.old_hash = chunk.hash.clone();
+            }
+        }
+    }
+    let mut deltas = chunks.into_values().collect::<Vec<_>>();
+    deltas.sort_by(|a, b| {
+        b.delta()
+            .unsigned_abs()
+            .cmp(&a.delta().unsigned_abs())
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    deltas
+}
+
+/// A change in size with its sign, such as `+1.2 KB` or `-300 B`.
+pub fn format_delta(delta: i64) -> String {
+    match delta {
+        0 => "0 B".to_string(),
+        delta if delta > 0 => format!("+{}", ByteSize(delta as usize)),
+        delta => format!("-{}", ByteSize(delta.unsigned_abs() as usize)),
+    }
+}
+
+pub fn format_change(delta: i64, old: Option<usize>) -> String {
+    match old {
+        Some(old) if old > 0 && delta != 0 => {
+            format!(
+                "{} ({:+.1}%)",
+                format_delta(delta),
+                100.0 * delta as f64 / old as f64
+            )
+        }
+        _ => format_delta(delta),
+    }
+}
+
+pub fn format_size(size: Option<usize>) -> String {
+    size.map_or_else(|| "-".to_string(), |size| ByteSize(size).to_string())
+}
+
+/// Sum of `deltas`, named `total`.
+pub fn total(deltas: &[ChunkDelta]) -> ChunkDelta {
+    let sum = |size: fn(&ChunkDelta) -> Option<usize>| -> Option<usize> {
+        deltas.iter().filter_map(size).reduce(|a, b| a + b)
+    };
+    ChunkDelta {
+        name: "total".to_string(),
+        old: Some(sum(|delta| delta.old).unwrap_or(0)),
+        new: Some(sum(|delta| delta.new).unwrap_or(0)),
+        // Only comparable if every chunk has a gzip size on its side.
+        old_gzip: deltas
+            .iter()
+            .all(|delta| delta.old.is_none() || delta.old_gzip.is_some())
+            .then(|| sum(|delta| delta.old_gzip).unwrap_or(0)),
+        new_gzip: deltas
+            .iter()
+            .all(|delta| delta.new.is_none() || delta.new_gzip.is_some())
+            .then(|| sum(|delta| delta.new_gzip).unwrap_or(0)),
+        old_hash: None,
+        new_hash: None,
+    }
+}
+
+/// Chunks of both builds whose contents changed, the number of chunks in
+/// both, and the size of the changed ones in the new build, which returning
+/// visitors download again. `None` unless the manifests record hashes.
+fn churn(deltas: &[ChunkDelta]) -> Option<(usize, usize, usize)> {
+    let kept = deltas
+        .iter()
+        .filter_map(|delta| Some((delta, delta.content_changed()?)))
+        .collect::<Vec<_>>();
+    if kept.is_empty() {
+        return None;
+    }
+    let changed = kept.iter().filter(|(_, changed)| *changed);
+    Some((
+        changed.clone().count(),
+        kept.len(),
+        changed.map(|(delta, _)| delta.new.unwrap_or(0)).sum(),
+    ))
+}
+
+pub fn format_text(deltas: &[ChunkDelta]) -> String {
+    let total = total(deltas);
+    let rows = deltas
+        .iter()
+        .chain([&total])
+        .map(|delta| {
+            [
+                format!("{}{}", delta.name, delta.status()),
+                format_size(delta.old),
+                format_size(delta.new),
+                format_change(delta.delta(), delta.old),
+                delta.gzip_delta().map_or_else(String::new, format_delta),
+            ]
+        })
+        .collect::<Vec<_>>();
+    let width = rows
+        .iter()
+        .map(|row| row[0].len())
+        .max()
+        .unwrap_or(0)
+        .max("chunk".len());
+    let mut out = format!(
+        "{:width$}  {:>10}  {:>10}  {:>18}  {:>10}\n",
+        "chunk", "old", "new", "change", "gzip"
+    );
+    for (index, row) in rows.iter().enumerate() {
+        if index == deltas.len() {
+            out.push('\n');
+        }
+        writeln!(
+            out,
+            "{:width$}  {:>10}  {:>10}  {:>18}  {:>10}",
+            row[0], row[1], row[2], row[3], row[4]
+        )
+        .unwrap();
+    }
+    if let Some((changed, kept, size)) = churn(deltas) {
+        writeln!(
+            out,
+            "\n{changed} of {kept} chunks in both builds changed contents, {} to download again",
+            ByteSize(size)
+        )
+        .unwrap();
+    }
+    out
+}
+
+pub fn format_markdown(deltas: &[ChunkDelta]) -> String {
+    let total = total(deltas);
+    let gzip = total.gzip_delta().is_some();
+    let mut out = format!(
+        "**wasm-split:** {} → {}, {}",
+        format_size(total.old),
+        format_size(total.new),
+        format_change(total.delta(), total.old)
+    );
+    if let Some(delta) = total.gzip_delta() {
+        write!(out, ", {} gzipped", format_delta(delta)).unwrap();
+    }
+    out.push_str("\n\n");
+    if let Some((changed, kept, size)) = churn(deltas) {
+        writeln!(
+            out,
+            "{changed} of {kept} chunks in both builds changed contents, so returning visitors \
+             download {} again.\n",
+            ByteSize(size)
+        )
+        .unwrap();
+    }
+    let changed = deltas
+        .iter()
+        .filter(|delta| {
+            delta.delta() != 0
+                || delta.old.is_none()
+                || delta.new.is_none()
+                || delta.content_changed() == Some(true)
+        })
+        .collect::<Vec<_>>();
+    if changed.is_empty() {
+        out.push_str("No chunk changed.\n");
+        return out;
+    }
+    out.push_str(if gzip {
+        "| Chunk | Old | New | Change | Gzip |\n|:--|--:|--:|--:|--:|\n"
+    } else {
+        "| Chunk | Old | New | Change |\n|:--|--:|--:|--:|\n"
+    });
+    for delta in changed.iter() {
+        write!(
+            out,
+            "| `{}`{} | {} | {} | {} |",
+            delta.name,
+            delta.status(),
+            format_size(delta.old),
+            format_size(delta.new),
+            format_change(delta.delta(), delta.old)
+        )
+        .unwrap();
+        if gzip {
+            let gzip_delta = delta.gzip_delta().map_or_else(String::new, format_delta);
+            write!(out, " {gzip_delta} |").unwrap();
+        }
+        out.push('\n');
+    }
+    let unchanged = deltas.len() - changed.len();
+    if unchanged > 0 {
+        writeln!(out, "\n{unchanged} other chunks are unchanged.").unwrap();
+    }
+    out
+}
+
+/// The manifest at `path`, or in the directory at `path`.
+pub fn read_build(path: &Path) -> Result<Manifest> {
+    if path.is_dir() {
+        read_manifest(&path.join(MANIFEST_FILENAME))
+    } else {
+        read_manifest(path)
+    }
+}
+
+pub fn run(old: &Path, new: &Path, markdown: bool, out: Option<&Path>) -> Result<()> {
+    let deltas = get_deltas(&read_build(old)?, &read_build(new)?);
+    let report = if markdown {
+        format_markdown(&deltas)
+    } else {
+        format_text(&deltas)
+    };
+    match out {
+        Some(out) => std::fs::write(out, report)
+            .with_context(|| format!("Failed to write size diff {out:?}"))?,
+        None => print!("{report}"),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_markdown, format_text, get_deltas, read_build};
+    use crate::{config::ByteSize, test_fixtures::split};
+
+    #[test]
+    fn diffs_chunk_sizes_of_two_builds() {
+        let old = split("no_std_app.wasm", &[]);
+        // Folds every split module into the main module.
+        let new = split("no_std_app.wasm", &["--fold-threshold", "100000000"]);
+        let old_manifest = read_build(&old.dir).unwrap();
+        let new_manifest = read_build(&new.dir).unwrap();
+        let deltas = get_deltas(&old_manifest, &new_manifest);
+
+        let main = deltas.iter().find(|delta| delta.name == "main").unwrap();
+        assert!(main.delta() > 0, "{main:?}");
+        let first = deltas.iter().find(|delta| delta.name == "first").unwrap();
+        assert!(first.old.is_some() && first.new.is_none(), "{first:?}");
+        assert_eq!(
+            deltas.len(),
+            old_manifest.chunks.len().max(new_manifest.chunks.len())
+        );
+        assert!(get_deltas(&old_manifest, &old_manifest)
+            .iter()
+            .all(|delta| delta.delta() == 0));
+
+        let text = format_text(&deltas);
+        assert!(text.contains("first (removed)"), "{text}");
+        assert!(text.contains("\ntotal "), "{text}");
+        assert!(text.ends_with("to download again\n"), "{text}");
+        let markdown = format_markdown(&deltas);
+        assert!(markdown.starts_with("**wasm-split:**"), "{markdown}");
+        assert!(markdown.contains("| `first` (removed) |"), "{markdown}");
+        assert!(format_markdown(&get_deltas(&old_manifest, &old_manifest))
+            .contains("No chunk changed."));
+    }
+
+    #[test]
+    fn reports_chunks_whose_contents_changed() {
+        let output = split("no_std_app.wasm", &[]);
+        let old = read_build(&output.dir).unwrap();
+        assert!(get_deltas(&old, &old)
+            .iter()
+            .all(|delta| delta.content_changed() == Some(false)));
+        let text = format_text(&get_deltas(&old, &old));
+        let chunks = old.chunks.len();
+        assert!(
+            text.ends_with(&format!(
+                "0 of {chunks} chunks in both builds changed contents, 0 B to download again\n"
+            )),
+            "{text}"
+        );
+
+        // Same size, other contents.
+        let mut new = old.clone();
+        let first = new
+            .chunks
+            .iter_mut()
+            .find(|chunk| chunk.name == "first")
+            .unwrap();
+        first.hash = Some("0000000000000000".to_string());
+        let size = first.size;
+        let deltas = get_deltas(&old, &new);
+        let first = deltas.iter().find(|delta| delta.name == "first").unwrap();
+        assert_eq!((first.delta(), first.content_changed()), (0, Some(true)));
+        let markdown = format_markdown(&deltas);
+        assert!(
+            markdown.contains(&format!(
+                "1 of {chunks} chunks in both builds changed contents, so returning visitors \
+                 download {} again.",
+                ByteSize(size)
+            )),
+            "{markdown}"
+        );
+        assert!(markdown.contains("| `first` |"), "{markdown}");
+    }
+}
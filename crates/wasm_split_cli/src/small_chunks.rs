@@ -0,0 +1,162 @@
+//! Merging of small split modules, for `--min-chunk-size` and `--max-chunks`.
+//!
+//! Every chunk costs a request, which for a few kilobytes of code costs more
+//! than loading it with another chunk would. Unlike `--fold-threshold`,
+//! which moves small split modules into the main module, these merge them
+//! into another split module, as if both were declared with `group`: the
+//! smallest module is merged into the neighbor that it shares the most code
+//! with, which is the one most likely to be loaded along with it, and the
+//! modules are split again until every chunk is large enough and there are
+//! no more chunks than allowed. The merges are listed under `merged` in the
+//! manifest.
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    dep_graph::DepGraph,
+    metadata::{self, SplitModuleMetadata},
+    read::InputModule,
+    split_point::{self, ChunkingOptions, SplitModuleIdentifier, SplitPoint},
+};
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChunkLimits {
+    /// Split modules with less code than this are merged into another.
+    pub min_chunk_size: usize,
+    /// Most chunks besides the main module, shared chunks included.
+    pub max_chunks: Option<usize>,
+}
+
+impl ChunkLimits {
+    pub fn is_unlimited(&self) -> bool {
+        self.min_chunk_size == 0 && self.max_chunks.is_none()
+    }
+}
+
+/// A split module merged into another by [`merge_small_modules`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MergedModule {
+    pub name: String,
+    /// Split module whose chunk holds its code, as for an alias.
+    pub into: String,
+    /// Code size of the module when it was merged, including that of the
+    /// modules merged into it before.
+    pub code_size: usize,
+    /// Code of the shared chunks of both modules, which is what made `into`
+    /// its closest neighbor.
+    pub shared_size: usize,
+}
+
+/// Merges split modules of `split_points` into others, one at a time, until
+/// `limits` are met or a single split module is left, and returns the
+/// merges in order. Each merge renames the split points of the merged module
+/// and records it as an alias in `metadata`, as `group` would.
+///
+/// Split modules without split points, those of `auto_split_crates` and
+/// `entries`, are never merged, and neither are modules folded into the main
+/// module, which have no chunk of their own, nor `strict` modules, as the
+/// dependencies of the other module would then be checked as well.
+pub fn merge_small_modules(
+    module: &InputModule,
+    dep_graph: &DepGraph,
+    split_points: &mut [SplitPoint],
+    metadata: &mut SplitModuleMetadata,
+    options: &ChunkingOptions,
+    limits: ChunkLimits,
+) -> Result<Vec<MergedModule>> {
+    let mut merged = Vec::new();
+    if limits.is_unlimited() {
+        return Ok(merged);
+    }
+    loop {
+        let module_aliases = metadata::get_module_aliases(metadata);
+        let on_load_hooks = split_point::get_on_load_hooks(module, split_points, &module_aliases)?;
+        let program_info = split_point::compute_split_modules(
+            module,
+            dep_graph,
+            split_points,
+            &on_load_hooks,
+            options,
+        )?;
+        let chunks = program_info.output_modules.len() - 1;
+        let mut sizes = BTreeMap::<&str, usize>::new();
+        for (identifier, info) in program_info.output_modules.iter() {
+            let SplitModuleIdentifier::Split(name) = identifier else {
+                continue;
+            };
+            let mergeable = split_points
+                .iter()
+                .any(|split_point| split_point.module_name == *name)
+                && !program_info.auto_split_modules.contains(name)
+                && metadata
+                    .get(name)
+                    .is_none_or(|attributes| attributes.strict.is_empty())
+                && !program_info
+                    .entry_modules
+                    .iter()
+                    .any(|(_, entry_module)| entry_module == name);
+            if mergeable {
+                sizes.insert(name, info.code_size(module));
+            }
+        }
+        let Some((&smallest, &code_size)) = sizes.iter().min_by_key(|&(&name, &size)| (size, name))
+        else {
+            break;
+        };
+        let over_count = limits.max_chunks.is_some_and(|max| chunks > max);
+        if sizes.len() < 2 || (code_size >= limits.min_chunk_size && !over_count) {
+            if over_count {
+                println!(
+                    "Split into {chunks} chunks, more than --max-chunks allows, as no more split \
+                     modules can be merged"
+                );
+            }
+            break;
+        }
+
+        // Code that both modules load, in the chunks shared by them.
+        let mut shared = BTreeMap::<&str, usize>::new();
+        for (identifier, info) in program_info.output_modules.iter() {
+            let Some(dependents) = identifier.dependents() else {
+                continue;
+            };
+            if !dependents.iter().any(|name| name == smallest) {
+                continue;
+            }
+            let size = info.code_size(module);
+            for name in dependents.iter() {
+                *shared.entry(name.as_str()).or_default() += size;
+            }
+        }
+        let (&into, _) = sizes
+            .iter()
+            .filter(|&(&name, _)| name != smallest)
+            .max_by_key(|&(&name, &size)| {
+                (
+                    shared.get(name).copied().unwrap_or_default(),
+                    std::cmp::Reverse(size),
+                    std::cmp::Reverse(name),
+                )
+            })
+            .expect("there are at least two split modules");
+        let merge = MergedModule {
+            name: smallest.to_string(),
+            into: into.to_string(),
+            code_size,
+            shared_size: shared.get(into).copied().unwrap_or_default(),
+        };
+        metadata::merge_module_groups(
+            metadata,
+            split_points,
+            &BTreeMap::from([(
+                merge.into.clone(),
+                vec![merge.into.clone(), merge.name.clone()],
+            )]),
+        )?;
+        merged.push(merge);
+    }
+    Ok(merged)
+}
@@ -0,0 +1,359 @@
+//! `wasm-split snippets`: makes the glue that wasm-bindgen generated for the
+//! main module import the JS snippets that only chunks use, of
+//! `#[wasm_bindgen(module = "...")]` and `inline_js`, when the loader loads
+//! one of those chunks rather than when it starts.
+//!
+//! wasm-bindgen runs on the main module after the split, so the split only
+//! lists the wasm-bindgen imports that each chunk calls and the main
+//! module's own code does not, as `imports` of the chunk in the manifest.
+//! The glue then defines each import as a function of its own, and imports
+//! the snippets that it calls statically:
+//!
+//! ```js
+//! import { chart_sum } from './snippets/app-1234/inline0.js';
+//! // ...
+//!     __wbg_chart_sum_113f: function(arg0, arg1) {
+//!         const ret = chart_sum(arg0 >>> 0, arg1 >>> 0);
+//! ```
+//!
+//! A snippet whose names are only used by the imports of chunks is instead
+//! imported with `import()` by `__wasm_split_import_snippets`, which the glue
+//! exports for the loader to call with the `imports` of a chunk before
+//! instantiating it, and its names are read from the imported module. Other
+//! snippets are left as they are. `wasm-split serve`, `trunk` and `leptos`
+//! process the glue after running wasm-bindgen; builds that run
+//! wasm-bindgen themselves run `wasm-split snippets` after it.
+
+use std::{collections::BTreeSet, ops::Range, path::Path};
+
+use anyhow::{Context, Result};
+
+use crate::manifest::Manifest;
+
+/// Export of the processed glue that the loader calls.
+const IMPORT_SNIPPETS_EXPORT: &str = "__wasm_split_import_snippets";
+
+/// A static import of a snippet by the glue.
+struct SnippetImport {
+    /// Range of the import statement, including its line break.
+    range: Range<usize>,
+    path: String,
+    /// Imported names, and the names they are bound to in the glue.
+    names: Vec<(String, String)>,
+}
+
+/// Processes the glue at `glue_path` for the build of `manifest_path`, in
+/// place.
+pub fn run(glue_path: &Path, manifest_path: &Path) -> Result<()> {
+    let glue = std::fs::read_to_string(glue_path)
+        .with_context(|| format!("Failed to read {}", glue_path.display()))?;
+    let manifest: Manifest = serde_json::from_slice(
+        &std::fs::read(manifest_path)
+            .with_context(|| format!("Failed to read {}", manifest_path.display()))?,
+    )
+    .with_context(|| format!("Failed to parse {}", manifest_path.display()))?;
+    match split_snippets(&glue, &manifest) {
+        Some((glue, lazy)) => {
+            std::fs::write(glue_path, glue)
+                .with_context(|| format!("Failed to write {}", glue_path.display()))?;
+            println!(
+                "{} imports {} with the chunks that use them",
+                glue_path.display(),
+                lazy.join(", ")
+            );
+        }
+        None => println!(
+            "{} has no snippets that only chunks use",
+            glue_path.display()
+        ),
+    }
+    Ok(())
+}
+
+/// The glue with the snippets that only the chunks of `manifest` use
+/// imported lazily, along with their paths, or `None` if there are none.
+pub fn split_snippets(glue: &str, manifest: &Manifest) -> Option<(String, Vec<String>)> {
+    if glue.contains(IMPORT_SNIPPETS_EXPORT) {
+        // Already processed.
+        return None;
+    }
+    let chunk_imports = manifest
+        .chunks
+        .iter()
+        .flat_map(|chunk| chunk.imports.iter().map(String::as_str))
+        .collect::<BTreeSet<_>>();
+    let entries = find_import_entries(glue);
+
+    // Snippets that only the imports of chunks use, with the entries that
+    // use them and the ranges of the names to replace.
+    let mut lazy = Vec::new();
+    for snippet in find_snippet_imports(glue) {
+        let mut uses = Vec::new();
+        let mut users = BTreeSet::new();
+        let is_lazy = snippet.names.iter().all(|(_, local)| {
+            find_identifier(glue, local)
+                .filter(|position| !snippet.range.contains(position))
+                .all(|position| {
+                    let Some((entry, _)) =
+                        entries.iter().find(|(_, range)| range.contains(&position))
+                    else {
+                        return false;
+                    };
+                    uses.push((position..position + local.len(), local.clone()));
+                    users.insert(entry.clone());
+                    chunk_imports.contains(entry.as_str())
+                })
+        });
+        if is_lazy && !users.is_empty() {
+            lazy.push((snippet, uses, users));
+        }
+    }
+    if lazy.is_empty() {
+        return None;
+    }
+
+    let mut replacements = Vec::<(Range<usize>, String)>::new();
+    let mut loaders = String::new();
+    let mut users_of = String::new();
+    for (index, (snippet, uses, users)) in lazy.iter().enumerate() {
+        let binding = format!("__wasm_split_snippet{index}");
+        replacements.push((snippet.range.clone(), format!("let {binding};\n")));
+        for (range, local) in uses {
+            let (imported, _) = snippet
+                .names
+                .iter()
+                .find(|(_, name)| name == local)
+                .unwrap();
+            replacements.push((range.clone(), format!("{binding}.{imported}")));
+        }
+        loaders.push_str(&format!(
+            "    () => import({}).then((module) => {{ {binding} = module; }}),\n",
+            serde_json::to_string(&snippet.path).unwrap()
+        ));
+        for user in users {
+            users_of.push_str(&format!(
+                "    {}: {index},\n",
+                serde_json::to_string(user).unwrap()
+            ));
+        }
+    }
+    replacements.sort_by_key(|(range, _)| range.start);
+    let mut output = String::with_capacity(glue.len());
+    let mut end = 0;
+    for (range, replacement) in replacements {
+        output.push_str(&glue[end..range.start]);
+        output.push_str(&replacement);
+        end = range.end;
+    }
+    output.push_str(&glue[end..]);
+    output.push_str(&format!(
+        "
+const __wasm_split_snippet_loaders = [
+{loaders}];
+const __wasm_split_snippet_users = {{
+{users_of}}};
+const __wasm_split_snippet_loads = [];
+
+// Imports the snippets used by `imports`, the `imports` of a chunk in the
+// manifest.
+export function {IMPORT_SNIPPETS_EXPORT}(imports) {{
+    const loads = new Set();
+    for (const name of imports) {{
+        const index = __wasm_split_snippet_users[name];
+        if (index === undefined) continue;
+        __wasm_split_snippet_loads[index] ??= __wasm_split_snippet_loaders[index]().catch((e) => {{
+            __wasm_split_snippet_loads[index] = undefined;
+            throw e;
+        }});
+        loads.add(__wasm_split_snippet_loads[index]);
+    }}
+    return Promise.all(loads);
+}}
+"
+    ));
+    let paths = lazy
+        .into_iter()
+        .map(|(snippet, _, _)| snippet.path)
+        .collect();
+    Some((output, paths))
+}
+
+/// Static imports of snippets, `import { a, b as c } from
+/// './snippets/...';`, each on a line of its own.
+fn find_snippet_imports(glue: &str) -> Vec<SnippetImport> {
+    let mut imports = Vec::new();
+    let mut start = 0;
+    for line in glue.split_inclusive('\n') {
+        let range = start..start + line.len();
+        start = range.end;
+        let Some(rest) = line.trim_end().strip_prefix("import {") else {
+            continue;
+        };
+        let Some((names, from)) = rest.split_once('}') else {
+            continue;
+        };
+        let path = from
+            .trim()
+            .strip_prefix("from")
+            .map(|path| path.trim().trim_end_matches(';'))
+            .and_then(|path| {
+                path.strip_prefix('\'')
+                    .and_then(|path| path.strip_suffix('\''))
+                    .or_else(|| path.strip_prefix('"')?.strip_suffix('"'))
+            });
+        let Some(path) = path.filter(|path| path.starts_with("./snippets/")) else {
+            continue;
+        };
+        let names = names
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| match name.split_once(" as ") {
+                Some((imported, local)) => (imported.trim().to_string(), local.trim().to_string()),
+                None => (name.to_string(), name.to_string()),
+            })
+            .collect();
+        imports.push(SnippetImport {
+            range,
+            path: path.to_string(),
+            names,
+        });
+    }
+    imports
+}
+
+/// The functions that the glue passes to the main module as its
+/// wasm-bindgen imports, by name, with the range of their definitions: up to
+/// the next one, or to the end of the object or statements defining them.
+///
+/// Current versions of wasm-bindgen define them as properties of an
+/// object, `__wbg_name_hash: function(...) {`, and older ones as
+/// `imports.wbg.__wbg_name_hash = function(...) {`.
+fn find_import_entries(glue: &str) -> Vec<(String, Range<usize>)> {
+    let mut entries: Vec<(String, Range<usize>)> = Vec::new();
+    let mut start = 0;
+    for line in glue.split_inclusive('\n') {
+        let line_start = start;
+        start += line.len();
+        let trimmed = line.trim_start();
+        let trimmed = trimmed.strip_prefix("imports.wbg.").unwrap_or(trimmed);
+        let name_len = trimmed
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '$'))
+            .unwrap_or(trimmed.len());
+        let (name, rest) = trimmed.split_at(name_len);
+        let is_entry = name.starts_with("__wbg")
+            && (rest.starts_with(": function") || rest.starts_with(" = function"));
+        let ends_entries = line.starts_with("    return") || line.starts_with("    };");
+        if is_entry || ends_entries {
+            if let Some((_, range)) = entries.last_mut() {
+                if range.end == usize::MAX {
+                    range.end = line_start;
+                }
+            }
+        }
+        if is_entry {
+            entries.push((name.to_string(), line_start..usize::MAX));
+        }
+    }
+    if let Some((_, range)) = entries.last_mut() {
+        range.end = range.end.min(glue.len());
+    }
+    entries
+}
+
+/// Positions of `name` in `glue` as an identifier of its own, rather than
+/// part of another or a property.
+fn find_identifier<'a>(glue: &'a str, name: &'a str) -> impl Iterator<Item = usize> + 'a {
+    let is_identifier_char = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '$';
+    glue.match_indices(name)
+        .map(|(position, _)| position)
+        .filter(move |&position| {
+            let before = glue[..position].chars().next_back();
+            let after = glue[position + name.len()..].chars().next();
+            !before.is_some_and(|c| is_identifier_char(c) || c == '.')
+                && !after.is_some_and(is_identifier_char)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::{ChunkKind, ManifestChunk};
+
+    const GLUE: &str = "\
+import { chart_sum, Chart as Chart1 } from './snippets/app-1234/inline0.js';
+import { eager } from './snippets/app-1234/inline1.js';
+import * as import1 from \"./__wasm_split.js\"
+
+function __wbg_get_imports() {
+    const import0 = {
+        __proto__: null,
+        __wbg_chart_sum_113f: function(arg0, arg1) {
+            const ret = chart_sum(arg0 >>> 0, arg1 >>> 0);
+            return ret;
+        },
+        __wbg_eager_6b61: function(arg0) {
+            const ret = eager(arg0 >>> 0);
+            return ret;
+        },
+        __wbg_new_dc31: function(arg0) {
+            const ret = new Chart1(arg0 >>> 0);
+            return ret;
+        },
+    };
+    return {
+        \"./main_bg.js\": import0,
+    };
+}
+";
+
+    fn manifest(imports: &[&str]) -> Manifest {
+        Manifest {
+            chunks: vec![ManifestChunk {
+                name: "chart".to_string(),
+                file: "chart.wasm".to_string(),
+                kind: ChunkKind::Split,
+                size: 0,
+                gzip_size: None,
+                compressed_sizes: Default::default(),
+                hash: None,
+                sha256: None,
+                integrity: None,
+                priority: None,
+                features: Vec::new(),
+                entries: Vec::new(),
+                signatures: Default::default(),
+                dependencies: Vec::new(),
+                defined_functions: 0..0,
+                duplicated: None,
+                pinned: false,
+                auto: false,
+                entry: None,
+                on_load: Vec::new(),
+                imports: imports.iter().map(|name| name.to_string()).collect(),
+                table_slots: 0..0,
+                referenced: false,
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn imports_snippets_of_chunks_lazily() {
+        let (glue, lazy) =
+            split_snippets(GLUE, &manifest(&["__wbg_chart_sum_113f", "__wbg_new_dc31"])).unwrap();
+        assert_eq!(lazy, ["./snippets/app-1234/inline0.js"]);
+        assert!(!glue.contains("import { chart_sum"));
+        assert!(glue.contains("import { eager } from './snippets/app-1234/inline1.js';"));
+        assert!(glue.contains("let __wasm_split_snippet0;\n"));
+        assert!(glue.contains("const ret = __wasm_split_snippet0.chart_sum(arg0 >>> 0"));
+        assert!(glue.contains("const ret = new __wasm_split_snippet0.Chart(arg0 >>> 0);"));
+        assert!(glue.contains("const ret = eager(arg0 >>> 0);"));
+        assert!(glue.contains("    \"__wbg_new_dc31\": 0,\n"));
+        assert!(glue.contains("export function __wasm_split_import_snippets(imports) {"));
+        assert!(split_snippets(&glue, &manifest(&["__wbg_chart_sum_113f"])).is_none());
+
+        // `Chart` is also used by an import of the main module.
+        assert!(split_snippets(GLUE, &manifest(&["__wbg_chart_sum_113f"])).is_none());
+    }
+}
@@ -0,0 +1,200 @@
+//! Source maps of the output modules, with `--source-maps`, for crash
+//! reporters that symbolicate the `wasm-function[N]:0x<offset>` frames of
+//! stack traces with source maps rather than DWARF, and so need one for each
+//! lazily loaded chunk.
+//!
+//! As those of emscripten's `wasm-sourcemap.py`, the maps have a single line,
+//! whose columns are byte offsets into the module. They are made from the
+//! line tables of the DWARF sections that every module carries with the
+//! addresses of its own code; see `generate_debug_sections` of `emit.rs`.
+//! Each module links to its map with a [`SOURCE_MAPPING_URL_SECTION`].
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use wasm_encoder::Encode;
+
+use crate::{
+    read::InputModule,
+    symbols::{load_dwarf, source_path},
+};
+
+pub const SOURCE_MAPPING_URL_SECTION: &str = "sourceMappingURL";
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// URL of the map of the module written to `file`, relative to the module,
+/// or under `base` if given.
+pub fn url(file: &str, base: Option<&str>) -> String {
+    match base {
+        Some(base) => format!("{}/{file}.map", base.trim_end_matches('/')),
+        None => format!("{}.map", file.rsplit('/').next().unwrap_or(file)),
+    }
+}
+
+/// Custom section linking a module to its source map at `url`.
+pub fn section(url: &str) -> wasm_encoder::CustomSection<'static> {
+    let mut data = Vec::new();
+    url.encode(&mut data);
+    wasm_encoder::CustomSection {
+        name: SOURCE_MAPPING_URL_SECTION.into(),
+        data: data.into(),
+    }
+}
+
+/// Source map of the encoded module `data`, written to `file`, or `None` if
+/// its DWARF sections have no line table rows for its code.
+pub fn source_map(data: &[u8], file: &str) -> Result<Option<String>> {
+    let module = InputModule::parse(data)?;
+    let code_end = module
+        .defined_funcs
+        .last()
+        .map_or(0, |func| func.body.range().end - module.code_section_offset)
+        as u64;
+    let dwarf = load_dwarf(&module)?;
+
+    // Mappings as `(offset in the module, source index, line, column)`, with
+    // lines and columns from 0.
+    let mut sources = Vec::<String>::new();
+    let mut source_indices = HashMap::<String, usize>::new();
+    let mut mappings = Vec::<(u64, usize, u64, u64)>::new();
+    let mut headers = dwarf.units();
+    while let Some(header) = headers.next()? {
+        let unit = dwarf.unit(header)?;
+        let Some(program) = unit.line_program.clone() else {
+            continue;
+        };
+        let mut rows = program.rows();
+        while let Some((_, row)) = rows.next_row()? {
+            // Code of other modules has tombstone addresses past the end.
+            let (Some(line), false) = (row.line(), row.end_sequence()) else {
+                continue;
+            };
+            if row.address() >= code_end {
+                continue;
+            }
+            let Some(path) = source_path(&dwarf, &unit, row.file_index())? else {
+                continue;
+            };
+            let source = *source_indices.entry(path).or_insert_with_key(|path| {
+                sources.push(path.clone());
+                sources.len() - 1
+            });
+            let column = match row.column() {
+                gimli::ColumnType::LeftEdge => 0,
+                gimli::ColumnType::Column(column) => column.get() - 1,
+            };
+            mappings.push((
+                module.code_section_offset as u64 + row.address(),
+                source,
+                line.get() - 1,
+                column,
+            ));
+        }
+    }
+    if mappings.is_empty() {
+        return Ok(None);
+    }
+    mappings.sort();
+    // Rows of the same source position, e.g. of the instructions of one
+    // expression, need only the first.
+    mappings.dedup_by(|next, previous| {
+        (next.1, next.2, next.3) == (previous.1, previous.2, previous.3)
+    });
+
+    let mut encoded = String::new();
+    let mut previous = (0, 0, 0, 0);
+    for &(offset, source, line, column) in mappings.iter() {
+        if !encoded.is_empty() {
+            encoded.push(',');
+        }
+        push_vlq(&mut encoded, offset as i64 - previous.0 as i64);
+        push_vlq(&mut encoded, source as i64 - previous.1 as i64);
+        push_vlq(&mut encoded, line as i64 - previous.2 as i64);
+        push_vlq(&mut encoded, column as i64 - previous.3 as i64);
+        previous = (offset, source, line, column);
+    }
+    let map = serde_json::json!({
+        "version": 3,
+        "file": file.rsplit('/').next().unwrap_or(file),
+        "sources": sources,
+        "names": [],
+        "mappings": encoded,
+    });
+    Ok(Some(serde_json::to_string(&map)?))
+}
+
+/// Appends `value` as a Base64 VLQ, as source maps encode the fields of their
+/// mappings.
+fn push_vlq(output: &mut String, value: i64) {
+    let mut vlq = if value < 0 {
+        (value.unsigned_abs() << 1) | 1
+    } else {
+        (value as u64) << 1
+    };
+    loop {
+        let digit = (vlq & 0x1f) as usize;
+        vlq >>= 5;
+        output.push(BASE64[digit | if vlq > 0 { 0x20 } else { 0 }] as char);
+        if vlq == 0 {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{push_vlq, SOURCE_MAPPING_URL_SECTION};
+    use crate::test_fixtures::{split, try_split};
+
+    #[test]
+    fn encodes_base64_vlqs() {
+        let mut output = String::new();
+        for value in [0, 1, -1, 15, 16, -16, 1000] {
+            push_vlq(&mut output, value);
+            output.push(' ');
+        }
+        assert_eq!(output, "A C D e gB hB w+B ");
+    }
+
+    #[test]
+    fn writes_a_source_map_for_every_module() {
+        let output = split("no_std_app_debug.wasm", &["--source-maps"]);
+        output.validate();
+        for file in output.wasm_files() {
+            let data = output.read(&file);
+            let url = wasmparser::Parser::new(0)
+                .parse_all(&data)
+                .find_map(|payload| match payload.unwrap() {
+                    wasmparser::Payload::CustomSection(reader)
+                        if reader.name() == SOURCE_MAPPING_URL_SECTION =>
+                    {
+                        let mut reader = wasmparser::BinaryReader::new(reader.data());
+                        Some(reader.read_string().unwrap().to_string())
+                    }
+                    _ => None,
+                })
+                .unwrap_or_else(|| panic!("{file} has no {SOURCE_MAPPING_URL_SECTION}"));
+            assert_eq!(url, format!("{file}.map"));
+            let map: serde_json::Value = serde_json::from_slice(&output.read(&url)).unwrap();
+            assert_eq!(map["version"], 3);
+            assert_eq!(map["file"], file);
+            assert!(map["sources"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .any(|source| source == "src/lib.rs"));
+            assert!(!map["mappings"].as_str().unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn requires_debug_info() {
+        let (_, result) = try_split("no_std_app.wasm", "", &["--source-maps"]);
+        let error = result.unwrap_err().to_string();
+        assert!(
+            error.contains("--source-maps requires DWARF line tables"),
+            "{error}"
+        );
+    }
+}
@@ -1,16 +1,13 @@
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 
 use crate::dep_graph::{DepGraph, DepNode};
-use crate::read::{ExportId, ImportId, InputFuncId, InputModule, SymbolIndex};
+use crate::read::{ExportId, ImportId, InputFuncId, InputModule};
+use crate::{deny::get_crate_name, small_chunks::MergedModule, symbols::demangle};
 use anyhow::{anyhow, bail};
 use lazy_static::lazy_static;
+use rayon::prelude::*;
 use regex::Regex;
-
-#[derive(Debug, PartialEq, Eq, Clone)]
-pub struct SplitModule {
-    pub module_name: String,
-    pub load_func: SymbolIndex,
-}
+use serde::Deserialize;
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct SplitPoint {
@@ -21,30 +18,51 @@ pub struct SplitPoint {
     pub export_func: InputFuncId,
 }
 
-pub fn get_split_modules(module: &InputModule) -> HashMap<String, SplitModule> {
-    const PREFIX: &str = "__wasm_split_load_";
-    let mut split_modules: HashMap<String, SplitModule> = HashMap::new();
-    for (i, info) in module.symbols.iter().enumerate() {
-        let wasmparser::SymbolInfo::Func {
-            name: Some(symbol_name),
-            ..
-        } = info
-        else {
+/// Function declared with `#[wasm_split::on_load(module)]`, which the loader
+/// calls once the chunk of the module has been instantiated.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct OnLoadHook {
+    pub module_name: String,
+    pub export_func: InputFuncId,
+}
+
+/// Finds the hooks exported by `#[wasm_split::on_load]`, whose module may be
+/// one co-located with another by `with`, which `aliases` maps to the other.
+pub fn get_on_load_hooks(
+    module: &InputModule,
+    split_points: &[SplitPoint],
+    aliases: &BTreeMap<String, String>,
+) -> anyhow::Result<Vec<OnLoadHook>> {
+    lazy_static! {
+        static ref PATTERN: Regex =
+            Regex::new("^__wasm_split_00(.*)00_onload_[0-9a-f]{32}_").unwrap();
+    }
+    let mut hooks = Vec::new();
+    for export in module.exports.iter() {
+        let Some(captures) = PATTERN.captures(export.name) else {
             continue;
         };
-        if !symbol_name.starts_with(PREFIX) {
-            continue;
+        let module_name = aliases
+            .get(&captures[1])
+            .map_or(&captures[1], |module_name| module_name.as_str());
+        if export.kind != wasmparser::ExternalKind::Func {
+            bail!("Expected exported function but received: {export:?}");
         }
-        let name = &symbol_name[PREFIX.len()..];
-        split_modules.insert(
-            name.into(),
-            SplitModule {
-                module_name: String::from(name),
-                load_func: i,
-            },
-        );
+        if !split_points
+            .iter()
+            .any(|split_point| split_point.module_name == module_name)
+        {
+            bail!(
+                "`#[wasm_split::on_load({module_name})]` refers to a split module without \
+                 `#[wasm_split({module_name})]` functions"
+            );
+        }
+        hooks.push(OnLoadHook {
+            module_name: module_name.to_string(),
+            export_func: export.index as InputFuncId,
+        });
     }
-    split_modules
+    Ok(hooks)
 }
 
 pub fn get_split_points(module: &InputModule) -> anyhow::Result<Vec<SplitPoint>> {
@@ -84,7 +102,10 @@ pub fn get_split_points(module: &InputModule) -> anyhow::Result<Vec<SplitPoint>>
         .drain()
         .map(|(key, import_id)| -> anyhow::Result<SplitPoint> {
             let export_id = export_map.remove(&key).ok_or_else(|| {
-                anyhow::anyhow!("No corresponding export for split import {key:?}")
+                anyhow::anyhow!(
+                    "No corresponding export for split import {key:?}. Exports must be kept \
+                     until the module has been split, so run wasm-split before wasm-opt."
+                )
             })?;
             let export = module.exports[export_id];
             let wasmparser::Export {
@@ -111,7 +132,7 @@ pub fn get_split_points(module: &InputModule) -> anyhow::Result<Vec<SplitPoint>>
         })
         .collect::<anyhow::Result<Vec<SplitPoint>>>()?;
 
-    for (key, _) in export_map.iter() {
+    if let Some(key) = export_map.keys().next() {
         anyhow::bail!("No corresponding import for split export {key:?}");
     }
 
@@ -130,6 +151,13 @@ pub struct OutputModuleInfo {
     pub parents: HashMap<DepNode, DepNode>,
     pub shared_imports: HashSet<InputFuncId>,
     pub split_points: Vec<SplitPoint>,
+    /// Hooks of the split modules whose code is part of this module, i.e.
+    /// of the module itself or, for the main module, of folded modules.
+    pub on_load_hooks: Vec<OnLoadHook>,
+    /// Functions owned by another module of which this module includes its
+    /// own copy, rather than calling them through the indirect function table.
+    /// These are also contained in `included_symbols`.
+    pub duplicated_funcs: HashSet<InputFuncId>,
 }
 
 impl OutputModuleInfo {
@@ -173,12 +201,7 @@ fn print_deps(
         };
         let size = index
             .checked_sub(module.imported_funcs.len())
-            .map(|defined_index| {
-                module.defined_funcs[index - module.imported_funcs.len()]
-                    .body
-                    .range()
-                    .len()
-            })
+            .map(|defined_index| module.defined_funcs[defined_index].body.range().len())
             .unwrap_or_default();
         total_size += size;
         println!("   {} size={size:?}", format_dep(dep));
@@ -191,27 +214,28 @@ fn print_deps(
     println!("SPLIT: ============== {module_name}  : total size: {total_size}");
 }
 
-impl ReachabilityGraph {
-    pub fn print(&self, module_name: &str, module: &InputModule) {
-        print_deps(module_name, module, &self.reachable, &self.parents);
-    }
-}
-
 pub fn find_reachable_deps(
     deps: &DepGraph,
     roots: &HashSet<DepNode>,
     exclude: &HashSet<DepNode>,
 ) -> ReachabilityGraph {
+    // Roots and children are visited in order, so that the chains reported
+    // from `parents` do not depend on hash set iteration.
     let mut queue: VecDeque<DepNode> = roots.iter().copied().collect();
+    queue.make_contiguous().sort_unstable();
     let mut seen = HashSet::<DepNode>::new();
     let mut parents = HashMap::<DepNode, DepNode>::new();
+    let mut children = Vec::new();
     while let Some(node) = queue.pop_front() {
         seen.insert(node);
-        let Some(children) = deps.get(&node) else {
+        let Some(node_deps) = deps.get(&node) else {
             continue;
         };
-        for child in children {
-            if seen.contains(&child) || exclude.contains(&child) {
+        children.clear();
+        children.extend(node_deps.iter().copied());
+        children.sort_unstable();
+        for child in children.iter() {
+            if seen.contains(child) || exclude.contains(child) {
                 continue;
             }
             parents.entry(*child).or_insert(node);
@@ -224,6 +248,35 @@ pub fn find_reachable_deps(
     }
 }
 
+/// Whether calls to `import` are interpreted by wasm-bindgen when it processes
+/// the main module, which then replaces the calling function, e.g. by a JS
+/// import that creates a closure. Such imports are never called at runtime.
+pub fn is_wasm_bindgen_describe_import(import: &wasmparser::Import) -> bool {
+    import.module == "__wbindgen_placeholder__" && import.name.starts_with("__wbindgen_describe")
+}
+
+/// Functions that must remain in the main module so that wasm-bindgen can
+/// process them: those calling a describe import directly, which includes the
+/// descriptors of exported functions as well as the functions that convert
+/// Rust closures to JS functions.
+pub fn get_wasm_bindgen_pinned_funcs(
+    module: &InputModule,
+    dep_graph: &DepGraph,
+) -> HashSet<DepNode> {
+    let describe_imports: HashSet<DepNode> = module
+        .imported_funcs
+        .iter()
+        .enumerate()
+        .filter(|(_, &import_id)| is_wasm_bindgen_describe_import(&module.imports[import_id]))
+        .map(|(func_id, _)| DepNode::Function(func_id))
+        .collect();
+    dep_graph
+        .iter()
+        .filter(|(_, deps)| !deps.is_disjoint(&describe_imports))
+        .map(|(&node, _)| node)
+        .collect()
+}
+
 pub fn get_main_module_roots(
     module: &InputModule,
     split_points: &[SplitPoint],
@@ -253,6 +306,20 @@ pub fn get_main_module_roots(
     roots
 }
 
+/// Name of the annotated function, which ends the name of the export of a
+/// split point.
+pub fn get_split_function_name(module: &InputModule, split_point: &SplitPoint) -> String {
+    split_function_name(module.exports[split_point.export].name).to_string()
+}
+
+/// The name of the split function whose split point is exported as `export`.
+pub fn split_function_name(export: &str) -> &str {
+    export
+        .split_once("_export_")
+        .and_then(|(_, rest)| rest.split_once('_'))
+        .map_or(export, |(_, function)| function)
+}
+
 pub fn get_split_points_by_module(
     split_points: &[SplitPoint],
 ) -> HashMap<String, Vec<&SplitPoint>> {
@@ -260,8 +327,8 @@ pub fn get_split_points_by_module(
         .iter()
         .fold(HashMap::new(), |mut map, split_point| {
             map.entry(split_point.module_name.clone())
-                .or_insert_with(|| Vec::new())
-                .push(&split_point);
+                .or_default()
+                .push(split_point);
             map
         })
 }
@@ -271,6 +338,9 @@ pub enum SplitModuleIdentifier {
     Main,
     Split(String),
     Chunk(Vec<String>),
+    /// Chunk of the crates that `[chunks]` of the config puts into it, with
+    /// the split modules that load it, like those of a shared chunk.
+    Named(String, Vec<String>),
 }
 
 impl SplitModuleIdentifier {
@@ -279,8 +349,164 @@ impl SplitModuleIdentifier {
             Self::Main => "main".to_string(),
             Self::Split(name) => name.clone(),
             Self::Chunk(names) => names.join("_"),
+            Self::Named(name, _) => name.clone(),
+        }
+    }
+
+    /// The split modules that load a shared or named chunk along with their
+    /// own.
+    pub fn dependents(&self) -> Option<&[String]> {
+        match self {
+            Self::Chunk(names) | Self::Named(_, names) => Some(names),
+            Self::Main | Self::Split(_) => None,
+        }
+    }
+}
+
+/// How code reachable from several split modules is divided into chunks.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SharedStrategy {
+    /// A chunk for each set of split modules that share code, so that no
+    /// module loads code it does not need.
+    #[default]
+    PerGroup,
+    /// A single chunk of all shared code, loaded by every split module that
+    /// shares any, which trades bytes for fewer requests.
+    Single,
+}
+
+/// Options controlling how symbols are assigned to output modules.
+#[derive(Debug, Default, Clone)]
+pub struct ChunkingOptions {
+    /// If specified, defined functions whose body is at most this many bytes
+    /// are copied into each split module that calls them, instead of being
+    /// called through the indirect function table.
+    pub duplicate_threshold: Option<usize>,
+    /// Split modules whose code is smaller than this many bytes are folded
+    /// back into the main module, since the indirection and the additional
+    /// request would cost more than splitting saves.
+    pub fold_threshold: usize,
+    /// Groups of split modules that users commonly load one after another,
+    /// according to the navigation flows given to the CLI. See
+    /// [`ChunkingOptions::colocate`].
+    pub colocated_modules: Vec<Vec<String>>,
+    /// Whether there is a shared chunk for each set of split modules that
+    /// share code, or a single one.
+    pub shared_strategy: SharedStrategy,
+    /// Shared chunks whose code is smaller than this many bytes are merged
+    /// into another; see [`merge_small_shared_chunks`].
+    pub min_shared_size: usize,
+    /// Chunks that the code of the given crates goes into wherever it is not
+    /// part of the main module, such as `("dates", ["chrono"])`.
+    pub crate_chunks: Vec<(String, Vec<String>)>,
+    /// Crates whose code the main module does not need at startup goes into
+    /// a split module of its own, without any split points; see
+    /// [`get_auto_split_funcs`].
+    pub auto_split_crates: Vec<String>,
+    /// Chunks that the code of the given crates goes into even where the
+    /// main module reaches it at startup, such as `("vendor", ["serde_json"])`,
+    /// which the main module calls through the table; see
+    /// [`get_auto_split_funcs`].
+    pub vendor_chunks: Vec<(String, Vec<String>)>,
+    /// Exports that the app calls at startup, which with the start function
+    /// determine the code of `auto_split_crates` that stays in the main
+    /// module.
+    pub startup_exports: Vec<String>,
+    /// Entry points other than the main thread, such as a web worker, each
+    /// with the exports that only it calls. The code that only they reach
+    /// goes into a split module of the entry, which the entry loads before
+    /// calling them; see [`get_entry_funcs`].
+    pub entries: Vec<(String, Vec<String>)>,
+    /// Split modules that are folded into the main module regardless of
+    /// their size, as the profile of the app calls them at startup.
+    pub hoisted_modules: Vec<String>,
+    /// Export functions of single split points that are folded likewise,
+    /// while the rest of their module is not.
+    pub hoisted_split_points: HashSet<InputFuncId>,
+    /// Whether the split functions of folded modules keep a slot in the
+    /// indirect function table, like those of split modules, for the JS entry
+    /// points of `--emit-js-entries` to call them through.
+    pub folded_split_point_slots: bool,
+    /// Whether functions that the main module only takes the address of,
+    /// e.g. methods in the vtables of its trait objects, go into the split
+    /// modules that also reach them, rather than into the main module. Their
+    /// table slots then load the owning chunk on the first call, with
+    /// `--lazy-indirect-calls`.
+    pub lazy_indirect_calls: bool,
+}
+
+impl ChunkingOptions {
+    /// Adds the other members of the co-location groups of `modules`, a set
+    /// of split modules that share a symbol, if any of `candidates`. The
+    /// symbol then goes into a chunk of more modules, so none of them misses
+    /// it, while the chunks that would be shared by only some members of a
+    /// group become one, which is fetched once by a journey across them.
+    fn colocate(&self, modules: &mut Vec<String>, candidates: impl Fn(&str) -> bool) {
+        for group in self.colocated_modules.iter() {
+            if group.iter().any(|module| modules.contains(module)) {
+                modules.extend(
+                    group
+                        .iter()
+                        .filter(|module| candidates(module) && !modules.contains(module))
+                        .cloned()
+                        .collect::<Vec<_>>(),
+                );
+            }
+        }
+        modules.sort();
+    }
+
+    /// The chunk of `crate_chunks` that `node` goes into, by index.
+    fn crate_chunk(&self, module: &InputModule, node: &DepNode) -> Option<usize> {
+        if self.crate_chunks.is_empty() {
+            return None;
+        }
+        let DepNode::Function(func_id) = node else {
+            return None;
+        };
+        let name = demangle(module.names.functions.get(func_id)?);
+        let crate_name = get_crate_name(&name);
+        self.crate_chunks.iter().position(|(_, crates)| {
+            crates
+                .iter()
+                .any(|listed| listed.replace('-', "_") == crate_name)
+        })
+    }
+
+    /// The crate of `auto_split_crates` that `func_id` belongs to, by index,
+    /// or that of `vendor_chunks` after them, which takes precedence.
+    fn auto_split_crate(&self, module: &InputModule, func_id: InputFuncId) -> Option<usize> {
+        let name = demangle(module.names.functions.get(&func_id)?);
+        let crate_name = get_crate_name(&name);
+        let is_listed = |listed: &String| listed.replace('-', "_") == crate_name;
+        match self
+            .vendor_chunks
+            .iter()
+            .position(|(_, crates)| crates.iter().any(is_listed))
+        {
+            Some(index) => Some(self.auto_split_crates.len() + index),
+            None => self.auto_split_crates.iter().position(is_listed),
         }
     }
+
+    fn can_duplicate(
+        &self,
+        module: &InputModule,
+        split_points: &[SplitPoint],
+        func_id: InputFuncId,
+    ) -> bool {
+        let Some(threshold) = self.duplicate_threshold else {
+            return false;
+        };
+        let Some(defined_index) = func_id.checked_sub(module.imported_funcs.len()) else {
+            return false;
+        };
+        module.defined_funcs[defined_index].body.range().len() <= threshold
+            && !split_points
+                .iter()
+                .any(|split_point| split_point.export_func == func_id)
+    }
 }
 
 #[derive(Debug, Default)]
@@ -289,55 +515,615 @@ pub struct SplitProgramInfo {
     pub output_module_identifiers: HashMap<SplitModuleIdentifier, usize>,
     pub shared_funcs: HashSet<InputFuncId>,
     pub symbol_output_module: HashMap<DepNode, usize>,
+    /// Split modules that were folded into the main module, with the code
+    /// size that they would have had.
+    pub folded_modules: Vec<(String, usize)>,
+    /// Split modules that were merged into others for `--min-chunk-size` and
+    /// `--max-chunks`, in order.
+    pub merged_modules: Vec<MergedModule>,
+    /// Functions pinned to the main module by [`get_wasm_bindgen_pinned_funcs`].
+    pub wasm_bindgen_funcs: HashSet<InputFuncId>,
+    /// Split modules of the code of [`ChunkingOptions::auto_split_crates`],
+    /// which the main module calls without a split point.
+    pub auto_split_modules: Vec<String>,
+    /// Split modules of [`ChunkingOptions::vendor_chunks`], which the main
+    /// module likewise calls without a split point.
+    pub vendor_modules: Vec<String>,
+    /// Split modules of [`ChunkingOptions::entries`], by entry name.
+    pub entry_modules: Vec<(String, String)>,
+    /// Imported functions that the code or data of the main module refers
+    /// to, rather than only that of other modules.
+    pub main_imports: HashSet<InputFuncId>,
+}
+
+impl OutputModuleInfo {
+    /// Total size of the bodies of the functions owned by this module.
+    pub fn code_size(&self, module: &InputModule) -> usize {
+        self.included_symbols
+            .iter()
+            .filter_map(|symbol| match symbol {
+                DepNode::Function(func_id) if !self.duplicated_funcs.contains(func_id) => {
+                    func_id.checked_sub(module.imported_funcs.len())
+                }
+                _ => None,
+            })
+            .map(|defined_index| module.defined_funcs[defined_index].body.range().len())
+            .sum()
+    }
 }
 
 pub fn compute_split_modules(
     module: &InputModule,
     dep_graph: &DepGraph,
     split_points: &[SplitPoint],
+    on_load_hooks: &[OnLoadHook],
+    options: &ChunkingOptions,
 ) -> anyhow::Result<SplitProgramInfo> {
-    let split_points_by_module = get_split_points_by_module(&split_points[..]);
-
     println!("split_points={split_points:?}");
+    let call_graph = options
+        .lazy_indirect_calls
+        .then(|| crate::dep_graph::get_call_dependencies(module))
+        .transpose()?;
+
+    // Folding a module moves its code, and possibly code shared with other
+    // split modules, into the main module, which affects the size of the
+    // remaining split modules. Therefore, repeat until no more modules fall
+    // below the threshold.
+    let mut folded_modules = Vec::<(String, usize)>::new();
+    loop {
+        let mut program_info = compute_split_modules_with_folded(
+            module,
+            dep_graph,
+            call_graph.as_ref(),
+            split_points,
+            on_load_hooks,
+            &folded_modules,
+            options,
+        )?;
+        let newly_folded: Vec<(String, usize)> = program_info
+            .output_modules
+            .iter()
+            .filter_map(|(identifier, info)| {
+                let SplitModuleIdentifier::Split(name) = identifier else {
+                    return None;
+                };
+                let code_size = info.code_size(module);
+                (code_size < options.fold_threshold || options.hoisted_modules.contains(name))
+                    .then(|| (name.clone(), code_size))
+            })
+            .collect();
+        if newly_folded.is_empty() {
+            program_info.folded_modules = folded_modules;
+            return Ok(program_info);
+        }
+        for (name, code_size) in newly_folded.iter() {
+            if options.hoisted_modules.contains(name) {
+                continue;
+            }
+            println!("{}", fold_note(name, *code_size, options.fold_threshold));
+        }
+        folded_modules.extend(newly_folded);
+    }
+}
+
+/// Line of the build output for a split module folded for its size.
+fn fold_note(name: &str, code_size: usize, fold_threshold: usize) -> String {
+    format!(
+        "Folding split module {name} into main: \
+         code size of {code_size} bytes is below the fold threshold of {fold_threshold} bytes"
+    )
+}
+
+/// Name of the split module of the auto-split crate `crate_name`.
+pub fn auto_split_module_name(crate_name: &str) -> String {
+    format!("auto_{}", crate_name.replace('-', "_"))
+}
+
+/// Defined functions of [`ChunkingOptions::auto_split_crates`] that the main
+/// module can call through the table rather than own, by the name of the
+/// split module of their crate. Startup code cannot wait for a chunk, so the
+/// functions that the start function, the startup exports or the exports
+/// that the loader calls reach all stay, along with the roots of the main
+/// module itself. Crates of folded split modules are skipped.
+///
+/// The functions of [`ChunkingOptions::vendor_chunks`] follow, by the name
+/// of their chunk, which only leave behind those that the start function,
+/// the loader's exports or the code of wasm_split reach: the startup exports
+/// call the chunk through the table as well, which is up to the loader to
+/// have loaded by then.
+fn get_auto_split_funcs(
+    module: &InputModule,
+    dep_graph: &DepGraph,
+    split_points: &[SplitPoint],
+    main_roots: &HashSet<DepNode>,
+    is_folded: &dyn Fn(&str) -> bool,
+    options: &ChunkingOptions,
+) -> anyhow::Result<Vec<(String, HashSet<DepNode>)>> {
+    if options.auto_split_crates.is_empty() && options.vendor_chunks.is_empty() {
+        return Ok(Vec::new());
+    }
+    let names = options
+        .auto_split_crates
+        .iter()
+        .map(|crate_name| auto_split_module_name(crate_name))
+        .chain(options.vendor_chunks.iter().map(|(name, _)| name.clone()))
+        .collect::<Vec<_>>();
+    for (crate_name, name) in options.auto_split_crates.iter().zip(names.iter()) {
+        if crate_name.replace('-', "_") == "wasm_split" {
+            bail!("`auto-split` cannot split out wasm_split, whose code loads the chunks");
+        }
+        if split_points
+            .iter()
+            .any(|split_point| split_point.module_name == *name)
+        {
+            bail!("The split module of `auto-split` crate {crate_name:?} has the name of a split module, {name:?}");
+        }
+    }
+    for (name, crates) in options.vendor_chunks.iter() {
+        if crates
+            .iter()
+            .any(|crate_name| crate_name.replace('-', "_") == "wasm_split")
+        {
+            bail!("Vendor chunk {name:?} cannot take wasm_split, whose code loads the chunks");
+        }
+        if split_points
+            .iter()
+            .any(|split_point| split_point.module_name == *name)
+        {
+            bail!("Vendor chunk {name:?} has the name of a split module");
+        }
+    }
 
-    let split_func_map: HashMap<InputFuncId, InputFuncId> = split_points
+    let split_exports = split_points
+        .iter()
+        .map(|split_point| split_point.export)
+        .collect::<HashSet<_>>();
+    let roots_of = |startup_exports: bool| {
+        let mut roots = module
+            .exports
+            .iter()
+            .enumerate()
+            .filter(|(export_id, export)| {
+                export.kind == wasmparser::ExternalKind::Func
+                    && !split_exports.contains(export_id)
+                    && (export.name.starts_with("__wasm_split_")
+                        || startup_exports
+                            && options
+                                .startup_exports
+                                .iter()
+                                .any(|name| name == export.name))
+            })
+            .map(|(_, export)| DepNode::Function(export.index as InputFuncId))
+            .collect::<HashSet<_>>();
+        roots.extend(module.start.map(DepNode::Function));
+        roots
+    };
+    let exclude = split_points
+        .iter()
+        .map(|split_point| DepNode::Function(split_point.export_func))
+        .collect();
+    let startup = find_reachable_deps(dep_graph, &roots_of(true), &exclude).reachable;
+    // Loading a chunk runs the code of wasm_split, which must not wait for
+    // one itself.
+    let loader = if options.vendor_chunks.is_empty() {
+        HashSet::new()
+    } else {
+        let mut roots = roots_of(false);
+        roots.extend(
+            module
+                .names
+                .functions
+                .iter()
+                .filter(|(_, name)| get_crate_name(&demangle(name)) == "wasm_split")
+                .map(|(&func_id, _)| DepNode::Function(func_id)),
+        );
+        find_reachable_deps(dep_graph, &roots, &exclude).reachable
+    };
+
+    let mut funcs = vec![HashSet::<DepNode>::new(); names.len()];
+    let defined_funcs =
+        module.imported_funcs.len()..module.imported_funcs.len() + module.defined_funcs.len();
+    for func_id in defined_funcs {
+        let node = DepNode::Function(func_id);
+        if main_roots.contains(&node) || exclude.contains(&node) {
+            continue;
+        }
+        if let Some(index) = options.auto_split_crate(module, func_id) {
+            let vendor = index >= options.auto_split_crates.len();
+            if !(if vendor { &loader } else { &startup }).contains(&node) {
+                funcs[index].insert(node);
+            }
+        }
+    }
+    Ok(names
+        .into_iter()
+        .zip(funcs)
+        .filter(|(name, funcs)| !is_folded(name) && !funcs.is_empty())
+        .collect())
+}
+
+/// Name of the split module of the entry point `entry`.
+pub fn entry_module_name(entry: &str) -> String {
+    format!("entry_{}", entry.replace('-', "_"))
+}
+
+/// Defined functions that only the exports of each of
+/// [`ChunkingOptions::entries`] reach, by entry name and the name of its
+/// split module. The exports themselves stay in the main module, which every
+/// entry instantiates, and call the rest through the table once the entry
+/// loaded its chunk. Code that the main thread also reaches stays, and code
+/// that split modules or other entries also reach ends up in chunks shared
+/// with them. Entries of folded split modules are skipped.
+fn get_entry_funcs(
+    module: &InputModule,
+    dep_graph: &DepGraph,
+    split_points: &[SplitPoint],
+    main_roots: &HashSet<DepNode>,
+    exclude: &HashSet<DepNode>,
+    is_folded: &dyn Fn(&str) -> bool,
+    options: &ChunkingOptions,
+) -> anyhow::Result<Vec<(String, String, HashSet<DepNode>)>> {
+    if options.entries.is_empty() {
+        return Ok(Vec::new());
+    }
+    let split_exports = split_points
+        .iter()
+        .map(|split_point| split_point.export)
+        .collect::<HashSet<_>>();
+    let mut entry_exports = Vec::new();
+    for (entry, exports) in options.entries.iter() {
+        let name = entry_module_name(entry);
+        if split_points
+            .iter()
+            .any(|split_point| split_point.module_name == name)
+        {
+            bail!("The split module of entry {entry:?} has the name of a split module, {name:?}");
+        }
+        let mut roots = HashSet::new();
+        for export_name in exports.iter() {
+            let Some((_, export)) =
+                module
+                    .exports
+                    .iter()
+                    .enumerate()
+                    .find(|(export_id, export)| {
+                        export.kind == wasmparser::ExternalKind::Func
+                            && !split_exports.contains(export_id)
+                            && export.name == export_name
+                    })
+            else {
+                bail!("Entry {entry:?} calls {export_name:?}, which is not an exported function");
+            };
+            roots.insert(DepNode::Function(export.index as InputFuncId));
+        }
+        entry_exports.push((entry, name, roots));
+    }
+
+    let mut exclude = exclude.clone();
+    exclude.extend(
+        split_points
+            .iter()
+            .map(|split_point| DepNode::Function(split_point.export_func)),
+    );
+    let mut main_thread_roots = main_roots.clone();
+    for (_, _, roots) in entry_exports.iter() {
+        for root in roots.iter() {
+            main_thread_roots.remove(root);
+        }
+    }
+    let main_thread = find_reachable_deps(dep_graph, &main_thread_roots, &exclude).reachable;
+    exclude.extend(main_thread);
+
+    let defined_funcs =
+        module.imported_funcs.len()..module.imported_funcs.len() + module.defined_funcs.len();
+    Ok(entry_exports
+        .into_iter()
+        .filter(|(_, name, _)| !is_folded(name))
+        .map(|(entry, name, roots)| {
+            let funcs = find_reachable_deps(dep_graph, &roots, &exclude)
+                .reachable
+                .into_iter()
+                .filter(|node| {
+                    !roots.contains(node)
+                        && matches!(node, DepNode::Function(func_id) if defined_funcs.contains(func_id))
+                })
+                .collect::<HashSet<_>>();
+            (entry.clone(), name, funcs)
+        })
+        .filter(|(_, _, funcs)| !funcs.is_empty())
+        .collect())
+}
+
+/// Merges each shared chunk with less than `min_size` bytes of code into the
+/// smallest chunk shared by more split modules, including all of its own, or
+/// else into a chunk of its modules and those of another small shared chunk
+/// that has some of them. Either way, every module that needs the code still
+/// loads it, along with code that some of them do not need, in exchange for
+/// fewer requests. A small chunk with neither stays as it is.
+fn merge_small_shared_chunks(
+    module: &InputModule,
+    contents: &mut HashMap<SplitModuleIdentifier, OutputModuleInfo>,
+    min_size: usize,
+) {
+    if min_size == 0 {
+        return;
+    }
+    loop {
+        let mut shared = contents
+            .iter()
+            .filter_map(|(identifier, info)| match identifier {
+                SplitModuleIdentifier::Chunk(modules) => {
+                    Some((modules.clone(), info.code_size(module)))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        shared.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+        let merge = shared
+            .iter()
+            .filter(|(_, size)| *size < min_size)
+            .find_map(|(modules, _)| {
+                let superset = shared.iter().find(|(other, _)| {
+                    other.len() > modules.len() && modules.iter().all(|m| other.contains(m))
+                });
+                let overlapping = || {
+                    shared.iter().find(|(other, size)| {
+                        *size < min_size
+                            && modules.iter().any(|m| other.contains(m))
+                            && !other.iter().all(|m| modules.contains(m))
+                    })
+                };
+                let target = match superset.or_else(overlapping) {
+                    Some((other, _)) if superset.is_some() => other.clone(),
+                    Some((other, _)) => {
+                        let mut union = modules.iter().chain(other).cloned().collect::<Vec<_>>();
+                        union.sort();
+                        union.dedup();
+                        union
+                    }
+                    None => return None,
+                };
+                Some((modules.clone(), target))
+            });
+        let Some((from, to)) = merge else {
+            return;
+        };
+        let from = contents
+            .remove(&SplitModuleIdentifier::Chunk(from))
+            .unwrap();
+        contents
+            .entry(SplitModuleIdentifier::Chunk(to))
+            .or_default()
+            .included_symbols
+            .extend(from.included_symbols);
+    }
+}
+
+/// The symbols of the main module for [`ChunkingOptions::lazy_indirect_calls`]:
+/// those that `main_roots` reach through `call_graph`, and the functions
+/// whose address they take that none of the split modules of `split_roots`
+/// reach, which stay along with what they reach in turn. The others are left
+/// to those split modules.
+fn find_main_deps_with_lazy_targets(
+    dep_graph: &DepGraph,
+    call_graph: &DepGraph,
+    main_roots: &HashSet<DepNode>,
+    split_roots: &[HashSet<DepNode>],
+    exclude: &HashSet<DepNode>,
+) -> ReachabilityGraph {
+    let mut roots = main_roots.clone();
+    loop {
+        let main_deps = find_reachable_deps(call_graph, &roots, exclude);
+        let split_reachable = split_roots
+            .par_iter()
+            .flat_map_iter(|roots| {
+                find_reachable_deps(dep_graph, roots, &main_deps.reachable).reachable
+            })
+            .collect::<HashSet<_>>();
+        let unowned = main_deps
+            .reachable
+            .iter()
+            .filter_map(|node| dep_graph.get(node))
+            .flatten()
+            .filter(|child| {
+                !main_deps.reachable.contains(child)
+                    && !exclude.contains(child)
+                    && !split_reachable.contains(child)
+            })
+            .copied()
+            .collect::<Vec<_>>();
+        if unowned.is_empty() {
+            return main_deps;
+        }
+        roots.extend(unowned);
+    }
+}
+
+fn compute_split_modules_with_folded(
+    module: &InputModule,
+    dep_graph: &DepGraph,
+    call_graph: Option<&DepGraph>,
+    all_split_points: &[SplitPoint],
+    all_on_load_hooks: &[OnLoadHook],
+    folded_modules: &[(String, usize)],
+    options: &ChunkingOptions,
+) -> anyhow::Result<SplitProgramInfo> {
+    let is_folded = |module_name: &str| folded_modules.iter().any(|(name, _)| name == module_name);
+    let (folded_split_points, split_points): (Vec<SplitPoint>, Vec<SplitPoint>) =
+        all_split_points.iter().cloned().partition(|split_point| {
+            is_folded(&split_point.module_name)
+                || options
+                    .hoisted_split_points
+                    .contains(&split_point.export_func)
+        });
+    let (folded_on_load_hooks, on_load_hooks): (Vec<OnLoadHook>, Vec<OnLoadHook>) =
+        all_on_load_hooks
+            .iter()
+            .cloned()
+            .partition(|hook| is_folded(&hook.module_name));
+    let split_points = &split_points[..];
+
+    let split_points_by_module = get_split_points_by_module(split_points);
+
+    // Calls to the import of a folded split point are likewise resolved to
+    // the corresponding export, which is simply defined in the main module.
+    let split_func_map: HashMap<InputFuncId, InputFuncId> = all_split_points
         .iter()
         .map(|split_point| (split_point.import_func, split_point.export_func))
         .collect();
 
     let remove_ignored_deps = |deps: &mut HashSet<DepNode>| {
-        for split_point in split_points.iter() {
+        for split_point in all_split_points.iter() {
             deps.remove(&DepNode::Function(split_point.import_func));
         }
     };
     let remove_ignored_funcs = |deps: &mut HashSet<InputFuncId>| {
-        for split_point in split_points.iter() {
+        for split_point in all_split_points.iter() {
             deps.remove(&split_point.import_func);
         }
     };
 
-    let main_roots = get_main_module_roots(module, &split_points);
+    let mut main_roots = get_main_module_roots(module, split_points);
+    // Hooks are exported only for the split tool to find them.
+    for hook in on_load_hooks.iter() {
+        main_roots.remove(&DepNode::Function(hook.export_func));
+    }
+    let pinned_funcs = get_wasm_bindgen_pinned_funcs(module, dep_graph);
+    if let Some(split_point) = split_points
+        .iter()
+        .find(|split_point| pinned_funcs.contains(&DepNode::Function(split_point.export_func)))
+    {
+        bail!(
+            "Split function {split_point:?} directly calls a wasm-bindgen describe import, \
+             which requires it to stay in the main module. This should not happen with code \
+             generated by `#[wasm_split]`."
+        );
+    }
+    main_roots.extend(pinned_funcs.iter().copied());
 
-    let mut main_deps = find_reachable_deps(dep_graph, &main_roots, &HashSet::new());
+    let auto_split_funcs = get_auto_split_funcs(
+        module,
+        dep_graph,
+        all_split_points,
+        &main_roots,
+        &is_folded,
+        options,
+    )?;
+    let mut main_exclude: HashSet<DepNode> = auto_split_funcs
+        .iter()
+        .flat_map(|(_, funcs)| funcs.iter().copied())
+        .collect();
+    let entry_funcs = get_entry_funcs(
+        module,
+        dep_graph,
+        all_split_points,
+        &main_roots,
+        &main_exclude,
+        &is_folded,
+        options,
+    )?;
+    main_exclude.extend(
+        entry_funcs
+            .iter()
+            .flat_map(|(_, _, funcs)| funcs.iter().copied()),
+    );
+    let split_module_roots = |module_name: &str, entry_points: &[&SplitPoint]| {
+        let mut roots = HashSet::<DepNode>::new();
+        for entry_point in entry_points.iter() {
+            roots.insert(DepNode::Function(entry_point.export_func));
+        }
+        for hook in on_load_hooks.iter() {
+            if hook.module_name == module_name {
+                roots.insert(DepNode::Function(hook.export_func));
+            }
+        }
+        roots
+    };
+    let mut main_deps = match call_graph {
+        Some(call_graph) => find_main_deps_with_lazy_targets(
+            dep_graph,
+            call_graph,
+            &main_roots,
+            &split_points_by_module
+                .iter()
+                .map(|(module_name, entry_points)| split_module_roots(module_name, entry_points))
+                .collect::<Vec<_>>(),
+            &main_exclude,
+        ),
+        None => find_reachable_deps(dep_graph, &main_roots, &main_exclude),
+    };
 
     remove_ignored_deps(&mut main_deps.reachable);
 
     // Determine reachable symbols (excluding main module symbols) for each
-    // split module. Symbols may be reachable from more than one split module;
-    // these symbols will be moved to a separate module.
+    // split module, in parallel. Symbols may be reachable from more than one
+    // split module; these symbols will be moved to a separate module.
     let mut split_module_candidates: HashMap<String, ReachabilityGraph> = split_points_by_module
-        .iter()
+        .par_iter()
         .map(|(module_name, entry_points)| {
-            let mut roots = HashSet::<DepNode>::new();
-            for entry_point in entry_points.iter() {
-                roots.insert(DepNode::Function(entry_point.export_func));
-            }
+            let roots = split_module_roots(module_name, entry_points);
             let mut split_functions = find_reachable_deps(dep_graph, &roots, &main_deps.reachable);
             remove_ignored_deps(&mut split_functions.reachable);
             (module_name.clone(), split_functions)
         })
         .collect();
 
+    // The functions of an auto-split crate or a vendor chunk that the main
+    // module calls are the roots of its split module, which also gets the
+    // code that only they reach. Crates that the main module does not call at
+    // all are left to the split modules using them.
+    let mut auto_split_modules = Vec::new();
+    let mut vendor_modules = Vec::new();
+    for (module_name, funcs) in auto_split_funcs {
+        let roots = main_deps
+            .reachable
+            .iter()
+            .filter_map(|node| dep_graph.get(node))
+            .flatten()
+            .filter(|child| funcs.contains(child))
+            .copied()
+            .collect::<HashSet<_>>();
+        if roots.is_empty() {
+            continue;
+        }
+        let mut deps = find_reachable_deps(dep_graph, &roots, &main_deps.reachable);
+        remove_ignored_deps(&mut deps.reachable);
+        split_module_candidates.insert(module_name.clone(), deps);
+        if options
+            .vendor_chunks
+            .iter()
+            .any(|(name, _)| *name == module_name)
+        {
+            vendor_modules.push(module_name);
+        } else {
+            auto_split_modules.push(module_name);
+        }
+    }
+    auto_split_modules.sort();
+    vendor_modules.sort();
+
+    // Likewise, the functions of an entry that its exports call are the
+    // roots of the entry's split module.
+    let mut entry_modules = Vec::new();
+    for (entry, module_name, funcs) in entry_funcs {
+        let roots = main_deps
+            .reachable
+            .iter()
+            .filter_map(|node| dep_graph.get(node))
+            .flatten()
+            .filter(|child| funcs.contains(child))
+            .copied()
+            .collect::<HashSet<_>>();
+        if roots.is_empty() {
+            continue;
+        }
+        let mut deps = find_reachable_deps(dep_graph, &roots, &main_deps.reachable);
+        remove_ignored_deps(&mut deps.reachable);
+        split_module_candidates.insert(module_name.clone(), deps);
+        entry_modules.push((entry, module_name));
+    }
+    entry_modules.sort();
+
     // Set of split modules from which each symbol is reachable.
     let mut dep_candidate_modules = HashMap::<DepNode, Vec<String>>::new();
     for (module_name, deps) in split_module_candidates.iter() {
@@ -349,15 +1135,67 @@ pub fn compute_split_modules(
         }
     }
 
-    let mut program_info = SplitProgramInfo::default();
+    let mut program_info = SplitProgramInfo {
+        wasm_bindgen_funcs: pinned_funcs
+            .iter()
+            .filter_map(|node| match node {
+                DepNode::Function(func_id) => Some(*func_id),
+                _ => None,
+            })
+            .collect(),
+        auto_split_modules,
+        vendor_modules,
+        entry_modules,
+        ..Default::default()
+    };
 
     let mut split_module_contents = HashMap::<SplitModuleIdentifier, OutputModuleInfo>::new();
 
     split_module_contents.insert(SplitModuleIdentifier::Main, main_deps.into());
 
+    // Code of the crates that the config puts into named chunks, which each
+    // split module that reaches any of it loads.
+    let mut crate_chunk_deps = HashMap::<DepNode, usize>::new();
+    let mut crate_chunk_modules = vec![Vec::<String>::new(); options.crate_chunks.len()];
+    // Split modules that share any code, for `SharedStrategy::Single`.
+    let mut sharing_modules = Vec::<String>::new();
+    for (dep, modules) in dep_candidate_modules.iter() {
+        if let Some(index) = options.crate_chunk(module, dep) {
+            crate_chunk_deps.insert(*dep, index);
+            crate_chunk_modules[index].extend(modules.iter().cloned());
+        } else if modules.len() > 1 {
+            sharing_modules.extend(modules.iter().cloned());
+        }
+    }
+    for modules in crate_chunk_modules
+        .iter_mut()
+        .chain(std::iter::once(&mut sharing_modules))
+    {
+        modules.sort();
+        modules.dedup();
+    }
+
     for (dep, mut modules) in dep_candidate_modules {
-        if modules.len() > 1 {
-            modules.sort();
+        if let Some(&index) = crate_chunk_deps.get(&dep) {
+            for module in modules.iter() {
+                let module_contents = split_module_candidates.get_mut(module).unwrap();
+                module_contents.reachable.remove(&dep);
+            }
+            split_module_contents
+                .entry(SplitModuleIdentifier::Named(
+                    options.crate_chunks[index].0.clone(),
+                    crate_chunk_modules[index].clone(),
+                ))
+                .or_default()
+                .included_symbols
+                .insert(dep);
+        } else if modules.len() > 1 {
+            match options.shared_strategy {
+                SharedStrategy::PerGroup => options.colocate(&mut modules, |name| {
+                    split_module_candidates.contains_key(name)
+                }),
+                SharedStrategy::Single => modules.clone_from(&sharing_modules),
+            }
             for module in modules.iter() {
                 let module_contents = split_module_candidates.get_mut(module).unwrap();
                 module_contents.reachable.remove(&dep);
@@ -375,31 +1213,61 @@ pub fn compute_split_modules(
             .drain()
             .map(|(module_name, deps)| (SplitModuleIdentifier::Split(module_name), deps.into())),
     );
+    merge_small_shared_chunks(module, &mut split_module_contents, options.min_shared_size);
+    if let Some((name, _)) = options.crate_chunks.iter().find(|(name, _)| {
+        split_module_contents
+            .keys()
+            .any(|identifier| identifier.dependents().is_none() && identifier.name() == *name)
+    }) {
+        bail!("Chunk {name:?} of [chunks] has the name of a split module");
+    }
 
-    for contents in split_module_contents.values_mut() {
-        for symbol in contents.included_symbols.iter() {
-            let Some(neighbors) = dep_graph.get(symbol) else {
-                continue;
-            };
-            for mut called_func_id in neighbors.iter().filter_map(|symbol| match symbol {
-                DepNode::Function(func_id) => Some(*func_id),
-                _ => None,
-            }) {
-                called_func_id = *split_func_map
-                    .get(&called_func_id)
-                    .unwrap_or(&called_func_id);
-                if !contents
-                    .included_symbols
-                    .contains(&DepNode::Function(called_func_id))
-                {
-                    contents.shared_imports.insert(called_func_id);
-                    program_info.shared_funcs.insert(called_func_id);
+    // Each module resolves its calls of other modules' code on its own, so
+    // they are done in parallel and their imports added up afterwards.
+    split_module_contents
+        .par_iter_mut()
+        .for_each(|(identifier, contents)| {
+            let mut queue: VecDeque<DepNode> = contents.included_symbols.iter().copied().collect();
+            while let Some(symbol) = queue.pop_front() {
+                let Some(neighbors) = dep_graph.get(&symbol) else {
+                    continue;
+                };
+                for mut called_func_id in neighbors.iter().filter_map(|symbol| match symbol {
+                    DepNode::Function(func_id) => Some(*func_id),
+                    _ => None,
+                }) {
+                    called_func_id = *split_func_map
+                        .get(&called_func_id)
+                        .unwrap_or(&called_func_id);
+                    if contents
+                        .included_symbols
+                        .contains(&DepNode::Function(called_func_id))
+                    {
+                        continue;
+                    }
+                    if *identifier != SplitModuleIdentifier::Main
+                        && options.can_duplicate(module, split_points, called_func_id)
+                        && !pinned_funcs.contains(&DepNode::Function(called_func_id))
+                    {
+                        // The copy may itself call other functions, which must
+                        // also be either duplicated or imported.
+                        contents
+                            .included_symbols
+                            .insert(DepNode::Function(called_func_id));
+                        contents.duplicated_funcs.insert(called_func_id);
+                        queue.push_back(DepNode::Function(called_func_id));
+                    } else {
+                        contents.shared_imports.insert(called_func_id);
+                    }
                 }
             }
-        }
-        remove_ignored_funcs(&mut contents.shared_imports);
-    }
-    remove_ignored_funcs(&mut program_info.shared_funcs);
+            remove_ignored_funcs(&mut contents.shared_imports);
+        });
+    program_info.shared_funcs.extend(
+        split_module_contents
+            .values()
+            .flat_map(|contents| contents.shared_imports.iter().copied()),
+    );
 
     for split_point in split_points {
         program_info.shared_funcs.insert(split_point.export_func);
@@ -410,6 +1278,30 @@ pub fn compute_split_modules(
             .unwrap();
         output_module.split_points.push(split_point.clone());
     }
+    // The loader calls hooks through the table, like split points.
+    for hook in on_load_hooks {
+        program_info.shared_funcs.insert(hook.export_func);
+        split_module_contents
+            .get_mut(&SplitModuleIdentifier::Split(hook.module_name.clone()))
+            .unwrap()
+            .on_load_hooks
+            .push(hook);
+    }
+    program_info
+        .shared_funcs
+        .extend(folded_on_load_hooks.iter().map(|hook| hook.export_func));
+    if options.folded_split_point_slots {
+        program_info.shared_funcs.extend(
+            folded_split_points
+                .iter()
+                .map(|split_point| split_point.export_func),
+        );
+    }
+    let main_contents = split_module_contents
+        .get_mut(&SplitModuleIdentifier::Main)
+        .unwrap();
+    main_contents.split_points.extend(folded_split_points);
+    main_contents.on_load_hooks.extend(folded_on_load_hooks);
 
     program_info.output_modules = split_module_contents.drain().collect();
     program_info
@@ -424,11 +1316,102 @@ pub fn compute_split_modules(
 
     for (output_index, (_, info)) in program_info.output_modules.iter().enumerate() {
         for &symbol in info.included_symbols.iter() {
+            if let DepNode::Function(func_id) = symbol {
+                if info.duplicated_funcs.contains(&func_id) {
+                    continue;
+                }
+            }
             program_info
                 .symbol_output_module
                 .insert(symbol, output_index);
         }
     }
+    let is_import = |func_id: InputFuncId| func_id < module.imported_funcs.len();
+    program_info.main_imports = program_info.output_modules
+        [program_info.output_module_identifiers[&SplitModuleIdentifier::Main]]
+        .1
+        .included_symbols
+        .iter()
+        .filter(|symbol| !matches!(symbol, DepNode::Function(func_id) if is_import(*func_id)))
+        .filter_map(|symbol| dep_graph.get(symbol))
+        .flatten()
+        .filter_map(|symbol| match *symbol {
+            DepNode::Function(func_id) if is_import(func_id) => Some(func_id),
+            _ => None,
+        })
+        .collect();
 
     Ok(program_info)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::fold_note;
+    use crate::{
+        options::{split_wasm, SplitOptions},
+        test_fixtures::{expected_no_std_app_result, fixture_path, split, SplitOutput},
+    };
+
+    fn folded(output: &SplitOutput) -> Vec<(String, u64)> {
+        let manifest = output.manifest();
+        let mut folded = manifest["folded"]
+            .as_array()
+            .map_or(&[][..], Vec::as_slice)
+            .iter()
+            .map(|module| {
+                (
+                    module["name"].as_str().unwrap().to_string(),
+                    module["code_size"].as_u64().unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        folded.sort();
+        folded
+    }
+
+    #[test]
+    fn folds_split_modules_below_the_threshold() {
+        assert!(folded(&split("no_std_app.wasm", &["--fold-threshold", "0"])).is_empty());
+
+        // By default, all but `second` cost more as chunks than they save.
+        let input = std::fs::read(fixture_path("no_std_app.wasm")).unwrap();
+        let output = split_wasm(&input, &SplitOptions::new()).unwrap();
+        let mut folded_by_default = output
+            .manifest
+            .folded
+            .iter()
+            .map(|module| module.name.as_str())
+            .collect::<Vec<_>>();
+        folded_by_default.sort();
+        assert_eq!(
+            folded_by_default,
+            ["bonus", "details", "first", "geometry", "squares", "tally", "verify"]
+        );
+
+        let output = split("no_std_app.wasm", &["--fold-threshold", "50"]);
+        output.validate();
+        let folded = folded(&output);
+        assert_eq!(
+            folded.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+            ["bonus", "details", "squares"]
+        );
+        assert!(folded.iter().all(|&(_, code_size)| code_size < 50));
+        let manifest = output.manifest();
+        let chunks = manifest["chunks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|chunk| chunk["name"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert!(chunks.contains(&"geometry"), "{chunks:?}");
+        assert!(!chunks.contains(&"bonus"), "{chunks:?}");
+        assert_eq!(
+            fold_note("bonus", 46, 50),
+            "Folding split module bonus into main: code size of 46 bytes is below the fold \
+             threshold of 50 bytes"
+        );
+        if let Some(result) = output.run_no_std_app(4) {
+            assert_eq!(result, expected_no_std_app_result(4));
+        }
+    }
+}
@@ -0,0 +1,55 @@
+// Takes the place of the wasm-bindgen glue import at the top of the loader for
+// inputs built without wasm-bindgen, such as `no_std` apps that declare their
+// imports and exports by hand. The app instantiates the main module with
+//
+//   import { instantiateMain } from "./__wasm_split.js";
+//
+//   const instance = await instantiateMain({ env: { ... } });
+//
+// passing its own imports, to which those of the loader are added.
+//
+// Copies of this script for the same build share the instance, as those of
+// apps built with wasm-bindgen share the module of the glue, so that an app
+// that imports the loader anew, e.g. under another URL, uses the chunks that
+// the first copy loaded; see `getRegistry`. The imports of the first call
+// are the ones used.
+const MAIN_URL = new URL("./main.wasm", import.meta.url);
+const MAIN_INSTANTIATIONS = Symbol.for("wasm-split:main-instantiations");
+
+let mainInstantiation;
+let mainExports;
+
+export function instantiateMain(imports = {}) {
+  // Checked against the manifest, which is defined further down as well, as
+  // is the fallback with `fallback-after`.
+  const [url, integrity] = USE_FALLBACK
+    ? [FALLBACK_URL, FALLBACK_INTEGRITY ?? undefined]
+    : [MAIN_URL, MANIFEST.chunks.find(({ kind }) => kind === "main")?.integrity];
+  const shared = (globalThis[MAIN_INSTANTIATIONS] ??= new Map());
+  const key = MANIFEST.build_id + " " + url.href;
+  if (!shared.has(key)) {
+    const instantiation = WebAssembly.instantiateStreaming(fetch(url, { integrity }), {
+      ...imports,
+      // Both defined further down in the loader.
+      [LOADER_MODULE]: MAIN_IMPORTS,
+    });
+    shared.set(key, instantiation);
+  }
+  mainInstantiation ??= shared.get(key).then(({ instance }) => {
+    mainExports = instance.exports;
+    return instance;
+  });
+  return mainInstantiation;
+}
+
+function initSync() {
+  if (mainExports === undefined) {
+    throw new Error(
+      "The main module has not been instantiated; call instantiateMain first",
+    );
+  }
+  return mainExports;
+}
+
+// Stands in for the namespace of the glue, which `getMainExports` reads.
+const mainGlue = { initSync };
@@ -0,0 +1,175 @@
+// Service worker integration for wasm-split.
+//
+// Import this script into the application's service worker to keep chunks in
+// Cache Storage, so that they are available locally the next time the app is
+// opened:
+//
+//   importScripts("/pkg/wasm-split-sw.js");
+//
+//   const chunkCache = new WasmSplitChunkCache("/pkg/");
+//
+//   // Serve cached chunks without going to the network.
+//   self.addEventListener("fetch", (event) => chunkCache.handleFetch(event));
+//
+//   // Download the chunks of the routes named by a push message such as
+//   // `{"routes": ["/admin"]}`.
+//   self.addEventListener("push", (event) => {
+//     event.waitUntil(chunkCache.hydrateRoutes(event.data.json().routes));
+//   });
+//
+//   // Or keep a fixed set of routes up to date with periodic background sync.
+//   self.addEventListener("periodicsync", (event) => {
+//     if (event.tag !== "wasm-split") return;
+//     event.waitUntil(chunkCache.hydrateRoutes(["/", "/admin"]));
+//   });
+//
+//   // Or download every chunk when the service worker is installed, for apps
+//   // that must work offline.
+//   self.addEventListener("install", (event) => {
+//     event.waitUntil(chunkCache.precacheAll());
+//   });
+//
+// The loader takes chunks from these caches itself, even without the `fetch`
+// handler, with `cache-storage = true` in the `[loader]` table. Service
+// workers built with Workbox can precache `wasm-split-precache.json`
+// instead, which also lists the main module, its glue and the loader.
+//
+// Routes and the split modules they need are declared in the `[routes]` table
+// of `wasm-split.toml` and listed in the manifest. Each build is cached
+// separately, and hydrating deletes the caches of all other builds.
+//
+// For builds written with `--asset-version`, import the script of the current
+// version and pass its directory, e.g. `/pkg/v123/`. Requests of sessions
+// still running an older version are then left to the network, which serves
+// them from that version's directory.
+//
+// For builds whose manifest is signed (see `wasm_split::manifest_public_key!`),
+// pass the same public key, which the service worker cannot read from the
+// main module, to only cache chunks listed by a manifest whose signature
+// verifies:
+//
+//   const chunkCache = new WasmSplitChunkCache("/pkg/", {
+//     publicKey: new Uint8Array([/* the 32 bytes of the key */]),
+//   });
+
+const CACHE_PREFIX = "wasm-split-";
+
+self.WasmSplitChunkCache = class WasmSplitChunkCache {
+  // `baseUrl` is the URL of the directory containing the split output.
+  constructor(baseUrl, { publicKey } = {}) {
+    this.baseUrl = new URL(baseUrl, self.location.href);
+    this.publicKey = publicKey;
+  }
+
+  // Throws unless the `.sig` file next to the manifest at `url` is a valid
+  // Ed25519 signature of `bytes`.
+  async verifyManifest(url, bytes) {
+    const signatureUrl = new URL(url);
+    signatureUrl.pathname += ".sig";
+    const response = await fetch(signatureUrl, { cache: "no-cache" });
+    if (!response.ok) {
+      throw new Error(
+        `Failed to fetch ${signatureUrl}: HTTP status ${response.status}`,
+      );
+    }
+    const key = await crypto.subtle.importKey(
+      "raw",
+      this.publicKey,
+      { name: "Ed25519" },
+      false,
+      ["verify"],
+    );
+    const valid = await crypto.subtle.verify(
+      { name: "Ed25519" },
+      key,
+      await response.arrayBuffer(),
+      bytes,
+    );
+    if (!valid) throw new Error(`Invalid signature of ${url}`);
+  }
+
+  // Fetches the deployed manifest, bypassing the HTTP cache.
+  async fetchManifest() {
+    // Rewritten by wasm-split if the manifest is moved with `[output]`.
+    const url = new URL("./wasm-split-manifest.json", this.baseUrl);
+    const response = await fetch(url, { cache: "no-cache" });
+    if (!response.ok) {
+      throw new Error(`Failed to fetch ${url}: HTTP status ${response.status}`);
+    }
+    const bytes = await response.arrayBuffer();
+    if (this.publicKey !== undefined) {
+      await this.verifyManifest(response.url, bytes);
+    }
+    return JSON.parse(new TextDecoder().decode(bytes));
+  }
+
+  // Downloads all chunks needed by `routes`, including shared chunks they
+  // depend on, that are not cached yet.
+  async hydrateRoutes(routes) {
+    const manifest = await this.fetchManifest();
+    const chunks = new Map(manifest.chunks.map((chunk) => [chunk.name, chunk]));
+    const names = new Set();
+    const visit = (name) => {
+      if (names.has(name)) return;
+      names.add(name);
+      for (const dep of chunks.get(name)?.dependencies ?? []) visit(dep);
+    };
+    for (const route of routes) {
+      const modules = manifest.routes?.[route];
+      if (modules === undefined) {
+        console.warn(`wasm-split: unknown route ${route}`);
+        continue;
+      }
+      modules.forEach(visit);
+    }
+    await this.cacheChunks(manifest, names);
+  }
+
+  // Downloads all chunks of the deployed build that are not cached yet.
+  async precacheAll() {
+    const manifest = await this.fetchManifest();
+    const names = manifest.chunks
+      .filter((chunk) => chunk.kind !== "main")
+      .map((chunk) => chunk.name);
+    await this.cacheChunks(manifest, names);
+  }
+
+  // Caches the chunks of `manifest` named by `names`, and deletes the caches
+  // of all other builds.
+  async cacheChunks(manifest, names) {
+    const chunks = new Map(manifest.chunks.map((chunk) => [chunk.name, chunk]));
+    const cacheName = CACHE_PREFIX + manifest.build_id;
+    const cache = await caches.open(cacheName);
+    const urls = [];
+    for (const name of names) {
+      // Folded modules have no chunk of their own.
+      const chunk = chunks.get(name);
+      if (chunk === undefined) continue;
+      const url = new URL(chunk.file, this.baseUrl);
+      // Matches the URL requested by the loader.
+      url.searchParams.set("build", manifest.build_id);
+      if ((await cache.match(url)) === undefined) urls.push(url);
+    }
+    await cache.addAll(urls);
+
+    for (const key of await caches.keys()) {
+      if (key.startsWith(CACHE_PREFIX) && key !== cacheName) {
+        await caches.delete(key);
+      }
+    }
+  }
+
+  // Responds to a request for a chunk from the cache, if it is cached.
+  handleFetch(event) {
+    const url = new URL(event.request.url);
+    if (!url.href.startsWith(this.baseUrl.href)) return;
+    const build = url.searchParams.get("build");
+    if (!url.pathname.endsWith(".wasm") || build === null) return;
+    event.respondWith(
+      caches
+        .open(CACHE_PREFIX + build)
+        .then((cache) => cache.match(event.request))
+        .then((response) => response ?? fetch(event.request)),
+    );
+  }
+};
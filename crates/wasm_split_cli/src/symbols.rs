@@ -0,0 +1,275 @@
+//! Symbol map for server-side symbolication of stack traces.
+//!
+//! The symbol map is written to [`SYMBOLS_FILENAME`] as UTF-8 text. The first
+//! line is the header `# wasm-split symbols v1`; every following line
+//! describes one function of one output module, as four tab-separated fields:
+//!
+//! ```text
+//! <chunk>\t<function index>\t<demangled name>\t<location>
+//! ```
+//!
+//! - `chunk` is the logical chunk name, as in the manifest.
+//! - `function index` is the index of the function within that chunk, as it
+//!   appears in `wasm-function[N]` stack frames. Imported functions are not
+//!   listed since they cannot appear as frames of the chunk.
+//! - `demangled name` is the function name from the name section, demangled
+//!   as chosen with `--demangle`. Tabs and newlines are replaced by spaces.
+//! - `location` is `<file>:<line>` of the first line-table row of the
+//!   function, if the input module contains DWARF line information, and
+//!   empty otherwise.
+
+use std::{borrow::Cow, collections::HashMap, fmt::Write};
+
+use anyhow::Result;
+
+use crate::{
+    emit::EmittedModule,
+    read::{InputFuncId, InputModule},
+    split_point::SplitProgramInfo,
+};
+
+pub const SYMBOLS_FILENAME: &str = "wasm-split-symbols.tsv";
+
+pub type Reader<'a> = gimli::EndianSlice<'a, gimli::LittleEndian>;
+
+pub fn demangle(name: &str) -> String {
+    format!("{:#}", rustc_demangle::demangle(name))
+}
+
+/// How reports display function names, chosen with `--demangle`. Crates are
+/// attributed by the full name either way.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Demangling {
+    /// As demangled by `rustc-demangle`, with every generic argument.
+    #[default]
+    Full,
+    /// With the generic arguments of every path elided, e.g.
+    /// `core::ptr::drop_glue::<_>` for all of its instances, which keeps
+    /// the names of code generic over serde or Leptos types readable.
+    Compact,
+}
+
+impl Demangling {
+    pub fn demangle(self, name: &str) -> String {
+        let demangled = demangle(name);
+        match self {
+            Self::Full => demangled,
+            Self::Compact => elide_generic_args(&demangled),
+        }
+    }
+}
+
+/// Replaces the generic arguments of path segments by `_`, while
+/// keeping the types of qualified paths such as `<T as Trait>::method`, whose
+/// own arguments are elided in turn.
+fn elide_generic_args(demangled: &str) -> String {
+    let mut output = String::with_capacity(demangled.len());
+    // Nesting depth within elided arguments.
+    let mut depth = 0;
+    let mut previous = None;
+    for c in demangled.chars() {
+        // The `>` of the `->` of a function pointer type closes nothing.
+        let is_arrow = previous == Some('-');
+        match c {
+            '<' if depth > 0 => depth += 1,
+            '>' if depth > 0 && !is_arrow => depth -= 1,
+            _ if depth > 0 => {}
+            // After a segment, or `::` as in `drop_glue::<T>`.
+            '<' if previous.is_some_and(|previous: char| {
+                previous.is_alphanumeric() || matches!(previous, '_' | ':')
+            }) =>
+            {
+                output.push_str("<_>");
+                depth = 1;
+            }
+            _ => output.push(c),
+        }
+        previous = Some(c);
+    }
+    output
+}
+
+/// Reads the DWARF sections of `module`, which are missing if it has no
+/// debug info.
+pub fn load_dwarf<'a>(module: &InputModule<'a>) -> Result<gimli::Dwarf<Reader<'a>>> {
+    gimli::Dwarf::load(|id| -> Result<Reader> {
+        let data = module
+            .custom_sections
+            .iter()
+            .find(|section| section.name == id.name())
+            .map_or(&[][..], |section| section.data);
+        Ok(gimli::EndianSlice::new(data, gimli::LittleEndian))
+    })
+}
+
+/// Path of the source file of the line table of `unit` with `file_index`.
+pub fn source_path(
+    dwarf: &gimli::Dwarf<Reader>,
+    unit: &gimli::Unit<Reader>,
+    file_index: u64,
+) -> Result<Option<String>> {
+    let Some(header) = unit.line_program.as_ref().map(|program| program.header()) else {
+        return Ok(None);
+    };
+    let Some(file) = header.file(file_index) else {
+        return Ok(None);
+    };
+    let mut path = String::new();
+    if let Some(directory) = file.directory(header) {
+        path.push_str(&dwarf.attr_string(unit, directory)?.to_string_lossy());
+        path.push('/');
+    }
+    path.push_str(&dwarf.attr_string(unit, file.path_name())?.to_string_lossy());
+    Ok(Some(path))
+}
+
+/// Returns the source location of each defined function for which DWARF line
+/// information is available.
+pub fn get_function_locations(module: &InputModule) -> Result<HashMap<InputFuncId, String>> {
+    let dwarf = load_dwarf(module)?;
+
+    // Line table rows as `(address, unit index, file index, line)`, where the
+    // address is relative to the start of the code section.
+    let mut units = Vec::new();
+    let mut rows = Vec::<(u64, usize, u64, u64)>::new();
+    let mut headers = dwarf.units();
+    while let Some(header) = headers.next()? {
+        let unit = dwarf.unit(header)?;
+        if let Some(program) = unit.line_program.clone() {
+            let mut program_rows = program.rows();
+            while let Some((_, row)) = program_rows.next_row()? {
+                let Some(line) = row.line() else {
+                    continue;
+                };
+                if row.end_sequence() {
+                    continue;
+                }
+                rows.push((row.address(), units.len(), row.file_index(), line.get()));
+            }
+        }
+        units.push(unit);
+    }
+    rows.sort_unstable();
+
+    let mut locations = HashMap::new();
+    if rows.is_empty() {
+        return Ok(locations);
+    }
+    for (defined_index, func) in module.defined_funcs.iter().enumerate() {
+        let range = func.body.range();
+        let start = (range.start - module.code_section_offset) as u64;
+        let end = (range.end - module.code_section_offset) as u64;
+        let index = rows.partition_point(|&(address, ..)| address < start);
+        let Some(&(address, unit_index, file_index, line)) = rows.get(index) else {
+            continue;
+        };
+        if address >= end {
+            continue;
+        }
+        let Some(path) = source_path(&dwarf, &units[unit_index], file_index)? else {
+            continue;
+        };
+        locations.insert(
+            module.imported_funcs.len() + defined_index,
+            format!("{path}:{line}"),
+        );
+    }
+    Ok(locations)
+}
+
+fn sanitize(field: &str) -> Cow<'_, str> {
+    if field.contains(['\t', '\n']) {
+        Cow::Owned(field.replace(['\t', '\n'], " "))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+pub fn get_symbol_map(
+    module: &InputModule,
+    program_info: &SplitProgramInfo,
+    emitted_modules: &[EmittedModule],
+    demangling: Demangling,
+) -> Result<String> {
+    let locations = get_function_locations(module)?;
+    let mut output = String::from("# wasm-split symbols v1\n");
+    let mut functions_by_name = HashMap::new();
+    for ((identifier, _), emitted) in program_info.output_modules.iter().zip(emitted_modules) {
+        let chunk = identifier.name();
+        if let Some(names) = &emitted.optimized_names {
+            // Functions that `wasm-opt` created or merged have names of their
+            // own, and no location.
+            if functions_by_name.is_empty() {
+                functions_by_name = module
+                    .names
+                    .functions
+                    .iter()
+                    .map(|(&func_id, &name)| (name, func_id))
+                    .collect();
+            }
+            for (&output_func_id, name) in names.range(emitted.defined_functions.start..) {
+                let location = functions_by_name
+                    .get(name.as_str())
+                    .and_then(|input_func_id| locations.get(input_func_id))
+                    .map(String::as_str)
+                    .unwrap_or_default();
+                writeln!(
+                    output,
+                    "{chunk}\t{output_func_id}\t{name}\t{location}",
+                    name = sanitize(&demangling.demangle(name)),
+                    location = sanitize(location),
+                )?;
+            }
+            continue;
+        }
+        for (output_func_id, input_func_id) in emitted.functions.iter().enumerate() {
+            // Imports precede all other functions.
+            if output_func_id < emitted.defined_functions.start {
+                continue;
+            }
+            let name = module
+                .names
+                .functions
+                .get(input_func_id)
+                .map(|name| demangling.demangle(name))
+                .unwrap_or_default();
+            let location = locations
+                .get(input_func_id)
+                .map(String::as_str)
+                .unwrap_or_default();
+            writeln!(
+                output,
+                "{chunk}\t{output_func_id}\t{name}\t{location}",
+                name = sanitize(&name),
+                location = sanitize(location),
+            )?;
+        }
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::elide_generic_args;
+
+    #[test]
+    fn elides_generic_arguments_of_paths() {
+        for (full, compact) in [
+            (
+                "core::ptr::drop_glue::<alloc::vec::Vec<serde_json::Value>>",
+                "core::ptr::drop_glue::<_>",
+            ),
+            (
+                "<alloc::vec::Vec<u8> as core::ops::drop::Drop>::drop",
+                "<alloc::vec::Vec<_> as core::ops::drop::Drop>::drop",
+            ),
+            (
+                "leptos::mount<fn() -> app::View, app::View>::{closure#0}",
+                "leptos::mount<_>::{closure#0}",
+            ),
+            ("app::main", "app::main"),
+        ] {
+            assert_eq!(elide_generic_args(full), compact);
+        }
+    }
+}
@@ -0,0 +1,913 @@
+//! Relocation-free splitting, for inputs that were not linked with
+//! `--emit-relocs`.
+//!
+//! Without relocations, function and data references in the input cannot be
+//! located reliably, so the main module is copied almost unchanged and only
+//! the bodies of `#[wasm_split]` functions are moved into split modules.
+//! Everything they call stays in the main module. Specifically:
+//!
+//! - Every function called by a moved body is given a slot in the indirect
+//!   function table of the main module, and the moved body calls it through
+//!   that slot. This only requires parsing the moved bodies themselves.
+//! - Each moved function is also given a table slot, which the split module's
+//!   element segment fills in when it is instantiated. The split point import
+//!   remains an import of the main module and is implemented by the JS loader
+//!   as a call through that slot.
+//! - The original bodies in the main module are replaced by `unreachable`.
+//!
+//! Function indices of the main module remain unchanged, so its name section
+//! stays valid. DWARF sections are dropped since code offsets change.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    ops::Range,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use wasmparser::{ElementItems, ElementKind, ExternalKind, Operator, Payload, TypeRef};
+
+use crate::{
+    dep_graph::DepNode,
+    emit::EmittedModule,
+    features,
+    manifest::{content_hash, gzip_size, sha256_hex, sri_hash},
+    read::{GlobalId, InputFuncId, InputModule},
+    split_point::{
+        get_split_points_by_module, is_wasm_bindgen_describe_import, OutputModuleInfo,
+        SplitModuleIdentifier, SplitPoint, SplitProgramInfo,
+    },
+    toolchain::WASM_SPLIT_JS_MODULE,
+};
+
+pub struct TableOnlySplit {
+    pub program_info: SplitProgramInfo,
+    pub emitted_modules: Vec<EmittedModule>,
+    /// Table slot of the moved function that each split point import calls,
+    /// keyed by the name of the import.
+    pub import_slots: BTreeMap<String, u32>,
+}
+
+fn encode_u32(mut value: u32, out: &mut Vec<u8>) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn encode_i32(mut value: i32, out: &mut Vec<u8>) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0) {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// Assignment of indirect function table slots in the main module.
+struct TableSlots {
+    /// Slots of functions that were already in the table of the input.
+    existing: HashMap<InputFuncId, u32>,
+    /// Functions added to the table, starting at slot `base`.
+    added: Vec<InputFuncId>,
+    base: u32,
+    /// Functions whose slot holds a forwarder appended to the main module
+    /// rather than the function itself, in the order of the forwarders.
+    forwarded: Vec<InputFuncId>,
+    forwarded_slots: HashMap<InputFuncId, u32>,
+    /// Number of empty slots after the added ones, for callbacks registered
+    /// at runtime.
+    reserved: u32,
+}
+
+impl TableSlots {
+    fn new(module: &InputModule, reserved: u32) -> Result<Self> {
+        if module.tables.len() != 1
+            || module
+                .imports
+                .iter()
+                .any(|import| matches!(import.ty, TypeRef::Table(_)))
+        {
+            bail!("Table-only splitting requires exactly one table, defined by the input module");
+        }
+        let mut existing = HashMap::new();
+        for element in module.elements.iter() {
+            let ElementKind::Active {
+                table_index: None | Some(0),
+                offset_expr,
+            } = &element.kind
+            else {
+                continue;
+            };
+            let Ok(Operator::I32Const { value: offset }) =
+                offset_expr.get_operators_reader().read()
+            else {
+                continue;
+            };
+            let ElementItems::Functions(funcs) = &element.items else {
+                continue;
+            };
+            for (i, func) in funcs.clone().into_iter().enumerate() {
+                existing
+                    .entry(func? as InputFuncId)
+                    .or_insert(offset as u32 + i as u32);
+            }
+        }
+        Ok(Self {
+            existing,
+            added: Vec::new(),
+            base: module.tables[0].ty.initial,
+            forwarded: Vec::new(),
+            forwarded_slots: HashMap::new(),
+            reserved,
+        })
+    }
+
+    /// Returns the slot through which split modules reach `func_id`.
+    ///
+    /// wasm-bindgen changes the signature of imports that take or return JS
+    /// values, and replaces the functions converting Rust closures to JS
+    /// functions by such imports, but only adapts direct calls to them. These
+    /// are therefore called through a forwarder with the original signature,
+    /// in a slot of its own even if the function already has one.
+    fn get_for_split(&mut self, module: &InputModule, func_id: InputFuncId) -> Result<u32> {
+        if !needs_forwarder(module, func_id)? {
+            return Ok(self.get(func_id));
+        }
+        if let Some(&slot) = self.forwarded_slots.get(&func_id) {
+            return Ok(slot);
+        }
+        let slot = self.base + self.added.len() as u32;
+        self.added.push(func_id);
+        self.forwarded.push(func_id);
+        self.forwarded_slots.insert(func_id, slot);
+        Ok(slot)
+    }
+
+    fn get(&mut self, func_id: InputFuncId) -> u32 {
+        if let Some(&slot) = self.existing.get(&func_id) {
+            return slot;
+        }
+        let slot = self.base + self.added.len() as u32;
+        self.existing.insert(func_id, slot);
+        self.added.push(func_id);
+        slot
+    }
+
+    /// Allocates the slot of a function that is moved into a split module,
+    /// which fills it in when instantiated. Until then the slot refers to the
+    /// function's body in the main module, which traps.
+    fn reserve(&mut self, func_id: InputFuncId) {
+        self.get(func_id);
+    }
+
+    fn size(&self) -> u32 {
+        self.base + self.added.len() as u32 + self.reserved
+    }
+
+    fn reserved_range(&self) -> Range<usize> {
+        (self.size() - self.reserved) as usize..self.size() as usize
+    }
+}
+
+fn needs_forwarder(module: &InputModule, func_id: InputFuncId) -> Result<bool> {
+    let Some(defined_index) = func_id.checked_sub(module.imported_funcs.len()) else {
+        return Ok(true);
+    };
+    let mut operators = module.defined_funcs[defined_index]
+        .body
+        .get_operators_reader()?;
+    while !operators.eof() {
+        if let Operator::Call { function_index } = operators.read()? {
+            if module
+                .imported_funcs
+                .get(function_index as usize)
+                .is_some_and(|&import_id| {
+                    is_wasm_bindgen_describe_import(&module.imports[import_id])
+                })
+            {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// A moved function as emitted into its split module.
+struct MovedFunction {
+    func_id: InputFuncId,
+    slot: u32,
+    body: Vec<u8>,
+}
+
+struct SplitModuleBuilder<'a> {
+    module: &'a InputModule<'a>,
+    /// Index within the split module of each function moved into it.
+    local_funcs: HashMap<InputFuncId, u32>,
+    /// Globals imported by the split module, in order of first use.
+    globals: Vec<GlobalId>,
+}
+
+impl<'a> SplitModuleBuilder<'a> {
+    fn global_index(&mut self, global: u32) -> u32 {
+        let global = global as GlobalId;
+        match self.globals.iter().position(|&g| g == global) {
+            Some(index) => index as u32,
+            None => {
+                self.globals.push(global);
+                self.globals.len() as u32 - 1
+            }
+        }
+    }
+
+    /// Re-encodes a moved function body for the split module, rewriting
+    /// references to functions and globals.
+    fn rewrite_body(&mut self, func_id: InputFuncId, slots: &mut TableSlots) -> Result<Vec<u8>> {
+        let module = self.module;
+        let body = &module.defined_funcs[func_id - module.imported_funcs.len()].body;
+        let range = body.range();
+        let mut operators = body.get_operators_reader()?;
+        let mut out = module.raw[range.start..operators.original_position()].to_vec();
+        let mut instructions = Vec::new();
+        while !operators.eof() {
+            let (operator, offset) = operators.read_with_offset()?;
+            instructions.push((operator, offset, operators.original_position()));
+        }
+        for (operator, start, end) in instructions {
+            let call_indirect =
+                |slots: &mut TableSlots, target: u32, opcode: u8, out: &mut Vec<u8>| {
+                    out.push(0x41); // i32.const
+                    encode_i32(
+                        slots.get_for_split(module, target as InputFuncId)? as i32,
+                        out,
+                    );
+                    out.push(opcode);
+                    encode_u32(module.func_type_id(target as InputFuncId) as u32, out);
+                    out.push(0x00); // table 0
+                    anyhow::Ok(())
+                };
+            match operator {
+                Operator::Call { function_index }
+                    if module
+                        .imported_funcs
+                        .get(function_index as usize)
+                        .is_some_and(|&import_id| {
+                            is_wasm_bindgen_describe_import(&module.imports[import_id])
+                        }) =>
+                {
+                    bail!(
+                        "Function {func_id} calls a wasm-bindgen describe import, so it must \
+                         stay in the main module for wasm-bindgen to process it"
+                    );
+                }
+                Operator::Call { function_index } => {
+                    match self.local_funcs.get(&(function_index as InputFuncId)) {
+                        Some(&local) => {
+                            out.push(0x10);
+                            encode_u32(local, &mut out);
+                        }
+                        None => call_indirect(slots, function_index, 0x11, &mut out)?,
+                    }
+                }
+                Operator::ReturnCall { function_index } => {
+                    match self.local_funcs.get(&(function_index as InputFuncId)) {
+                        Some(&local) => {
+                            out.push(0x12);
+                            encode_u32(local, &mut out);
+                        }
+                        None => call_indirect(slots, function_index, 0x13, &mut out)?,
+                    }
+                }
+                Operator::RefFunc { function_index } => {
+                    match self.local_funcs.get(&(function_index as InputFuncId)) {
+                        Some(&local) => {
+                            out.push(0xd2);
+                            encode_u32(local, &mut out);
+                        }
+                        None => {
+                            out.push(0x41); // i32.const
+                            let slot =
+                                slots.get_for_split(module, function_index as InputFuncId)?;
+                            encode_i32(slot as i32, &mut out);
+                            out.extend([0x25, 0x00]); // table.get 0
+                        }
+                    }
+                }
+                Operator::GlobalGet { global_index } => {
+                    out.push(0x23);
+                    encode_u32(self.global_index(global_index), &mut out);
+                }
+                Operator::GlobalSet { global_index } => {
+                    out.push(0x24);
+                    encode_u32(self.global_index(global_index), &mut out);
+                }
+                Operator::MemoryInit { .. }
+                | Operator::DataDrop { .. }
+                | Operator::TableInit { .. }
+                | Operator::ElemDrop { .. }
+                | Operator::Throw { .. }
+                | Operator::Try { .. }
+                | Operator::Catch { .. }
+                | Operator::TryTable { .. } => {
+                    bail!(
+                        "Instruction {operator:?} in function {func_id} is not supported \
+                         by table-only splitting"
+                    );
+                }
+                _ => out.extend_from_slice(&module.raw[start..end]),
+            }
+        }
+        Ok(out)
+    }
+}
+
+fn get_global_types(module: &InputModule) -> Vec<wasmparser::GlobalType> {
+    module
+        .imports
+        .iter()
+        .filter_map(|import| match import.ty {
+            TypeRef::Global(ty) => Some(ty),
+            _ => None,
+        })
+        .chain(module.globals.iter().map(|global| global.ty))
+        .collect()
+}
+
+/// Name under which the main module exports a global, and whether the
+/// export needs to be added.
+fn get_global_export_name(module: &InputModule, global: GlobalId) -> (String, bool) {
+    match module
+        .export_map
+        .get(&(ExternalKind::Global as isize, global))
+    {
+        Some((_, name)) => (name.to_string(), false),
+        None => (
+            module
+                .names
+                .globals
+                .get(&global)
+                .map_or_else(|| format!("__global_{global}"), |name| name.to_string()),
+            true,
+        ),
+    }
+}
+
+fn emit_split_module(
+    module: &InputModule,
+    table_size: u32,
+    builder: &SplitModuleBuilder,
+    moved: &[MovedFunction],
+) -> Result<Vec<u8>> {
+    let mut output = wasm_encoder::Module::new();
+
+    let mut types = wasm_encoder::TypeSection::new();
+    for func_type in module.types.iter() {
+        let func_type: wasm_encoder::FuncType = func_type.clone().try_into().unwrap();
+        types.function(
+            func_type.params().iter().cloned(),
+            func_type.results().iter().cloned(),
+        );
+    }
+    output.section(&types);
+
+    let mut imports = wasm_encoder::ImportSection::new();
+    let table_type = module.tables[0].ty;
+    imports.import(
+        "__wasm_split",
+        "__indirect_function_table",
+        wasm_encoder::TableType {
+            element_type: table_type.element_type.try_into().unwrap(),
+            minimum: table_size,
+            maximum: crate::limits::table_maximum(Some(&table_type), table_size)?,
+        },
+    );
+    for (memory_index, memory) in module.memories.iter().enumerate() {
+        let name = module
+            .export_map
+            .get(&(ExternalKind::Memory as isize, memory_index))
+            .map_or("memory", |(_, name)| name);
+        imports.import(
+            "__wasm_split",
+            name,
+            wasm_encoder::MemoryType::from(*memory),
+        );
+    }
+    let global_types = get_global_types(module);
+    for &global in builder.globals.iter() {
+        let (name, _) = get_global_export_name(module, global);
+        let ty: wasm_encoder::GlobalType = global_types[global].try_into().unwrap();
+        imports.import("__wasm_split", &name, ty);
+    }
+    output.section(&imports);
+
+    let mut functions = wasm_encoder::FunctionSection::new();
+    for moved in moved {
+        functions.function(module.func_type_id(moved.func_id) as u32);
+    }
+    output.section(&functions);
+
+    let mut elements = wasm_encoder::ElementSection::new();
+    for (local, moved) in moved.iter().enumerate() {
+        elements.active(
+            None,
+            &wasm_encoder::ConstExpr::i32_const(moved.slot as i32),
+            wasm_encoder::Elements::Functions(&[local as u32]),
+        );
+    }
+    output.section(&elements);
+
+    let mut code = wasm_encoder::CodeSection::new();
+    for moved in moved {
+        code.raw(&moved.body);
+    }
+    output.section(&code);
+
+    Ok(output.finish())
+}
+
+fn emit_main_module(
+    module: &InputModule,
+    moved_funcs: &HashSet<InputFuncId>,
+    removed_exports: &HashSet<usize>,
+    slots: &TableSlots,
+    global_exports: &[(String, GlobalId)],
+    loader_module: &str,
+) -> Result<Vec<u8>> {
+    let mut output = wasm_encoder::Module::new();
+    let func_count = module.imported_funcs.len() + module.defined_funcs.len();
+    let mut emitted_elements = false;
+    let emit_elements = |output: &mut wasm_encoder::Module| {
+        let mut section = wasm_encoder::ElementSection::new();
+        for element in module.elements.iter() {
+            section.raw(&module.raw[element.range.clone()]);
+        }
+        let added = slots
+            .added
+            .iter()
+            .enumerate()
+            .map(|(i, &func)| {
+                if slots.forwarded_slots.get(&func) == Some(&(slots.base + i as u32)) {
+                    let forwarder = slots.forwarded.iter().position(|&f| f == func).unwrap();
+                    (func_count + forwarder) as u32
+                } else {
+                    func as u32
+                }
+            })
+            .collect::<Vec<_>>();
+        section.active(
+            None,
+            &wasm_encoder::ConstExpr::i32_const(slots.base as i32),
+            wasm_encoder::Elements::Functions(&added),
+        );
+        output.section(&section);
+    };
+
+    for payload in wasmparser::Parser::new(0).parse_all(module.raw) {
+        let payload = payload?;
+        // The element section follows the start section and precedes all
+        // other known sections.
+        if !emitted_elements
+            && matches!(
+                payload,
+                Payload::DataCountSection { .. }
+                    | Payload::CodeSectionStart { .. }
+                    | Payload::DataSection(_)
+                    | Payload::End(_)
+            )
+        {
+            emit_elements(&mut output);
+            emitted_elements = true;
+        }
+        match payload {
+            Payload::ImportSection(_) => {
+                let mut section = wasm_encoder::ImportSection::new();
+                for import in module.imports.iter() {
+                    let import_module = if import.module == WASM_SPLIT_JS_MODULE {
+                        loader_module
+                    } else {
+                        import.module
+                    };
+                    let ty: wasm_encoder::EntityType = import.ty.try_into().unwrap();
+                    section.import(import_module, import.name, ty);
+                }
+                output.section(&section);
+            }
+            Payload::TableSection(_) => {
+                let mut section = wasm_encoder::TableSection::new();
+                let table_type = module.tables[0].ty;
+                let size = slots.size();
+                section.table(wasm_encoder::TableType {
+                    element_type: table_type.element_type.try_into().unwrap(),
+                    minimum: size,
+                    maximum: crate::limits::table_maximum(Some(&table_type), size)?,
+                });
+                output.section(&section);
+            }
+            Payload::ExportSection(_) => {
+                let mut section = wasm_encoder::ExportSection::new();
+                for (export_id, export) in module.exports.iter().enumerate() {
+                    if removed_exports.contains(&export_id) {
+                        continue;
+                    }
+                    section.export(export.name, export.kind.into(), export.index);
+                }
+                if !module.exports.iter().any(|export| {
+                    export.kind == ExternalKind::Table && export.name == "__indirect_function_table"
+                }) {
+                    section.export(
+                        "__indirect_function_table",
+                        wasm_encoder::ExportKind::Table,
+                        0,
+                    );
+                }
+                for (name, global) in global_exports {
+                    section.export(name, wasm_encoder::ExportKind::Global, *global as u32);
+                }
+                output.section(&section);
+            }
+            Payload::ElementSection(_) => {
+                emit_elements(&mut output);
+                emitted_elements = true;
+            }
+            Payload::FunctionSection(_) => {
+                let mut section = wasm_encoder::FunctionSection::new();
+                for func_id in
+                    (module.imported_funcs.len()..func_count).chain(slots.forwarded.iter().copied())
+                {
+                    section.function(module.func_type_id(func_id) as u32);
+                }
+                output.section(&section);
+            }
+            Payload::CodeSectionStart { .. } => {
+                let mut section = wasm_encoder::CodeSection::new();
+                for (defined_index, func) in module.defined_funcs.iter().enumerate() {
+                    if moved_funcs.contains(&(module.imported_funcs.len() + defined_index)) {
+                        // No locals, `unreachable`, `end`.
+                        section.raw(&[0x00, 0x00, 0x0b]);
+                    } else {
+                        section.raw(&module.raw[func.body.range()]);
+                    }
+                }
+                for &func_id in slots.forwarded.iter() {
+                    let func_type = &module.types[module.func_type_id(func_id)];
+                    let mut function = wasm_encoder::Function::new([]);
+                    for param in 0..func_type.params().len() {
+                        function.instruction(&wasm_encoder::Instruction::LocalGet(param as u32));
+                    }
+                    function.instruction(&wasm_encoder::Instruction::Call(func_id as u32));
+                    function.instruction(&wasm_encoder::Instruction::End);
+                    section.function(&function);
+                }
+                output.section(&section);
+            }
+            Payload::CodeSectionEntry(_) | Payload::Version { .. } | Payload::End(_) => {}
+            Payload::CustomSection(reader)
+                if reader.name().starts_with(".debug_")
+                    || reader.name() == "linking"
+                    || reader.name().starts_with("reloc.") => {}
+            payload => {
+                let Some((id, range)) = payload.as_section() else {
+                    bail!("Unexpected payload {payload:?}");
+                };
+                output.section(&wasm_encoder::RawSection {
+                    id,
+                    data: &module.raw[range],
+                });
+            }
+        }
+    }
+    Ok(output.finish())
+}
+
+pub fn split(
+    module: &InputModule,
+    split_points: &[SplitPoint],
+    loader_module: &str,
+    reserved_table_slots: usize,
+    emit_fn: &dyn Fn(&SplitModuleIdentifier, &[u8]) -> Result<()>,
+) -> Result<TableOnlySplit> {
+    let mut slots = TableSlots::new(module, reserved_table_slots as u32)?;
+    let moved_funcs: HashSet<InputFuncId> = split_points
+        .iter()
+        .map(|split_point| split_point.export_func)
+        .collect();
+    let removed_exports: HashSet<usize> = split_points
+        .iter()
+        .map(|split_point| split_point.export)
+        .collect();
+
+    let mut program_info = SplitProgramInfo::default();
+    let mut split_modules = Vec::new();
+    let mut import_slots = BTreeMap::new();
+    let mut global_exports = BTreeMap::<GlobalId, String>::new();
+    let mut split_points_by_module = get_split_points_by_module(split_points)
+        .into_iter()
+        .collect::<Vec<_>>();
+    split_points_by_module.sort_by(|a, b| a.0.cmp(&b.0));
+    // Moved functions get their slots first, so that they are allocated in a
+    // stable order and calls between split modules use them.
+    for (_, module_split_points) in split_points_by_module.iter_mut() {
+        module_split_points.sort_by_key(|split_point| module.exports[split_point.export].name);
+        for split_point in module_split_points.iter() {
+            slots.reserve(split_point.export_func);
+        }
+    }
+    for (name, module_split_points) in split_points_by_module {
+        let mut builder = SplitModuleBuilder {
+            module,
+            local_funcs: HashMap::new(),
+            globals: Vec::new(),
+        };
+        for split_point in module_split_points.iter() {
+            let local = builder.local_funcs.len() as u32;
+            builder
+                .local_funcs
+                .entry(split_point.export_func)
+                .or_insert(local);
+        }
+        let mut moved = Vec::new();
+        for split_point in module_split_points.iter() {
+            if moved
+                .iter()
+                .any(|moved: &MovedFunction| moved.func_id == split_point.export_func)
+            {
+                continue;
+            }
+            let slot = slots.get(split_point.export_func);
+            let body = builder
+                .rewrite_body(split_point.export_func, &mut slots)
+                .with_context(|| format!("Error moving split point {split_point:?}"))?;
+            moved.push(MovedFunction {
+                func_id: split_point.export_func,
+                slot,
+                body,
+            });
+        }
+        for split_point in module_split_points.iter() {
+            let moved = moved
+                .iter()
+                .find(|moved| moved.func_id == split_point.export_func)
+                .ok_or_else(|| anyhow!("Split point {split_point:?} was not moved"))?;
+            import_slots.insert(
+                module.imports[split_point.import].name.to_string(),
+                moved.slot,
+            );
+        }
+        for &global in builder.globals.iter() {
+            if let (name, true) = get_global_export_name(module, global) {
+                global_exports.insert(global, name);
+            }
+        }
+        split_modules.push((name, module_split_points, builder, moved));
+    }
+
+    // All slots are allocated by now, so the table size is final.
+    let table_size = slots.size();
+    let mut emitted_modules = Vec::new();
+
+    let mut main_data = emit_main_module(
+        module,
+        &moved_funcs,
+        &removed_exports,
+        &slots,
+        &global_exports
+            .iter()
+            .map(|(&global, name)| (name.clone(), global))
+            .collect::<Vec<_>>(),
+        loader_module,
+    )?;
+    let main_features = features::add_section(&mut main_data)?;
+    emit_fn(&SplitModuleIdentifier::Main, &main_data)?;
+    let func_count = module.imported_funcs.len() + module.defined_funcs.len();
+    program_info.output_modules.push((
+        SplitModuleIdentifier::Main,
+        OutputModuleInfo {
+            included_symbols: (0..func_count)
+                .filter(|func_id| !moved_funcs.contains(func_id))
+                .map(DepNode::Function)
+                .collect(),
+            ..Default::default()
+        },
+    ));
+    emitted_modules.push(EmittedModule {
+        defined_functions: module.imported_funcs.len()..func_count,
+        functions: (0..func_count)
+            .chain(slots.forwarded.iter().copied())
+            .collect(),
+        size: main_data.len(),
+        gzip_size: gzip_size(&main_data),
+        hash: content_hash(&main_data),
+        sha256: sha256_hex(&main_data),
+        integrity: sri_hash(&main_data),
+        compressed_sizes: BTreeMap::new(),
+        optimized_names: None,
+        features: main_features,
+        signatures: Default::default(),
+        table_slots: slots
+            .added
+            .iter()
+            .enumerate()
+            .filter(|(_, func_id)| !moved_funcs.contains(func_id))
+            .map(|(i, &func_id)| (slots.base as usize + i, func_id))
+            .collect(),
+        reserved_table_slots: slots.reserved_range(),
+    });
+
+    for (name, module_split_points, builder, moved) in split_modules {
+        let identifier = SplitModuleIdentifier::Split(name);
+        let mut data = emit_split_module(module, table_size, &builder, &moved)?;
+        let features = features::add_section(&mut data)?;
+        emit_fn(&identifier, &data)?;
+        let output_module_index = program_info.output_modules.len();
+        for moved in moved.iter() {
+            program_info
+                .symbol_output_module
+                .insert(DepNode::Function(moved.func_id), output_module_index);
+        }
+        program_info
+            .output_module_identifiers
+            .insert(identifier.clone(), output_module_index);
+        program_info.output_modules.push((
+            identifier,
+            OutputModuleInfo {
+                included_symbols: moved
+                    .iter()
+                    .map(|moved| DepNode::Function(moved.func_id))
+                    .collect(),
+                split_points: module_split_points.into_iter().cloned().collect(),
+                ..Default::default()
+            },
+        ));
+        emitted_modules.push(EmittedModule {
+            defined_functions: 0..moved.len(),
+            functions: moved.iter().map(|moved| moved.func_id).collect(),
+            size: data.len(),
+            gzip_size: gzip_size(&data),
+            hash: content_hash(&data),
+            sha256: sha256_hex(&data),
+            integrity: sri_hash(&data),
+            compressed_sizes: BTreeMap::new(),
+            optimized_names: None,
+            features,
+            signatures: Default::default(),
+            table_slots: moved
+                .iter()
+                .map(|moved| (moved.slot as usize, moved.func_id))
+                .collect(),
+            reserved_table_slots: 0..0,
+        });
+    }
+    program_info
+        .output_module_identifiers
+        .insert(SplitModuleIdentifier::Main, 0);
+
+    Ok(TableOnlySplit {
+        program_info,
+        emitted_modules,
+        import_slots,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Range;
+
+    use wasmparser::{ElementItems, ElementKind, Operator, Parser, Payload};
+
+    use crate::test_fixtures::{
+        expected_no_std_app_result, split, SplitOutput, CLOSURE_APP_RESULTS,
+    };
+
+    /// Initial size of the module's table, if it defines one, and the slots
+    /// that its active element segments fill in.
+    fn get_table(wasm: &[u8]) -> (Option<u32>, Vec<u32>) {
+        let mut size = None;
+        let mut slots = Vec::new();
+        for payload in Parser::new(0).parse_all(wasm) {
+            match payload.unwrap() {
+                Payload::TableSection(reader) => {
+                    size = Some(reader.into_iter().next().unwrap().unwrap().ty.initial);
+                }
+                Payload::ElementSection(reader) => {
+                    for element in reader {
+                        let element = element.unwrap();
+                        let ElementKind::Active { offset_expr, .. } = element.kind else {
+                            continue;
+                        };
+                        let Operator::I32Const { value: offset } =
+                            offset_expr.get_operators_reader().read().unwrap()
+                        else {
+                            panic!("Element segment with a non-constant offset");
+                        };
+                        let count = match element.items {
+                            ElementItems::Functions(funcs) => funcs.count(),
+                            ElementItems::Expressions(_, exprs) => exprs.count(),
+                        };
+                        slots.extend(offset as u32..offset as u32 + count);
+                    }
+                }
+                _ => {}
+            }
+        }
+        (size, slots)
+    }
+
+    fn split_without_relocs() -> SplitOutput {
+        let output = split("no_std_app_no_relocs.wasm", &[]);
+        output.validate();
+        output
+    }
+
+    #[test]
+    fn moves_split_functions_into_modules() {
+        let output = split_without_relocs();
+        assert_eq!(
+            output.wasm_files(),
+            [
+                "bonus.wasm",
+                "details.wasm",
+                "first.wasm",
+                "geometry.wasm",
+                "main.wasm",
+                "second.wasm",
+                "squares.wasm",
+                "tally.wasm",
+                "verify.wasm"
+            ]
+        );
+        // Both the even and the odd case of the call through a function
+        // pointer in `second`.
+        for n in [4, 5] {
+            if let Some(result) = output.run_no_std_app(n) {
+                assert_eq!(result, expected_no_std_app_result(n));
+            }
+        }
+    }
+
+    #[test]
+    fn split_modules_fill_in_the_slots_of_their_functions() {
+        let output = split_without_relocs();
+        let manifest = output.manifest();
+        let table = manifest["table"].as_array().unwrap();
+        let loader = String::from_utf8(output.read("__wasm_split.js")).unwrap();
+        for chunk in ["first", "second"] {
+            let (size, filled) = get_table(&output.read(&format!("{chunk}.wasm")));
+            assert_eq!(size, None, "{chunk} imports the table of the main module");
+            let slots = table
+                .iter()
+                .filter(|slot| slot["chunk"] == chunk)
+                .map(|slot| slot["slot"].as_u64().unwrap() as u32)
+                .collect::<Vec<_>>();
+            assert!(!slots.is_empty());
+            assert_eq!(filled, slots);
+            // The loader implements the split point import by calling through
+            // the slot.
+            for slot in slots {
+                assert!(loader.contains(&format!("callTableSlot({slot}, args)")));
+            }
+        }
+    }
+
+    #[test]
+    fn reserves_empty_table_slots_after_the_added_ones() {
+        let output = split_without_relocs();
+        let manifest = output.manifest();
+        let reserved = &manifest["reserved_table_slots"];
+        let reserved =
+            reserved["start"].as_u64().unwrap() as u32..reserved["end"].as_u64().unwrap() as u32;
+        assert_eq!(reserved.len(), 4, "`second` reserves 4 slots");
+        let last_added = manifest["table"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|slot| slot["slot"].as_u64().unwrap() as u32)
+            .max()
+            .unwrap();
+        assert_eq!(reserved.start, last_added + 1);
+        let (size, _) = get_table(&output.read("main.wasm"));
+        assert_eq!(size, Some(reserved.end));
+        for file in output.wasm_files() {
+            let (_, filled) = get_table(&output.read(&file));
+            assert!(
+                !filled.iter().any(|slot| Range::contains(&reserved, slot)),
+                "{file} fills in a reserved slot"
+            );
+        }
+    }
+
+    #[test]
+    fn calls_closures_created_in_split_functions() {
+        let output = split("closure_app_no_relocs.wasm", &[]);
+        output.validate();
+        if let Some(results) = output.run_closure_app() {
+            assert_eq!(results, CLOSURE_APP_RESULTS);
+        }
+    }
+}
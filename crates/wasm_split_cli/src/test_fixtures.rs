@@ -0,0 +1,608 @@
+//! Splitting the prebuilt fixtures in `testdata` in tests; see `build.py`
+//! there for how they are built.
+
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use clap::Parser;
+
+use crate::Cli;
+
+/// Version of the wasm-bindgen CLI matching the one `closure_app` depends on.
+const WASM_BINDGEN_VERSION: &str = "0.2.129";
+
+pub fn fixture_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("testdata")
+        .join(name)
+}
+
+/// Output directory of a split, removed when dropped along with the config
+/// file the split read, if any.
+pub struct SplitOutput {
+    pub dir: PathBuf,
+}
+
+impl SplitOutput {
+    fn config_path(&self) -> PathBuf {
+        self.dir.with_extension("toml")
+    }
+}
+
+impl Drop for SplitOutput {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+        let _ = std::fs::remove_file(self.config_path());
+    }
+}
+
+fn output_dir() -> PathBuf {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    std::env::temp_dir().join(format!(
+        "wasm-split-test-{}-{}",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+/// An empty directory, removed when dropped, for tests that write files of
+/// their own.
+pub fn temp_dir() -> SplitOutput {
+    let output = SplitOutput { dir: output_dir() };
+    std::fs::create_dir_all(&output.dir).unwrap();
+    output
+}
+
+/// Splits the fixture `name` with the given `wasm-split.toml` and command
+/// line options, and returns the error the split failed with, if any, along
+/// with its output.
+pub fn try_split(name: &str, config: &str, options: &[&str]) -> (SplitOutput, anyhow::Result<()>) {
+    let output = SplitOutput { dir: output_dir() };
+    let config_path = output.config_path();
+    std::fs::write(&config_path, config).unwrap();
+    let input = fixture_path(name);
+    // The fixtures are small enough that most of their split modules would
+    // be folded by default.
+    let no_folding = (!options.contains(&"--fold-threshold") && !config.contains("min-size"))
+        .then_some(["--fold-threshold", "0"]);
+    let args = [
+        "wasm-split".as_ref(),
+        input.as_os_str(),
+        output.dir.as_os_str(),
+        "--config".as_ref(),
+        config_path.as_os_str(),
+    ]
+    .into_iter()
+    .chain(
+        no_folding
+            .into_iter()
+            .flatten()
+            .map(|option| option.as_ref()),
+    )
+    .chain(options.iter().map(|option| option.as_ref()));
+    let result = crate::run(&Cli::parse_from(args));
+    (output, result)
+}
+
+pub fn split(name: &str, options: &[&str]) -> SplitOutput {
+    let (output, result) = try_split(name, "", options);
+    result.unwrap_or_else(|err| panic!("Failed to split {name}: {err:?}"));
+    output
+}
+
+impl SplitOutput {
+    pub fn read(&self, path: &str) -> Vec<u8> {
+        std::fs::read(self.dir.join(path))
+            .unwrap_or_else(|err| panic!("Failed to read {path}: {err}"))
+    }
+
+    pub fn manifest(&self) -> serde_json::Value {
+        serde_json::from_slice(&self.read("wasm-split-manifest.json")).unwrap()
+    }
+
+    pub fn write(&self, path: &str, contents: &[u8]) {
+        std::fs::write(self.dir.join(path), contents).unwrap();
+    }
+
+    /// Names of the `.wasm` files written, sorted.
+    pub fn wasm_files(&self) -> Vec<String> {
+        let mut files = std::fs::read_dir(&self.dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .filter(|name| name.ends_with(".wasm"))
+            .collect::<Vec<_>>();
+        files.sort();
+        files
+    }
+
+    /// Checks that every module written is valid.
+    pub fn validate(&self) {
+        for file in self.wasm_files() {
+            wasmparser::Validator::new_with_features(wasmparser::WasmFeatures::all())
+                .validate_all(&self.read(&file))
+                .unwrap_or_else(|err| panic!("{file} is invalid: {err}"));
+        }
+    }
+
+    /// Structure of the split as the manifest describes it, one line per
+    /// fact: the chunks with their kind, entries, dependencies and features,
+    /// and the folded modules, routes and aliases. Sizes, hashes and table
+    /// slots are left out, as are the hashes in the export names of split
+    /// points, which change with the position of the function in its file,
+    /// so that only changes to what the splitter does change the outline.
+    pub fn manifest_outline(&self) -> String {
+        let manifest = self.manifest();
+        let export_hash = regex::Regex::new("_export_[0-9a-f]{32}_").unwrap();
+        let strings = |value: &serde_json::Value| {
+            let mut values = value
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|value| export_hash.replace(value.as_str().unwrap(), "_export_"))
+                .collect::<Vec<_>>();
+            values.sort();
+            values.join(" ")
+        };
+        let mut out = String::new();
+        for chunk in manifest["chunks"].as_array().unwrap() {
+            out += &format!(
+                "chunk {} {}\n",
+                chunk["name"].as_str().unwrap(),
+                chunk["kind"].as_str().unwrap()
+            );
+            for key in ["entries", "dependencies", "features"] {
+                let values = strings(&chunk[key]);
+                if !values.is_empty() {
+                    out += &format!("  {key} {values}\n");
+                }
+            }
+        }
+        for folded in manifest["folded"].as_array().into_iter().flatten() {
+            out += &format!("folded {}\n", folded["name"].as_str().unwrap());
+        }
+        for (route, modules) in manifest["routes"].as_object().into_iter().flatten() {
+            out += &format!("route {route} {}\n", strings(modules));
+        }
+        for (alias, module) in manifest["aliases"].as_object().into_iter().flatten() {
+            out += &format!("alias {alias} {}\n", module.as_str().unwrap());
+        }
+        out
+    }
+
+    /// Runs the split `no_std_app`, returning the result of its `run(n)`, or
+    /// `None` if Node is not installed.
+    pub fn run_no_std_app(&self, n: u32) -> Option<u32> {
+        self.run_no_std_app_export("run", n)
+    }
+
+    /// As [`Self::run_no_std_app`], returning what Node wrote to stderr if
+    /// the app failed.
+    pub fn try_run_no_std_app(&self, n: u32) -> Option<Result<u32, String>> {
+        let n = n.to_string();
+        self.try_run_node("run.mjs", &[self.dir.as_os_str(), n.as_ref()], &[])
+            .map(|result| result.map(|output| output.parse().unwrap()))
+    }
+
+    /// As [`Self::run_no_std_app`], for another export with the signature
+    /// of `run`.
+    pub fn run_no_std_app_export(&self, export: &str, n: u32) -> Option<u32> {
+        self.run_no_std_app_with(export, n, &[])
+    }
+
+    /// As [`Self::run_no_std_app`], with `WebAssembly.compileStreaming`
+    /// removed.
+    pub fn run_no_std_app_without_streaming(&self, n: u32) -> Option<u32> {
+        self.run_no_std_app_with("run", n, &[("NO_COMPILE_STREAMING", "1")])
+    }
+
+    /// As [`Self::run_no_std_app`], failing if the loader compiles a chunk
+    /// with `WebAssembly.compile` rather than while streaming it.
+    pub fn run_no_std_app_streaming_only(&self, n: u32) -> Option<u32> {
+        self.run_no_std_app_with("run", n, &[("NO_COMPILE", "1")])
+    }
+
+    /// As [`Self::run_no_std_app`], once the loader has loaded the chunks of
+    /// `auto-split` crates, which the main module calls directly.
+    pub fn run_no_std_app_after_auto_chunks(&self, n: u32) -> Option<u32> {
+        self.run_no_std_app_with("run", n, &[("AWAIT_AUTO_CHUNKS", "1")])
+    }
+
+    /// As [`Self::run_no_std_app_export`], once the loader has loaded the
+    /// chunks of `entry` of the `[entries]` config, as that entry would.
+    pub fn run_no_std_app_entry(&self, entry: &str, export: &str, n: u32) -> Option<u32> {
+        self.run_no_std_app_with(export, n, &[("ENTRY", entry)])
+    }
+
+    /// As [`Self::run_no_std_app_export`], with `Worker` provided, which
+    /// fails unless some call ran on the worker.
+    pub fn run_no_std_app_with_workers(&self, export: &str, n: u32) -> Option<u32> {
+        self.run_no_std_app_with(export, n, &[("WEB_WORKERS", "1")])
+    }
+
+    /// As [`Self::run_no_std_app_export`], with the first `count` requests
+    /// for chunks failing as if the network was down.
+    pub fn run_no_std_app_with_failing_fetches(
+        &self,
+        export: &str,
+        n: u32,
+        count: usize,
+    ) -> Option<u32> {
+        let count = count.to_string();
+        self.run_no_std_app_with(export, n, &[("FAILING_FETCHES", &count)])
+    }
+
+    /// As [`Self::run_no_std_app`], with the base URL of chunks set before
+    /// the loader runs, as by a page that serves them from elsewhere.
+    pub fn run_no_std_app_with_base_url(&self, n: u32, base_url: &str) -> Option<u32> {
+        self.run_no_std_app_with("run", n, &[("BASE_URL", base_url)])
+    }
+
+    /// As [`Self::run_no_std_app_export`], returning what the loader reports
+    /// to devtools once the export is done, rather than its result.
+    pub fn inspect_no_std_app(&self, export: &str, n: u32) -> Option<serde_json::Value> {
+        let n = n.to_string();
+        self.run_node(
+            "run.mjs",
+            &[self.dir.as_os_str(), n.as_ref(), export.as_ref()],
+            &[("INSPECT", "1")],
+        )
+        .map(|output| serde_json::from_str(&output).unwrap())
+    }
+
+    /// As [`Self::run_no_std_app_export`], returning along with the result
+    /// how many times each file was requested.
+    pub fn count_no_std_app_fetches(
+        &self,
+        export: &str,
+        n: u32,
+    ) -> Option<(u32, std::collections::BTreeMap<String, u32>)> {
+        let n = n.to_string();
+        self.run_node(
+            "run.mjs",
+            &[self.dir.as_os_str(), n.as_ref(), export.as_ref()],
+            &[("COUNT_FETCHES", "1")],
+        )
+        .map(|output| {
+            let (result, counts) = output.split_once('\n').unwrap();
+            (
+                result.parse().unwrap(),
+                serde_json::from_str(counts).unwrap(),
+            )
+        })
+    }
+
+    /// As [`Self::run_no_std_app_export`], with requests for chunks
+    /// responding after a delay, returning along with the result the most
+    /// of them that were in flight at once.
+    pub fn run_no_std_app_with_slow_fetches(&self, export: &str, n: u32) -> Option<(u32, u32)> {
+        let n = n.to_string();
+        self.run_node(
+            "run.mjs",
+            &[self.dir.as_os_str(), n.as_ref(), export.as_ref()],
+            &[("FETCH_DELAY_MS", "20")],
+        )
+        .map(|output| {
+            let (result, in_flight) = output.split_once('\n').unwrap();
+            (result.parse().unwrap(), in_flight.trim().parse().unwrap())
+        })
+    }
+
+    /// As [`Self::run_no_std_app_export`], with the loader configured with
+    /// a `fetch` of its own that requires a header and credentials,
+    /// returning along with the result the number of its requests.
+    pub fn run_no_std_app_with_custom_fetch(&self, export: &str, n: u32) -> Option<(u32, u32)> {
+        let n = n.to_string();
+        self.run_node(
+            "run.mjs",
+            &[self.dir.as_os_str(), n.as_ref(), export.as_ref()],
+            &[("CUSTOM_FETCH", "1")],
+        )
+        .map(|output| {
+            let (result, fetches) = output.split_once('\n').unwrap();
+            (result.parse().unwrap(), fetches.trim().parse().unwrap())
+        })
+    }
+
+    /// As [`Self::run_no_std_app_export`], returning along with the result
+    /// the details of the chunk compiles that the loader measured.
+    pub fn measure_no_std_app_compiles(
+        &self,
+        export: &str,
+        n: u32,
+    ) -> Option<(u32, Vec<serde_json::Value>)> {
+        let n = n.to_string();
+        self.run_node(
+            "run.mjs",
+            &[self.dir.as_os_str(), n.as_ref(), export.as_ref()],
+            &[("COMPILE_MEASURES", "1")],
+        )
+        .map(|output| {
+            let (result, measures) = output.split_once('\n').unwrap();
+            (
+                result.parse().unwrap(),
+                serde_json::from_str(measures).unwrap(),
+            )
+        })
+    }
+
+    /// As [`Self::run_no_std_app_export`], returning along with the result
+    /// the details of the navigations that the loader measured.
+    pub fn measure_no_std_app_navigations(
+        &self,
+        export: &str,
+        n: u32,
+    ) -> Option<(u32, Vec<serde_json::Value>)> {
+        let n = n.to_string();
+        self.run_node(
+            "run.mjs",
+            &[self.dir.as_os_str(), n.as_ref(), export.as_ref()],
+            &[("NAVIGATION_MEASURES", "1")],
+        )
+        .map(|output| {
+            let (result, measures) = output.split_once('\n').unwrap();
+            (
+                result.parse().unwrap(),
+                serde_json::from_str(measures).unwrap(),
+            )
+        })
+    }
+
+    /// As [`Self::run_no_std_app_export`], returning along with the result
+    /// the name, start, end and details of the measures of the fetch,
+    /// compile and instantiation of each chunk that the loader loaded.
+    pub fn measure_no_std_app_phases(
+        &self,
+        export: &str,
+        n: u32,
+    ) -> Option<(u32, Vec<serde_json::Value>)> {
+        let n = n.to_string();
+        self.run_node(
+            "run.mjs",
+            &[self.dir.as_os_str(), n.as_ref(), export.as_ref()],
+            &[("PHASE_MEASURES", "1")],
+        )
+        .map(|output| {
+            let (result, measures) = output.split_once('\n').unwrap();
+            (
+                result.parse().unwrap(),
+                serde_json::from_str(measures).unwrap(),
+            )
+        })
+    }
+
+    /// As [`Self::run_no_std_app_export`], returning along with the result
+    /// what another copy of the loader found once the export was done; see
+    /// `SECOND_LOADER` of `run.mjs`.
+    pub fn run_no_std_app_with_second_loader(
+        &self,
+        export: &str,
+        n: u32,
+    ) -> Option<(u32, serde_json::Value)> {
+        let n = n.to_string();
+        self.run_node(
+            "run.mjs",
+            &[self.dir.as_os_str(), n.as_ref(), export.as_ref()],
+            &[("SECOND_LOADER", "1")],
+        )
+        .map(|output| {
+            let (result, second_loader) = output.split_once('\n').unwrap();
+            (
+                result.parse().unwrap(),
+                serde_json::from_str(second_loader).unwrap(),
+            )
+        })
+    }
+
+    /// As [`Self::run_no_std_app_export`], returning along with the result
+    /// what the export did on each of `pages` before, by their query string;
+    /// see `PAGES` of `run.mjs`.
+    pub fn run_no_std_app_on_pages(
+        &self,
+        export: &str,
+        n: u32,
+        pages: &[&str],
+    ) -> Option<(u32, Vec<serde_json::Value>)> {
+        let n = n.to_string();
+        self.run_node(
+            "run.mjs",
+            &[self.dir.as_os_str(), n.as_ref(), export.as_ref()],
+            &[("PAGES", &pages.join(","))],
+        )
+        .map(|output| {
+            let (result, pages) = output.split_once('\n').unwrap();
+            (
+                result.parse().unwrap(),
+                serde_json::from_str(pages).unwrap(),
+            )
+        })
+    }
+
+    /// Calls `function` of the JS entry points of split module `module`,
+    /// written with `--emit-js-entries`, with `n`, once the main module is
+    /// instantiated.
+    pub fn call_no_std_app_js_entry(&self, module: &str, function: &str, n: u32) -> Option<u32> {
+        let entry = format!("{module}.{function}");
+        self.run_no_std_app_with("run", n, &[("JS_ENTRY", &entry)])
+    }
+
+    /// As [`Self::run_no_std_app`], importing the module of
+    /// `--emit-bundler-assets` before the main module is instantiated.
+    pub fn run_no_std_app_with_bundled_assets(&self, n: u32) -> Option<u32> {
+        self.run_no_std_app_with("run", n, &[("BUNDLED_ASSETS", "1")])
+    }
+
+    /// As [`Self::run_no_std_app`], with the loader of `--target no-modules`
+    /// run as a classic script.
+    pub fn run_no_std_app_classic(&self, n: u32) -> Option<u32> {
+        self.run_no_std_app_with("run", n, &[("CLASSIC", "1")])
+    }
+
+    /// As [`Self::run_no_std_app`], with Node's own `fetch`, for the loader
+    /// of `--target node`.
+    pub fn run_no_std_app_without_fetch(&self, n: u32) -> Option<u32> {
+        self.run_no_std_app_export_without_fetch("run", n)
+    }
+
+    /// As [`Self::run_no_std_app_without_fetch`], for another export.
+    pub fn run_no_std_app_export_without_fetch(&self, export: &str, n: u32) -> Option<u32> {
+        self.run_no_std_app_with(export, n, &[("NODE_FETCH", "1")])
+    }
+
+    /// As [`Self::run_no_std_app_export_without_fetch`], returning what
+    /// Node wrote to stderr if the app failed.
+    pub fn try_run_no_std_app_export_without_fetch(
+        &self,
+        export: &str,
+        n: u32,
+    ) -> Option<Result<u32, String>> {
+        let n = n.to_string();
+        self.try_run_node(
+            "run.mjs",
+            &[self.dir.as_os_str(), n.as_ref(), export.as_ref()],
+            &[("NODE_FETCH", "1")],
+        )
+        .map(|result| result.map(|output| output.parse().unwrap()))
+    }
+
+    fn run_no_std_app_with(&self, export: &str, n: u32, env: &[(&str, &str)]) -> Option<u32> {
+        let n = n.to_string();
+        self.run_node(
+            "run.mjs",
+            &[self.dir.as_os_str(), n.as_ref(), export.as_ref()],
+            env,
+        )
+        .map(|output| output.parse().unwrap())
+    }
+
+    /// Runs wasm-bindgen on the split `closure_app`, and then the app,
+    /// returning the results of the calls to its closures, or `None` if Node
+    /// or a matching version of wasm-bindgen is not installed.
+    pub fn run_closure_app(&self) -> Option<String> {
+        let pkg_dir = self.bind_closure_app()?;
+        self.run_node("run_closure_app.mjs", &[pkg_dir.as_os_str()], &[])
+    }
+
+    /// As [`Self::run_closure_app`], calling its split function that panics
+    /// instead, with chunks served with `chunk_content_type`, and returning
+    /// what the panic hook reported.
+    pub fn run_closure_app_panic(&self, chunk_content_type: &str) -> Option<String> {
+        let pkg_dir = self.bind_closure_app()?;
+        self.run_node(
+            "run_closure_app.mjs",
+            &[pkg_dir.as_os_str(), "panic".as_ref()],
+            &[("CHUNK_CONTENT_TYPE", chunk_content_type)],
+        )
+    }
+
+    /// Runs wasm-bindgen on the split `closure_app`, returning the directory
+    /// of its output and the chunks, or `None` if a matching version of
+    /// wasm-bindgen is not installed.
+    fn bind_closure_app(&self) -> Option<PathBuf> {
+        let version = match Command::new("wasm-bindgen").arg("--version").output() {
+            Ok(output) => String::from_utf8(output.stdout).unwrap(),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(err) => panic!("Failed to run wasm-bindgen: {err}"),
+        };
+        if version.split_whitespace().nth(1) != Some(WASM_BINDGEN_VERSION) {
+            eprintln!(
+                "Skipping running the split app, as wasm-bindgen {WASM_BINDGEN_VERSION} is not \
+                 installed"
+            );
+            return None;
+        }
+        let pkg_dir = self.dir.join("pkg");
+        let status = Command::new("wasm-bindgen")
+            .arg(self.dir.join("main.wasm"))
+            .arg("--out-dir")
+            .arg(&pkg_dir)
+            .args(["--target", "web", "--no-demangle", "--keep-lld-exports"])
+            .status()
+            .unwrap();
+        assert!(status.success(), "wasm-bindgen failed");
+        for entry in std::fs::read_dir(&self.dir).unwrap() {
+            let entry = entry.unwrap();
+            if entry.file_type().unwrap().is_file() && entry.file_name() != "main.wasm" {
+                std::fs::copy(entry.path(), pkg_dir.join(entry.file_name())).unwrap();
+            }
+        }
+        Some(pkg_dir)
+    }
+
+    /// Runs the split `threads_app` on the main thread and a worker sharing
+    /// its memory, returning the results of its calls, or `None` if Node is
+    /// not installed; see `run_threads_app.mjs`.
+    pub fn run_threads_app(&self) -> Option<String> {
+        self.run_node("run_threads_app.mjs", &[self.dir.as_os_str()], &[])
+    }
+
+    /// Runs one of the scripts in `testdata` and returns its output, or
+    /// `None` if Node is not installed.
+    fn run_node(&self, script: &str, args: &[&OsStr], env: &[(&str, &str)]) -> Option<String> {
+        self.try_run_node(script, args, env).map(|result| {
+            result.unwrap_or_else(|stderr| panic!("Running the split app failed:\n{stderr}"))
+        })
+    }
+
+    /// As [`Self::run_node`], returning what the script wrote to stderr if
+    /// it failed.
+    fn try_run_node(
+        &self,
+        script: &str,
+        args: &[&OsStr],
+        env: &[(&str, &str)],
+    ) -> Option<Result<String, String>> {
+        let output = match Command::new("node")
+            .arg(fixture_path(script))
+            .args(args)
+            .envs(env.iter().copied())
+            .output()
+        {
+            Ok(output) => output,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                eprintln!("Skipping running the split app, as Node is not installed");
+                return None;
+            }
+            Err(err) => panic!("Failed to run Node: {err}"),
+        };
+        Some(if output.status.success() {
+            Ok(String::from_utf8(output.stdout).unwrap().trim().to_string())
+        } else {
+            Err(String::from_utf8_lossy(&output.stderr).into_owned())
+        })
+    }
+}
+
+/// Compares `actual` with the golden file `testdata/golden/{name}`, or
+/// writes it there when `WASM_SPLIT_BLESS` is set, as after a change to a
+/// fixture or to the splitter that changes its output on purpose.
+pub fn assert_golden(name: &str, actual: &str) {
+    let path = fixture_path("golden").join(name);
+    if std::env::var_os("WASM_SPLIT_BLESS").is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path).unwrap_or_default();
+    assert!(
+        actual == expected,
+        "{name} differs from {}; rerun with WASM_SPLIT_BLESS=1 if the change is \
+         intended\n--- expected\n{expected}--- actual\n{actual}",
+        path.display()
+    );
+}
+
+/// Result of `run(n)` in `no_std_app`.
+pub fn expected_no_std_app_result(n: u32) -> u32 {
+    let first = (0..n).map(|value| value * value).sum::<u32>() + n;
+    let second = if n.is_multiple_of(2) { 2 * n } else { 3 * n } + n * n;
+    first + second
+}
+
+/// Output of `run_closure_app.mjs`: the results of two calls of the counter
+/// closure, of the closure called while its split function runs, of the
+/// async split function and of the task that it spawned.
+pub const CLOSURE_APP_RESULTS: &str = "1008 2009 15 12 24";
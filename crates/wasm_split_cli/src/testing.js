@@ -0,0 +1,67 @@
+// Fault injection for integration tests, written with `--emit-test-utils`.
+//
+// Registers `wasm-split-fault-sw.js`, which must be allowed to control the
+// page under test. Unless the page lives in the output directory, serve the
+// service worker with a `Service-Worker-Allowed: /` header. It replaces the
+// application's own service worker, if any, for the same scope.
+//
+//   import { injectFaults } from "/pkg/wasm-split-testing.js";
+//
+//   // Fail the first load of `view_b` with HTTP 503, then succeed.
+//   await injectFaults([
+//     { chunk: "view_b", kind: "http", status: 503, times: 1 },
+//   ]);
+//
+// Each fault names a chunk from the manifest and has one of these kinds:
+//
+// - "network": the request fails as if offline.
+// - "http": the response has `status` (default 503).
+// - "delay": the response is delayed by `ms` milliseconds (default 1000).
+// - "hang": the response never arrives.
+// - "corrupt": the byte at `offset` (default 0) of the response is flipped.
+// - "content-type": the response is served with the Content-Type `type`
+//   (default "application/octet-stream"), which the loader diagnoses.
+//
+// `times` limits how many requests a fault applies to. Calling `injectFaults`
+// again replaces all faults; `injectFaults([])` removes them.
+
+const FAULT_SW_URL = new URL("./wasm-split-fault-sw.js", import.meta.url);
+// Rewritten by wasm-split if the manifest is moved with `[output]`.
+const MANIFEST_URL = new URL("./wasm-split-manifest.json", import.meta.url);
+
+async function getController(scope) {
+  await navigator.serviceWorker.register(FAULT_SW_URL, { scope });
+  const { serviceWorker } = navigator;
+  // The worker claims the page once it has activated, replacing any other
+  // worker with the same scope.
+  while (serviceWorker.controller?.scriptURL !== FAULT_SW_URL.href) {
+    await new Promise((resolve) =>
+      serviceWorker.addEventListener("controllerchange", resolve, {
+        once: true,
+      }),
+    );
+  }
+  return serviceWorker.controller;
+}
+
+export async function injectFaults(faults, { scope = "/" } = {}) {
+  const response = await fetch(MANIFEST_URL, { cache: "no-cache" });
+  const manifest = await response.json();
+  const resolved = faults.map((fault) => {
+    const chunk = manifest.chunks.find(({ name }) => name === fault.chunk);
+    if (chunk === undefined) {
+      throw new Error(`wasm-split: unknown chunk ${fault.chunk}`);
+    }
+    const { pathname } = new URL("./" + chunk.file, import.meta.url);
+    return { ...fault, path: pathname };
+  });
+  const controller = await getController(scope);
+  const channel = new MessageChannel();
+  const acknowledged = new Promise((resolve) => {
+    channel.port1.onmessage = resolve;
+  });
+  controller.postMessage({ type: "wasm-split-faults", faults: resolved }, [
+    channel.port2,
+  ]);
+  await acknowledged;
+}
@@ -0,0 +1,149 @@
+//! Wall-clock time of each phase of a split, printed with `--timings` to find
+//! out where the time of a slow build goes. Each phase is also a `phase`
+//! span, for the logs of `--verbose` and `--log-format`.
+
+use std::time::{Duration, Instant};
+
+use tracing::{field::Empty, span::EnteredSpan};
+
+pub struct Timings {
+    enabled: bool,
+    start: Instant,
+    phase_start: Instant,
+    phases: Vec<(&'static str, Duration)>,
+    /// Span of the phase in progress, whose name is only recorded once it
+    /// ends.
+    span: EnteredSpan,
+}
+
+fn phase_span() -> EnteredSpan {
+    tracing::info_span!("phase", phase = Empty, part = Empty, part_ms = Empty).entered()
+}
+
+impl Timings {
+    pub fn new(enabled: bool) -> Self {
+        let now = Instant::now();
+        Self {
+            enabled,
+            start: now,
+            phase_start: now,
+            phases: Vec::new(),
+            span: phase_span(),
+        }
+    }
+
+    /// Ends the phase that began when the previous one ended.
+    pub fn end_phase(&mut self, name: &'static str) {
+        self.end_phase_with(name, None);
+    }
+
+    /// Ends the last phase, after which no other begins.
+    pub fn end_last_phase(&mut self, name: &'static str) {
+        self.record_phase(name, None);
+        self.span = tracing::Span::none().entered();
+    }
+
+    /// Like [`Self::end_phase`], but reports `part` of the phase's time, spent
+    /// on work interleaved with the rest of it, as a phase of its own.
+    pub fn end_phase_with(&mut self, name: &'static str, part: Option<(&'static str, Duration)>) {
+        self.record_phase(name, part);
+        // Closes the span of the phase before the next one starts, so that it
+        // is not nested in it.
+        self.span = tracing::Span::none().entered();
+        self.span = phase_span();
+    }
+
+    fn record_phase(&mut self, name: &'static str, part: Option<(&'static str, Duration)>) {
+        let now = Instant::now();
+        let mut elapsed = now - self.phase_start;
+        self.phase_start = now;
+        self.span.record("phase", name);
+        if let Some((part_name, part_elapsed)) = part {
+            let part_elapsed = part_elapsed.min(elapsed);
+            elapsed -= part_elapsed;
+            self.phases.push((name, elapsed));
+            self.phases.push((part_name, part_elapsed));
+            self.span.record("part", part_name);
+            self.span
+                .record("part_ms", part_elapsed.as_secs_f64() * 1000.0);
+        } else {
+            self.phases.push((name, elapsed));
+        }
+    }
+
+    pub fn print(&self) {
+        if !self.enabled {
+            return;
+        }
+        let total = self.start.elapsed();
+        println!("Timings, on {} thread(s):", rayon::current_num_threads());
+        for (name, elapsed) in self.phases.iter() {
+            println!(
+                "  {name:<24} {:>9.1} ms {:>5.1}%",
+                elapsed.as_secs_f64() * 1000.0,
+                elapsed.as_secs_f64() / total.as_secs_f64().max(f64::EPSILON) * 100.0
+            );
+        }
+        println!("  {:<24} {:>9.1} ms", "total", total.as_secs_f64() * 1000.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_interleaved_work_as_its_own_phase() {
+        let mut timings = Timings::new(true);
+        std::thread::sleep(Duration::from_millis(5));
+        timings.end_phase("read");
+        std::thread::sleep(Duration::from_millis(10));
+        timings.end_phase_with("emit", Some(("compress", Duration::from_millis(4))));
+        let names = timings
+            .phases
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["read", "emit", "compress"]);
+        assert_eq!(timings.phases[2].1, Duration::from_millis(4));
+        assert!(timings.phases[1].1 >= Duration::from_millis(6));
+    }
+
+    #[test]
+    fn logs_each_phase_as_a_span_once_it_ends() {
+        #[derive(Clone, Default)]
+        struct Log(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+        impl std::io::Write for Log {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let log = Log::default();
+        let writer = log.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .json()
+            .with_span_events(tracing_subscriber::fmt::format::FmtSpan::CLOSE)
+            .with_writer(move || writer.clone())
+            .finish();
+        tracing::subscriber::with_default(subscriber, || {
+            let mut timings = Timings::new(false);
+            timings.end_phase("read");
+            timings.end_phase_with("emit", Some(("compress", Duration::ZERO)));
+            timings.end_last_phase("write");
+        });
+        let lines = String::from_utf8(log.0.lock().unwrap().clone()).unwrap();
+        let spans = lines
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["span"].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(spans.len(), 3, "{lines}");
+        assert_eq!(spans[0]["phase"], "read");
+        assert_eq!(spans[1]["phase"], "emit");
+        assert_eq!(spans[1]["part"], "compress");
+        assert_eq!(spans[2]["phase"], "write");
+    }
+}
@@ -0,0 +1,106 @@
+//! Checks that the input was produced by a pipeline that the splitter can
+//! work with, explaining what to change if it was not.
+//!
+//! None of this requires a nightly toolchain: the `#[wasm_split]` macro and
+//! its runtime only use stable features, the standard `wasm32-unknown-unknown`
+//! target suffices, and the only linker flag involved, `--emit-relocs`, is
+//! optional (see `table_only`).
+
+use anyhow::{bail, Result};
+
+use crate::{read::InputModule, split_point::SplitPoint};
+
+/// Import module of the JS glue before wasm-bindgen has processed the input.
+pub const WASM_BINDGEN_PLACEHOLDER_MODULE: &str = "__wbindgen_placeholder__";
+
+/// Import module that wasm-bindgen rewrites the placeholder imports to.
+const WASM_BINDGEN_OUTPUT_MODULE: &str = "wbg";
+
+/// Custom section that wasm-bindgen generates the JS glue from, present in
+/// the output of every crate that uses `#[wasm_bindgen]`.
+pub const WASM_BINDGEN_SECTION: &str = "__wasm_bindgen_unstable";
+
+/// Import module of the functions that `#[wasm_split]` and the runtime import
+/// from the loader, which the emitted main module imports from the configured
+/// loader path instead.
+pub const WASM_SPLIT_JS_MODULE: &str = "./__wasm_split.js";
+
+/// Export of the runtime with its `rust-loader` feature, whose main module
+/// loads its chunks by itself rather than through the loader script.
+pub const RUST_LOADER_EXPORT: &str = "__wasm_split_rust_loader";
+
+pub fn uses_rust_loader(module: &InputModule) -> bool {
+    module
+        .exports
+        .iter()
+        .any(|export| export.name == RUST_LOADER_EXPORT)
+}
+
+/// Names of the functions that the input imports from the loader script
+/// other than the split points, i.e. those of the runtime's API.
+pub fn loader_imports<'a>(module: &'a InputModule, split_points: &[SplitPoint]) -> Vec<&'a str> {
+    module
+        .imports
+        .iter()
+        .enumerate()
+        .filter(|(index, import)| {
+            import.module == WASM_SPLIT_JS_MODULE
+                && !split_points
+                    .iter()
+                    .any(|split_point| split_point.import == *index)
+        })
+        .map(|(_, import)| import.name)
+        .collect()
+}
+
+/// Whether the input uses wasm-bindgen at all. Those that don't, such as
+/// `no_std` apps that declare their imports and exports by hand, have no JS
+/// glue that the loader could get the main module's exports from, so the
+/// loader instantiates the main module itself; see `standalone.js`.
+pub fn uses_wasm_bindgen(module: &InputModule) -> bool {
+    module
+        .custom_sections
+        .iter()
+        .any(|section| section.name == WASM_BINDGEN_SECTION)
+        || module
+            .imports
+            .iter()
+            .any(|import| import.module == WASM_BINDGEN_PLACEHOLDER_MODULE)
+}
+
+pub fn check_input(module: &InputModule, split_points: &[SplitPoint]) -> Result<()> {
+    if module
+        .imports
+        .iter()
+        .any(|import| import.module == WASM_BINDGEN_OUTPUT_MODULE)
+        && !module
+            .imports
+            .iter()
+            .any(|import| import.module == WASM_BINDGEN_PLACEHOLDER_MODULE)
+    {
+        bail!(
+            "The input has already been processed by wasm-bindgen. Run wasm-split on the \
+             `.wasm` file produced by `cargo build`, then run wasm-bindgen on the `main.wasm` \
+             that it outputs, passing `--keep-lld-exports`."
+        );
+    }
+    if split_points.is_empty() {
+        let uses_split_js = module
+            .imports
+            .iter()
+            .any(|import| import.module == WASM_SPLIT_JS_MODULE);
+        if uses_split_js {
+            bail!(
+                "The input imports from {WASM_SPLIT_JS_MODULE:?} but has no split points. \
+                 This happens if the exports generated by `#[wasm_split]` were removed, e.g. \
+                 by wasm-opt or by building a `bin` rather than a `cdylib` crate; wasm-split \
+                 must run directly on the output of the linker."
+            );
+        }
+        println!(
+            "No #[wasm_split] functions found in the input; only the main module will be \
+             emitted. Check that the crate (or feature) that uses `#[wasm_split]` is enabled."
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,222 @@
+//! Interactive treemap of the split output, written to [`TREEMAP_FILENAME`]
+//! with `--treemap`.
+//!
+//! The report is a single HTML file, with no dependencies, that shows every
+//! chunk by file size, divided into the crates and functions it contains.
+//! Unlike the `analyze` reports, it is read from the chunks as written, so it
+//! shows the stubs, duplicated functions and any `wasm-opt` optimization of
+//! each, and sizes add up to the bytes that browsers actually download.
+//!
+//! Code is attributed to crates by the function names of each chunk's name
+//! section, as for `deny-in-main`. The rest of a chunk's file, such as data
+//! segments and custom sections, is shown as one block of its own.
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use serde::Serialize;
+use wasmparser::{Name, Payload};
+
+use crate::{deny::get_crate_name, symbols::Demangling};
+
+pub const TREEMAP_FILENAME: &str = "wasm-split-treemap.html";
+
+/// Name of the block of a chunk's bytes that are not function bodies.
+const OTHER_SECTIONS: &str = "(data and other sections)";
+
+/// Name of the group of functions that the name section does not name.
+const UNNAMED: &str = "(unnamed)";
+
+/// Name of the group of functions whose names are not Rust paths, such as
+/// the exports of split points and functions of `extern "C"`.
+const NO_CRATE: &str = "(no crate)";
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct TreemapNode {
+    pub name: String,
+    pub size: usize,
+    /// Number of functions of the same displayed name that a function node
+    /// stands for, such as the instances of a generic function with
+    /// `--demangle compact`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count: Option<usize>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<TreemapNode>,
+}
+
+impl TreemapNode {
+    fn new(name: impl Into<String>, size: usize, children: Vec<TreemapNode>) -> Self {
+        Self {
+            name: name.into(),
+            size,
+            count: None,
+            children,
+        }
+    }
+}
+
+/// Sorted by decreasing size and then by name, so that the layout is stable
+/// across builds.
+fn sorted(mut nodes: Vec<TreemapNode>) -> Vec<TreemapNode> {
+    nodes.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+    nodes
+}
+
+/// Tree of a chunk, by crate and function, largest first.
+pub fn chunk_node(name: &str, data: &[u8], demangling: Demangling) -> Result<TreemapNode> {
+    let mut imported = 0;
+    let mut bodies = Vec::new();
+    let mut names = BTreeMap::new();
+    for payload in wasmparser::Parser::new(0).parse_all(data) {
+        match payload? {
+            Payload::ImportSection(reader) => {
+                for import in reader {
+                    if let wasmparser::TypeRef::Func(_) = import?.ty {
+                        imported += 1;
+                    }
+                }
+            }
+            Payload::CodeSectionEntry(body) => bodies.push(body.range().len()),
+            Payload::CustomSection(section) if section.name() == "name" => {
+                let reader =
+                    wasmparser::NameSectionReader::new(section.data(), section.data_offset());
+                for part in reader {
+                    if let Name::Function(functions) = part? {
+                        for naming in functions {
+                            let naming = naming?;
+                            names.insert(naming.index as usize, naming.name);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Functions of each crate by displayed name, with their size and count.
+    let mut crates = BTreeMap::<String, BTreeMap<String, (usize, usize)>>::new();
+    for (defined_index, size) in bodies.iter().enumerate() {
+        let (crate_name, function) = match names.get(&(imported + defined_index)) {
+            Some(name) => {
+                let demangled = crate::symbols::demangle(name);
+                let crate_name = match get_crate_name(&demangled) {
+                    crate_name if crate_name == demangled => NO_CRATE,
+                    crate_name => crate_name,
+                };
+                (crate_name.to_string(), demangling.demangle(name))
+            }
+            None => (
+                UNNAMED.to_string(),
+                format!("func[{}]", imported + defined_index),
+            ),
+        };
+        let function = crates
+            .entry(crate_name)
+            .or_default()
+            .entry(function)
+            .or_default();
+        function.0 += size;
+        function.1 += 1;
+    }
+    let mut children = crates
+        .into_iter()
+        .map(|(crate_name, functions)| {
+            let functions = functions
+                .into_iter()
+                .map(|(name, (size, count))| TreemapNode {
+                    count: (count > 1).then_some(count),
+                    ..TreemapNode::new(name, size, Vec::new())
+                })
+                .collect::<Vec<_>>();
+            let size = functions.iter().map(|function| function.size).sum();
+            TreemapNode::new(crate_name, size, sorted(functions))
+        })
+        .collect::<Vec<_>>();
+    let code_size = bodies.iter().sum::<usize>();
+    if data.len() > code_size {
+        children.push(TreemapNode::new(
+            OTHER_SECTIONS,
+            data.len() - code_size,
+            Vec::new(),
+        ));
+    }
+    Ok(TreemapNode::new(name, data.len(), sorted(children)))
+}
+
+/// Tree of the chunks, by name and encoding, with the build as its root.
+pub fn get_treemap(modules: &[(String, &[u8])], demangling: Demangling) -> Result<TreemapNode> {
+    let chunks = modules
+        .iter()
+        .map(|(name, data)| chunk_node(name, data, demangling))
+        .collect::<Result<Vec<_>>>()?;
+    let size = chunks.iter().map(|chunk| chunk.size).sum();
+    Ok(TreemapNode::new("all chunks", size, sorted(chunks)))
+}
+
+/// The report, with the tree embedded in the page.
+pub fn render(treemap: &TreemapNode) -> Result<String> {
+    // Keeps function names such as `<T as Trait>` from closing the script.
+    let data = serde_json::to_string(treemap)?.replace("</", "<\\/");
+    Ok(include_str!("treemap.html").replacen("/*TREEMAP*/null", &data, 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_treemap, render, OTHER_SECTIONS};
+    use crate::{symbols::Demangling, test_fixtures::split};
+
+    #[test]
+    fn maps_chunks_by_crate() {
+        let output = split("no_std_app.wasm", &[]);
+        let modules = output
+            .wasm_files()
+            .into_iter()
+            .map(|file| {
+                (
+                    file.trim_end_matches(".wasm").to_string(),
+                    output.read(&file),
+                )
+            })
+            .collect::<Vec<_>>();
+        let modules = modules
+            .iter()
+            .map(|(name, data)| (name.clone(), data.as_slice()))
+            .collect::<Vec<_>>();
+        let treemap = get_treemap(&modules, Demangling::Full).unwrap();
+        assert_eq!(treemap.children.len(), modules.len());
+        // Chunks are sorted by size, so find the file of each.
+        for chunk in treemap.children.iter() {
+            let (_, data) = modules
+                .iter()
+                .find(|(name, _)| *name == chunk.name)
+                .unwrap();
+            assert_eq!(chunk.size, data.len());
+            assert_eq!(
+                chunk.children.iter().map(|node| node.size).sum::<usize>(),
+                data.len()
+            );
+            assert!(chunk
+                .children
+                .iter()
+                .any(|node| node.name == OTHER_SECTIONS));
+        }
+        let first = treemap
+            .children
+            .iter()
+            .find(|chunk| chunk.name == "first")
+            .unwrap();
+        let app = first
+            .children
+            .iter()
+            .find(|node| node.name == "no_std_app")
+            .unwrap();
+        assert!(app
+            .children
+            .iter()
+            .any(|function| function.name.starts_with("no_std_app::")));
+
+        let html = render(&treemap).unwrap();
+        assert!(html.contains("\"name\":\"all chunks\""));
+        assert!(!html.contains("/*TREEMAP*/"));
+    }
+}
@@ -0,0 +1,356 @@
+//! `wasm-split trunk`: a `post_build` hook of [Trunk](https://trunkrs.dev)
+//! that replaces the app that Trunk staged by a split one.
+//!
+//! ```toml
+//! # Trunk.toml
+//! [[hooks]]
+//! stage = "post_build"
+//! command = "wasm-split"
+//! command_arguments = ["trunk", "--"]
+//! ```
+//!
+//! Trunk has run wasm-bindgen by then, whose output has lost the relocations
+//! that splitting needs, so the hook splits the module that cargo built
+//! instead, found from the name of the staged one and `TRUNK_PROFILE` unless
+//! given with `--input`. Its main module is processed by wasm-bindgen as with
+//! `wasm-split serve`, and written to the staging directory with the other
+//! files of the split, in place of Trunk's JS glue and module. The
+//! references of the staged `index.html` to those are rewritten, along with
+//! their `integrity` attributes, and a `modulepreload` of the glue gets one of
+//! the loader next to it.
+//!
+//! Trunk's own wasm-opt step only ever applies to the module it staged, so
+//! chunks are optimized by `[wasm-opt]` of the config instead.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+#[derive(Debug, clap::Args)]
+pub struct TrunkArgs {
+    /// Trunk's staging directory. Defaults to `TRUNK_STAGING_DIR`, as set by
+    /// Trunk for its hooks.
+    #[arg(long, value_name = "DIR")]
+    staging_dir: Option<PathBuf>,
+
+    /// Module built by cargo, before wasm-bindgen, to split. Defaults to the
+    /// one of the staged module in the target directory, for the profile of
+    /// `TRUNK_PROFILE`.
+    #[arg(long, value_name = "PATH")]
+    input: Option<PathBuf>,
+
+    /// Directory within the staging directory to write the split app to,
+    /// rather than the staging directory itself.
+    #[arg(long, value_name = "DIR")]
+    out_dir: Option<PathBuf>,
+
+    /// wasm-bindgen binary to run, which must be of the version that the app
+    /// depends on, as Trunk's is.
+    #[arg(long, value_name = "PATH", default_value = "wasm-bindgen")]
+    wasm_bindgen: PathBuf,
+
+    /// Options of the split, after `--`, such as `-- --fold-threshold 0`.
+    #[arg(last = true, value_name = "SPLIT_OPTIONS")]
+    split_options: Vec<String>,
+}
+
+pub fn run(args: &TrunkArgs) -> Result<()> {
+    let staging_dir = match &args.staging_dir {
+        Some(dir) => dir.clone(),
+        None => std::env::var_os("TRUNK_STAGING_DIR")
+            .map(PathBuf::from)
+            .context("TRUNK_STAGING_DIR is not set; run as a Trunk hook or pass --staging-dir")?,
+    };
+    let staged_stem = find_staged_module(&staging_dir)?;
+    let input = match &args.input {
+        Some(input) => input.clone(),
+        None => find_cargo_module(&staged_stem)?,
+    };
+    if !crate::serve::uses_wasm_bindgen(&input)? {
+        bail!(
+            "{} does not use wasm-bindgen, unlike apps built by Trunk",
+            input.display()
+        );
+    }
+    let relative_out_dir = args.out_dir.clone().unwrap_or_default();
+    let output = crate::serve::split_and_bind(
+        &args.split_options,
+        &input,
+        &staging_dir.join(&relative_out_dir),
+        &args.wasm_bindgen,
+    )?;
+
+    let build_dir = match split_asset_version(&args.split_options) {
+        Some(version) => relative_out_dir.join(version),
+        None => relative_out_dir,
+    };
+    let main_stem = output
+        .main
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy();
+    let glue = build_dir.join(output.main.with_extension("js"));
+    let main_bg = build_dir.join(output.main.with_file_name(format!("{main_stem}_bg.wasm")));
+    let loader = build_dir.join(&output.loader);
+    let replacements = [
+        (format!("{staged_stem}.js"), glue),
+        (format!("{staged_stem}_bg.wasm"), main_bg),
+    ];
+    for (staged, _) in replacements.iter() {
+        let path = staging_dir.join(staged);
+        if !replacements
+            .iter()
+            .any(|(_, new)| staging_dir.join(new) == path)
+        {
+            std::fs::remove_file(&path)
+                .with_context(|| format!("Failed to remove {}", path.display()))?;
+        }
+    }
+
+    let html_path = staging_dir.join("index.html");
+    let html = std::fs::read_to_string(&html_path)
+        .with_context(|| format!("Failed to read {}", html_path.display()))?;
+    let html = rewrite_html(&html, &staging_dir, &replacements, &loader)?;
+    std::fs::write(&html_path, html)
+        .with_context(|| format!("Failed to write {}", html_path.display()))?;
+    println!(
+        "Split {} into {}",
+        input.display(),
+        staging_dir.join(&build_dir).display()
+    );
+    Ok(())
+}
+
+/// Stem of the module that Trunk staged, such as `app-1f2e3d4c5b6a7988`, of
+/// `app-1f2e3d4c5b6a7988_bg.wasm`.
+fn find_staged_module(staging_dir: &Path) -> Result<String> {
+    let mut stems = std::fs::read_dir(staging_dir)
+        .with_context(|| format!("Failed to read {}", staging_dir.display()))?
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name().into_string().ok()?;
+            Some(name.strip_suffix("_bg.wasm")?.to_string())
+        })
+        .collect::<Vec<_>>();
+    match stems.len() {
+        1 => Ok(stems.remove(0)),
+        0 => bail!(
+            "No module built by wasm-bindgen in {}; is the hook's stage post_build?",
+            staging_dir.display()
+        ),
+        _ => bail!(
+            "Several modules built by wasm-bindgen in {}, so pass the one to split with --input",
+            staging_dir.display()
+        ),
+    }
+}
+
+/// Name of the cargo artifact of a module that Trunk staged as
+/// `staged_stem`, which Trunk suffixes with a hash unless given
+/// `--filehash false`.
+fn artifact_name(staged_stem: &str) -> &str {
+    match staged_stem.rsplit_once('-') {
+        Some((name, hash))
+            if hash.len() == 16 && hash.bytes().all(|byte| byte.is_ascii_hexdigit()) =>
+        {
+            name
+        }
+        _ => staged_stem,
+    }
+}
+
+/// The module that cargo built for Trunk, in the target directory of the
+/// package of `TRUNK_SOURCE_DIR`.
+fn find_cargo_module(staged_stem: &str) -> Result<PathBuf> {
+    let target_dir = match std::env::var_os("CARGO_TARGET_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            let cargo = std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+            let mut command = std::process::Command::new(cargo);
+            command.args(["metadata", "--format-version", "1", "--no-deps"]);
+            if let Some(dir) = std::env::var_os("TRUNK_SOURCE_DIR") {
+                command.current_dir(dir);
+            }
+            let output = command.output().context("Failed to run cargo metadata")?;
+            if !output.status.success() {
+                bail!(
+                    "cargo metadata failed with {}: {}",
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr)
+                );
+            }
+            let metadata: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+            metadata["target_directory"]
+                .as_str()
+                .map(PathBuf::from)
+                .context("cargo metadata did not report a target directory")?
+        }
+    };
+    let profile = std::env::var("TRUNK_PROFILE").unwrap_or_else(|_| "debug".to_string());
+    let path = target_dir
+        .join("wasm32-unknown-unknown")
+        .join(profile)
+        .join(format!("{}.wasm", artifact_name(staged_stem)));
+    if !path.exists() {
+        bail!(
+            "Found no module built by cargo at {}; pass the one to split with --input",
+            path.display()
+        );
+    }
+    Ok(path)
+}
+
+/// `--asset-version` among `split_options`, which moves the split's files
+/// into a subdirectory.
+fn split_asset_version(split_options: &[String]) -> Option<&str> {
+    split_options
+        .iter()
+        .enumerate()
+        .find_map(
+            |(index, option)| match option.strip_prefix("--asset-version") {
+                Some("") => split_options.get(index + 1).map(String::as_str),
+                Some(rest) => rest.strip_prefix('='),
+                None => None,
+            },
+        )
+}
+
+/// `html` with its references to the staged files of `replacements` replaced
+/// by the split's, as paths relative to `staging_dir`, and the `integrity`
+/// attributes of the tags referencing them updated.
+fn rewrite_html(
+    html: &str,
+    staging_dir: &Path,
+    replacements: &[(String, PathBuf)],
+    loader: &Path,
+) -> Result<String> {
+    let url = |path: &Path| path.to_string_lossy().replace('\\', "/");
+    let mut html = html.to_string();
+    for (staged, new) in replacements {
+        // Only whole file names of URLs, wherever they are, such as in the
+        // inline script that starts the app.
+        let reference =
+            Regex::new(&format!(r#"([\"'/]){}([\"'?#])"#, regex::escape(staged))).unwrap();
+        html = reference
+            .replace_all(&html, format!("${{1}}{}${{2}}", url(new)))
+            .into_owned();
+    }
+
+    let tag = Regex::new(r"<(?:link|script)\b[^>]*>").unwrap();
+    let href = Regex::new(r#"(?:href|src)="([^"]*)""#).unwrap();
+    let integrity = Regex::new(r#"integrity="(sha256|sha384|sha512)-[^"]*""#).unwrap();
+    let mut error = None;
+    let html = tag.replace_all(&html, |captures: &regex::Captures| {
+        let mut tag = captures[0].to_string();
+        let Some(target) = href.captures(&tag).map(|captures| captures[1].to_string()) else {
+            return tag;
+        };
+        let Some((prefix, new)) = replacements
+            .iter()
+            .find_map(|(_, new)| Some((target.strip_suffix(url(new).as_str())?.to_string(), new)))
+        else {
+            return tag;
+        };
+        if let Some(algorithm) = integrity
+            .captures(&tag)
+            .map(|captures| captures[1].to_string())
+        {
+            match std::fs::read(staging_dir.join(new)) {
+                Ok(data) => {
+                    let hash = match algorithm.as_str() {
+                        "sha256" => base64_encode(&Sha256::digest(&data)),
+                        "sha384" => base64_encode(&Sha384::digest(&data)),
+                        _ => base64_encode(&Sha512::digest(&data)),
+                    };
+                    tag = integrity
+                        .replace(&tag, format!(r#"integrity="{algorithm}-{hash}""#))
+                        .into_owned();
+                }
+                Err(read_error) => error = Some((new.clone(), read_error)),
+            }
+        }
+        if tag.contains("modulepreload")
+            && new.extension().is_some_and(|extension| extension == "js")
+        {
+            // The glue imports the loader, which would otherwise only be
+            // requested once the glue has arrived.
+            tag.push_str(&format!(
+                r#"<link rel="modulepreload" href="{prefix}{}" crossorigin="anonymous">"#,
+                url(loader)
+            ));
+        }
+        tag
+    });
+    if let Some((path, error)) = error {
+        return Err(error).with_context(|| format!("Failed to read {}", path.display()));
+    }
+    Ok(html.into_owned())
+}
+
+fn base64_encode(data: &[u8]) -> String {
+    use base64::Engine;
+    base64::engine::general_purpose::STANDARD.encode(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_cargo_artifact_of_staged_module() {
+        assert_eq!(artifact_name("app-1f2e3d4c5b6a7988"), "app");
+        assert_eq!(artifact_name("my-app-1f2e3d4c5b6a7988"), "my-app");
+        assert_eq!(artifact_name("my-app"), "my-app");
+        assert_eq!(
+            split_asset_version(&["--asset-version".into(), "v1".into()]),
+            Some("v1")
+        );
+        assert_eq!(
+            split_asset_version(&["--asset-version=v2".into()]),
+            Some("v2")
+        );
+        assert_eq!(split_asset_version(&["--verbose".into()]), None);
+    }
+
+    #[test]
+    fn rewrites_references_of_staged_html() {
+        let dir = std::env::temp_dir().join(format!("wasm-split-trunk-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("pkg")).unwrap();
+        std::fs::write(dir.join("pkg/main.js"), "glue").unwrap();
+        std::fs::write(dir.join("pkg/main_bg.wasm"), "module").unwrap();
+        let html = r#"<head>
+<link rel="modulepreload" href="/app/app-1f2e3d4c5b6a7988.js" crossorigin="anonymous" integrity="sha384-old">
+<link rel="preload" href="/app/app-1f2e3d4c5b6a7988_bg.wasm" crossorigin="anonymous" integrity="sha256-old" as="fetch" type="application/wasm">
+<script type="module">
+import init, * as bindings from '/app/app-1f2e3d4c5b6a7988.js';
+const wasm = await init({ module_or_path: '/app/app-1f2e3d4c5b6a7988_bg.wasm' });
+</script>
+</head>"#;
+        let replacements = [
+            (
+                "app-1f2e3d4c5b6a7988.js".to_string(),
+                PathBuf::from("pkg/main.js"),
+            ),
+            (
+                "app-1f2e3d4c5b6a7988_bg.wasm".to_string(),
+                PathBuf::from("pkg/main_bg.wasm"),
+            ),
+        ];
+        let html =
+            rewrite_html(html, &dir, &replacements, Path::new("pkg/__wasm_split.js")).unwrap();
+        assert!(!html.contains("1f2e3d4c5b6a7988"), "{html}");
+        assert!(html.contains(&format!(
+            r#"<link rel="modulepreload" href="/app/pkg/main.js" crossorigin="anonymous" integrity="{}"><link rel="modulepreload" href="/app/pkg/__wasm_split.js" crossorigin="anonymous">"#,
+            crate::manifest::sri_hash(b"glue")
+        )));
+        assert!(html.contains(&format!(
+            r#"href="/app/pkg/main_bg.wasm" crossorigin="anonymous" integrity="sha256-{}""#,
+            base64_encode(&Sha256::digest(b"module"))
+        )));
+        // The inline script that starts the app loads the split module too.
+        assert!(html.contains("from '/app/pkg/main.js'"));
+        assert!(html.contains("module_or_path: '/app/pkg/main_bg.wasm'"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,81 @@
+//! TypeScript declarations written next to the loader and the manifest, for
+//! JS apps that embed the split output: those of the loader's API, and a
+//! module of the types of the manifest with the chunk names and routes of
+//! the build, which the loader's declarations use, so that e.g.
+//! `preload("setings")` fails to type-check.
+
+use std::collections::BTreeSet;
+
+use crate::manifest::Manifest;
+
+/// Declarations of the loader, importing the manifest types with
+/// `manifest_types_url`.
+pub fn loader_types(manifest_types_url: &str, uses_wasm_bindgen: bool) -> String {
+    let mut types = include_str!("loader.d.ts").replace(
+        "\"./wasm-split-manifest.js\"",
+        &serde_json::to_string(manifest_types_url).unwrap(),
+    );
+    if !uses_wasm_bindgen {
+        types.push_str(
+            "\n/** Instantiates the main module with `imports`, to which those of the loader are added. */\n\
+             export function instantiateMain(imports?: WebAssembly.Imports): Promise<WebAssembly.Instance>;\n",
+        );
+    }
+    types
+}
+
+/// Types of the manifest, with the names of `manifest`.
+pub fn manifest_types(manifest: &Manifest) -> String {
+    let names = manifest
+        .chunks
+        .iter()
+        .map(|chunk| &chunk.name)
+        .chain(manifest.folded.iter().map(|folded| &folded.name))
+        .chain(manifest.aliases.keys())
+        .collect::<BTreeSet<_>>();
+    let types = include_str!("manifest.d.ts");
+    let types = replace_never(types, "export type ChunkName = ", names);
+    replace_never(&types, "export type RoutePath = ", manifest.routes.keys())
+}
+
+/// Replaces the `never` of the type alias declared by `context` with the
+/// union of the string literal types of `values`, if any.
+fn replace_never<'a>(
+    types: &str,
+    context: &str,
+    values: impl IntoIterator<Item = &'a String>,
+) -> String {
+    let union = values
+        .into_iter()
+        .map(|value| serde_json::to_string(value).unwrap())
+        .collect::<Vec<_>>();
+    let pattern = format!("{context}never;");
+    assert!(types.contains(&pattern), "Types are missing {pattern}");
+    if union.is_empty() {
+        return types.to_string();
+    }
+    types.replacen(&pattern, &format!("{context}{};", union.join(" | ")), 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_fixtures::try_split;
+
+    #[test]
+    fn declares_loader_and_manifest_types() {
+        let (output, result) = try_split(
+            "no_std_app.wasm",
+            "[routes]\n\"/both\" = [\"first\", \"second\"]\n",
+            &[],
+        );
+        result.unwrap();
+        let loader = String::from_utf8(output.read("__wasm_split.d.ts")).unwrap();
+        assert!(loader.contains("from \"./wasm-split-manifest.js\";"));
+        assert!(loader.contains("export function preload(name: ChunkName): Promise<void>;"));
+        assert!(loader.contains("export function instantiateMain("));
+        let manifest = String::from_utf8(output.read("wasm-split-manifest.d.ts")).unwrap();
+        assert!(manifest.contains("export type ChunkName = \"bonus\" | "));
+        assert!(manifest.contains("\"second\""));
+        assert!(manifest.contains("export type RoutePath = \"/both\";"));
+    }
+}
@@ -0,0 +1,349 @@
+//! Validation of the written modules, which would otherwise only fail in the
+//! browser, with a `CompileError` or a `LinkError` naming neither the chunk
+//! nor the cause.
+//!
+//! Every module is validated on its own, and the imports of every chunk are
+//! checked against what the loader instantiates it with: the exports of the
+//! main module, under [`SPLIT_MODULE`], and the main module's memory.
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, Result};
+use wasmparser::{
+    types::{EntityType, Types},
+    ExternalKind, FuncValidatorAllocations, Operator, Payload, TypeRef, ValidPayload, Validator,
+    WasmFeatures,
+};
+
+use crate::limits::accepts;
+
+/// Import module of the items that chunks import from the main module.
+const SPLIT_MODULE: &str = "__wasm_split";
+
+struct ValidModule<'a> {
+    types: Types,
+    imports: Vec<wasmparser::Import<'a>>,
+    exports: Vec<wasmparser::Export<'a>>,
+}
+
+/// Whether `body` uses the `try` blocks of the legacy exception handling
+/// proposal, which LLVM emits for `panic = "unwind"` and wasmparser does not
+/// validate.
+fn uses_legacy_exceptions(body: &wasmparser::FunctionBody) -> Result<bool> {
+    for operator in body.get_operators_reader()? {
+        if matches!(
+            operator?,
+            Operator::Try { .. } | Operator::Catch { .. } | Operator::Delegate { .. }
+        ) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn validate(data: &[u8]) -> Result<ValidModule<'_>> {
+    let mut validator = Validator::new_with_features(WasmFeatures::all());
+    let mut allocations = FuncValidatorAllocations::default();
+    let mut imports = Vec::new();
+    let mut exports = Vec::new();
+    for payload in wasmparser::Parser::new(0).parse_all(data) {
+        let payload = payload?;
+        match &payload {
+            Payload::ImportSection(reader) => {
+                imports = reader.clone().into_iter().collect::<Result<_, _>>()?;
+            }
+            Payload::ExportSection(reader) => {
+                exports = reader.clone().into_iter().collect::<Result<_, _>>()?;
+            }
+            _ => {}
+        }
+        match validator.payload(&payload)? {
+            ValidPayload::Func(func, body) => {
+                // The rest of the module is still validated.
+                if uses_legacy_exceptions(&body)? {
+                    continue;
+                }
+                let mut func = func.into_validator(std::mem::take(&mut allocations));
+                func.validate(&body)?;
+                allocations = func.into_allocations();
+            }
+            ValidPayload::End(types) => {
+                return Ok(ValidModule {
+                    types,
+                    imports,
+                    exports,
+                })
+            }
+            _ => {}
+        }
+    }
+    bail!("The module ends early")
+}
+
+fn describe(kind: ExternalKind) -> &'static str {
+    match kind {
+        ExternalKind::Func => "function",
+        ExternalKind::Table => "table",
+        ExternalKind::Memory => "memory",
+        ExternalKind::Global => "global",
+        ExternalKind::Tag => "tag",
+    }
+}
+
+fn import_kind(ty: &TypeRef) -> ExternalKind {
+    match ty {
+        TypeRef::Func(_) => ExternalKind::Func,
+        TypeRef::Table(_) => ExternalKind::Table,
+        TypeRef::Memory(_) => ExternalKind::Memory,
+        TypeRef::Global(_) => ExternalKind::Global,
+        TypeRef::Tag(_) => ExternalKind::Tag,
+    }
+}
+
+/// Type of an item, as in the text format.
+fn describe_type(types: &Types, ty: &EntityType) -> String {
+    let limits = |initial: u64, maximum: Option<u64>| match maximum {
+        Some(maximum) => format!("{initial} {maximum}"),
+        None => initial.to_string(),
+    };
+    match ty {
+        EntityType::Func(id) | EntityType::Tag(id) => {
+            let ty = types[*id].unwrap_func();
+            let list = |types: &[wasmparser::ValType]| {
+                types.iter().map(|ty| format!(" {ty}")).collect::<String>()
+            };
+            format!(
+                "(param{}) (result{})",
+                list(ty.params()),
+                list(ty.results())
+            )
+        }
+        EntityType::Global(ty) if ty.mutable => format!("(mut {})", ty.content_type),
+        EntityType::Global(ty) => ty.content_type.to_string(),
+        EntityType::Table(ty) => format!(
+            "{} {}",
+            limits(ty.initial.into(), ty.maximum.map(u64::from)),
+            ty.element_type
+        ),
+        EntityType::Memory(ty) => format!(
+            "{}{}{}",
+            if ty.memory64 { "i64 " } else { "" },
+            limits(ty.initial, ty.maximum),
+            if ty.shared { " shared" } else { "" }
+        ),
+    }
+}
+
+/// Whether an item of type `export` of the main module satisfies an import
+/// of type `import` of a chunk.
+fn matches(chunk: &Types, import: &EntityType, main: &Types, export: &EntityType) -> bool {
+    match (import, export) {
+        (EntityType::Func(import), EntityType::Func(export))
+        | (EntityType::Tag(import), EntityType::Tag(export)) => {
+            chunk[*import].unwrap_func() == main[*export].unwrap_func()
+        }
+        (EntityType::Global(import), EntityType::Global(export)) => import == export,
+        (EntityType::Table(import), EntityType::Table(export)) => {
+            import.element_type == export.element_type
+                && accepts(
+                    (import.initial.into(), import.maximum.map(u64::from)),
+                    (export.initial.into(), export.maximum.map(u64::from)),
+                )
+        }
+        (EntityType::Memory(import), EntityType::Memory(export)) => {
+            import.shared == export.shared
+                && import.memory64 == export.memory64
+                && accepts(
+                    (import.initial, import.maximum),
+                    (export.initial, export.maximum),
+                )
+        }
+        _ => false,
+    }
+}
+
+/// Validates the modules, by name and encoding, and checks the imports of
+/// all but the main one against the main one's exports.
+pub fn check(modules: &[(String, &[u8])]) -> Result<()> {
+    let mut valid = HashMap::new();
+    for (name, data) in modules {
+        let module = validate(data).map_err(|err| {
+            anyhow!(
+                "Module {name:?} is invalid, so browsers would fail to compile it: {err:#}. \
+                 Unless the input is invalid as well, this is a bug in wasm-split."
+            )
+        })?;
+        valid.insert(name.as_str(), module);
+    }
+    let Some(main) = valid.get("main") else {
+        return Ok(());
+    };
+    let main_exports = main
+        .exports
+        .iter()
+        .map(|export| (export.name, export))
+        .collect::<HashMap<_, _>>();
+    for (name, _) in modules {
+        if name == "main" {
+            continue;
+        }
+        let chunk = &valid[name.as_str()];
+        for import in chunk.imports.iter() {
+            let kind = import_kind(&import.ty);
+            if import.module != SPLIT_MODULE {
+                // The loader still passes the memory of the main module as
+                // `env.memory`, as chunks imported it before.
+                if (import.module, import.name, kind) == ("env", "memory", ExternalKind::Memory) {
+                    continue;
+                }
+                bail!(
+                    "Chunk {name:?} imports {} {}.{}, which the loader does not provide, so it \
+                     would fail to load; chunks may only import from {SPLIT_MODULE:?}",
+                    describe(kind),
+                    import.module,
+                    import.name
+                );
+            }
+            let Some(export) = main_exports.get(import.name) else {
+                bail!(
+                    "Chunk {name:?} imports {} {:?}, which the main module does not export, so \
+                     it would fail to load",
+                    describe(kind),
+                    import.name
+                );
+            };
+            let import_type = chunk.types.entity_type_from_import(import);
+            let export_type = match export.kind {
+                // wasmparser looks up the types of exported tags among those
+                // of functions.
+                ExternalKind::Tag => Some(EntityType::Tag(main.types.tag_at(export.index))),
+                _ => main.types.entity_type_from_export(export),
+            };
+            let (Some(import_type), Some(export_type)) = (import_type, export_type) else {
+                bail!(
+                    "Failed to resolve the type of {} {:?}",
+                    describe(kind),
+                    import.name
+                );
+            };
+            if !matches(&chunk.types, &import_type, &main.types, &export_type) {
+                bail!(
+                    "Chunk {name:?} imports {} {:?} of type {}, but the main module exports {} \
+                     of type {} under that name, so it would fail to load",
+                    describe(kind),
+                    import.name,
+                    describe_type(&chunk.types, &import_type),
+                    describe(export.kind),
+                    describe_type(&main.types, &export_type),
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use wasm_encoder::{
+        ConstExpr, ExportKind, ExportSection, GlobalSection, GlobalType, ImportSection, Module,
+        ValType,
+    };
+
+    use super::check;
+    use crate::test_fixtures::split;
+
+    fn main_exporting(ty: ValType) -> Vec<u8> {
+        let mut main = Module::new();
+        let mut globals = GlobalSection::new();
+        globals.global(
+            GlobalType {
+                val_type: ty,
+                mutable: false,
+                shared: false,
+            },
+            &ConstExpr::i32_const(0),
+        );
+        main.section(&globals);
+        let mut exports = ExportSection::new();
+        exports.export("answer", ExportKind::Global, 0);
+        main.section(&exports);
+        main.finish()
+    }
+
+    fn chunk_importing(module: &str, name: &str) -> Vec<u8> {
+        let mut chunk = Module::new();
+        let mut imports = ImportSection::new();
+        imports.import(
+            module,
+            name,
+            GlobalType {
+                val_type: ValType::I32,
+                mutable: false,
+                shared: false,
+            },
+        );
+        chunk.section(&imports);
+        chunk.finish()
+    }
+
+    #[test]
+    fn checks_the_imports_of_chunks() {
+        let main = main_exporting(ValType::I32);
+        let check_chunk = |chunk: &[u8]| check(&[("main".into(), &main), ("a".into(), chunk)]);
+        check_chunk(&chunk_importing("__wasm_split", "answer")).unwrap();
+        let err = check_chunk(&chunk_importing("__wasm_split", "question")).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("imports global \"question\", which the main module does not export"),
+            "{err}"
+        );
+        let err = check_chunk(&chunk_importing("env", "answer")).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("imports global env.answer, which the loader does not provide"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn rejects_imports_of_another_type() {
+        // An `i32.const` initializer of an `i64` global, which is also
+        // invalid, so only the chunk's import is checked in the second case.
+        let invalid_main = main_exporting(ValType::I64);
+        let chunk = chunk_importing("__wasm_split", "answer");
+        let err = check(&[("main".into(), &invalid_main), ("a".into(), &chunk)]).unwrap_err();
+        assert!(
+            err.to_string().contains("Module \"main\" is invalid"),
+            "{err}"
+        );
+        let mut main = Module::new();
+        let mut globals = GlobalSection::new();
+        globals.global(
+            GlobalType {
+                val_type: ValType::I64,
+                mutable: false,
+                shared: false,
+            },
+            &ConstExpr::i64_const(0),
+        );
+        main.section(&globals);
+        let mut exports = ExportSection::new();
+        exports.export("answer", ExportKind::Global, 0);
+        main.section(&exports);
+        let main = main.finish();
+        let err = check(&[("main".into(), &main), ("a".into(), &chunk)]).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains(
+            "imports global \"answer\" of type i32, but the main module exports global of type i64"
+        ),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn accepts_split_output() {
+        // `split` runs the check with every split.
+        split("closure_app.wasm", &[]);
+    }
+}
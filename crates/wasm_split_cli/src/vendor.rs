@@ -0,0 +1,184 @@
+//! Report of the calls from the main module into the vendor chunks of
+//! `[chunks]`, whose crates leave the main module even where its startup
+//! code reaches them. Each such call goes through the table, to a slot that
+//! only the chunk fills in: a call before the loader preloaded the chunk
+//! traps, or with `--lazy-indirect-calls` blocks on loading it. The report
+//! lists them, those at startup first, so that users can judge whether the
+//! smaller main module is worth it.
+
+use std::{cmp::Reverse, collections::HashSet};
+
+use crate::{
+    deny::format_node,
+    dep_graph::{DepGraph, DepNode},
+    read::{InputFuncId, InputModule},
+    split_point::{find_reachable_deps, SplitModuleIdentifier, SplitProgramInfo},
+    symbols::Demangling,
+};
+
+/// Calls listed for each chunk unless `--verbose` is given.
+const SUMMARY_CALLS: usize = 10;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct VendorCall {
+    pub chunk: String,
+    /// Whether the start function or a startup export reaches the caller.
+    pub at_startup: bool,
+    pub caller: String,
+    pub callee: String,
+}
+
+/// Calls from the code of the main module into the vendor chunks of
+/// `program_info`, by chunk and then those at startup first, given the
+/// exports that the app calls at startup.
+pub fn get_vendor_calls(
+    module: &InputModule,
+    dep_graph: &DepGraph,
+    program_info: &SplitProgramInfo,
+    startup_exports: &[String],
+    demangling: Demangling,
+) -> Vec<VendorCall> {
+    if program_info.vendor_modules.is_empty() {
+        return Vec::new();
+    }
+    let Some(&main) = program_info
+        .output_module_identifiers
+        .get(&SplitModuleIdentifier::Main)
+    else {
+        return Vec::new();
+    };
+    let mut roots = module
+        .exports
+        .iter()
+        .filter(|export| {
+            export.kind == wasmparser::ExternalKind::Func
+                && startup_exports.iter().any(|name| name == export.name)
+        })
+        .map(|export| DepNode::Function(export.index as InputFuncId))
+        .collect::<HashSet<_>>();
+    roots.extend(module.start.map(DepNode::Function));
+    let startup = find_reachable_deps(dep_graph, &roots, &HashSet::new()).reachable;
+
+    let mut calls = Vec::new();
+    for chunk in program_info.vendor_modules.iter() {
+        let Some(&index) = program_info
+            .output_module_identifiers
+            .get(&SplitModuleIdentifier::Split(chunk.clone()))
+        else {
+            continue;
+        };
+        for (caller, callees) in dep_graph.iter() {
+            if !matches!(caller, DepNode::Function(_))
+                || program_info.symbol_output_module.get(caller) != Some(&main)
+            {
+                continue;
+            }
+            for callee in callees.iter().filter(|callee| {
+                matches!(callee, DepNode::Function(_))
+                    && program_info.symbol_output_module.get(callee) == Some(&index)
+            }) {
+                calls.push(VendorCall {
+                    chunk: chunk.clone(),
+                    at_startup: startup.contains(caller),
+                    caller: format_node(module, caller, demangling),
+                    callee: format_node(module, callee, demangling),
+                });
+            }
+        }
+    }
+    calls.sort_by(|a, b| {
+        (&a.chunk, Reverse(a.at_startup), &a.caller, &a.callee).cmp(&(
+            &b.chunk,
+            Reverse(b.at_startup),
+            &b.caller,
+            &b.callee,
+        ))
+    });
+    calls.dedup();
+    calls
+}
+
+/// Prints `calls` by chunk, the first few of each unless `verbose`.
+pub fn print_vendor_calls(calls: &[VendorCall], verbose: bool) {
+    let mut chunks = calls.iter().map(|call| &call.chunk).collect::<Vec<_>>();
+    chunks.dedup();
+    for chunk in chunks {
+        let chunk_calls = calls
+            .iter()
+            .filter(|call| call.chunk == *chunk)
+            .collect::<Vec<_>>();
+        let at_startup = chunk_calls.iter().filter(|call| call.at_startup).count();
+        println!(
+            "The main module calls into vendor chunk {chunk} through the table at {} call \
+             sites, {at_startup} of them at startup:",
+            chunk_calls.len()
+        );
+        let shown = if verbose {
+            chunk_calls.len()
+        } else {
+            SUMMARY_CALLS
+        };
+        for call in chunk_calls.iter().take(shown) {
+            println!(
+                "  {} -> {}{}",
+                call.caller,
+                call.callee,
+                if call.at_startup { " (at startup)" } else { "" }
+            );
+        }
+        if chunk_calls.len() > shown {
+            println!(
+                "  and {} more; pass --verbose to list all",
+                chunk_calls.len() - shown
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::get_vendor_calls;
+    use crate::{
+        dep_graph::get_dependencies,
+        read::InputModule,
+        split_point::{compute_split_modules, get_split_points, ChunkingOptions},
+        symbols::Demangling,
+        test_fixtures::fixture_path,
+    };
+
+    #[test]
+    fn reports_calls_into_vendor_chunks_at_startup_first() {
+        let wasm = std::fs::read(fixture_path("no_std_app.wasm")).unwrap();
+        let module = InputModule::parse(&wasm).unwrap();
+        let split_points = get_split_points(&module).unwrap();
+        let dep_graph = get_dependencies(&module).unwrap();
+        let program_info = compute_split_modules(
+            &module,
+            &dep_graph,
+            &split_points,
+            &[],
+            &ChunkingOptions {
+                vendor_chunks: vec![("vendor".to_string(), vec!["alloc".to_string()])],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(program_info.vendor_modules, ["vendor"]);
+        let calls = get_vendor_calls(
+            &module,
+            &dep_graph,
+            &program_info,
+            &["run".to_string()],
+            Demangling::Full,
+        );
+        assert!(calls[0].at_startup);
+        assert!(calls[0].caller.starts_with("no_std_app::run"));
+        assert!(calls.iter().any(|call| !call.at_startup));
+        assert!(calls
+            .iter()
+            .all(|call| call.chunk == "vendor" && call.callee.contains("alloc::")));
+        assert!(calls
+            .iter()
+            .all(|call| !call.caller.starts_with("wasm_split::")));
+    }
+}
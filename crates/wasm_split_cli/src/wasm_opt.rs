@@ -0,0 +1,226 @@
+//! Optimization of the split chunks with binaryen's `wasm-opt`, with the
+//! arguments of the `[wasm-opt]` table of the config.
+//!
+//! Optimizing the input before splitting is not the same: the splitter can
+//! only move whole functions, and `wasm-opt` then cannot see which of them a
+//! chunk ends up with, e.g. to inline a function that has only one caller
+//! left in its chunk. The main module is left alone, as wasm-bindgen has yet
+//! to process it; run `wasm-opt` on its output as usual.
+//!
+//! `wasm-opt` renumbers, merges and removes functions, so the manifest and
+//! symbol map describe the optimized chunks as read back from their files,
+//! by the function names that `--debuginfo` keeps. Provenance reports still
+//! count the code of each crate before optimization.
+
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    process::Command,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use anyhow::{bail, Context, Result};
+use wasmparser::{Name, Payload, TypeRef};
+
+use crate::{
+    cache::Cache,
+    config::WasmOptOptions,
+    emit::{EmittedModule, ModuleDigest},
+    features::{self, Feature},
+    manifest::content_hash,
+    split_point::{SplitModuleIdentifier, SplitPoint},
+};
+
+impl WasmOptOptions {
+    /// Arguments to optimize the chunk of `identifier` with, if any.
+    pub fn args_for(&self, identifier: &SplitModuleIdentifier) -> Option<&[String]> {
+        let args = match identifier {
+            SplitModuleIdentifier::Main => return None,
+            SplitModuleIdentifier::Split(name) => self.modules.get(name).unwrap_or(&self.args),
+            SplitModuleIdentifier::Chunk(_) | SplitModuleIdentifier::Named(..) => &self.args,
+        };
+        (!args.is_empty()).then_some(args.as_slice())
+    }
+
+    /// Fails for modules of `[wasm-opt.modules]` that no split point names.
+    pub fn check_modules(&self, split_points: &[SplitPoint]) -> Result<()> {
+        for name in self.modules.keys() {
+            if !split_points.iter().any(|point| point.module_name == *name) {
+                bail!("[wasm-opt.modules] refers to unknown split module {name:?}");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Runs `wasm-opt` with `args` on a chunk. Features that the chunk uses are
+/// enabled on top of those of its `target_features` section, which
+/// `wasm-opt` reads itself, so that it neither rejects the chunk nor uses
+/// features that browsers of the app might lack.
+pub fn optimize(data: &[u8], args: &[String]) -> Result<Vec<u8>> {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let path = std::env::temp_dir().join(format!(
+        "wasm-split-opt-{}-{}",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ));
+    let input = path.with_extension("wasm");
+    let output = path.with_extension("opt.wasm");
+    let result = run_wasm_opt(data, args, &input, &output);
+    let _ = std::fs::remove_file(&input);
+    let _ = std::fs::remove_file(&output);
+    result
+}
+
+fn run_wasm_opt(data: &[u8], args: &[String], input: &Path, output: &Path) -> Result<Vec<u8>> {
+    std::fs::write(input, data)?;
+    let enable = features::detect(data)?
+        .into_iter()
+        .map(|feature| match feature {
+            Feature::Simd => "--enable-simd",
+            Feature::Atomics => "--enable-threads",
+            Feature::BulkMemory => "--enable-bulk-memory",
+            Feature::ExceptionHandling => "--enable-exception-handling",
+            Feature::MultiMemory => "--enable-multimemory",
+        });
+    let result = Command::new("wasm-opt")
+        .arg(input)
+        .arg("-o")
+        .arg(output)
+        .arg("--debuginfo")
+        .args(enable)
+        .args(args)
+        .output();
+    let result = match result {
+        Ok(result) => result,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => bail!(
+            "[wasm-opt] of the config requires binaryen's `wasm-opt` command, e.g. from the \
+             `binaryen` package of your system or `cargo install wasm-opt`"
+        ),
+        Err(err) => return Err(err).context("Failed to run wasm-opt"),
+    };
+    if !result.status.success() {
+        bail!(
+            "wasm-opt failed with {}:\n{}",
+            result.status,
+            String::from_utf8_lossy(&result.stderr)
+        );
+    }
+    Ok(std::fs::read(output)?)
+}
+
+/// Function layout of a module as read from its encoding.
+#[derive(Debug, Default)]
+pub struct FunctionLayout {
+    pub imported: usize,
+    pub defined: usize,
+    /// Names of the name section, by function index.
+    pub names: BTreeMap<usize, String>,
+}
+
+pub fn read_function_layout(data: &[u8]) -> Result<FunctionLayout> {
+    let mut layout = FunctionLayout::default();
+    for payload in wasmparser::Parser::new(0).parse_all(data) {
+        match payload? {
+            Payload::ImportSection(reader) => {
+                for import in reader {
+                    if let TypeRef::Func(_) = import?.ty {
+                        layout.imported += 1;
+                    }
+                }
+            }
+            Payload::FunctionSection(reader) => layout.defined = reader.count() as usize,
+            Payload::CustomSection(section) if section.name() == "name" => {
+                let reader =
+                    wasmparser::NameSectionReader::new(section.data(), section.data_offset());
+                for part in reader {
+                    if let Name::Function(names) = part? {
+                        for naming in names {
+                            let naming = naming?;
+                            layout
+                                .names
+                                .insert(naming.index as usize, naming.name.into());
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(layout)
+}
+
+impl EmittedModule {
+    /// Describes the module by its optimized encoding `data` instead. Its
+    /// defined functions then include those duplicated into it, which
+    /// `wasm-opt` may have merged with its own.
+    pub fn set_optimized(&mut self, data: &[u8], cache: Option<&Cache>) -> Result<()> {
+        let layout = read_function_layout(data)?;
+        self.defined_functions = layout.imported..layout.imported + layout.defined;
+        self.optimized_names = Some(layout.names);
+        self.size = data.len();
+        let digest = ModuleDigest::new(data, cache)?;
+        self.gzip_size = digest.gzip_size;
+        self.hash = content_hash(data);
+        self.sha256 = digest.sha256;
+        self.integrity = digest.integrity;
+        self.features = features::detect(data)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::read_function_layout;
+    use crate::test_fixtures::{split, try_split};
+
+    #[test]
+    fn reads_function_layout_of_chunks() {
+        let output = split("no_std_app.wasm", &[]);
+        for chunk in output.manifest()["chunks"].as_array().unwrap() {
+            let layout =
+                read_function_layout(&output.read(chunk["file"].as_str().unwrap())).unwrap();
+            let range = &chunk["defined_functions"];
+            assert_eq!(range["start"], layout.imported);
+            // Functions duplicated into the chunk, and stubs, follow them.
+            assert!(range["end"].as_u64().unwrap() as usize <= layout.imported + layout.defined);
+            assert!(!layout.names.is_empty());
+        }
+    }
+
+    #[test]
+    fn optimizes_split_chunks() {
+        let (output, result) = try_split(
+            "no_std_app.wasm",
+            "[wasm-opt]\nargs = [\"-Oz\"]\n[wasm-opt.modules]\nsecond = [\"-O3\"]\n",
+            &[],
+        );
+        if let Err(err) = result {
+            assert!(
+                format!("{err:#}").contains("requires binaryen's `wasm-opt` command"),
+                "{err:?}"
+            );
+            eprintln!("Skipping running the optimized app, as wasm-opt is not installed");
+            return;
+        }
+        output.validate();
+        for chunk in output.manifest()["chunks"].as_array().unwrap() {
+            let data = output.read(chunk["file"].as_str().unwrap());
+            assert_eq!(chunk["size"], data.len());
+            assert_eq!(chunk["integrity"], crate::manifest::sri_hash(&data));
+        }
+        if let Some(result) = output.run_no_std_app(4) {
+            assert_eq!(result, crate::test_fixtures::expected_no_std_app_result(4));
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_modules() {
+        let (_output, result) = try_split(
+            "no_std_app.wasm",
+            "[wasm-opt.modules]\nthird = [\"-O3\"]\n",
+            &[],
+        );
+        assert!(format!("{:#}", result.unwrap_err()).contains("unknown split module \"third\""));
+    }
+}
@@ -0,0 +1,315 @@
+//! `wasm-split workspace`: splits the modules of several apps built from one
+//! workspace, such as micro-frontends, each into a directory of its own, and
+//! reports the code that they duplicate.
+//!
+//! ```sh
+//! wasm-split workspace shop.wasm checkout.wasm --out-dir pkg -- --fold-threshold 0
+//! ```
+//!
+//! Separately linked modules cannot share chunks: each lays out its own
+//! memory and function table, which the code of its chunks addresses
+//! directly, so even a function of a dependency they share is compiled to
+//! other code in each. The combined manifest [`WORKSPACE_MANIFEST_FILENAME`]
+//! lists the build of each app, and the code that several of them contain,
+//! by crate: the functions of the same symbol and size, which one module
+//! would only hold once. Apps that duplicate much of their code are best
+//! built as one module instead, with each app as an entry of `[entries]`,
+//! whose code is then split into chunks of its own while the dependencies
+//! that several of them use go to shared chunks.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+
+use crate::{
+    config::ByteSize,
+    deny::get_crate_name,
+    manifest::{ChunkKind, Manifest},
+    read::InputModule,
+    serve::split_args,
+    sink::{DirectorySink, OutputSink},
+    symbols::Demangling,
+};
+
+pub const WORKSPACE_MANIFEST_FILENAME: &str = "wasm-split-workspace.json";
+
+/// Crates listed in the summary of the duplicated code.
+const SUMMARY_CRATES: usize = 5;
+
+#[derive(Debug, clap::Args)]
+pub struct WorkspaceArgs {
+    /// Modules of the apps as built by cargo, before wasm-bindgen, each of
+    /// which is split into the subdirectory of its file stem.
+    #[arg(required = true, num_args = 2..)]
+    inputs: Vec<PathBuf>,
+
+    /// Directory to write the splits and the combined manifest to.
+    #[arg(long, value_name = "DIR")]
+    out_dir: PathBuf,
+
+    /// Options of every split, after `--`, such as `-- --fold-threshold 0`.
+    #[arg(last = true, value_name = "SPLIT_OPTIONS")]
+    split_options: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WorkspaceManifest {
+    pub apps: Vec<WorkspaceApp>,
+    /// Code contained by more than one app, by crate, most first.
+    pub duplicated: Vec<DuplicatedCrate>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WorkspaceApp {
+    pub name: String,
+    /// Output directory of the app, relative to that of the workspace.
+    pub dir: String,
+    pub build_id: String,
+    /// Size of the main module, which the app loads at once.
+    pub main_size: usize,
+    /// Size of all of its chunks, main module included.
+    pub total_size: usize,
+    pub chunks: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DuplicatedCrate {
+    pub crate_name: String,
+    pub functions: usize,
+    /// Code size of the copies of these functions beyond the first, which a
+    /// single module would not contain.
+    pub redundant_bytes: usize,
+    /// Apps containing any of these functions, sorted.
+    pub apps: Vec<String>,
+}
+
+/// Passes the output through to a [`DirectorySink`], keeping the manifest.
+struct ManifestSink {
+    dir: DirectorySink,
+    manifest: Option<Manifest>,
+}
+
+impl OutputSink for ManifestSink {
+    fn write(&mut self, path: &Path, contents: &[u8]) -> Result<()> {
+        self.dir.write(path, contents)
+    }
+
+    fn finish(&mut self, manifest: &Manifest) -> Result<()> {
+        self.manifest = Some(manifest.clone());
+        Ok(())
+    }
+}
+
+pub fn run(args: &WorkspaceArgs) -> Result<()> {
+    let mut names = Vec::new();
+    for input in args.inputs.iter() {
+        let Some(name) = input.file_stem() else {
+            bail!("{} is not the path of a module", input.display());
+        };
+        let name = name.to_string_lossy().into_owned();
+        if names.contains(&name) {
+            bail!("Several inputs are named {name}, which would be split into the same directory");
+        }
+        names.push(name);
+    }
+    let mut apps = Vec::new();
+    let mut functions = BTreeMap::<(String, usize), BTreeSet<String>>::new();
+    for (input, name) in args.inputs.iter().zip(names) {
+        let out_dir = args.out_dir.join(&name);
+        let split_args = split_args(&args.split_options, input, &out_dir)?;
+        let mut sink = ManifestSink {
+            dir: DirectorySink::new(match split_args.asset_version.as_deref() {
+                Some(version) => out_dir.join(version),
+                None => out_dir,
+            }),
+            manifest: None,
+        };
+        crate::split(&split_args, &mut sink)
+            .with_context(|| format!("Failed to split {}", input.display()))?;
+        let manifest = sink
+            .manifest
+            .expect("the split finished without a manifest");
+        apps.push(WorkspaceApp {
+            name: name.clone(),
+            dir: name.clone(),
+            build_id: manifest.build_id.clone(),
+            main_size: manifest
+                .chunks
+                .iter()
+                .filter(|chunk| chunk.kind == ChunkKind::Main)
+                .map(|chunk| chunk.size)
+                .sum(),
+            total_size: manifest.chunks.iter().map(|chunk| chunk.size).sum(),
+            chunks: manifest.chunks.len(),
+        });
+
+        let wasm =
+            std::fs::read(input).with_context(|| format!("Failed to read {}", input.display()))?;
+        let module = InputModule::parse(&wasm)?;
+        let imported = module.imported_funcs.len();
+        for (index, func) in module.defined_funcs.iter().enumerate() {
+            if let Some(symbol) = module.names.functions.get(&(imported + index)) {
+                functions
+                    .entry((symbol.to_string(), func.body.range().len()))
+                    .or_default()
+                    .insert(name.clone());
+            }
+        }
+    }
+
+    let workspace = WorkspaceManifest {
+        apps,
+        duplicated: get_duplicated_crates(&functions),
+    };
+    let path = args.out_dir.join(WORKSPACE_MANIFEST_FILENAME);
+    std::fs::write(&path, serde_json::to_string_pretty(&workspace)?)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    print!("{}", format_summary(&workspace));
+    Ok(())
+}
+
+/// [`DuplicatedCrate`]s of the `functions` of each app, by symbol and size.
+fn get_duplicated_crates(
+    functions: &BTreeMap<(String, usize), BTreeSet<String>>,
+) -> Vec<DuplicatedCrate> {
+    let mut crates = BTreeMap::<String, DuplicatedCrate>::new();
+    for ((symbol, size), apps) in functions.iter().filter(|(_, apps)| apps.len() > 1) {
+        let crate_name = get_crate_name(&Demangling::Full.demangle(symbol)).to_string();
+        let duplicated = crates
+            .entry(crate_name.clone())
+            .or_insert_with(|| DuplicatedCrate {
+                crate_name,
+                functions: 0,
+                redundant_bytes: 0,
+                apps: Vec::new(),
+            });
+        duplicated.functions += 1;
+        duplicated.redundant_bytes += size * (apps.len() - 1);
+        for app in apps {
+            if !duplicated.apps.contains(app) {
+                duplicated.apps.push(app.clone());
+            }
+        }
+    }
+    let mut crates = crates.into_values().collect::<Vec<_>>();
+    for duplicated in crates.iter_mut() {
+        duplicated.apps.sort();
+    }
+    crates.sort_by(|a, b| {
+        b.redundant_bytes
+            .cmp(&a.redundant_bytes)
+            .then(a.crate_name.cmp(&b.crate_name))
+    });
+    crates
+}
+
+fn format_summary(workspace: &WorkspaceManifest) -> String {
+    let mut summary = String::new();
+    for app in workspace.apps.iter() {
+        summary.push_str(&format!(
+            "{}: main module {}, {} in {} chunks\n",
+            app.name,
+            ByteSize(app.main_size),
+            ByteSize(app.total_size),
+            app.chunks
+        ));
+    }
+    let redundant = workspace
+        .duplicated
+        .iter()
+        .map(|duplicated| duplicated.redundant_bytes)
+        .sum::<usize>();
+    if redundant == 0 {
+        summary.push_str("The apps duplicate no code\n");
+        return summary;
+    }
+    summary.push_str(&format!(
+        "The apps duplicate {} of code, which one module with an [entries] entry per app \
+         would only hold once:\n",
+        ByteSize(redundant)
+    ));
+    for duplicated in workspace.duplicated.iter().take(SUMMARY_CRATES) {
+        summary.push_str(&format!(
+            "  {}: {} in {} functions\n",
+            duplicated.crate_name,
+            ByteSize(duplicated.redundant_bytes),
+            duplicated.functions
+        ));
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::WORKSPACE_MANIFEST_FILENAME;
+    use crate::{test_fixtures::fixture_path, Cli};
+
+    #[test]
+    fn splits_apps_and_reports_code_they_duplicate() {
+        let dir = std::env::temp_dir().join(format!("wasm-split-workspace-{}", std::process::id()));
+        let (no_std_app, closure_app) = (
+            fixture_path("no_std_app.wasm"),
+            fixture_path("closure_app.wasm"),
+        );
+        let args = Cli::parse_from([
+            "wasm-split".as_ref(),
+            "workspace".as_ref(),
+            no_std_app.as_os_str(),
+            closure_app.as_os_str(),
+            "--out-dir".as_ref(),
+            dir.as_os_str(),
+            "--".as_ref(),
+            "--fold-threshold".as_ref(),
+            "0".as_ref(),
+        ]);
+        crate::run(&args).unwrap();
+        let workspace: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(dir.join(WORKSPACE_MANIFEST_FILENAME)).unwrap())
+                .unwrap();
+        let apps = workspace["apps"].as_array().unwrap();
+        assert_eq!(apps.len(), 2);
+        for (app, name) in apps.iter().zip(["no_std_app", "closure_app"]) {
+            assert_eq!(app["name"], name);
+            let manifest: serde_json::Value = serde_json::from_slice(
+                &std::fs::read(dir.join(name).join("wasm-split-manifest.json")).unwrap(),
+            )
+            .unwrap();
+            assert_eq!(app["build_id"], manifest["build_id"]);
+        }
+        let duplicated = workspace["duplicated"].as_array().unwrap();
+        assert!(!duplicated.is_empty());
+        for crate_size in duplicated {
+            assert_eq!(
+                crate_size["apps"],
+                serde_json::json!(["closure_app", "no_std_app"])
+            );
+            assert!(crate_size["redundant_bytes"].as_u64().unwrap() > 0);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_inputs_of_the_same_name() {
+        let input = fixture_path("no_std_app.wasm");
+        let args = Cli::parse_from([
+            "wasm-split".as_ref(),
+            "workspace".as_ref(),
+            input.as_os_str(),
+            input.as_os_str(),
+            "--out-dir".as_ref(),
+            std::env::temp_dir()
+                .join(format!("wasm-split-workspace-same-{}", std::process::id()))
+                .as_os_str(),
+        ]);
+        let error = crate::run(&args).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("Several inputs are named no_std_app"));
+    }
+}
@@ -0,0 +1,149 @@
+#!/usr/bin/env python3
+
+# Rebuilds the prebuilt fixtures that the tests of the splitter read, so that
+# the tests need no wasm toolchain. Run after changing a fixture, or the code
+# of wasm_split that it includes, and update the outlines of their splits in
+# `golden` with `WASM_SPLIT_BLESS=1 cargo test -p wasm_split_cli corpus`.
+
+import glob
+import os
+import shutil
+import subprocess
+
+testdata_dir = os.path.dirname(os.path.abspath(__file__))
+
+# Output file name, crate, whether to link with relocations, features, and
+# settings of the release profile that differ from the crate's. The builds
+# with other optimization levels and without LTO are part of the corpus of
+# the golden tests, for the shapes of code that the splitter meets in apps.
+fixtures = [
+    ("no_std_app.wasm", "no_std_app", True, [], {}),
+    ("no_std_app_no_relocs.wasm", "no_std_app", False, ["table-only"], {}),
+    ("no_std_app_signed.wasm", "no_std_app", True, ["signed"], {}),
+    ("no_std_app_guarded.wasm", "no_std_app", True, ["guard-calls"], {}),
+    ("no_std_app_debug.wasm", "no_std_app", True, [], {"debug": "line-tables-only"}),
+    ("no_std_app_o1.wasm", "no_std_app", True, [], {"opt-level": "1", "lto": "false"}),
+    ("no_std_app_o3.wasm", "no_std_app", True, [], {"opt-level": "3"}),
+    ("closure_app.wasm", "closure_app", True, [], {}),
+    ("closure_app_no_relocs.wasm", "closure_app", False, [], {}),
+    ("closure_app_no_lto.wasm", "closure_app", True, [], {"lto": "false"}),
+    ("closure_app_panic.wasm", "closure_app", True, ["panic"], {}),
+]
+
+for filename, crate, emit_relocs, features, profile in fixtures:
+    crate_dir = os.path.join(testdata_dir, crate)
+    target_dir = os.path.join(crate_dir, "target", filename.removesuffix(".wasm"))
+    env = dict(os.environ)
+    if emit_relocs:
+        env["RUSTFLAGS"] = "-Clink-args=--emit-relocs"
+    for key, value in profile.items():
+        env["CARGO_PROFILE_RELEASE_" + key.upper().replace("-", "_")] = value
+    subprocess.run(
+        [
+            "cargo",
+            "build",
+            "--release",
+            "--target",
+            "wasm32-unknown-unknown",
+            "--target-dir",
+            target_dir,
+            "--features",
+            ",".join(features),
+        ],
+        cwd=crate_dir,
+        env=env,
+        check=True,
+    )
+    shutil.copy(
+        os.path.join(
+            target_dir, "wasm32-unknown-unknown", "release", crate + ".wasm"
+        ),
+        os.path.join(testdata_dir, filename),
+    )
+
+# Fixtures written in assembly, for code that Rust cannot produce without
+# rebuilding std. Needs LLVM's `llvm-mc`, and the `rust-lld` of the toolchain.
+rust_lld = glob.glob(
+    os.path.join(
+        subprocess.run(
+            ["rustc", "--print", "sysroot"], capture_output=True, text=True, check=True
+        ).stdout.strip(),
+        "lib",
+        "rustlib",
+        "*",
+        "bin",
+        "rust-lld",
+    )
+)[0]
+
+# Output file name, assembly source, mattr of llvm-mc, exported symbols, and
+# other arguments of the linker.
+assembly_fixtures = [
+    (
+        "eh_app.wasm",
+        "eh_app.s",
+        "+exception-handling",
+        ["run", "__wasm_split_00unwind00_export_00112233445566778899aabbccddeeff_boom"],
+        [],
+    ),
+    (
+        "refs_app.wasm",
+        "refs_app.s",
+        "+reference-types",
+        ["run", "__wasm_split_00keep00_export_00112233445566778899aabbccddeeff_keep"],
+        [],
+    ),
+    (
+        "threads_app.wasm",
+        "threads_app.s",
+        "+atomics,+bulk-memory",
+        ["run", "__wasm_split_00add00_export_00112233445566778899aabbccddeeff_add"],
+        [
+            "--features=atomics,bulk-memory",
+            "--import-memory",
+            "--shared-memory",
+            "--max-memory=1114112",
+        ],
+    ),
+    (
+        "dead_data_app.wasm",
+        "dead_data_app.s",
+        "",
+        ["run", "__wasm_split_00keep00_export_00112233445566778899aabbccddeeff_keep"],
+        ["--no-gc-sections"],
+    ),
+    (
+        "rust_loader_app.wasm",
+        "rust_loader_app.s",
+        "",
+        [
+            "run",
+            "__wasm_split_rust_loader",
+            "__wasm_split_00triple00_export_00112233445566778899aabbccddeeff_triple",
+        ],
+        [],
+    ),
+]
+
+for filename, source, mattr, exports, link_args in assembly_fixtures:
+    obj = os.path.join(testdata_dir, "target", source.removesuffix(".s") + ".o")
+    os.makedirs(os.path.dirname(obj), exist_ok=True)
+    subprocess.run(
+        [
+            "llvm-mc",
+            "-triple=wasm32-unknown-unknown",
+            "-mattr=" + mattr,
+            "-filetype=obj",
+            os.path.join(testdata_dir, source),
+            "-o",
+            obj,
+        ],
+        check=True,
+    )
+    subprocess.run(
+        [rust_lld, "-flavor", "wasm", "--no-entry", "--emit-relocs"]
+        + ["--export=" + export for export in exports]
+        + link_args
+        + [obj, "-o", os.path.join(testdata_dir, filename)],
+        check=True,
+    )
@@ -0,0 +1,87 @@
+//! Fixture for the tests of the splitter: split functions that hand Rust
+//! closures to JS, and an async one that awaits JS promises and spawns a
+//! task, run with `run_closure_app.mjs`, and, with the `panic` feature, one
+//! that panics, whose chunk the panic hook reports.
+
+use wasm_bindgen::{closure::Closure, prelude::*};
+use wasm_bindgen_futures::JsFuture;
+use wasm_split::wasm_split;
+
+#[wasm_bindgen]
+extern "C" {
+    /// Keeps the callback for JS to call after the split function returned.
+    fn store_callback(callback: &Closure<dyn FnMut(u32) -> u32>);
+    /// Calls the callback right away, while the split function runs.
+    fn call_now(callback: &Closure<dyn Fn(u32) -> u32>, value: u32) -> u32;
+    /// Resolves to twice `value` on a later turn of the event loop.
+    fn double_later(value: u32) -> js_sys::Promise;
+    /// Receives the result of the task spawned by the split function.
+    fn report_total(value: u32);
+}
+
+/// Hands JS a callback that adds `offset` and counts its calls, which it
+/// can call after the module has been loaded.
+#[wasm_split(counter)]
+fn register_counter(offset: u32) {
+    let mut calls = 0;
+    let callback = Closure::<dyn FnMut(u32) -> u32>::new(move |value: u32| {
+        calls += 1;
+        value + offset + calls * 1000
+    });
+    store_callback(&callback);
+    callback.forget();
+}
+
+#[wasm_split(scale)]
+fn scale(factor: u32, value: u32) -> u32 {
+    let callback = Closure::<dyn Fn(u32) -> u32>::new(move |value: u32| value * factor);
+    call_now(&callback, value)
+}
+
+async fn doubled(value: u32) -> u32 {
+    let doubled = JsFuture::from(double_later(value)).await.unwrap();
+    doubled.as_f64().unwrap() as u32
+}
+
+/// Sums the doubles of `values` from JS, and spawns a task that reports the
+/// double of the sum once it returned.
+#[wasm_split(totals)]
+async fn sum_doubled(values: Vec<u32>) -> u32 {
+    let mut total = 0;
+    for value in values {
+        total += doubled(value).await;
+    }
+    wasm_bindgen_futures::spawn_local(async move {
+        report_total(doubled(total).await);
+    });
+    total
+}
+
+#[cfg(feature = "panic")]
+#[wasm_split(checked)]
+fn check_positive(value: i32) -> i32 {
+    assert!(value > 0, "{value} is not positive");
+    value
+}
+
+#[wasm_bindgen]
+pub async fn register(offset: u32) {
+    register_counter(offset).await;
+}
+
+#[wasm_bindgen]
+pub async fn run_scale(factor: u32, value: u32) -> u32 {
+    scale(factor, value).await
+}
+
+#[wasm_bindgen]
+pub async fn run_sum_doubled(values: Vec<u32>) -> u32 {
+    sum_doubled(values).await
+}
+
+#[cfg(feature = "panic")]
+#[wasm_bindgen]
+pub async fn run_check_positive(value: i32) -> i32 {
+    wasm_split::panic_hook::set_once();
+    check_positive(value).await
+}
@@ -0,0 +1,62 @@
+# A split point that calls `triple` through a vtable in data, linked without
+# `--gc-sections`, so that the input also keeps `unused_vtable`, which no code
+# refers to, and the table slot of `unused` that it holds. `run(n)` calls
+# `done(3 * n)`.
+#
+# Assembled and linked by build.py.
+
+	.functype	done (i32) -> ()
+	.import_module	done, env
+	.import_name	done, done
+	.functype	__wasm_split_00keep00_import_00112233445566778899aabbccddeeff_keep (i32) -> (i32)
+	.import_module	__wasm_split_00keep00_import_00112233445566778899aabbccddeeff_keep, env
+	.import_name	__wasm_split_00keep00_import_00112233445566778899aabbccddeeff_keep, __wasm_split_00keep00_import_00112233445566778899aabbccddeeff_keep
+
+	.section	.text.triple,"",@
+	.type	triple,@function
+triple:
+	.functype	triple (i32) -> (i32)
+	local.get	0
+	i32.const	3
+	i32.mul
+	end_function
+
+	.section	.text.unused,"",@
+	.type	unused,@function
+unused:
+	.functype	unused (i32) -> (i32)
+	local.get	0
+	end_function
+
+	.section	.data.vtable,"",@
+	.p2align	2
+vtable:
+	.int32	triple
+	.size	vtable, 4
+
+	.section	.data.unused_vtable,"",@
+	.p2align	2
+unused_vtable:
+	.int32	unused
+	.size	unused_vtable, 4
+
+	.section	.text.keep,"",@
+	.globl	__wasm_split_00keep00_export_00112233445566778899aabbccddeeff_keep
+	.type	__wasm_split_00keep00_export_00112233445566778899aabbccddeeff_keep,@function
+__wasm_split_00keep00_export_00112233445566778899aabbccddeeff_keep:
+	.functype	__wasm_split_00keep00_export_00112233445566778899aabbccddeeff_keep (i32) -> (i32)
+	local.get	0
+	i32.const	0
+	i32.load	vtable
+	call_indirect	(i32) -> (i32)
+	end_function
+
+	.section	.text.run,"",@
+	.globl	run
+	.type	run,@function
+run:
+	.functype	run (i32) -> ()
+	local.get	0
+	call	__wasm_split_00keep00_import_00112233445566778899aabbccddeeff_keep
+	call	done
+	end_function
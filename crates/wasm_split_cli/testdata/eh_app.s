@@ -0,0 +1,62 @@
+# A split point whose function throws, caught by its caller in the main
+# module, as Rust code built with `panic = "unwind"` and the exception
+# handling proposal unwinds a panic. Written by hand, since the prebuilt std
+# of wasm32-unknown-unknown aborts on panics. `run(n)` calls `done(n)`, or
+# `done(1000 + n)` if `n > 3`, for which `boom` throws.
+#
+# Assembled and linked by build.py.
+
+	.functype	done (i32) -> ()
+	.import_module	done, env
+	.import_name	done, done
+	.functype	__wasm_split_00unwind00_import_00112233445566778899aabbccddeeff_boom (i32) -> (i32)
+	.import_module	__wasm_split_00unwind00_import_00112233445566778899aabbccddeeff_boom, env
+	.import_name	__wasm_split_00unwind00_import_00112233445566778899aabbccddeeff_boom, __wasm_split_00unwind00_import_00112233445566778899aabbccddeeff_boom
+
+	.tagtype	panic_tag i32
+	.section	.data.panic_tag,"",@
+	.globl	panic_tag
+panic_tag:
+
+	.section	.text.raise,"",@
+	.type	raise,@function
+raise:
+	.functype	raise (i32) -> ()
+	local.get	0
+	throw	panic_tag
+	unreachable
+	end_function
+
+	.section	.text.boom,"",@
+	.globl	__wasm_split_00unwind00_export_00112233445566778899aabbccddeeff_boom
+	.type	__wasm_split_00unwind00_export_00112233445566778899aabbccddeeff_boom,@function
+__wasm_split_00unwind00_export_00112233445566778899aabbccddeeff_boom:
+	.functype	__wasm_split_00unwind00_export_00112233445566778899aabbccddeeff_boom (i32) -> (i32)
+	local.get	0
+	i32.const	3
+	i32.gt_u
+	if
+	local.get	0
+	call	raise
+	end_if
+	local.get	0
+	end_function
+
+	.section	.text.run,"",@
+	.globl	run
+	.type	run,@function
+run:
+	.functype	run (i32) -> ()
+	.local	i32
+	try
+	local.get	0
+	call	__wasm_split_00unwind00_import_00112233445566778899aabbccddeeff_boom
+	local.set	1
+	catch	panic_tag
+	i32.const	1000
+	i32.add
+	local.set	1
+	end_try
+	local.get	1
+	call	done
+	end_function
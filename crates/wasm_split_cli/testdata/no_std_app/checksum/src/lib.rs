@@ -0,0 +1,15 @@
+//! A dependency of the fixture that both eager code and the split module
+//! `verify` call, which thus anchors it in the main module.
+
+#![no_std]
+
+/// The Adler-32 checksum of `data`.
+#[inline(never)]
+pub fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
@@ -0,0 +1,965 @@
+//! Fixture for the tests of the splitter: a `no_std` app with two split
+//! modules that share a helper, run with `run.mjs` through `instantiateMain`.
+
+#![no_std]
+extern crate alloc;
+
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    cell::UnsafeCell,
+    future::{poll_fn, Future},
+    pin::{pin, Pin},
+    sync::atomic::{AtomicU32, Ordering},
+    task::{Context, Poll, RawWaker, RawWakerVTable, Waker},
+};
+
+use wasm_split::{wasm_split, TableSlot};
+
+const HEAP_SIZE: usize = 1 << 16;
+
+struct Bump(UnsafeCell<usize>);
+unsafe impl Sync for Bump {}
+static mut HEAP: [u8; HEAP_SIZE] = [0; HEAP_SIZE];
+
+unsafe impl GlobalAlloc for Bump {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let next = &mut *self.0.get();
+        let start = (*next + layout.align() - 1) & !(layout.align() - 1);
+        if start + layout.size() > HEAP_SIZE {
+            return core::ptr::null_mut();
+        }
+        *next = start + layout.size();
+        core::ptr::addr_of_mut!(HEAP).cast::<u8>().add(start)
+    }
+
+    unsafe fn dealloc(&self, _: *mut u8, _: Layout) {}
+}
+
+#[global_allocator]
+static ALLOC: Bump = Bump(UnsafeCell::new(0));
+
+#[panic_handler]
+fn panic(_: &core::panic::PanicInfo) -> ! {
+    core::arch::wasm32::unreachable()
+}
+
+struct SingleThreaded;
+critical_section::set_impl!(SingleThreaded);
+unsafe impl critical_section::Impl for SingleThreaded {
+    unsafe fn acquire() {}
+    unsafe fn release(_: ()) {}
+}
+
+#[cfg(feature = "signed")]
+wasm_split::manifest_public_key!(*include_bytes!("../../manifest-key.pub"));
+
+#[inline(never)]
+fn sum_of_squares(values: &[u32]) -> u32 {
+    values.iter().map(|value| value * value).sum()
+}
+
+fn double(x: u32) -> u32 {
+    x * 2
+}
+
+fn triple(x: u32) -> u32 {
+    x * 3
+}
+
+#[wasm_split(first)]
+fn first(values: Vec<u32>) -> u32 {
+    sum_of_squares(&values) + values.len() as u32
+}
+
+/// Reserves table slots and calls through a function pointer, which must
+/// still resolve once the module is split out.
+#[wasm_split(second, table_slots = 4)]
+fn second(x: u32) -> u32 {
+    let scale: fn(u32) -> u32 = if x % 2 == 0 { double } else { triple };
+    core::hint::black_box(scale)(x) + sum_of_squares(&[x])
+}
+
+struct Squares(Vec<u32>);
+
+#[wasm_split]
+impl Squares {
+    /// Shares the chunk of `first`, by being in the same module.
+    #[wasm_split(group = "first")]
+    fn sum(&self, offset: u32) -> u32 {
+        sum_of_squares(&self.0) + offset
+    }
+}
+
+trait Scale {
+    async fn scale(self, x: u32) -> u32;
+}
+
+struct Factor(u32);
+
+/// A method of a trait impl, taking `self` by value.
+#[wasm_split]
+impl Scale for Factor {
+    #[wasm_split(second)]
+    async fn scale(mut self, x: u32) -> u32 {
+        self.0 *= x;
+        Self::square(self.0)
+    }
+}
+
+impl Factor {
+    fn square(x: u32) -> u32 {
+        x * x
+    }
+}
+
+/// As the `LazyRoute` trait of Leptos' router.
+trait Route {
+    fn data(x: u32) -> Self;
+    async fn view(self) -> u32;
+}
+
+struct Cube(u32);
+
+#[wasm_split::lazy_route(second)]
+impl Route for Cube {
+    fn data(x: u32) -> Self {
+        Self(x)
+    }
+
+    async fn view(self) -> u32 {
+        self.0 * self.0 * self.0
+    }
+}
+
+/// Runs on the loader's worker where there is one, and otherwise as any
+/// other function of `second`.
+#[wasm_split(second, worker)]
+fn word_lengths(text: &str, min_len: usize) -> Vec<u32> {
+    text.split(' ')
+        .filter(|word| word.len() >= min_len)
+        .map(|word| word.len() as u32)
+        .collect()
+}
+
+/// Called by `quadrupled` of `second`, and through a pointer from the main
+/// module by `run_lazy_pointer`, which with `--lazy-indirect-calls` leaves
+/// it to `second`.
+#[inline(never)]
+fn quadruple(x: u32) -> u32 {
+    core::hint::black_box(x) * 4
+}
+
+static QUADRUPLE: fn(u32) -> u32 = quadruple;
+
+#[wasm_split(second)]
+fn quadrupled(x: u32) -> u32 {
+    quadruple(x)
+}
+
+/// Returns the error of a failed load of `second`, which the next call
+/// retries.
+#[wasm_split(second, fallible)]
+fn cube(x: u32) -> u32 {
+    x * x * x
+}
+
+/// Called instead of `tenfold` if `second` fails to load.
+fn tenfold_offline(x: u32) -> u32 {
+    x
+}
+
+#[wasm_split(second, fallback = tenfold_offline)]
+fn tenfold(x: u32) -> u32 {
+    x * 10
+}
+
+struct Point {
+    x: u32,
+    y: u32,
+}
+
+/// Borrows structs of the caller, the result tied to them, a closure as a
+/// trait object, and mutable state bound by a pattern.
+#[wasm_split(second)]
+fn heaviest<'a>(
+    points: &'a [Point],
+    weight: &dyn Fn(&Point) -> u32,
+    (calls, total): &mut (u32, u32),
+) -> Option<&'a Point> {
+    *calls += 1;
+    *total += points.iter().map(weight).sum::<u32>();
+    points.iter().max_by_key(|point| weight(point))
+}
+
+/// A generic function, split for both types that `run_generic` calls it
+/// with.
+#[wasm_split(second, types(u32, u64))]
+fn sum_as<T: From<u32> + core::iter::Sum<T>>(values: &[u32]) -> T {
+    values.iter().map(|&value| T::from(value)).sum()
+}
+
+/// Takes a closure of the main module, which is passed as a trait object.
+#[wasm_split(second)]
+fn apply_twice(x: u32, f: impl Fn(u32) -> u32) -> u32 {
+    f(f(x))
+}
+
+/// Strict, as its code must stay out of the main module.
+#[wasm_split(details, strict)]
+fn details(x: u32) -> u32 {
+    x + 1
+}
+
+/// Data that only `bonus` reads, which is thus part of its module.
+static BONUS_TABLE: [u8; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < table.len() {
+        table[i] = i as u8;
+        i += 1;
+    }
+    table
+};
+
+/// An enhancement that the app does without.
+#[wasm_split(bonus, optional)]
+fn bonus(x: u32) -> u32 {
+    x + 100 + core::hint::black_box(&BONUS_TABLE)[0] as u32
+}
+
+/// A table that stays in the data of `squares` until it is loaded.
+#[wasm_split(squares, data)]
+static SQUARES: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < table.len() {
+        table[i] = (i * i) as u32;
+        i += 1;
+    }
+    table
+};
+
+/// Built on first use by its initializer, which is thus part of the chunk of
+/// `second` rather than of the main module.
+#[wasm_split(second)]
+static PRIMES: Vec<u32> = (2..200u32)
+    .filter(|&n| (2..n).all(|d| n % d != 0))
+    .collect();
+
+/// Calls `checksum`, as does `run_checksum` in the main module, which would
+/// fail the split if `verify` were strict.
+#[wasm_split(verify)]
+fn verify(data: Vec<u8>) -> u32 {
+    checksum::adler32(&data)
+}
+
+/// Returns a future that calls into `details`, whose code is thus part of
+/// the chunk of `first`, the only caller of `details`.
+#[wasm_split(first)]
+fn with_details(x: u32) -> Pin<Box<dyn Future<Output = u32>>> {
+    Box::pin(async move { details(x).await * 2 })
+}
+
+#[link(wasm_import_module = "env")]
+extern "C" {
+    fn wake();
+    fn done(result: u32);
+}
+
+struct Task(UnsafeCell<Option<Pin<Box<dyn Future<Output = ()>>>>>);
+unsafe impl Sync for Task {}
+static TASK: Task = Task(UnsafeCell::new(None));
+
+const VTABLE: RawWakerVTable = RawWakerVTable::new(
+    |_| RawWaker::new(core::ptr::null(), &VTABLE),
+    |_| unsafe { wake() },
+    |_| unsafe { wake() },
+    |_| {},
+);
+
+/// Calls both split modules, and then `done` with the sum of their results:
+/// `n * (n - 1) * (2 * n - 1) / 6 + n + (2 or 3) * n + n * n`.
+#[no_mangle]
+pub extern "C" fn run(n: u32) {
+    let task = async move {
+        let result = first((0..n).collect()).await + second(n).await;
+        unsafe { done(result) }
+    };
+    unsafe { *TASK.0.get() = Some(Box::pin(task)) };
+    poll();
+}
+
+/// As `run`, but drops the module of `first` while it is loading, and then
+/// loads it again in a group once the call completed, which must still
+/// succeed as it was loaded.
+#[no_mangle]
+pub extern "C" fn run_dropping_first(n: u32) {
+    let task = async move {
+        let mut first = Box::pin(first((0..n).collect()));
+        poll_fn(|cx| {
+            assert!(first.as_mut().poll(cx).is_pending());
+            Poll::Ready(())
+        })
+        .await;
+        wasm_split::drop_module("first");
+        let first = first.await;
+        if wasm_split::load_group(&["first", "second"]).await.is_err() {
+            core::arch::wasm32::unreachable();
+        }
+        let result = first + second(n).await;
+        unsafe { done(result) }
+    };
+    unsafe { *TASK.0.get() = Some(Box::pin(task)) };
+    poll();
+}
+
+/// As `run`, after preloading both modules, which the calls then wait for,
+/// and a module that does not exist.
+#[no_mangle]
+pub extern "C" fn run_preloaded(n: u32) {
+    wasm_split::preload("second");
+    wasm_split::SplitChunk::new("first").preload();
+    wasm_split::preload("third");
+    run(n)
+}
+
+/// As `run`, with the chunks moved to `cdn/` by the test.
+#[no_mangle]
+pub extern "C" fn run_with_base_url(n: u32) {
+    wasm_split::set_base_url("cdn");
+    run(n)
+}
+
+/// Calls `cube(n)` until its module loads: `n^3 + 1000 *` the number of
+/// failed loads.
+#[no_mangle]
+pub extern "C" fn run_retrying(n: u32) {
+    let task = async move {
+        let mut failures = 0;
+        let cube = loop {
+            match cube(n).await {
+                Ok(cube) => break cube,
+                Err(_) => failures += 1,
+            }
+        };
+        unsafe { done(cube + 1000 * failures) }
+    };
+    unsafe { *TASK.0.get() = Some(Box::pin(task)) };
+    poll();
+}
+
+/// As `run_retrying`, and then calls `first`, counting the load events:
+/// `1000 * started + 100 * instantiated + 10 * downloaded + failed`, where
+/// `downloaded` counts the chunks whose progress reached their size.
+#[no_mangle]
+pub extern "C" fn run_counting_events(n: u32) {
+    let counts = alloc::rc::Rc::new(core::cell::Cell::new(0));
+    let subscription = wasm_split::on_event({
+        let counts = counts.clone();
+        move |event| {
+            let count = match event {
+                wasm_split::LoadEvent::Started { .. } => 1000,
+                wasm_split::LoadEvent::Instantiated { .. } => 100,
+                wasm_split::LoadEvent::Progress {
+                    bytes,
+                    total: Some(total),
+                    ..
+                } if bytes == total => 10,
+                wasm_split::LoadEvent::Failed { .. } => 1,
+                _ => 0,
+            };
+            counts.set(counts.get() + count);
+        }
+    });
+    let task = async move {
+        while cube(n).await.is_err() {}
+        first((0..n).collect()).await;
+        drop(subscription);
+        unsafe { done(counts.get()) }
+    };
+    unsafe { *TASK.0.get() = Some(Box::pin(task)) };
+    poll();
+}
+
+/// Loads the chunks of the route `/both`, which the test declares to need
+/// both modules, along with data of `n`, and then runs as `run` without
+/// further loads: `n + 1000 *` whether all bytes of the route were counted
+/// `+ 10000 *` whether progress was reported, `+ run(n)` at `done`.
+#[no_mangle]
+pub extern "C" fn run_route(n: u32) {
+    let reports = alloc::rc::Rc::new(core::cell::Cell::new(0));
+    let task = async move {
+        if wasm_split::load_route("/none").await != Err(wasm_split::LoadError::UnknownChunk) {
+            core::arch::wasm32::unreachable();
+        }
+        let mut load = wasm_split::load_route("/both")
+            .with_data(async move { n }, 100)
+            .on_progress({
+                let reports = reports.clone();
+                move |_| reports.set(reports.get() + 1)
+            });
+        let Ok(data) = (&mut load).await else {
+            core::arch::wasm32::unreachable();
+        };
+        let progress = load.progress();
+        let counted = progress.total() > 0 && progress.bytes() == progress.total();
+        let result = data
+            + 1000 * counted as u32
+            + 10000 * (reports.get() > 0) as u32
+            + first((0..n).collect()).await
+            + second(n).await;
+        unsafe { done(result) }
+    };
+    unsafe { *TASK.0.get() = Some(Box::pin(task)) };
+    poll();
+}
+
+/// Lists the preload links of `/both/n`, which is nested under the route
+/// `/both` that the test declares, before and after running as `run`: `n +
+/// 1000 *` whether there were links for both modules, with integrity, `+
+/// 10000 *` whether none were left after, `+ run(n)` at `done`.
+#[no_mangle]
+pub extern "C" fn run_route_preload_links(n: u32) {
+    let task = async move {
+        let before = wasm_split::route_preload_links("/both/42");
+        let listed = before.len() >= 2
+            && before
+                .iter()
+                .all(|link| link.href().contains(".wasm") && link.integrity().is_some());
+        let result = first((0..n).collect()).await + second(n).await;
+        let after = wasm_split::route_preload_links("/both/42");
+        unsafe { done(n + 1000 * listed as u32 + 10000 * after.is_empty() as u32 + result) }
+    };
+    unsafe { *TASK.0.get() = Some(Box::pin(task)) };
+    poll();
+}
+
+/// Looks up the chunks of `/both/n`, which is nested under the route `/both`
+/// that the test declares, preloads them, and then runs as `run(n)`: `n +
+/// 1000 *` whether both modules were listed `+ 10000 *` whether an unknown
+/// route had none, `+ run(n)` at `done`.
+#[no_mangle]
+pub extern "C" fn run_route_chunks(n: u32) {
+    let chunks = wasm_split::route_chunks("/both/42");
+    let listed = ["first", "second"]
+        .iter()
+        .all(|name| chunks.iter().any(|chunk| chunk == name));
+    let unknown = wasm_split::route_chunks("/unknown").is_empty();
+    wasm_split::preload_route("/both/42");
+    let task = async move {
+        let result = first((0..n).collect()).await + second(n).await;
+        unsafe { done(n + 1000 * listed as u32 + 10000 * unknown as u32 + result) }
+    };
+    unsafe { *TASK.0.get() = Some(Box::pin(task)) };
+    poll();
+}
+
+/// Loads the modules of a server-rendered page of both, along with one that
+/// does not exist, as its hydration would, and waits for them to be
+/// instantiated before calling into them: `n + 1000 *` whether both were
+/// loaded without a call `+ run(n)` at `done`.
+#[no_mangle]
+pub extern "C" fn run_hydrating(n: u32) {
+    let instantiated = alloc::rc::Rc::new(core::cell::Cell::new((false, false)));
+    let subscription = wasm_split::on_event({
+        let instantiated = instantiated.clone();
+        move |event| {
+            if let wasm_split::LoadEvent::Instantiated { chunk } = event {
+                let (first, second) = instantiated.get();
+                instantiated.set((first || chunk == "first", second || chunk == "second"));
+                unsafe { wake() }
+            }
+        }
+    });
+    wasm_split::hydrate_preload(&["first", "second", "third"]);
+    let task = async move {
+        poll_fn(|_| match instantiated.get() {
+            (true, true) => Poll::Ready(()),
+            _ => Poll::Pending,
+        })
+        .await;
+        drop(subscription);
+        let loaded = wasm_split::is_loaded("first") && wasm_split::is_loaded("second");
+        let result = first((0..n).collect()).await + second(n).await;
+        unsafe { done(n + 1000 * loaded as u32 + result) }
+    };
+    unsafe { *TASK.0.get() = Some(Box::pin(task)) };
+    poll();
+}
+
+/// As `run_racing`, after limiting the loader to one chunk fetch at a time
+/// and lowering the priority of `first`.
+#[no_mangle]
+pub extern "C" fn run_configured(n: u32) {
+    wasm_split::configure(
+        wasm_split::LoaderConfig::new()
+            .max_concurrent_fetches(1)
+            .priority("first", wasm_split::Priority::Low),
+    );
+    run_racing(n)
+}
+
+/// Starts a call of `cube(n)` and cancels the download of `second`, and then
+/// calls it again: `n^3 + 1000 *` the number of downloads cancelled `+ 10000
+/// *` whether the first call failed as aborted.
+#[no_mangle]
+pub extern "C" fn run_aborting(n: u32) {
+    let task = async move {
+        let mut first_call = Box::pin(cube(n));
+        poll_fn(|cx| {
+            assert!(first_call.as_mut().poll(cx).is_pending());
+            Poll::Ready(())
+        })
+        .await;
+        let aborted = wasm_split::abort_loads(&["second"]) as u32;
+        let failed = first_call.await == Err(wasm_split::LoadError::Aborted);
+        let Ok(cube) = cube(n).await else {
+            core::arch::wasm32::unreachable();
+        };
+        unsafe { done(cube + 1000 * aborted + 10000 * failed as u32) }
+    };
+    unsafe { *TASK.0.get() = Some(Box::pin(task)) };
+    poll();
+}
+
+/// Calls `cube(n)` once: `n^3`, or `1` if its load timed out, and `2` if it
+/// failed otherwise.
+#[no_mangle]
+pub extern "C" fn run_timing_out(n: u32) {
+    let task = async move {
+        let result = match cube(n).await {
+            Ok(cube) => cube,
+            Err(wasm_split::LoadError::Timeout) => 1,
+            Err(_) => 2,
+        };
+        unsafe { done(result) }
+    };
+    unsafe { *TASK.0.get() = Some(Box::pin(task)) };
+    poll();
+}
+
+/// Calls `cube(n)` until its module loads, as `run_retrying`: `n^3 + 1000 *`
+/// whether the first call failed `+ 10000 *` whether `load_failure` then
+/// described the failure of `second` `+ 100000 *` whether it no longer did
+/// once loaded.
+#[no_mangle]
+pub extern "C" fn run_reporting_failure(n: u32) {
+    let task = async move {
+        let failed = cube(n).await.is_err();
+        let described = wasm_split::load_failure("second").is_some_and(|failure| {
+            failure.chunk() == "second"
+                && failure.url().contains("second.wasm")
+                && failure.error() == wasm_split::LoadError::Network
+                && failure.message().contains("Failed to fetch")
+        });
+        let Ok(cube) = cube(n).await else {
+            core::arch::wasm32::unreachable();
+        };
+        let cleared = wasm_split::load_failure("second").is_none();
+        unsafe {
+            done(cube + 1000 * failed as u32 + 10000 * described as u32 + 100000 * cleared as u32)
+        }
+    };
+    unsafe { *TASK.0.get() = Some(Box::pin(task)) };
+    poll();
+}
+
+/// Times a navigation to `/cube`, whose data is `n` right away and whose
+/// view is `cube(n)`, loading `second`, and drops one to `/dropped`, which
+/// the loader does not report: `n^3`.
+#[no_mangle]
+pub extern "C" fn run_timing_navigation(n: u32) {
+    let task = async move {
+        drop(wasm_split::NavigationTiming::start("/dropped"));
+        let timing = wasm_split::NavigationTiming::start("/cube");
+        let data = timing.track_data(async { n }).await;
+        let Ok(result) = cube(data).await else {
+            core::arch::wasm32::unreachable();
+        };
+        timing.finish();
+        unsafe { done(result) }
+    };
+    unsafe { *TASK.0.get() = Some(Box::pin(task)) };
+    poll();
+}
+
+/// Calls `tenfold(n)` twice: `20 * n`, or `2 * n` from its fallback if
+/// `second` failed to load, as the failed load is not retried.
+#[no_mangle]
+pub extern "C" fn run_falling_back(n: u32) {
+    let task = async move {
+        let result = tenfold(n).await + tenfold(n).await;
+        unsafe { done(result) }
+    };
+    unsafe { *TASK.0.get() = Some(Box::pin(task)) };
+    poll();
+}
+
+/// Calls `cube(n)` twice through `with_fallback`, first while `second`
+/// loads, which the test delays past the fallback's delay, and then once
+/// loaded: `2 * n^3 + 1000 *` the fallback's changes, `1` for shown and `2`
+/// for hidden as digits in order, i.e. `12` if only the first call showed it.
+#[no_mangle]
+pub extern "C" fn run_with_fallback(n: u32) {
+    let task = async move {
+        let changes = core::cell::Cell::new(0);
+        let set_fallback = |shown: bool| changes.set(changes.get() * 10 + 2 - shown as u32);
+        let timing = wasm_split::FallbackTiming::new(1, 50);
+        let Ok(slow) = wasm_split::with_fallback(cube(n), timing, set_fallback).await else {
+            core::arch::wasm32::unreachable();
+        };
+        let timing = wasm_split::FallbackTiming::default();
+        let Ok(fast) = wasm_split::with_fallback(cube(n), timing, set_fallback).await else {
+            core::arch::wasm32::unreachable();
+        };
+        unsafe { done(slow + fast + 1000 * changes.get()) }
+    };
+    unsafe { *TASK.0.get() = Some(Box::pin(task)) };
+    poll();
+}
+
+/// Makes many first calls of the functions of both modules at once, while
+/// loading both as a group and preloading `second`, all before either is
+/// loaded: `8 * (run(n) + n * (n - 1) * (2 * n - 1) / 6)`.
+#[no_mangle]
+pub extern "C" fn run_racing(n: u32) {
+    let task = async move {
+        let mut calls = Vec::<Pin<Box<dyn Future<Output = u32>>>>::new();
+        calls.push(Box::pin(async {
+            if wasm_split::load_group(&["first", "second"]).await.is_err() {
+                core::arch::wasm32::unreachable();
+            }
+            0
+        }));
+        for _ in 0..8 {
+            calls.push(Box::pin(first((0..n).collect())));
+            calls.push(Box::pin(second(n)));
+            calls.push(Box::pin(async move { Squares((0..n).collect()).sum(0).await }));
+            wasm_split::preload("second");
+        }
+        let mut result = 0;
+        poll_fn(|cx| {
+            calls.retain_mut(|call| match call.as_mut().poll(cx) {
+                Poll::Ready(value) => {
+                    result += value;
+                    false
+                }
+                Poll::Pending => true,
+            });
+            if calls.is_empty() {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+        unsafe { done(result) }
+    };
+    unsafe { *TASK.0.get() = Some(Box::pin(task)) };
+    poll();
+}
+
+/// Calls `bonus(n)` twice, which is `n + 100` each time, or `0` if its
+/// module failed to load, and then `cube(n)` to show that other modules still load.
+#[no_mangle]
+pub extern "C" fn run_optional(n: u32) {
+    let task = async move {
+        let bonus = bonus(n).await + bonus(n).await;
+        let Ok(cube) = cube(n).await else {
+            core::arch::wasm32::unreachable();
+        };
+        unsafe { done(bonus + cube) }
+    };
+    unsafe { *TASK.0.get() = Some(Box::pin(task)) };
+    poll();
+}
+
+/// Looks up `n * n` in `SQUARES`, twice to show that the second lookup
+/// needs no load: `2 * n * n`.
+#[no_mangle]
+pub extern "C" fn run_squares(n: u32) {
+    let task = async move {
+        let square = SQUARES.get().await[n as usize];
+        unsafe { done(square + SQUARES.get().await[n as usize]) }
+    };
+    unsafe { *TASK.0.get() = Some(Box::pin(task)) };
+    poll();
+}
+
+/// Counts the primes below `n` in `PRIMES`, twice to show that the second
+/// lookup runs no initializer: twice the number of primes below `n`.
+#[no_mangle]
+pub extern "C" fn run_split_static(n: u32) {
+    let task = async move {
+        let count = |primes: &Vec<u32>| primes.iter().filter(|&&p| p < n).count() as u32;
+        let first = count(PRIMES.get().await);
+        unsafe { done(first + count(PRIMES.get().await)) }
+    };
+    unsafe { *TASK.0.get() = Some(Box::pin(task)) };
+    poll();
+}
+
+/// Allocates the table slots that `second` reserves until they run out,
+/// frees `n` of them and allocates again until they run out: 100 times the
+/// number of slots allocated at first, plus the number of those allocated
+/// again, which are the freed ones.
+#[no_mangle]
+pub extern "C" fn run_table_slots(n: u32) {
+    let mut slots = Vec::new();
+    while let Some(slot) = TableSlot::allocate() {
+        slots.push(slot);
+    }
+    let allocated = slots.len() as u32;
+    let freed = slots
+        .drain(..n as usize)
+        .map(|slot| slot.index())
+        .collect::<Vec<_>>();
+    let mut reused = 0;
+    while let Some(slot) = TableSlot::allocate() {
+        reused += freed.contains(&slot.index()) as u32;
+        slots.push(slot);
+    }
+    unsafe { done(allocated * 100 + reused) }
+}
+
+/// Calls the split methods: `n * (n - 1) * (2 * n - 1) / 6 + n + n^4 + n^3`.
+#[no_mangle]
+pub extern "C" fn run_methods(n: u32) {
+    let task = async move {
+        let result = Squares((0..n).collect()).sum(n).await
+            + Factor(n).scale(n).await
+            + Cube::data(n).view().await;
+        unsafe { done(result) }
+    };
+    unsafe { *TASK.0.get() = Some(Box::pin(task)) };
+    poll();
+}
+
+/// Sums the lengths of the words of `a aa aaa ...` with `n` words that are at
+/// least two letters long: `n * (n + 1) / 2 - 1`.
+#[no_mangle]
+pub extern "C" fn run_on_worker(n: u32) {
+    let task = async move {
+        let words = (1..=n as usize)
+            .map(|len| "a".repeat(len))
+            .collect::<Vec<String>>()
+            .join(" ");
+        let result = word_lengths(&words, 2).await.iter().sum();
+        unsafe { done(result) }
+    };
+    unsafe { *TASK.0.get() = Some(Box::pin(task)) };
+    poll();
+}
+
+/// A module of which every public function is split into `geometry`.
+#[wasm_split(geometry)]
+mod geometry {
+    pub fn area(width: u32, height: u32) -> u32 {
+        scale(width) * height
+    }
+
+    pub fn perimeter(width: u32, height: u32) -> u32 {
+        scale(width + height)
+    }
+
+    #[inline(never)]
+    fn scale(x: u32) -> u32 {
+        core::hint::black_box(x) * 2
+    }
+}
+
+/// Calls both functions of `geometry`: `2 * n * n + 4 * n`.
+#[no_mangle]
+pub extern "C" fn run_module(n: u32) {
+    let task = async move {
+        let result = geometry::area(n, n).await + geometry::perimeter(n, n).await;
+        unsafe { done(result) }
+    };
+    unsafe { *TASK.0.get() = Some(Box::pin(task)) };
+    poll();
+}
+
+/// Calls both instantiations of `sum_as` and `apply_twice`: `n * (n - 1) +
+/// 3 * n`.
+#[no_mangle]
+pub extern "C" fn run_generic(n: u32) {
+    let task = async move {
+        let values = (0..n).collect::<Vec<_>>();
+        let narrow = sum_as::<u32>(&values).await;
+        let wide = sum_as::<u64>(&values).await;
+        let offset = core::hint::black_box(n);
+        let applied = apply_twice(n, move |x| x + offset).await;
+        unsafe { done(narrow + wide as u32 + applied) }
+    };
+    unsafe { *TASK.0.get() = Some(Box::pin(task)) };
+    poll();
+}
+
+/// Calls `heaviest` with the points `(i, 2 * i)` below `n`, weighed by `x +
+/// y`: `3 * (n - 1)` for the heaviest `+ 1000 *` the calls `+ 3 * n * (n -
+/// 1) / 2` for the total weight `+ 10000 *` whether the result points into
+/// the slice.
+#[no_mangle]
+pub extern "C" fn run_borrowing(n: u32) {
+    let task = async move {
+        let points = (0..n).map(|i| Point { x: i, y: 2 * i }).collect::<Vec<_>>();
+        let mut state = (0, 0);
+        let weight = |point: &Point| point.x + point.y;
+        let Some(point) = heaviest(&points, &weight, &mut state).await else {
+            core::arch::wasm32::unreachable();
+        };
+        let borrowed = core::ptr::eq(point, points.last().unwrap());
+        let (calls, total) = state;
+        unsafe { done(point.x + point.y + 1000 * calls + total + 10000 * borrowed as u32) }
+    };
+    unsafe { *TASK.0.get() = Some(Box::pin(task)) };
+    poll();
+}
+
+#[wasm_split(tally, sync)]
+fn tally(n: u32) -> u32 {
+    (1..=core::hint::black_box(n)).sum()
+}
+
+/// Calls `tally` synchronously before and after awaiting a call that loads
+/// it: `n * (n + 1) + 1000` if the first call failed as it was not loaded.
+#[no_mangle]
+pub extern "C" fn run_sync(n: u32) {
+    let task = async move {
+        let not_loaded = try_tally_sync(n) == Err(wasm_split::LoadError::NotLoaded);
+        let result = tally(n).await + tally_sync(n) + 1000 * not_loaded as u32;
+        unsafe { done(result) }
+    };
+    unsafe { *TASK.0.get() = Some(Box::pin(task)) };
+    poll();
+}
+
+static TALLY_INITS: AtomicU32 = AtomicU32::new(0);
+
+#[cfg(not(feature = "table-only"))]
+#[wasm_split::on_load(group = "tally")]
+fn init_tally() {
+    TALLY_INITS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Calls `tally` twice, which runs the hook of its module once, before the
+/// first call returns: `n * (n + 1) + 1000` times the runs of the hook, plus
+/// `10000` if it ran before the first call.
+#[no_mangle]
+pub extern "C" fn run_on_load(n: u32) {
+    let task = async move {
+        let before = TALLY_INITS.load(Ordering::Relaxed);
+        let result = tally(n).await + tally(n).await;
+        let inits = TALLY_INITS.load(Ordering::Relaxed);
+        unsafe { done(result + 1000 * inits + 10000 * before) }
+    };
+    unsafe { *TASK.0.get() = Some(Box::pin(task)) };
+    poll();
+}
+
+/// Checksums `n` bytes eagerly and then in `verify`: twice the Adler-32 of
+/// `0, 1, ..., n - 1`.
+#[no_mangle]
+pub extern "C" fn run_checksum(n: u32) {
+    let data = (0..n).map(|i| i as u8).collect::<Vec<u8>>();
+    let eager = checksum::adler32(core::hint::black_box(&data));
+    let task = async move {
+        let result = eager.wrapping_add(verify(data).await);
+        unsafe { done(result) }
+    };
+    unsafe { *TASK.0.get() = Some(Box::pin(task)) };
+    poll();
+}
+
+/// Calls `tally`, unloads its module and calls it again, which loads it anew
+/// and so runs its hook again, and then reloads it and calls it once more:
+/// `3 * n * (n + 1) / 2 + 1000 *` the runs of the hook, plus `10000` if the
+/// module was not loaded once unloaded.
+#[no_mangle]
+pub extern "C" fn run_unloading(n: u32) {
+    let task = async move {
+        let mut result = tally(n).await;
+        let unloaded = wasm_split::unload("tally") && !wasm_split::is_loaded("tally");
+        result += tally(n).await;
+        let reloaded = wasm_split::reload("tally").await.is_ok();
+        result += tally(n).await * reloaded as u32;
+        let inits = TALLY_INITS.load(Ordering::Relaxed);
+        unsafe { done(result + 1000 * inits + 10000 * unloaded as u32) }
+    };
+    unsafe { *TASK.0.get() = Some(Box::pin(task)) };
+    poll();
+}
+
+/// Counts the primes below `n` with a sieve, which only `run_entry` reaches
+/// and thus goes into the chunk of the `worker` entry in the tests.
+#[inline(never)]
+fn count_primes(n: u32) -> u32 {
+    let n = n as usize;
+    let mut composite = alloc::vec![false; n.max(2)];
+    let mut count = 0;
+    for i in 2..n {
+        if composite[i] {
+            continue;
+        }
+        count += 1;
+        for multiple in (i * i..n).step_by(i) {
+            composite[multiple] = true;
+        }
+    }
+    count
+}
+
+/// Counts the primes below `n`, as the export that a worker entry calls.
+#[no_mangle]
+pub extern "C" fn run_entry(n: u32) {
+    unsafe { done(count_primes(core::hint::black_box(n))) }
+}
+
+/// Loads `first` and then `details`: `2 * (n + 1)`.
+#[no_mangle]
+pub extern "C" fn run_nested(n: u32) {
+    let task = async move {
+        let result = with_details(n).await.await;
+        unsafe { done(result) }
+    };
+    unsafe { *TASK.0.get() = Some(Box::pin(task)) };
+    poll();
+}
+
+/// Calls `quadruple` through the pointer of the main module before loading
+/// `second`, and then `quadrupled`: `8 * n + 1000 *` whether the first call
+/// loaded `second`.
+#[no_mangle]
+pub extern "C" fn run_lazy_pointer(n: u32) {
+    let task = async move {
+        let quadruple = core::hint::black_box(QUADRUPLE)(n);
+        let loaded = wasm_split::is_loaded("second");
+        let result = quadruple + quadrupled(n).await + 1000 * loaded as u32;
+        unsafe { done(result) }
+    };
+    unsafe { *TASK.0.get() = Some(Box::pin(task)) };
+    poll();
+}
+
+/// Starts loading `second` by polling a call of it once, from the export
+/// itself rather than from a task, as the start function of an app might.
+#[no_mangle]
+pub extern "C" fn prefetch(n: u32) {
+    let waker = unsafe { Waker::from_raw(RawWaker::new(core::ptr::null(), &VTABLE)) };
+    let _ = pin!(second(n)).poll(&mut Context::from_waker(&waker));
+}
+
+#[no_mangle]
+pub extern "C" fn poll() {
+    let waker = unsafe { Waker::from_raw(RawWaker::new(core::ptr::null(), &VTABLE)) };
+    if let Some(task) = unsafe { &mut *TASK.0.get() } {
+        if task
+            .as_mut()
+            .poll(&mut Context::from_waker(&waker))
+            .is_ready()
+        {
+            unsafe { *TASK.0.get() = None };
+        }
+    }
+}
@@ -0,0 +1,65 @@
+# A split point that takes an `externref` and uses tables of its own besides
+# the indirect function table, as code built with reference types does: it
+# grows the `externrefs` table with its argument and puts `triple`, through
+# its slot of the indirect function table, into `callbacks`, which `run`
+# calls it through. The linker puts the indirect function table after those
+# tables. `run(n)` calls `done(1000 * (size of externrefs) + 3 * n)`.
+#
+# Assembled and linked by build.py, without `ref.func`, which the `llvm-mc` of
+# LLVM 14 does not assemble.
+
+	.functype	done (i32) -> ()
+	.import_module	done, env
+	.import_name	done, done
+	.functype	__wasm_split_00keep00_import_00112233445566778899aabbccddeeff_keep (externref, i32) -> (i32)
+	.import_module	__wasm_split_00keep00_import_00112233445566778899aabbccddeeff_keep, env
+	.import_name	__wasm_split_00keep00_import_00112233445566778899aabbccddeeff_keep, __wasm_split_00keep00_import_00112233445566778899aabbccddeeff_keep
+
+	.tabletype	__indirect_function_table, funcref
+	.tabletype	externrefs, externref
+externrefs:
+
+	.tabletype	callbacks, funcref, 1
+callbacks:
+
+	.section	.text.triple,"",@
+	.type	triple,@function
+triple:
+	.functype	triple (i32) -> (i32)
+	local.get	0
+	i32.const	3
+	i32.mul
+	end_function
+
+	.section	.text.keep,"",@
+	.globl	__wasm_split_00keep00_export_00112233445566778899aabbccddeeff_keep
+	.type	__wasm_split_00keep00_export_00112233445566778899aabbccddeeff_keep,@function
+__wasm_split_00keep00_export_00112233445566778899aabbccddeeff_keep:
+	.functype	__wasm_split_00keep00_export_00112233445566778899aabbccddeeff_keep (externref, i32) -> (i32)
+	i32.const	0
+	i32.const	triple
+	table.get	__indirect_function_table
+	table.set	callbacks
+	local.get	0
+	i32.const	1
+	table.grow	externrefs
+	local.get	1
+	i32.add
+	end_function
+
+	.section	.text.run,"",@
+	.globl	run
+	.type	run,@function
+run:
+	.functype	run (i32) -> ()
+	ref.null_extern
+	local.get	0
+	call	__wasm_split_00keep00_import_00112233445566778899aabbccddeeff_keep
+	i32.const	0
+	call_indirect	callbacks, (i32) -> (i32)
+	table.size	externrefs
+	i32.const	1000
+	i32.mul
+	i32.add
+	call	done
+	end_function
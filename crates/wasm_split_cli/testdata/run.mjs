@@ -0,0 +1,344 @@
+// Runs the split output of `no_std_app` in Node, and prints the result of
+// `run(n)`, or of another export with the same signature:
+// `node run.mjs <output directory> <n> [export]`.
+//
+// With `WEB_WORKERS` set, `Worker` is provided on top of `worker_threads`,
+// which run this script to set up the globals of a web worker, and the run
+// fails unless some call ran on the worker.
+//
+// With `BASE_URL` set, the loader reads the base URL of chunks from
+// `globalThis.WASM_SPLIT_BASE_URL`, as a page would set it.
+//
+// With `INSPECT` set, prints what `window.__WASM_SPLIT__` reports about the
+// loader once the export is done, as JSON, instead of the result.
+//
+// With `COUNT_FETCHES` set, prints the number of requests for each file, as
+// JSON, after the result.
+//
+// With `AWAIT_AUTO_CHUNKS` set, waits until the loader has loaded the chunks
+// of `auto-split` crates before calling the export.
+//
+// With `ENTRY` set, awaits `loadEntry` of the loader with its value, as an
+// entry point of the `[entries]` config, such as a worker, would before
+// calling the export.
+//
+// With `COMPILE_MEASURES` set, prints the details of the loader's
+// `wasm-split:chunk-compile` performance measures, as JSON, after the result.
+//
+// With `NAVIGATION_MEASURES` set, prints the details of the loader's
+// `wasm-split:navigation` performance measures, as JSON, after the result.
+//
+// With `PHASE_MEASURES` set, prints the loader's `wasm-split:<chunk>:<phase>`
+// performance measures, as JSON, after the result: the name, start and end of
+// each, and its details.
+//
+// With `JS_ENTRY` set to `<module>.<function>`, prints the result of calling
+// that function of the JS entry points of `--emit-js-entries` with `n` instead
+// of the export.
+//
+// With `FETCH_DELAY_MS` set, requests for chunks respond after that delay,
+// unless their signal aborts them in the meantime, and the most of them in
+// flight at once is printed after the result.
+//
+// With `SECOND_LOADER` set, imports another copy of the loader under another
+// URL once the export is done, as an app that remounts would, and prints
+// after the result, as JSON: whether it shares the main instance of the
+// first copy, the number of requests for each chunk that the first copy
+// loaded once the second loaded them too, that number once the second
+// loaded them again after `reset`, and the reserved table slots that can be
+// allocated after freeing one that was allocated before `reset`.
+//
+// With `PAGES` set to the query strings of pages separated by commas, such as
+// `?wasm_split=off,`, first runs the export on each of those pages in turn,
+// with a `document`, `location` and `localStorage` as in a browser and a copy
+// of the loader of its own, and prints after the result, as JSON, for each
+// page: whether it ran the fallback according to `inspect`, the chunks that
+// the loader preloaded while idle before the export was called, the modules
+// that the page requested, the warnings of the loader, the result, and the
+// local storage once the export was done.
+//
+// With `NODE_FETCH` set, leaves Node's own `fetch` in place, which the loader
+// of `--target node` reads files without.
+//
+// With `CUSTOM_FETCH` set, configures the loader with a `fetch` of its own,
+// which fails requests without the `Authorization` header and credentials
+// that it also configures, and prints the number of its requests after the
+// result.
+//
+// With `BUNDLED_ASSETS` set, imports the module of `--emit-bundler-assets`,
+// which gives the loader the URLs of the chunks, before instantiating the main
+// module.
+//
+// With `CLASSIC` set, runs the loader of `--target no-modules` as a classic
+// script, with its URL in `globalThis.WASM_SPLIT_SCRIPT_URL`, and uses the
+// `__wasm_split` global it defines.
+
+import { createHash } from "node:crypto";
+import { readFileSync } from "node:fs";
+import { runInThisContext } from "node:vm";
+import { pathToFileURL, fileURLToPath } from "node:url";
+import {
+  Worker as NodeWorker,
+  isMainThread,
+  parentPort,
+  workerData,
+} from "node:worker_threads";
+
+// As in browsers that only support compiling from a buffer.
+if (process.env.NO_COMPILE_STREAMING) delete WebAssembly.compileStreaming;
+// To check that chunks are only ever compiled while streaming. Node itself
+// compiles its HTTP parser with `WebAssembly.compile`.
+if (process.env.NO_COMPILE) {
+  const compile = WebAssembly.compile;
+  WebAssembly.compile = (bytes) => {
+    if (new Error().stack.includes("__wasm_split.js")) {
+      throw new Error("The loader compiled a chunk from a buffer");
+    }
+    return compile(bytes);
+  };
+}
+
+// Number of requests for chunks, rather than the main module, that fail as
+// without a network connection.
+let failingFetches = Number(process.env.FAILING_FETCHES ?? 0);
+const fetchCounts = {};
+const fetchDelayMs = Number(process.env.FETCH_DELAY_MS ?? 0);
+let chunkFetchesInFlight = 0;
+let maxChunkFetchesInFlight = 0;
+
+// Fails requests whose body does not match their `integrity`, as browsers do.
+const fileFetch = async (url, { integrity, signal } = {}) => {
+  const fileUrl = new URL(url);
+  fileUrl.search = "";
+  const type = fileUrl.pathname.endsWith(".wasm") ? "application/wasm" : "application/json";
+  const isChunk = type === "application/wasm" && !fileUrl.pathname.endsWith("/main.wasm");
+  const file = fileUrl.pathname.split("/").pop();
+  fetchCounts[file] = (fetchCounts[file] ?? 0) + 1;
+  if (isChunk && failingFetches > 0) {
+    --failingFetches;
+    throw new TypeError("Failed to fetch");
+  }
+  if (isChunk && fetchDelayMs > 0) {
+    maxChunkFetchesInFlight = Math.max(maxChunkFetchesInFlight, ++chunkFetchesInFlight);
+    await new Promise((resolve) => setTimeout(resolve, fetchDelayMs));
+    --chunkFetchesInFlight;
+    signal?.throwIfAborted();
+  }
+  const body = readFileSync(fileURLToPath(fileUrl));
+  if (integrity && integrity !== "sha384-" + createHash("sha384").update(body).digest("base64")) {
+    throw new TypeError(`Integrity mismatch for ${url}`);
+  }
+  const response = new Response(body, {
+    headers: { "content-type": type },
+  });
+  // Which manifests resolve chunk URLs against.
+  Object.defineProperty(response, "url", { value: String(url) });
+  return response;
+};
+if (!process.env.NODE_FETCH) globalThis.fetch = fileFetch;
+
+let workerReplies = 0;
+const workers = [];
+
+class Worker {
+  constructor(url) {
+    this.worker = new NodeWorker(new URL(import.meta.url), { workerData: url.href });
+    workers.push(this.worker);
+    this.worker.on("message", (data) => {
+      ++workerReplies;
+      this.onmessage({ data });
+    });
+    this.worker.on("error", (e) => this.onerror(e));
+  }
+
+  postMessage(message, transfer) {
+    this.worker.postMessage(message, transfer);
+  }
+
+  terminate() {
+    this.worker.terminate();
+  }
+}
+
+// Instantiates the main module through `loader`, with imports that resolve
+// `done` with the result of an export.
+async function instantiate(loader) {
+  let resolveDone;
+  const done = new Promise((resolve) => (resolveDone = resolve));
+  const instance = await loader.instantiateMain({
+    env: {
+      wake: () => queueMicrotask(() => instance.exports.poll()),
+      done: (result) => resolveDone(result),
+    },
+  });
+  return { instance, done };
+}
+
+if (!isMainThread) {
+  globalThis.postMessage = (message, transfer) => parentPort.postMessage(message, transfer);
+  await import(workerData);
+  // As in browsers, where messages are queued until the worker's script ran.
+  parentPort.on("message", (data) => globalThis.onmessage({ data }));
+} else {
+  if (process.env.WEB_WORKERS) globalThis.Worker = Worker;
+  if (process.env.BASE_URL) globalThis.WASM_SPLIT_BASE_URL = process.env.BASE_URL;
+
+  const [dir, n, name = "run"] = process.argv.slice(2);
+  const loaderUrl = pathToFileURL(`${dir}/__wasm_split.js`);
+  let pages;
+  if (process.env.PAGES !== undefined) {
+    const storage = new Map();
+    globalThis.localStorage = {
+      getItem: (key) => storage.get(key) ?? null,
+      setItem: (key, value) => storage.set(key, String(value)),
+      removeItem: (key) => storage.delete(key),
+    };
+    globalThis.document = { querySelector: () => null };
+    const { info, warn } = console;
+    pages = [];
+    for (const [i, search] of process.env.PAGES.split(",").entries()) {
+      globalThis.location = new URL(`index.html${search}`, loaderUrl);
+      const warnings = [];
+      console.info = () => {};
+      console.warn = (message) => warnings.push(message);
+      const before = { ...fetchCounts };
+      const page = await import(`${loaderUrl.href}?page=${i}`);
+      const { instance, done } = await instantiate(page);
+      // Idle time, in which the loader preloads pinned chunks.
+      await new Promise((resolve) => setTimeout(resolve, 50));
+      const chunksIn = (state) =>
+        page
+          .inspect()
+          .chunks.filter((chunk) => chunk.kind !== "main" && chunk.state === state)
+          .map((chunk) => chunk.name);
+      while (chunksIn("loading").length > 0) {
+        await new Promise((resolve) => setTimeout(resolve, 5));
+      }
+      const preloaded = chunksIn("loaded");
+      instance.exports[name](Number(n));
+      const result = await done;
+      const modules = Object.keys(fetchCounts).filter(
+        (file) => file.endsWith(".wasm") && fetchCounts[file] !== before[file],
+      );
+      pages.push({
+        fallback: page.inspect().fallback,
+        preloaded,
+        modules,
+        warnings,
+        result,
+        storage: Object.fromEntries(storage),
+      });
+      // As the next page starts without the instance of this one.
+      page.reset();
+    }
+    console.info = info;
+    console.warn = warn;
+    delete globalThis.localStorage;
+    delete globalThis.document;
+    delete globalThis.location;
+  }
+  let loader;
+  if (process.env.CLASSIC) {
+    globalThis.WASM_SPLIT_SCRIPT_URL = loaderUrl.href;
+    runInThisContext(readFileSync(loaderUrl, "utf8"), { filename: loaderUrl.href });
+    loader = globalThis.__wasm_split;
+  } else {
+    loader = await import(loaderUrl);
+  }
+  if (process.env.BUNDLED_ASSETS) {
+    await import(pathToFileURL(`${dir}/wasm-split-assets.js`));
+  }
+  let customFetches = 0;
+  if (process.env.CUSTOM_FETCH) {
+    loader.configure({
+      fetch: (url, init) => {
+        ++customFetches;
+        if (init.headers?.authorization !== "Bearer token" || init.credentials !== "include") {
+          return Promise.reject(new TypeError(`Unauthorized request for ${url}`));
+        }
+        return fileFetch(url, init);
+      },
+      headers: { Authorization: "Bearer token", "X-Removed": "1" },
+      credentials: "include",
+    });
+    loader.configure({ headers: { "x-removed": null } });
+  }
+  const { instance, done } = await instantiate(loader);
+  if (process.env.AWAIT_AUTO_CHUNKS) {
+    const [inspector] = globalThis.__WASM_SPLIT__.loaders;
+    const pending = () =>
+      inspector.inspect().chunks.some((chunk) => chunk.auto && chunk.state !== "loaded");
+    while (pending()) await new Promise((resolve) => setTimeout(resolve, 5));
+  }
+  if (process.env.ENTRY) await loader.loadEntry(process.env.ENTRY);
+  let result;
+  if (process.env.JS_ENTRY) {
+    const [module, entry] = process.env.JS_ENTRY.split(".");
+    const entries = await import(pathToFileURL(`${dir}/wasm-split-entries/${module}.js`));
+    result = await entries[entry](Number(n));
+  } else {
+    instance.exports[name](Number(n));
+    result = await done;
+  }
+  let secondLoader;
+  if (process.env.SECOND_LOADER) {
+    const copy = await import(`${loaderUrl.href}?copy=2`);
+    const shared = (await copy.instantiateMain()) === instance;
+    const loaded = loader.loadedChunks();
+    const fileCounts = () =>
+      Object.fromEntries(
+        loaded.map((name) => {
+          const { file } = copy.inspect().chunks.find((chunk) => chunk.name === name);
+          return [file, fetchCounts[file]];
+        }),
+      );
+    await Promise.all(loaded.map((name) => copy.preload(name)));
+    const fetches = fileCounts();
+    const slot = loader.allocateTableSlot();
+    copy.reset();
+    await Promise.all(loaded.map((name) => copy.preload(name)));
+    const refetches = fileCounts();
+    copy.freeTableSlot(slot);
+    const slots = [];
+    for (let next; (next = copy.allocateTableSlot()) !== undefined; ) slots.push(next);
+    secondLoader = { shared, fetches, refetches, slots };
+  }
+  if (process.env.WEB_WORKERS && workerReplies === 0) {
+    throw new Error("No call ran on the worker");
+  }
+  if (process.env.INSPECT) {
+    const [inspector] = globalThis.__WASM_SPLIT__.loaders;
+    console.log(JSON.stringify(inspector.inspect()));
+  } else {
+    console.log(result);
+  }
+  if (process.env.SECOND_LOADER) console.log(JSON.stringify(secondLoader));
+  if (pages) console.log(JSON.stringify(pages));
+  if (process.env.CUSTOM_FETCH) console.log(customFetches);
+  if (process.env.COUNT_FETCHES) console.log(JSON.stringify(fetchCounts));
+  if (fetchDelayMs > 0) console.log(maxChunkFetchesInFlight);
+  if (process.env.COMPILE_MEASURES) {
+    const measures = performance.getEntriesByName("wasm-split:chunk-compile");
+    console.log(JSON.stringify(measures.map((measure) => measure.detail)));
+  }
+  if (process.env.NAVIGATION_MEASURES) {
+    const measures = performance.getEntriesByName("wasm-split:navigation");
+    console.log(JSON.stringify(measures.map((measure) => measure.detail)));
+  }
+  if (process.env.PHASE_MEASURES) {
+    const measures = performance
+      .getEntriesByType("measure")
+      .filter((measure) => measure.detail?.phase !== undefined);
+    console.log(
+      JSON.stringify(
+        measures.map(({ name, startTime, duration, detail }) => ({
+          name,
+          start: startTime,
+          end: startTime + duration,
+          detail,
+        })),
+      ),
+    );
+  }
+  for (const worker of workers) worker.terminate();
+}
@@ -0,0 +1,52 @@
+// Runs the split output of `closure_app`, after running wasm-bindgen on its
+// main module: `node run_closure_app.mjs <wasm-bindgen output directory>`.
+// Prints the results of calling the closures created by the split functions,
+// of the async split function, and of the task that it spawned. With
+// `panic` after the directory, calls the split function that panics instead,
+// and prints what the panic hook reported. Chunks are served with
+// `CHUNK_CONTENT_TYPE` if set, e.g. to have the loader compile them from
+// their bytes rather than while they download.
+
+import { readFileSync } from "node:fs";
+import { pathToFileURL, fileURLToPath } from "node:url";
+
+globalThis.fetch = async (url) => {
+  const fileUrl = new URL(url);
+  fileUrl.search = "";
+  const type = fileUrl.pathname.endsWith(".wasm")
+    ? (process.env.CHUNK_CONTENT_TYPE ?? "application/wasm")
+    : "application/json";
+  return new Response(readFileSync(fileURLToPath(fileUrl)), {
+    headers: { "content-type": type },
+  });
+};
+
+let stored;
+globalThis.store_callback = (callback) => (stored = callback);
+globalThis.call_now = (callback, value) => callback(value);
+globalThis.double_later = (value) =>
+  new Promise((resolve) => setTimeout(() => resolve(value * 2), 0));
+let reported;
+globalThis.report_total = (value) => (reported = value);
+
+const [dir, mode] = process.argv.slice(2);
+const app = await import(pathToFileURL(`${dir}/main.js`));
+app.initSync({ module: readFileSync(`${dir}/main_bg.wasm`) });
+if (mode === "panic") {
+  const errors = [];
+  console.error = (message) => errors.push(message);
+  // The trap after the hook is thrown from the queue of the futures rather
+  // than rejecting the promise.
+  const trapped = new Promise((resolve) => process.once("uncaughtException", resolve));
+  app.run_check_positive(-2);
+  await trapped;
+  console.log(errors.join("\n"));
+  process.exit(0);
+}
+await app.register(7);
+const results = [stored(1), stored(2), await app.run_scale(3, 5)];
+results.push(await app.run_sum_doubled(new Uint32Array([1, 2, 3])));
+while (reported === undefined) await new Promise((resolve) => setTimeout(resolve, 1));
+results.push(reported);
+console.log(results.join(" "));
+
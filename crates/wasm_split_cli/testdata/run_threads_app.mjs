@@ -0,0 +1,44 @@
+// Runs the split output of `threads_app`, written with `--target node`, on
+// the main thread and on a worker that share its memory, each with a main
+// module and chunks of its own, as an app with threads does, and prints the
+// results of `run(2)` on the main thread, then `run(5)` on the worker and
+// `run(3)` on the main thread again: `node run_threads_app.mjs <output
+// directory>`. Each thread preloads the chunk of `add` before calling `run`,
+// which calls it synchronously, and fails if the chunk was loaded already.
+
+import { pathToFileURL } from "node:url";
+import { Worker, isMainThread, parentPort, workerData } from "node:worker_threads";
+
+async function instantiate(dir, memory) {
+  const loader = await import(pathToFileURL(`${dir}/__wasm_split.js`));
+  let resolveDone;
+  const instance = await loader.instantiateMain({
+    env: { memory, done: (result) => resolveDone(result) },
+  });
+  if (loader.isLoaded("add")) throw new Error("add was loaded before the preload");
+  await loader.preload("add");
+  return (n) => {
+    const done = new Promise((resolve) => (resolveDone = resolve));
+    instance.exports.run(n);
+    return done;
+  };
+}
+
+if (!isMainThread) {
+  const { dir, memory, n } = workerData;
+  const run = await instantiate(dir, memory);
+  parentPort.postMessage(await run(n));
+} else {
+  const [dir] = process.argv.slice(2);
+  const memory = new WebAssembly.Memory({ initial: 2, maximum: 17, shared: true });
+  const run = await instantiate(dir, memory);
+  const results = [await run(2)];
+  const worker = new Worker(new URL(import.meta.url), { workerData: { dir, memory, n: 5 } });
+  results.push(await new Promise((resolve, reject) => {
+    worker.on("message", resolve);
+    worker.on("error", reject);
+  }));
+  await worker.terminate();
+  results.push(await run(3));
+  console.log(results.join(","));
+}
@@ -0,0 +1,38 @@
+# A split point in an app built with the `rust-loader` feature of wasm_split,
+# whose runtime exports `__wasm_split_rust_loader` and imports nothing from
+# the loader script besides the split point. `run(n)` calls `done(3 * n)`.
+#
+# Assembled and linked by build.py.
+
+	.functype	done (i32) -> ()
+	.import_module	done, env
+	.import_name	done, done
+	.functype	__wasm_split_00triple00_import_00112233445566778899aabbccddeeff_triple (i32) -> (i32)
+	.import_module	__wasm_split_00triple00_import_00112233445566778899aabbccddeeff_triple, env
+	.import_name	__wasm_split_00triple00_import_00112233445566778899aabbccddeeff_triple, __wasm_split_00triple00_import_00112233445566778899aabbccddeeff_triple
+
+	.section	.rodata.__wasm_split_rust_loader,"",@
+	.globl	__wasm_split_rust_loader
+__wasm_split_rust_loader:
+	.int8	1
+	.size	__wasm_split_rust_loader, 1
+
+	.section	.text.triple,"",@
+	.globl	__wasm_split_00triple00_export_00112233445566778899aabbccddeeff_triple
+	.type	__wasm_split_00triple00_export_00112233445566778899aabbccddeeff_triple,@function
+__wasm_split_00triple00_export_00112233445566778899aabbccddeeff_triple:
+	.functype	__wasm_split_00triple00_export_00112233445566778899aabbccddeeff_triple (i32) -> (i32)
+	local.get	0
+	i32.const	3
+	i32.mul
+	end_function
+
+	.section	.text.run,"",@
+	.globl	run
+	.type	run,@function
+run:
+	.functype	run (i32) -> ()
+	local.get	0
+	call	__wasm_split_00triple00_import_00112233445566778899aabbccddeeff_triple
+	call	done
+	end_function
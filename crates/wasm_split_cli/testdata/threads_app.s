@@ -0,0 +1,42 @@
+# A split point that adds to a counter in shared memory with an atomic
+# instruction, as code run by several threads, e.g. with rayon, does. The
+# memory is imported, as wasm-bindgen's threads support links it, so that
+# the main module instantiated on every thread shares it. `run(n)` adds `n`
+# and calls `done` with the new total.
+#
+# Assembled and linked by build.py.
+
+	.functype	done (i32) -> ()
+	.import_module	done, env
+	.import_name	done, done
+	.functype	__wasm_split_00add00_import_00112233445566778899aabbccddeeff_add (i32) -> (i32)
+	.import_module	__wasm_split_00add00_import_00112233445566778899aabbccddeeff_add, env
+	.import_name	__wasm_split_00add00_import_00112233445566778899aabbccddeeff_add, __wasm_split_00add00_import_00112233445566778899aabbccddeeff_add
+
+	.section	.bss.counter,"",@
+	.p2align	2
+counter:
+	.int32	0
+	.size	counter, 4
+
+	.section	.text.add,"",@
+	.globl	__wasm_split_00add00_export_00112233445566778899aabbccddeeff_add
+	.type	__wasm_split_00add00_export_00112233445566778899aabbccddeeff_add,@function
+__wasm_split_00add00_export_00112233445566778899aabbccddeeff_add:
+	.functype	__wasm_split_00add00_export_00112233445566778899aabbccddeeff_add (i32) -> (i32)
+	i32.const	counter
+	local.get	0
+	i32.atomic.rmw.add	0
+	local.get	0
+	i32.add
+	end_function
+
+	.section	.text.run,"",@
+	.globl	run
+	.type	run,@function
+run:
+	.functype	run (i32) -> ()
+	local.get	0
+	call	__wasm_split_00add00_import_00112233445566778899aabbccddeeff_add
+	call	done
+	end_function
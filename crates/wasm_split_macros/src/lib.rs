@@ -1,15 +1,1025 @@
 use proc_macro::TokenStream;
 
 use digest::Digest;
-use quote::{format_ident, quote};
-use syn::{parse_macro_input, Ident, ItemFn, Signature};
+use proc_macro2::{Group, Spacing, TokenTree};
+use quote::{format_ident, quote, ToTokens};
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_macro_input, parse_quote,
+    punctuated::Punctuated,
+    Attribute, FnArg, Ident, ImplItem, Item, ItemFn, ItemImpl, ItemMod, ItemStatic, LitInt, LitStr,
+    Meta, Signature, StaticMutability, Token, Type,
+};
+
+/// Load priority of a split module, from most to least urgent.
+#[derive(Debug, Clone, Copy)]
+enum Priority {
+    Critical,
+    High,
+    Low,
+}
+
+impl Priority {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Critical => "critical",
+            Self::High => "high",
+            Self::Low => "low",
+        }
+    }
+}
+
+impl Parse for Priority {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let value: LitStr = input.parse()?;
+        match value.value().as_str() {
+            "critical" => Ok(Self::Critical),
+            "high" => Ok(Self::High),
+            "low" => Ok(Self::Low),
+            _ => Err(syn::Error::new(
+                value.span(),
+                "expected one of \"critical\", \"high\", \"low\"",
+            )),
+        }
+    }
+}
+
+/// Arguments to `#[wasm_split(module_name, option = value, ...)]`.
+struct Args {
+    module_ident: Ident,
+    priority: Option<Priority>,
+    /// Number of indirect function table slots to reserve for callbacks that
+    /// the module registers at runtime.
+    table_slots: Option<u32>,
+    /// Function in the main module with the same signature, called instead
+    /// of the split function if its module fails to load. The failed load is
+    /// not retried, so later calls go to the fallback right away.
+    fallback: Option<syn::Path>,
+    /// Another split module to put this module's code into, such as the view
+    /// that always uses a data function, to avoid a separate request. The
+    /// module's name remains usable, e.g. with `SplitChunk::new`, as an
+    /// alias of the other.
+    with: Option<Ident>,
+    /// Full path of the router route whose lazy view this function is, which
+    /// the split tool matches against the routes of its config.
+    route: Option<LitStr>,
+    /// Whether the function returns `Result<_, wasm_split::LoadError>`, with
+    /// the error of a failed load, which the next call retries.
+    fallible: bool,
+    /// Whether the module only enhances the app, such as analytics that an
+    /// ad blocker may block, so that calls return `Default::default()` if it
+    /// fails to load, without loading it again.
+    optional: bool,
+    /// Whether calls run on a worker, with arguments and result copied
+    /// over with `wasm_split::Transfer`.
+    worker: bool,
+    /// Whether the function gets synchronous companions, `<name>_sync` and
+    /// `try_<name>_sync`, which call it right away if its module is loaded,
+    /// and panic or return `LoadError::NotLoaded` otherwise.
+    sync: bool,
+    /// Whether the build fails if eager code anchors any code of the
+    /// module's dependencies in the main module.
+    strict: bool,
+    /// Whether a static is a constant table that stays in the split module's
+    /// data, rather than a value that its initializer builds at runtime.
+    data: bool,
+    /// Type of the impl block of a method, added by `#[wasm_split]` on the
+    /// block.
+    self_type: Option<Type>,
+    /// Types that a generic function is split for, as its type parameter,
+    /// each of which gets an import and export pair of its own.
+    types: Vec<Type>,
+}
+
+impl Parse for Args {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        // The module name may be left out if `group = ...` gives it.
+        let module_ident = if input.peek(Ident) && input.peek2(Token![=]) {
+            None
+        } else {
+            Some(input.parse::<Ident>()?)
+        };
+        let mut group = None;
+        let mut args = Args {
+            // Replaced by the group below if left out.
+            module_ident: module_ident
+                .clone()
+                .unwrap_or_else(|| format_ident!("__wasm_split_group")),
+            priority: None,
+            table_slots: None,
+            fallback: None,
+            with: None,
+            route: None,
+            fallible: false,
+            optional: false,
+            worker: false,
+            sync: false,
+            strict: false,
+            data: false,
+            self_type: None,
+            types: Vec::new(),
+        };
+        let mut needs_comma = module_ident.is_some();
+        while !input.is_empty() {
+            if needs_comma {
+                input.parse::<Token![,]>()?;
+                if input.is_empty() {
+                    break;
+                }
+            }
+            needs_comma = true;
+            let key: Ident = input.parse()?;
+            match key.to_string().as_str() {
+                "priority" => {
+                    input.parse::<Token![=]>()?;
+                    args.priority = Some(input.parse()?);
+                }
+                "table_slots" => {
+                    input.parse::<Token![=]>()?;
+                    args.table_slots = Some(input.parse::<LitInt>()?.base10_parse()?);
+                }
+                "fallback" => {
+                    input.parse::<Token![=]>()?;
+                    args.fallback = Some(input.parse()?);
+                }
+                "with" => {
+                    input.parse::<Token![=]>()?;
+                    args.with = Some(input.parse()?);
+                }
+                "group" => {
+                    input.parse::<Token![=]>()?;
+                    let name: LitStr = input.parse()?;
+                    group = Some(name.parse::<Ident>().map_err(|_| {
+                        syn::Error::new(name.span(), "expected the name of a split module")
+                    })?);
+                }
+                "route" => {
+                    input.parse::<Token![=]>()?;
+                    let route: LitStr = input.parse()?;
+                    let value = route.value();
+                    if !value.starts_with('/') || value.contains('\n') {
+                        return Err(syn::Error::new(
+                            route.span(),
+                            "expected a route path starting with `/`",
+                        ));
+                    }
+                    args.route = Some(route);
+                }
+                "types" => {
+                    let content;
+                    syn::parenthesized!(content in input);
+                    args.types = content
+                        .parse_terminated(Type::parse, Token![,])?
+                        .into_iter()
+                        .collect();
+                    if args.types.is_empty() {
+                        return Err(syn::Error::new(key.span(), "expected at least one type"));
+                    }
+                }
+                "fallible" => args.fallible = true,
+                "optional" => args.optional = true,
+                "worker" => args.worker = true,
+                "sync" => args.sync = true,
+                "strict" => args.strict = true,
+                "data" => args.data = true,
+                "__self_type" => {
+                    input.parse::<Token![=]>()?;
+                    args.self_type = Some(input.parse()?);
+                }
+                _ => {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        format!("unknown wasm_split option `{key}`"),
+                    ));
+                }
+            }
+        }
+        if let (true, Some(fallback)) = (args.fallible, &args.fallback) {
+            return Err(syn::Error::new_spanned(
+                fallback,
+                "`fallible` functions return load errors instead of calling a fallback",
+            ));
+        }
+        if let (true, Some(fallback)) = (args.optional, &args.fallback) {
+            return Err(syn::Error::new_spanned(
+                fallback,
+                "`optional` functions return the default value instead of calling a fallback",
+            ));
+        }
+        if args.optional && args.fallible {
+            return Err(input.error(
+                "`optional` functions return the default value instead of load errors, \
+                 unlike `fallible` ones",
+            ));
+        }
+        // A group is the module whose chunk holds the code, so with a module
+        // name of its own, the function's module is an alias of it as with
+        // `with`.
+        match (module_ident, group) {
+            (None, None) => {
+                return Err(input.error("expected the name of a split module, or `group = ...`"));
+            }
+            (None, Some(group)) => args.module_ident = group,
+            (Some(_), Some(group)) => {
+                if args.with.is_some() {
+                    return Err(syn::Error::new(
+                        group.span(),
+                        "`group` and `with` both give the module to put the code into",
+                    ));
+                }
+                args.with = Some(group);
+            }
+            (Some(_), None) => {}
+        }
+        Ok(args)
+    }
+}
+
+/// Generates a static placed in the `__wasm_split_meta` custom section,
+/// which the split tool reads to obtain per-module attributes.
+///
+/// Each record is a single line of the form `<module> <key> <value>\n`. The
+/// linker concatenates custom sections of the same name, so records from all
+/// split points end up in a single section of the input module.
+fn metadata_record(module_ident: &Ident, key: &str, value: &str) -> proc_macro2::TokenStream {
+    let record = format!("{module_ident} {key} {value}\n");
+    let len = record.len();
+    let bytes = syn::LitByteStr::new(record.as_bytes(), proc_macro2::Span::call_site());
+    quote! {
+        #[cfg(target_arch = "wasm32")]
+        const _: () = {
+            #[link_section = "__wasm_split_meta"]
+            static __WASM_SPLIT_META: [u8; #len] = *#bytes;
+        };
+    }
+}
+
+/// Generates a static placed in the `__wasm_split_signatures` custom section,
+/// with a `<export> <hash>\n` record of the hash of the Rust signature of a
+/// split function, which the split tool checks chunks of other builds
+/// against, as types that differ in Rust may be the same in wasm.
+fn signature_record(export_ident: &Ident, signature: &str) -> proc_macro2::TokenStream {
+    let hash = base16::encode_lower(&sha2::Sha256::digest(signature)[..8]);
+    let record = format!("{export_ident} {hash}\n");
+    let len = record.len();
+    let bytes = syn::LitByteStr::new(record.as_bytes(), proc_macro2::Span::call_site());
+    quote! {
+        #[cfg(target_arch = "wasm32")]
+        const _: () = {
+            #[link_section = "__wasm_split_signatures"]
+            static __WASM_SPLIT_SIGNATURE: [u8; #len] = *#bytes;
+        };
+    }
+}
 
 #[proc_macro_attribute]
 pub fn wasm_split(args: TokenStream, input: TokenStream) -> TokenStream {
-    let module_ident = parse_macro_input!(args as Ident);
+    if args.is_empty() {
+        return match parse_macro_input!(input as Item) {
+            Item::Impl(item_impl) => split_impl(item_impl),
+            item => syn::Error::new_spanned(
+                item,
+                "#[wasm_split] takes the name of the split module, except on impl blocks",
+            )
+            .to_compile_error(),
+        }
+        .into();
+    }
+    let args_tokens = proc_macro2::TokenStream::from(args.clone());
+    let args = parse_macro_input!(args as Args);
+    match parse_macro_input!(input as Item) {
+        Item::Fn(item_fn) => split_fn(args, item_fn),
+        Item::Static(item_static) => split_static(args, item_static),
+        Item::Mod(item_mod) => split_mod(&args, args_tokens, item_mod),
+        Item::Impl(item_impl) => syn::Error::new_spanned(
+            item_impl.impl_token,
+            "#[wasm_split] on an impl block takes no arguments; put them on its methods",
+        )
+        .to_compile_error(),
+        item => syn::Error::new_spanned(
+            item,
+            "#[wasm_split] applies to functions, statics and modules",
+        )
+        .to_compile_error(),
+    }
+    .into()
+}
+
+/// Splits all functions of an inline module that are visible outside of it,
+/// e.g. those of a feature, into one split module, as if each had
+/// `#[wasm_split]` with the arguments of the module. Functions with an
+/// attribute of their own keep it, such as generic ones with `types(...)`,
+/// while private functions, `const fn`s and those with an ABI of their own
+/// stay as they are, and go into the chunk along with the code that calls
+/// them. Calls between the split functions of the module go through their
+/// split points as well, and so must be awaited.
+fn split_mod(
+    args: &Args,
+    args_tokens: proc_macro2::TokenStream,
+    mut item_mod: ItemMod,
+) -> proc_macro2::TokenStream {
+    let Some((_, items)) = &mut item_mod.content else {
+        return syn::Error::new_spanned(
+            &item_mod,
+            "#[wasm_split] only applies to inline modules, as in `mod feature { ... }`",
+        )
+        .to_compile_error();
+    };
+    if let Some(ty) = args.types.first() {
+        return syn::Error::new_spanned(
+            ty,
+            "`types` applies to single generic functions; put it on the function",
+        )
+        .to_compile_error();
+    }
+    if let Some(fallback) = &args.fallback {
+        return syn::Error::new_spanned(
+            fallback,
+            "`fallback` applies to single functions, as its signature must match",
+        )
+        .to_compile_error();
+    }
+    if args.data {
+        return syn::Error::new_spanned(
+            &item_mod.ident,
+            "`data` applies to statics, whose constant value stays in the split module",
+        )
+        .to_compile_error();
+    }
+    let mut split_any = false;
+    for item in items.iter_mut() {
+        let Item::Fn(item_fn) = item else {
+            continue;
+        };
+        if matches!(item_fn.vis, syn::Visibility::Inherited)
+            || item_fn.sig.constness.is_some()
+            || item_fn.sig.abi.is_some()
+            || item_fn.attrs.iter().any(is_wasm_split_attr)
+        {
+            continue;
+        }
+        item_fn
+            .attrs
+            .push(parse_quote!(#[::wasm_split::wasm_split(#args_tokens)]));
+        split_any = true;
+    }
+    if !split_any {
+        return syn::Error::new_spanned(
+            &item_mod.ident,
+            "#[wasm_split] on a module splits its public functions, but it has none",
+        )
+        .to_compile_error();
+    }
+    item_mod.into_token_stream()
+}
+
+/// Whether `attr` is `#[wasm_split(...)]`, by any path, or within
+/// `#[cfg_attr(...)]`.
+fn is_wasm_split_attr(attr: &Attribute) -> bool {
+    let is_wasm_split = |path: &syn::Path| {
+        path.segments
+            .last()
+            .is_some_and(|segment| segment.ident == "wasm_split")
+    };
+    match &attr.meta {
+        Meta::List(list) if list.path.is_ident("cfg_attr") => list
+            .parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)
+            .is_ok_and(|metas| metas.iter().skip(1).any(|meta| is_wasm_split(meta.path()))),
+        meta => is_wasm_split(meta.path()),
+    }
+}
+
+/// Name of the type alias for the type of the impl block that the functions
+/// generated for a split method use instead of `Self`, which they cannot
+/// refer to as they are nested in the method.
+const SELF_ALIAS: &str = "__WasmSplitSelf";
+
+/// Name of the argument that takes the receiver of a split method.
+const SELF_ARG: &str = "__wasm_split_self";
+
+/// Lets the `#[wasm_split]` methods of an impl block take `self` and use
+/// `Self`, by handing them the type of the block, which the attribute on a
+/// method does not see. The block must not be generic, as split functions
+/// cannot be.
+fn split_impl(mut item_impl: ItemImpl) -> proc_macro2::TokenStream {
+    if !item_impl.generics.params.is_empty() {
+        return syn::Error::new_spanned(
+            &item_impl.generics,
+            "#[wasm_split] methods cannot be in generic impl blocks",
+        )
+        .to_compile_error();
+    }
+    let self_ty = item_impl.self_ty.clone();
+    for item in item_impl.items.iter_mut() {
+        if let ImplItem::Fn(method) = item {
+            for attr in method.attrs.iter_mut() {
+                if let Err(error) = add_self_type(attr, &self_ty) {
+                    return error.to_compile_error();
+                }
+            }
+        }
+    }
+    item_impl.into_token_stream()
+}
+
+/// Appends `__self_type = <self_ty>` to the arguments of a `#[wasm_split]`
+/// attribute, including one within `#[cfg_attr(...)]`.
+fn add_self_type(attr: &mut Attribute, self_ty: &Type) -> syn::Result<()> {
+    let Meta::List(list) = &mut attr.meta else {
+        return Ok(());
+    };
+    if list.path.is_ident("cfg_attr") {
+        let mut metas = list.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)?;
+        // The first one is the predicate.
+        for meta in metas.iter_mut().skip(1) {
+            if let Meta::List(inner) = meta {
+                add_self_type_to_list(inner, self_ty);
+            }
+        }
+        list.tokens = metas.into_token_stream();
+    } else {
+        add_self_type_to_list(list, self_ty);
+    }
+    Ok(())
+}
+
+fn add_self_type_to_list(list: &mut syn::MetaList, self_ty: &Type) {
+    let is_wasm_split = list
+        .path
+        .segments
+        .last()
+        .is_some_and(|segment| segment.ident == "wasm_split");
+    if !is_wasm_split {
+        return;
+    }
+    let ends_with_comma = matches!(
+        list.tokens.clone().into_iter().last(),
+        Some(TokenTree::Punct(punct)) if punct.as_char() == ','
+    );
+    if !ends_with_comma {
+        list.tokens.extend(quote!(,));
+    }
+    list.tokens.extend(quote!(__self_type = #self_ty));
+}
+
+/// Replaces `self` by [`SELF_ARG`] and `Self` by [`SELF_ALIAS`], for the
+/// functions generated for a split method.
+fn replace_self(tokens: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+    let mut tokens = tokens.into_iter().peekable();
+    let mut replaced = Vec::new();
+    while let Some(tree) = tokens.next() {
+        replaced.push(match tree {
+            TokenTree::Group(group) => {
+                let mut new_group = Group::new(group.delimiter(), replace_self(group.stream()));
+                new_group.set_span(group.span());
+                new_group.into()
+            }
+            TokenTree::Ident(ident) if ident == "Self" => {
+                Ident::new(SELF_ALIAS, ident.span()).into()
+            }
+            // Unless it is the start of a path, as in `self::function`.
+            TokenTree::Ident(ident)
+                if ident == "self"
+                    && !matches!(
+                        tokens.peek(),
+                        Some(TokenTree::Punct(punct))
+                            if punct.as_char() == ':' && punct.spacing() == Spacing::Joint
+                    ) =>
+            {
+                Ident::new(SELF_ARG, ident.span()).into()
+            }
+            tree => tree,
+        });
+    }
+    replaced.into_iter().collect()
+}
+
+fn mentions_self(tokens: proc_macro2::TokenStream) -> bool {
+    tokens.into_iter().any(|tree| match tree {
+        TokenTree::Group(group) => mentions_self(group.stream()),
+        TokenTree::Ident(ident) => ident == "Self" || ident == "self",
+        _ => false,
+    })
+}
+
+/// Whether the type of `tokens` may borrow, as a reference or a type with a
+/// lifetime other than `'static`.
+fn borrows(tokens: proc_macro2::TokenStream) -> bool {
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some(tree) = tokens.next() {
+        match tree {
+            TokenTree::Group(group) if borrows(group.stream()) => return true,
+            // A lifetime, such as that of `&'a T`, is checked on its own.
+            TokenTree::Punct(punct)
+                if punct.as_char() == '&'
+                    && !matches!(tokens.peek(), Some(TokenTree::Punct(next)) if next.as_char() == '\'') =>
+            {
+                return true
+            }
+            TokenTree::Punct(punct)
+                if punct.as_char() == '\''
+                    && !matches!(tokens.peek(), Some(TokenTree::Ident(ident)) if ident == "static") =>
+            {
+                return true
+            }
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Splits the `view` method of a lazy route's impl block, such as that of
+/// Leptos' `LazyRoute`, into the given module, so that only the route's data
+/// is loaded with the main module:
+///
+/// ```ignore
+/// #[wasm_split::lazy_route(view_b, route = "/b")]
+/// impl LazyRoute for ViewB {
+///     fn data() -> Self { ... }
+///
+///     async fn view(this: Self) -> AnyView { ... }
+/// }
+/// ```
+///
+/// Takes the same arguments as `#[wasm_split]`, and is equivalent to it on
+/// both the impl block and `view`.
+#[proc_macro_attribute]
+pub fn lazy_route(args: TokenStream, input: TokenStream) -> TokenStream {
+    let args = proc_macro2::TokenStream::from(args);
+    if let Err(error) = syn::parse2::<Args>(args.clone()) {
+        return error.to_compile_error().into();
+    }
+    let mut item_impl = parse_macro_input!(input as ItemImpl);
+    let Some(view) = item_impl.items.iter_mut().find_map(|item| match item {
+        ImplItem::Fn(method) if method.sig.ident == "view" => Some(method),
+        _ => None,
+    }) else {
+        return syn::Error::new_spanned(
+            &item_impl.self_ty,
+            "#[wasm_split::lazy_route] applies to impl blocks with a `view` method",
+        )
+        .to_compile_error()
+        .into();
+    };
+    view.attrs
+        .push(parse_quote!(#[::wasm_split::wasm_split(#args)]));
+    split_impl(item_impl).into()
+}
+
+/// The split module of `#[wasm_split::on_load(...)]`, given by name or as
+/// `group = "..."`, as with `#[wasm_split]`.
+struct OnLoadArgs {
+    module_ident: Ident,
+}
+
+impl Parse for OnLoadArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let key: Ident = input.parse()?;
+        let module_ident = if key == "group" && input.peek(Token![=]) {
+            input.parse::<Token![=]>()?;
+            let name: LitStr = input.parse()?;
+            name.parse::<Ident>()
+                .map_err(|_| syn::Error::new(name.span(), "expected the name of a split module"))?
+        } else {
+            key
+        };
+        input.parse::<Option<Token![,]>>()?;
+        if !input.is_empty() {
+            return Err(input.error("#[wasm_split::on_load] takes only the split module"));
+        }
+        Ok(Self { module_ident })
+    }
+}
+
+/// Runs a function once the chunk of a split module has been instantiated,
+/// before any of its `#[wasm_split]` functions is called, e.g. to register
+/// components or warm caches that the module's code expects:
+///
+/// ```ignore
+/// #[wasm_split::on_load(view_c)]
+/// fn init() { ... }
+///
+/// #[wasm_split::on_load(group = "charts")]
+/// fn register_elements() { ... }
+/// ```
+///
+/// The function is exported under a name that the split tool recognizes, and
+/// moved into the module along with everything it calls. It must neither be
+/// async nor take arguments or return a value.
+#[proc_macro_attribute]
+pub fn on_load(args: TokenStream, input: TokenStream) -> TokenStream {
+    let OnLoadArgs { module_ident } = parse_macro_input!(args as OnLoadArgs);
     let item_fn = parse_macro_input!(input as ItemFn);
+    let sig = &item_fn.sig;
+    if sig.asyncness.is_some()
+        || !sig.inputs.is_empty()
+        || !matches!(sig.output, syn::ReturnType::Default)
+        || !sig.generics.params.is_empty()
+    {
+        return syn::Error::new_spanned(
+            sig,
+            "#[wasm_split::on_load] functions take no arguments and return nothing",
+        )
+        .to_compile_error()
+        .into();
+    }
+    let name = &sig.ident;
+    let unique_identifier = base16::encode_lower(
+        &sha2::Sha256::digest(format!("{name} {span:?}", span = name.span()))[..16],
+    );
+    let export_ident =
+        format_ident!("__wasm_split_00{module_ident}00_onload_{unique_identifier}_{name}");
+    quote! {
+        #item_fn
 
-    let name = &item_fn.sig.ident;
+        const _: () = {
+            #[no_mangle]
+            pub extern "C" fn #export_ident() {
+                #name()
+            }
+        };
+    }
+    .into()
+}
+
+/// Moves a lazily initialized static into the split module: its type becomes
+/// `wasm_split::SplitLazy`, and its initializer expression the body of a
+/// split function that runs when the value is first requested.
+///
+/// With `data`, the initializer is a constant expression that becomes a
+/// static of the split function instead, which returns a reference to it.
+/// The value is thus in the read-only data that only the split module's code
+/// uses, which the split tool moves into its chunk, and is neither built nor
+/// copied at runtime.
+fn split_static(mut args: Args, item_static: ItemStatic) -> proc_macro2::TokenStream {
+    let ItemStatic {
+        attrs,
+        vis,
+        mutability,
+        ident,
+        ty,
+        expr,
+        ..
+    } = item_static;
+    if let StaticMutability::Mut(token) = mutability {
+        return syn::Error::new_spanned(token, "#[wasm_split] statics cannot be mutable")
+            .to_compile_error();
+    }
+    // Named after the static so that its symbols are recognizable in the
+    // split tool's output, and spanned like it so that it gets a unique
+    // identifier.
+    let init_ident = Ident::new(
+        &format!("{}_init", ident.to_string().to_lowercase()),
+        ident.span(),
+    );
+    let (lazy_ty, body) = if std::mem::take(&mut args.data) {
+        (
+            quote! { &'static #ty },
+            quote! {
+                static #ident: #ty = #expr;
+                &#ident
+            },
+        )
+    } else {
+        (quote! { #ty }, quote! { #expr })
+    };
+    let init_fn = split_fn(
+        args,
+        parse_quote! {
+            fn #init_ident() -> #lazy_ty {
+                #body
+            }
+        },
+    );
+    quote! {
+        #(#attrs)*
+        #vis static #ident: ::wasm_split::SplitLazy<#lazy_ty> = {
+            #init_fn
+
+            ::wasm_split::SplitLazy::new(|| ::wasm_split::__macro_support::Box::pin(#init_ident()))
+        };
+    }
+}
+
+/// A static of `locale_data!`, with the value of each locale.
+struct LocaleStatic {
+    attrs: Vec<Attribute>,
+    vis: syn::Visibility,
+    ident: Ident,
+    ty: Type,
+    locales: Vec<(LitStr, syn::Expr)>,
+}
+
+impl Parse for LocaleStatic {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let vis = input.parse()?;
+        input.parse::<Token![static]>()?;
+        let ident = input.parse()?;
+        input.parse::<Token![:]>()?;
+        let ty = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let content;
+        syn::braced!(content in input);
+        let mut locales = Vec::<(LitStr, syn::Expr)>::new();
+        while !content.is_empty() {
+            let tag: LitStr = content.parse()?;
+            let value = tag.value();
+            if value.is_empty()
+                || !value.split(['-', '_']).all(|subtag| {
+                    !subtag.is_empty() && subtag.chars().all(|c| c.is_ascii_alphanumeric())
+                })
+            {
+                return Err(syn::Error::new(
+                    tag.span(),
+                    "expected a BCP 47 language tag, such as \"de\" or \"pt-BR\"",
+                ));
+            }
+            if locales
+                .iter()
+                .any(|(other, _)| locale_module_name(other) == locale_module_name(&tag))
+            {
+                return Err(syn::Error::new(tag.span(), "duplicate locale"));
+            }
+            content.parse::<Token![=>]>()?;
+            locales.push((tag, content.parse()?));
+            if content.is_empty() {
+                break;
+            }
+            content.parse::<Token![,]>()?;
+        }
+        input.parse::<Token![;]>()?;
+        Ok(Self {
+            attrs,
+            vis,
+            ident,
+            ty,
+            locales,
+        })
+    }
+}
+
+/// The statics of a `locale_data!`.
+struct LocaleStatics(Vec<LocaleStatic>);
+
+impl Parse for LocaleStatics {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut statics = Vec::new();
+        while !input.is_empty() {
+            statics.push(input.parse()?);
+        }
+        Ok(Self(statics))
+    }
+}
+
+/// The split module of the locale `tag`, which every `locale_data!` shares.
+fn locale_module_name(tag: &LitStr) -> String {
+    format!("locale_{}", tag.value().to_lowercase().replace('-', "_"))
+}
+
+/// Statics with a value for each locale, each in the split module of its
+/// locale, such as translations or ICU formatting data, of which an app
+/// needs only those of the active locale:
+///
+/// ```ignore
+/// wasm_split::locale_data! {
+///     static MESSAGES: Catalog = {
+///         "de" => Catalog::parse(include_str!("locales/de.ftl")),
+///         "pt-BR" => Catalog::parse(include_str!("locales/pt-BR.ftl")),
+///     };
+/// }
+/// ```
+///
+/// Each value becomes a static of `#[wasm_split(locale_<tag>)]`, such as
+/// `locale_pt_br`, so that its code and data go into the chunk of the
+/// locale, and the static a `wasm_split::LocaleData` of them.
+#[proc_macro]
+pub fn locale_data(input: TokenStream) -> TokenStream {
+    let LocaleStatics(statics) = parse_macro_input!(input as LocaleStatics);
+    statics
+        .into_iter()
+        .map(|item| {
+            let LocaleStatic {
+                attrs,
+                vis,
+                ident,
+                ty,
+                locales,
+            } = item;
+            let mut splits = Vec::new();
+            let mut entries = Vec::new();
+            for (tag, expr) in locales {
+                let module_name = locale_module_name(&tag);
+                let module_ident = Ident::new(&module_name, tag.span());
+                // Spanned like the tag, so that each gets a unique
+                // identifier.
+                let locale_ident = Ident::new(
+                    &format!("{ident}_{}", module_name["locale_".len()..].to_uppercase()),
+                    tag.span(),
+                );
+                let args = match syn::parse2::<Args>(quote!(#module_ident)) {
+                    Ok(args) => args,
+                    Err(error) => return error.to_compile_error(),
+                };
+                splits.push(split_static(
+                    args,
+                    parse_quote!(static #locale_ident: #ty = #expr;),
+                ));
+                entries.push(quote! {
+                    (#tag, ::wasm_split::SplitChunk::new(#module_name), &#locale_ident)
+                });
+            }
+            quote! {
+                #(#attrs)*
+                #vis static #ident: ::wasm_split::LocaleData<#ty> = {
+                    #(#splits)*
+
+                    ::wasm_split::LocaleData::new(&[#(#entries),*])
+                };
+            }
+        })
+        .collect::<proc_macro2::TokenStream>()
+        .into()
+}
+
+fn mentions_impl(tokens: proc_macro2::TokenStream) -> bool {
+    tokens.into_iter().any(|tree| match tree {
+        TokenTree::Group(group) => mentions_impl(group.stream()),
+        TokenTree::Ident(ident) => ident == "impl",
+        _ => false,
+    })
+}
+
+/// The type parameter of a generic split function, whose instantiations
+/// `types` lists, once checked that the macro can split its signature.
+fn split_type_param(sig: &Signature, types: &[Type]) -> syn::Result<Option<Ident>> {
+    if let Some(param) = sig.generics.const_params().next() {
+        return Err(syn::Error::new_spanned(
+            param,
+            "#[wasm_split] functions cannot have const parameters",
+        ));
+    }
+    if let syn::ReturnType::Type(_, ty) = &sig.output {
+        if mentions_impl(ty.to_token_stream()) {
+            return Err(syn::Error::new_spanned(
+                ty,
+                "#[wasm_split] functions cannot return `impl Trait`; return a `Box<dyn Trait>` instead",
+            ));
+        }
+    }
+    for input in sig.inputs.iter() {
+        if let FnArg::Typed(pat_type) = input {
+            if !matches!(*pat_type.ty, Type::ImplTrait(_))
+                && mentions_impl(pat_type.ty.to_token_stream())
+            {
+                return Err(syn::Error::new_spanned(
+                    &pat_type.ty,
+                    "#[wasm_split] functions only take `impl Trait` as the type of an argument \
+                     itself, which is passed to the split function as a `Box<dyn Trait>`",
+                ));
+            }
+        }
+    }
+    let mut params = sig.generics.type_params();
+    let param = params.next();
+    if let Some(param) = params.next() {
+        return Err(syn::Error::new_spanned(
+            param,
+            "#[wasm_split] functions can have one type parameter at most",
+        ));
+    }
+    match (param, types.first()) {
+        (None, None) => Ok(None),
+        (None, Some(ty)) => Err(syn::Error::new_spanned(
+            ty,
+            "`types` lists instantiations of the type parameter of a generic function",
+        )),
+        (Some(param), None) => Err(syn::Error::new_spanned(
+            param,
+            "generic #[wasm_split] functions need `types(...)`, listing the types to split them \
+             for, as the split tool cannot instantiate them",
+        )),
+        (Some(param), Some(_)) => Ok(Some(param.ident.clone())),
+    }
+}
+
+/// The type that an `impl Trait` argument is passed to the split function
+/// as, borrowing what the value borrows unless the bounds give a lifetime.
+fn boxed_impl_type(impl_trait: &syn::TypeImplTrait) -> Type {
+    let bounds = &impl_trait.bounds;
+    let lifetime = (!bounds
+        .iter()
+        .any(|bound| matches!(bound, syn::TypeParamBound::Lifetime(_))))
+    .then(|| quote!(+ '_));
+    parse_quote!(::wasm_split::__macro_support::Box<dyn #bounds #lifetime>)
+}
+
+fn split_fn(args: Args, item_fn: ItemFn) -> proc_macro2::TokenStream {
+    let Args {
+        module_ident,
+        priority,
+        table_slots,
+        fallback,
+        with,
+        route,
+        fallible,
+        optional,
+        worker,
+        sync,
+        strict,
+        data,
+        self_type,
+        types,
+    } = args;
+    if data {
+        return syn::Error::new_spanned(
+            &item_fn.sig,
+            "`data` applies to statics, whose constant value stays in the split module",
+        )
+        .to_compile_error();
+    }
+    if self_type.is_none() && mentions_self(item_fn.sig.to_token_stream()) {
+        return syn::Error::new_spanned(
+            &item_fn.sig,
+            "#[wasm_split] methods need #[wasm_split] on their impl block as well",
+        )
+        .to_compile_error();
+    }
+    if worker && self_type.is_some() {
+        return syn::Error::new_spanned(
+            &item_fn.sig,
+            "`worker` is not supported on methods, as the worker has no `self` to call them on",
+        )
+        .to_compile_error();
+    }
+    let type_param = match split_type_param(&item_fn.sig, &types) {
+        Ok(type_param) => type_param,
+        Err(error) => return error.to_compile_error(),
+    };
+    if let (true, Some(type_param)) = (sync, &type_param) {
+        return syn::Error::new_spanned(type_param, "`sync` is not supported on generic functions")
+            .to_compile_error();
+    }
+    let has_impl_args = item_fn.sig.inputs.iter().any(
+        |input| matches!(input, FnArg::Typed(pat_type) if matches!(*pat_type.ty, Type::ImplTrait(_))),
+    );
+    if worker && (type_param.is_some() || has_impl_args) {
+        return syn::Error::new_spanned(
+            &item_fn.sig,
+            "`worker` is not supported on generic functions",
+        )
+        .to_compile_error();
+    }
+    let mut_arg = item_fn.sig.inputs.iter().find(|input| {
+        matches!(input, FnArg::Typed(pat_type)
+            if matches!(&*pat_type.ty, Type::Reference(reference) if reference.mutability.is_some()))
+    });
+    if let (true, Some(mut_arg)) = (worker, mut_arg) {
+        return syn::Error::new_spanned(
+            mut_arg,
+            "`worker` functions cannot take `&mut` arguments, as the worker only gets a copy of \
+             them with `wasm_split::Transfer`",
+        )
+        .to_compile_error();
+    }
+    // The body of an async function runs as a future that its chunk creates,
+    // and that the caller polls after the call returned, which therefore
+    // must not borrow from the caller.
+    if let Some(asyncness) = &item_fn.sig.asyncness {
+        let unsupported = if worker {
+            Some("`worker` is not supported on async functions, as the worker runs calls to completion")
+        } else if sync {
+            Some("`sync` is not supported on async functions, whose result must be awaited")
+        } else if type_param.is_some() || has_impl_args {
+            Some("async split functions cannot be generic")
+        } else {
+            None
+        };
+        if let Some(message) = unsupported {
+            return syn::Error::new_spanned(asyncness, message).to_compile_error();
+        }
+        let borrowed = item_fn.sig.inputs.iter().find(|input| match input {
+            FnArg::Receiver(receiver) => borrows(receiver.ty.to_token_stream()),
+            FnArg::Typed(pat_type) => borrows(pat_type.ty.to_token_stream()),
+        });
+        if let Some(borrowed) = borrowed {
+            return syn::Error::new_spanned(
+                borrowed,
+                "async split functions must take their arguments by value, as the future of a \
+                 call, which their chunk creates, is boxed as `'static`; pass owned values, \
+                 such as clones",
+            )
+            .to_compile_error();
+        }
+    }
+    // The split point belongs to the other module, under whose name the
+    // metadata records of this one are merged.
+    let (module_ident, alias) = match with {
+        Some(with) => (with, Some(module_ident)),
+        None => (module_ident, None),
+    };
+
+    let name = item_fn.sig.ident.clone();
 
     let unique_identifier = base16::encode_lower(
         &sha2::Sha256::digest(format!("{name} {span:?}", span = name.span()))[..16],
@@ -21,70 +1031,473 @@ pub fn wasm_split(args: TokenStream, input: TokenStream) -> TokenStream {
         format_ident!("__wasm_split_00{module_ident}00_import_{unique_identifier}_{name}");
     let impl_export_ident =
         format_ident!("__wasm_split_00{module_ident}00_export_{unique_identifier}_{name}");
+    let worker_entry_ident = format_ident!("__wasm_split_worker_{unique_identifier}_{name}");
 
-    let import_sig = Signature {
-        ident: impl_import_ident.clone(),
-        asyncness: None,
-        ..item_fn.sig.clone()
+    // The receiver of a method becomes an ordinary argument.
+    let receiver_arg = |with_mutability: bool| -> Option<FnArg> {
+        let receiver = item_fn.sig.receiver()?;
+        let ty = &receiver.ty;
+        let mutability = (with_mutability && receiver.reference.is_none())
+            .then_some(receiver.mutability)
+            .flatten();
+        let arg_ident = Ident::new(SELF_ARG, receiver.self_token.span);
+        Some(parse_quote!(#mutability #arg_ident: #ty))
     };
-    let export_sig = Signature {
-        ident: impl_export_ident.clone(),
-        asyncness: None,
-        ..item_fn.sig.clone()
+    // Only within impl blocks, as the body of a free function can still
+    // use `Self` within impls of its own.
+    let is_method = self_type.is_some();
+    let replace_self = |tokens| {
+        if is_method {
+            replace_self(tokens)
+        } else {
+            tokens
+        }
+    };
+    let output = match &item_fn.sig.output {
+        syn::ReturnType::Default => quote!(()),
+        syn::ReturnType::Type(_, ty) => ty.to_token_stream(),
     };
+    let is_async = item_fn.sig.asyncness.is_some();
+    // Across the split, `impl Trait` arguments are boxed, the type parameter
+    // is an alias of the instantiation, and an async function returns its
+    // future boxed.
+    // Without `keep_patterns`, each argument is a plain identifier, as
+    // patterns such as `mut self`, `mut state` or `(x, y)` are not allowed in
+    // foreign functions.
+    let nested_sig = |ident: &Ident, keep_patterns: bool, across_split: bool| {
+        let inputs = item_fn
+            .sig
+            .inputs
+            .iter()
+            .enumerate()
+            .map(|(i, input)| match input {
+                FnArg::Receiver(_) => receiver_arg(keep_patterns).unwrap(),
+                FnArg::Typed(pat_type) => {
+                    let mut pat_type = pat_type.clone();
+                    if !keep_patterns {
+                        let arg_ident = format_ident!("__wasm_split_arg_{i}");
+                        *pat_type.pat = parse_quote!(#arg_ident);
+                    }
+                    if let (true, Type::ImplTrait(impl_trait)) = (across_split, &*pat_type.ty) {
+                        *pat_type.ty = boxed_impl_type(impl_trait);
+                    }
+                    FnArg::Typed(pat_type)
+                }
+            })
+            .collect();
+        let mut generics = item_fn.sig.generics.clone();
+        if across_split {
+            generics.params = generics
+                .params
+                .into_iter()
+                .filter(|param| !matches!(param, syn::GenericParam::Type(_)))
+                .collect();
+        }
+        let (asyncness, output) = match (is_async, across_split) {
+            (true, true) => (
+                None,
+                parse_quote!(-> ::wasm_split::__macro_support::SplitFuture<#output>),
+            ),
+            _ => (item_fn.sig.asyncness, item_fn.sig.output.clone()),
+        };
+        let sig = Signature {
+            ident: ident.clone(),
+            asyncness,
+            inputs,
+            generics,
+            output,
+            ..item_fn.sig.clone()
+        };
+        replace_self(sig.into_token_stream())
+    };
+    // Of the type of the impl block as well, which the signature of a method
+    // only refers to by its alias.
+    let self_type_tokens = self_type
+        .as_ref()
+        .map(|self_type| self_type.to_token_stream().to_string());
+    let self_alias = self_type.map(|self_type| {
+        let alias = Ident::new(SELF_ALIAS, proc_macro2::Span::call_site());
+        quote! {
+            #[allow(non_camel_case_types, dead_code)]
+            type #alias = #self_type;
+        }
+    });
 
-    let mut wrapper_sig = item_fn.sig;
+    let mut wrapper_sig = item_fn.sig.clone();
     wrapper_sig.asyncness = Some(Default::default());
+    if fallible {
+        wrapper_sig.output =
+            parse_quote!(-> ::core::result::Result<#output, ::wasm_split::LoadError>);
+    }
+    // For the `TypeId` that picks the instantiation.
+    if let Some(param) = wrapper_sig.generics.type_params_mut().next() {
+        param.colon_token.get_or_insert_with(Default::default);
+        param.bounds.push(parse_quote!('static));
+    }
     let mut args = Vec::new();
+    let mut arg_types = Vec::new();
+    let mut split_args = Vec::new();
     for (i, param) in wrapper_sig.inputs.iter_mut().enumerate() {
         match param {
             syn::FnArg::Typed(pat_type) => {
                 let param_ident = format_ident!("__wasm_split_arg_{i}");
                 args.push(param_ident.clone());
-                pat_type.pat = Box::new(syn::Pat::Ident(syn::PatIdent {
+                arg_types.push(pat_type.ty.clone());
+                split_args.push(match &*pat_type.ty {
+                    Type::ImplTrait(impl_trait) => {
+                        let boxed = boxed_impl_type(impl_trait);
+                        quote! {{
+                            let __wasm_split_boxed: #boxed = ::wasm_split::__macro_support::Box::new(#param_ident);
+                            __wasm_split_boxed
+                        }}
+                    }
+                    _ => param_ident.to_token_stream(),
+                });
+                *pat_type.pat = syn::Pat::Ident(syn::PatIdent {
                     attrs: vec![],
                     by_ref: None,
                     mutability: None,
                     ident: param_ident,
                     subpat: None,
-                }));
+                });
             }
-            syn::FnArg::Receiver(_) => {
+            syn::FnArg::Receiver(receiver) => {
+                // Only the export mutates it.
+                if receiver.reference.is_none() {
+                    receiver.mutability = None;
+                }
                 args.push(format_ident!("self"));
+                split_args.push(quote!(self));
             }
         }
     }
 
     let attrs = item_fn.attrs;
+    // The wrapper is what callers see, e.g. from outside of a split module.
+    let vis = &item_fn.vis;
 
-    let stmts = &item_fn.block.stmts;
+    let stmts = item_fn
+        .block
+        .stmts
+        .iter()
+        .map(|stmt| replace_self(stmt.to_token_stream()))
+        .collect::<Vec<_>>();
+    // So that the state machine of an async function, and everything that
+    // only it calls, such as the futures it spawns, is in the chunk, whose
+    // code creates the future that the caller then polls.
+    let export_body = if is_async {
+        quote!(::wasm_split::__macro_support::Box::pin(async move { #(#stmts)* }))
+    } else {
+        quote!(#(#stmts)*)
+    };
+    let await_call = is_async.then(|| quote!(.await));
 
-    quote! {
-        #wrapper_sig {
-            thread_local! {
-                static #split_loader_ident: ::wasm_split::LazySplitLoader = unsafe { ::wasm_split::LazySplitLoader::new(#load_module_ident) };
+    let wrap_result = fallible.then(|| quote!(::core::result::Result::Ok));
+    // The import and export pair of the function, or of each instantiation of
+    // a generic one, in a module with the type parameter as an alias of its
+    // type, and the call of the import.
+    let mut split_items = Vec::new();
+    let mut split_calls = Vec::new();
+    for (index, ty) in types
+        .iter()
+        .map(Some)
+        .chain(types.is_empty().then_some(None))
+        .enumerate()
+    {
+        let (import_ident, export_ident) = match ty {
+            None => (impl_import_ident.clone(), impl_export_ident.clone()),
+            Some(ty) => {
+                let unique_identifier = base16::encode_lower(
+                    &sha2::Sha256::digest(format!(
+                        "{name} {span:?} {ty}",
+                        span = name.span(),
+                        ty = ty.to_token_stream()
+                    ))[..16],
+                );
+                (
+                    format_ident!(
+                        "__wasm_split_00{module_ident}00_import_{unique_identifier}_{name}"
+                    ),
+                    format_ident!(
+                        "__wasm_split_00{module_ident}00_export_{unique_identifier}_{name}"
+                    ),
+                )
             }
+        };
+        let import_sig = nested_sig(&import_ident, false, true);
+        let export_sig = nested_sig(&export_ident, true, true);
+        let signature = signature_record(
+            &export_ident,
+            &format!(
+                "{} {} {}",
+                nested_sig(&format_ident!("split"), false, true),
+                self_type_tokens.as_deref().unwrap_or_default(),
+                ty.map(|ty| ty.to_token_stream().to_string())
+                    .unwrap_or_default(),
+            ),
+        );
+        let items = quote! {
+            #signature
 
             #[link(wasm_import_module = "./__wasm_split.js")]
             extern "C" {
-                #[no_mangle]
-                fn #load_module_ident (callback: unsafe extern "C" fn(*const ::std::ffi::c_void, bool), data: *const ::std::ffi::c_void) -> ();
-
                 #[allow(improper_ctypes)]
                 #[no_mangle]
-                #import_sig;
+                pub #import_sig;
             }
 
             #(#attrs)*
             #[allow(improper_ctypes_definitions)]
             #[no_mangle]
             pub extern "C" #export_sig {
+                #export_body
+            }
+        };
+        match (ty, &type_param) {
+            (Some(ty), Some(type_param)) => {
+                let instance_ident = format_ident!("__wasm_split_instance_{index}");
+                split_items.push(quote! {
+                    mod #instance_ident {
+                        #[allow(unused_imports)]
+                        use super::*;
+
+                        #self_alias
+
+                        #[allow(dead_code)]
+                        type #type_param = #ty;
+
+                        #items
+                    }
+                });
+                let split_args = split_args
+                    .iter()
+                    .map(|arg| quote!(::wasm_split::__macro_support::cast(#arg)));
+                split_calls.push(quote! {
+                    if ::core::any::TypeId::of::<#type_param>() == ::core::any::TypeId::of::<#ty>() {
+                        return #wrap_result(unsafe {
+                            ::wasm_split::__macro_support::cast::<_, #output>(
+                                #instance_ident::#import_ident( #(#split_args),* )
+                            )
+                        });
+                    }
+                });
+            }
+            _ => {
+                split_items.push(items);
+                split_calls.push(quote! {
+                    #wrap_result(unsafe { #import_ident( #(#split_args),* ) } #await_call)
+                });
+            }
+        }
+    }
+    if let Some(type_param) = &type_param {
+        split_calls.push(quote! {
+            panic!(
+                "split function `{}` is not split for `{}`; add it to `types(...)`",
+                ::core::stringify!(#name),
+                ::core::any::type_name::<#type_param>()
+            )
+        });
+    }
+    let native_sig = nested_sig(&impl_export_ident, true, false);
+
+    // Without `.await`, for call sites that cannot be async, such as event
+    // handlers, once the module was loaded, e.g. with `wasm_split::preload`.
+    // They neither load the module nor run on a worker, and ask the loader
+    // whether it is loaded, which covers loads by other functions of the
+    // module.
+    let sync_fns = sync.then(|| {
+        let sync_ident = format_ident!("{name}_sync");
+        let try_sync_ident = format_ident!("try_{name}_sync");
+        let mut sync_sig = wrapper_sig.clone();
+        sync_sig.ident = sync_ident;
+        sync_sig.asyncness = None;
+        sync_sig.output = parse_quote!(-> #output);
+        let mut try_sync_sig = sync_sig.clone();
+        try_sync_sig.ident = try_sync_ident.clone();
+        try_sync_sig.output =
+            parse_quote!(-> ::core::result::Result<#output, ::wasm_split::LoadError>);
+        let import_sig = nested_sig(&impl_import_ident, false, true);
+        let try_sync_path = if is_method {
+            quote!(Self::#try_sync_ident)
+        } else {
+            quote!(#try_sync_ident)
+        };
+        quote! {
+            #[cfg(target_arch = "wasm32")]
+            #vis #try_sync_sig {
+                #self_alias
+
+                #[link(wasm_import_module = "./__wasm_split.js")]
+                extern "C" {
+                    #[allow(improper_ctypes)]
+                    #[no_mangle]
+                    #import_sig;
+                }
+
+                if !::wasm_split::is_loaded(::core::stringify!(#module_ident)) {
+                    return ::core::result::Result::Err(::wasm_split::LoadError::NotLoaded);
+                }
+                ::core::result::Result::Ok(unsafe { #impl_import_ident( #(#split_args),* ) })
+            }
+
+            #[cfg(not(target_arch = "wasm32"))]
+            #vis #try_sync_sig {
+                #self_alias
+
+                #(#attrs)*
+                #native_sig {
+                    #(#stmts)*
+                }
+
+                ::core::result::Result::Ok(#impl_export_ident( #(#args),* ))
+            }
+
+            #vis #sync_sig {
+                match #try_sync_path( #(#args),* ) {
+                    ::core::result::Result::Ok(result) => result,
+                    ::core::result::Result::Err(_) => panic!(
+                        "split function `{}` called synchronously before split module `{}` was loaded",
+                        ::core::stringify!(#name),
+                        ::core::stringify!(#module_ident)
+                    ),
+                }
+            }
+        }
+    });
+
+    let metadata = priority
+        .map(|priority| metadata_record(&module_ident, "priority", priority.as_str()))
+        .into_iter()
+        .chain(table_slots.map(|table_slots| {
+            metadata_record(&module_ident, "table_slots", &table_slots.to_string())
+        }))
+        .chain(
+            alias
+                .as_ref()
+                .map(|alias| metadata_record(&module_ident, "alias", &alias.to_string())),
+        )
+        .chain(route.map(|route| metadata_record(&module_ident, "route", &route.value())))
+        .chain(strict.then(|| {
+            // The crate's own code may stay in the main module, as the split
+            // function may well share it with eager code.
+            let crate_name = std::env::var("CARGO_CRATE_NAME").unwrap_or_default();
+            metadata_record(&module_ident, "strict", &crate_name)
+        }));
+
+    let fallback_path = fallback.clone();
+    let ensure_loaded = match fallback {
+        _ if fallible => quote! {
+            if let Err(error) = ::wasm_split::__macro_support::ensure_loaded_retrying(&#split_loader_ident).await {
+                return ::core::result::Result::Err(error);
+            }
+        },
+        _ if optional => quote! {
+            if ::wasm_split::__macro_support::ensure_loaded(&#split_loader_ident).await.is_err() {
+                return ::core::default::Default::default();
+            }
+        },
+        Some(fallback) => quote! {
+            if ::wasm_split::__macro_support::ensure_loaded(&#split_loader_ident).await.is_err() {
+                return #fallback( #(#args),* ) #await_call;
+            }
+        },
+        // Names the annotated module, rather than only the one that its code
+        // was put into with `with = ...`.
+        None => match &alias {
+            Some(alias) => quote! {
+                if let Err(error) = ::wasm_split::__macro_support::ensure_loaded(&#split_loader_ident).await {
+                    panic!(
+                        "failed to load split module `{}`, whose code is in split module `{}`: {}",
+                        ::core::stringify!(#alias),
+                        ::core::stringify!(#module_ident),
+                        error
+                    );
+                }
+            },
+            None => quote! {
+                if let Err(error) = ::wasm_split::__macro_support::ensure_loaded(&#split_loader_ident).await {
+                    panic!("failed to load split module `{}`: {}", ::core::stringify!(#module_ident), error);
+                }
+            },
+        },
+    };
+
+    // Used by the wasm expansion only.
+    let unused_fallback = fallback_path.map(|fallback| quote!(let _ = #fallback;));
+    // The worker's instance of the main module calls the entry point, which
+    // runs the function in its own instance of the chunk.
+    let (worker_entry, run_on_worker) = if worker {
+        (
+            quote! {
+                #[cfg(target_arch = "wasm32")]
+                #[no_mangle]
+                pub extern "C" fn #worker_entry_ident() -> usize {
+                    ::wasm_split::__macro_support::run_worker_entry(|__wasm_split_input, __wasm_split_output| {
+                        #( let #args = <#arg_types as ::wasm_split::Transfer>::decode(__wasm_split_input); )*
+                        let __wasm_split_result = unsafe { #impl_import_ident( #(#args),* ) };
+                        ::wasm_split::Transfer::encode(&__wasm_split_result, __wasm_split_output);
+                    })
+                }
+            },
+            quote! {
+                let mut __wasm_split_input = ::wasm_split::__macro_support::Vec::new();
+                #( ::wasm_split::Transfer::encode(&#args, &mut __wasm_split_input); )*
+                if let Ok(__wasm_split_output) = ::wasm_split::__macro_support::run_on_worker(
+                    ::core::stringify!(#module_ident),
+                    ::core::stringify!(#worker_entry_ident),
+                    __wasm_split_input,
+                ).await {
+                    return #wrap_result(::wasm_split::Transfer::decode(&mut __wasm_split_output.as_slice()));
+                }
+            },
+        )
+    } else {
+        Default::default()
+    };
+
+    quote! {
+        #[cfg(target_arch = "wasm32")]
+        #vis #wrapper_sig {
+            #(#metadata)*
+
+            #self_alias
+
+            ::wasm_split::__macro_support::split_loader!(#split_loader_ident, #load_module_ident, ::core::stringify!(#module_ident));
+
+            #[link(wasm_import_module = "./__wasm_split.js")]
+            extern "C" {
+                #[no_mangle]
+                fn #load_module_ident (callback: ::wasm_split::__macro_support::LoadCallbackFn, data: *const ::core::ffi::c_void) -> ();
+            }
+
+            #(#split_items)*
+
+            #worker_entry
+
+            ::wasm_split::__macro_support::trace_call(::core::stringify!(#module_ident), ::core::stringify!(#name), async move {
+                #run_on_worker
+                #ensure_loaded
+                #(#split_calls)*
+            }).await
+        }
+
+        // Elsewhere, such as in the server build of an SSR app that shares
+        // the crate, the function is called directly, as there are no chunks
+        // to load it from.
+        #[cfg(not(target_arch = "wasm32"))]
+        #vis #wrapper_sig {
+            #self_alias
+
+            #(#attrs)*
+            #native_sig {
                 #(#stmts)*
             }
 
-            ::wasm_split::ensure_loaded(&#split_loader_ident).await.unwrap();
-            unsafe { #impl_import_ident( #(#args),* ) }
+            #unused_fallback
+            #wrap_result(#impl_export_ident( #(#args),* ) #await_call)
         }
+
+        #sync_fns
     }
-    .into()
 }
@@ -0,0 +1,103 @@
+//! Integration with [actix-web](https://docs.rs/actix-web).
+//!
+//! ```ignore
+//! let hints = web::Data::new(PreloadHints::load("pkg", "/pkg")?);
+//! HttpServer::new(move || {
+//!     App::new()
+//!         .app_data(hints.clone())
+//!         .wrap(actix_web::middleware::from_fn(
+//!             wasm_split_server::actix::preload_links,
+//!         ))
+//!         .service(wasm_split_server::actix::static_files(
+//!             "/pkg",
+//!             StaticFiles::new("pkg"),
+//!         ))
+//!         .route("/", web::get().to(index))
+//! })
+//! ```
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{
+        HeaderValue, ACCEPT_ENCODING, CACHE_CONTROL, CONTENT_ENCODING, CONTENT_TYPE, LINK, VARY,
+    },
+    middleware::Next,
+    web, Error, HttpRequest, HttpResponse, Resource,
+};
+
+use crate::{PreloadHints, StaticFile, StaticFiles};
+
+/// Adds the `Link` header for the route of the request, if any, to the
+/// response if it is an HTML page. Reads the hints from the
+/// `web::Data<PreloadHints>` of the app.
+pub async fn preload_links(
+    request: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let link = request
+        .app_data::<web::Data<PreloadHints>>()
+        .and_then(|hints| hints.link_header(request.path()))
+        .and_then(|link| HeaderValue::from_str(link).ok());
+    let mut response = next.call(request).await?;
+    let is_html = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(crate::preload::is_html);
+    if let (Some(link), true) = (link, is_html) {
+        response.headers_mut().append(LINK, link);
+    }
+    Ok(response)
+}
+
+/// Resource serving the output directory of `wasm-split` under `mount_path`.
+pub fn static_files(mount_path: &str, files: StaticFiles) -> Resource {
+    web::resource(format!("{}/{{path:.*}}", mount_path.trim_end_matches('/')))
+        .app_data(web::Data::new(files))
+        .route(web::get().to(serve))
+}
+
+async fn serve(request: HttpRequest, files: web::Data<StaticFiles>) -> HttpResponse {
+    let path = request.match_info().query("path").to_string();
+    let query = request.query_string().to_string();
+    let accept_encoding = request
+        .headers()
+        .get(ACCEPT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let files = files.into_inner();
+    let result = web::block(move || {
+        let file = files.resolve(&path, Some(&query), accept_encoding.as_deref())?;
+        let StaticFile {
+            contents,
+            content_type,
+            cache_control,
+            content_encoding,
+            vary,
+        } = file;
+        let contents = contents.read().ok()?;
+        Some((
+            contents,
+            content_type,
+            cache_control,
+            content_encoding,
+            vary,
+        ))
+    })
+    .await;
+    let Ok(Some((contents, content_type, cache_control, content_encoding, vary))) = result else {
+        return HttpResponse::NotFound().finish();
+    };
+    let mut response = HttpResponse::Ok();
+    response
+        .insert_header((CONTENT_TYPE, content_type))
+        .insert_header((CACHE_CONTROL, cache_control));
+    if let Some(encoding) = content_encoding {
+        response.insert_header((CONTENT_ENCODING, encoding));
+    }
+    if let Some(vary) = vary {
+        response.insert_header((VARY, vary));
+    }
+    response.body(contents)
+}
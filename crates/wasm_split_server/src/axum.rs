@@ -0,0 +1,93 @@
+//! Integration with [axum](https://docs.rs/axum).
+//!
+//! ```ignore
+//! let hints = Arc::new(PreloadHints::load("pkg", "/pkg")?);
+//! let app = Router::new()
+//!     .route("/", get(index))
+//!     .nest("/pkg", wasm_split_server::axum::static_files(StaticFiles::new("pkg")))
+//!     .layer(axum::middleware::from_fn_with_state(
+//!         hints,
+//!         wasm_split_server::axum::preload_links,
+//!     ));
+//! ```
+
+use std::{borrow::Cow, sync::Arc};
+
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{
+        header::{ACCEPT_ENCODING, CACHE_CONTROL, CONTENT_ENCODING, CONTENT_TYPE, LINK, VARY},
+        HeaderValue, StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+
+use crate::{Contents, PreloadHints, StaticFiles};
+
+/// Adds the `Link` header for the route of the request, if any, to the
+/// response if it is an HTML page. Other responses, such as the chunks
+/// themselves or API calls below a route, get none, as the browser would
+/// preload the chunks again for each of them.
+pub async fn preload_links(
+    State(hints): State<Arc<PreloadHints>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let link = hints
+        .link_header(request.uri().path())
+        .and_then(|link| HeaderValue::from_str(link).ok());
+    let mut response = next.run(request).await;
+    let is_html = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(crate::preload::is_html);
+    if let (Some(link), true) = (link, is_html) {
+        response.headers_mut().append(LINK, link);
+    }
+    response
+}
+
+/// Router serving the output directory of `wasm-split`, to be mounted with
+/// [`Router::nest`].
+pub fn static_files<S>(files: StaticFiles) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .fallback(get(serve))
+        .with_state(Arc::new(files))
+}
+
+async fn serve(State(files): State<Arc<StaticFiles>>, request: Request) -> Response {
+    let accept_encoding = request
+        .headers()
+        .get(ACCEPT_ENCODING)
+        .and_then(|value| value.to_str().ok());
+    let Some(file) = files.resolve(request.uri().path(), request.uri().query(), accept_encoding)
+    else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let contents = match file.contents {
+        Contents::Path(path) => tokio::fs::read(path).await.map(Cow::Owned),
+        contents => contents.read(),
+    };
+    let Ok(contents) = contents else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let mut response = Response::new(Body::from(contents));
+    let headers = response.headers_mut();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static(file.content_type));
+    headers.insert(CACHE_CONTROL, HeaderValue::from_static(file.cache_control));
+    if let Some(encoding) = file.content_encoding {
+        headers.insert(CONTENT_ENCODING, HeaderValue::from_static(encoding));
+    }
+    if let Some(vary) = file.vary {
+        headers.insert(VARY, HeaderValue::from_static(vary));
+    }
+    response
+}
@@ -0,0 +1,29 @@
+//! Server-side support for applications split with `wasm-split`.
+//!
+//! [`PreloadHints`] reads the `wasm-split-preload.json` file written by
+//! `wasm-split` and produces the `Link` header that lets the browser fetch the
+//! chunks of a route while it is still loading the page. CDNs and proxies that
+//! support HTTP 103 Early Hints send these headers ahead of the response.
+//! Server-rendered pages can instead include its `<link>` tags, and hand the
+//! split modules of their route to `wasm_split::hydrate_preload` on the
+//! client.
+//!
+//! [`StaticFiles`] serves the output directory itself, with the content types
+//! and caching headers that the loader relies on. It can also serve files
+//! embedded in the binary with e.g. rust-embed or include_dir, see
+//! [`StaticFiles::embedded`].
+//!
+//! The `axum` and `actix` features integrate both with the respective web
+//! framework.
+
+#[cfg(feature = "actix")]
+pub mod actix;
+#[cfg(feature = "axum")]
+pub mod axum;
+mod preload;
+mod static_files;
+
+pub use preload::{PreloadHints, PRELOAD_FILENAME};
+pub use static_files::{
+    accepts_encoding, Contents, EmbeddedLookup, StaticFile, StaticFiles, MANIFEST_FILENAME,
+};
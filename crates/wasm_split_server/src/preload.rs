@@ -0,0 +1,204 @@
+use std::{collections::BTreeMap, io, path::Path};
+
+use serde::Deserialize;
+
+/// Name of the file that `wasm-split` writes to its output directory.
+pub const PRELOAD_FILENAME: &str = "wasm-split-preload.json";
+
+#[derive(Debug, Deserialize)]
+struct PreloadFile {
+    routes: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    integrity: BTreeMap<String, String>,
+    #[serde(default)]
+    modules: BTreeMap<String, Vec<String>>,
+}
+
+/// Chunks to preload for each route of the application.
+#[derive(Debug, Clone)]
+pub struct PreloadHints {
+    /// Value of the `Link` header for each route.
+    links: BTreeMap<String, String>,
+    /// `<link>` tags for each route.
+    tags: BTreeMap<String, String>,
+    /// Split modules of each route.
+    modules: BTreeMap<String, Vec<String>>,
+}
+
+impl PreloadHints {
+    /// Parses the contents of `wasm-split-preload.json`. `base_url` is the URL
+    /// under which the output directory of `wasm-split` is served, e.g.
+    /// `/pkg`.
+    ///
+    /// When the output is embedded in the binary, pass the contents of the
+    /// embedded file.
+    pub fn from_json(json: &str, base_url: &str) -> serde_json::Result<Self> {
+        let file: PreloadFile = serde_json::from_str(json)?;
+        let base_url = base_url.trim_end_matches('/');
+        let mut links = BTreeMap::new();
+        let mut tags = BTreeMap::new();
+        for (route, urls) in file.routes.into_iter().filter(|(_, urls)| !urls.is_empty()) {
+            let link = urls
+                .iter()
+                .map(|url| {
+                    format!(
+                        "<{base_url}/{url}>; rel=preload; as=fetch; crossorigin; \
+                         type=\"application/wasm\""
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            // Unlike `Link` headers, tags can carry the `integrity` that the
+            // loader requests the chunk with, without which the browser
+            // would not reuse the preloaded response.
+            let tag = urls
+                .iter()
+                .map(|url| {
+                    let integrity = file
+                        .integrity
+                        .get(url)
+                        .map(|integrity| format!(" integrity=\"{}\"", escape_attribute(integrity)))
+                        .unwrap_or_default();
+                    format!(
+                        "<link rel=\"preload\" href=\"{}\" as=\"fetch\" \
+                         type=\"application/wasm\" crossorigin{integrity}>",
+                        escape_attribute(&format!("{base_url}/{url}"))
+                    )
+                })
+                .collect::<String>();
+            let route = normalize_route(&route);
+            links.insert(route.clone(), link);
+            tags.insert(route, tag);
+        }
+        let modules = file
+            .modules
+            .into_iter()
+            .map(|(route, modules)| (normalize_route(&route), modules))
+            .collect();
+        Ok(Self {
+            links,
+            tags,
+            modules,
+        })
+    }
+
+    /// Reads `wasm-split-preload.json` from the output directory of
+    /// `wasm-split`. See [`from_json`](Self::from_json).
+    pub fn load(output_dir: impl AsRef<Path>, base_url: &str) -> io::Result<Self> {
+        let json = std::fs::read_to_string(output_dir.as_ref().join(PRELOAD_FILENAME))?;
+        Ok(Self::from_json(&json, base_url)?)
+    }
+
+    /// Value of the `Link` header to send with the page at `path`, if its
+    /// route needs any chunks.
+    ///
+    /// Paths that are not a configured route use the hints of the closest
+    /// route that they are nested under, so that e.g. `/posts/42` preloads
+    /// the chunks of `/posts`.
+    pub fn link_header(&self, path: &str) -> Option<&str> {
+        lookup_route(&self.links, path).map(String::as_str)
+    }
+
+    /// `<link rel="preload">` tags for the page at `path`, for server-side
+    /// rendering to put into its `<head>`, so that the chunks of its route
+    /// download while the page and the main module load. Routes are looked
+    /// up as by [`link_header`](Self::link_header).
+    pub fn html_tags(&self, path: &str) -> Option<&str> {
+        lookup_route(&self.tags, path).map(String::as_str)
+    }
+
+    /// Split modules of the route of the page at `path`, for the client to
+    /// pass to `wasm_split::hydrate_preload` before it hydrates the page, so
+    /// that their chunks are instantiated right away rather than as
+    /// hydration first calls into them. Routes are looked up as by
+    /// [`link_header`](Self::link_header); `None` if no route matches.
+    ///
+    /// Servers hand the list to the client with the rest of the page's
+    /// hydration state, e.g. serialized into a `<script>` element of the
+    /// page, and render the [`html_tags`](Self::html_tags) of the route
+    /// along with it, which download the chunks while the main module loads.
+    pub fn hydrate_modules(&self, path: &str) -> Option<&[String]> {
+        lookup_route(&self.modules, path).map(Vec::as_slice)
+    }
+}
+
+/// `route` without a trailing slash, as paths are looked up.
+fn normalize_route(route: &str) -> String {
+    match route.trim_end_matches('/') {
+        "" => String::from("/"),
+        trimmed => trimmed.to_string(),
+    }
+}
+
+fn lookup_route<'a, V>(values: &'a BTreeMap<String, V>, path: &str) -> Option<&'a V> {
+    let mut path = path.trim_end_matches('/');
+    loop {
+        let route = if path.is_empty() { "/" } else { path };
+        if let Some(value) = values.get(route) {
+            return Some(value);
+        }
+        path = &path[..path.rfind('/')?];
+    }
+}
+
+fn escape_attribute(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+}
+
+/// Whether a `Content-Type` is that of an HTML page, the only kind of
+/// response that gets preload hints.
+#[cfg(any(feature = "actix", feature = "axum"))]
+pub(crate) fn is_html(content_type: &str) -> bool {
+    content_type
+        .split(';')
+        .next()
+        .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("text/html"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_tags_of_closest_route() {
+        let hints = PreloadHints::from_json(
+            r#"{
+                "routes": {"/": [], "/posts": ["posts.wasm?build=1"]},
+                "integrity": {"posts.wasm?build=1": "sha384-abc"},
+                "modules": {"/": [], "/posts/": ["posts"]}
+            }"#,
+            "/pkg/",
+        )
+        .unwrap();
+        assert_eq!(
+            hints.html_tags("/posts/42"),
+            Some(
+                r#"<link rel="preload" href="/pkg/posts.wasm?build=1" as="fetch" type="application/wasm" crossorigin integrity="sha384-abc">"#
+            )
+        );
+        assert_eq!(
+            hints.link_header("/posts"),
+            Some(
+                r#"</pkg/posts.wasm?build=1>; rel=preload; as=fetch; crossorigin; type="application/wasm""#
+            )
+        );
+        assert_eq!(hints.html_tags("/"), None);
+        assert_eq!(
+            hints.hydrate_modules("/posts/42"),
+            Some(&["posts".to_string()][..])
+        );
+        assert_eq!(hints.hydrate_modules("/about"), Some(&[][..]));
+    }
+
+    #[cfg(any(feature = "actix", feature = "axum"))]
+    #[test]
+    fn recognizes_html_content_types() {
+        assert!(is_html("text/html"));
+        assert!(is_html("text/HTML; charset=utf-8"));
+        assert!(!is_html("application/wasm"));
+        assert!(!is_html("application/json"));
+    }
+}
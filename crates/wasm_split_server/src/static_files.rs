@@ -0,0 +1,396 @@
+use std::{
+    borrow::Cow,
+    io,
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use serde::Deserialize;
+
+/// Serves the output directory of `wasm-split`, with headers suited to each
+/// kind of file.
+///
+/// - Chunks requested by the loader carry the build ID in their query string,
+///   so their URLs change with every build and they are cached indefinitely.
+///   That only holds for the build ID of the manifest being served: a chunk
+///   requested with another one is not found, as the file now holds the code
+///   of another build, which must not end up cached under the old URL.
+/// - Everything else, in particular the manifest and the loader script, is
+///   revalidated on every use, so that a deployment takes effect immediately.
+#[derive(Debug, Clone)]
+pub struct StaticFiles {
+    source: Source,
+    precompressed: bool,
+    manifest: PathBuf,
+    /// Shared between clones, such as those in the app of each actix worker.
+    build_id: Arc<Mutex<Option<CachedBuildId>>>,
+}
+
+/// Build ID of the manifest, along with the modification time of the file it
+/// was read from, or `None` for embedded files, which never change.
+#[derive(Debug)]
+struct CachedBuildId {
+    modified: Option<SystemTime>,
+    build_id: Option<String>,
+}
+
+/// Name of the manifest that `wasm-split` writes to its output directory,
+/// unless `[output]` of its config says otherwise.
+pub const MANIFEST_FILENAME: &str = "wasm-split-manifest.json";
+
+#[derive(Deserialize)]
+struct ManifestBuild {
+    build_id: String,
+}
+
+/// Looks up a file embedded in the binary by its path, e.g.
+/// `|path| Assets::get(path).map(|file| file.data)` with rust-embed or
+/// `|path| DIR.get_file(path).map(|file| file.contents().into())` with
+/// include_dir.
+pub type EmbeddedLookup = fn(&str) -> Option<Cow<'static, [u8]>>;
+
+#[derive(Debug, Clone)]
+enum Source {
+    Dir(PathBuf),
+    Embedded {
+        prefix: String,
+        lookup: EmbeddedLookup,
+    },
+}
+
+/// Contents of a [`StaticFile`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Contents {
+    /// A file on disk, to be read when sending the response.
+    Path(PathBuf),
+    /// A file embedded in the binary.
+    Memory(Cow<'static, [u8]>),
+}
+
+impl Contents {
+    pub fn read(self) -> io::Result<Cow<'static, [u8]>> {
+        match self {
+            Self::Path(path) => std::fs::read(path).map(Cow::Owned),
+            Self::Memory(data) => Ok(data),
+        }
+    }
+}
+
+/// A file resolved by [`StaticFiles::resolve`], along with the response
+/// headers to send with it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaticFile {
+    /// Body to send, which is a precompressed variant if `content_encoding`
+    /// is set.
+    pub contents: Contents,
+    pub content_type: &'static str,
+    pub cache_control: &'static str,
+    pub content_encoding: Option<&'static str>,
+    /// Set whenever precompressed variants may be served, including for the
+    /// uncompressed file, so that caches keep the variants apart.
+    pub vary: Option<&'static str>,
+}
+
+const CACHE_IMMUTABLE: &str = "public, max-age=31536000, immutable";
+const CACHE_REVALIDATE: &str = "no-cache";
+
+/// Precompressed variants, in order of preference.
+const ENCODINGS: [(&str, &str); 2] = [("br", "br"), ("gzip", "gz")];
+
+impl StaticFiles {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            source: Source::Dir(dir.into()),
+            precompressed: false,
+            manifest: PathBuf::from(MANIFEST_FILENAME),
+            build_id: Arc::default(),
+        }
+    }
+
+    /// Serves files embedded in the binary, for single-binary deployments.
+    ///
+    /// `prefix` is the directory that holds the output of `wasm-split` within
+    /// the embedded files, e.g. `pkg` if the whole site is embedded. Request
+    /// paths are looked up below it, and since the loader resolves chunk and
+    /// manifest URLs relative to its own URL, no other paths need rewriting.
+    pub fn embedded(prefix: &str, lookup: EmbeddedLookup) -> Self {
+        Self {
+            source: Source::Embedded {
+                prefix: prefix.trim_matches('/').to_string(),
+                lookup,
+            },
+            precompressed: false,
+            manifest: PathBuf::from(MANIFEST_FILENAME),
+            build_id: Arc::default(),
+        }
+    }
+
+    fn get(&self, relative: &Path) -> Option<Contents> {
+        match &self.source {
+            Source::Dir(dir) => {
+                let path = dir.join(relative);
+                path.is_file().then_some(Contents::Path(path))
+            }
+            Source::Embedded { prefix, lookup } => {
+                // Embedded paths always use `/`, regardless of the platform.
+                let relative = relative.to_str()?.replace('\\', "/");
+                let path = if prefix.is_empty() {
+                    relative
+                } else {
+                    format!("{prefix}/{relative}")
+                };
+                lookup(&path).map(Contents::Memory)
+            }
+        }
+    }
+
+    /// Serves `<file>.br` or `<file>.gz` instead of `<file>`, if they exist and
+    /// the client accepts the encoding.
+    pub fn precompressed(mut self, precompressed: bool) -> Self {
+        self.precompressed = precompressed;
+        self
+    }
+
+    /// Path of the manifest within the directory, if `[output]` of the config
+    /// of `wasm-split` moves it.
+    pub fn manifest(mut self, path: impl Into<PathBuf>) -> Self {
+        self.manifest = path.into();
+        self.build_id = Arc::default();
+        self
+    }
+
+    /// Build ID of the manifest currently in the directory. It is read again
+    /// whenever the modification time of the manifest changes, so that a
+    /// deployment replacing the files takes effect at once, and only once for
+    /// embedded files.
+    fn build_id(&self) -> Option<String> {
+        let modified = match &self.source {
+            Source::Dir(dir) => Some(
+                std::fs::metadata(dir.join(&self.manifest))
+                    .and_then(|metadata| metadata.modified())
+                    .ok()?,
+            ),
+            Source::Embedded { .. } => None,
+        };
+        let mut cached = self.build_id.lock().unwrap();
+        if let Some(cached) = &*cached {
+            if cached.modified == modified {
+                return cached.build_id.clone();
+            }
+        }
+        let build_id = self.read_build_id();
+        *cached = Some(CachedBuildId {
+            modified,
+            build_id: build_id.clone(),
+        });
+        build_id
+    }
+
+    fn read_build_id(&self) -> Option<String> {
+        let json = self.get(&self.manifest)?.read().ok()?;
+        let manifest: ManifestBuild = serde_json::from_slice(&json).ok()?;
+        Some(manifest.build_id)
+    }
+
+    /// Resolves the request for `path` (relative to the directory, without the
+    /// query string) to a file. Returns `None` if the file does not exist, the
+    /// path would escape the directory, or a chunk is requested for another
+    /// build than that of the manifest.
+    ///
+    /// Precompressed variants are looked up by appending `.br` or `.gz` to the
+    /// file name, both on disk and among embedded files.
+    pub fn resolve(
+        &self,
+        path: &str,
+        query: Option<&str>,
+        accept_encoding: Option<&str>,
+    ) -> Option<StaticFile> {
+        let relative = Path::new(path.trim_start_matches('/'));
+        if !relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            return None;
+        }
+        let extension = relative
+            .extension()
+            .and_then(|extension| extension.to_str());
+        let content_type = match extension {
+            Some("wasm") => "application/wasm",
+            Some("js" | "mjs") => "text/javascript",
+            Some("json") => "application/json",
+            Some("html") => "text/html; charset=utf-8",
+            Some("css") => "text/css",
+            Some("tsv") => "text/tab-separated-values",
+            _ => "application/octet-stream",
+        };
+        let requested_build = query.and_then(|query| {
+            query
+                .split('&')
+                .find_map(|pair| pair.strip_prefix("build="))
+        });
+        let cache_control = match requested_build {
+            Some(requested) if extension == Some("wasm") => match self.build_id() {
+                Some(build_id) if build_id == requested => CACHE_IMMUTABLE,
+                Some(_) => return None,
+                // Without a manifest to compare with, the chunk may be of
+                // any build.
+                None => CACHE_REVALIDATE,
+            },
+            _ => CACHE_REVALIDATE,
+        };
+        let vary = self.precompressed.then_some("Accept-Encoding");
+        if self.precompressed {
+            for (encoding, suffix) in ENCODINGS {
+                if !accepts_encoding(accept_encoding, encoding) {
+                    continue;
+                }
+                let mut variant = relative.as_os_str().to_os_string();
+                variant.push(".");
+                variant.push(suffix);
+                if let Some(contents) = self.get(Path::new(&variant)) {
+                    return Some(StaticFile {
+                        contents,
+                        content_type,
+                        cache_control,
+                        content_encoding: Some(encoding),
+                        vary,
+                    });
+                }
+            }
+        }
+        Some(StaticFile {
+            contents: self.get(relative)?,
+            content_type,
+            cache_control,
+            content_encoding: None,
+            vary,
+        })
+    }
+}
+
+/// Whether an `Accept-Encoding` header value allows `encoding`, such as for
+/// a server that compresses what has no precompressed variant. Explicitly
+/// refused encodings (`q=0`) are honored, other weights are ignored.
+pub fn accepts_encoding(accept_encoding: Option<&str>, encoding: &str) -> bool {
+    let Some(accept_encoding) = accept_encoding else {
+        return false;
+    };
+    accept_encoding.split(',').any(|item| {
+        let mut parts = item.split(';').map(str::trim);
+        let name = parts.next().unwrap_or_default();
+        let refused = parts.any(|parameter| {
+            parameter
+                .strip_prefix("q=")
+                .and_then(|q| q.parse::<f32>().ok())
+                == Some(0.0)
+        });
+        (name.eq_ignore_ascii_case(encoding) || name == "*") && !refused
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use super::*;
+
+    fn lookup(path: &str) -> Option<Cow<'static, [u8]>> {
+        match path {
+            "pkg/wasm-split-manifest.json" => Some(Cow::Borrowed(br#"{"build_id":"abc"}"#)),
+            "pkg/main.wasm" => Some(Cow::Borrowed(b"\0asm")),
+            _ => None,
+        }
+    }
+
+    fn cache_control(query: Option<&str>) -> Option<&'static str> {
+        StaticFiles::embedded("pkg", lookup)
+            .resolve("/main.wasm", query, None)
+            .map(|file| file.cache_control)
+    }
+
+    #[test]
+    fn caches_chunks_of_current_build_indefinitely() {
+        assert_eq!(cache_control(Some("build=abc")), Some(CACHE_IMMUTABLE));
+        assert_eq!(cache_control(None), Some(CACHE_REVALIDATE));
+    }
+
+    #[test]
+    fn does_not_serve_chunks_of_other_builds() {
+        assert_eq!(cache_control(Some("build=def")), None);
+        assert_eq!(cache_control(Some("x=1&build=ab")), None);
+    }
+
+    #[test]
+    fn revalidates_chunks_without_manifest() {
+        let files = StaticFiles::embedded("pkg", lookup).manifest("other.json");
+        let file = files
+            .resolve("/main.wasm", Some("build=abc"), None)
+            .unwrap();
+        assert_eq!(file.cache_control, CACHE_REVALIDATE);
+    }
+
+    static MANIFEST_READS: AtomicUsize = AtomicUsize::new(0);
+
+    fn counting_lookup(path: &str) -> Option<Cow<'static, [u8]>> {
+        if path == "pkg/wasm-split-manifest.json" {
+            MANIFEST_READS.fetch_add(1, Ordering::Relaxed);
+        }
+        lookup(path)
+    }
+
+    #[test]
+    fn reads_embedded_manifest_once() {
+        let files = StaticFiles::embedded("pkg", counting_lookup);
+        let clone = files.clone();
+        for files in [&files, &clone, &files] {
+            let file = files.resolve("/main.wasm", Some("build=abc"), None);
+            assert_eq!(file.unwrap().cache_control, CACHE_IMMUTABLE);
+        }
+        assert_eq!(MANIFEST_READS.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn rereads_manifest_in_directory_when_modified() {
+        let dir =
+            std::env::temp_dir().join(format!("wasm-split-server-manifest-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("main.wasm"), b"\0asm").unwrap();
+        let manifest = dir.join(MANIFEST_FILENAME);
+        let write_manifest = |build_id: &str, modified: SystemTime| {
+            std::fs::write(&manifest, format!(r#"{{"build_id":"{build_id}"}}"#)).unwrap();
+            std::fs::File::options()
+                .write(true)
+                .open(&manifest)
+                .unwrap()
+                .set_modified(modified)
+                .unwrap();
+        };
+        let cache_control = |files: &StaticFiles, build_id: &str| {
+            files
+                .resolve("/main.wasm", Some(&format!("build={build_id}")), None)
+                .map(|file| file.cache_control)
+        };
+        let files = StaticFiles::new(&dir);
+        let earlier = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        write_manifest("abc", earlier);
+        assert_eq!(cache_control(&files, "abc"), Some(CACHE_IMMUTABLE));
+
+        // The manifest is not read again while its modification time stays
+        // the same.
+        write_manifest("def", earlier);
+        assert_eq!(cache_control(&files, "abc"), Some(CACHE_IMMUTABLE));
+
+        write_manifest("def", earlier + Duration::from_secs(1));
+        assert_eq!(cache_control(&files, "abc"), None);
+        assert_eq!(cache_control(&files, "def"), Some(CACHE_IMMUTABLE));
+
+        std::fs::remove_file(&manifest).unwrap();
+        assert_eq!(cache_control(&files, "abc"), Some(CACHE_REVALIDATE));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
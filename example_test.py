@@ -0,0 +1,340 @@
+#!/usr/bin/env python3
+
+# Builds `crates/example` with and without splitting, opens every route of
+# both builds in a headless browser through WebDriver, and checks that the
+# split build renders each the same as the unsplit one, whether the route is
+# loaded directly or navigated to from the nav. `/checks` renders the results
+# of calls into split functions with all kinds of arguments, and its "Panic"
+# button checks that a panic in a split function reaches the app's panic hook
+# with the same message. In the split build, the chunks of the functions that
+# `/checks` calls at once must load at the same time, each once.
+#
+# Unlike `browser_test.py`, which checks that the split build loads in every
+# browser, this compares every route with the unsplit build. It requires what
+# `build.py` does (wasm-bindgen, and the wasm32 target), and the WebDriver of
+# the browser on PATH: chromedriver or geckodriver. The data that `/b`, `/c`
+# and `/data` fetch is served by the test server, with `EXAMPLE_DATA_URL`.
+#
+#     ./example_test.py
+#     ./example_test.py --browser firefox --routes /,/checks
+
+import argparse
+import http.server
+import json
+import os
+import shutil
+import subprocess
+import sys
+import threading
+import time
+
+from webdriver import WebDriver, free_port, wait_for_driver
+
+# Text that each route shows once it is rendered with its data.
+ROUTES = {
+    "/": ["View A"],
+    "/b": ["View B", "Nested Child", '"userId"'],
+    "/c": ["Nested Child", "post_id"],
+    "/charts": ["Requests per hour", "Total by day"],
+    "/editor": ["Markdown editor", "words"],
+    "/data": ["Photo albums", "albums"],
+    "/checks": ["concurrent"],
+}
+
+# Fallbacks of the suspenses of the routes.
+PENDING = ["Loading...", "Loading photos…"]
+
+PANIC_MESSAGE = "split function panicked with 7"
+
+BROWSERS = {
+    "chrome": {
+        "driver": ["chromedriver", "--port={port}"],
+        "capabilities": {
+            "browserName": "chrome",
+            "goog:chromeOptions": {"args": ["--headless=new", "--no-sandbox"]},
+        },
+    },
+    "firefox": {
+        "driver": ["geckodriver", "--port", "{port}"],
+        "capabilities": {
+            "browserName": "firefox",
+            "moz:firefoxOptions": {"args": ["-headless"]},
+        },
+    },
+}
+
+# The chunks of the split functions of `/checks` are smaller than the default
+# fold threshold, and would otherwise be folded into the main module.
+VARIANTS = {"unsplit": ["--no-split"], "split": ["--fold-threshold", "0"]}
+
+# Path of the test server that the example fetches its data from instead of
+# jsonplaceholder.typicode.com, with copies of the documents it fetches there.
+DATA_PATH = "/fixtures"
+PHOTO = (
+    '<svg xmlns="http://www.w3.org/2000/svg" width="32" height="32">'
+    '<rect width="32" height="32" fill="#92c952"/></svg>'
+)
+DATA = {
+    "todos/1": {
+        "userId": 1,
+        "id": 1,
+        "title": "delectus aut autem",
+        "completed": False,
+    },
+    "comments": [
+        {
+            "postId": id // 5 + 1,
+            "id": id + 1,
+            "name": f"comment {id + 1}",
+            "email": f"reader{id + 1}@example.com",
+            "body": "quia molestiae reprehenderit quasi aspernatur\n" * (id % 3 + 1),
+        }
+        for id in range(500)
+    ],
+    # As many photos as jsonplaceholder has, for a document of about the same
+    # size, which `/data` deserializes on a worker.
+    "photos": [
+        {
+            "albumId": id // 50 + 1,
+            "id": id + 1,
+            "title": f"photo {id + 1} of album {id // 50 + 1}" + " et" * (id % 7),
+            "url": f"{DATA_PATH}/photo.svg",
+            "thumbnailUrl": f"{DATA_PATH}/photo.svg",
+        }
+        for id in range(5000)
+    ],
+}
+
+ap = argparse.ArgumentParser()
+ap.add_argument("--browser", choices=sorted(BROWSERS), default="chrome")
+ap.add_argument(
+    "--routes",
+    type=lambda value: [route for route in value.split(",") if route],
+    default=list(ROUTES),
+    help="comma-separated routes to check",
+)
+ap.add_argument(
+    "--skip-build", action="store_true", help="test the builds of a previous run"
+)
+ap.add_argument("--timeout", type=float, default=30)
+args = ap.parse_args()
+
+unknown = [route for route in args.routes if route not in ROUTES]
+if unknown:
+    ap.error(f"unknown routes {unknown}, expected some of {list(ROUTES)}")
+
+root_dir = os.path.dirname(os.path.abspath(__file__))
+test_dir = os.path.join(root_dir, "example-test")
+
+if not args.skip_build:
+    shutil.rmtree(test_dir, ignore_errors=True)
+    for variant, options in VARIANTS.items():
+        out_dir = os.path.join("example-test", variant, "pkg")
+        subprocess.run(
+            [sys.executable, os.path.join(root_dir, "build.py"), "--out-dir", out_dir]
+            + options,
+            cwd=root_dir,
+            env={**os.environ, "EXAMPLE_DATA_URL": DATA_PATH},
+            check=True,
+        )
+        shutil.copy(
+            os.path.join(root_dir, "index.html"),
+            os.path.join(test_dir, variant, "index.html"),
+        )
+        data_dir = os.path.join(test_dir, variant, DATA_PATH.lstrip("/"))
+        for name, document in DATA.items():
+            path = os.path.join(data_dir, name)
+            os.makedirs(os.path.dirname(path), exist_ok=True)
+            with open(path, "w") as f:
+                json.dump(document, f)
+        with open(os.path.join(data_dir, "photo.svg"), "w") as f:
+            f.write(PHOTO)
+
+
+def serve(directory):
+    class Handler(http.server.SimpleHTTPRequestHandler):
+        extensions_map = {
+            **http.server.SimpleHTTPRequestHandler.extensions_map,
+            ".js": "text/javascript",
+            ".json": "application/json",
+            ".svg": "image/svg+xml",
+            ".wasm": "application/wasm",
+        }
+
+        def __init__(self, *handler_args, **kwargs):
+            super().__init__(*handler_args, directory=directory, **kwargs)
+
+        def send_head(self):
+            # Routes of the app are not files, and get the page as any
+            # server of a single-page app would.
+            if not os.path.exists(self.translate_path(self.path)):
+                self.path = "/index.html"
+            return super().send_head()
+
+        def log_message(self, format, *log_args):
+            pass
+
+    server = http.server.ThreadingHTTPServer(("127.0.0.1", free_port()), Handler)
+    threading.Thread(target=server.serve_forever, daemon=True).start()
+    return f"http://127.0.0.1:{server.server_address[1]}"
+
+
+# Rendered text, the panic message recorded by `/checks`, and the chunks
+# reported by the loader's `wasm-split:chunk-loaded` performance marks.
+STATE_SCRIPT = """
+return {
+  text: document.body ? document.body.innerText : "",
+  panic: document.body ? document.body.dataset.panic || null : null,
+  chunks: performance
+    .getEntriesByName("wasm-split:chunk-loaded", "mark")
+    .map((mark) => mark.detail.chunk),
+};
+"""
+
+
+def wait_for(driver, description, done):
+    deadline = time.monotonic() + args.timeout
+    while True:
+        state = driver.execute(STATE_SCRIPT)
+        if done(state):
+            return state
+        if time.monotonic() > deadline:
+            raise RuntimeError(
+                f"{description}: timed out, page text {state['text']!r}"
+            )
+        time.sleep(0.2)
+
+
+def rendered(route):
+    def done(state):
+        return all(text in state["text"] for text in ROUTES[route]) and not any(
+            text in state["text"] for text in PENDING
+        )
+
+    return done
+
+
+def check_route(driver, base_url, route):
+    """Text of `route` loaded directly, after checking that navigating to it
+    from `/` through the nav, where it has a link, renders the same."""
+    driver.navigate(base_url + route)
+    text = wait_for(driver, route, rendered(route))["text"]
+    driver.navigate(base_url + "/")
+    wait_for(driver, "/", rendered("/"))
+    clicked = driver.execute(
+        "const link = document.querySelector(`nav a[href='${arguments[0]}']`);"
+        "if (link) link.click();"
+        "return !!link;",
+        route,
+    )
+    if clicked:
+        navigated = wait_for(driver, f"/ -> {route}", rendered(route))["text"]
+        if navigated != text:
+            raise RuntimeError(
+                f"{route}: navigated to, renders {navigated!r} "
+                f"instead of {text!r} as when loaded"
+            )
+    return text
+
+
+def check_panic(driver, base_url):
+    driver.navigate(base_url + "/checks")
+    wait_for(driver, "/checks", rendered("/checks"))
+    driver.execute("document.getElementById('panic').click();")
+    state = wait_for(driver, "/checks panic", lambda state: state["panic"])
+    return state
+
+
+# Intervals of the requests for the chunks of the two split functions that
+# `/checks` calls at once, from the loader's `wasm-split:<chunk>:fetch`
+# performance measures.
+CONCURRENT_SCRIPT = """
+const fetches = (chunk) =>
+  performance
+    .getEntriesByName(`wasm-split:${chunk}:fetch`, "measure")
+    .map((measure) => [measure.startTime, measure.startTime + measure.duration]);
+return [fetches("check_concurrent_a"), fetches("check_concurrent_b")];
+"""
+
+
+def check_concurrent_loads(driver):
+    """Checks that the chunks that `/checks` loads at once, calling one of
+    them twice, were each requested once, at the same time."""
+    a, b = driver.execute(CONCURRENT_SCRIPT)
+    if len(a) != 1 or len(b) != 1:
+        raise RuntimeError(
+            f"/checks: expected one request for each concurrently loaded chunk, "
+            f"got {len(a)} and {len(b)}"
+        )
+    (a_start, a_end), (b_start, b_end) = a[0], b[0]
+    if a_end < b_start or b_end < a_start:
+        raise RuntimeError(
+            f"/checks: chunks loaded one after the other, from {a_start:.1f} to "
+            f"{a_end:.1f} and from {b_start:.1f} to {b_end:.1f}"
+        )
+
+
+def run_variant(driver, variant):
+    base_url = serve(os.path.join(test_dir, variant))
+    texts = {route: check_route(driver, base_url, route) for route in args.routes}
+    panic = None
+    if "/checks" in args.routes:
+        state = check_panic(driver, base_url)
+        panic = state["panic"]
+        if variant == "split" and not state["chunks"]:
+            raise RuntimeError("the split build loaded no chunks on /checks")
+        if variant == "split":
+            check_concurrent_loads(driver)
+    return texts, panic
+
+
+config = BROWSERS[args.browser]
+if shutil.which(config["driver"][0]) is None:
+    sys.exit(f"{config['driver'][0]} not found")
+port = free_port()
+process = subprocess.Popen(
+    [arg.format(port=port) for arg in config["driver"]],
+    stdout=subprocess.DEVNULL,
+    stderr=subprocess.DEVNULL,
+)
+results = {}
+try:
+    driver_url = f"http://127.0.0.1:{port}"
+    wait_for_driver(driver_url, process, args.timeout)
+    driver = WebDriver(driver_url, config["capabilities"])
+    try:
+        for variant in VARIANTS:
+            print(f"{variant}: running", flush=True)
+            results[variant] = run_variant(driver, variant)
+    finally:
+        driver.quit()
+finally:
+    process.terminate()
+    process.wait()
+
+failures = []
+(unsplit_texts, unsplit_panic), (split_texts, split_panic) = (
+    results["unsplit"],
+    results["split"],
+)
+for route in args.routes:
+    if split_texts[route] != unsplit_texts[route]:
+        failures.append(
+            f"{route}: split build renders {split_texts[route]!r}, "
+            f"unsplit build {unsplit_texts[route]!r}"
+        )
+    else:
+        print(f"{route}: ok")
+if "/checks" in args.routes:
+    if unsplit_panic != PANIC_MESSAGE or split_panic != PANIC_MESSAGE:
+        failures.append(
+            f"panic: expected {PANIC_MESSAGE!r}, split build got {split_panic!r}, "
+            f"unsplit build {unsplit_panic!r}"
+        )
+    else:
+        print("panic: ok")
+
+for failure in failures:
+    print(f"FAILED: {failure}", file=sys.stderr)
+if failures:
+    sys.exit(1)
@@ -0,0 +1,64 @@
+# Helpers shared by `browser_test.py` and `example_test.py`, which drive
+# browsers through the WebDriver protocol with nothing but the standard
+# library.
+
+import json
+import socket
+import time
+import urllib.error
+import urllib.request
+
+
+def free_port():
+    with socket.socket() as s:
+        s.bind(("127.0.0.1", 0))
+        return s.getsockname()[1]
+
+
+class WebDriver:
+    def __init__(self, url, capabilities):
+        self.url = url
+        response = self.request(
+            "POST", "/session", {"capabilities": {"alwaysMatch": capabilities}}
+        )
+        self.url += "/session/" + response["sessionId"]
+
+    def request(self, method, path, body=None):
+        data = None if body is None else json.dumps(body).encode()
+        request = urllib.request.Request(
+            self.url + path,
+            data=data,
+            method=method,
+            headers={"Content-Type": "application/json"},
+        )
+        try:
+            with urllib.request.urlopen(request) as response:
+                return json.load(response)["value"]
+        except urllib.error.HTTPError as e:
+            message = e.read().decode()
+            raise RuntimeError(f"WebDriver {method} {path}: {message}") from None
+
+    def navigate(self, url):
+        self.request("POST", "/url", {"url": url})
+
+    def execute(self, script, *script_args):
+        return self.request(
+            "POST", "/execute/sync", {"script": script, "args": list(script_args)}
+        )
+
+    def quit(self):
+        self.request("DELETE", "")
+
+
+def wait_for_driver(url, process, timeout):
+    """Waits for the WebDriver `process` to answer at `url`."""
+    deadline = time.monotonic() + timeout
+    while time.monotonic() < deadline:
+        if process.poll() is not None:
+            raise RuntimeError(f"WebDriver exited with status {process.returncode}")
+        try:
+            with urllib.request.urlopen(url + "/status"):
+                return
+        except OSError:
+            time.sleep(0.2)
+    raise RuntimeError("WebDriver did not start")